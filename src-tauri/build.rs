@@ -1,3 +1,71 @@
+use std::fs;
+
+/// Every app command is named in one of the permission sets under
+/// `permissions/`, and the capability files grant those sets per window
+/// label. A command missing from all sets is rejected for every window.
 fn main() {
-    tauri_build::build()
+    require_registry_key();
+
+    let mut commands = Vec::new();
+    for entry in fs::read_dir("permissions").expect("failed to read permissions/") {
+        let path = entry.expect("failed to read permissions/").path();
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            println!("cargo:rerun-if-changed={}", path.display());
+            let text = fs::read_to_string(&path).expect("failed to read permission set");
+            commands.extend(allowed_commands(&text));
+        }
+    }
+    commands.sort();
+    commands.dedup();
+    let commands: &'static [&'static str] = commands
+        .into_iter()
+        .map(|c| &*Box::leak(c.into_boxed_str()))
+        .collect::<Vec<_>>()
+        .leak();
+
+    tauri_build::try_build(
+        tauri_build::Attributes::new()
+            .app_manifest(tauri_build::AppManifest::new().commands(commands)),
+    )
+    .expect("failed to run tauri-build");
+}
+
+/// Command names behind the `"allow-<command>"` entries of a set, the same
+/// way `commands::window_access` reads them.
+fn allowed_commands(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines().filter_map(|line| {
+        let name = line.trim().trim_end_matches(',').trim_matches('"');
+        name.strip_prefix("allow-").map(|c| c.replace('-', "_"))
+    })
+}
+
+/// Shipped builds verify plugin registry signatures against the key in
+/// `MORAYA_REGISTRY_PUBKEY`. CI and release builds (`CI` or
+/// `MORAYA_RELEASE` set) fail without it; local release builds only warn,
+/// and the app then skips the checks.
+fn require_registry_key() {
+    println!("cargo:rerun-if-env-changed=MORAYA_REGISTRY_PUBKEY");
+    println!("cargo:rerun-if-env-changed=MORAYA_RELEASE");
+    println!("cargo:rerun-if-env-changed=CI");
+    if std::env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_some() {
+        return;
+    }
+    let key = std::env::var("MORAYA_REGISTRY_PUBKEY").unwrap_or_default();
+    if !key.trim().is_empty() {
+        return;
+    }
+    let enforced = ["CI", "MORAYA_RELEASE"]
+        .iter()
+        .any(|var| std::env::var(var).is_ok_and(|v| !v.is_empty() && v != "0" && v != "false"));
+    if enforced {
+        panic!(
+            "MORAYA_REGISTRY_PUBKEY is not set. Release builds embed the plugin registry's \
+             minisign public key: export MORAYA_REGISTRY_PUBKEY=<base64 key line of the \
+             registry's minisign.pub> before building (see \"Build\" in README.md)"
+        );
+    }
+    println!(
+        "cargo:warning=MORAYA_REGISTRY_PUBKEY is not set: this build will NOT verify plugin \
+         registry signatures. Set it for builds you distribute (see \"Build\" in README.md)"
+    );
 }
@@ -0,0 +1,210 @@
+//! The "toggle AI panel" shortcut.
+//!
+//! By default it is registered only while a Moraya window has focus (Windows
+//! and Linux), so Ctrl+Shift+I reaches the AI panel before the WebView opens
+//! its DevTools; on macOS the menu accelerator covers it. Made global with
+//! `set_ai_panel_shortcut`, it stays registered whichever app is in front
+//! and brings the last focused Moraya window forward first.
+
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+
+use tauri::{AppHandle, Emitter, Manager, State, WindowEvent};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut};
+
+const EVENT: &str = "menu:view_ai_panel";
+
+/// Platforms where the WebView claims Ctrl+Shift+I unless we take it first.
+const REGISTER_ON_FOCUS: bool = cfg!(any(target_os = "windows", target_os = "linux"));
+
+struct Inner {
+    shortcut: Shortcut,
+    global: bool,
+    /// Whether `shortcut` is currently registered with the OS.
+    registered: bool,
+    /// Focused Moraya windows; moving between windows is a loss and a gain.
+    focused: u32,
+    last_focused: Option<String>,
+}
+
+pub struct AiShortcutState(Mutex<Inner>);
+
+impl AiShortcutState {
+    pub fn new() -> Self {
+        Self(Mutex::new(Inner {
+            shortcut: default_shortcut(),
+            global: false,
+            registered: false,
+            focused: 0,
+            last_focused: None,
+        }))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        match self.0.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        }
+    }
+}
+
+impl Default for AiShortcutState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_shortcut() -> Shortcut {
+    Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyI)
+}
+
+/// An accelerator such as `CmdOrCtrl+Alt+A`; empty means the default.
+fn parse(accel: &str) -> Result<Shortcut, String> {
+    let accel = accel.trim();
+    if accel.is_empty() {
+        return Ok(default_shortcut());
+    }
+    let shortcut =
+        Shortcut::from_str(accel).map_err(|e| format!("Invalid shortcut '{}': {}", accel, e))?;
+    // A bare key would swallow ordinary typing in every app
+    if shortcut.mods.is_empty() {
+        return Err(format!("Shortcut '{}' needs a modifier key", accel));
+    }
+    Ok(shortcut)
+}
+
+/// Whether the shortcut should be registered right now.
+fn wanted(inner: &Inner) -> bool {
+    if inner.global {
+        return true;
+    }
+    // In developer mode Ctrl+Shift+I is left to the WebView
+    REGISTER_ON_FOCUS && inner.focused > 0 && !crate::commands::settings::developer_mode()
+}
+
+/// Register or unregister the shortcut to match `wanted`.
+fn sync(app: &AppHandle, inner: &mut Inner) {
+    let wanted = wanted(inner);
+    if wanted == inner.registered {
+        return;
+    }
+    let shortcuts = app.global_shortcut();
+    if wanted {
+        match shortcuts.register(inner.shortcut) {
+            Ok(()) => inner.registered = true,
+            Err(e) => log::warn!("Cannot register AI panel shortcut: {}", e),
+        }
+    } else {
+        let _ = shortcuts.unregister(inner.shortcut);
+        inner.registered = false;
+    }
+}
+
+/// Follow window focus: register the focus-scoped shortcut while a Moraya
+/// window is in front, and remember which one a global press brings back.
+pub fn handle_window_event(app: &AppHandle, label: &str, event: &WindowEvent) {
+    let Some(state) = app.try_state::<AiShortcutState>() else {
+        return;
+    };
+    let mut inner = state.lock();
+    match event {
+        WindowEvent::Focused(true) => {
+            inner.focused += 1;
+            inner.last_focused = Some(label.to_string());
+        }
+        WindowEvent::Focused(false) => inner.focused = inner.focused.saturating_sub(1),
+        WindowEvent::Destroyed => {
+            if inner.last_focused.as_deref() == Some(label) {
+                inner.last_focused = None;
+            }
+            return;
+        }
+        _ => return,
+    }
+    sync(app, &mut inner);
+}
+
+/// Global shortcut handler.
+pub fn handle_pressed(app: &AppHandle, shortcut: &Shortcut) {
+    let Some(state) = app.try_state::<AiShortcutState>() else {
+        return;
+    };
+    let (global, last_focused) = {
+        let inner = state.lock();
+        if inner.shortcut != *shortcut {
+            return;
+        }
+        (inner.global, inner.last_focused.clone())
+    };
+    if !global {
+        // Registered before developer mode was switched on:
+        // do what the WebView would have done
+        if crate::commands::settings::developer_mode() {
+            if let Some(label) = crate::focused_window_label(app) {
+                let _ = crate::toggle_devtools(app.clone(), label);
+            }
+            return;
+        }
+        crate::emit_to_focused(app, EVENT, ());
+        return;
+    }
+    let Some(window) = crate::focused_window_label(app)
+        .or(last_focused)
+        .and_then(|label| app.get_webview_window(&label))
+        .or_else(|| app.get_webview_window("main"))
+    else {
+        return;
+    };
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    let _ = app.emit_to(window.label(), EVENT, ());
+}
+
+/// Set the AI panel shortcut (empty `accel` for Ctrl+Shift+I). With `global`
+/// it stays registered while other apps are in front; otherwise only while
+/// a Moraya window has focus. Invalid accelerators and ones already taken
+/// are errors and leave the previous shortcut in place.
+#[tauri::command]
+pub fn set_ai_panel_shortcut(
+    app: AppHandle,
+    state: State<'_, AiShortcutState>,
+    accel: String,
+    global: bool,
+) -> Result<(), String> {
+    let shortcut = parse(&accel)?;
+    let mut inner = state.lock();
+    let shortcuts = app.global_shortcut();
+    if inner.registered {
+        let _ = shortcuts.unregister(inner.shortcut);
+        inner.registered = false;
+    }
+    // Claim the new shortcut once even when it is only wanted on focus, so
+    // conflicts show up now rather than on the next focus change
+    if let Err(e) = shortcuts.register(shortcut) {
+        sync(&app, &mut inner);
+        return Err(format!("Shortcut '{}' is not available: {}", accel.trim(), e));
+    }
+    inner.shortcut = shortcut;
+    inner.global = global;
+    inner.registered = true;
+    sync(&app, &mut inner);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_accelerators() {
+        assert_eq!(parse("").unwrap(), default_shortcut());
+        assert_eq!(parse(" Ctrl+Shift+I ").unwrap(), default_shortcut());
+        assert_eq!(
+            parse("Alt+Space").unwrap(),
+            Shortcut::new(Some(Modifiers::ALT), Code::Space)
+        );
+        assert!(parse("I").unwrap_err().contains("modifier"));
+        assert!(parse("Ctrl+Nope").unwrap_err().starts_with("Invalid shortcut"));
+    }
+}
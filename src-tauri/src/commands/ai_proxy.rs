@@ -1,10 +1,15 @@
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::ipc::Channel;
 
+use super::ai_usage::{self, request_model, TokenUsage, UsageRecord, UsageStatus};
+use super::error::{CommandError, ErrorCode};
+
 pub(crate) const SERVICE_NAME: &str = "com.moraya.app";
 const AI_KEY_PREFIX: &str = "ai-key:";
+const IMAGE_KEY_PREFIX: &str = "image-key:";
 const SECRETS_KEY: &str = "moraya-secrets";
 const REQUEST_TIMEOUT_SECS: u64 = 300;
 
@@ -13,108 +18,235 @@ fn dev_secrets_path() -> Option<std::path::PathBuf> {
     dirs::config_dir().map(|d| d.join(SERVICE_NAME).join("dev-secrets.json"))
 }
 
+/// Where the secrets blob currently lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    /// OS keychain / Secret Service / Credential Manager.
+    Keychain,
+    /// Encrypted file under the config dir, used when no keychain backend
+    /// is reachable (see `fallback_secrets_path`).
+    Fallback,
+    /// Plain dev-secrets.json (debug builds).
+    Dev,
+}
+
+/// Why an OS keychain call failed.
+enum OsSecretsError {
+    /// No keychain backend at all (e.g. Linux without a Secret Service
+    /// daemon). Triggers the encrypted file fallback.
+    Unavailable,
+    /// The backend exists but refused (locked, prompt dismissed, ...).
+    Failed(String),
+}
+
+// ---------------------------------------------------------------------------
+// Encrypted file fallback (release builds without a keychain backend)
+// ---------------------------------------------------------------------------
+//
+// The blob is sealed with XChaCha20-Poly1305 under a random machine key kept
+// in `secrets.key` next to it (both 0600). This is much weaker than a real
+// keychain: anyone who can read the user's config dir can decrypt it. It
+// only keeps API keys out of plain-text greps, logs and single-file copies.
+
+const FALLBACK_AAD: &[u8] = b"moraya-fallback-secrets/v1";
+
+fn fallback_secrets_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|d| d.join(SERVICE_NAME).join("secrets.enc"))
+}
+
+fn fallback_key_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|d| d.join(SERVICE_NAME).join("secrets.key"))
+}
+
+fn write_private_file(path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, data)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// Load the machine key, generating it on first use when `create` is set.
+fn fallback_key(create: bool) -> Result<Option<[u8; 32]>, String> {
+    let path = fallback_key_path().ok_or("No config directory")?;
+    match std::fs::read(&path) {
+        Ok(bytes) => bytes
+            .try_into()
+            .map(Some)
+            .map_err(|_| "Corrupted fallback secrets key".to_string()),
+        Err(_) if create => {
+            let key = super::keychain::random_key();
+            write_private_file(&path, &key)
+                .map_err(|_| "Failed to write fallback secrets key".to_string())?;
+            Ok(Some(key))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+fn read_fallback_secrets() -> Result<String, String> {
+    let Some(sealed) = fallback_secrets_path().and_then(|p| std::fs::read(p).ok()) else {
+        return Ok(String::new());
+    };
+    let key = fallback_key(false)?.ok_or("Fallback secrets key is missing")?;
+    let json = super::keychain::open_with_key(&key, &sealed, FALLBACK_AAD)?;
+    String::from_utf8(json).map_err(|_| "Corrupted fallback secrets file".to_string())
+}
+
+fn write_fallback_secrets(json: &str) -> Result<(), String> {
+    let path = fallback_secrets_path().ok_or("No config directory")?;
+    let key = fallback_key(true)?.ok_or("Fallback secrets key is missing")?;
+    let sealed = super::keychain::seal_with_key(&key, json.as_bytes(), FALLBACK_AAD)?;
+    write_private_file(&path, &sealed)
+        .map_err(|_| "Failed to write fallback secrets file".to_string())
+}
+
+fn remove_fallback_secrets() {
+    for path in [fallback_secrets_path(), fallback_key_path()]
+        .into_iter()
+        .flatten()
+    {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Release-build load: the keychain wins when it holds data, otherwise a
+/// fallback file (written while no keychain was reachable) stays in use
+/// until it is migrated explicitly.
+fn load_release_secrets() -> (String, SecretBackend) {
+    match read_os_secrets() {
+        Ok(json) if !json.is_empty() => (json, SecretBackend::Keychain),
+        Ok(_) => match read_fallback_secrets() {
+            Ok(json) if !json.is_empty() => (json, SecretBackend::Fallback),
+            _ => (String::new(), SecretBackend::Keychain),
+        },
+        Err(OsSecretsError::Unavailable) => (
+            read_fallback_secrets().unwrap_or_default(),
+            SecretBackend::Fallback,
+        ),
+        Err(OsSecretsError::Failed(_)) => (String::new(), SecretBackend::Keychain),
+    }
+}
+
+/// Release-build store; returns the backend that now holds the secrets.
+fn store_release_secrets(json: &str, backend: SecretBackend) -> Result<SecretBackend, String> {
+    if backend == SecretBackend::Fallback {
+        write_fallback_secrets(json)?;
+        return Ok(SecretBackend::Fallback);
+    }
+    match write_os_secrets(json) {
+        Ok(()) => Ok(SecretBackend::Keychain),
+        Err(OsSecretsError::Unavailable) => {
+            write_fallback_secrets(json)?;
+            Ok(SecretBackend::Fallback)
+        }
+        Err(OsSecretsError::Failed(e)) => Err(e),
+    }
+}
+
+/// Whether an OS keychain backend is reachable right now.
+pub(crate) fn os_keychain_available() -> bool {
+    !matches!(read_os_secrets(), Err(OsSecretsError::Unavailable))
+}
+
 // ---------------------------------------------------------------------------
 // Platform-specific keychain helpers (release builds only)
 // ---------------------------------------------------------------------------
 //
 // macOS: the `keyring` crate creates keychain items with an ACL tied to the
 // calling binary's code signature.  After an overwrite-install the signature
-// changes and macOS shows a password dialog.  To avoid this we use the
-// `security` CLI with the `-A` flag ("allow all applications").  The secrets
-// are still encrypted at rest inside the login keychain.
+// changes and macOS shows a password dialog.  To avoid this we talk to
+// Security.framework directly (`macos_keychain`) and create the item with an
+// "allow all applications" ACL.  The secrets are still encrypted at rest
+// inside the login keychain.
 //
 // Windows / Linux: the `keyring` crate works without ACL issues on updates.
 
 #[cfg(target_os = "macos")]
-fn read_os_secrets() -> String {
-    std::process::Command::new("security")
-        .args([
-            "find-generic-password",
-            "-s", SERVICE_NAME,
-            "-a", SECRETS_KEY,
-            "-w",
-        ])
-        .output()
+fn read_os_secrets() -> Result<String, OsSecretsError> {
+    Ok(super::macos_keychain::read_password(SERVICE_NAME, SECRETS_KEY)
         .ok()
-        .and_then(|o| {
-            if o.status.success() {
-                String::from_utf8(o.stdout)
-                    .ok()
-                    .map(|s| s.trim_end().to_string())
-            } else {
-                None
-            }
-        })
-        .unwrap_or_default()
+        .flatten()
+        .unwrap_or_default())
 }
 
 #[cfg(target_os = "macos")]
-fn write_os_secrets(json: &str) -> Result<(), String> {
-    // Delete existing entry first (works regardless of old ACL)
-    let _ = std::process::Command::new("security")
-        .args([
-            "delete-generic-password",
-            "-s", SERVICE_NAME,
-            "-a", SECRETS_KEY,
-        ])
-        .output();
-
-    // Add new entry with -A (allow all applications — avoids per-binary ACL)
-    let output = std::process::Command::new("security")
-        .args([
-            "add-generic-password",
-            "-s", SERVICE_NAME,
-            "-a", SECRETS_KEY,
-            "-w", json,
-            "-A",
-        ])
-        .output()
-        .map_err(|_| "Failed to access keychain".to_string())?;
-
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err("Failed to store in keychain".to_string())
-    }
+fn write_os_secrets(json: &str) -> Result<(), OsSecretsError> {
+    super::macos_keychain::write_password(SERVICE_NAME, SECRETS_KEY, json)
+        .map_err(OsSecretsError::Failed)
 }
 
+// `PlatformFailure` is what keyring reports when the backend itself is
+// missing (no D-Bus session / Secret Service daemon). `NoStorageAccess`
+// means a locked or declined keyring and must not trigger the fallback.
 #[cfg(not(target_os = "macos"))]
-fn read_os_secrets() -> String {
-    keyring::Entry::new(SERVICE_NAME, SECRETS_KEY)
-        .ok()
-        .and_then(|e| e.get_password().ok())
-        .unwrap_or_default()
+fn read_os_secrets() -> Result<String, OsSecretsError> {
+    match keyring::Entry::new(SERVICE_NAME, SECRETS_KEY).and_then(|e| e.get_password()) {
+        Ok(json) => Ok(json),
+        Err(keyring::Error::PlatformFailure(_)) => Err(OsSecretsError::Unavailable),
+        Err(_) => Ok(String::new()),
+    }
 }
 
 #[cfg(not(target_os = "macos"))]
-fn write_os_secrets(json: &str) -> Result<(), String> {
+fn write_os_secrets(json: &str) -> Result<(), OsSecretsError> {
     let entry = keyring::Entry::new(SERVICE_NAME, SECRETS_KEY)
-        .map_err(|_| "Failed to access keychain".to_string())?;
-    entry
-        .set_password(json)
-        .map_err(|_| "Failed to store in keychain".to_string())
+        .map_err(|_| OsSecretsError::Failed("Failed to access keychain".to_string()))?;
+    entry.set_password(json).map_err(|e| match e {
+        keyring::Error::PlatformFailure(_) => OsSecretsError::Unavailable,
+        _ => OsSecretsError::Failed("Failed to store in keychain".to_string()),
+    })
 }
 
 /// Shared state for aborting in-flight streaming requests and caching API keys.
 ///
-/// - **Release builds**: secrets stored in OS keychain (`moraya-secrets`).
+/// - **Release builds**: secrets stored in OS keychain (`moraya-secrets`),
+///   or an encrypted file when no keychain backend is available.
 /// - **Debug builds**: secrets stored in a local JSON file to avoid
 ///   repeated macOS keychain authorization prompts.
 pub struct AIProxyState {
     abort_flags: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    /// Streams by request id; see [`StreamRelay`].
+    relays: Mutex<HashMap<String, Arc<StreamRelay>>>,
     /// In-memory mirror of secrets.
     pub(crate) key_cache: Mutex<HashMap<String, String>>,
     /// Guards the one-time keychain load. tokio::sync::Mutex ensures concurrent
     /// callers properly wait for the first load to complete instead of racing.
     secrets_loaded: tokio::sync::Mutex<bool>,
+    backend: Mutex<SecretBackend>,
 }
 
 impl AIProxyState {
     pub fn new() -> Self {
         Self {
             abort_flags: Mutex::new(HashMap::new()),
+            relays: Mutex::new(HashMap::new()),
             key_cache: Mutex::new(HashMap::new()),
             secrets_loaded: tokio::sync::Mutex::new(false),
+            backend: Mutex::new(if cfg!(debug_assertions) {
+                SecretBackend::Dev
+            } else {
+                SecretBackend::Keychain
+            }),
+        }
+    }
+
+    pub(crate) fn backend(&self) -> SecretBackend {
+        self.backend
+            .lock()
+            .map(|b| *b)
+            .unwrap_or(SecretBackend::Keychain)
+    }
+
+    fn set_backend(&self, backend: SecretBackend) {
+        if let Ok(mut b) = self.backend.lock() {
+            *b = backend;
         }
     }
 
@@ -138,9 +270,11 @@ impl AIProxyState {
                 .and_then(|p| std::fs::read_to_string(p).ok())
                 .unwrap_or_default()
         } else {
-            tokio::task::spawn_blocking(read_os_secrets)
+            let (json, backend) = tokio::task::spawn_blocking(load_release_secrets)
                 .await
-                .unwrap_or_default()
+                .unwrap_or((String::new(), SecretBackend::Keychain));
+            self.set_backend(backend);
+            json
         };
 
         if !json.is_empty() {
@@ -179,12 +313,43 @@ impl AIProxyState {
                     .map_err(|_| "Failed to write dev secrets file".to_string())?;
             }
         } else {
-            tokio::task::spawn_blocking(move || write_os_secrets(&json))
+            let backend = self.backend();
+            let stored = tokio::task::spawn_blocking(move || store_release_secrets(&json, backend))
                 .await
                 .unwrap_or_else(|_| Err("Keychain task failed".to_string()))?;
+            self.set_backend(stored);
         }
         Ok(())
     }
+
+    /// Move secrets from the fallback file into the OS keychain and delete
+    /// the file. No-op unless the fallback is in use.
+    pub(crate) async fn migrate_to_keychain(&self) -> Result<(), String> {
+        self.ensure_secrets_loaded().await;
+        if self.backend() != SecretBackend::Fallback {
+            return Ok(());
+        }
+        let json = {
+            let cache = self
+                .key_cache
+                .lock()
+                .map_err(|_| "Lock error".to_string())?;
+            serde_json::to_string(&*cache)
+                .map_err(|_| "Failed to serialize secrets".to_string())?
+        };
+        tokio::task::spawn_blocking(move || match write_os_secrets(&json) {
+            Ok(()) => {
+                remove_fallback_secrets();
+                Ok(())
+            }
+            Err(OsSecretsError::Unavailable) => Err("No OS keychain is available".to_string()),
+            Err(OsSecretsError::Failed(e)) => Err(e),
+        })
+        .await
+        .unwrap_or_else(|_| Err("Keychain task failed".to_string()))?;
+        self.set_backend(SecretBackend::Keychain);
+        Ok(())
+    }
 }
 
 impl Default for AIProxyState {
@@ -193,8 +358,86 @@ impl Default for AIProxyState {
     }
 }
 
+/// How long a detached stream's chunks and result are kept for
+/// `ai_proxy_resume` after the stream ends.
+const RELAY_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Default)]
+struct RelayInner {
+    /// `None` once a send failed, e.g. because the webview reloaded.
+    channel: Option<Channel<String>>,
+    /// Chunks that arrived while no channel was attached.
+    buffer: Vec<String>,
+    result: Option<Result<(), CommandError>>,
+    finished_at: Option<Instant>,
+}
+
+/// Where a stream's chunks go: the caller's channel while it works, a
+/// replay buffer otherwise, until `ai_proxy_resume` attaches a new channel.
+pub(crate) struct StreamRelay {
+    inner: Mutex<RelayInner>,
+    finished: tokio::sync::Notify,
+}
+
+impl StreamRelay {
+    fn new(channel: Channel<String>) -> Self {
+        Self {
+            inner: Mutex::new(RelayInner {
+                channel: Some(channel),
+                ..Default::default()
+            }),
+            finished: tokio::sync::Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RelayInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send(&self, text: String) {
+        let mut inner = self.lock();
+        if let Some(channel) = &inner.channel {
+            if channel.send(text.clone()).is_ok() {
+                return;
+            }
+            log::warn!("Stream channel closed; buffering the rest for resume");
+            inner.channel = None;
+        }
+        inner.buffer.push(text);
+    }
+
+    /// Store the result and wake a waiting resume. Returns whether the
+    /// stream ended detached, so its relay must be kept for a resume.
+    fn finish(&self, result: Result<(), CommandError>) -> bool {
+        let detached = {
+            let mut inner = self.lock();
+            inner.result = Some(result);
+            inner.finished_at = Some(Instant::now());
+            inner.channel.is_none()
+        };
+        self.finished.notify_waiters();
+        detached
+    }
+
+    fn expired(&self) -> bool {
+        self.lock()
+            .finished_at
+            .is_some_and(|t| t.elapsed() > RELAY_TTL)
+    }
+}
+
+impl AIProxyState {
+    fn relays(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<StreamRelay>>> {
+        self.relays.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn prune_relays(&self) {
+        self.relays().retain(|_, relay| !relay.expired());
+    }
+}
+
 fn build_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
+    super::settings::client_builder()
         .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
         .build()
         .map_err(|_| "Failed to create HTTP client".to_string())
@@ -249,33 +492,52 @@ fn build_request(
     req
 }
 
-/// Resolve the API key: use override if provided, otherwise read from the
-/// in-memory secrets cache (populated from the single keychain entry).
-async fn resolve_api_key(
-    state: &AIProxyState,
+/// Cache key prefixes the proxies may read: chat and image generation keys.
+/// Other secrets in the cache (storage, speech, ...) never leave through here.
+const PROXY_KEY_PREFIXES: [&str; 2] = [AI_KEY_PREFIX, IMAGE_KEY_PREFIX];
+
+/// A masked placeholder the settings UI shows instead of a saved key.
+fn is_masked_key(key: &str) -> bool {
+    key.trim().chars().all(|c| c == '*' || c == '•')
+}
+
+/// Pick the API key for a request: a real override wins, otherwise the
+/// cached key of `config_id` under `key_prefix`. A masked override falls
+/// through to the cache.
+fn lookup_api_key(
+    cache: &HashMap<String, String>,
     config_id: &str,
     key_prefix: Option<&str>,
     api_key_override: Option<&str>,
-) -> Result<String, String> {
-    if let Some(key) = api_key_override {
-        if !key.is_empty() && key != "***" {
-            return Ok(key.to_string());
-        }
-    }
-
-    state.ensure_secrets_loaded().await;
-
+) -> Result<String, CommandError> {
     let prefix = key_prefix.unwrap_or(AI_KEY_PREFIX);
-    let cache_key = format!("{}{}", prefix, config_id);
-
-    if let Ok(cache) = state.key_cache.lock() {
-        if let Some(cached) = cache.get(&cache_key) {
-            return Ok(cached.clone());
-        }
+    if !PROXY_KEY_PREFIXES.contains(&prefix) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            format!("Unknown key prefix: {}", prefix),
+        ));
+    }
+    if let Some(key) = api_key_override.filter(|k| !is_masked_key(k)) {
+        return Ok(key.to_string());
     }
-
     // No key found — empty string (e.g. Ollama needs no auth)
-    Ok(String::new())
+    Ok(cache
+        .get(&format!("{}{}", prefix, config_id))
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Resolve the API key from the override or the in-memory secrets cache
+/// (populated from the single keychain entry); see [`lookup_api_key`].
+async fn resolve_api_key(
+    state: &AIProxyState,
+    config_id: &str,
+    key_prefix: Option<&str>,
+    api_key_override: Option<&str>,
+) -> Result<String, CommandError> {
+    state.ensure_secrets_loaded().await;
+    let cache = state.key_cache.lock().map_err(|e| e.to_string())?;
+    lookup_api_key(&cache, config_id, key_prefix, api_key_override)
 }
 
 /// Non-streaming AI API proxy.
@@ -293,7 +555,7 @@ pub async fn ai_proxy_fetch(
     body: Option<String>,
     headers: Option<HashMap<String, String>>,
     method: Option<String>,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     let api_key = resolve_api_key(
         &state,
         &config_id,
@@ -305,6 +567,7 @@ pub async fn ai_proxy_fetch(
     let m = method.as_deref().unwrap_or("POST");
     let b = body.as_deref().unwrap_or("{}");
     let req = build_request(&client, &provider, &api_key, &url, b, &hdrs, m);
+    let started = Instant::now();
 
     // Register abort flag when request_id is provided
     let abort_flag = if let Some(ref rid) = request_id {
@@ -329,7 +592,7 @@ pub async fn ai_proxy_fetch(
         };
         tokio::select! {
             res = do_fetch(req) => res,
-            _ = abort_checker => Err("Aborted by user".to_string()),
+            _ = abort_checker => Err(CommandError::new(ErrorCode::AiAborted, "Aborted by user")),
         }
     } else {
         do_fetch(req).await
@@ -342,28 +605,73 @@ pub async fn ai_proxy_fetch(
         }
     }
 
+    // GETs are model lists and task polls, not billed generations
+    if m != "GET" {
+        let mut tokens = TokenUsage::default();
+        if let Ok(text) = &result {
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(text) {
+                tokens.observe(&json);
+            }
+        }
+        let status = match &result {
+            Ok(_) => UsageStatus::Ok,
+            Err(e) if e.code == ErrorCode::AiAborted => UsageStatus::Aborted,
+            Err(_) => UsageStatus::Error,
+        };
+        record_usage(&provider, &url, b, started, tokens, status);
+    }
+
     result
 }
 
-async fn do_fetch(req: reqwest::RequestBuilder) -> Result<String, String> {
-    let response = req.send().await.map_err(|e| {
-        if e.is_timeout() {
-            "AI request timed out".to_string()
-        } else {
-            "AI request failed".to_string()
-        }
-    })?;
+/// Add a finished request to the usage ledger. Only the model name is
+/// taken from the body, never the prompt.
+fn record_usage(
+    provider: &str,
+    url: &str,
+    body: &str,
+    started: Instant,
+    tokens: TokenUsage,
+    status: UsageStatus,
+) {
+    ai_usage::record(UsageRecord {
+        at: chrono::Utc::now().timestamp_millis(),
+        provider: provider.to_string(),
+        model: request_model(url, body),
+        prompt_tokens: tokens.prompt,
+        completion_tokens: tokens.completion,
+        duration_ms: started.elapsed().as_millis() as u64,
+        status,
+    });
+}
+
+async fn do_fetch(req: reqwest::RequestBuilder) -> Result<String, CommandError> {
+    let response = req.send().await.map_err(request_error)?;
 
     if !response.status().is_success() {
         let status = response.status().as_u16();
         let err_body = response.text().await.unwrap_or_default();
-        return Err(truncate_api_error(status, &err_body));
+        return Err(api_error(status, &err_body));
     }
 
     response
         .text()
         .await
-        .map_err(|_| "Failed to read response".to_string())
+        .map_err(|_| CommandError::new(ErrorCode::AiNetworkError, "Failed to read response"))
+}
+
+fn request_error(e: reqwest::Error) -> CommandError {
+    if e.is_timeout() {
+        CommandError::new(ErrorCode::AiTimeout, "AI request timed out")
+    } else {
+        CommandError::new(ErrorCode::AiNetworkError, "AI request failed")
+    }
+}
+
+/// Non-2xx provider response; `details.status` carries the HTTP status.
+fn api_error(status: u16, body: &str) -> CommandError {
+    CommandError::new(ErrorCode::AiHttpError, truncate_api_error(status, body))
+        .with_details(serde_json::json!({ "status": status }))
 }
 
 /// Streaming AI API proxy.
@@ -374,13 +682,19 @@ pub async fn ai_proxy_stream(
     on_event: Channel<String>,
     request_id: String,
     config_id: String,
+    key_prefix: Option<String>,
     api_key_override: Option<String>,
     provider: String,
     url: String,
     body: String,
     headers: Option<HashMap<String, String>>,
-) -> Result<(), String> {
-    let api_key = resolve_api_key(&state, &config_id, None, api_key_override.as_deref()).await?;
+) -> Result<(), CommandError> {
+    let api_key = resolve_api_key(
+        &state,
+        &config_id,
+        key_prefix.as_deref(),
+        api_key_override.as_deref(),
+    ).await?;
     let client = build_client()?;
     let hdrs = headers.unwrap_or_default();
     let req = build_request(&client, &provider, &api_key, &url, &body, &hdrs, "POST");
@@ -392,12 +706,31 @@ pub async fn ai_proxy_stream(
         flags.insert(request_id.clone(), abort_flag.clone());
     }
 
-    let result = do_stream(&on_event, &provider, req, &abort_flag).await;
+    state.prune_relays();
+    let relay = Arc::new(StreamRelay::new(on_event));
+    state.relays().insert(request_id.clone(), relay.clone());
+
+    let started = Instant::now();
+    let mut tokens = TokenUsage::default();
+    let result = do_stream(&relay, &provider, req, &abort_flag, &mut tokens).await;
 
     // Cleanup
     if let Ok(mut flags) = state.abort_flags.lock() {
         flags.remove(&request_id);
     }
+    // A detached stream waits for `ai_proxy_resume` or `ai_proxy_ack`
+    if !relay.finish(result.clone()) {
+        state.relays().remove(&request_id);
+    }
+
+    let status = if abort_flag.load(Ordering::SeqCst) {
+        UsageStatus::Aborted
+    } else if result.is_err() {
+        UsageStatus::Error
+    } else {
+        UsageStatus::Ok
+    };
+    record_usage(&provider, &url, &body, started, tokens, status);
 
     result
 }
@@ -407,7 +740,7 @@ pub async fn ai_proxy_stream(
 pub fn ai_proxy_abort(
     state: tauri::State<'_, AIProxyState>,
     request_id: String,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let flags = state.abort_flags.lock().map_err(|e| e.to_string())?;
     if let Some(flag) = flags.get(&request_id) {
         flag.store(true, Ordering::SeqCst);
@@ -415,24 +748,255 @@ pub fn ai_proxy_abort(
     Ok(())
 }
 
+/// Continue a stream whose channel was lost, e.g. after a webview reload or
+/// when its document moved to another window: buffered chunks are replayed
+/// to `on_event`, then the stream continues live. Resolves when the stream
+/// ends, like `ai_proxy_stream`.
+#[tauri::command]
+pub async fn ai_proxy_resume(
+    state: tauri::State<'_, AIProxyState>,
+    request_id: String,
+    on_event: Channel<String>,
+) -> Result<(), CommandError> {
+    state.prune_relays();
+    let relay = state.relays().get(&request_id).cloned().ok_or_else(|| {
+        CommandError::new(ErrorCode::InvalidArgument, "No stream to resume")
+    })?;
+    let finished = relay.finished.notified();
+    {
+        let mut inner = relay.lock();
+        for chunk in inner.buffer.drain(..) {
+            let _ = on_event.send(chunk);
+        }
+        if inner.result.is_none() {
+            // The notified future above was created under this lock, so a
+            // finish after the lock is released still wakes it
+            inner.channel = Some(on_event);
+        }
+    }
+    if relay.lock().result.is_none() {
+        finished.await;
+    }
+    state.relays().remove(&request_id);
+    let result = relay.lock().result.clone();
+    result.unwrap_or(Ok(()))
+}
+
+/// Drop a detached stream's buffer without resuming it.
+#[tauri::command]
+pub fn ai_proxy_ack(state: tauri::State<'_, AIProxyState>, request_id: String) {
+    state.relays().remove(&request_id);
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetachedStream {
+    pub request_id: String,
+    pub buffered_chunks: usize,
+    pub finished: bool,
+}
+
+/// Streams that lost their channel and can be resumed, so a reloaded
+/// webview can pick them up.
+#[tauri::command]
+pub fn ai_proxy_detached_streams(state: tauri::State<'_, AIProxyState>) -> Vec<DetachedStream> {
+    state.prune_relays();
+    state
+        .relays()
+        .iter()
+        .filter_map(|(id, relay)| {
+            let inner = relay.lock();
+            if inner.channel.is_some() {
+                return None;
+            }
+            Some(DetachedStream {
+                request_id: id.clone(),
+                buffered_chunks: inner.buffer.len(),
+                finished: inner.result.is_some(),
+            })
+        })
+        .collect()
+}
+
+/// One entry of a provider's model list, normalized across vendors.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u64>,
+    /// Input modalities and features the provider advertises (`vision`,
+    /// `tools`, `generateContent`, ...), in the provider's own words.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Vec<String>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelList {
+    /// `false` when the provider has no list endpoint; `models` is empty then.
+    pub supported: bool,
+    pub models: Vec<ModelInfo>,
+}
+
+/// `/v1` + `path` unless the base URL already ends with a version segment,
+/// like `openaiEndpoint` on the frontend.
+fn openai_endpoint(base_url: &str, path: &str) -> String {
+    let clean = base_url.trim_end_matches('/');
+    let versioned = clean
+        .rsplit('/')
+        .next()
+        .and_then(|seg| seg.strip_prefix('v'))
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+    if versioned {
+        format!("{}{}", clean, path)
+    } else {
+        format!("{}/v1{}", clean, path)
+    }
+}
+
+fn is_openrouter(provider: &str, base_url: &str) -> bool {
+    provider == "openrouter" || base_url.contains("openrouter.ai")
+}
+
+/// The model list endpoint and extra headers for `provider`, or `None`
+/// when it has no list endpoint.
+fn models_endpoint(provider: &str, base_url: &str) -> Option<(String, HashMap<String, String>)> {
+    let base = base_url.trim_end_matches('/');
+    let mut headers = HashMap::new();
+    let url = match provider {
+        _ if is_openrouter(provider, base) => openai_endpoint(base, "/models"),
+        "claude" => {
+            headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
+            format!("{}/v1/models?limit=1000", base)
+        }
+        "gemini" => format!("{}/v1beta/models?pageSize=1000", base),
+        "ollama" => format!("{}/api/tags", base),
+        "glm" | "minimax" | "doubao" => return None,
+        _ => openai_endpoint(base, "/models"),
+    };
+    Some((url, headers))
+}
+
+/// Normalize a model list response. OpenAI-style `data` arrays (OpenAI,
+/// Anthropic, OpenRouter, Mistral, ...) and `models` arrays (Gemini,
+/// Ollama) are both accepted.
+fn normalize_models(provider: &str, body: &serde_json::Value) -> Option<Vec<ModelInfo>> {
+    let items = body
+        .get("data")
+        .or_else(|| body.get("models"))?
+        .as_array()?;
+    let str_of = |v: &serde_json::Value, key: &str| {
+        v.get(key)
+            .and_then(|s| s.as_str())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+    };
+    let models = items
+        .iter()
+        .filter_map(|m| {
+            let id = if provider == "gemini" {
+                str_of(m, "name").map(|n| n.trim_start_matches("models/").to_string())
+            } else {
+                str_of(m, "id").or_else(|| str_of(m, "name"))
+            }?;
+            let display_name = str_of(m, "display_name")
+                .or_else(|| str_of(m, "displayName"))
+                .or_else(|| str_of(m, "name").filter(|_| provider != "gemini"))
+                .unwrap_or_else(|| id.clone());
+            let context_length = [
+                "context_length",
+                "max_context_length",
+                "context_window",
+                "inputTokenLimit",
+            ]
+            .iter()
+            .find_map(|k| m.get(*k).and_then(|v| v.as_u64()));
+            Some(ModelInfo {
+                id,
+                display_name,
+                context_length,
+                capabilities: capabilities(m),
+            })
+        })
+        .collect();
+    Some(models)
+}
+
+fn capabilities(model: &serde_json::Value) -> Option<Vec<String>> {
+    let mut caps: Vec<String> = Vec::new();
+    // Mistral: { "capabilities": { "vision": true, ... } }
+    if let Some(obj) = model.get("capabilities").and_then(|c| c.as_object()) {
+        caps.extend(
+            obj.iter()
+                .filter(|(_, v)| v.as_bool() == Some(true))
+                .map(|(k, _)| k.clone()),
+        );
+    }
+    let strings = |v: Option<&serde_json::Value>| -> Vec<String> {
+        v.and_then(|a| a.as_array())
+            .map(|a| a.iter().filter_map(|s| s.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    };
+    // Gemini
+    caps.extend(strings(model.get("supportedGenerationMethods")));
+    // OpenRouter
+    caps.extend(strings(model.pointer("/architecture/input_modalities")));
+    if strings(model.get("supported_parameters")).iter().any(|p| p == "tools") {
+        caps.push("tools".to_string());
+    }
+    let mut seen = std::collections::HashSet::new();
+    caps.retain(|c| seen.insert(c.clone()));
+    (!caps.is_empty()).then_some(caps)
+}
+
+/// List the models a provider offers, for the model picker. Auth is added
+/// as in [`ai_proxy_fetch`]; `api_key_override` lets the settings screen
+/// list models for a key that isn't saved yet.
+#[tauri::command]
+pub async fn ai_list_models(
+    state: tauri::State<'_, AIProxyState>,
+    config_id: String,
+    provider: String,
+    base_url: String,
+    api_key_override: Option<String>,
+) -> Result<ModelList, CommandError> {
+    let Some((url, headers)) = models_endpoint(&provider, &base_url) else {
+        return Ok(ModelList {
+            supported: false,
+            models: Vec::new(),
+        });
+    };
+    let api_key = resolve_api_key(&state, &config_id, None, api_key_override.as_deref()).await?;
+    let client = build_client()?;
+    let req = build_request(&client, &provider, &api_key, &url, "", &headers, "GET");
+    let text = do_fetch(req).await?;
+    let body: serde_json::Value = serde_json::from_str(&text).map_err(|_| {
+        CommandError::new(ErrorCode::AiProviderError, "Unexpected model list response")
+    })?;
+    let models = normalize_models(&provider, &body).ok_or_else(|| {
+        CommandError::new(ErrorCode::AiProviderError, "Unexpected model list response")
+    })?;
+    Ok(ModelList {
+        supported: true,
+        models,
+    })
+}
+
 async fn do_stream(
-    on_event: &Channel<String>,
+    relay: &StreamRelay,
     provider: &str,
     req: reqwest::RequestBuilder,
     abort_flag: &Arc<AtomicBool>,
-) -> Result<(), String> {
-    let response = req.send().await.map_err(|e| {
-        if e.is_timeout() {
-            "AI request timed out".to_string()
-        } else {
-            "AI request failed".to_string()
-        }
-    })?;
+    tokens: &mut TokenUsage,
+) -> Result<(), CommandError> {
+    let response = req.send().await.map_err(request_error)?;
 
     if !response.status().is_success() {
         let status = response.status().as_u16();
         let err_body = response.text().await.unwrap_or_default();
-        return Err(truncate_api_error(status, &err_body));
+        return Err(api_error(status, &err_body));
     }
 
     use futures_util::StreamExt;
@@ -465,7 +1029,7 @@ async fn do_stream(
                 c = stream.next() => c,
                 _ = abort_wait => None,
                 _ = read_timeout => {
-                    eprintln!("[ai_proxy] Stream read timeout: no data for {}s", CHUNK_READ_TIMEOUT_SECS);
+                    log::warn!("Stream read timeout: no data for {}s", CHUNK_READ_TIMEOUT_SECS);
                     None
                 },
             }
@@ -476,41 +1040,44 @@ async fn do_stream(
             None => break, // stream ended, aborted, or timed out
         };
 
-        let bytes = chunk.map_err(|_| "Stream read error".to_string())?;
+        let bytes = chunk
+            .map_err(|_| CommandError::new(ErrorCode::AiNetworkError, "Stream read error"))?;
         buffer.push_str(&String::from_utf8_lossy(&bytes));
 
         // Process complete lines
         while let Some(pos) = buffer.find('\n') {
             let line = buffer[..pos].to_string();
             buffer = buffer[pos + 1..].to_string();
+            tokens.observe_line(&line);
 
             if let Some(text) = extract_sse_event(provider, &line) {
                 events_sent += 1;
-                let _ = on_event.send(text);
+                relay.send(text);
             } else if let Some(err) = extract_sse_error(&line) {
                 last_sse_error = Some(err);
             } else if line.contains("data") {
-                eprintln!("[ai_proxy] SSE line not parsed: {}", safe_truncate(&line, 200));
+                log::debug!("SSE line not parsed: {}", safe_truncate(&line, 200));
             }
         }
     }
 
     // Flush remaining buffer
     if !buffer.is_empty() {
+        tokens.observe_line(&buffer);
         if let Some(text) = extract_sse_event(provider, &buffer) {
             events_sent += 1;
-            let _ = on_event.send(text);
+            relay.send(text);
         } else if let Some(err) = extract_sse_error(&buffer) {
             last_sse_error = Some(err);
         } else if buffer.contains("data") {
-            eprintln!("[ai_proxy] SSE buffer not parsed: {}", safe_truncate(&buffer, 200));
+            log::debug!("SSE buffer not parsed: {}", safe_truncate(&buffer, 200));
         }
     }
 
     // If no valid events were sent but an error was found in the SSE stream, report it
     if events_sent == 0 {
         if let Some(err) = last_sse_error {
-            return Err(err);
+            return Err(CommandError::new(ErrorCode::AiProviderError, err));
         }
     }
 
@@ -643,3 +1210,143 @@ fn extract_sse_error(line: &str) -> Option<String> {
         _ => Some(format!("API error: {}", error)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_carry_status_in_api_error() {
+        let e = api_error(401, r#"{"error":{"message":"invalid x-api-key"}}"#);
+        assert_eq!(e.code, ErrorCode::AiHttpError);
+        assert_eq!(e.message, "API error (401): invalid x-api-key");
+        assert_eq!(e.details, Some(serde_json::json!({ "status": 401 })));
+    }
+
+    #[test]
+    fn should_extract_error_embedded_in_sse() {
+        assert_eq!(
+            extract_sse_error(r#"data: {"error":{"message":"model not found","code":"404"}}"#),
+            Some("API error (404): model not found".to_string())
+        );
+        assert_eq!(extract_sse_error(r#"data: {"choices":[]}"#), None);
+    }
+
+    fn cache() -> HashMap<String, String> {
+        HashMap::from([
+            ("ai-key:c1".to_string(), "sk-chat".to_string()),
+            ("image-key:c1".to_string(), "sk-image".to_string()),
+            ("storage-secret:c1".to_string(), "s3-secret".to_string()),
+        ])
+    }
+
+    #[test]
+    fn should_look_up_key_by_prefix() {
+        let cache = cache();
+        assert_eq!(lookup_api_key(&cache, "c1", None, None).unwrap(), "sk-chat");
+        assert_eq!(
+            lookup_api_key(&cache, "c1", Some("image-key:"), None).unwrap(),
+            "sk-image"
+        );
+        assert_eq!(lookup_api_key(&cache, "c2", Some("image-key:"), None).unwrap(), "");
+        let err = lookup_api_key(&cache, "c1", Some("storage-secret:"), None).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn should_pick_models_endpoint_per_provider() {
+        let url = |p: &str, base: &str| models_endpoint(p, base).map(|(u, _)| u);
+        assert_eq!(url("openai", "https://api.openai.com").unwrap(), "https://api.openai.com/v1/models");
+        assert_eq!(url("custom", "https://x.test/api/v3/").unwrap(), "https://x.test/api/v3/models");
+        assert_eq!(
+            url("custom", "https://openrouter.ai/api/v1").unwrap(),
+            "https://openrouter.ai/api/v1/models"
+        );
+        assert_eq!(url("ollama", "http://localhost:11434").unwrap(), "http://localhost:11434/api/tags");
+        let (claude, headers) = models_endpoint("claude", "https://api.anthropic.com").unwrap();
+        assert_eq!(claude, "https://api.anthropic.com/v1/models?limit=1000");
+        assert_eq!(headers.get("anthropic-version").map(String::as_str), Some("2023-06-01"));
+        assert_eq!(url("minimax", "https://api.minimax.io/v1"), None);
+    }
+
+    #[test]
+    fn should_normalize_model_lists() {
+        let gemini = serde_json::json!({ "models": [{
+            "name": "models/gemini-2.5-pro",
+            "displayName": "Gemini 2.5 Pro",
+            "inputTokenLimit": 1048576,
+            "supportedGenerationMethods": ["generateContent", "countTokens"]
+        }]});
+        assert_eq!(
+            normalize_models("gemini", &gemini).unwrap(),
+            vec![ModelInfo {
+                id: "gemini-2.5-pro".to_string(),
+                display_name: "Gemini 2.5 Pro".to_string(),
+                context_length: Some(1048576),
+                capabilities: Some(vec!["generateContent".to_string(), "countTokens".to_string()]),
+            }]
+        );
+
+        let openrouter = serde_json::json!({ "data": [{
+            "id": "anthropic/claude-sonnet-4",
+            "name": "Anthropic: Claude Sonnet 4",
+            "context_length": 200000,
+            "architecture": { "input_modalities": ["text", "image"] },
+            "supported_parameters": ["tools", "temperature"]
+        }]});
+        let models = normalize_models("custom", &openrouter).unwrap();
+        assert_eq!(models[0].display_name, "Anthropic: Claude Sonnet 4");
+        assert_eq!(models[0].context_length, Some(200000));
+        assert_eq!(
+            models[0].capabilities,
+            Some(vec!["text".to_string(), "image".to_string(), "tools".to_string()])
+        );
+
+        let ollama = serde_json::json!({ "models": [{ "name": "llama3:8b", "model": "llama3:8b" }] });
+        let openai = serde_json::json!({ "object": "list", "data": [{ "id": "gpt-4o", "object": "model" }] });
+        for (provider, body, id) in [("ollama", ollama, "llama3:8b"), ("openai", openai, "gpt-4o")] {
+            let models = normalize_models(provider, &body).unwrap();
+            assert_eq!(models[0].id, id);
+            assert_eq!(models[0].display_name, id);
+            assert_eq!(models[0].capabilities, None);
+        }
+        assert_eq!(normalize_models("openai", &serde_json::json!({ "error": "x" })), None);
+    }
+
+    #[test]
+    fn should_buffer_chunks_once_the_channel_fails() {
+        let alive = Arc::new(AtomicBool::new(true));
+        let delivered = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (a, d) = (alive.clone(), delivered.clone());
+        let relay = StreamRelay::new(Channel::new(move |_| {
+            if !a.load(Ordering::SeqCst) {
+                return Err(tauri::Error::WebviewNotFound);
+            }
+            d.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }));
+        relay.send("a".to_string());
+        alive.store(false, Ordering::SeqCst);
+        relay.send("b".to_string());
+        relay.send("c".to_string());
+        assert_eq!(delivered.load(Ordering::SeqCst), 1);
+        assert_eq!(relay.lock().buffer, ["b", "c"]);
+        assert!(relay.finish(Ok(())));
+        assert!(!relay.expired());
+    }
+
+    #[test]
+    fn should_not_let_masked_override_shadow_cache() {
+        let cache = cache();
+        for masked in ["***", " *** ", "••••", ""] {
+            assert_eq!(
+                lookup_api_key(&cache, "c1", Some("image-key:"), Some(masked)).unwrap(),
+                "sk-image"
+            );
+        }
+        assert_eq!(
+            lookup_api_key(&cache, "c1", Some("image-key:"), Some("sk-new")).unwrap(),
+            "sk-new"
+        );
+    }
+}
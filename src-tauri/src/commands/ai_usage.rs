@@ -0,0 +1,477 @@
+//! AI usage ledger: one JSON line per proxied chat or image request in
+//! `app_data_dir()/usage/<YYYY-MM>.jsonl`, with provider, model, token
+//! counts, duration and outcome. Prompt and response text are never stored.
+//!
+//! Recording is best-effort and never fails the request. A new month starts
+//! a new file; files older than [`KEEP_MONTHS`] are deleted then.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::sanitize_io_error;
+
+const KEEP_MONTHS: usize = 12;
+
+static USAGE_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Serializes appends and pruning.
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageStatus {
+    Ok,
+    Error,
+    Aborted,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    /// Milliseconds since the Unix epoch.
+    pub at: i64,
+    pub provider: String,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub duration_ms: u64,
+    pub status: UsageStatus,
+}
+
+/// Token counts seen in a response body or stream. Later values win, since
+/// streams report running totals (Gemini) or the final count last (Claude).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct TokenUsage {
+    pub prompt: Option<u64>,
+    pub completion: Option<u64>,
+}
+
+impl TokenUsage {
+    /// Pick up usage fields in the formats of OpenAI-compatible providers,
+    /// Claude, Gemini and Ollama.
+    pub fn observe(&mut self, json: &serde_json::Value) {
+        let num = |ptr: &str| json.pointer(ptr).and_then(|v| v.as_u64());
+        let prompt = num("/usage/prompt_tokens")
+            .or_else(|| num("/usage/input_tokens"))
+            .or_else(|| num("/message/usage/input_tokens"))
+            .or_else(|| num("/usageMetadata/promptTokenCount"))
+            .or_else(|| num("/prompt_eval_count"));
+        let completion = num("/usage/completion_tokens")
+            .or_else(|| num("/usage/output_tokens"))
+            .or_else(|| num("/message/usage/output_tokens"))
+            .or_else(|| num("/usageMetadata/candidatesTokenCount"))
+            .or_else(|| num("/eval_count"));
+        self.prompt = prompt.or(self.prompt);
+        self.completion = completion.or(self.completion);
+    }
+
+    /// One SSE `data:` line or NDJSON line of a stream.
+    pub fn observe_line(&mut self, line: &str) {
+        let line = line.trim();
+        let data = line.strip_prefix("data:").unwrap_or(line).trim_start();
+        if !data.starts_with('{') {
+            return;
+        }
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
+            self.observe(&json);
+        }
+    }
+}
+
+/// The model a request asked for: the body's `model` field, or the
+/// `models/<id>:` segment of a Gemini URL.
+pub(crate) fn request_model(url: &str, body: &str) -> Option<String> {
+    let from_body = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v.get("model")?.as_str().map(String::from))
+        .filter(|m| !m.is_empty());
+    from_body.or_else(|| {
+        let rest = &url[url.find("/models/")? + "/models/".len()..];
+        let end = rest.find([':', '?', '/']).unwrap_or(rest.len());
+        Some(rest[..end].to_string()).filter(|m| !m.is_empty())
+    })
+}
+
+/// Resolve the ledger directory. Called from the setup hook.
+pub fn init(app: &tauri::AppHandle) {
+    if let Ok(dir) = app.path().app_data_dir() {
+        let _ = USAGE_DIR.set(dir.join("usage"));
+    }
+}
+
+fn usage_dir() -> Result<&'static Path, CommandError> {
+    USAGE_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .ok_or_else(|| CommandError::new(ErrorCode::Internal, "Usage ledger is not available"))
+}
+
+fn month_key(at_ms: i64) -> String {
+    Local
+        .timestamp_millis_opt(at_ms)
+        .single()
+        .unwrap_or_else(Local::now)
+        .format("%Y-%m")
+        .to_string()
+}
+
+fn append(dir: &Path, record: &UsageRecord) -> std::io::Result<()> {
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.jsonl", month_key(record.at)));
+    let new_month = !path.exists();
+    let mut line = serde_json::to_string(record).map_err(std::io::Error::other)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(line.as_bytes())?;
+    if new_month {
+        prune(dir);
+    }
+    Ok(())
+}
+
+/// Ledger files, oldest first.
+fn ledger_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut files: Vec<(String, PathBuf)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let month = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".jsonl")?
+                .to_string();
+            Some((month, path))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn prune(dir: &Path) {
+    let files = ledger_files(dir);
+    let excess = files.len().saturating_sub(KEEP_MONTHS);
+    for (_, path) in &files[..excess] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Append `record` to the ledger on a background thread. Failures are
+/// logged and otherwise ignored.
+pub(crate) fn record(record: UsageRecord) {
+    let Some(dir) = USAGE_DIR.get() else { return };
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = append(dir, &record) {
+            log::warn!("Failed to record AI usage: {}", e);
+        }
+    });
+}
+
+/// USD per million prompt and completion tokens, for the estimate in
+/// [`UsageTotals`]. The first entry the model id starts with wins.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("o3", 2.0, 8.0),
+    ("claude-opus", 15.0, 75.0),
+    ("claude-sonnet", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-haiku", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini-2.5-flash", 0.3, 2.5),
+    ("deepseek-chat", 0.27, 1.1),
+    ("deepseek-reasoner", 0.55, 2.19),
+];
+
+fn estimated_cost(record: &UsageRecord) -> Option<f64> {
+    // OpenRouter ids carry a vendor prefix: `openai/gpt-4o`
+    let model = record
+        .model
+        .as_deref()?
+        .rsplit('/')
+        .next()?
+        .to_ascii_lowercase();
+    let (_, input, output) = PRICES.iter().find(|(id, _, _)| model.starts_with(id))?;
+    let prompt = record.prompt_tokens? as f64;
+    let completion = record.completion_tokens.unwrap_or(0) as f64;
+    Some((prompt * input + completion * output) / 1_000_000.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Provider,
+    Model,
+    Day,
+    Month,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub requests: u64,
+    /// Requests that errored or were aborted.
+    pub failed: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub duration_ms: u64,
+    /// From list prices of known models; see `unpriced_requests`.
+    pub estimated_cost_usd: f64,
+    /// Requests of unknown models or without token counts, not in the estimate.
+    pub unpriced_requests: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        if record.status != UsageStatus::Ok {
+            self.failed += 1;
+        }
+        self.prompt_tokens += record.prompt_tokens.unwrap_or(0);
+        self.completion_tokens += record.completion_tokens.unwrap_or(0);
+        self.duration_ms += record.duration_ms;
+        match estimated_cost(record) {
+            Some(cost) => self.estimated_cost_usd += cost,
+            None => self.unpriced_requests += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageGroup {
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummary {
+    pub total: UsageTotals,
+    /// Empty without `group_by`; otherwise sorted by key.
+    pub groups: Vec<UsageGroup>,
+}
+
+fn summarize(
+    records: impl Iterator<Item = UsageRecord>,
+    from: Option<i64>,
+    to: Option<i64>,
+    group_by: Option<GroupBy>,
+) -> UsageSummary {
+    let mut summary = UsageSummary::default();
+    let mut groups: BTreeMap<String, UsageTotals> = BTreeMap::new();
+    let in_range = |at: i64| from.is_none_or(|f| at >= f) && to.is_none_or(|t| at < t);
+    for record in records.filter(|r| in_range(r.at)) {
+        summary.total.add(&record);
+        let key = match group_by {
+            None => continue,
+            Some(GroupBy::Provider) => record.provider.clone(),
+            Some(GroupBy::Model) => record.model.clone().unwrap_or_default(),
+            Some(GroupBy::Day) | Some(GroupBy::Month) => {
+                let format = if group_by == Some(GroupBy::Day) {
+                    "%Y-%m-%d"
+                } else {
+                    "%Y-%m"
+                };
+                Local
+                    .timestamp_millis_opt(record.at)
+                    .single()
+                    .map(|t| t.format(format).to_string())
+                    .unwrap_or_default()
+            }
+        };
+        groups.entry(key).or_default().add(&record);
+    }
+    summary.groups = groups
+        .into_iter()
+        .map(|(key, totals)| UsageGroup { key, totals })
+        .collect();
+    summary
+}
+
+fn read_records(dir: &Path, from: Option<i64>, to: Option<i64>) -> Vec<UsageRecord> {
+    let first = from.map(month_key);
+    let last = to.map(month_key);
+    ledger_files(dir)
+        .into_iter()
+        .filter(|(month, _)| {
+            first.as_ref().is_none_or(|f| month >= f) && last.as_ref().is_none_or(|l| month <= l)
+        })
+        .filter_map(|(_, path)| fs::read_to_string(path).ok())
+        .flat_map(|text| {
+            text.lines()
+                .filter_map(|line| serde_json::from_str::<UsageRecord>(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Usage between `from` (inclusive) and `to` (exclusive), in milliseconds
+/// since the Unix epoch, optionally grouped.
+#[tauri::command]
+pub async fn usage_summary(
+    from: Option<i64>,
+    to: Option<i64>,
+    group_by: Option<GroupBy>,
+) -> Result<UsageSummary, CommandError> {
+    let dir = usage_dir()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        summarize(read_records(dir, from, to).into_iter(), from, to, group_by)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// Delete the whole ledger.
+#[tauri::command]
+pub fn usage_clear() -> Result<(), CommandError> {
+    let dir = usage_dir()?;
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for (_, path) in ledger_files(dir) {
+        fs::remove_file(path).map_err(sanitize_io_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(at: i64, provider: &str, model: &str, status: UsageStatus) -> UsageRecord {
+        UsageRecord {
+            at,
+            provider: provider.to_string(),
+            model: Some(model.to_string()),
+            prompt_tokens: Some(1_000_000),
+            completion_tokens: Some(100_000),
+            duration_ms: 1200,
+            status,
+        }
+    }
+
+    #[test]
+    fn should_collect_token_usage_across_formats() {
+        let mut claude = TokenUsage::default();
+        for line in [
+            r#"data: {"type":"message_start","message":{"usage":{"input_tokens":25,"output_tokens":1}}}"#,
+            r#"data: {"type":"content_block_delta","delta":{"text":"Hi"}}"#,
+            r#"data: {"type":"message_delta","usage":{"output_tokens":15}}"#,
+            "data: [DONE]",
+        ] {
+            claude.observe_line(line);
+        }
+        assert_eq!(
+            claude,
+            TokenUsage {
+                prompt: Some(25),
+                completion: Some(15)
+            }
+        );
+
+        let mut gemini = TokenUsage::default();
+        gemini.observe(&serde_json::json!({
+            "usageMetadata": { "promptTokenCount": 8, "candidatesTokenCount": 40 }
+        }));
+        assert_eq!(
+            gemini,
+            TokenUsage {
+                prompt: Some(8),
+                completion: Some(40)
+            }
+        );
+
+        let mut ollama = TokenUsage::default();
+        ollama.observe_line(r#"{"done":true,"prompt_eval_count":12,"eval_count":30}"#);
+        assert_eq!(
+            ollama,
+            TokenUsage {
+                prompt: Some(12),
+                completion: Some(30)
+            }
+        );
+    }
+
+    #[test]
+    fn should_find_the_requested_model() {
+        assert_eq!(
+            request_model(
+                "https://api.openai.com/v1/chat/completions",
+                r#"{"model":"gpt-4o"}"#
+            ),
+            Some("gpt-4o".to_string())
+        );
+        assert_eq!(
+            request_model(
+                "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-pro:streamGenerateContent?alt=sse",
+                r#"{"contents":[]}"#
+            ),
+            Some("gemini-2.5-pro".to_string())
+        );
+        assert_eq!(request_model("http://localhost/x", "{}"), None);
+    }
+
+    #[test]
+    fn should_summarize_by_group_within_range() {
+        let records = vec![
+            record(1_000, "openai", "gpt-4o-mini", UsageStatus::Ok),
+            record(2_000, "claude", "claude-sonnet-4-5", UsageStatus::Ok),
+            record(3_000, "openai", "my-finetune", UsageStatus::Error),
+            record(9_000, "openai", "gpt-4o-mini", UsageStatus::Ok),
+        ];
+        let summary = summarize(
+            records.into_iter(),
+            Some(1_000),
+            Some(9_000),
+            Some(GroupBy::Provider),
+        );
+        assert_eq!(summary.total.requests, 3);
+        assert_eq!(summary.total.failed, 1);
+        assert_eq!(summary.total.prompt_tokens, 3_000_000);
+        assert_eq!(summary.total.unpriced_requests, 1);
+        // 0.15 + 0.06 for gpt-4o-mini, 3.0 + 1.5 for Sonnet
+        assert!((summary.total.estimated_cost_usd - 4.71).abs() < 1e-9);
+        let keys: Vec<(&str, u64)> = summary
+            .groups
+            .iter()
+            .map(|g| (g.key.as_str(), g.totals.requests))
+            .collect();
+        assert_eq!(keys, [("claude", 1), ("openai", 2)]);
+    }
+
+    #[test]
+    fn should_append_and_keep_recent_months_only() {
+        let dir = std::env::temp_dir().join(format!("moraya-usage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for month in 1..=KEEP_MONTHS + 1 {
+            fs::write(dir.join(format!("2020-{:02}.jsonl", month)), "").unwrap();
+        }
+        let now = Local::now().timestamp_millis();
+        append(&dir, &record(now, "openai", "gpt-4o", UsageStatus::Ok)).unwrap();
+        append(&dir, &record(now, "openai", "gpt-4o", UsageStatus::Aborted)).unwrap();
+        let files = ledger_files(&dir);
+        assert_eq!(files.len(), KEEP_MONTHS);
+        assert_eq!(files.last().unwrap().0, month_key(now));
+        let records = read_records(&dir, Some(now), None);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].status, UsageStatus::Aborted);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,429 @@
+//! Unused images and attachments: files under `assets/` or `attachments/`
+//! folders of a vault that no note references.
+//!
+//! References come from markdown links and images (inline and reference
+//! style, percent-encoded or not), `src`/`href` attributes of HTML tags and
+//! wiki embeds (`![[shot.png|300]]`). A file no reference resolves to, but
+//! whose name still appears in some note — a wiki embed matching several
+//! files, a code sample, a link Moraya can't resolve — is reported as
+//! uncertain and never deleted.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use regex::Regex;
+use serde::Serialize;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{resolve_document_reference, validate_path};
+use super::link_metadata::{attr, attributes};
+use super::search::collect_files;
+
+/// Folder names whose files are candidates, compared case-insensitively.
+const ASSET_DIRS: &[&str] = &["assets", "attachments"];
+/// HTML attributes that point at files.
+const HTML_URL_ATTRS: &[&str] = &["src", "href", "poster", "data"];
+const MAX_DEPTH: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanedAsset {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanReport {
+    /// Safe to delete: nothing refers to them.
+    pub orphaned: Vec<OrphanedAsset>,
+    /// Not linked, but their name appears in a note; review by hand.
+    pub uncertain: Vec<OrphanedAsset>,
+    /// Total size of `orphaned`.
+    pub orphaned_bytes: u64,
+    pub notes_scanned: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedAssets {
+    pub trashed: Vec<String>,
+    /// Requested paths that are no longer (or never were) safe to delete.
+    pub skipped: Vec<String>,
+    /// Folder the files were moved to.
+    pub trash_dir: Option<String>,
+}
+
+/// Comparison key for a path: case-insensitive where the file system is.
+pub(crate) fn path_key(path: &Path) -> String {
+    let s = path.to_string_lossy().replace('\\', "/");
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        s.to_lowercase()
+    } else {
+        s
+    }
+}
+
+/// Files under every asset folder below `dir`, skipping hidden entries
+/// and symlinks like the file tree does.
+fn collect_assets(dir: &Path, depth: u32, in_assets: bool, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == "node_modules" {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() && depth < MAX_DEPTH {
+            let is_asset_dir = ASSET_DIRS.contains(&name.to_lowercase().as_str());
+            collect_assets(&entry.path(), depth + 1, in_assets || is_asset_dir, out);
+        } else if file_type.is_file() && in_assets {
+            out.push(entry.path());
+        }
+    }
+}
+
+pub(crate) fn wiki_embed_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    // [[target]], ![[target|size]], [[target#heading]]
+    RE.get_or_init(|| Regex::new(r"\[\[([^\]|#^\n]+)[^\]\n]*\]\]").expect("valid wiki regex"))
+}
+
+fn html_tag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)<(?:img|a|video|audio|source|embed|object|iframe)\b[^>]*>")
+            .expect("valid tag regex")
+    })
+}
+
+/// Link targets in one note: markdown URLs, HTML attribute URLs, and wiki
+/// embed targets (the latter resolved by name, separately).
+#[derive(Debug, Default, PartialEq)]
+struct NoteReferences {
+    urls: Vec<String>,
+    wiki: Vec<String>,
+}
+
+fn push_html_urls(html: &str, urls: &mut Vec<String>) {
+    for tag in html_tag_re().find_iter(html) {
+        let attrs = attributes(tag.as_str());
+        for name in HTML_URL_ATTRS {
+            if let Some(url) = attr(&attrs, name) {
+                urls.push(url.to_string());
+            }
+        }
+    }
+}
+
+fn push_wiki_targets(text: &str, targets: &mut Vec<String>) {
+    for c in wiki_embed_re().captures_iter(text) {
+        targets.push(c[1].trim().to_string());
+    }
+}
+
+fn extract_references(markdown: &str) -> NoteReferences {
+    let mut refs = NoteReferences::default();
+    // Wiki links aren't markdown: they arrive as text, split at brackets
+    let mut text_run = String::new();
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut parser = Parser::new_ext(markdown, options);
+    for event in parser.by_ref() {
+        if let Event::Text(text) = &event {
+            text_run.push_str(text);
+            continue;
+        }
+        push_wiki_targets(&text_run, &mut refs.wiki);
+        text_run.clear();
+        match event {
+            Event::Start(Tag::Image { dest_url, .. })
+            | Event::Start(Tag::Link { dest_url, .. }) => refs.urls.push(dest_url.to_string()),
+            Event::Html(html) | Event::InlineHtml(html) => push_html_urls(&html, &mut refs.urls),
+            _ => {}
+        }
+    }
+    push_wiki_targets(&text_run, &mut refs.wiki);
+    for (_, def) in parser.reference_definitions().iter() {
+        refs.urls.push(def.dest.to_string());
+    }
+    refs
+}
+
+/// Local file a markdown or HTML URL points at, without query or fragment.
+pub(crate) fn resolve_url(url: &str, doc_dir: &Path) -> Option<PathBuf> {
+    let url = url.trim().trim_start_matches('<').trim_end_matches('>');
+    let path = url.split(['#', '?']).next().unwrap_or(url);
+    if path.is_empty() {
+        return None;
+    }
+    resolve_document_reference(path, Some(doc_dir))
+}
+
+/// `name` as it appears percent-encoded in a link, lowercased.
+fn encoded_name(name: &str) -> Option<String> {
+    let url = url::Url::parse("file:///").ok()?.join(name).ok()?;
+    url.path().rsplit('/').next().map(str::to_lowercase)
+}
+
+/// Sort `assets` below `root` into orphaned and uncertain ones.
+fn scan(root: &Path) -> OrphanReport {
+    let mut notes = Vec::new();
+    collect_files(root, 0, &mut notes);
+    let mut assets = Vec::new();
+    collect_assets(root, 0, false, &mut assets);
+    assets.sort();
+
+    // Assets by lowercased file name, for wiki embeds
+    let mut by_name: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    for asset in &assets {
+        if let Some(name) = asset.file_name() {
+            let name = name.to_string_lossy().to_lowercase();
+            by_name.entry(name).or_default().push(asset);
+        }
+    }
+
+    let mut referenced: HashSet<String> = HashSet::new();
+    let mut ambiguous: HashSet<String> = HashSet::new();
+    let mut texts: Vec<String> = Vec::with_capacity(notes.len());
+    for note in &notes {
+        let Ok(text) = fs::read_to_string(note) else {
+            continue;
+        };
+        let doc_dir = note.parent().unwrap_or(root);
+        let refs = extract_references(&text);
+        for url in &refs.urls {
+            let Some(path) = resolve_url(url, doc_dir) else {
+                continue;
+            };
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            referenced.insert(path_key(&path));
+        }
+        for target in &refs.wiki {
+            let target = target.replace('\\', "/");
+            let target_lower = target.to_lowercase();
+            let name = target_lower.rsplit('/').next().unwrap_or(&target_lower);
+            let Some(candidates) = by_name.get(name) else {
+                continue;
+            };
+            // `![[assets/shot.png]]` narrows the match down by path suffix
+            let matches: Vec<&&PathBuf> = candidates
+                .iter()
+                .filter(|p| {
+                    let key = p.to_string_lossy().replace('\\', "/").to_lowercase();
+                    key.ends_with(&format!("/{}", target_lower))
+                })
+                .collect();
+            match matches.as_slice() {
+                [only] => {
+                    referenced.insert(path_key(only));
+                }
+                // Which one Obsidian-style resolution picks depends on the
+                // vault layout; keep all of them
+                _ => ambiguous.extend(candidates.iter().map(|p| path_key(p))),
+            }
+        }
+        texts.push(text.to_lowercase());
+    }
+
+    let mut report = OrphanReport {
+        notes_scanned: texts.len(),
+        ..Default::default()
+    };
+    for asset in &assets {
+        let key = path_key(asset);
+        if referenced.contains(&key) {
+            continue;
+        }
+        let size = fs::metadata(asset).map(|m| m.len()).unwrap_or(0);
+        let entry = OrphanedAsset {
+            path: asset.to_string_lossy().to_string(),
+            size,
+        };
+        let name = asset
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let encoded = encoded_name(&name).unwrap_or_else(|| name.clone());
+        let mentioned = texts
+            .iter()
+            .any(|t| t.contains(&name) || t.contains(&encoded));
+        if ambiguous.contains(&key) || mentioned {
+            report.uncertain.push(entry);
+        } else {
+            report.orphaned_bytes += size;
+            report.orphaned.push(entry);
+        }
+    }
+    report
+}
+
+/// Move the requested paths that are still orphaned into `trash_dir`,
+/// keeping their paths relative to `root`. `access` validates each path.
+fn trash_orphans(
+    root: &Path,
+    paths: &[String],
+    trash_dir: &Path,
+    access: impl Fn(&str) -> Option<PathBuf>,
+) -> TrashedAssets {
+    let orphaned: HashSet<String> = scan(root)
+        .orphaned
+        .iter()
+        .map(|a| path_key(Path::new(&a.path)))
+        .collect();
+    let mut result = TrashedAssets::default();
+    for requested in paths {
+        let Some(path) = access(requested) else {
+            result.skipped.push(requested.clone());
+            continue;
+        };
+        let rel = path.strip_prefix(root).ok().map(Path::to_path_buf);
+        let (true, Some(rel)) = (orphaned.contains(&path_key(&path)), rel) else {
+            result.skipped.push(requested.clone());
+            continue;
+        };
+        match super::kb_sync::move_to_trash(&path, &trash_dir.join(rel)) {
+            Ok(()) => result.trashed.push(requested.clone()),
+            Err(e) => {
+                log::warn!("{}", e);
+                result.skipped.push(requested.clone());
+            }
+        }
+    }
+    if !result.trashed.is_empty() {
+        result.trash_dir = Some(trash_dir.to_string_lossy().to_string());
+    }
+    result
+}
+
+pub(crate) fn validate_root(root: &str) -> Result<PathBuf, CommandError> {
+    let root = validate_path(root)?;
+    if !root.is_dir() {
+        return Err(CommandError::new(ErrorCode::NotADirectory, "Not a folder"));
+    }
+    Ok(root)
+}
+
+/// Images and attachments under `root` that no note references.
+#[tauri::command]
+pub async fn find_orphaned_assets(root: String) -> Result<OrphanReport, CommandError> {
+    let root = validate_root(&root)?;
+    tauri::async_runtime::spawn_blocking(move || scan(&root))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// Move orphaned assets to `~/.moraya/trash/assets/<timestamp>/`. Paths are
+/// checked again first: anything referenced or uncertain by now is skipped.
+#[tauri::command]
+pub async fn delete_orphaned_assets(
+    root: String,
+    paths: Vec<String>,
+) -> Result<TrashedAssets, CommandError> {
+    let root = validate_root(&root)?;
+    let home = dirs::home_dir()
+        .ok_or_else(|| CommandError::new(ErrorCode::Internal, "No home directory"))?;
+    let trash_dir = home
+        .join(".moraya")
+        .join("trash")
+        .join("assets")
+        .join(chrono::Utc::now().timestamp_millis().to_string());
+    tauri::async_runtime::spawn_blocking(move || {
+        trash_orphans(&root, &paths, &trash_dir, |p| validate_path(p).ok())
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    fn names(assets: &[OrphanedAsset], root: &Path) -> Vec<String> {
+        assets
+            .iter()
+            .map(|a| {
+                Path::new(&a.path)
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_extract_markdown_html_and_wiki_references() {
+        let refs = extract_references(
+            "![shot](assets/a%20b.png \"t\")\n\n\
+             <img width=\"200\" src='assets/c.png'>\n\n\
+             See ![[d.png|300]] and [[Other note]].\n\n\
+             [ref]: ../attachments/f.pdf\n",
+        );
+        assert_eq!(
+            refs.urls,
+            ["assets/a%20b.png", "assets/c.png", "../attachments/f.pdf"]
+        );
+        assert_eq!(refs.wiki, ["d.png", "Other note"]);
+    }
+
+    #[test]
+    fn should_report_orphans_and_keep_ambiguous_ones_uncertain() {
+        let root = TempDir::new("assets-scan");
+        for dir in ["assets", "notes/assets", "attachments", "other"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "assets/a b.png",
+            "assets/c.png",
+            "assets/d.png",
+            "assets/orphan.png",
+            "assets/dup.png",
+            "notes/assets/dup.png",
+            "attachments/mentioned.pdf",
+            "other/not-an-asset.png",
+        ] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        fs::write(
+            root.join("index.md"),
+            "![](assets/a%20b.png)\n\n<img src=\"assets/c.png\">\n\n![[d.png]] ![[dup.png]]\n\n\
+             ```\nsee mentioned.pdf\n```\n",
+        )
+        .unwrap();
+
+        let report = scan(&root);
+        assert_eq!(report.notes_scanned, 1);
+        assert_eq!(names(&report.orphaned, &root), ["assets/orphan.png"]);
+        assert_eq!(report.orphaned_bytes, 1);
+        assert_eq!(
+            names(&report.uncertain, &root),
+            [
+                "assets/dup.png",
+                "attachments/mentioned.pdf",
+                "notes/assets/dup.png"
+            ]
+        );
+
+        let trash = root.join(".trash");
+        let orphan = root.join("assets/orphan.png").to_string_lossy().to_string();
+        let dup = root.join("assets/dup.png").to_string_lossy().to_string();
+        let result = trash_orphans(&root, &[orphan.clone(), dup.clone()], &trash, |p| {
+            fs::canonicalize(p).ok()
+        });
+        assert_eq!(result.trashed, [orphan]);
+        assert_eq!(result.skipped, [dup]);
+        assert!(trash.join("assets/orphan.png").exists());
+        assert!(!root.join("assets/orphan.png").exists());
+    }
+}
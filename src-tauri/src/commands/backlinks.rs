@@ -0,0 +1,660 @@
+//! Notes that link to a file, so deleting it can warn first and fix the
+//! links instead of leaving them broken.
+//!
+//! Links are found like for unused assets: markdown links, images and
+//! reference definitions resolved against the note's folder, and wiki links
+//! matched by note name. Links inside code are ignored, and a wiki name
+//! shared by several notes is not counted since it may mean another one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use super::assets::{path_key, resolve_url, validate_root, wiki_embed_re};
+use super::error::{CommandError, ErrorCode};
+use super::file::{relative_path, strip_unc_prefix, validate_path, write_atomic};
+use super::search::{
+    clip, collect_files, mtime_secs, FileMatches, MatchPreview, ReplaceSummary, SkipReason,
+    SkippedFile, MAX_PREVIEWS_PER_FILE,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backlink {
+    pub path: String,
+    /// 1-based lines with a link to the file.
+    pub lines: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LinkKind {
+    /// `[text](dest)` or `![alt](dest)`.
+    Inline,
+    /// `[text][ref]`; the destination is written in the definition.
+    Reference,
+    /// `[ref]: dest`
+    Definition,
+    /// `[[Note]]`, `[[Note|alias]]`, `![[file.png]]`
+    Wiki,
+}
+
+#[derive(Debug)]
+struct Link {
+    kind: LinkKind,
+    /// URL, or the note name for wiki links.
+    dest: String,
+    span: Range<usize>,
+    /// Where `dest` is written, when it can be rewritten in place.
+    target: Option<Range<usize>>,
+    /// The text the link shows, which is what stays when it is removed.
+    label: Range<usize>,
+}
+
+fn push_wiki_links(text: &str, run: Range<usize>, links: &mut Vec<Link>) {
+    for c in wiki_embed_re().captures_iter(&text[run.clone()]) {
+        let whole = c.get(0).expect("group 0 always matches");
+        let name = c.get(1).expect("name group always matches");
+        let mut span = run.start + whole.start()..run.start + whole.end();
+        if text[..span.start].ends_with('!') {
+            span.start -= 1;
+        }
+        let lead = name.as_str().len() - name.as_str().trim_start().len();
+        let target = run.start + name.start() + lead
+            ..run.start + name.start() + lead + name.as_str().trim().len();
+        let label = match whole.as_str().find('|') {
+            Some(bar) => {
+                let alias = &whole.as_str()[bar + 1..whole.len() - 2];
+                let start =
+                    run.start + whole.start() + bar + 1 + (alias.len() - alias.trim_start().len());
+                start..start + alias.trim().len()
+            }
+            None => target.clone(),
+        };
+        links.push(Link {
+            kind: LinkKind::Wiki,
+            dest: text[target.clone()].to_string(),
+            span,
+            target: Some(target),
+            label,
+        });
+    }
+}
+
+/// Every link in a note with where it is written.
+fn find_links(text: &str) -> Vec<Link> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut links = Vec::new();
+    // Links being parsed: index into `links` and the label seen so far
+    let mut open: Vec<(usize, Option<Range<usize>>)> = Vec::new();
+    // Wiki links aren't markdown: they arrive as text, split at brackets
+    let mut text_run: Option<Range<usize>> = None;
+    let mut in_code = false;
+    let mut parser = Parser::new_ext(text, options).into_offset_iter();
+    for (event, range) in parser.by_ref() {
+        let closes = matches!(event, Event::End(TagEnd::Link | TagEnd::Image));
+        if let (false, Some((_, label))) = (closes, open.last_mut()) {
+            *label = Some(match label.take() {
+                Some(l) => l.start.min(range.start)..l.end.max(range.end),
+                None => range.clone(),
+            });
+        }
+        if let (Event::Text(_), false) = (&event, in_code) {
+            text_run = Some(match text_run.take() {
+                Some(run) if run.end == range.start => run.start..range.end,
+                Some(run) => {
+                    push_wiki_links(text, run, &mut links);
+                    range
+                }
+                None => range,
+            });
+            continue;
+        }
+        if let Some(run) = text_run.take() {
+            push_wiki_links(text, run, &mut links);
+        }
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            })
+            | Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                ..
+            }) => {
+                let kind = match link_type {
+                    LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut => {
+                        LinkKind::Reference
+                    }
+                    _ => LinkKind::Inline,
+                };
+                let open_len = if text[range.start..].starts_with('!') {
+                    2
+                } else {
+                    1
+                };
+                let label_start = range.start + open_len;
+                open.push((links.len(), None));
+                links.push(Link {
+                    kind,
+                    dest: dest_url.to_string(),
+                    span: range,
+                    target: None,
+                    label: label_start..label_start,
+                });
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                let Some((i, label)) = open.pop() else {
+                    continue;
+                };
+                let link = &mut links[i];
+                if let Some(label) = label {
+                    link.label = label;
+                }
+                if link.kind == LinkKind::Inline && !link.dest.is_empty() {
+                    let from = link.label.end;
+                    link.target = text[from..link.span.end]
+                        .find(link.dest.as_str())
+                        .map(|at| from + at..from + at + link.dest.len());
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(run) = text_run.take() {
+        push_wiki_links(text, run, &mut links);
+    }
+    for (_, def) in parser.reference_definitions().iter() {
+        let span = def.span.clone();
+        let target = text[span.clone()]
+            .find(def.dest.as_ref())
+            .map(|at| span.start + at..span.start + at + def.dest.len());
+        links.push(Link {
+            kind: LinkKind::Definition,
+            dest: def.dest.to_string(),
+            label: span.start..span.start,
+            span,
+            target,
+        });
+    }
+    links.sort_by_key(|l| l.span.start);
+    links
+}
+
+/// `path` with symlinks and `..` resolved, also once it no longer exists.
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(p) = fs::canonicalize(path) {
+        return strip_unc_prefix(p);
+    }
+    match (
+        path.parent().and_then(|p| fs::canonicalize(p).ok()),
+        path.file_name(),
+    ) {
+        (Some(dir), Some(name)) => strip_unc_prefix(dir).join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// A file being deleted, as wiki links name it.
+struct WikiName {
+    /// Lowercased, with `/` separators.
+    path: String,
+    file_name: String,
+    /// Notes are linked without their extension.
+    stem: Option<String>,
+}
+
+/// The file or folder being deleted.
+struct Deleted {
+    key: String,
+    names: Vec<WikiName>,
+    /// How many notes under the root have each (lowercased) name.
+    stems: HashMap<String, usize>,
+}
+
+impl Deleted {
+    fn contains_key(&self, key: &str) -> bool {
+        key == self.key || key.starts_with(&format!("{}/", self.key))
+    }
+
+    fn matches_url(&self, url: &str, doc_dir: &Path) -> bool {
+        resolve_url(url, doc_dir).is_some_and(|p| self.contains_key(&path_key(&canonical(&p))))
+    }
+
+    fn matches_wiki(&self, name: &str) -> bool {
+        let name = name.trim().replace('\\', "/").to_lowercase();
+        let last = name.rsplit('/').next().unwrap_or(&name);
+        let nested = name.contains('/');
+        let stem = last.strip_suffix(".md").unwrap_or(last);
+        if !nested && self.stems.get(stem).copied().unwrap_or(0) > 1 {
+            return false;
+        }
+        self.names.iter().any(|n| {
+            (n.file_name == last || n.stem.as_deref() == Some(last))
+                && (!nested
+                    || n.path.ends_with(&format!("/{}", name))
+                    || n.path.ends_with(&format!("/{}.md", name)))
+        })
+    }
+}
+
+/// A note that links to the deleted path, and the links that do.
+struct Referrer {
+    path: PathBuf,
+    text: String,
+    links: Vec<Link>,
+}
+
+fn scan(root: &Path, deleted: &Path) -> Vec<Referrer> {
+    let deleted = canonical(deleted);
+    let mut notes = Vec::new();
+    collect_files(root, 0, &mut notes);
+    notes.sort();
+
+    let mut stems: HashMap<String, usize> = HashMap::new();
+    for note in &notes {
+        if let Some(stem) = note.file_stem() {
+            *stems
+                .entry(stem.to_string_lossy().to_lowercase())
+                .or_default() += 1;
+        }
+    }
+    let mut files = Vec::new();
+    if deleted.is_dir() {
+        collect_files(&deleted, 0, &mut files);
+    } else {
+        files.push(deleted.clone());
+    }
+    let names = files
+        .iter()
+        .map(|f| {
+            let file_name = f
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let stem = super::search::is_markdown(&file_name)
+                .then(|| f.file_stem().map(|s| s.to_string_lossy().to_lowercase()))
+                .flatten();
+            WikiName {
+                path: f.to_string_lossy().replace('\\', "/").to_lowercase(),
+                file_name,
+                stem,
+            }
+        })
+        .collect();
+    let deleted = Deleted {
+        key: path_key(&deleted),
+        names,
+        stems,
+    };
+
+    let mut referrers = Vec::new();
+    for note in notes {
+        let note = canonical(&note);
+        // Notes inside a deleted folder go with it
+        if deleted.contains_key(&path_key(&note)) {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&note) else {
+            continue;
+        };
+        let doc_dir = note.parent().unwrap_or(root);
+        let links: Vec<Link> = find_links(&text)
+            .into_iter()
+            .filter(|l| match l.kind {
+                LinkKind::Wiki => deleted.matches_wiki(&l.dest),
+                _ => deleted.matches_url(&l.dest, doc_dir),
+            })
+            .collect();
+        if !links.is_empty() {
+            referrers.push(Referrer {
+                path: note,
+                text,
+                links,
+            });
+        }
+    }
+    referrers
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// The lines a reference definition takes up, newline included.
+fn definition_lines(text: &str, span: &Range<usize>) -> Range<usize> {
+    let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let start = if text[line_start..span.start].trim().is_empty() {
+        line_start
+    } else {
+        span.start
+    };
+    if text[span.clone()].ends_with('\n') {
+        return start..span.end;
+    }
+    let rest = &text[span.end..];
+    let end = match rest.find('\n') {
+        Some(nl) if rest[..nl].trim().is_empty() => span.end + nl + 1,
+        None if rest.trim().is_empty() => text.len(),
+        _ => span.end,
+    };
+    start..end
+}
+
+/// Where links are pointed instead of being removed.
+struct Replacement {
+    path: PathBuf,
+    /// Name for plain wiki links.
+    wiki_name: String,
+    /// Root-relative name for wiki links written with a folder.
+    wiki_path: String,
+}
+
+impl Replacement {
+    fn new(path: PathBuf, root: &Path) -> Self {
+        let strip_md = |s: &str| {
+            s.strip_suffix(".md")
+                .or_else(|| s.strip_suffix(".markdown"))
+                .unwrap_or(s)
+                .to_string()
+        };
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let wiki_path = relative_path(&path, root)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|| file_name.clone());
+        Self {
+            wiki_name: strip_md(&file_name),
+            wiki_path: strip_md(&wiki_path),
+            path,
+        }
+    }
+
+    fn url_from(&self, doc_dir: &Path, old: &str) -> Option<String> {
+        let rel = relative_path(&self.path, doc_dir)?;
+        let rel = rel
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace('%', "%25")
+            .replace(' ', "%20")
+            .replace('(', "%28")
+            .replace(')', "%29");
+        // Keep a `#heading` or `?query` the old link had
+        let suffix = old.find(['#', '?']).map_or("", |i| &old[i..]);
+        Some(format!("{}{}", rel, suffix))
+    }
+}
+
+/// How one link is rewritten: removed (keeping its text) or pointed at
+/// `replacement`.
+fn edit_for(
+    link: &Link,
+    text: &str,
+    doc_dir: &Path,
+    replacement: Option<&Replacement>,
+) -> Option<(Range<usize>, String)> {
+    let Some(replacement) = replacement else {
+        return Some(match link.kind {
+            LinkKind::Definition => (definition_lines(text, &link.span), String::new()),
+            _ => (link.span.clone(), text[link.label.clone()].to_string()),
+        });
+    };
+    let target = link.target.clone()?;
+    match link.kind {
+        // Follows its definition
+        LinkKind::Reference => None,
+        LinkKind::Wiki if link.dest.contains(['/', '\\']) => {
+            Some((target, replacement.wiki_path.clone()))
+        }
+        LinkKind::Wiki => Some((target, replacement.wiki_name.clone())),
+        LinkKind::Inline | LinkKind::Definition => {
+            Some((target, replacement.url_from(doc_dir, &link.dest)?))
+        }
+    }
+}
+
+/// Apply non-overlapping edits, returning the new text, how many were made
+/// and line previews.
+fn apply_edits(
+    text: &str,
+    mut edits: Vec<(Range<usize>, String)>,
+) -> (String, usize, Vec<MatchPreview>) {
+    edits.sort_by_key(|(r, _)| r.start);
+    let mut out = String::with_capacity(text.len());
+    let mut previews = Vec::new();
+    let mut made = 0;
+    let mut last = 0;
+    for (range, with) in edits {
+        // An image inside a link that is already being replaced
+        if range.start < last {
+            continue;
+        }
+        if previews.len() < MAX_PREVIEWS_PER_FILE {
+            let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[range.start..]
+                .find('\n')
+                .map_or(text.len(), |i| range.start + i);
+            let after = if range.end > line_end {
+                String::new()
+            } else {
+                format!(
+                    "{}{}{}",
+                    &text[line_start..range.start],
+                    with,
+                    &text[range.end..line_end]
+                )
+            };
+            previews.push(MatchPreview {
+                line: line_of(text, range.start),
+                before: clip(&text[line_start..line_end]),
+                after: clip(&after),
+            });
+        }
+        out.push_str(&text[last..range.start]);
+        out.push_str(&with);
+        last = range.end;
+        made += 1;
+    }
+    out.push_str(&text[last..]);
+    (out, made, previews)
+}
+
+fn relink(
+    root: &Path,
+    deleted: &Path,
+    replacement: Option<&Replacement>,
+    dry_run: bool,
+) -> ReplaceSummary {
+    let mut summary = ReplaceSummary {
+        dry_run,
+        files: Vec::new(),
+        total_matches: 0,
+        changed_files: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for referrer in scan(root, deleted) {
+        let doc_dir = referrer.path.parent().unwrap_or(root);
+        let edits: Vec<_> = referrer
+            .links
+            .iter()
+            .filter_map(|l| edit_for(l, &referrer.text, doc_dir, replacement))
+            .collect();
+        let (new_text, made, previews) = apply_edits(&referrer.text, edits);
+        if made == 0 {
+            continue;
+        }
+        let path_str = referrer.path.to_string_lossy().to_string();
+        if !dry_run {
+            if write_atomic(&referrer.path, new_text.as_bytes()).is_err() {
+                summary.skipped.push(SkippedFile {
+                    path: path_str,
+                    reason: SkipReason::WriteFailed,
+                });
+                continue;
+            }
+            summary.changed_files.push(path_str.clone());
+        }
+        summary.total_matches += made;
+        summary.files.push(FileMatches {
+            path: path_str,
+            matches: made,
+            modified: mtime_secs(&referrer.path).unwrap_or(0.0),
+            previews,
+        });
+    }
+    summary
+}
+
+/// Point links to `old` at `new` after a rename. Returns the notes changed.
+pub(crate) fn redirect_links(root: &Path, old: &Path, new: &Path) -> Vec<String> {
+    let replacement = Replacement::new(new.to_path_buf(), root);
+    relink(root, old, Some(&replacement), false).changed_files
+}
+
+/// Notes under `root` that link to `path` (a file or folder), with the
+/// lines the links are on. Call before deleting `path`.
+#[tauri::command]
+pub async fn check_references(path: String, root: String) -> Result<Vec<Backlink>, CommandError> {
+    let root = validate_root(&root)?;
+    let path = validate_path(&path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        scan(&root, &path)
+            .into_iter()
+            .map(|r| {
+                let mut lines: Vec<usize> = r
+                    .links
+                    .iter()
+                    .map(|l| line_of(&r.text, l.span.start))
+                    .collect();
+                lines.dedup();
+                Backlink {
+                    path: r.path.to_string_lossy().to_string(),
+                    lines,
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// Fix links to `deleted_path` in the notes under `root`: point them at
+/// `replacement`, or without one, remove them and keep their text. With
+/// `dry_run` only the changes are reported.
+#[tauri::command]
+pub async fn remove_or_redirect_links(
+    root: String,
+    deleted_path: String,
+    replacement: Option<String>,
+    dry_run: bool,
+) -> Result<ReplaceSummary, CommandError> {
+    let root = validate_root(&root)?;
+    let deleted = validate_path(&deleted_path)?;
+    let replacement = match replacement {
+        Some(p) => Some(Replacement::new(validate_path(&p)?, &root)),
+        None => None,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        relink(&root, &deleted, replacement.as_ref(), dry_run)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    const NOTE: &str = "# A\n\nSee [the target](target.md) and [[target|that note]].\n\n\
+                        ```\n[code](target.md)\n```\n\n\
+                        Also [ref][t] and [other](other.md).\n\n[t]: ./target.md\n";
+
+    fn vault(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("backlinks-{}", name));
+        fs::create_dir_all(dir.join("new")).unwrap();
+        fs::write(dir.join("a.md"), NOTE).unwrap();
+        fs::write(dir.join("target.md"), "# Target\n").unwrap();
+        fs::write(dir.join("other.md"), "[[a]]\n").unwrap();
+        fs::write(dir.join("new/dest note.md"), "# Dest\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn should_find_links_outside_code_with_labels() {
+        let links = find_links(NOTE);
+        let found: Vec<_> = links
+            .iter()
+            .map(|l| (l.kind, l.dest.as_str(), &NOTE[l.label.clone()]))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (LinkKind::Inline, "target.md", "the target"),
+                (LinkKind::Wiki, "target", "that note"),
+                (LinkKind::Reference, "./target.md", "ref"),
+                (LinkKind::Inline, "other.md", "other"),
+                (LinkKind::Definition, "./target.md", ""),
+            ]
+        );
+        assert_eq!(&NOTE[links[0].target.clone().unwrap()], "target.md");
+        assert_eq!(&NOTE[links[4].target.clone().unwrap()], "./target.md");
+    }
+
+    #[test]
+    fn should_report_and_remove_backlinks() {
+        let root = vault("remove");
+        let target = root.join("target.md");
+        let referrers = scan(&root, &target);
+        assert_eq!(referrers.len(), 1);
+        assert_eq!(referrers[0].path, root.join("a.md"));
+        let lines: Vec<usize> = referrers[0]
+            .links
+            .iter()
+            .map(|l| line_of(NOTE, l.span.start))
+            .collect();
+        assert_eq!(lines, [3, 3, 9, 11]);
+
+        let dry = relink(&root, &target, None, true);
+        assert_eq!(dry.total_matches, 4);
+        assert!(dry.changed_files.is_empty());
+        assert_eq!(fs::read_to_string(root.join("a.md")).unwrap(), NOTE);
+
+        let done = relink(&root, &target, None, false);
+        assert_eq!(done.changed_files.len(), 1);
+        assert_eq!(
+            fs::read_to_string(root.join("a.md")).unwrap(),
+            "# A\n\nSee the target and that note.\n\n```\n[code](target.md)\n```\n\n\
+             Also ref and [other](other.md).\n\n"
+        );
+    }
+
+    #[test]
+    fn should_redirect_backlinks_to_replacement() {
+        let root = vault("redirect");
+        let replacement = Replacement::new(root.join("new/dest note.md"), &root);
+        let done = relink(&root, &root.join("target.md"), Some(&replacement), false);
+        // The reference link follows its definition
+        assert_eq!(done.total_matches, 3);
+        assert_eq!(
+            fs::read_to_string(root.join("a.md")).unwrap(),
+            NOTE.replace(
+                "(target.md) and [[target|",
+                "(new/dest%20note.md) and [[dest note|"
+            )
+            .replace("[t]: ./target.md", "[t]: new/dest%20note.md")
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! Vault backups: zip archives of a folder, written to a backup folder.
+//!
+//! `backup_workspace` makes one on demand. With `backupSourceDir` and
+//! `backupTargetDir` set, the scheduler started from the setup hook makes
+//! one every `backupIntervalHours`, keeps the newest `maxBackups` and
+//! reports each run as `backup:completed` or `backup:failed`. The schedule
+//! is measured from the newest archive on disk, so a restart or a night
+//! asleep doesn't cause an extra backup, and a missed one runs on wake.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::{Emitter, Listener, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{sanitize_io_error, validate_path};
+use super::settings;
+
+pub const COMPLETED_EVENT: &str = "backup:completed";
+pub const FAILED_EVENT: &str = "backup:failed";
+
+const ARCHIVE_PREFIX: &str = "moraya-backup-";
+const ARCHIVE_EXT: &str = "zip";
+/// Folders that are rebuilt or versioned elsewhere.
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", ".Trash"];
+/// Longest the scheduler sleeps before checking the clock again, so sleep,
+/// wake and clock changes are noticed.
+const MAX_SLEEP: Duration = Duration::from_secs(15 * 60);
+/// Wait after a failed scheduled run before trying again.
+const RETRY_AFTER_FAILURE: Duration = Duration::from_secs(60 * 60);
+
+/// Payload of `backup:completed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupCompleted {
+    pub path: String,
+    pub files: usize,
+    /// Size of the archive.
+    pub bytes: u64,
+    /// Archives deleted because there were more than `maxBackups`.
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupStatus {
+    /// Milliseconds since the Unix epoch of the last finished run.
+    pub last_run: Option<i64>,
+    pub last_archive: Option<String>,
+    /// Error of the last run, `None` if it succeeded.
+    pub last_error: Option<CommandError>,
+    /// Milliseconds since the Unix epoch; `None` when scheduled backups are
+    /// off or not configured.
+    pub next_scheduled: Option<i64>,
+    pub running: bool,
+}
+
+pub struct BackupState {
+    status: Mutex<BackupStatus>,
+    /// Held for the whole of a run so manual and scheduled runs don't overlap.
+    run_lock: Mutex<()>,
+    /// Wakes the scheduler early after a settings change or manual run.
+    wake: tokio::sync::Notify,
+}
+
+impl BackupState {
+    pub fn new() -> Self {
+        Self {
+            status: Mutex::new(BackupStatus::default()),
+            run_lock: Mutex::new(()),
+            wake: tokio::sync::Notify::new(),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut BackupStatus)) {
+        match self.status.lock() {
+            Ok(mut g) => f(&mut g),
+            Err(e) => f(&mut e.into_inner()),
+        }
+    }
+
+    fn snapshot(&self) -> BackupStatus {
+        match self.status.lock() {
+            Ok(g) => g.clone(),
+            Err(e) => e.into_inner().clone(),
+        }
+    }
+}
+
+impl Default for BackupState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The backup settings, when both folders are set.
+struct Config {
+    source: String,
+    target: String,
+    /// `None` when scheduled backups are off.
+    interval: Option<Duration>,
+    keep: usize,
+}
+
+impl Config {
+    fn current() -> Option<Self> {
+        let hours = settings::backup_interval_hours();
+        Some(Self {
+            source: settings::backup_source_dir()?,
+            target: settings::backup_target_dir()?,
+            interval: (hours > 0).then(|| Duration::from_secs(hours * 60 * 60)),
+            keep: settings::max_backups() as usize,
+        })
+    }
+}
+
+fn not_configured() -> CommandError {
+    CommandError::new(
+        ErrorCode::BackupNotConfigured,
+        "Choose the folder to back up and where to keep backups first",
+    )
+}
+
+fn millis(time: SystemTime) -> Option<i64> {
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+fn is_archive_name(name: &str) -> bool {
+    name.starts_with(ARCHIVE_PREFIX)
+        && Path::new(name).extension().and_then(|e| e.to_str()) == Some(ARCHIVE_EXT)
+}
+
+/// Backup archives in `dir` with their modification times, newest first.
+fn list_archives(dir: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut archives: Vec<(PathBuf, SystemTime)> = entries
+        .flatten()
+        .filter(|e| is_archive_name(&e.file_name().to_string_lossy()))
+        .filter_map(|e| {
+            let meta = e.metadata().ok().filter(|m| m.is_file())?;
+            Some((e.path(), meta.modified().ok()?))
+        })
+        .collect();
+    archives.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+    archives
+}
+
+/// Delete all but the newest `keep` archives. Returns how many were deleted.
+fn rotate(dir: &Path, keep: usize) -> usize {
+    list_archives(dir)
+        .into_iter()
+        .skip(keep)
+        .filter(|(path, _)| fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// How long until the next backup is due, or `None` if it is due now.
+fn time_until_due(
+    last: Option<SystemTime>,
+    interval: Duration,
+    now: SystemTime,
+) -> Option<Duration> {
+    let last = last?;
+    // A backup "from the future" (clock moved back) counts as just made
+    let elapsed = now.duration_since(last).unwrap_or(Duration::ZERO);
+    interval.checked_sub(elapsed).filter(|d| !d.is_zero())
+}
+
+/// Unused archive path in `dir` for a backup started at `now`.
+fn archive_path(dir: &Path, now: chrono::NaiveDateTime) -> PathBuf {
+    let stem = format!("{}{}", ARCHIVE_PREFIX, now.format("%Y%m%d-%H%M%S"));
+    let mut path = dir.join(format!("{}.{}", stem, ARCHIVE_EXT));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", stem, n, ARCHIVE_EXT));
+        n += 1;
+    }
+    path
+}
+
+/// Files under `source` to archive, with their `/`-separated relative
+/// names. Symlinks, `SKIPPED_DIRS` and `exclude` (the backup folder, when
+/// it lives inside the vault) are left out.
+fn collect_files(source: &Path, exclude: &Path) -> std::io::Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(source.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let relative = format!("{}{}", prefix, name);
+            if file_type.is_dir() {
+                if !SKIPPED_DIRS.contains(&name.as_str()) && path != exclude {
+                    dirs.push((path, format!("{}/", relative)));
+                }
+            } else if file_type.is_file() {
+                files.push((path, relative));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
+/// Zip `source` into `dest`. The archive is written under a temporary name
+/// and renamed when complete, so an interrupted backup never looks like a
+/// finished one. Returns the number of files archived.
+fn write_archive(source: &Path, dest: &Path) -> Result<usize, CommandError> {
+    let exclude = dest.parent().unwrap_or(dest);
+    let files = collect_files(source, exclude).map_err(sanitize_io_error)?;
+    let partial = dest.with_extension(format!("{}.part", ARCHIVE_EXT));
+    let write = || -> zip::result::ZipResult<()> {
+        let mut zip = ZipWriter::new(fs::File::create(&partial)?);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (path, name) in &files {
+            // Files deleted or locked mid-backup are skipped, not fatal
+            let Ok(mut file) = fs::File::open(path) else {
+                continue;
+            };
+            let large = file.metadata().map(|m| m.len() >= u32::MAX as u64)?;
+            zip.start_file(name.as_str(), options.large_file(large))?;
+            std::io::copy(&mut file, &mut zip)?;
+        }
+        let mut file = zip.finish()?;
+        file.flush()?;
+        file.sync_all()?;
+        Ok(())
+    };
+    let result = write().and_then(|_| Ok(fs::rename(&partial, dest)?));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(
+            CommandError::new(ErrorCode::BackupFailed, "Failed to write the backup")
+                .with_details(serde_json::json!({ "reason": e.to_string() })),
+        );
+    }
+    Ok(files.len())
+}
+
+/// Validate both folders and archive `source` into `target`.
+fn backup_into(source: &str, target: &str) -> Result<(PathBuf, usize), CommandError> {
+    let source = validate_path(source)?;
+    if !source.is_dir() {
+        return Err(CommandError::new(ErrorCode::NotADirectory, "Not a folder"));
+    }
+    let target = validate_path(target)?;
+    fs::create_dir_all(&target).map_err(sanitize_io_error)?;
+    let dest = archive_path(&target, chrono::Local::now().naive_local());
+    let files = write_archive(&source, &dest)?;
+    Ok((dest, files))
+}
+
+/// One backup with the configured settings: archive, rotate, record the
+/// outcome in `BackupState` and emit the event.
+fn run(app: &tauri::AppHandle) -> Result<BackupCompleted, CommandError> {
+    let state = app.state::<BackupState>();
+    let config = Config::current().ok_or_else(not_configured)?;
+    let _running = match state.run_lock.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    };
+    state.update(|s| s.running = true);
+    let result = backup_into(&config.source, &config.target).map(|(path, files)| {
+        let removed = rotate(Path::new(&config.target), config.keep);
+        BackupCompleted {
+            bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            path: path.to_string_lossy().to_string(),
+            files,
+            removed,
+        }
+    });
+    state.update(|s| {
+        s.running = false;
+        s.last_run = millis(SystemTime::now());
+        match &result {
+            Ok(done) => {
+                s.last_archive = Some(done.path.clone());
+                s.last_error = None;
+            }
+            Err(e) => s.last_error = Some(e.clone()),
+        }
+    });
+    match &result {
+        Ok(done) => {
+            let _ = app.emit(COMPLETED_EVENT, done);
+        }
+        Err(e) => {
+            log::warn!("Backup failed: {}", e);
+            let _ = app.emit(FAILED_EVENT, e);
+        }
+    }
+    result
+}
+
+async fn run_blocking(app: &tauri::AppHandle) -> Result<BackupCompleted, CommandError> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || run(&app))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Start the backup scheduler. Called once from the setup hook, after
+/// settings are loaded.
+pub fn start_scheduler(app: &tauri::AppHandle) {
+    // Re-plan as soon as a backup setting changes
+    let handle = app.clone();
+    app.listen(settings::CHANGED_EVENT, move |event| {
+        let key = serde_json::from_str::<serde_json::Value>(event.payload())
+            .ok()
+            .and_then(|v| v["key"].as_str().map(String::from));
+        if key.is_some_and(|k| k.starts_with("backup") || k == "maxBackups") {
+            handle.state::<BackupState>().wake.notify_one();
+        }
+    });
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<BackupState>();
+        loop {
+            let config = Config::current();
+            let now = SystemTime::now();
+            let wait = match config.as_ref().and_then(|c| c.interval.map(|i| (c, i))) {
+                None => None,
+                Some((config, interval)) => {
+                    let last = list_archives(Path::new(&config.target))
+                        .first()
+                        .map(|(_, mtime)| *mtime);
+                    match time_until_due(last, interval, now) {
+                        Some(wait) => Some(wait),
+                        None => match run_blocking(&app).await {
+                            Ok(_) => continue,
+                            Err(_) => Some(RETRY_AFTER_FAILURE),
+                        },
+                    }
+                }
+            };
+            state.update(|s| s.next_scheduled = wait.and_then(|w| millis(now + w)));
+            let sleep = wait.map_or(MAX_SLEEP, |w| w.min(MAX_SLEEP));
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = state.wake.notified() => {}
+            }
+        }
+    });
+}
+
+/// Zip `source` into a new timestamped archive in `target_dir`. Returns the
+/// archive path.
+#[tauri::command]
+pub async fn backup_workspace(source: String, target_dir: String) -> Result<String, CommandError> {
+    tauri::async_runtime::spawn_blocking(move || backup_into(&source, &target_dir))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+        .map(|(path, _)| path.to_string_lossy().to_string())
+}
+
+/// Back up the configured vault now, outside the schedule.
+#[tauri::command]
+pub async fn run_backup_now(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, BackupState>,
+) -> Result<BackupCompleted, CommandError> {
+    let result = run_blocking(&app).await;
+    // The next scheduled run counts from this one
+    state.wake.notify_one();
+    result
+}
+
+#[tauri::command]
+pub fn get_backup_status(state: tauri::State<'_, BackupState>) -> BackupStatus {
+    state.snapshot()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+    use std::io::Read;
+
+    #[test]
+    fn should_archive_the_vault_without_the_backup_folder() {
+        let vault = TempDir::new("backup-vault");
+        fs::create_dir_all(vault.join("notes/sub")).unwrap();
+        fs::create_dir_all(vault.join(".git")).unwrap();
+        fs::write(vault.join("index.md"), "# Home").unwrap();
+        fs::write(vault.join("notes/sub/笔记.md"), "text").unwrap();
+        fs::write(vault.join(".git/HEAD"), "ref").unwrap();
+        let target = vault.join("backups");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("moraya-backup-old.zip"), "old").unwrap();
+
+        let dest = archive_path(&target, chrono::NaiveDateTime::default());
+        assert!(dest.ends_with("moraya-backup-19700101-000000.zip"));
+        assert_eq!(write_archive(&vault, &dest).unwrap(), 2);
+        let mut archive = zip::ZipArchive::new(fs::File::open(&dest).unwrap()).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names.len(), 2);
+        let mut text = String::new();
+        archive
+            .by_name("notes/sub/笔记.md")
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "text");
+        assert!(!target
+            .join("moraya-backup-19700101-000000.zip.part")
+            .exists());
+    }
+
+    #[test]
+    fn should_keep_only_the_newest_archives() {
+        let dir = TempDir::new("backup-rotate");
+        for i in 0..4 {
+            let path = dir.join(format!("moraya-backup-{}.zip", i));
+            fs::write(&path, "zip").unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(UNIX_EPOCH + Duration::from_secs(1_000 + i))
+                .unwrap();
+        }
+        fs::write(dir.join("notes.zip"), "not a backup").unwrap();
+        assert_eq!(rotate(&dir, 2), 2);
+        let left: Vec<String> = list_archives(&dir)
+            .iter()
+            .map(|(p, _)| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(left, ["moraya-backup-3.zip", "moraya-backup-2.zip"]);
+        assert!(dir.join("notes.zip").exists());
+    }
+
+    #[test]
+    fn should_schedule_from_the_newest_backup() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = UNIX_EPOCH + day * 100;
+        let hour = Duration::from_secs(60 * 60);
+        // Never backed up
+        assert_eq!(time_until_due(None, day, now), None);
+        // Backed up 3 hours ago
+        assert_eq!(
+            time_until_due(Some(now - hour * 3), day, now),
+            Some(hour * 21)
+        );
+        // Woke up after two days asleep
+        assert_eq!(time_until_due(Some(now - day * 2), day, now), None);
+        // Clock moved back
+        assert_eq!(time_until_due(Some(now + hour), day, now), Some(day));
+    }
+}
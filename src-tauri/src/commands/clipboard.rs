@@ -0,0 +1,16 @@
+//! Clipboard access from Rust, so copying works before (or without) the
+//! WebView being granted clipboard permission.
+
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use super::error::{CommandError, ErrorCode};
+
+/// Put `text` on the system clipboard.
+#[tauri::command]
+pub fn copy_to_clipboard(app: AppHandle, text: String) -> Result<(), CommandError> {
+    app.clipboard().write_text(text).map_err(|e| {
+        log::warn!("Clipboard write failed: {}", e);
+        CommandError::new(ErrorCode::Internal, "Could not write to the clipboard")
+    })
+}
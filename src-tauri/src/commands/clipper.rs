@@ -0,0 +1,556 @@
+//! Web clipper companion: an opt-in HTTP listener on 127.0.0.1 that takes
+//! clippings from a browser extension and files them into the clippings
+//! note or folder chosen in settings (`clippingsFile` / `clippingsFolder`).
+//!
+//! The extension sends `POST /clip` with `Authorization: Bearer <token>`
+//! and a JSON body `{ title, url, html | markdown }`.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::Emitter;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+use super::encryption::is_encrypted_file;
+use super::error::{CommandError, ErrorCode};
+use super::file::{create_markdown_file, sanitize_io_error, validate_path, write_file};
+use super::html_markdown;
+use super::settings;
+
+pub const RECEIVED_EVENT: &str = "clipper:received";
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+const MAX_BODY_BYTES: usize = 5 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const MIN_TOKEN_CHARS: usize = 16;
+const MAX_TITLE_CHARS: usize = 120;
+
+#[derive(Debug, Deserialize)]
+struct Clipping {
+    title: Option<String>,
+    url: Option<String>,
+    html: Option<String>,
+    markdown: Option<String>,
+}
+
+/// Payload of [`RECEIVED_EVENT`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipReceived {
+    pub title: String,
+    pub url: Option<String>,
+    /// File the clipping was written to.
+    pub path: String,
+}
+
+struct Listener {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+pub struct ClipperState(Mutex<Option<Listener>>);
+
+impl ClipperState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the running listener, if any. Returns whether one was running.
+    fn stop(&self) -> bool {
+        let listener = match self.0.lock() {
+            Ok(mut g) => g.take(),
+            Err(e) => e.into_inner().take(),
+        };
+        match listener {
+            Some(listener) => {
+                let _ = listener.shutdown.send(());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// What a request was refused with.
+type Rejection = (u16, &'static str);
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Request line and headers (lowercased names) of an HTTP/1.1 request head;
+/// the body is read separately.
+fn parse_head(head: &str) -> Option<Request> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    Some(Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    })
+}
+
+/// Compare digests so the time taken doesn't reveal how much matched.
+fn token_matches(given: &str, expected: &str) -> bool {
+    let (a, b) = (Sha256::digest(given), Sha256::digest(expected));
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, Rejection> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let head_end = loop {
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Err((431, "Request headers too large"));
+        }
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|_| (400, "Bad request"))?;
+        if n == 0 {
+            return Err((400, "Bad request"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = std::str::from_utf8(&buf[..head_end]).map_err(|_| (400, "Bad request"))?;
+    let mut request = parse_head(head).ok_or((400, "Bad request"))?;
+    request.body = buf[head_end + 4..].to_vec();
+    let length = match request.header("content-length") {
+        Some(v) => v
+            .parse::<usize>()
+            .map_err(|_| (400, "Bad Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err((413, "Clipping too large"));
+    }
+    while request.body.len() < length {
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|_| (400, "Bad request"))?;
+        if n == 0 {
+            return Err((400, "Incomplete body"));
+        }
+        request.body.extend_from_slice(&chunk[..n]);
+    }
+    request.body.truncate(length);
+    Ok(request)
+}
+
+/// Check method, path and token, and parse the clipping.
+fn accept(request: &Request, token: &str) -> Result<Clipping, Rejection> {
+    if request.path.split('?').next() != Some("/clip") {
+        return Err((404, "Not found"));
+    }
+    if request.method != "POST" {
+        return Err((405, "Use POST"));
+    }
+    let given = request
+        .header("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    if !token_matches(given, token) {
+        return Err((401, "Invalid token"));
+    }
+    let clipping: Clipping =
+        serde_json::from_slice(&request.body).map_err(|_| (400, "Invalid JSON"))?;
+    let has_content = [&clipping.html, &clipping.markdown]
+        .iter()
+        .any(|c| c.as_deref().is_some_and(|c| !c.trim().is_empty()));
+    if !has_content {
+        return Err((400, "Missing html or markdown"));
+    }
+    Ok(clipping)
+}
+
+fn clipping_title(clipping: &Clipping) -> String {
+    let title = clipping
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .or(clipping.url.as_deref())
+        .unwrap_or("Clipping");
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_TITLE_CHARS)
+        .collect()
+}
+
+/// The clipping as Markdown, below a heading added by the caller.
+fn clipping_body(clipping: &Clipping, now: chrono::NaiveDateTime) -> String {
+    let body = match &clipping.markdown {
+        Some(md) if !md.trim().is_empty() => md.trim().to_string(),
+        _ => html_markdown::convert(
+            clipping.html.as_deref().unwrap_or(""),
+            clipping.url.as_deref(),
+        )
+        .trim()
+        .to_string(),
+    };
+    let clipped = now.format("%Y-%m-%d %H:%M");
+    match &clipping.url {
+        Some(url) => format!("> Source: <{}> · {}\n\n{}\n", url, clipped, body),
+        None => format!("> Clipped {}\n\n{}\n", clipped, body),
+    }
+}
+
+/// A file name for `title`, without characters file systems reject.
+pub(crate) fn note_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_matches('.').trim();
+    if name.is_empty() {
+        "Clipping".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// File the clipping with the validated file commands. Returns the path.
+fn save(clipping: &Clipping) -> Result<String, CommandError> {
+    let title = clipping_title(clipping);
+    let body = clipping_body(clipping, chrono::Local::now().naive_local());
+
+    if let Some(file) = settings::clippings_file() {
+        let path = validate_path(&file)?;
+        if is_encrypted_file(&path) {
+            return Err(CommandError::new(
+                ErrorCode::Encrypted,
+                "Clippings can't be added to an encrypted document",
+            ));
+        }
+        let mut content = match std::fs::read_to_string(&path) {
+            Ok(existing) => existing.trim_end().to_string(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(sanitize_io_error(e)),
+        };
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        content.push_str(&format!("## {}\n\n{}", title, body));
+        write_file(file, content, None)?;
+        return Ok(path.to_string_lossy().to_string());
+    }
+
+    if let Some(dir) = settings::clippings_folder() {
+        let name = note_name(&title);
+        for n in 1..=100 {
+            let file_name = match n {
+                1 => name.clone(),
+                _ => format!("{} {}", name, n),
+            };
+            match create_markdown_file(dir.clone(), file_name, None) {
+                Ok(path) => {
+                    write_file(path.clone(), format!("# {}\n\n{}", title, body), None)?;
+                    return Ok(path);
+                }
+                Err(e) if e.code == ErrorCode::FileExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        return Err(CommandError::new(
+            ErrorCode::FileExists,
+            "Too many clippings with the same title",
+        ));
+    }
+
+    Err(not_configured())
+}
+
+fn not_configured() -> CommandError {
+    CommandError::new(
+        ErrorCode::ClipperNotConfigured,
+        "Choose a clippings file or folder first",
+    )
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: u16, body: serde_json::Value) {
+    let body = if status == 204 {
+        String::new()
+    } else {
+        body.to_string()
+    };
+    // Extensions call from their own origin; the token is what guards access
+    let response = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Authorization, Content-Type\r\n\
+         Access-Control-Allow-Private-Network: true\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn handle(app: tauri::AppHandle, mut stream: TcpStream, token: String) {
+    let request = match read_request(&mut stream).await {
+        Ok(request) => request,
+        Err((status, message)) => {
+            respond(&mut stream, status, serde_json::json!({ "error": message })).await;
+            return;
+        }
+    };
+    // CORS preflight: browsers send it without credentials
+    if request.method == "OPTIONS" {
+        respond(&mut stream, 204, serde_json::Value::Null).await;
+        return;
+    }
+    let clipping = match accept(&request, &token) {
+        Ok(clipping) => clipping,
+        Err((status, message)) => {
+            respond(&mut stream, status, serde_json::json!({ "error": message })).await;
+            return;
+        }
+    };
+    let saved =
+        tauri::async_runtime::spawn_blocking(move || save(&clipping).map(|path| (clipping, path)))
+            .await;
+    match saved {
+        Ok(Ok((clipping, path))) => {
+            let _ = app.emit(
+                RECEIVED_EVENT,
+                ClipReceived {
+                    title: clipping_title(&clipping),
+                    url: clipping.url,
+                    path: path.clone(),
+                },
+            );
+            respond(&mut stream, 200, serde_json::json!({ "path": path })).await;
+        }
+        Ok(Err(e)) => {
+            respond(&mut stream, 500, serde_json::json!({ "error": e.message })).await;
+        }
+        Err(_) => {
+            respond(
+                &mut stream,
+                500,
+                serde_json::json!({ "error": "Internal error" }),
+            )
+            .await;
+        }
+    }
+}
+
+async fn serve(
+    app: tauri::AppHandle,
+    listener: TcpListener,
+    token: String,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => {
+                let Ok((stream, peer)) = accepted else { continue };
+                if !peer.ip().is_loopback() {
+                    continue;
+                }
+                let (app, token) = (app.clone(), token.clone());
+                tauri::async_runtime::spawn(async move {
+                    let _ = tokio::time::timeout(REQUEST_TIMEOUT, handle(app, stream, token)).await;
+                });
+            }
+        }
+    }
+    // Dropping the listener closes the socket
+}
+
+/// Start (or restart) the clipper listener on `127.0.0.1:port`; `port` 0
+/// picks a free one. Every request must carry `token`. Returns the port.
+#[tauri::command]
+pub async fn clipper_enable(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, ClipperState>,
+    port: u16,
+    token: String,
+) -> Result<u16, CommandError> {
+    if token.trim().chars().count() < MIN_TOKEN_CHARS {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "The token must have at least {} characters",
+                MIN_TOKEN_CHARS
+            ),
+        ));
+    }
+    if settings::clippings_file().is_none() && settings::clippings_folder().is_none() {
+        return Err(not_configured());
+    }
+    state.stop();
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    // Never reachable from other machines
+    if !addr.ip().is_loopback() {
+        return Err(CommandError::new(
+            ErrorCode::ClipperStartFailed,
+            "The clipper only listens on 127.0.0.1",
+        ));
+    }
+    let listener = TcpListener::bind(addr).await.map_err(|e| {
+        CommandError::new(
+            ErrorCode::ClipperStartFailed,
+            format!("Failed to listen on port {}: {}", port, e),
+        )
+    })?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| CommandError::new(ErrorCode::ClipperStartFailed, e.to_string()))?
+        .port();
+    let (shutdown, rx) = oneshot::channel();
+    tauri::async_runtime::spawn(serve(app, listener, token.trim().to_string(), rx));
+    if let Ok(mut running) = state.0.lock() {
+        *running = Some(Listener { port, shutdown });
+    }
+    Ok(port)
+}
+
+/// Shut the listener down. Returns whether it was running.
+#[tauri::command]
+pub fn clipper_disable(state: tauri::State<'_, ClipperState>) -> bool {
+    state.stop()
+}
+
+/// Port the listener is running on, if it is.
+#[tauri::command]
+pub fn clipper_status(state: tauri::State<'_, ClipperState>) -> Option<u16> {
+    state.0.lock().ok()?.as_ref().map(|l| l.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, auth: Option<&str>, body: &str) -> Request {
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: 127.0.0.1\r\n", method, path);
+        if let Some(auth) = auth {
+            head.push_str(&format!("Authorization: {}\r\n", auth));
+        }
+        let mut request = parse_head(head.trim_end()).unwrap();
+        request.body = body.as_bytes().to_vec();
+        request
+    }
+
+    #[test]
+    fn should_require_the_token_and_a_clipping() {
+        let token = "0123456789abcdef";
+        let body = r#"{"title":"T","url":"https://example.com","html":"<p>x</p>"}"#;
+        let bearer = format!("Bearer {}", token);
+        assert!(accept(&request("POST", "/clip", Some(&bearer), body), token).is_ok());
+        assert_eq!(
+            accept(&request("POST", "/clip", None, body), token)
+                .unwrap_err()
+                .0,
+            401
+        );
+        assert_eq!(
+            accept(
+                &request("POST", "/clip", Some("Bearer 0123456789abcdeX"), body),
+                token
+            )
+            .unwrap_err()
+            .0,
+            401
+        );
+        assert_eq!(
+            accept(&request("GET", "/clip", Some(&bearer), ""), token)
+                .unwrap_err()
+                .0,
+            405
+        );
+        assert_eq!(
+            accept(&request("POST", "/other", Some(&bearer), body), token)
+                .unwrap_err()
+                .0,
+            404
+        );
+        assert_eq!(
+            accept(
+                &request("POST", "/clip", Some(&bearer), r#"{"title":"T"}"#),
+                token
+            )
+            .unwrap_err()
+            .0,
+            400
+        );
+    }
+
+    #[test]
+    fn should_format_clippings_as_markdown() {
+        let clipping = Clipping {
+            title: Some("  A   page\n".into()),
+            url: Some("https://example.com/a/b".into()),
+            html: Some(r#"<p>Hello <a href="../c">there</a></p>"#.into()),
+            markdown: None,
+        };
+        let now = chrono::NaiveDate::from_ymd_opt(2026, 3, 1)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        assert_eq!(clipping_title(&clipping), "A page");
+        assert_eq!(
+            clipping_body(&clipping, now),
+            "> Source: <https://example.com/a/b> · 2026-03-01 09:30\n\n\
+             Hello [there](https://example.com/c)\n"
+        );
+        assert_eq!(note_name("What? A/B: \"test\"."), "What- A-B- -test-");
+        assert_eq!(note_name("..."), "Clipping");
+    }
+}
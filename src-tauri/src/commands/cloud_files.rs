@@ -0,0 +1,252 @@
+//! Cloud placeholder files: iCloud Drive documents evicted by "Optimize Mac
+//! Storage" (dataless files) and OneDrive/Dropbox "online-only" files. Their
+//! content isn't on disk until the sync client downloads it, and reading one
+//! either blocks until it arrives or fails.
+//!
+//! `read_file` asks for the download, waits briefly while emitting
+//! `file:download-progress`, and fails with `FILE_DOWNLOADING` when the
+//! download takes longer, so the UI can retry instead of showing an empty
+//! note. Previews skip placeholders.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::Emitter;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{sanitize_io_error, validate_path};
+
+pub const DOWNLOAD_EVENT: &str = "file:download-progress";
+/// How long `read_file` waits for a download before giving up.
+const WAIT_FOR_DOWNLOAD: Duration = Duration::from_secs(3);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Suggested delay before retrying a read that failed with `FILE_DOWNLOADING`.
+const RETRY_AFTER_MS: u64 = 2000;
+
+/// macOS `SF_DATALESS` (`sys/stat.h`): content lives in iCloud.
+const SF_DATALESS: u32 = 0x4000_0000;
+/// Windows attributes of cloud-files placeholders (`winnt.h`).
+const FILE_ATTRIBUTE_OFFLINE: u32 = 0x0000_1000;
+const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x0004_0000;
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+/// Placeholders with a download in flight, so retries don't start another.
+static DOWNLOADING: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileAvailability {
+    /// Content is on disk.
+    Local,
+    /// Content is in the cloud; reading it triggers a download.
+    Placeholder,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress {
+    path: String,
+    elapsed_ms: u64,
+    done: bool,
+}
+
+/// Keep the app handle for progress events. Called from the setup hook.
+pub fn init(app: &tauri::AppHandle) {
+    let _ = APP.set(app.clone());
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn is_dataless(st_flags: u32) -> bool {
+    st_flags & SF_DATALESS != 0
+}
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn is_recall_placeholder(attributes: u32) -> bool {
+    attributes
+        & (FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS
+            | FILE_ATTRIBUTE_RECALL_ON_OPEN
+            | FILE_ATTRIBUTE_OFFLINE)
+        != 0
+}
+
+/// Whether `meta` describes a file whose content isn't on disk. Reading
+/// metadata never triggers a download.
+fn is_placeholder(_meta: &fs::Metadata) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+        is_dataless(_meta.st_flags())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::fs::MetadataExt;
+        is_recall_placeholder(_meta.file_attributes())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    false
+}
+
+/// Whether the content of `path` can be read without a download.
+pub(crate) fn file_availability(path: &Path) -> FileAvailability {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_file() && is_placeholder(&meta) => FileAvailability::Placeholder,
+        Ok(_) => FileAvailability::Local,
+        Err(_) => FileAvailability::Missing,
+    }
+}
+
+/// Ask iCloud to download an evicted item. Reading it would do the same,
+/// but only for the bytes read and while blocking the reader.
+#[cfg(target_os = "macos")]
+fn request_ubiquitous_download(path: &Path) {
+    use objc::runtime::{Object, BOOL};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    let Ok(c_path) = std::ffi::CString::new(path.to_string_lossy().as_bytes()) else {
+        return;
+    };
+    objc::rc::autoreleasepool(|| unsafe {
+        // SAFETY: stringWithUTF8String: returns an autoreleased NSString, or nil
+        let ns_path: *mut Object =
+            msg_send![class!(NSString), stringWithUTF8String: c_path.as_ptr()];
+        if ns_path.is_null() {
+            return;
+        }
+        // SAFETY: both return autoreleased or shared objects, or nil
+        let url: *mut Object = msg_send![class!(NSURL), fileURLWithPath: ns_path];
+        let manager: *mut Object = msg_send![class!(NSFileManager), defaultManager];
+        if url.is_null() || manager.is_null() {
+            return;
+        }
+        let mut error: *mut Object = std::ptr::null_mut();
+        // SAFETY: the error out-parameter receives an autoreleased NSError
+        let started: BOOL =
+            msg_send![manager, startDownloadingUbiquitousItemAtURL: url error: &mut error];
+        if started == objc::runtime::NO {
+            log::warn!("iCloud refused to download a placeholder file");
+        }
+    });
+}
+
+/// Download `path` in the background: read it to the end, which makes the
+/// sync client fetch the content.
+fn start_download(path: &Path) {
+    {
+        let mut downloading = match DOWNLOADING.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        };
+        if !downloading
+            .get_or_insert_with(HashSet::new)
+            .insert(path.to_path_buf())
+        {
+            return;
+        }
+    }
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        #[cfg(target_os = "macos")]
+        request_ubiquitous_download(&path);
+        if let Ok(mut file) = fs::File::open(&path) {
+            let _ = std::io::copy(&mut file, &mut std::io::sink());
+        }
+        let mut downloading = match DOWNLOADING.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        };
+        if let Some(set) = downloading.as_mut() {
+            set.remove(&path);
+        }
+    });
+}
+
+fn emit_progress(path: &Path, started: Instant, done: bool) {
+    if let Some(app) = APP.get() {
+        let _ = app.emit(
+            DOWNLOAD_EVENT,
+            DownloadProgress {
+                path: path.to_string_lossy().to_string(),
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                done,
+            },
+        );
+    }
+}
+
+fn downloading_error(path: &Path) -> CommandError {
+    CommandError::new(
+        ErrorCode::FileDownloading,
+        "The file is still downloading from the cloud",
+    )
+    .with_details(serde_json::json!({
+        "path": path.to_string_lossy(),
+        "retryAfterMs": RETRY_AFTER_MS,
+    }))
+}
+
+/// Make sure the content of `path` is on disk before reading it. Starts the
+/// download of a placeholder and waits up to `WAIT_FOR_DOWNLOAD`; after
+/// that fails with `FILE_DOWNLOADING` while the download continues.
+pub(crate) fn ensure_local(path: &Path) -> Result<(), CommandError> {
+    if file_availability(path) != FileAvailability::Placeholder {
+        return Ok(());
+    }
+    start_download(path);
+    let started = Instant::now();
+    loop {
+        emit_progress(path, started, false);
+        std::thread::sleep(POLL_INTERVAL);
+        match fs::metadata(path) {
+            Ok(meta) if !is_placeholder(&meta) => {
+                emit_progress(path, started, true);
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) => return Err(sanitize_io_error(e)),
+        }
+        if started.elapsed() >= WAIT_FOR_DOWNLOAD {
+            return Err(downloading_error(path));
+        }
+    }
+}
+
+/// Whether `path` is on disk, a cloud placeholder, or missing.
+#[tauri::command]
+pub fn get_file_availability(path: String) -> Result<FileAvailability, CommandError> {
+    let safe_path = validate_path(&path)?;
+    Ok(file_availability(&safe_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_recognize_placeholder_flags() {
+        // UF_COMPRESSED | SF_DATALESS
+        assert!(is_dataless(0x0000_0020 | SF_DATALESS));
+        assert!(!is_dataless(0x0000_0020));
+        // OneDrive online-only: ARCHIVE | RECALL_ON_DATA_ACCESS | unpinned
+        assert!(is_recall_placeholder(
+            0x0000_0020 | 0x0040_0000 | 0x0010_0000
+        ));
+        // Locally available, pinned
+        assert!(!is_recall_placeholder(0x0000_0020 | 0x0008_0000));
+    }
+
+    #[test]
+    fn should_treat_ordinary_files_as_local() {
+        let path = std::env::temp_dir().join(format!("moraya-cloud-{}.md", std::process::id()));
+        fs::write(&path, "# Note").unwrap();
+        assert_eq!(file_availability(&path), FileAvailability::Local);
+        assert!(ensure_local(&path).is_ok());
+        let _ = fs::remove_file(&path);
+        assert_eq!(file_availability(&path), FileAvailability::Missing);
+    }
+}
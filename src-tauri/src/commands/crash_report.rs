@@ -0,0 +1,228 @@
+//! Crash reports: a panic hook that writes the message, backtrace, app
+//! version, platform and recent log lines to
+//! `app_data_dir()/crashes/crash-<timestamp>.txt`, plus commands to list
+//! and read them on the next launch.
+//!
+//! Panics inside async commands are caught by tokio and only fail that
+//! task, but the hook still runs first, so they are recorded too.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::Manager;
+
+const REPORT_PREFIX: &str = "crash-";
+const REPORT_EXT: &str = ".txt";
+/// Log lines appended to each report.
+const REPORT_LOG_LINES: usize = 200;
+/// Older reports are pruned once there are more than this many.
+const MAX_REPORTS: usize = 20;
+
+static CRASH_DIR: OnceLock<PathBuf> = OnceLock::new();
+static APP_VERSION: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReportInfo {
+    pub name: String,
+    pub size: u64,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: i64,
+}
+
+/// Install the panic hook. Called first thing in `run()` so early panics
+/// are caught too; reports are only written once `init` has resolved the
+/// crash directory.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(dir) = CRASH_DIR.get() {
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(s) => s.to_string(),
+                None => info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_else(|| "Box<dyn Any>".to_string()),
+            };
+            let location = info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+                .unwrap_or_else(|| "unknown".to_string());
+            let report = build_report(
+                &chrono::Local::now().to_rfc3339(),
+                std::thread::current().name().unwrap_or("unnamed"),
+                &location,
+                &message,
+                &std::backtrace::Backtrace::force_capture().to_string(),
+                &super::app_log::recent_lines(REPORT_LOG_LINES),
+            );
+            let _ = write_report(dir, &report);
+        }
+        previous(info);
+    }));
+}
+
+/// Resolve the crash directory and app version. Called from the setup hook.
+pub fn init(app: &tauri::AppHandle) {
+    let _ = APP_VERSION.set(app.package_info().version.to_string());
+    if let Ok(dir) = app.path().app_data_dir() {
+        let _ = CRASH_DIR.set(dir.join("crashes"));
+    }
+}
+
+fn build_report(
+    time: &str,
+    thread: &str,
+    location: &str,
+    message: &str,
+    backtrace: &str,
+    log_lines: &[String],
+) -> String {
+    let platform = super::update::get_platform_info();
+    let install_kind = platform
+        .install_kind
+        .map(|k| format!(" ({:?})", k))
+        .unwrap_or_default();
+    format!(
+        "Moraya crash report\n\
+         Time: {}\n\
+         Version: {}\n\
+         Platform: {}/{}{}\n\
+         Thread: {}\n\
+         Location: {}\n\
+         Message: {}\n\
+         \n\
+         Backtrace:\n{}\n\
+         \n\
+         Recent log ({} lines):\n{}\n",
+        time,
+        APP_VERSION.get().map(String::as_str).unwrap_or("unknown"),
+        platform.os,
+        platform.arch,
+        install_kind,
+        thread,
+        location,
+        super::app_log::redact(message),
+        backtrace,
+        log_lines.len(),
+        log_lines.join("\n"),
+    )
+}
+
+fn write_report(dir: &Path, report: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let path = dir.join(format!("{}{}{}", REPORT_PREFIX, stamp, REPORT_EXT));
+    std::fs::write(&path, report)?;
+    prune_reports(dir);
+    Ok(())
+}
+
+fn is_report_name(name: &str) -> bool {
+    name.starts_with(REPORT_PREFIX)
+        && name.ends_with(REPORT_EXT)
+        && !name.contains(['/', '\\'])
+        && !name.contains("..")
+}
+
+/// Reports in `dir`, newest first.
+fn list_reports(dir: &Path) -> Vec<CrashReportInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<CrashReportInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !is_report_name(&name) {
+                return None;
+            }
+            let meta = entry.metadata().ok()?;
+            let created_at = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
+            Some(CrashReportInfo {
+                name,
+                size: meta.len(),
+                created_at,
+            })
+        })
+        .collect();
+    // Names embed a sortable timestamp
+    reports.sort_by(|a, b| b.name.cmp(&a.name));
+    reports
+}
+
+fn prune_reports(dir: &Path) {
+    for old in list_reports(dir).iter().skip(MAX_REPORTS) {
+        let _ = std::fs::remove_file(dir.join(&old.name));
+    }
+}
+
+#[tauri::command]
+pub fn list_crash_reports() -> Result<Vec<CrashReportInfo>, String> {
+    Ok(CRASH_DIR.get().map(|d| list_reports(d)).unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn read_crash_report(name: String) -> Result<String, String> {
+    if !is_report_name(&name) {
+        return Err("Invalid crash report name".to_string());
+    }
+    let dir = CRASH_DIR.get().ok_or("Crash reports are not initialized")?;
+    std::fs::read_to_string(dir.join(&name))
+        .map_err(|e| format!("Failed to read crash report: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_accept_only_plain_report_names() {
+        assert!(is_report_name("crash-20260101-120000.123.txt"));
+        assert!(!is_report_name("../crash-1.txt"));
+        assert!(!is_report_name("crash-1/../../etc.txt"));
+        assert!(!is_report_name("moraya.log"));
+    }
+
+    #[test]
+    fn should_build_report_with_context() {
+        let report = build_report(
+            "2026-01-01T00:00:00+00:00",
+            "tokio-runtime-worker",
+            "src/commands/ai_proxy.rs:10:5",
+            "bad key sk-abcdefghijklmnopqrst",
+            "0: main",
+            &["line one".to_string(), "line two".to_string()],
+        );
+        assert!(report.contains("Thread: tokio-runtime-worker"));
+        assert!(report.contains("Location: src/commands/ai_proxy.rs:10:5"));
+        assert!(report.contains("Message: bad key sk-[REDACTED]"));
+        assert!(report.contains(&format!("Platform: {}/", std::env::consts::OS)));
+        assert!(report.ends_with("Recent log (2 lines):\nline one\nline two\n"));
+    }
+
+    #[test]
+    fn should_list_newest_first_and_prune() {
+        let dir = std::env::temp_dir().join(format!("moraya-crash-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..MAX_REPORTS + 3 {
+            std::fs::write(dir.join(format!("crash-2026{:04}.txt", i)), "x").unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "x").unwrap();
+        prune_reports(&dir);
+        let reports = list_reports(&dir);
+        assert_eq!(reports.len(), MAX_REPORTS);
+        assert_eq!(
+            reports[0].name,
+            format!("crash-2026{:04}.txt", MAX_REPORTS + 2)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,241 @@
+//! Daily notes: one markdown file per day under a knowledge base, placed by
+//! the `dailyNotePattern` setting (default `{{year}}/{{month}}/{{date}}.md`).
+
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{Local, NaiveDate};
+use serde::Serialize;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{instantiate_template, sanitize_io_error, validate_path};
+use super::settings;
+
+/// `list_daily_notes` checks one path per day; keep ranges to a calendar's worth.
+const MAX_LIST_DAYS: i64 = 3660;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyNote {
+    pub path: String,
+    /// `false` when the note already existed.
+    pub created: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyNoteEntry {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub path: String,
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, CommandError> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| {
+        CommandError::new(
+            ErrorCode::InvalidArgument,
+            format!("Invalid date (expected YYYY-MM-DD): {}", date),
+        )
+    })
+}
+
+/// The note's path relative to the root for `date`.
+fn note_relative_path(pattern: &str, date: NaiveDate) -> Result<PathBuf, CommandError> {
+    let expanded = pattern
+        .replace("{{date}}", &date.format("%Y-%m-%d").to_string())
+        .replace("{{year}}", &date.format("%Y").to_string())
+        .replace("{{month}}", &date.format("%m").to_string())
+        .replace("{{day}}", &date.format("%d").to_string());
+    let mut rel = PathBuf::new();
+    for component in Path::new(&expanded).components() {
+        match component {
+            Component::Normal(part) => rel.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidPath,
+                    "Daily note pattern must stay inside the folder",
+                ))
+            }
+        }
+    }
+    if rel.as_os_str().is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidPath,
+            "Daily note pattern is empty",
+        ));
+    }
+    if rel.extension().is_none() {
+        rel.set_extension("md");
+    }
+    Ok(rel)
+}
+
+fn validate_root(root: &str) -> Result<PathBuf, CommandError> {
+    let root = validate_path(root)?;
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotADirectory,
+            "Not a directory",
+        ));
+    }
+    Ok(root)
+}
+
+/// Create (if missing) the note at `root/rel`, from `template` if given.
+fn open_note(
+    root: &Path,
+    rel: &Path,
+    date: NaiveDate,
+    template: Option<&str>,
+) -> Result<DailyNote, CommandError> {
+    let path = validate_path(&root.join(rel).to_string_lossy())?;
+    if !path.starts_with(root) {
+        return Err(CommandError::new(
+            ErrorCode::AccessDenied,
+            "Daily note must stay inside the folder",
+        ));
+    }
+    let note = |created| DailyNote {
+        path: path.to_string_lossy().to_string(),
+        created,
+    };
+    if path.exists() {
+        return Ok(note(false));
+    }
+
+    let content = match template {
+        Some(template) => {
+            let title = date.format("%Y-%m-%d").to_string();
+            let now = date.and_time(Local::now().time());
+            instantiate_template(template, now, &title)?
+        }
+        None => String::new(),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(sanitize_io_error)?;
+    }
+    // `create_new` so two windows racing on the same day don't clobber each other
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+    {
+        Ok(mut file) => {
+            file.write_all(content.as_bytes())
+                .map_err(sanitize_io_error)?;
+            Ok(note(true))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(note(false)),
+        Err(e) => Err(sanitize_io_error(e)),
+    }
+}
+
+/// Open the daily note for `date` (today when omitted), creating it and its
+/// folders if needed. `template_path` falls back to the `dailyNoteTemplate`
+/// setting.
+#[tauri::command]
+pub fn open_daily_note(
+    root: String,
+    date: Option<String>,
+    template_path: Option<String>,
+) -> Result<DailyNote, CommandError> {
+    let root = validate_root(&root)?;
+    let date = match date {
+        Some(d) => parse_date(&d)?,
+        None => Local::now().date_naive(),
+    };
+    let rel = note_relative_path(&settings::daily_note_pattern(), date)?;
+    let template = template_path.or_else(settings::daily_note_template);
+    open_note(&root, &rel, date, template.as_deref())
+}
+
+/// Existing daily notes between `from` and `to` (inclusive, `YYYY-MM-DD`).
+#[tauri::command]
+pub fn list_daily_notes(
+    root: String,
+    from: String,
+    to: String,
+) -> Result<Vec<DailyNoteEntry>, CommandError> {
+    let root = validate_root(&root)?;
+    list_notes(
+        &root,
+        &settings::daily_note_pattern(),
+        parse_date(&from)?,
+        parse_date(&to)?,
+    )
+}
+
+fn list_notes(
+    root: &Path,
+    pattern: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<DailyNoteEntry>, CommandError> {
+    if to < from || (to - from).num_days() > MAX_LIST_DAYS {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Invalid date range",
+        ));
+    }
+    Ok(from
+        .iter_days()
+        .take_while(|d| *d <= to)
+        .filter_map(|date| {
+            let path = root.join(note_relative_path(pattern, date).ok()?);
+            path.is_file().then(|| DailyNoteEntry {
+                date: date.format("%Y-%m-%d").to_string(),
+                path: path.to_string_lossy().to_string(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn should_expand_pattern_into_a_relative_path() {
+        assert_eq!(
+            note_relative_path("{{year}}/{{month}}/{{date}}.md", day("2026-03-07")).unwrap(),
+            PathBuf::from("2026/03/2026-03-07.md")
+        );
+        assert_eq!(
+            note_relative_path("journal/{{date}}", day("2026-03-07")).unwrap(),
+            PathBuf::from("journal/2026-03-07.md")
+        );
+        assert_eq!(
+            note_relative_path("../{{date}}.md", day("2026-03-07"))
+                .unwrap_err()
+                .code,
+            ErrorCode::InvalidPath
+        );
+        assert_eq!(
+            parse_date("07/03/2026").unwrap_err().code,
+            ErrorCode::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn should_list_existing_notes_in_range() {
+        let root = std::env::temp_dir().join(format!("moraya-daily-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let pattern = "{{year}}/{{date}}.md";
+        for date in ["2026-02-27", "2026-03-01", "2026-03-10"] {
+            let path = root.join(note_relative_path(pattern, day(date)).unwrap());
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let notes = list_notes(&root, pattern, day("2026-02-28"), day("2026-03-10")).unwrap();
+        let dates: Vec<&str> = notes.iter().map(|n| n.date.as_str()).collect();
+        assert_eq!(dates, vec!["2026-03-01", "2026-03-10"]);
+        assert!(list_notes(&root, pattern, day("2026-03-10"), day("2026-03-01")).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,364 @@
+//! Diagram rendering for exports. Fenced `mermaid` / `dot` blocks are turned
+//! into SVG or PNG by an installed renderer (`mmdc` from mermaid-cli,
+//! Graphviz `dot`), so exported files get images instead of raw code.
+//!
+//! Renderers are looked up on PATH by bare name through
+//! `check_command_exists`, never run through a shell, and killed once
+//! `RENDER_TIMEOUT` passes.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use super::error::{CommandError, ErrorCode};
+use super::mcp;
+
+const RENDER_TIMEOUT: Duration = Duration::from_secs(30);
+/// Diagram source accepted per call (256 KB).
+const MAX_SOURCE_BYTES: usize = 256 * 1024;
+/// Rendered image returned per call (16 MB).
+const MAX_OUTPUT_BYTES: usize = 16 * 1024 * 1024;
+/// Renderer stderr kept for the error details (16 KB).
+const MAX_STDERR_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramKind {
+    Mermaid,
+    #[serde(alias = "graphviz")]
+    Dot,
+}
+
+impl DiagramKind {
+    fn renderer(self) -> &'static str {
+        match self {
+            DiagramKind::Mermaid => "mmdc",
+            DiagramKind::Dot => "dot",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DiagramKind::Mermaid => "mermaid",
+            DiagramKind::Dot => "dot",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    Svg,
+    Png,
+}
+
+impl DiagramFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            DiagramFormat::Svg => "svg",
+            DiagramFormat::Png => "png",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            DiagramFormat::Svg => "image/svg+xml",
+            DiagramFormat::Png => "image/png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedDiagram {
+    pub format: DiagramFormat,
+    pub mime_type: String,
+    /// Base64-encoded image bytes.
+    pub data: String,
+}
+
+fn renderer_missing(kind: DiagramKind) -> CommandError {
+    CommandError::new(
+        ErrorCode::DiagramRendererMissing,
+        format!(
+            "{} is not installed; {} diagrams can't be rendered",
+            kind.renderer(),
+            kind.name()
+        ),
+    )
+    .with_details(serde_json::json!({
+        "renderer": kind.renderer(),
+        "kind": kind.name(),
+    }))
+}
+
+fn render_failed(message: impl Into<String>, stderr: &[u8]) -> CommandError {
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+    let e = CommandError::new(ErrorCode::DiagramRenderFailed, message);
+    if stderr.is_empty() {
+        e
+    } else {
+        e.with_details(serde_json::json!({ "stderr": stderr }))
+    }
+}
+
+fn too_large() -> CommandError {
+    CommandError::new(
+        ErrorCode::DiagramTooLarge,
+        "Rendered diagram exceeds the size limit",
+    )
+}
+
+/// Make sure the renderer for `kind` is installed, using the same name
+/// validation and lookup as MCP server commands.
+fn find_renderer(kind: DiagramKind) -> Result<&'static str, CommandError> {
+    let name = kind.renderer();
+    match mcp::check_command_exists(name.to_string()) {
+        Ok(_) => Ok(name),
+        Err(e) if e.code == ErrorCode::CommandNotFound => Err(renderer_missing(kind)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read a child pipe on its own thread so a chatty renderer can't stall on
+/// a full pipe. Keeps at most `cap + 1` bytes and drains the rest.
+fn drain(mut pipe: impl Read + Send + 'static, cap: usize) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = (&mut pipe).take(cap as u64 + 1).read_to_end(&mut buf);
+        let _ = std::io::copy(&mut pipe, &mut std::io::sink());
+        buf
+    })
+}
+
+/// Run `cmd` with `input` on stdin and return its stdout, killing it after
+/// `timeout`.
+fn run(mut cmd: Command, input: &[u8], timeout: Duration) -> Result<Vec<u8>, CommandError> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| render_failed(format!("Failed to start renderer: {}", e), &[]))?;
+
+    let stdin = child.stdin.take();
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(&input);
+        }
+    });
+    let stdout = child.stdout.take().map(|p| drain(p, MAX_OUTPUT_BYTES));
+    let stderr = child.stderr.take().map(|p| drain(p, MAX_STDERR_BYTES));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(CommandError::new(
+                    ErrorCode::DiagramTimeout,
+                    "Diagram renderer timed out",
+                ));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => return Err(render_failed(format!("Renderer wait failed: {}", e), &[])),
+        }
+    };
+    let _ = writer.join();
+    let stdout = stdout.and_then(|h| h.join().ok()).unwrap_or_default();
+    let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+
+    if !status.success() {
+        return Err(render_failed("Diagram renderer reported an error", &stderr));
+    }
+    if stdout.len() > MAX_OUTPUT_BYTES {
+        return Err(too_large());
+    }
+    Ok(stdout)
+}
+
+/// Scratch directory for renderers that only work on files (mmdc).
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Result<Self, CommandError> {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir =
+            std::env::temp_dir().join(format!("moraya-diagram-{}-{}", std::process::id(), nanos));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| render_failed(format!("Failed to create temp dir: {}", e), &[]))?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn render_mermaid(source: &str, format: DiagramFormat) -> Result<Vec<u8>, CommandError> {
+    let dir = TempDir::new()?;
+    let input = dir.0.join("diagram.mmd");
+    let output = dir.0.join(format!("diagram.{}", format.extension()));
+    std::fs::write(&input, source)
+        .map_err(|e| render_failed(format!("Failed to write diagram source: {}", e), &[]))?;
+
+    let mut cmd = Command::new(DiagramKind::Mermaid.renderer());
+    cmd.arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["-b", "transparent"]);
+    if format == DiagramFormat::Png {
+        // Crisp at print resolution
+        cmd.args(["-s", "2"]);
+    }
+    run(cmd, &[], RENDER_TIMEOUT)?;
+
+    let size = std::fs::metadata(&output)
+        .map_err(|_| render_failed("Diagram renderer produced no output", &[]))?
+        .len();
+    if size > MAX_OUTPUT_BYTES as u64 {
+        return Err(too_large());
+    }
+    std::fs::read(&output)
+        .map_err(|e| render_failed(format!("Failed to read rendered diagram: {}", e), &[]))
+}
+
+fn render_dot(source: &str, format: DiagramFormat) -> Result<Vec<u8>, CommandError> {
+    let mut cmd = Command::new(DiagramKind::Dot.renderer());
+    cmd.arg(format!("-T{}", format.extension()));
+    if format == DiagramFormat::Png {
+        cmd.arg("-Gdpi=192");
+    }
+    run(cmd, source.as_bytes(), RENDER_TIMEOUT)
+}
+
+fn validate_source(source: &str) -> Result<(), CommandError> {
+    if source.trim().is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Diagram source is empty",
+        ));
+    }
+    if source.len() > MAX_SOURCE_BYTES {
+        return Err(CommandError::new(
+            ErrorCode::DiagramTooLarge,
+            "Diagram source exceeds the size limit",
+        ));
+    }
+    Ok(())
+}
+
+fn render(
+    kind: DiagramKind,
+    source: &str,
+    format: DiagramFormat,
+) -> Result<RenderedDiagram, CommandError> {
+    validate_source(source)?;
+    find_renderer(kind)?;
+    let bytes = match kind {
+        DiagramKind::Mermaid => render_mermaid(source, format)?,
+        DiagramKind::Dot => render_dot(source, format)?,
+    };
+    if bytes.is_empty() {
+        return Err(render_failed("Diagram renderer produced no output", &[]));
+    }
+    Ok(RenderedDiagram {
+        format,
+        mime_type: format.mime_type().to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+    })
+}
+
+/// Render a mermaid or Graphviz diagram to SVG/PNG with the installed
+/// renderer. Fails with `DIAGRAM_RENDERER_MISSING` when it isn't installed.
+#[tauri::command]
+pub async fn render_diagram(
+    kind: DiagramKind,
+    source: String,
+    format: DiagramFormat,
+) -> Result<RenderedDiagram, CommandError> {
+    tokio::task::spawn_blocking(move || render(kind, &source, format))
+        .await
+        .map_err(|e| {
+            CommandError::new(
+                ErrorCode::Internal,
+                format!("Diagram render task failed: {}", e),
+            )
+        })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_accept_graphviz_alias_for_dot() {
+        let kind: DiagramKind = serde_json::from_str("\"graphviz\"").unwrap();
+        assert_eq!(kind, DiagramKind::Dot);
+        let kind: DiagramKind = serde_json::from_str("\"mermaid\"").unwrap();
+        assert_eq!(kind.renderer(), "mmdc");
+    }
+
+    #[test]
+    fn should_reject_empty_and_oversized_source() {
+        assert_eq!(
+            validate_source("  \n").unwrap_err().code,
+            ErrorCode::InvalidArgument
+        );
+        let big = "a".repeat(MAX_SOURCE_BYTES + 1);
+        assert_eq!(
+            validate_source(&big).unwrap_err().code,
+            ErrorCode::DiagramTooLarge
+        );
+        assert!(validate_source("graph TD; A-->B").is_ok());
+    }
+
+    #[test]
+    fn should_describe_missing_renderer() {
+        let e = renderer_missing(DiagramKind::Mermaid);
+        assert_eq!(e.code, ErrorCode::DiagramRendererMissing);
+        let details = e.details.unwrap();
+        assert_eq!(details["renderer"], "mmdc");
+        assert_eq!(details["kind"], "mermaid");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_pipe_source_through_renderer() {
+        let out = run(Command::new("cat"), b"digraph {}", Duration::from_secs(5)).unwrap();
+        assert_eq!(out, b"digraph {}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_kill_renderer_after_timeout() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+        let e = run(cmd, &[], Duration::from_millis(100)).unwrap_err();
+        assert_eq!(e.code, ErrorCode::DiagramTimeout);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_report_renderer_errors_with_stderr() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo 'syntax error' >&2; exit 1"]);
+        let e = run(cmd, &[], Duration::from_secs(5)).unwrap_err();
+        assert_eq!(e.code, ErrorCode::DiagramRenderFailed);
+        assert_eq!(e.details.unwrap()["stderr"], "syntax error");
+    }
+}
@@ -0,0 +1,221 @@
+//! `read_dir_recursive` for trees that may be huge, such as a home folder
+//! opened by mistake. The walk runs on a blocking task, so the IPC thread
+//! stays free. Every ~200 ms it reports `dirscan:progress`, and it stops
+//! with an error on `cancel_dir_scan` or after `dirScanMaxEntries` entries.
+//!
+//! The synchronous `read_dir_recursive` stays for shallow reads, like one
+//! level of a templates folder.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::json;
+use tauri::Emitter;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{validate_path, walk_dir, FileEntry, Walked};
+use super::settings;
+
+pub const PROGRESS_EVENT: &str = "dirscan:progress";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Payload of `dirscan:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirScanProgress {
+    pub scan_id: String,
+    pub directories: usize,
+    pub entries: usize,
+}
+
+pub struct DirScanState {
+    /// Cancel flags of the scans in flight, by scan id.
+    scans: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl DirScanState {
+    pub fn new() -> Self {
+        Self {
+            scans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_scans<T>(&self, f: impl FnOnce(&mut HashMap<String, Arc<AtomicBool>>) -> T) -> T {
+        match self.scans.lock() {
+            Ok(mut g) => f(&mut g),
+            Err(e) => f(&mut e.into_inner()),
+        }
+    }
+}
+
+impl Default for DirScanState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts a walk, reports it every `PROGRESS_INTERVAL` and stops it when
+/// cancelled or past the ceiling.
+struct Scan<'a> {
+    cancel: &'a AtomicBool,
+    max_entries: usize,
+    directories: usize,
+    entries: usize,
+    last_report: Instant,
+}
+
+impl Scan<'_> {
+    fn observe(
+        &mut self,
+        walked: Walked,
+        report: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), CommandError> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(CommandError::new(
+                ErrorCode::DirScanCancelled,
+                "Folder scan cancelled",
+            ));
+        }
+        match walked {
+            Walked::Dir => self.directories += 1,
+            Walked::Entry => self.entries += 1,
+        }
+        if self.entries > self.max_entries {
+            return Err(CommandError::new(
+                ErrorCode::TooManyEntries,
+                "Too many entries, narrow the folder",
+            )
+            .with_details(json!({ "limit": self.max_entries })));
+        }
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            report(self.directories, self.entries);
+        }
+        Ok(())
+    }
+}
+
+/// Read the tree under `path` like `read_dir_recursive`, off the IPC
+/// thread. `scan_id` names the scan in progress events and for
+/// `cancel_dir_scan`, and must not be in use by another scan.
+#[tauri::command]
+pub async fn scan_dir_recursive(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, DirScanState>,
+    scan_id: String,
+    path: String,
+    depth: Option<u32>,
+    all_files: Option<bool>,
+    include_meta: Option<bool>,
+) -> Result<Vec<FileEntry>, CommandError> {
+    let safe_path = validate_path(&path)?;
+    let cancel = Arc::new(AtomicBool::new(false));
+    let registered = state.with_scans(|scans| match scans.entry(scan_id.clone()) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(slot) => {
+            slot.insert(cancel.clone());
+            true
+        }
+    });
+    if !registered {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "A scan with this id is already running",
+        ));
+    }
+
+    let id = scan_id.clone();
+    let max_entries = settings::dir_scan_max_entries() as usize;
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut scan = Scan {
+            cancel: &cancel,
+            max_entries,
+            directories: 0,
+            entries: 0,
+            last_report: Instant::now(),
+        };
+        let mut report = |directories: usize, entries: usize| {
+            let _ = app.emit(
+                PROGRESS_EVENT,
+                DirScanProgress {
+                    scan_id: id.clone(),
+                    directories,
+                    entries,
+                },
+            );
+        };
+        walk_dir(
+            &safe_path,
+            depth,
+            all_files.unwrap_or(false),
+            include_meta.unwrap_or(false),
+            &mut |walked| scan.observe(walked, &mut report),
+        )
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()));
+    state.with_scans(|scans| scans.remove(&scan_id));
+    result?
+}
+
+/// Stop the scan `scan_id`; it then fails with `DIR_SCAN_CANCELLED`.
+/// Returns false when no such scan is running.
+#[tauri::command]
+pub fn cancel_dir_scan(state: tauri::State<'_, DirScanState>, scan_id: String) -> bool {
+    state.with_scans(|scans| match scans.get(&scan_id) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn should_stop_on_cancel_or_past_the_ceiling() {
+        let dir = std::env::temp_dir().join(format!("moraya-dir-scan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        for name in ["one.md", "two.md", "a/three.md", "a/b/four.md"] {
+            fs::write(dir.join(name), "x").unwrap();
+        }
+        let cancel = AtomicBool::new(false);
+        let walk = |max_entries| {
+            let mut scan = Scan {
+                cancel: &cancel,
+                max_entries,
+                directories: 0,
+                entries: 0,
+                // Due at once, so the first step reports
+                last_report: Instant::now() - PROGRESS_INTERVAL,
+            };
+            let mut reports = Vec::new();
+            let tree = walk_dir(&dir, None, false, false, &mut |walked| {
+                scan.observe(walked, &mut |d, e| reports.push((d, e)))
+            });
+            (tree, scan.directories, scan.entries, reports)
+        };
+
+        let (tree, directories, entries, reports) = walk(10);
+        assert_eq!(tree.unwrap().len(), 3);
+        assert_eq!((directories, entries), (3, 6));
+        assert_eq!(reports.first(), Some(&(1, 0)));
+
+        let err = walk(4).0.unwrap_err();
+        assert_eq!(err.code, ErrorCode::TooManyEntries);
+        assert_eq!(err.details.unwrap()["limit"], 4);
+
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(walk(10).0.unwrap_err().code, ErrorCode::DirScanCancelled);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,987 @@
+//! Word (.docx) export. Markdown is converted to WordprocessingML and the
+//! OOXML package is assembled by hand with `zip`. Blocks map onto Word's
+//! built-in styles (Heading 1–6, Quote, List Paragraph, Hyperlink), so the
+//! result stays editable with Word's own formatting tools.
+
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use image::ImageFormat;
+use pulldown_cmark::{Alignment, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::epub_export::{is_line_break, xml_escape};
+use super::error::CommandError;
+use super::export_preset::{Preset, PresetUsage};
+use super::frontmatter;
+use super::pdf_export::MarkdownSource;
+use crate::commands::file as file_cmd;
+
+/// A4 with 1" margins, in twips (1/20 pt).
+const PAGE_WIDTH: u32 = 11906;
+const PAGE_HEIGHT: u32 = 16838;
+const MARGIN: u32 = 1440;
+const TEXT_WIDTH: u32 = PAGE_WIDTH - 2 * MARGIN;
+/// Indent per list or quote level, in twips.
+const INDENT: u32 = 720;
+const EMU_PER_TWIP: u64 = 635;
+/// Images are sized at 96 dpi.
+const EMU_PER_PX: u64 = 9525;
+
+const REL_HYPERLINK: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+const REL_IMAGE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Default Extension="png" ContentType="image/png"/>
+<Default Extension="jpeg" ContentType="image/jpeg"/>
+<Default Extension="gif" ContentType="image/gif"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+<Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/>
+</Types>
+"#;
+
+const PACKAGE_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>
+"#;
+
+const STYLES_HEAD: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:docDefaults>
+<w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri" w:cs="Calibri"/><w:sz w:val="22"/><w:szCs w:val="22"/></w:rPr></w:rPrDefault>
+<w:pPrDefault><w:pPr><w:spacing w:after="160" w:line="276" w:lineRule="auto"/></w:pPr></w:pPrDefault>
+</w:docDefaults>
+<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>
+<w:style w:type="paragraph" w:styleId="Quote"><w:name w:val="Quote"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:pBdr><w:left w:val="single" w:sz="18" w:space="8" w:color="CCCCCC"/></w:pBdr><w:ind w:left="720"/></w:pPr><w:rPr><w:i/><w:color w:val="595959"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="60"/><w:ind w:left="720"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="SourceCode"><w:name w:val="Source Code"/><w:basedOn w:val="Normal"/><w:pPr><w:shd w:val="clear" w:color="auto" w:fill="F4F4F4"/><w:spacing w:after="160" w:line="240" w:lineRule="auto"/></w:pPr><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/><w:szCs w:val="20"/></w:rPr></w:style>
+<w:style w:type="character" w:styleId="VerbatimChar"><w:name w:val="Verbatim Char"/><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/><w:szCs w:val="20"/><w:shd w:val="clear" w:color="auto" w:fill="F4F4F4"/></w:rPr></w:style>
+<w:style w:type="character" w:styleId="Hyperlink"><w:name w:val="Hyperlink"/><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr></w:style>
+<w:style w:type="table" w:styleId="TableGrid"><w:name w:val="Table Grid"/><w:pPr><w:spacing w:after="0"/></w:pPr><w:tblPr><w:tblBorders><w:top w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:left w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:bottom w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:right w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:insideH w:val="single" w:sz="4" w:space="0" w:color="auto"/><w:insideV w:val="single" w:sz="4" w:space="0" w:color="auto"/></w:tblBorders><w:tblCellMar><w:left w:w="108" w:type="dxa"/><w:right w:w="108" w:type="dxa"/></w:tblCellMar></w:tblPr></w:style>
+"#;
+
+/// Heading font sizes in half-points, H1..H6.
+const HEADING_SIZES: [u32; 6] = [32, 28, 26, 24, 22, 22];
+const BULLETS: [&str; 3] = ["\u{2022}", "\u{25E6}", "\u{25AA}"];
+
+struct NumberingDef {
+    ordered: bool,
+    level: usize,
+    start: u64,
+}
+
+struct Relationship {
+    kind: &'static str,
+    target: String,
+    external: bool,
+}
+
+struct TableState {
+    alignments: Vec<Alignment>,
+    column: usize,
+    head: bool,
+}
+
+struct Converter<'a, F> {
+    base_dir: Option<&'a Path>,
+    access: F,
+    body: String,
+    in_para: bool,
+    bold: u32,
+    italic: u32,
+    strike: u32,
+    /// Whether the current link was written as a `w:hyperlink`.
+    link: Option<bool>,
+    quote_depth: u32,
+    /// `numId` of each open list, outermost first.
+    lists: Vec<usize>,
+    /// The next paragraph starts a list item and gets its number or bullet.
+    pending_item: bool,
+    heading: Option<HeadingLevel>,
+    heading_text: String,
+    code: Option<String>,
+    /// Destination and alt text of the image being read.
+    image: Option<(String, String)>,
+    table: Option<TableState>,
+    numbering: Vec<NumberingDef>,
+    /// Relationships after the fixed styles (rId1) and numbering (rId2).
+    rels: Vec<Relationship>,
+    media: Vec<(String, Vec<u8>)>,
+    bookmarks: HashSet<String>,
+}
+
+/// Word bookmark for a heading or `#fragment`: hidden (leading `_`), only
+/// letters, digits and underscores, at most 40 characters.
+fn bookmark_name(text: &str) -> String {
+    let name: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() => Some(c),
+            c if c.is_whitespace() || c == '-' || c == '_' => Some('_'),
+            _ => None,
+        })
+        .take(36)
+        .collect();
+    format!("_{}", name)
+}
+
+fn image_bytes(
+    src: &str,
+    base_dir: Option<&Path>,
+    access: &impl Fn(&str) -> Option<PathBuf>,
+) -> Option<Vec<u8>> {
+    if let Some(data) = src.trim().strip_prefix("data:") {
+        let (meta, payload) = data.split_once(',')?;
+        if !meta.ends_with(";base64") {
+            return None;
+        }
+        return base64::engine::general_purpose::STANDARD
+            .decode(payload.trim())
+            .ok();
+    }
+    let path = file_cmd::resolve_document_reference(src, base_dir)?;
+    std::fs::read(access(&path.to_string_lossy())?).ok()
+}
+
+impl<'a, F> Converter<'a, F>
+where
+    F: Fn(&str) -> Option<PathBuf>,
+{
+    fn new(base_dir: Option<&'a Path>, access: F) -> Self {
+        Self {
+            base_dir,
+            access,
+            body: String::new(),
+            in_para: false,
+            bold: 0,
+            italic: 0,
+            strike: 0,
+            link: None,
+            quote_depth: 0,
+            lists: Vec::new(),
+            pending_item: false,
+            heading: None,
+            heading_text: String::new(),
+            code: None,
+            image: None,
+            table: None,
+            numbering: Vec::new(),
+            rels: Vec::new(),
+            media: Vec::new(),
+            bookmarks: HashSet::new(),
+        }
+    }
+
+    fn add_rel(&mut self, kind: &'static str, target: String, external: bool) -> String {
+        self.rels.push(Relationship {
+            kind,
+            target,
+            external,
+        });
+        format!("rId{}", self.rels.len() + 2)
+    }
+
+    fn start_para(&mut self) {
+        let depth = self.lists.len() as u32;
+        let mut style = None;
+        let mut numbering = None;
+        let mut indent = 0;
+        let mut justify = None;
+        if let Some(level) = self.heading {
+            style = Some(format!("Heading{}", level as usize));
+        } else if let Some(table) = &self.table {
+            justify = match table.alignments.get(table.column) {
+                Some(Alignment::Center) => Some("center"),
+                Some(Alignment::Right) => Some("right"),
+                _ => None,
+            };
+        } else if depth > 0 {
+            style = Some("ListParagraph".to_string());
+            if self.pending_item {
+                numbering = self.lists.last().map(|&id| (id, depth - 1));
+            } else {
+                indent = INDENT * (depth + self.quote_depth);
+            }
+        } else if self.quote_depth > 0 {
+            style = Some("Quote".to_string());
+            indent = INDENT * self.quote_depth;
+        }
+        self.pending_item = false;
+
+        let mut props = String::new();
+        if let Some(style) = style {
+            props.push_str(&format!("<w:pStyle w:val=\"{}\"/>", style));
+        }
+        if let Some((id, level)) = numbering {
+            props.push_str(&format!(
+                "<w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr>",
+                level.min(8),
+                id
+            ));
+        }
+        if indent > 0 {
+            props.push_str(&format!("<w:ind w:left=\"{}\"/>", indent));
+        }
+        if let Some(justify) = justify {
+            props.push_str(&format!("<w:jc w:val=\"{}\"/>", justify));
+        }
+        self.body.push_str("<w:p>");
+        if !props.is_empty() {
+            self.body.push_str(&format!("<w:pPr>{}</w:pPr>", props));
+        }
+        self.in_para = true;
+    }
+
+    fn ensure_para(&mut self) {
+        if !self.in_para {
+            self.start_para();
+        }
+    }
+
+    fn end_para(&mut self) {
+        if self.in_para {
+            self.body.push_str("</w:p>");
+            self.in_para = false;
+        }
+    }
+
+    fn run_props(&self, code: bool) -> String {
+        let mut props = String::new();
+        if code {
+            props.push_str("<w:rStyle w:val=\"VerbatimChar\"/>");
+        } else if self.link == Some(true) {
+            props.push_str("<w:rStyle w:val=\"Hyperlink\"/>");
+        }
+        if self.bold > 0 {
+            props.push_str("<w:b/>");
+        }
+        if self.italic > 0 {
+            props.push_str("<w:i/>");
+        }
+        if self.strike > 0 {
+            props.push_str("<w:strike/>");
+        }
+        if props.is_empty() {
+            props
+        } else {
+            format!("<w:rPr>{}</w:rPr>", props)
+        }
+    }
+
+    fn text(&mut self, text: &str, code: bool) {
+        if let Some((_, alt)) = self.image.as_mut() {
+            alt.push_str(text);
+            return;
+        }
+        if let Some(block) = self.code.as_mut() {
+            block.push_str(text);
+            return;
+        }
+        if self.heading.is_some() {
+            self.heading_text.push_str(text);
+        }
+        self.ensure_para();
+        let props = self.run_props(code);
+        self.body.push_str(&format!(
+            "<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r>",
+            props,
+            xml_escape(text)
+        ));
+    }
+
+    fn line_break(&mut self) {
+        self.ensure_para();
+        self.body.push_str("<w:r><w:br/></w:r>");
+    }
+
+    fn code_block(&mut self, text: &str) {
+        let indent = INDENT * (self.lists.len() as u32 + self.quote_depth);
+        self.body
+            .push_str("<w:p><w:pPr><w:pStyle w:val=\"SourceCode\"/>");
+        if indent > 0 {
+            self.body
+                .push_str(&format!("<w:ind w:left=\"{}\"/>", indent));
+        }
+        self.body.push_str("</w:pPr>");
+        for (i, line) in text.trim_end_matches('\n').split('\n').enumerate() {
+            if i > 0 {
+                self.body.push_str("<w:r><w:br/></w:r>");
+            }
+            self.body.push_str("<w:r>");
+            for (j, part) in line.split('\t').enumerate() {
+                if j > 0 {
+                    self.body.push_str("<w:tab/>");
+                }
+                if !part.is_empty() {
+                    self.body.push_str(&format!(
+                        "<w:t xml:space=\"preserve\">{}</w:t>",
+                        xml_escape(part)
+                    ));
+                }
+            }
+            self.body.push_str("</w:r>");
+        }
+        self.body.push_str("</w:p>");
+    }
+
+    /// Inline drawing for the image at `src`, or `None` when it can't be
+    /// read or decoded. Formats Word can't show are converted to PNG.
+    fn drawing(&mut self, src: &str, alt: &str) -> Option<String> {
+        let data = image_bytes(src, self.base_dir, &self.access)?;
+        let (data, ext) = match image::guess_format(&data).ok()? {
+            ImageFormat::Png => (data, "png"),
+            ImageFormat::Jpeg => (data, "jpeg"),
+            ImageFormat::Gif => (data, "gif"),
+            _ => {
+                let decoded = image::load_from_memory(&data).ok()?;
+                let mut png = Cursor::new(Vec::new());
+                decoded.write_to(&mut png, ImageFormat::Png).ok()?;
+                (png.into_inner(), "png")
+            }
+        };
+        let (width, height) = image::ImageReader::new(Cursor::new(&data))
+            .with_guessed_format()
+            .ok()?
+            .into_dimensions()
+            .ok()?;
+        let mut cx = width as u64 * EMU_PER_PX;
+        let mut cy = height as u64 * EMU_PER_PX;
+        let max = TEXT_WIDTH as u64 * EMU_PER_TWIP;
+        if cx > max {
+            cy = cy * max / cx;
+            cx = max;
+        }
+
+        let index = self.media.len() + 1;
+        let name = format!("image{}.{}", index, ext);
+        self.media.push((name.clone(), data));
+        let rel = self.add_rel(REL_IMAGE, format!("media/{}", name), false);
+        Some(format!(
+            "<w:r><w:drawing><wp:inline distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\">\
+             <wp:extent cx=\"{cx}\" cy=\"{cy}\"/>\
+             <wp:docPr id=\"{index}\" name=\"Picture {index}\" descr=\"{alt}\"/>\
+             <a:graphic><a:graphicData uri=\"http://schemas.openxmlformats.org/drawingml/2006/picture\">\
+             <pic:pic><pic:nvPicPr><pic:cNvPr id=\"{index}\" name=\"{name}\"/><pic:cNvPicPr/></pic:nvPicPr>\
+             <pic:blipFill><a:blip r:embed=\"{rel}\"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill>\
+             <pic:spPr><a:xfrm><a:off x=\"0\" y=\"0\"/><a:ext cx=\"{cx}\" cy=\"{cy}\"/></a:xfrm>\
+             <a:prstGeom prst=\"rect\"><a:avLst/></a:prstGeom></pic:spPr></pic:pic>\
+             </a:graphicData></a:graphic></wp:inline></w:drawing></w:r>",
+            alt = xml_escape(alt),
+        ))
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Paragraph) => self.start_para(),
+            Event::End(TagEnd::Paragraph) => self.end_para(),
+            Event::Start(Tag::Heading { level, .. }) => {
+                self.end_para();
+                self.heading = Some(level);
+                self.heading_text.clear();
+                self.start_para();
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                // Target for `[..](#heading)` links
+                let base = bookmark_name(&self.heading_text);
+                let mut name = base.clone();
+                let mut n = 1;
+                while !self.bookmarks.insert(name.clone()) {
+                    name = format!("{}_{}", base, n);
+                    n += 1;
+                }
+                let id = self.bookmarks.len();
+                self.body.push_str(&format!(
+                    "<w:bookmarkStart w:id=\"{}\" w:name=\"{}\"/><w:bookmarkEnd w:id=\"{}\"/>",
+                    id,
+                    xml_escape(&name),
+                    id
+                ));
+                self.end_para();
+                self.heading = None;
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.end_para();
+                self.quote_depth += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.end_para();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.end_para();
+                self.code = Some(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(text) = self.code.take() {
+                    self.code_block(&text);
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                self.end_para();
+                // Each list gets its own numbering instance so ordered
+                // lists restart at their first number
+                self.numbering.push(NumberingDef {
+                    ordered: start.is_some(),
+                    level: self.lists.len().min(8),
+                    start: start.unwrap_or(1),
+                });
+                self.lists.push(self.numbering.len());
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.end_para();
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.end_para();
+                self.pending_item = true;
+            }
+            Event::End(TagEnd::Item) => {
+                if self.pending_item {
+                    // Empty item: still show its bullet
+                    self.start_para();
+                }
+                self.end_para();
+            }
+            Event::TaskListMarker(checked) => {
+                self.text(if checked { "\u{2612} " } else { "\u{2610} " }, false)
+            }
+            Event::Start(Tag::Emphasis) => self.italic += 1,
+            Event::End(TagEnd::Emphasis) => self.italic = self.italic.saturating_sub(1),
+            Event::Start(Tag::Strong) => self.bold += 1,
+            Event::End(TagEnd::Strong) => self.bold = self.bold.saturating_sub(1),
+            Event::Start(Tag::Strikethrough) => self.strike += 1,
+            Event::End(TagEnd::Strikethrough) => self.strike = self.strike.saturating_sub(1),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let dest = dest_url.trim();
+                if dest.is_empty() || self.image.is_some() || self.code.is_some() {
+                    self.link = Some(false);
+                    return;
+                }
+                self.ensure_para();
+                let open = match dest.strip_prefix('#') {
+                    Some(fragment) => format!(
+                        "<w:hyperlink w:anchor=\"{}\" w:history=\"1\">",
+                        xml_escape(&bookmark_name(fragment))
+                    ),
+                    None => {
+                        let id = self.add_rel(REL_HYPERLINK, dest.to_string(), true);
+                        format!("<w:hyperlink r:id=\"{}\" w:history=\"1\">", id)
+                    }
+                };
+                self.body.push_str(&open);
+                self.link = Some(true);
+            }
+            Event::End(TagEnd::Link) => {
+                let written = self.link.take();
+                if written == Some(true) {
+                    self.body.push_str("</w:hyperlink>");
+                }
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((src, alt)) = self.image.take() {
+                    match self.drawing(&src, &alt) {
+                        Some(drawing) => {
+                            self.ensure_para();
+                            self.body.push_str(&drawing);
+                        }
+                        // Unreadable images keep their alt text
+                        None => self.text(&alt, false),
+                    }
+                }
+            }
+            Event::Start(Tag::Table(alignments)) => {
+                self.end_para();
+                let columns = alignments.len().max(1) as u32;
+                self.body.push_str(
+                    "<w:tbl><w:tblPr><w:tblStyle w:val=\"TableGrid\"/>\
+                     <w:tblW w:w=\"5000\" w:type=\"pct\"/></w:tblPr><w:tblGrid>",
+                );
+                for _ in 0..columns {
+                    self.body
+                        .push_str(&format!("<w:gridCol w:w=\"{}\"/>", TEXT_WIDTH / columns));
+                }
+                self.body.push_str("</w:tblGrid>");
+                self.table = Some(TableState {
+                    alignments,
+                    column: 0,
+                    head: false,
+                });
+            }
+            Event::End(TagEnd::Table) => {
+                self.body.push_str("</w:tbl>");
+                self.table = None;
+                // Keeps adjacent tables apart; Word also wants a paragraph
+                // after a table at the end of a cell or document
+                self.body.push_str("<w:p/>");
+            }
+            Event::Start(Tag::TableHead) => {
+                self.body.push_str("<w:tr><w:trPr><w:tblHeader/></w:trPr>");
+                if let Some(table) = self.table.as_mut() {
+                    table.head = true;
+                    table.column = 0;
+                }
+            }
+            Event::Start(Tag::TableRow) => {
+                self.body.push_str("<w:tr>");
+                if let Some(table) = self.table.as_mut() {
+                    table.head = false;
+                    table.column = 0;
+                }
+            }
+            Event::End(TagEnd::TableHead) | Event::End(TagEnd::TableRow) => {
+                self.body.push_str("</w:tr>");
+            }
+            Event::Start(Tag::TableCell) => {
+                let (columns, head) = self
+                    .table
+                    .as_ref()
+                    .map_or((1, false), |t| (t.alignments.len().max(1) as u32, t.head));
+                self.body.push_str(&format!(
+                    "<w:tc><w:tcPr><w:tcW w:w=\"{}\" w:type=\"dxa\"/></w:tcPr>",
+                    TEXT_WIDTH / columns
+                ));
+                if head {
+                    self.bold += 1;
+                }
+                self.start_para();
+            }
+            Event::End(TagEnd::TableCell) => {
+                self.ensure_para();
+                self.end_para();
+                self.body.push_str("</w:tc>");
+                if let Some(table) = self.table.as_mut() {
+                    if table.head {
+                        self.bold = self.bold.saturating_sub(1);
+                    }
+                    table.column += 1;
+                }
+            }
+            Event::Text(text) => self.text(&text, false),
+            Event::Code(text) => self.text(&text, true),
+            Event::SoftBreak => self.text(" ", false),
+            Event::HardBreak => self.line_break(),
+            // Other raw HTML has no Word equivalent
+            Event::Html(html) | Event::InlineHtml(html) if is_line_break(&html) => {
+                self.line_break()
+            }
+            Event::Rule => {
+                self.end_para();
+                self.body.push_str(
+                    "<w:p><w:pPr><w:pBdr><w:bottom w:val=\"single\" w:sz=\"6\" \
+                     w:space=\"1\" w:color=\"auto\"/></w:pBdr></w:pPr></w:p>",
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn document_xml(&self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+             xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\" \
+             xmlns:wp=\"http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing\" \
+             xmlns:a=\"http://schemas.openxmlformats.org/drawingml/2006/main\" \
+             xmlns:pic=\"http://schemas.openxmlformats.org/drawingml/2006/picture\">\n\
+             <w:body>{}<w:sectPr><w:pgSz w:w=\"{}\" w:h=\"{}\"/>\
+             <w:pgMar w:top=\"{m}\" w:right=\"{m}\" w:bottom=\"{m}\" w:left=\"{m}\" \
+             w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/></w:sectPr></w:body>\n</w:document>\n",
+            self.body,
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            m = MARGIN
+        )
+    }
+
+    fn document_rels(&self) -> String {
+        let mut rels = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\n\
+             <Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>\n\
+             <Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering\" Target=\"numbering.xml\"/>\n",
+        );
+        for (i, rel) in self.rels.iter().enumerate() {
+            rels.push_str(&format!(
+                "<Relationship Id=\"rId{}\" Type=\"{}\" Target=\"{}\"{}/>\n",
+                i + 3,
+                rel.kind,
+                xml_escape(&rel.target),
+                if rel.external {
+                    " TargetMode=\"External\""
+                } else {
+                    ""
+                }
+            ));
+        }
+        rels.push_str("</Relationships>\n");
+        rels
+    }
+
+    fn numbering_xml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <w:numbering xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\n",
+        );
+        for (id, ordered) in [(0, false), (1, true)] {
+            xml.push_str(&format!(
+                "<w:abstractNum w:abstractNumId=\"{}\"><w:multiLevelType w:val=\"hybridMultilevel\"/>",
+                id
+            ));
+            for level in 0..9 {
+                let (format, text) = if ordered {
+                    ("decimal", format!("%{}.", level + 1))
+                } else {
+                    ("bullet", BULLETS[level % BULLETS.len()].to_string())
+                };
+                xml.push_str(&format!(
+                    "<w:lvl w:ilvl=\"{}\"><w:start w:val=\"1\"/><w:numFmt w:val=\"{}\"/>\
+                     <w:lvlText w:val=\"{}\"/><w:lvlJc w:val=\"left\"/>\
+                     <w:pPr><w:ind w:left=\"{}\" w:hanging=\"360\"/></w:pPr></w:lvl>",
+                    level,
+                    format,
+                    text,
+                    INDENT * (level as u32 + 1)
+                ));
+            }
+            xml.push_str("</w:abstractNum>\n");
+        }
+        for (i, def) in self.numbering.iter().enumerate() {
+            xml.push_str(&format!(
+                "<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"{}\"/>",
+                i + 1,
+                def.ordered as u8
+            ));
+            if def.ordered {
+                xml.push_str(&format!(
+                    "<w:lvlOverride w:ilvl=\"{}\"><w:startOverride w:val=\"{}\"/></w:lvlOverride>",
+                    def.level, def.start
+                ));
+            }
+            xml.push_str("</w:num>\n");
+        }
+        xml.push_str("</w:numbering>\n");
+        xml
+    }
+}
+
+fn styles_xml() -> String {
+    let mut xml = String::from(STYLES_HEAD);
+    for (i, size) in HEADING_SIZES.iter().enumerate() {
+        xml.push_str(&format!(
+            "<w:style w:type=\"paragraph\" w:styleId=\"Heading{n}\"><w:name w:val=\"heading {n}\"/>\
+             <w:basedOn w:val=\"Normal\"/><w:next w:val=\"Normal\"/><w:qFormat/>\
+             <w:pPr><w:keepNext/><w:spacing w:before=\"240\" w:after=\"80\"/><w:outlineLvl w:val=\"{lvl}\"/></w:pPr>\
+             <w:rPr><w:b/><w:sz w:val=\"{size}\"/><w:szCs w:val=\"{size}\"/></w:rPr></w:style>\n",
+            n = i + 1,
+            lvl = i,
+        ));
+    }
+    xml.push_str("</w:styles>\n");
+    xml
+}
+
+/// Convert `markdown` to a .docx package. Relative image paths resolve
+/// against `base_dir`; `access` checks (and canonicalizes) every file read.
+pub(crate) fn build_docx<F>(
+    markdown: &str,
+    base_dir: Option<&Path>,
+    access: F,
+) -> Result<Vec<u8>, CommandError>
+where
+    F: Fn(&str) -> Option<PathBuf>,
+{
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    // Frontmatter holds settings, not content
+    let body = &markdown[frontmatter::body_start(markdown)..];
+    let mut converter = Converter::new(base_dir, access);
+    for event in Parser::new_ext(body, options) {
+        converter.event(event);
+    }
+    converter.end_para();
+
+    let mut files = vec![
+        (
+            "[Content_Types].xml".to_string(),
+            CONTENT_TYPES.as_bytes().to_vec(),
+        ),
+        ("_rels/.rels".to_string(), PACKAGE_RELS.as_bytes().to_vec()),
+        (
+            "word/document.xml".to_string(),
+            converter.document_xml().into_bytes(),
+        ),
+        (
+            "word/_rels/document.xml.rels".to_string(),
+            converter.document_rels().into_bytes(),
+        ),
+        ("word/styles.xml".to_string(), styles_xml().into_bytes()),
+        (
+            "word/numbering.xml".to_string(),
+            converter.numbering_xml().into_bytes(),
+        ),
+    ];
+    for (name, data) in converter.media {
+        files.push((format!("word/media/{}", name), data));
+    }
+
+    let write = || -> zip::result::ZipResult<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, data) in &files {
+            zip.start_file(name.as_str(), options)?;
+            zip.write_all(data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    };
+    write().map_err(|e| CommandError::from(format!("Failed to write DOCX: {}", e)))
+}
+
+/// Convert markdown (a file, or editor content plus its folder) to a Word
+/// document at `output_path`. The document's export preset can add a
+/// table of contents.
+#[tauri::command]
+pub async fn export_docx(
+    source: MarkdownSource,
+    output_path: String,
+) -> Result<PresetUsage, CommandError> {
+    let output = file_cmd::validate_path(&output_path)?;
+    let (markdown, base_dir) = source.load()?;
+    let mut preset = Preset::read(&markdown);
+    let markdown = preset.apply_toc(markdown);
+    let usage = preset.finish("Word");
+    let bytes = tokio::task::spawn_blocking(move || {
+        build_docx(&markdown, base_dir.as_deref(), |path| {
+            file_cmd::validate_path(path).ok()
+        })
+    })
+    .await
+    .map_err(|e| CommandError::from(format!("DOCX export task failed: {}", e)))??;
+    std::fs::write(&output, bytes).map_err(file_cmd::sanitize_io_error)?;
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // 2x1 opaque PNG
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAADUlEQVR4nGP4zwAE/wEHAAH/4iOeWQAAAABJRU5ErkJggg==";
+
+    fn part(docx: &[u8], name: &str) -> String {
+        let mut archive = zip::ZipArchive::new(Cursor::new(docx)).unwrap();
+        let mut file = archive.by_name(name).unwrap();
+        let mut text = String::new();
+        file.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    fn no_access(_: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// Style, text and numbering (ilvl, numId) of a body paragraph.
+    type Para = (String, String, Option<(String, String)>);
+
+    fn paragraphs(xml: &str) -> Vec<Para> {
+        let doc = roxmltree::Document::parse(xml).unwrap();
+        doc.descendants()
+            .filter(|n| n.tag_name().name() == "p")
+            .map(|p| {
+                let attr = |name: &str| {
+                    p.descendants()
+                        .find(|n| n.tag_name().name() == name)
+                        .and_then(|n| n.attributes().find(|a| a.name() == "val"))
+                        .map(|a| a.value().to_string())
+                };
+                let text: String = p
+                    .descendants()
+                    .filter(|n| n.tag_name().name() == "t")
+                    .filter_map(|n| n.text())
+                    .collect();
+                let numbering = attr("ilvl").zip(attr("numId"));
+                (attr("pStyle").unwrap_or_default(), text, numbering)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_map_markdown_blocks_to_word_styles() {
+        let md = "# Title\n\n\
+                  Some **bold**, *italic*, ~~gone~~ and `code` with a [link](https://example.com?a=1&b=2).\n\n\
+                  > Quoted\n\n\
+                  ```rust\nfn main() {\n\tprintln!(\"hi\");\n}\n```\n\n\
+                  - one\n  - nested\n- two\n\n\
+                  3. three\n4. four\n\n\
+                  | Left | Right |\n|:-----|------:|\n| a | b |\n\n\
+                  See [the title](#title).\n";
+        let docx = build_docx(md, None, no_access).unwrap();
+        let xml = part(&docx, "word/document.xml");
+        let paras = paragraphs(&xml);
+
+        assert_eq!(paras[0].0, "Heading1");
+        assert_eq!(paras[0].1, "Title");
+        assert_eq!(paras[2], ("Quote".into(), "Quoted".into(), None));
+        assert_eq!(paras[3].0, "SourceCode");
+        assert_eq!(paras[3].1, "fn main() {println!(\"hi\");}");
+        let items: Vec<_> = paras
+            .iter()
+            .filter(|p| p.0 == "ListParagraph")
+            .map(|p| (p.1.as_str(), p.2.clone().unwrap()))
+            .collect();
+        assert_eq!(items.len(), 5);
+        assert_eq!(items[0], ("one", ("0".into(), "1".into())));
+        assert_eq!(items[1], ("nested", ("1".into(), "2".into())));
+        assert_eq!(items[2], ("two", ("0".into(), "1".into())));
+        assert_eq!(items[3], ("three", ("0".into(), "3".into())));
+
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let run_text = |prop: &str| -> String {
+            doc.descendants()
+                .filter(|n| n.tag_name().name() == "r")
+                .filter(|r| r.descendants().any(|n| n.tag_name().name() == prop))
+                .flat_map(|r| {
+                    r.descendants()
+                        .filter(|n| n.tag_name().name() == "t")
+                        .filter_map(|n| n.text())
+                })
+                .collect()
+        };
+        assert_eq!(run_text("b"), "boldLeftRight");
+        assert_eq!(run_text("i"), "italic");
+        assert_eq!(run_text("strike"), "gone");
+        assert_eq!(
+            doc.descendants()
+                .filter(|n| n.tag_name().name() == "br")
+                .count(),
+            2
+        );
+        assert!(xml.contains(
+            "<w:rStyle w:val=\"VerbatimChar\"/></w:rPr><w:t xml:space=\"preserve\">code</w:t>"
+        ));
+        assert!(xml.contains("<w:tab/>"));
+
+        // Table: header row repeats, second column right-aligned
+        assert_eq!(
+            doc.descendants()
+                .filter(|n| n.tag_name().name() == "tr")
+                .count(),
+            2
+        );
+        assert!(xml.contains("<w:tblHeader/>"));
+        assert!(xml.contains("<w:jc w:val=\"right\"/>"));
+
+        // Links: external via relationship, in-document via bookmark
+        let rels = part(&docx, "word/_rels/document.xml.rels");
+        let rels = roxmltree::Document::parse(&rels).unwrap();
+        let link = doc
+            .descendants()
+            .find(|n| {
+                n.tag_name().name() == "hyperlink" && n.attributes().any(|a| a.name() == "id")
+            })
+            .unwrap();
+        let id = link
+            .attributes()
+            .find(|a| a.name() == "id")
+            .unwrap()
+            .value();
+        let rel = rels
+            .descendants()
+            .find(|n| n.attribute("Id") == Some(id))
+            .unwrap();
+        assert_eq!(rel.attribute("Target"), Some("https://example.com?a=1&b=2"));
+        assert_eq!(rel.attribute("TargetMode"), Some("External"));
+        assert!(xml.contains("w:anchor=\"_title\""));
+        assert!(xml.contains("w:name=\"_title\""));
+
+        let numbering = part(&docx, "word/numbering.xml");
+        assert!(numbering.contains("<w:startOverride w:val=\"3\"/>"));
+    }
+
+    #[test]
+    fn should_embed_images() {
+        let dir = std::env::temp_dir().join(format!("moraya-docx-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(PNG)
+            .unwrap();
+        std::fs::write(dir.join("dot.png"), &png).unwrap();
+        let md = format!(
+            "![dot](dot.png) ![inline](data:image/png;base64,{}) ![gone](missing.png)",
+            PNG
+        );
+        let access = |path: &str| Path::new(path).canonicalize().ok();
+        let docx = build_docx(&md, Some(&dir), access).unwrap();
+
+        let xml = part(&docx, "word/document.xml");
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let pictures: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.tag_name().name() == "docPr")
+            .map(|n| n.attribute("descr").unwrap().to_string())
+            .collect();
+        assert_eq!(pictures, vec!["dot", "inline"]);
+        let extent = doc
+            .descendants()
+            .find(|n| n.tag_name().name() == "extent")
+            .unwrap();
+        assert_eq!(extent.attribute("cx"), Some("19050"));
+        assert_eq!(extent.attribute("cy"), Some("9525"));
+        assert!(xml.contains(">gone</w:t>"));
+
+        let rels = part(&docx, "word/_rels/document.xml.rels");
+        assert!(rels.contains("Target=\"media/image1.png\""));
+        assert!(rels.contains("Target=\"media/image2.png\""));
+        let mut archive = zip::ZipArchive::new(Cursor::new(&docx)).unwrap();
+        assert_eq!(
+            archive.by_name("word/media/image1.png").unwrap().size(),
+            png.len() as u64
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_write_well_formed_package_parts() {
+        let docx = build_docx("# Hi\n\n1. a\n2. b\n\n---\n", None, no_access).unwrap();
+        for name in [
+            "[Content_Types].xml",
+            "_rels/.rels",
+            "word/document.xml",
+            "word/_rels/document.xml.rels",
+            "word/styles.xml",
+            "word/numbering.xml",
+        ] {
+            let xml = part(&docx, name);
+            assert!(
+                roxmltree::Document::parse(&xml).is_ok(),
+                "{} is not valid XML",
+                name
+            );
+        }
+        let styles = part(&docx, "word/styles.xml");
+        assert!(styles.contains("w:styleId=\"Heading6\""));
+    }
+
+    #[test]
+    fn should_keep_heading_bookmarks_unique() {
+        let docx = build_docx("## Notes\n\n## Notes\n", None, no_access).unwrap();
+        let xml = part(&docx, "word/document.xml");
+        assert!(xml.contains("w:name=\"_notes\""));
+        assert!(xml.contains("w:name=\"_notes_1\""));
+        assert_eq!(bookmark_name("Details & more"), "_details__more");
+    }
+}
@@ -0,0 +1,329 @@
+//! Crash-recovery drafts: the frontend periodically hands over unsaved
+//! buffers, which are kept gzip-compressed in `app_data_dir()/drafts/`
+//! until the document is saved (`write_file` drops its draft) or the user
+//! discards them.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::Manager;
+
+use super::encryption::is_encrypted_file;
+use super::error::{CommandError, ErrorCode};
+use super::file::{sanitize_io_error, validate_path, write_atomic};
+
+const DRAFT_EXT: &str = "draft";
+/// Compressed size of all drafts together; the oldest go first.
+const MAX_TOTAL_BYTES: u64 = 50 * 1024 * 1024;
+
+static DRAFTS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Draft {
+    pub id: String,
+    pub window_label: String,
+    /// Document path, or `None` for an untitled buffer.
+    pub path: Option<String>,
+    pub untitled_id: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub saved_at: i64,
+    pub content: String,
+}
+
+/// Resolve the drafts directory. Called from the setup hook.
+pub fn init(app: &tauri::AppHandle) {
+    if let Ok(dir) = app.path().app_data_dir() {
+        let _ = DRAFTS_DIR.set(dir.join("drafts"));
+    }
+}
+
+fn drafts_dir() -> Result<&'static Path, CommandError> {
+    DRAFTS_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .ok_or_else(|| CommandError::new(ErrorCode::Internal, "Drafts are not available"))
+}
+
+/// Stable id for a document path or untitled buffer id.
+fn draft_id(key: &str) -> String {
+    hex::encode(&Sha256::digest(key.as_bytes())[..12])
+}
+
+fn draft_file(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, DRAFT_EXT))
+}
+
+fn mtime_millis(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+fn write_draft(dir: &Path, draft: &Draft) -> Result<(), CommandError> {
+    let json = serde_json::to_vec(draft).map_err(|e| CommandError::from(e.to_string()))?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(&json).map_err(sanitize_io_error)?;
+    let bytes = encoder.finish().map_err(sanitize_io_error)?;
+    fs::create_dir_all(dir).map_err(sanitize_io_error)?;
+    write_atomic(&draft_file(dir, &draft.id), &bytes).map_err(sanitize_io_error)
+}
+
+fn read_draft(path: &Path) -> Option<Draft> {
+    let mut json = Vec::new();
+    GzDecoder::new(fs::File::open(path).ok()?)
+        .read_to_end(&mut json)
+        .ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+fn draft_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == DRAFT_EXT))
+        .collect()
+}
+
+/// Delete the oldest drafts until the rest fit in `max_bytes`, always
+/// keeping the newest one.
+fn enforce_cap(dir: &Path, max_bytes: u64) {
+    let mut files: Vec<(PathBuf, u64, i64)> = draft_files(dir)
+        .into_iter()
+        .filter_map(|p| {
+            let size = fs::metadata(&p).ok()?.len();
+            let mtime = mtime_millis(&p)?;
+            Some((p, size, mtime))
+        })
+        .collect();
+    files.sort_by_key(|(_, _, mtime)| std::cmp::Reverse(*mtime));
+    let mut total = 0;
+    for (i, (path, size, _)) in files.iter().enumerate() {
+        total += size;
+        if i > 0 && total > max_bytes {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// What a draft belongs to.
+enum Target {
+    Path(String),
+    Untitled(String),
+}
+
+fn save_in(
+    dir: &Path,
+    window_label: String,
+    target: Target,
+    content: String,
+) -> Result<String, CommandError> {
+    let (key, path, untitled_id) = match target {
+        Target::Path(p) => (p.clone(), Some(p), None),
+        Target::Untitled(u) => (format!("untitled:{}", u), None, Some(u)),
+    };
+    let draft = Draft {
+        id: draft_id(&key),
+        window_label,
+        path,
+        untitled_id,
+        saved_at: chrono::Utc::now().timestamp_millis(),
+        content,
+    };
+    write_draft(dir, &draft)?;
+    enforce_cap(dir, MAX_TOTAL_BYTES);
+    Ok(draft.id)
+}
+
+/// Drafts worth offering: untitled buffers, and documents whose file is
+/// missing or older than the draft. Stale drafts are deleted on the way.
+fn list_in(dir: &Path) -> Vec<Draft> {
+    let mut drafts: Vec<Draft> = draft_files(dir)
+        .into_iter()
+        .filter_map(|file| {
+            let Some(draft) = read_draft(&file) else {
+                let _ = fs::remove_file(&file);
+                return None;
+            };
+            let stale = draft
+                .path
+                .as_deref()
+                .and_then(|p| mtime_millis(Path::new(p)))
+                .is_some_and(|mtime| mtime >= draft.saved_at);
+            if stale {
+                let _ = fs::remove_file(&file);
+                return None;
+            }
+            Some(draft)
+        })
+        .collect();
+    drafts.sort_by_key(|d| std::cmp::Reverse(d.saved_at));
+    drafts
+}
+
+/// Called after `write_file` saves `path`.
+pub(crate) fn document_saved(path: &Path) {
+    if let Some(dir) = DRAFTS_DIR.get() {
+        let _ = fs::remove_file(draft_file(dir, &draft_id(&path.to_string_lossy())));
+    }
+}
+
+/// Store an unsaved buffer. `path_or_untitled_id` is the document path, or
+/// any other string identifying an untitled buffer. Returns the draft id.
+#[tauri::command]
+pub fn save_draft(
+    window_label: String,
+    path_or_untitled_id: String,
+    content: String,
+) -> Result<String, CommandError> {
+    let dir = drafts_dir()?;
+    if Path::new(&path_or_untitled_id).is_absolute() {
+        let path = validate_path(&path_or_untitled_id)?;
+        // A plaintext copy would defeat the encryption
+        if is_encrypted_file(&path) {
+            return Err(CommandError::new(
+                ErrorCode::Encrypted,
+                "Drafts are not kept for encrypted documents",
+            ));
+        }
+        let path = path.to_string_lossy().to_string();
+        save_in(dir, window_label, Target::Path(path), content)
+    } else if path_or_untitled_id.trim().is_empty() {
+        Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Missing document id",
+        ))
+    } else {
+        save_in(
+            dir,
+            window_label,
+            Target::Untitled(path_or_untitled_id),
+            content,
+        )
+    }
+}
+
+#[tauri::command]
+pub fn list_recoverable_drafts() -> Result<Vec<Draft>, CommandError> {
+    Ok(list_in(drafts_dir()?))
+}
+
+#[tauri::command]
+pub fn discard_draft(id: String) -> Result<(), CommandError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Invalid draft id",
+        ));
+    }
+    match fs::remove_file(draft_file(drafts_dir()?, &id)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(sanitize_io_error(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    #[test]
+    fn should_offer_only_drafts_newer_than_their_file() {
+        let dir = TempDir::new("drafts-recover");
+        let docs = TempDir::new("drafts-recover-docs");
+        let saved = docs.join("saved.md");
+        let edited = docs.join("edited.md");
+        fs::write(&edited, "old").unwrap();
+        // Drafts taken in the same millisecond as the write count as stale
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        let label = || "main".to_string();
+        save_in(
+            &dir,
+            label(),
+            Target::Path(edited.to_string_lossy().into()),
+            "new".into(),
+        )
+        .unwrap();
+        save_in(
+            &dir,
+            label(),
+            Target::Untitled("tab-1".into()),
+            "scratch".into(),
+        )
+        .unwrap();
+        save_in(
+            &dir,
+            label(),
+            Target::Path(saved.to_string_lossy().into()),
+            "typed".into(),
+        )
+        .unwrap();
+        // Saved after the draft was taken: its draft is stale
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&saved, "typed").unwrap();
+
+        let drafts = list_in(&dir);
+        assert_eq!(drafts.len(), 2);
+        assert!(drafts
+            .iter()
+            .any(|d| d.content == "new" && d.path.is_some()));
+        assert!(drafts
+            .iter()
+            .any(|d| d.untitled_id.as_deref() == Some("tab-1") && d.content == "scratch"));
+        assert_eq!(draft_files(&dir).len(), 2);
+
+        // Re-saving the same document replaces its draft
+        save_in(
+            &dir,
+            label(),
+            Target::Untitled("tab-1".into()),
+            "more".into(),
+        )
+        .unwrap();
+        assert_eq!(draft_files(&dir).len(), 2);
+    }
+
+    #[test]
+    fn should_drop_oldest_drafts_over_the_size_cap() {
+        let dir = TempDir::new("drafts-cap");
+        for i in 0..3 {
+            let id = save_in(
+                &dir,
+                "main".into(),
+                Target::Untitled(format!("u{}", i)),
+                "x".repeat(100),
+            )
+            .unwrap();
+            // Distinct mtimes so "oldest" is well defined
+            let file = fs::File::options()
+                .write(true)
+                .open(draft_file(&dir, &id))
+                .unwrap();
+            file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(1_000 + i))
+                .unwrap();
+        }
+        // Compressed sizes differ by a byte or two, so cap at exactly the
+        // total minus the oldest
+        let size = |p: &Path| fs::metadata(p).unwrap().len();
+        let total: u64 = draft_files(&dir).iter().map(|p| size(p)).sum();
+        let oldest = size(&draft_file(&dir, &draft_id("untitled:u0")));
+        enforce_cap(&dir, total - oldest);
+        let left: Vec<String> = list_in(&dir)
+            .into_iter()
+            .filter_map(|d| d.untitled_id)
+            .collect();
+        assert_eq!(left.len(), 2);
+        assert!(!left.contains(&"u0".to_string()));
+    }
+}
@@ -0,0 +1,399 @@
+//! Passphrase-encrypted documents.
+//!
+//! File layout: the `MORAYAENC` magic, a format version byte, the Argon2id
+//! cost parameters (`u32` LE each), a 16-byte salt and a 16-byte key check,
+//! followed by the XChaCha20-Poly1305 `nonce || ciphertext`. Everything
+//! before the nonce is bound as associated data. The key check is what tells
+//! a wrong passphrase apart from a damaged file.
+//!
+//! Unlocked keys are kept in memory per document until
+//! `lock_encrypted_documents` or until unused for `encryptionAutoLockMinutes`.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{sanitize_io_error, validate_path, write_atomic};
+use super::keychain::{
+    derive_key, open_with_key, seal_with_key, KdfParams, MAX_KDF_MEMORY_KIB, MIN_PASSPHRASE_CHARS,
+};
+use super::settings;
+
+const MAGIC: &[u8] = b"MORAYAENC";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const CHECK_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN + CHECK_LEN;
+const CHECK_CONTEXT: &[u8] = b"moraya-encrypted-document/key-check";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    check: [u8; CHECK_LEN],
+}
+
+impl Header {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN);
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        for n in [self.kdf.m_cost, self.kdf.t_cost, self.kdf.p_cost] {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.check);
+        out
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, CommandError> {
+        let corrupted = || {
+            CommandError::new(
+                ErrorCode::EncryptedDataCorrupted,
+                "Encrypted document header is damaged",
+            )
+        };
+        if !is_encrypted(bytes) || bytes.len() < HEADER_LEN {
+            return Err(corrupted());
+        }
+        let version = bytes[MAGIC.len()];
+        if version != FORMAT_VERSION {
+            return Err(CommandError::new(
+                ErrorCode::EncryptedDataCorrupted,
+                format!("Unsupported encrypted document version {}", version),
+            ));
+        }
+        let mut rest = &bytes[MAGIC.len() + 1..HEADER_LEN];
+        let mut next_u32 = || {
+            let (n, tail) = rest.split_at(4);
+            rest = tail;
+            u32::from_le_bytes([n[0], n[1], n[2], n[3]])
+        };
+        let kdf = KdfParams {
+            m_cost: next_u32(),
+            t_cost: next_u32(),
+            p_cost: next_u32(),
+        };
+        let (salt, check) = rest.split_at(SALT_LEN);
+        Ok(Self {
+            kdf,
+            salt: salt.try_into().map_err(|_| corrupted())?,
+            check: check.try_into().map_err(|_| corrupted())?,
+        })
+    }
+}
+
+/// Whether `bytes` start with the encrypted-document magic.
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Whether the file at `path` is an encrypted document.
+pub(crate) fn is_encrypted_file(path: &Path) -> bool {
+    let mut head = [0u8; MAGIC.len()];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut head))
+        .is_ok()
+        && is_encrypted(&head)
+}
+
+fn key_check(key: &[u8; 32]) -> [u8; CHECK_LEN] {
+    let digest = Sha256::new()
+        .chain_update(CHECK_CONTEXT)
+        .chain_update(key)
+        .finalize();
+    let mut check = [0u8; CHECK_LEN];
+    check.copy_from_slice(&digest[..CHECK_LEN]);
+    check
+}
+
+/// A derived key together with the header it belongs to.
+#[derive(Clone)]
+struct DocumentKey {
+    key: [u8; 32],
+    header: Header,
+}
+
+impl DocumentKey {
+    /// A key for a fresh salt, used when (re-)encrypting with a passphrase.
+    fn create(passphrase: &str, kdf: KdfParams) -> Result<Self, CommandError> {
+        if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+            return Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Passphrase must be at least {} characters",
+                    MIN_PASSPHRASE_CHARS
+                ),
+            ));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&super::keychain::random_key()[..SALT_LEN]);
+        let key = derive_key(passphrase, &salt, kdf)?;
+        Ok(Self {
+            key,
+            header: Header {
+                kdf,
+                salt,
+                check: key_check(&key),
+            },
+        })
+    }
+
+    /// The key for an existing document's header.
+    fn unlock(passphrase: &str, header: Header) -> Result<Self, CommandError> {
+        if header.kdf.m_cost > MAX_KDF_MEMORY_KIB {
+            return Err(CommandError::new(
+                ErrorCode::EncryptedDataCorrupted,
+                "Encrypted document asks for too much memory",
+            ));
+        }
+        let key = derive_key(passphrase, &header.salt, header.kdf)
+            .map_err(|e| CommandError::new(ErrorCode::EncryptedDataCorrupted, e))?;
+        if key_check(&key) != header.check {
+            return Err(CommandError::new(
+                ErrorCode::WrongPassphrase,
+                "Wrong passphrase",
+            ));
+        }
+        Ok(Self { key, header })
+    }
+
+    fn seal(&self, content: &str) -> Result<Vec<u8>, CommandError> {
+        let mut out = self.header.to_bytes();
+        let sealed = seal_with_key(&self.key, content.as_bytes(), &out)?;
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Decrypt a whole document whose header matches this key.
+    fn open(&self, bytes: &[u8]) -> Result<String, CommandError> {
+        let corrupted = || {
+            CommandError::new(
+                ErrorCode::EncryptedDataCorrupted,
+                "Encrypted document is damaged",
+            )
+        };
+        let (header, sealed) = bytes.split_at(HEADER_LEN.min(bytes.len()));
+        let plaintext = open_with_key(&self.key, sealed, header).map_err(|_| corrupted())?;
+        String::from_utf8(plaintext).map_err(|_| corrupted())
+    }
+}
+
+struct Unlocked {
+    key: DocumentKey,
+    last_used: Instant,
+}
+
+/// Keys of the documents unlocked this session, by canonical path.
+pub struct EncryptionState {
+    unlocked: Mutex<HashMap<PathBuf, Unlocked>>,
+}
+
+impl EncryptionState {
+    pub fn new() -> Self {
+        Self {
+            unlocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The unlocked key for `path`, refreshing its idle timer. Keys idle for
+    /// longer than `auto_lock` are dropped first.
+    fn get(&self, path: &Path, now: Instant, auto_lock: Option<Duration>) -> Option<DocumentKey> {
+        let mut unlocked = self.unlocked.lock().ok()?;
+        if let Some(limit) = auto_lock {
+            unlocked.retain(|_, u| now.saturating_duration_since(u.last_used) < limit);
+        }
+        let entry = unlocked.get_mut(path)?;
+        entry.last_used = now;
+        Some(entry.key.clone())
+    }
+
+    fn remember(&self, path: PathBuf, key: DocumentKey, now: Instant) {
+        if let Ok(mut unlocked) = self.unlocked.lock() {
+            unlocked.insert(
+                path,
+                Unlocked {
+                    key,
+                    last_used: now,
+                },
+            );
+        }
+    }
+
+    fn lock_all(&self) -> usize {
+        self.unlocked
+            .lock()
+            .map(|mut unlocked| {
+                let count = unlocked.len();
+                unlocked.clear();
+                count
+            })
+            .unwrap_or(0)
+    }
+}
+
+impl Default for EncryptionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn auto_lock_after() -> Option<Duration> {
+    let minutes = settings::encryption_auto_lock_minutes();
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+fn locked_error() -> CommandError {
+    CommandError::new(ErrorCode::Encrypted, "Document is encrypted")
+}
+
+/// Run Argon2 off the async runtime.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, CommandError> + Send + 'static,
+) -> Result<T, CommandError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|_| CommandError::from("Key derivation task failed".to_string()))?
+}
+
+/// Decrypt the document at `path`. Without `passphrase` the key unlocked
+/// earlier this session is used, failing with `ENCRYPTED` once it has been
+/// locked.
+#[tauri::command]
+pub async fn read_file_encrypted(
+    state: tauri::State<'_, EncryptionState>,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<String, CommandError> {
+    let safe_path = validate_path(&path)?;
+    let bytes = std::fs::read(&safe_path).map_err(sanitize_io_error)?;
+    if !is_encrypted(&bytes) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Document is not encrypted",
+        ));
+    }
+    let header = Header::parse(&bytes)?;
+    let key = match passphrase {
+        Some(passphrase) => blocking(move || DocumentKey::unlock(&passphrase, header)).await?,
+        None => state
+            .get(&safe_path, Instant::now(), auto_lock_after())
+            // Re-encrypted elsewhere (new salt) since it was unlocked
+            .filter(|k| k.header == header)
+            .ok_or_else(locked_error)?,
+    };
+    let content = key.open(&bytes)?;
+    state.remember(safe_path, key, Instant::now());
+    Ok(content)
+}
+
+/// Encrypt `content` to `path`. With `passphrase` the document gets a fresh
+/// salt and key (this is also how the passphrase is changed); without it the
+/// key unlocked earlier this session is reused.
+#[tauri::command]
+pub async fn write_file_encrypted(
+    state: tauri::State<'_, EncryptionState>,
+    path: String,
+    content: String,
+    passphrase: Option<String>,
+) -> Result<(), CommandError> {
+    let safe_path = validate_path(&path)?;
+    let key = match passphrase {
+        Some(passphrase) => {
+            blocking(move || DocumentKey::create(&passphrase, KdfParams::default())).await?
+        }
+        None => state
+            .get(&safe_path, Instant::now(), auto_lock_after())
+            .ok_or_else(locked_error)?,
+    };
+    let bytes = key.seal(&content)?;
+    write_atomic(&safe_path, &bytes).map_err(sanitize_io_error)?;
+    state.remember(safe_path.clone(), key, Instant::now());
+    super::drafts::document_saved(&safe_path);
+    Ok(())
+}
+
+/// Forget every unlocked key. Returns how many documents were locked.
+#[tauri::command]
+pub fn lock_encrypted_documents(state: tauri::State<'_, EncryptionState>) -> usize {
+    state.lock_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests don't spend seconds in Argon2.
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn should_tell_wrong_passphrase_from_corruption() {
+        let key = DocumentKey::create("correct horse", TEST_KDF).unwrap();
+        let mut bytes = key.seal("# Secret\n\npin 1234").unwrap();
+        assert!(is_encrypted(&bytes));
+
+        let header = Header::parse(&bytes).unwrap();
+        let unlocked = DocumentKey::unlock("correct horse", header).unwrap();
+        assert_eq!(unlocked.open(&bytes).unwrap(), "# Secret\n\npin 1234");
+        assert_eq!(
+            DocumentKey::unlock("wrong horse", header)
+                .err()
+                .unwrap()
+                .code,
+            ErrorCode::WrongPassphrase
+        );
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(
+            unlocked.open(&bytes).unwrap_err().code,
+            ErrorCode::EncryptedDataCorrupted
+        );
+        assert_eq!(
+            Header::parse(&bytes[..HEADER_LEN - 1]).unwrap_err().code,
+            ErrorCode::EncryptedDataCorrupted
+        );
+        assert_eq!(
+            DocumentKey::create("short", TEST_KDF).err().unwrap().code,
+            ErrorCode::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn should_forget_keys_after_idle_timeout() {
+        let state = EncryptionState::new();
+        let key = DocumentKey::create("correct horse", TEST_KDF).unwrap();
+        let path = PathBuf::from("/notes/secret.md");
+        let start = Instant::now();
+        let limit = Some(Duration::from_secs(60));
+        state.remember(path.clone(), key, start);
+
+        // Each use restarts the idle timer
+        assert!(state
+            .get(&path, start + Duration::from_secs(50), limit)
+            .is_some());
+        assert!(state
+            .get(&path, start + Duration::from_secs(100), limit)
+            .is_some());
+        assert!(state
+            .get(&path, start + Duration::from_secs(200), limit)
+            .is_none());
+
+        let key = DocumentKey::create("correct horse", TEST_KDF).unwrap();
+        state.remember(path.clone(), key, start);
+        assert!(state
+            .get(&path, start + Duration::from_secs(999), None)
+            .is_some());
+        assert_eq!(state.lock_all(), 1);
+        assert!(state.get(&path, start, None).is_none());
+    }
+}
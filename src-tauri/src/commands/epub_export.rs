@@ -0,0 +1,1077 @@
+//! EPUB 3 export. Each markdown document becomes one XHTML chapter, locally
+//! referenced images are embedded, and the navigation document is built
+//! from the chapters' headings.
+//!
+//! Problems with individual files (unreadable documents, missing images,
+//! raw HTML, links leaving the book) are collected as warnings; the export
+//! only fails when no document could be read at all.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::error::{CommandError, ErrorCode};
+use super::export_preset::{Preset, PresetUsage};
+use super::frontmatter;
+use crate::commands::file as file_cmd;
+
+/// Deepest heading level listed in the table of contents.
+const TOC_DEPTH: u8 = 3;
+const DEFAULT_LANGUAGE: &str = "en";
+
+const STYLESHEET: &str = "\
+body { font-family: serif; line-height: 1.5; margin: 0 5%; }
+h1, h2, h3, h4, h5, h6 { font-family: sans-serif; line-height: 1.25; page-break-after: avoid; }
+img { max-width: 100%; }
+pre { white-space: pre-wrap; font-size: 0.85em; background: #f4f4f4; padding: 0.5em; }
+code { font-family: monospace; }
+blockquote { margin: 0 0 0 1em; padding-left: 1em; border-left: 3px solid #ccc; color: #555; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; }
+.footnote-definition { font-size: 0.9em; }
+body.cover { margin: 0; text-align: center; }
+body.cover img { max-height: 100%; }
+";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EpubMetadata {
+    /// Falls back to the first chapter's title.
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// BCP 47 tag, `en` when missing or malformed.
+    pub language: Option<String>,
+    /// Path of the cover image.
+    pub cover: Option<String>,
+    /// Defaults to a `urn:uuid:` derived from title, author and documents,
+    /// so re-exports of the same book keep their identity on e-readers.
+    pub identifier: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EpubWarning {
+    /// Document (or cover) path the warning is about, as passed in; empty
+    /// for metadata problems.
+    pub file: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubExportResult {
+    pub chapters: usize,
+    pub images: usize,
+    pub warnings: Vec<EpubWarning>,
+    /// What the first document's export preset set.
+    pub preset: PresetUsage,
+}
+
+struct Document {
+    /// Path as passed in, for warnings.
+    label: String,
+    path: PathBuf,
+    markdown: String,
+    href: String,
+}
+
+struct TocEntry {
+    level: u8,
+    title: String,
+    href: String,
+}
+
+struct Chapter {
+    href: String,
+    title: String,
+    toc: Vec<TocEntry>,
+    body: String,
+}
+
+struct BookImage {
+    href: String,
+    media_type: &'static str,
+    data: Vec<u8>,
+}
+
+struct Book<F> {
+    access: F,
+    images: Vec<BookImage>,
+    /// Canonical path (or content hash for data URIs) to index in `images`.
+    image_ids: HashMap<String, usize>,
+    warnings: Vec<EpubWarning>,
+}
+
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// GitHub-style heading anchor, so `other.md#some-heading` links keep
+/// working. Ids must be XML names, hence the prefix for leading digits.
+fn slug(text: &str) -> String {
+    let slug: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            c if c.is_whitespace() => Some('-'),
+            _ => None,
+        })
+        .collect();
+    if slug.is_empty() {
+        "section".to_string()
+    } else if slug.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        slug
+    } else {
+        format!("h-{}", slug)
+    }
+}
+
+fn unique_id(base: String, used: &mut HashSet<String>) -> String {
+    let mut id = base.clone();
+    let mut n = 1;
+    while !used.insert(id.clone()) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+fn footnote_id(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("fn-{}", name)
+}
+
+pub(crate) fn is_line_break(html: &str) -> bool {
+    matches!(
+        html.trim().to_ascii_lowercase().as_str(),
+        "<br>" | "<br/>" | "<br />"
+    )
+}
+
+/// Media type and extension of an image the EPUB 3 core media types allow.
+pub(crate) fn image_type(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(("image/png", "png"));
+    }
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(("image/jpeg", "jpg"));
+    }
+    if data.starts_with(b"GIF8") {
+        return Some(("image/gif", "gif"));
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some(("image/webp", "webp"));
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    if head.starts_with('<') && head.contains("<svg") {
+        return Some(("image/svg+xml", "svg"));
+    }
+    None
+}
+
+fn decode_data_uri(data: &str) -> Option<Vec<u8>> {
+    let (meta, payload) = data.split_once(',')?;
+    if !meta.ends_with(";base64") {
+        return None;
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(payload.trim())
+        .ok()
+}
+
+fn language_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim();
+    let primary = tag.split('-').next()?;
+    let valid = (2..=8).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && tag.split('-').all(|part| {
+            (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    valid.then(|| tag.to_string())
+}
+
+/// `urn:uuid:` from a SHA-256 of `parts`, with the version 8 (custom) and
+/// RFC 4122 variant bits set.
+fn derived_identifier(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    let mut b: [u8; 16] = hasher.finalize()[..16].try_into().unwrap_or([0; 16]);
+    b[6] = (b[6] & 0x0f) | 0x80;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl<F> Book<F>
+where
+    F: Fn(&str) -> Result<PathBuf, CommandError>,
+{
+    fn warn(&mut self, file: &str, message: impl Into<String>) {
+        self.warnings.push(EpubWarning {
+            file: file.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Add the image at `src` (local path or base64 data URI) to the book
+    /// once, returning its index.
+    fn embed_image(&mut self, src: &str, base_dir: Option<&Path>) -> Result<usize, String> {
+        let src = src.trim();
+        let (key, data, label) = if let Some(data) = src.strip_prefix("data:") {
+            let data = decode_data_uri(data).ok_or("Inline image is not base64 encoded")?;
+            (
+                format!("data:{:x}", Sha256::digest(&data)),
+                data,
+                "Inline image",
+            )
+        } else {
+            let path = file_cmd::resolve_document_reference(src, base_dir)
+                .ok_or_else(|| format!("Image {} is not a local file and was left out", src))?;
+            let path = (self.access)(&path.to_string_lossy())
+                .map_err(|e| format!("Image {}: {}", src, e.message))?;
+            let key = path.to_string_lossy().into_owned();
+            if let Some(&index) = self.image_ids.get(&key) {
+                return Ok(index);
+            }
+            let data = std::fs::read(&path).map_err(|e| {
+                format!("Image {}: {}", src, file_cmd::sanitize_io_error(e).message)
+            })?;
+            (key, data, src)
+        };
+        if let Some(&index) = self.image_ids.get(&key) {
+            return Ok(index);
+        }
+        let (media_type, ext) = image_type(&data).ok_or_else(|| {
+            format!(
+                "{} is not a PNG, JPEG, GIF, WebP or SVG image and was left out",
+                label
+            )
+        })?;
+        let index = self.images.len();
+        self.images.push(BookImage {
+            href: format!("images/image-{:03}.{}", index + 1, ext),
+            media_type,
+            data,
+        });
+        self.image_ids.insert(key, index);
+        Ok(index)
+    }
+
+    /// Target of a link in `doc`: external and in-page links unchanged,
+    /// links to other documents of the book pointed at their chapter.
+    fn link_target(
+        &self,
+        dest: &str,
+        doc: &Document,
+        chapters: &HashMap<PathBuf, String>,
+    ) -> Result<String, String> {
+        if dest.is_empty() || dest.starts_with('#') {
+            return Ok(dest.to_string());
+        }
+        if !dest.starts_with("file:") {
+            // A one-letter "scheme" is a Windows drive
+            if let Ok(url) = url::Url::parse(dest) {
+                if url.scheme().len() > 1 {
+                    return Ok(dest.to_string());
+                }
+            }
+        }
+        let (path, fragment) = match dest.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (dest, None),
+        };
+        let href = file_cmd::resolve_document_reference(path, doc.path.parent())
+            .and_then(|path| (self.access)(&path.to_string_lossy()).ok())
+            .and_then(|path| chapters.get(&path))
+            .ok_or_else(|| format!("Link to {} points outside the book; kept as text", dest))?;
+        Ok(match fragment {
+            Some(fragment) if *href == doc.href => format!("#{}", fragment),
+            Some(fragment) => format!("{}#{}", href, fragment),
+            None => href.clone(),
+        })
+    }
+
+    fn convert(&mut self, doc: &Document, chapters: &HashMap<PathBuf, String>) -> Chapter {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES;
+        let base_dir = doc.path.parent();
+        let mut out: Vec<Event> = Vec::new();
+        // Heading events are held back until the text (and so the id) is known
+        let mut heading: Option<(Vec<Event>, String)> = None;
+        let mut ids = HashSet::new();
+        let mut toc = Vec::new();
+        let mut first_heading = None;
+        // Whether each open link / image was kept
+        let mut links = Vec::new();
+        let mut images = Vec::new();
+        let mut dropped_html = false;
+
+        // Frontmatter holds settings, not content
+        let body = &doc.markdown[frontmatter::body_start(&doc.markdown)..];
+        for event in Parser::new_ext(body, options) {
+            let event = match event {
+                Event::Start(Tag::Heading { .. }) => {
+                    heading = Some((Vec::new(), String::new()));
+                    continue;
+                }
+                Event::End(TagEnd::Heading(level)) => {
+                    let Some((events, text)) = heading.take() else {
+                        continue;
+                    };
+                    let text = text.trim().to_string();
+                    let id = unique_id(slug(&text), &mut ids);
+                    if !text.is_empty() {
+                        first_heading.get_or_insert_with(|| text.clone());
+                        if level as u8 <= TOC_DEPTH {
+                            toc.push(TocEntry {
+                                level: level as u8,
+                                title: text,
+                                href: format!("{}#{}", doc.href, id),
+                            });
+                        }
+                    }
+                    out.push(Event::Start(Tag::Heading {
+                        level,
+                        id: Some(id.into()),
+                        classes: Vec::new(),
+                        attrs: Vec::new(),
+                    }));
+                    out.extend(events);
+                    out.push(Event::End(TagEnd::Heading(level)));
+                    continue;
+                }
+                Event::Html(html) | Event::InlineHtml(html) => {
+                    if is_line_break(&html) {
+                        Event::HardBreak
+                    } else {
+                        if !dropped_html {
+                            dropped_html = true;
+                            self.warn(&doc.label, "Raw HTML is not supported and was left out");
+                        }
+                        continue;
+                    }
+                }
+                Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => match self.embed_image(&dest_url, base_dir) {
+                    Ok(index) => {
+                        images.push(true);
+                        Event::Start(Tag::Image {
+                            link_type,
+                            dest_url: format!("../{}", self.images[index].href).into(),
+                            title,
+                            id,
+                        })
+                    }
+                    Err(message) => {
+                        // The alt text stays as plain text
+                        self.warn(&doc.label, message);
+                        images.push(false);
+                        continue;
+                    }
+                },
+                Event::End(TagEnd::Image) => {
+                    if !images.pop().unwrap_or(false) {
+                        continue;
+                    }
+                    Event::End(TagEnd::Image)
+                }
+                Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => match self.link_target(&dest_url, doc, chapters) {
+                    Ok(href) => {
+                        links.push(true);
+                        Event::Start(Tag::Link {
+                            link_type,
+                            dest_url: href.into(),
+                            title,
+                            id,
+                        })
+                    }
+                    Err(message) => {
+                        self.warn(&doc.label, message);
+                        links.push(false);
+                        continue;
+                    }
+                },
+                Event::End(TagEnd::Link) => {
+                    if !links.pop().unwrap_or(false) {
+                        continue;
+                    }
+                    Event::End(TagEnd::Link)
+                }
+                // Footnote names become ids, which must be XML names
+                Event::FootnoteReference(name) => {
+                    Event::FootnoteReference(CowStr::from(footnote_id(&name)))
+                }
+                Event::Start(Tag::FootnoteDefinition(name)) => {
+                    Event::Start(Tag::FootnoteDefinition(CowStr::from(footnote_id(&name))))
+                }
+                event => event,
+            };
+            match heading.as_mut() {
+                Some((events, text)) => {
+                    if let Event::Text(t) | Event::Code(t) = &event {
+                        text.push_str(t);
+                    }
+                    events.push(event);
+                }
+                None => out.push(event),
+            }
+        }
+
+        let mut body = String::new();
+        pulldown_cmark::html::push_html(&mut body, out.into_iter());
+        let title = first_heading.unwrap_or_else(|| {
+            doc.path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| doc.href.clone())
+        });
+        // Each chapter's top headings start at the top of the TOC
+        if let Some(top) = toc.iter().map(|e| e.level).min() {
+            for entry in &mut toc {
+                entry.level -= top - 1;
+            }
+        } else {
+            toc.push(TocEntry {
+                level: 1,
+                title: title.clone(),
+                href: doc.href.clone(),
+            });
+        }
+        Chapter {
+            href: doc.href.clone(),
+            title,
+            toc,
+            body,
+        }
+    }
+}
+
+fn xhtml_page(title: &str, language: &str, body_class: Option<&str>, body: &str) -> String {
+    let lang = xml_escape(language);
+    let class = body_class
+        .map(|c| format!(" class=\"{}\"", c))
+        .unwrap_or_default();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"{lang}\" xml:lang=\"{lang}\">\n\
+         <head>\n<meta charset=\"UTF-8\"/>\n<title>{title}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"../style.css\"/>\n</head>\n\
+         <body{class}>\n{body}</body>\n</html>\n",
+        title = xml_escape(title),
+    )
+}
+
+/// Nested `<ol>` for a flat list of TOC entries: each entry owns the
+/// deeper entries that follow it.
+fn nav_list(entries: &[TocEntry], out: &mut String) {
+    if entries.is_empty() {
+        return;
+    }
+    out.push_str("<ol>\n");
+    let mut i = 0;
+    while i < entries.len() {
+        let end = entries[i + 1..]
+            .iter()
+            .position(|e| e.level <= entries[i].level)
+            .map_or(entries.len(), |p| i + 1 + p);
+        out.push_str(&format!(
+            "<li><a href=\"text/{}\">{}</a>\n",
+            xml_escape(&entries[i].href),
+            xml_escape(&entries[i].title)
+        ));
+        nav_list(&entries[i + 1..end], out);
+        out.push_str("</li>\n");
+        i = end;
+    }
+    out.push_str("</ol>\n");
+}
+
+fn nav_document(title: &str, language: &str, chapters: &[Chapter]) -> String {
+    let entries: Vec<TocEntry> = chapters
+        .iter()
+        .flat_map(|c| {
+            c.toc.iter().map(|e| TocEntry {
+                level: e.level,
+                title: e.title.clone(),
+                href: e.href.clone(),
+            })
+        })
+        .collect();
+    let mut list = String::new();
+    nav_list(&entries, &mut list);
+    let lang = xml_escape(language);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"{lang}\" xml:lang=\"{lang}\">\n\
+         <head>\n<meta charset=\"UTF-8\"/>\n<title>{title}</title>\n</head>\n\
+         <body>\n<nav epub:type=\"toc\" id=\"toc\">\n<h1>{title}</h1>\n{list}</nav>\n</body>\n</html>\n",
+        title = xml_escape(title),
+    )
+}
+
+struct Package<'a> {
+    identifier: &'a str,
+    title: &'a str,
+    author: Option<&'a str>,
+    language: &'a str,
+    cover: Option<usize>,
+    chapters: &'a [Chapter],
+    images: &'a [BookImage],
+}
+
+fn package_document(p: &Package) -> String {
+    let mut metadata = format!(
+        "<dc:identifier id=\"book-id\">{}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n<dc:language>{}</dc:language>\n",
+        xml_escape(p.identifier),
+        xml_escape(p.title),
+        xml_escape(p.language)
+    );
+    if let Some(author) = p.author {
+        metadata.push_str(&format!(
+            "<dc:creator>{}</dc:creator>\n",
+            xml_escape(author)
+        ));
+    }
+    metadata.push_str(&format!(
+        "<meta property=\"dcterms:modified\">{}</meta>\n",
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    ));
+
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    if let Some(cover) = p.cover {
+        // EPUB 2 readers look for the cover through this meta
+        metadata.push_str(&format!(
+            "<meta name=\"cover\" content=\"image-{:03}\"/>\n",
+            cover + 1
+        ));
+        manifest.push_str(
+            "<item id=\"cover\" href=\"text/cover.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+        );
+        spine.push_str("<itemref idref=\"cover\"/>\n");
+    }
+    for (i, chapter) in p.chapters.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"chapter-{:03}\" href=\"text/{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            chapter.href
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter-{:03}\"/>\n", i + 1));
+    }
+    for (i, image) in p.images.iter().enumerate() {
+        let properties = if p.cover == Some(i) {
+            " properties=\"cover-image\""
+        } else {
+            ""
+        };
+        manifest.push_str(&format!(
+            "<item id=\"image-{:03}\" href=\"{}\" media-type=\"{}\"{}/>\n",
+            i + 1,
+            image.href,
+            image.media_type,
+            properties
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{}\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{}</metadata>\n\
+         <manifest>\n{}</manifest>\n<spine>\n{}</spine>\n</package>\n",
+        xml_escape(p.language),
+        metadata,
+        manifest,
+        spine
+    )
+}
+
+const CONTAINER_XML: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n</rootfiles>\n\
+</container>\n";
+
+fn write_container(files: Vec<(String, Vec<u8>)>) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // Readers sniff the mimetype at a fixed offset: first entry, uncompressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, data) in files {
+        let compressed = !name.starts_with("OEBPS/images/") || name.ends_with(".svg");
+        zip.start_file(name, if compressed { deflated } else { stored })?;
+        zip.write_all(&data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Build the EPUB for `paths`. `access` checks and canonicalizes every
+/// path read (documents, images, cover).
+pub(crate) fn build_epub<F>(
+    paths: &[String],
+    metadata: &EpubMetadata,
+    access: F,
+) -> Result<(Vec<u8>, EpubExportResult), CommandError>
+where
+    F: Fn(&str) -> Result<PathBuf, CommandError>,
+{
+    let mut book = Book {
+        access,
+        images: Vec::new(),
+        image_ids: HashMap::new(),
+        warnings: Vec::new(),
+    };
+
+    // Read everything up front so links between documents can be rewritten
+    let mut docs = Vec::new();
+    for label in paths {
+        let read = (book.access)(label).and_then(|path| {
+            std::fs::read_to_string(&path)
+                .map(|markdown| (path, markdown))
+                .map_err(file_cmd::sanitize_io_error)
+        });
+        match read {
+            Ok((path, markdown)) => docs.push(Document {
+                label: label.clone(),
+                path,
+                markdown,
+                href: format!("chapter-{:03}.xhtml", docs.len() + 1),
+            }),
+            Err(e) => book.warn(label, format!("Skipped: {}", e.message)),
+        }
+    }
+    if docs.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "None of the documents could be read",
+        )
+        .with_details(serde_json::json!({ "warnings": book.warnings })));
+    }
+
+    // The first document's preset applies to the whole book, over `metadata`
+    let mut preset = Preset::read(&docs[0].markdown);
+    let mut metadata = metadata.clone();
+    preset.apply_epub(&mut metadata, docs[0].path.parent());
+    let preset = preset.finish("EPUB");
+    let metadata = &metadata;
+
+    let chapter_hrefs: HashMap<PathBuf, String> = docs
+        .iter()
+        .map(|d| (d.path.clone(), d.href.clone()))
+        .collect();
+
+    let language = match metadata
+        .language
+        .as_deref()
+        .filter(|l| !l.trim().is_empty())
+    {
+        None => DEFAULT_LANGUAGE.to_string(),
+        Some(language) => language_tag(language).unwrap_or_else(|| {
+            book.warn(
+                "",
+                format!(
+                    "Invalid language tag {:?}; using \"{}\"",
+                    language, DEFAULT_LANGUAGE
+                ),
+            );
+            DEFAULT_LANGUAGE.to_string()
+        }),
+    };
+    let cover = match metadata.cover.as_deref().filter(|c| !c.trim().is_empty()) {
+        None => None,
+        Some(cover) => match book.embed_image(cover, None) {
+            Ok(index) => Some(index),
+            Err(message) => {
+                book.warn(cover, message);
+                None
+            }
+        },
+    };
+
+    let chapters: Vec<Chapter> = docs
+        .iter()
+        .map(|doc| book.convert(doc, &chapter_hrefs))
+        .collect();
+
+    let title = metadata
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or(&chapters[0].title)
+        .to_string();
+    let author = metadata
+        .author
+        .as_deref()
+        .map(str::trim)
+        .filter(|a| !a.is_empty());
+    let identifier = match metadata.identifier.as_deref().map(str::trim) {
+        Some(id) if !id.is_empty() => id.to_string(),
+        _ => {
+            let mut parts = vec![title.as_str(), author.unwrap_or("")];
+            let doc_paths: Vec<String> = docs
+                .iter()
+                .map(|d| d.path.to_string_lossy().into_owned())
+                .collect();
+            parts.extend(doc_paths.iter().map(String::as_str));
+            derived_identifier(&parts)
+        }
+    };
+
+    let mut files = vec![
+        ("META-INF/container.xml".to_string(), CONTAINER_XML.into()),
+        (
+            "OEBPS/content.opf".to_string(),
+            package_document(&Package {
+                identifier: &identifier,
+                title: &title,
+                author,
+                language: &language,
+                cover,
+                chapters: &chapters,
+                images: &book.images,
+            })
+            .into_bytes(),
+        ),
+        (
+            "OEBPS/nav.xhtml".to_string(),
+            nav_document(&title, &language, &chapters).into_bytes(),
+        ),
+        ("OEBPS/style.css".to_string(), STYLESHEET.into()),
+    ];
+    if let Some(cover) = cover {
+        let body = format!(
+            "<img src=\"../{}\" alt=\"{}\"/>\n",
+            book.images[cover].href,
+            xml_escape(&title)
+        );
+        files.push((
+            "OEBPS/text/cover.xhtml".to_string(),
+            xhtml_page(&title, &language, Some("cover"), &body).into_bytes(),
+        ));
+    }
+    for chapter in &chapters {
+        files.push((
+            format!("OEBPS/text/{}", chapter.href),
+            xhtml_page(&chapter.title, &language, None, &chapter.body).into_bytes(),
+        ));
+    }
+    let image_count = book.images.len();
+    for image in book.images {
+        files.push((format!("OEBPS/{}", image.href), image.data));
+    }
+
+    let bytes = write_container(files)
+        .map_err(|e| CommandError::from(format!("Failed to write EPUB: {}", e)))?;
+    Ok((
+        bytes,
+        EpubExportResult {
+            chapters: chapters.len(),
+            images: image_count,
+            warnings: book.warnings,
+            preset,
+        },
+    ))
+}
+
+/// Export `document_paths` (in order, one chapter each) as an EPUB 3 book
+/// at `output_path`. Per-file problems come back as `warnings`.
+#[tauri::command]
+pub async fn export_epub(
+    document_paths: Vec<String>,
+    output_path: String,
+    metadata: EpubMetadata,
+) -> Result<EpubExportResult, CommandError> {
+    let output = file_cmd::validate_path(&output_path)?;
+    if document_paths.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "No documents to export",
+        ));
+    }
+    let (bytes, result) = tokio::task::spawn_blocking(move || {
+        build_epub(&document_paths, &metadata, file_cmd::validate_path)
+    })
+    .await
+    .map_err(|e| CommandError::from(format!("EPUB export task failed: {}", e)))??;
+    std::fs::write(&output, bytes).map_err(file_cmd::sanitize_io_error)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // 1x1 transparent PNG
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("moraya-epub-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("img")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn access(path: &str) -> Result<PathBuf, CommandError> {
+        Path::new(path)
+            .canonicalize()
+            .map_err(file_cmd::sanitize_io_error)
+    }
+
+    fn sample_book(dir: &Path) -> Vec<String> {
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(PNG)
+            .unwrap();
+        std::fs::write(dir.join("img/dot.png"), png).unwrap();
+        std::fs::write(
+            dir.join("one.md"),
+            "# First Chapter\n\nSee [the second](two.md#deep-dive) and \
+             [home](https://example.com).\n\n![dot](img/dot.png)\n\n\
+             ## Details & more\n\n- [x] done\n- [ ] todo\n\n\
+             | a | b |\n|---|---|\n| 1 | 2 |\n\nNote[^1].\n\n[^1]: A footnote.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("two.md"),
+            "# Second\n\n## Deep dive\n\nSame dot: ![again](img/dot.png)\n\n\
+             ### 3 steps\n\nline one<br>line two\n",
+        )
+        .unwrap();
+        vec![
+            dir.join("one.md").to_string_lossy().into_owned(),
+            dir.join("two.md").to_string_lossy().into_owned(),
+        ]
+    }
+
+    fn entry(epub: &[u8], name: &str) -> String {
+        let mut archive = zip::ZipArchive::new(Cursor::new(epub)).unwrap();
+        let mut file = archive.by_name(name).unwrap();
+        let mut text = String::new();
+        file.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn should_build_epub_container() {
+        let dir = test_dir("container");
+        let paths = sample_book(&dir);
+        let metadata = EpubMetadata {
+            title: Some("Notes".into()),
+            author: Some("A. Writer".into()),
+            language: Some("de".into()),
+            cover: Some(dir.join("img/dot.png").to_string_lossy().into_owned()),
+            identifier: None,
+        };
+        let (epub, result) = build_epub(&paths, &metadata, access).unwrap();
+        assert_eq!(result.chapters, 2);
+        assert_eq!(result.images, 1);
+        assert_eq!(result.warnings, Vec::new());
+
+        // mimetype: first entry, stored, no extra field
+        assert_eq!(&epub[30..38], b"mimetype");
+        assert_eq!(&epub[8..10], &[0, 0]);
+        assert_eq!(&epub[38..58], b"application/epub+zip");
+
+        let opf = entry(&epub, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>Notes</dc:title>"));
+        assert!(opf.contains("<dc:creator>A. Writer</dc:creator>"));
+        assert!(opf.contains("<dc:language>de</dc:language>"));
+        assert!(opf.contains("urn:uuid:"));
+        assert!(opf.contains("properties=\"cover-image\""));
+        assert!(opf.contains("<itemref idref=\"cover\"/>\n<itemref idref=\"chapter-001\"/>"));
+
+        let nav = entry(&epub, "OEBPS/nav.xhtml");
+        assert!(nav.contains("href=\"text/chapter-001.xhtml#first-chapter\">First Chapter</a>"));
+        assert!(nav.contains(">Details &amp; more</a>"));
+        assert!(nav.contains("href=\"text/chapter-002.xhtml#h-3-steps\""));
+
+        let one = entry(&epub, "OEBPS/text/chapter-001.xhtml");
+        assert!(one.contains("href=\"chapter-002.xhtml#deep-dive\""));
+        assert!(one.contains("href=\"https://example.com\""));
+        assert!(one.contains("src=\"../images/image-001.png\""));
+        assert!(one.contains("id=\"fn-1\""));
+        let two = entry(&epub, "OEBPS/text/chapter-002.xhtml");
+        assert!(two.contains("line one<br />"));
+        assert!(two.contains("src=\"../images/image-001.png\""));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_report_per_file_warnings() {
+        let dir = test_dir("warnings");
+        std::fs::write(
+            dir.join("doc.md"),
+            "# Doc\n\n![remote](https://example.com/a.png) ![gone](img/gone.png)\n\n\
+             <div>raw</div>\n\n<span>x</span> [out](../elsewhere.md)\n",
+        )
+        .unwrap();
+        let doc = dir.join("doc.md").to_string_lossy().into_owned();
+        let missing = dir.join("missing.md").to_string_lossy().into_owned();
+        let paths = vec![missing.clone(), doc.clone()];
+        let (epub, result) = build_epub(&paths, &EpubMetadata::default(), access).unwrap();
+        assert_eq!(result.chapters, 1);
+        let files: Vec<&str> = result.warnings.iter().map(|w| w.file.as_str()).collect();
+        assert_eq!(
+            files,
+            vec![missing.as_str(), doc.as_str(), &doc, &doc, &doc]
+        );
+        assert!(result.warnings[1].message.contains("not a local file"));
+
+        let text = entry(&epub, "OEBPS/text/chapter-001.xhtml");
+        assert!(text.contains("remote"));
+        assert!(!text.contains("<div>"));
+        assert!(text.contains("out"));
+        assert!(!text.contains("elsewhere"));
+        // Untitled books take the first chapter's title
+        assert!(entry(&epub, "OEBPS/content.opf").contains("<dc:title>Doc</dc:title>"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_fail_when_no_document_can_be_read() {
+        let dir = test_dir("empty");
+        let paths = vec![dir.join("nope.md").to_string_lossy().into_owned()];
+        let err = build_epub(&paths, &EpubMetadata::default(), access).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_nest_toc_entries() {
+        let entries: Vec<TocEntry> = [(2, "a"), (3, "b"), (2, "c"), (1, "d")]
+            .iter()
+            .map(|&(level, title)| TocEntry {
+                level,
+                title: title.into(),
+                href: format!("x.xhtml#{}", title),
+            })
+            .collect();
+        let mut out = String::new();
+        nav_list(&entries, &mut out);
+        let compact: String = out.lines().collect();
+        assert_eq!(
+            compact,
+            "<ol><li><a href=\"text/x.xhtml#a\">a</a>\
+             <ol><li><a href=\"text/x.xhtml#b\">b</a></li></ol></li>\
+             <li><a href=\"text/x.xhtml#c\">c</a></li>\
+             <li><a href=\"text/x.xhtml#d\">d</a></li></ol>"
+        );
+    }
+
+    #[test]
+    fn should_make_heading_ids_valid_and_unique() {
+        let mut used = HashSet::new();
+        assert_eq!(unique_id(slug("Hello, World!"), &mut used), "hello-world");
+        assert_eq!(unique_id(slug("Hello World"), &mut used), "hello-world-1");
+        assert_eq!(unique_id(slug("2024 plans"), &mut used), "h-2024-plans");
+        assert_eq!(unique_id(slug("概要"), &mut used), "概要");
+        assert_eq!(unique_id(slug("!!!"), &mut used), "section");
+        assert_eq!(footnote_id("my note"), "fn-my-note");
+    }
+
+    #[test]
+    fn should_validate_language_tags() {
+        assert_eq!(language_tag("zh-Hans"), Some("zh-Hans".into()));
+        assert_eq!(language_tag("en"), Some("en".into()));
+        assert_eq!(language_tag("e"), None);
+        assert_eq!(language_tag("en_US"), None);
+    }
+
+    /// Runs `epubcheck` on the sample book when it is installed; CI sets
+    /// `MORAYA_REQUIRE_EPUBCHECK=1` so a missing checker fails the test.
+    #[test]
+    fn should_pass_epubcheck() {
+        let available = std::process::Command::new("epubcheck")
+            .arg("--version")
+            .output()
+            .is_ok();
+        if !available {
+            assert!(
+                std::env::var("MORAYA_REQUIRE_EPUBCHECK").is_err(),
+                "epubcheck is required but not installed"
+            );
+            return;
+        }
+        let dir = test_dir("epubcheck");
+        let paths = sample_book(&dir);
+        let metadata = EpubMetadata {
+            title: Some("Sample".into()),
+            author: Some("Moraya".into()),
+            cover: Some(dir.join("img/dot.png").to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let (epub, _) = build_epub(&paths, &metadata, access).unwrap();
+        let file = dir.join("sample.epub");
+        std::fs::write(&file, epub).unwrap();
+        let output = std::process::Command::new("epubcheck")
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "epubcheck failed:\n{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
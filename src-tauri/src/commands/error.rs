@@ -0,0 +1,204 @@
+//! Structured command errors, serialized as
+//! `{ code: "FILE_EXISTS", message: "File already exists", details?: {...} }`.
+//!
+//! `code` is stable and what the frontend branches on and localizes;
+//! `message` is an English fallback for codes the UI doesn't know yet.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Anything without a more specific code; `message` says what.
+    Internal,
+    InvalidArgument,
+
+    // Files
+    FileNotFound,
+    FileExists,
+    PermissionDenied,
+    /// Path outside the directories Moraya may access.
+    AccessDenied,
+    InvalidPath,
+    NotADirectory,
+    IoError,
+    /// The file itself is marked read-only; `make_writable` can clear that
+    /// once the user agrees.
+    ReadOnlyFile,
+    /// The file lives on a read-only volume or mount.
+    ReadOnlyVolume,
+    /// Cloud placeholder whose content is still being downloaded;
+    /// `details.retryAfterMs` says when to try again.
+    FileDownloading,
+
+    // Encrypted documents
+    /// The document is encrypted and no unlocked key is available; the UI
+    /// should ask for the passphrase.
+    Encrypted,
+    WrongPassphrase,
+    /// The passphrase is right but the ciphertext or header is damaged.
+    EncryptedDataCorrupted,
+
+    // MCP servers
+    McpInvalidCommand,
+    McpUnsupported,
+    McpStartFailed,
+    McpNotConnected,
+    McpWriteFailed,
+    McpTimeout,
+    McpServerExited,
+    McpProtocolError,
+    CommandNotFound,
+
+    // AI proxy
+    AiAborted,
+    /// Provider answered with an error status; `details.status` has it.
+    AiHttpError,
+    AiNetworkError,
+    AiTimeout,
+    /// Error object embedded in an otherwise successful SSE stream.
+    AiProviderError,
+
+    // Plugins
+    NetworkError,
+    PluginNotFound,
+    PluginNotRunning,
+    PluginInvalidId,
+    PluginInvalidManifest,
+    PluginInvalidPackage,
+    PluginSignatureMissing,
+    PluginSignatureInvalid,
+    PluginIntegrityFailed,
+    PluginBlacklisted,
+    PluginUnsupportedPlatform,
+    PluginRuntimeMissing,
+    PluginFilesMissing,
+    PluginStartFailed,
+    PluginIncompatible,
+    PluginPermissionDenied,
+    PluginExited,
+    PluginTimeout,
+    PluginNoData,
+
+    // Diagram rendering
+    /// Renderer executable not on PATH; `details.renderer` names it.
+    DiagramRendererMissing,
+    DiagramRenderFailed,
+    DiagramTimeout,
+    DiagramTooLarge,
+
+    // Publishing to git repositories
+    GitNotRepository,
+    /// Merge, rebase, cherry-pick or revert in progress, or unmerged paths.
+    GitConflictInProgress,
+    /// `details` has `command`, git's sanitized `stderr` and, when a push
+    /// fails after committing, the `commit` hash.
+    GitCommandFailed,
+    GitTimeout,
+
+    // Web clipper
+    /// Neither `clippingsFile` nor `clippingsFolder` is set.
+    ClipperNotConfigured,
+    /// The port is taken or can't be bound.
+    ClipperStartFailed,
+
+    // Backups
+    /// `backupSourceDir` or `backupTargetDir` is not set.
+    BackupNotConfigured,
+    /// `details.reason` has the underlying error.
+    BackupFailed,
+
+    // File tags
+    /// The volume or platform has no place to keep tags.
+    FileTagsUnsupported,
+
+    // Windows
+    /// The calling window isn't one the command group is open to; see
+    /// `window_access`.
+    WindowNotAllowed,
+
+    // Directory scans
+    /// Stopped by `cancel_dir_scan`.
+    DirScanCancelled,
+    /// The tree has more entries than `dirScanMaxEntries`; `details.limit`
+    /// has the ceiling.
+    TooManyEntries,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Lets helpers that still return `String` errors feed into commands
+/// with `?`; they surface as `INTERNAL`.
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+/// For commands outside the converted modules that still return
+/// `Result<_, String>` and call into them (e.g. `validate_path`).
+impl From<CommandError> for String {
+    fn from(e: CommandError) -> Self {
+        e.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_serialize_code_message_and_details() {
+        let e = CommandError::new(ErrorCode::AiHttpError, "API error (401): bad key")
+            .with_details(serde_json::json!({ "status": 401 }));
+        assert_eq!(
+            serde_json::to_value(&e).unwrap(),
+            serde_json::json!({
+                "code": "AI_HTTP_ERROR",
+                "message": "API error (401): bad key",
+                "details": { "status": 401 }
+            })
+        );
+        let e = CommandError::new(ErrorCode::FileExists, "File already exists");
+        assert_eq!(
+            serde_json::to_string(&e).unwrap(),
+            r#"{"code":"FILE_EXISTS","message":"File already exists"}"#
+        );
+    }
+}
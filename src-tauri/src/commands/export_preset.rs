@@ -0,0 +1,383 @@
+//! Per-document export settings, read from the `moraya.export` section of
+//! a note's frontmatter:
+//!
+//! ```yaml
+//! moraya:
+//!   export:
+//!     paperSize: letter
+//!     margins: { top: 25, bottom: 25 }
+//!     footer: "{page} of {total}"
+//!     toc: true
+//! ```
+//!
+//! Precedence, lowest first: the exporter's defaults, the options the
+//! export was started with (the export dialog's settings), then the
+//! document's preset, since it is the most specific. A preset value that
+//! can't be used is reported as a warning and the lower layer's value is
+//! kept, so a typo never stops an export.
+
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::epub_export::EpubMetadata;
+use super::frontmatter;
+use super::markdown_format::verbatim_lines;
+use super::pdf_export::{Margins, PdfExportOptions};
+
+/// Every setting a preset may hold; each exporter uses some of them.
+const KNOWN: [&str; 16] = [
+    "paperSize",
+    "orientation",
+    "margins",
+    "fontSize",
+    "fontFamily",
+    "header",
+    "footer",
+    "highlight",
+    "mermaid",
+    "math",
+    "title",
+    "toc",
+    "theme",
+    "author",
+    "language",
+    "cover",
+];
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetUsage {
+    /// Keys of the settings taken from the document, as written there.
+    pub from_document: Vec<String>,
+    /// Preset values that were ignored, and why.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Pdf,
+    Docx,
+    Epub,
+}
+
+/// What the document's preset would change for `format`, so the export
+/// dialog can say it is using the document preset before exporting.
+#[tauri::command]
+pub fn read_export_preset(content: String, format: ExportFormat) -> PresetUsage {
+    let mut preset = Preset::read(&content);
+    match format {
+        ExportFormat::Pdf => {
+            preset.apply_pdf(&mut PdfExportOptions::default());
+            preset.apply_toc(String::new());
+            preset.finish("PDF")
+        }
+        ExportFormat::Docx => {
+            preset.apply_toc(String::new());
+            preset.finish("Word")
+        }
+        ExportFormat::Epub => {
+            preset.apply_epub(&mut EpubMetadata::default(), None);
+            preset.finish("EPUB")
+        }
+    }
+}
+
+pub(crate) struct Preset {
+    values: Map<String, Value>,
+    usage: PresetUsage,
+}
+
+impl Preset {
+    /// The preset in `markdown`'s frontmatter; empty when there is none.
+    pub(crate) fn read(markdown: &str) -> Self {
+        let mut usage = PresetUsage::default();
+        let frontmatter = frontmatter::parse(markdown);
+        let values = match frontmatter
+            .as_ref()
+            .and_then(|f| f.pointer("/moraya/export"))
+        {
+            Some(Value::Object(values)) => values.clone(),
+            None | Some(Value::Null) => Map::new(),
+            Some(_) => {
+                usage
+                    .warnings
+                    .push("`moraya.export` should be a mapping of settings; ignored".to_string());
+                Map::new()
+            }
+        };
+        Self { values, usage }
+    }
+
+    /// Take `key` out of the preset, converted; a value that doesn't
+    /// convert is warned about and skipped.
+    fn take<T>(
+        &mut self,
+        key: &str,
+        expected: &str,
+        convert: impl FnOnce(&Value) -> Option<T>,
+    ) -> Option<T> {
+        let value = self.values.remove(key)?;
+        match convert(&value) {
+            Some(converted) => {
+                self.usage.from_document.push(key.to_string());
+                Some(converted)
+            }
+            None => {
+                self.usage.warnings.push(format!(
+                    "Ignored `{}` in the document's export preset: expected {}",
+                    key, expected
+                ));
+                None
+            }
+        }
+    }
+
+    pub(crate) fn apply_pdf(&mut self, options: &mut PdfExportOptions) {
+        if let Some(size) = self.take("paperSize", "a4, letter, legal, a3 or a5", named) {
+            options.paper_size = size;
+        }
+        if let Some(orientation) = self.take("orientation", "portrait or landscape", named) {
+            options.orientation = orientation;
+        }
+        let current = options.margins.clone();
+        let expected = "millimetres, for all sides or as top, right, bottom and left";
+        if let Some(margins) = self.take("margins", expected, |v| margins(v, current)) {
+            options.margins = margins;
+        }
+        let font_size = |v: &Value| v.as_f64().filter(|s| (6.0..=72.0).contains(s));
+        if let Some(size) = self.take("fontSize", "a size from 6 to 72 points", font_size) {
+            options.font_size = size;
+        }
+        if let Some(family) = self.take("fontFamily", "a font name", text) {
+            options.font_family = family;
+        }
+        if let Some((enabled, template)) = self.take("header", "true, false or a template", band) {
+            options.header_enabled = enabled;
+            if let Some(template) = template {
+                options.header_template = template;
+            }
+        }
+        if let Some((enabled, template)) = self.take("footer", "true, false or a template", band) {
+            options.footer_enabled = enabled;
+            if let Some(template) = template {
+                options.footer_template = template;
+            }
+        }
+        for (key, flag) in [
+            ("highlight", &mut options.enable_highlight),
+            ("mermaid", &mut options.enable_mermaid),
+            ("math", &mut options.enable_math),
+        ] {
+            if let Some(enabled) = self.take(key, "true or false", Value::as_bool) {
+                *flag = enabled;
+            }
+        }
+        if let Some(title) = self.take("title", "text", text) {
+            options.document_title = title;
+        }
+    }
+
+    /// Book metadata; a relative `cover` is resolved against `base_dir`,
+    /// the folder of the document the preset came from.
+    pub(crate) fn apply_epub(&mut self, metadata: &mut EpubMetadata, base_dir: Option<&Path>) {
+        if let Some(title) = self.take("title", "text", text) {
+            metadata.title = Some(title);
+        }
+        if let Some(author) = self.take("author", "text", text) {
+            metadata.author = Some(author);
+        }
+        // Checked, with its own warning, when the book is built
+        if let Some(language) = self.take("language", "a language tag", text) {
+            metadata.language = Some(language);
+        }
+        if let Some(cover) = self.take("cover", "an image path", text) {
+            let path = Path::new(&cover);
+            metadata.cover = Some(match base_dir {
+                Some(dir) if path.is_relative() => dir.join(path).to_string_lossy().into_owned(),
+                _ => cover,
+            });
+        }
+    }
+
+    /// `markdown` with a table of contents after its frontmatter, when
+    /// the preset asks for one.
+    pub(crate) fn apply_toc(&mut self, markdown: String) -> String {
+        match self.take("toc", "true or false", Value::as_bool) {
+            Some(true) => with_toc(&markdown),
+            _ => markdown,
+        }
+    }
+
+    /// The report for the export, with a warning for each setting left
+    /// over: unknown ones and ones `format` export can't use.
+    pub(crate) fn finish(mut self, format: &str) -> PresetUsage {
+        let mut left: Vec<&String> = self.values.keys().collect();
+        left.sort();
+        for key in left {
+            self.usage.warnings.push(if KNOWN.contains(&key.as_str()) {
+                format!(
+                    "`{}` in the document's export preset doesn't apply to {} export",
+                    key, format
+                )
+            } else {
+                format!("Unknown setting `{}` in the document's export preset", key)
+            });
+        }
+        self.usage
+    }
+}
+
+/// A lowercase enum name, like the export options use.
+fn named<T: DeserializeOwned>(value: &Value) -> Option<T> {
+    let name = value.as_str()?.trim().to_lowercase();
+    serde_json::from_value(Value::String(name)).ok()
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn millimetres(value: &Value) -> Option<f64> {
+    value.as_f64().filter(|mm| (0.0..=100.0).contains(mm))
+}
+
+/// One number for all sides, or some of `top`, `right`, `bottom` and
+/// `left` over the current margins.
+fn margins(value: &Value, mut margins: Margins) -> Option<Margins> {
+    if let Some(all) = millimetres(value) {
+        return Some(Margins {
+            top: all,
+            right: all,
+            bottom: all,
+            left: all,
+        });
+    }
+    for (side, mm) in value.as_object()? {
+        let mm = millimetres(mm)?;
+        match side.as_str() {
+            "top" => margins.top = mm,
+            "right" => margins.right = mm,
+            "bottom" => margins.bottom = mm,
+            "left" => margins.left = mm,
+            _ => return None,
+        }
+    }
+    Some(margins)
+}
+
+/// `true`/`false` to turn a header or footer on or off, or a template,
+/// which also turns it on.
+fn band(value: &Value) -> Option<(bool, Option<String>)> {
+    match value {
+        Value::Bool(enabled) => Some((*enabled, None)),
+        Value::String(template) => Some((true, Some(template.clone()))),
+        _ => None,
+    }
+}
+
+fn heading_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^ {0,3}(#{1,3})[ \t]+(.+?)(?:[ \t]+#+)?[ \t]*$").expect("valid regex")
+    })
+}
+
+/// Insert a nested list of the headings, down to level 3, where the body
+/// starts.
+fn with_toc(markdown: &str) -> String {
+    let verbatim = verbatim_lines(markdown);
+    let headings: Vec<(usize, &str)> = markdown
+        .lines()
+        .zip(&verbatim)
+        .filter(|(_, code)| !**code)
+        .filter_map(|(line, _)| heading_re().captures(line))
+        .map(|c| {
+            (
+                c.get(1).map_or(1, |m| m.len()),
+                c.get(2).map_or("", |m| m.as_str()),
+            )
+        })
+        .collect();
+    let Some(top) = headings.iter().map(|(level, _)| *level).min() else {
+        return markdown.to_string();
+    };
+    let mut toc = String::new();
+    for (level, title) in &headings {
+        toc.push_str(&"  ".repeat(level - top));
+        toc.push_str("- ");
+        toc.push_str(title);
+        toc.push('\n');
+    }
+    toc.push('\n');
+    let at = frontmatter::body_start(markdown);
+    format!("{}{}{}", &markdown[..at], toc, &markdown[at..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::pdf_export::{Orientation, PaperSize};
+
+    const DOC: &str = "---\ntitle: Notes\nmoraya:\n  export:\n    paperSize: Letter\n    orientation: sideways\n    margins: { top: 30 }\n    footer: \"{page}\"\n    toc: true\n    theme: dark\n    colour: red\n---\n# One\n\n```\n# not a heading\n```\n\n## Two ##\n";
+
+    #[test]
+    fn should_merge_preset_over_pdf_options() {
+        let mut options = PdfExportOptions {
+            orientation: Orientation::Landscape,
+            font_size: 14.0,
+            ..PdfExportOptions::default()
+        };
+        let mut preset = Preset::read(DOC);
+        preset.apply_pdf(&mut options);
+        let markdown = preset.apply_toc(DOC.to_string());
+        let usage = preset.finish("PDF");
+
+        assert_eq!(options.paper_size, PaperSize::Letter);
+        // Invalid in the preset, so the option passed in stays
+        assert_eq!(options.orientation, Orientation::Landscape);
+        assert_eq!(options.font_size, 14.0);
+        assert_eq!((options.margins.top, options.margins.left), (30.0, 15.0));
+        assert!(options.footer_enabled);
+        assert_eq!(options.footer_template, "{page}");
+        assert_eq!(
+            usage.from_document,
+            ["paperSize", "margins", "footer", "toc"]
+        );
+        assert_eq!(usage.warnings.len(), 3);
+        assert!(usage.warnings[0].contains("`orientation`"));
+        assert!(usage.warnings[1].contains("Unknown setting `colour`"));
+        assert!(usage.warnings[2].contains("`theme`"));
+        assert!(markdown
+            .ends_with("---\n- One\n  - Two\n\n# One\n\n```\n# not a heading\n```\n\n## Two ##\n"));
+    }
+
+    #[test]
+    fn should_apply_epub_metadata_and_ignore_missing_preset() {
+        let doc = "---\nmoraya:\n  export:\n    author: Ada\n    cover: img/cover.png\n---\nText\n";
+        let mut metadata = EpubMetadata {
+            author: Some("Someone".into()),
+            ..Default::default()
+        };
+        let mut preset = Preset::read(doc);
+        preset.apply_epub(&mut metadata, Some(Path::new("/notes")));
+        assert_eq!(metadata.author.as_deref(), Some("Ada"));
+        assert_eq!(
+            metadata.cover.map(std::path::PathBuf::from),
+            Some(Path::new("/notes").join("img/cover.png"))
+        );
+        assert_eq!(preset.finish("EPUB").from_document, ["author", "cover"]);
+
+        let usage = read_export_preset("# Plain\n".into(), ExportFormat::Docx);
+        assert_eq!(usage, PresetUsage::default());
+    }
+}
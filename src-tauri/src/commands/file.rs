@@ -2,27 +2,114 @@ use serde::Serialize;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use super::error::{CommandError, ErrorCode};
+
 #[derive(Serialize)]
 pub struct FileEntry {
     pub name: String,
     pub path: String,
     pub is_dir: bool,
     pub children: Option<Vec<FileEntry>>,
+    /// Finder or xdg tags, when asked for with `include_meta`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
 }
 
 /// Sanitize IO errors to avoid leaking file system paths or OS error details.
-fn sanitize_io_error(e: std::io::Error) -> String {
+pub(crate) fn sanitize_io_error(e: std::io::Error) -> CommandError {
+    if is_read_only_fs_error(&e) {
+        return read_only_volume();
+    }
     match e.kind() {
-        std::io::ErrorKind::NotFound => "File not found".to_string(),
-        std::io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
-        std::io::ErrorKind::AlreadyExists => "File already exists".to_string(),
-        _ => "Operation failed".to_string(),
+        std::io::ErrorKind::NotFound => not_found(),
+        std::io::ErrorKind::PermissionDenied => {
+            CommandError::new(ErrorCode::PermissionDenied, "Permission denied")
+        }
+        std::io::ErrorKind::AlreadyExists => already_exists(),
+        _ => CommandError::new(ErrorCode::IoError, "Operation failed"),
+    }
+}
+
+fn not_found() -> CommandError {
+    CommandError::new(ErrorCode::FileNotFound, "File not found")
+}
+
+fn already_exists() -> CommandError {
+    CommandError::new(ErrorCode::FileExists, "File already exists")
+}
+
+fn invalid_path() -> CommandError {
+    CommandError::new(ErrorCode::InvalidPath, "Invalid path")
+}
+
+fn read_only_file() -> CommandError {
+    CommandError::new(ErrorCode::ReadOnlyFile, "File is read-only")
+}
+
+fn read_only_volume() -> CommandError {
+    CommandError::new(ErrorCode::ReadOnlyVolume, "The volume is read-only")
+}
+
+/// EROFS on Unix, ERROR_WRITE_PROTECT on Windows.
+fn is_read_only_fs_error(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        e.raw_os_error() == Some(libc::EROFS)
+    }
+    #[cfg(windows)]
+    {
+        e.raw_os_error() == Some(19)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Whether the file system holding `path` is mounted read-only.
+#[cfg(unix)]
+fn is_read_only_volume(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer
+    let rc = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    rc == 0 && stat.f_flag & libc::ST_RDONLY != 0
+}
+
+/// Windows reports write-protected volumes when the write is attempted.
+#[cfg(not(unix))]
+fn is_read_only_volume(_path: &Path) -> bool {
+    false
+}
+
+/// Refuse up front to write where it cannot succeed, so the user gets a
+/// specific reason instead of "Operation failed". Runs before anything is
+/// created, so a rejected save never touches the existing file.
+fn check_writable(path: &Path) -> Result<(), CommandError> {
+    if let Ok(meta) = fs::metadata(path) {
+        if meta.is_file() && meta.permissions().readonly() {
+            return Err(if is_read_only_volume(path) {
+                read_only_volume()
+            } else {
+                read_only_file()
+            });
+        }
+    }
+    // Nearest existing ancestor: parents may still have to be created
+    let dir = path.ancestors().skip(1).find(|p| p.exists());
+    if dir.is_some_and(is_read_only_volume) {
+        return Err(read_only_volume());
     }
+    Ok(())
 }
 
 /// Strip the `\\?\` extended-length path prefix that Windows' `canonicalize` adds.
 /// On non-Windows platforms this is a no-op.
-fn strip_unc_prefix(p: PathBuf) -> PathBuf {
+pub(crate) fn strip_unc_prefix(p: PathBuf) -> PathBuf {
     #[cfg(target_os = "windows")]
     {
         let s = p.to_string_lossy();
@@ -40,7 +127,8 @@ fn strip_unc_prefix(p: PathBuf) -> PathBuf {
 ///    - /Volumes/* on macOS (external drives, e.g. USB / HDD mounted by the OS)
 ///    - /media/* or /mnt/* on Linux (external drive mount points)
 ///    - Any drive letter other than C:\ on Windows is permitted (non-system volumes)
-pub(crate) fn validate_path(path: &str) -> Result<PathBuf, String> {
+///    - Directories listed in the `allowedRoots` setting
+pub(crate) fn validate_path(path: &str) -> Result<PathBuf, CommandError> {
     let canonical = std::fs::canonicalize(path)
         .or_else(|_| {
             // File/directory may not exist yet (write scenario).
@@ -59,7 +147,7 @@ pub(crate) fn validate_path(path: &str) -> Result<PathBuf, String> {
             while let Some(dir) = ancestor {
                 if dir.exists() {
                     let canonical_ancestor =
-                        std::fs::canonicalize(dir).map_err(|_| "Invalid path".to_string())?;
+                        std::fs::canonicalize(dir).map_err(|_| invalid_path())?;
                     // Reconstruct path by appending suffix parts in reverse order
                     let mut result = canonical_ancestor;
                     for part in suffix_parts.iter().rev() {
@@ -74,20 +162,30 @@ pub(crate) fn validate_path(path: &str) -> Result<PathBuf, String> {
                 ancestor = dir.parent();
             }
 
-            Err("Invalid path".to_string())
-        })
-        .map_err(|e: String| e)?;
+            Err(invalid_path())
+        })?;
 
     // On Windows, canonicalize returns \\?\C:\... but home_dir returns C:\...
     let canonical = strip_unc_prefix(canonical);
 
-    let home = dirs::home_dir().ok_or_else(|| "Cannot determine home directory".to_string())?;
+    let home = dirs::home_dir().ok_or_else(|| {
+        CommandError::new(ErrorCode::Internal, "Cannot determine home directory")
+    })?;
 
     // Always allow paths within the user's home directory
     if canonical.starts_with(&home) {
         return Ok(canonical);
     }
 
+    // Extra roots from the `allowedRoots` setting
+    if super::settings::allowed_roots()
+        .into_iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok().map(strip_unc_prefix))
+        .any(|root| canonical.starts_with(root))
+    {
+        return Ok(canonical);
+    }
+
     // macOS: allow external drives mounted under /Volumes/
     // (e.g. /Volumes/MyUSB/notes.md — user selected via native file dialog)
     #[cfg(target_os = "macos")]
@@ -114,13 +212,55 @@ pub(crate) fn validate_path(path: &str) -> Result<PathBuf, String> {
         }
     }
 
-    Err("Access denied: path outside allowed directory".to_string())
+    Err(CommandError::new(
+        ErrorCode::AccessDenied,
+        "Access denied: path outside allowed directory",
+    ))
 }
 
-#[tauri::command]
-pub fn read_file(path: String) -> Result<String, String> {
+/// Local path of a link or image reference in a markdown document: absolute
+/// paths and `file:` URLs as they are, anything else relative to the
+/// document's folder. Remote URLs (and relative ones without a folder)
+/// give `None`. The result still has to pass `validate_path`.
+pub(crate) fn resolve_document_reference(src: &str, base_dir: Option<&Path>) -> Option<PathBuf> {
+    let src = src.trim();
+    if src.starts_with("file:") {
+        return url::Url::parse(src).ok()?.to_file_path().ok();
+    }
+    if src.contains("://") || src.starts_with("data:") || src.starts_with("mailto:") {
+        return None;
+    }
+    let path = Path::new(src);
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+    let base = base_dir?;
+    let raw = base.join(src);
+    if raw.exists() {
+        return Some(raw);
+    }
+    // Markdown links often percent-encode spaces and non-ASCII names
+    url::Url::from_directory_path(base)
+        .ok()
+        .and_then(|dir| dir.join(src).ok())
+        .and_then(|u| u.to_file_path().ok())
+        .or(Some(raw))
+}
+
+/// Runs off the main thread: it can wait briefly for a cloud placeholder to
+/// download.
+#[tauri::command(async)]
+pub fn read_file(path: String) -> Result<String, CommandError> {
     let safe_path = validate_path(&path)?;
-    fs::read_to_string(&safe_path).map_err(sanitize_io_error)
+    super::cloud_files::ensure_local(&safe_path)?;
+    let bytes = fs::read(&safe_path).map_err(sanitize_io_error)?;
+    if super::encryption::is_encrypted(&bytes) {
+        return Err(CommandError::new(
+            ErrorCode::Encrypted,
+            "Document is encrypted",
+        ));
+    }
+    String::from_utf8(bytes).map_err(|_| sanitize_io_error(std::io::ErrorKind::InvalidData.into()))
 }
 
 /// Read a binary file and return its contents as a byte array.
@@ -128,7 +268,7 @@ pub fn read_file(path: String) -> Result<String, String> {
 /// a number[] that can be passed directly to `new Uint8Array(result)`.
 /// Used by renderer plugins (e.g. morcad) that need to read binary formats such as DWG.
 #[tauri::command]
-pub fn read_file_binary(path: String) -> Result<Vec<u8>, String> {
+pub fn read_file_binary(path: String) -> Result<Vec<u8>, CommandError> {
     let safe_path = validate_path(&path)?;
     fs::read(&safe_path).map_err(sanitize_io_error)
 }
@@ -136,33 +276,96 @@ pub fn read_file_binary(path: String) -> Result<Vec<u8>, String> {
 /// Return the embedded privacy policy content.
 /// The file is included at compile time so no runtime path resolution is needed.
 #[tauri::command]
-pub fn read_resource_file(name: String) -> Result<String, String> {
+pub fn read_resource_file(name: String) -> Result<String, CommandError> {
     match name.as_str() {
         "privacy-policy.md" => Ok(include_str!("../../resources/privacy-policy.md").to_string()),
-        _ => Err("Unknown resource".to_string()),
+        _ => Err(CommandError::new(ErrorCode::FileNotFound, "Unknown resource")),
     }
 }
 
+/// Write to a temporary sibling file, then rename it over `path`, so a crash
+/// or a concurrent reader never sees a half-written document. The existing
+/// file's permissions and file tags are kept.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+    let tmp = dir.join(format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(meta) = fs::metadata(path) {
+            fs::set_permissions(&tmp, meta.permissions())?;
+            super::file_tags::copy_tags(path, &tmp);
+        }
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Write `content` to `path` atomically. With `clean`, invisible characters
+/// are stripped first (see `clean_invisible_chars`).
 #[tauri::command]
-pub fn write_file(path: String, content: String) -> Result<(), String> {
+pub fn write_file(
+    path: String,
+    content: String,
+    clean: Option<super::invisible_chars::CleanOptions>,
+) -> Result<(), CommandError> {
     let safe_path = validate_path(&path)?;
-    if let Some(parent) = safe_path.parent() {
+    let content = match clean {
+        Some(options) => super::invisible_chars::clean(&content, &options).0,
+        None => content,
+    };
+    save_document(&safe_path, content.as_bytes())
+}
+
+/// Atomically write `contents` to the already validated `path`, creating
+/// parent folders, then update the tag and search indexes and drop the
+/// draft kept for it. Everything that saves a workspace document, like the
+/// editor and plugins, goes through here.
+pub(crate) fn save_document(path: &Path, contents: &[u8]) -> Result<(), CommandError> {
+    check_writable(path)?;
+    if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(sanitize_io_error)?;
     }
-    fs::write(&safe_path, content).map_err(sanitize_io_error)
+    write_atomic(path, contents).map_err(sanitize_io_error)?;
+    super::tags::file_written(path);
+    super::search_index::file_written(path);
+    super::drafts::document_saved(path);
+    Ok(())
 }
 
-/// Write binary data (base64-encoded) to a file.
-/// Used for exporting PDF, PNG, and other binary formats.
-#[tauri::command]
-pub fn write_file_binary(path: String, base64_data: String) -> Result<(), String> {
-    use std::io::Write;
-
-    let safe_path = validate_path(&path)?;
+/// Validate `path` and atomically write `bytes` there, creating parent
+/// folders. Shared by the binary writers and exports that produce bytes
+/// in Rust.
+pub(crate) fn write_binary(path: &str, bytes: &[u8]) -> Result<(), CommandError> {
+    let safe_path = validate_path(path)?;
+    check_writable(&safe_path)?;
     if let Some(parent) = safe_path.parent() {
         fs::create_dir_all(parent).map_err(sanitize_io_error)?;
     }
+    write_atomic(&safe_path, bytes).map_err(sanitize_io_error)
+}
 
+/// Write binary data (base64-encoded) to a file.
+/// Used for exporting PDF, PNG, and other binary formats.
+#[tauri::command]
+pub fn write_file_binary(path: String, base64_data: String) -> Result<(), CommandError> {
     // Strip optional data URL prefix (e.g. "data:image/png;base64,")
     let raw = if let Some(pos) = base64_data.find(",") {
         &base64_data[pos + 1..]
@@ -170,10 +373,10 @@ pub fn write_file_binary(path: String, base64_data: String) -> Result<(), String
         &base64_data
     };
 
-    let bytes = base64_decode(raw).map_err(|_| "Failed to decode data".to_string())?;
+    let bytes = base64_decode(raw)
+        .map_err(|_| CommandError::new(ErrorCode::InvalidArgument, "Failed to decode data"))?;
 
-    let mut file = fs::File::create(&safe_path).map_err(sanitize_io_error)?;
-    file.write_all(&bytes).map_err(sanitize_io_error)
+    write_binary(&path, &bytes)
 }
 
 /// Write raw binary bytes to a file via the IPC raw-body path.
@@ -182,28 +385,54 @@ pub fn write_file_binary(path: String, base64_data: String) -> Result<(), String
 /// The body arrives as `InvokeBody::Raw(Vec<u8>)` with no JSON or base64
 /// transcoding, which is the fast path for large exports (PDF, PNG).
 #[tauri::command]
-pub fn write_file_bytes(request: tauri::ipc::Request<'_>) -> Result<(), String> {
-    use std::io::Write;
-
+pub fn write_file_bytes(request: tauri::ipc::Request<'_>) -> Result<(), CommandError> {
     let path = request
         .headers()
         .get("X-File-Path")
         .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| "Missing X-File-Path header".to_string())?
+        .ok_or_else(|| {
+            CommandError::new(ErrorCode::InvalidArgument, "Missing X-File-Path header")
+        })?
         .to_string();
 
     let bytes: &[u8] = match request.body() {
         tauri::ipc::InvokeBody::Raw(b) => b.as_slice(),
-        _ => return Err("Expected raw bytes body".to_string()),
+        _ => {
+            return Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                "Expected raw bytes body",
+            ))
+        }
     };
 
+    write_binary(&path, bytes)
+}
+
+/// Clear a file's read-only flag (`chmod u+w` on Unix, the read-only
+/// attribute on Windows). The UI only calls this after the user agreed to
+/// a `READ_ONLY_FILE` prompt; a read-only volume cannot be fixed from here.
+#[tauri::command]
+pub fn make_writable(path: String) -> Result<(), CommandError> {
     let safe_path = validate_path(&path)?;
-    if let Some(parent) = safe_path.parent() {
-        fs::create_dir_all(parent).map_err(sanitize_io_error)?;
+    let meta = fs::metadata(&safe_path).map_err(sanitize_io_error)?;
+    if !meta.is_file() {
+        return Err(invalid_path());
     }
-
-    let mut file = fs::File::create(&safe_path).map_err(sanitize_io_error)?;
-    file.write_all(bytes).map_err(sanitize_io_error)
+    if is_read_only_volume(&safe_path) {
+        return Err(read_only_volume());
+    }
+    let mut perms = meta.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        perms.set_mode(perms.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    {
+        #[allow(clippy::permissions_set_readonly_false)]
+        perms.set_readonly(false);
+    }
+    fs::set_permissions(&safe_path, perms).map_err(sanitize_io_error)
 }
 
 /// Simple base64 decoder (no external dependency needed).
@@ -240,13 +469,41 @@ fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
     Ok(buf)
 }
 
-/// Create a new empty Markdown file in the given directory.
-/// Automatically appends `.md` if not already present.
+/// Fill `{{date}}`, `{{year}}`, `{{month}}`, `{{day}}`, `{{weekday}}`,
+/// `{{time}}` and `{{title}}` in a note template.
+pub(crate) fn expand_template(template: &str, now: chrono::NaiveDateTime, title: &str) -> String {
+    template
+        .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{year}}", &now.format("%Y").to_string())
+        .replace("{{month}}", &now.format("%m").to_string())
+        .replace("{{day}}", &now.format("%d").to_string())
+        .replace("{{weekday}}", &now.format("%A").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{title}}", title)
+}
+
+/// Read a template file for a new note and expand its placeholders.
+pub(crate) fn instantiate_template(
+    template_path: &str,
+    now: chrono::NaiveDateTime,
+    title: &str,
+) -> Result<String, CommandError> {
+    let safe_template = validate_path(template_path)?;
+    let template = fs::read_to_string(&safe_template).map_err(sanitize_io_error)?;
+    Ok(expand_template(&template, now, title))
+}
+
+/// Create a new Markdown file in the given directory, empty or instantiated
+/// from `template_path`. Automatically appends `.md` if not already present.
 #[tauri::command]
-pub fn create_markdown_file(dir_path: String, file_name: String) -> Result<String, String> {
+pub fn create_markdown_file(
+    dir_path: String,
+    file_name: String,
+    template_path: Option<String>,
+) -> Result<String, CommandError> {
     let safe_dir = validate_path(&dir_path)?;
     if !safe_dir.is_dir() {
-        return Err("Not a directory".to_string());
+        return Err(CommandError::new(ErrorCode::NotADirectory, "Not a directory"));
     }
 
     let name = if file_name.ends_with(".md") || file_name.ends_with(".markdown") {
@@ -260,53 +517,69 @@ pub fn create_markdown_file(dir_path: String, file_name: String) -> Result<Strin
     let safe_file = validate_path(file_path.to_str().unwrap_or(""))?;
 
     if safe_file.exists() {
-        return Err("File already exists".to_string());
+        return Err(already_exists());
     }
 
-    fs::write(&safe_file, "").map_err(sanitize_io_error)?;
+    let content = match template_path {
+        Some(template) => {
+            let title = Path::new(&name)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            instantiate_template(&template, chrono::Local::now().naive_local(), &title)?
+        }
+        None => String::new(),
+    };
+    fs::write(&safe_file, content).map_err(sanitize_io_error)?;
     Ok(safe_file.to_string_lossy().to_string())
 }
 
 /// Create a new directory (including intermediate directories).
 #[tauri::command]
-pub fn create_dir(path: String) -> Result<(), String> {
+pub fn create_dir(path: String) -> Result<(), CommandError> {
     let safe_path = validate_path(&path)?;
     if safe_path.exists() {
-        return Err("File already exists".to_string());
+        return Err(already_exists());
     }
     fs::create_dir_all(&safe_path).map_err(sanitize_io_error)
 }
 
 /// Rename a file or directory.
 #[tauri::command]
-pub fn rename_file(old_path: String, new_path: String) -> Result<(), String> {
+pub fn rename_file(old_path: String, new_path: String) -> Result<(), CommandError> {
     let safe_old = validate_path(&old_path)?;
     let safe_new = validate_path(&new_path)?;
 
     if !safe_old.exists() {
-        return Err("File not found".to_string());
+        return Err(not_found());
     }
     if safe_new.exists() {
-        return Err("File already exists".to_string());
+        return Err(already_exists());
     }
 
-    fs::rename(&safe_old, &safe_new).map_err(sanitize_io_error)
+    fs::rename(&safe_old, &safe_new).map_err(sanitize_io_error)?;
+    super::tags::path_renamed(&safe_old, &safe_new);
+    super::search_index::path_renamed(&safe_old, &safe_new);
+    Ok(())
 }
 
 /// Delete a file or directory (recursive for directories).
 #[tauri::command]
-pub fn delete_file(path: String) -> Result<(), String> {
+pub fn delete_file(path: String) -> Result<(), CommandError> {
     let safe_path = validate_path(&path)?;
 
     if !safe_path.exists() {
-        return Err("File not found".to_string());
+        return Err(not_found());
     }
 
     if safe_path.is_dir() {
-        fs::remove_dir_all(&safe_path).map_err(sanitize_io_error)
+        fs::remove_dir_all(&safe_path).map_err(sanitize_io_error)?;
     } else {
-        fs::remove_file(&safe_path).map_err(sanitize_io_error)
+        fs::remove_file(&safe_path).map_err(sanitize_io_error)?;
     }
+    super::tags::path_removed(&safe_path);
+    super::search_index::path_removed(&safe_path);
+    Ok(())
 }
 
 #[derive(Serialize)]
@@ -322,7 +595,7 @@ pub struct FilePreview {
 pub fn read_file_previews(
     paths: Vec<String>,
     max_chars: Option<usize>,
-) -> Result<Vec<FilePreview>, String> {
+) -> Result<Vec<FilePreview>, CommandError> {
     let limit = max_chars.unwrap_or(100);
     let mut previews = Vec::with_capacity(paths.len());
 
@@ -334,6 +607,9 @@ pub fn read_file_previews(
         if !safe_path.is_file() {
             continue;
         }
+        // Reading a cloud placeholder would wait for its download
+        let available = super::cloud_files::file_availability(&safe_path)
+            == super::cloud_files::FileAvailability::Local;
 
         let name = safe_path
             .file_name()
@@ -352,7 +628,12 @@ pub fn read_file_previews(
             .unwrap_or(0.0);
 
         // Read preview: extract title from frontmatter, or first content line
-        let preview = match fs::read_to_string(&safe_path) {
+        let content = if available {
+            fs::read_to_string(&safe_path)
+        } else {
+            Err(std::io::ErrorKind::WouldBlock.into())
+        };
+        let preview = match content {
             Ok(content) => {
                 // Strip BOM if present
                 let content = content.strip_prefix('\u{FEFF}').unwrap_or(&content);
@@ -433,12 +714,59 @@ pub fn read_file_previews(
 /// Maximum directory recursion depth
 const MAX_DIR_DEPTH: u32 = 10;
 
+/// Hidden files and common ignored directories, left out of the file tree.
+pub(crate) fn is_ignored_name(name: &str) -> bool {
+    name.starts_with('.') || name == "node_modules" || name == "target"
+}
+
+/// Files the file tree shows when not showing all files.
+pub(crate) fn is_markdown_name(name: &str) -> bool {
+    name.ends_with(".md") || name.ends_with(".markdown")
+}
+
 #[tauri::command]
-pub fn read_dir_recursive(path: String, depth: Option<u32>, all_files: Option<bool>) -> Result<Vec<FileEntry>, String> {
+pub fn read_dir_recursive(
+    path: String,
+    depth: Option<u32>,
+    all_files: Option<bool>,
+    include_meta: Option<bool>,
+) -> Result<Vec<FileEntry>, CommandError> {
     let safe_path = validate_path(&path)?;
+    walk_dir(
+        &safe_path,
+        depth,
+        all_files.unwrap_or(false),
+        include_meta.unwrap_or(false),
+        &mut |_| Ok(()),
+    )
+}
+
+/// One step of a directory walk, reported to its observer.
+pub(crate) enum Walked {
+    /// A directory was opened for reading.
+    Dir,
+    /// An entry that isn't ignored was found, whether or not it is kept.
+    Entry,
+}
+
+/// `read_dir_recursive` with every step reported to `observe`, which can
+/// stop the walk by returning an error. `path` must already be validated.
+pub(crate) fn walk_dir(
+    path: &Path,
+    depth: Option<u32>,
+    show_all: bool,
+    include_meta: bool,
+    observe: &mut dyn FnMut(Walked) -> Result<(), CommandError>,
+) -> Result<Vec<FileEntry>, CommandError> {
     let max_depth = depth.unwrap_or(3).min(MAX_DIR_DEPTH);
-    let show_all = all_files.unwrap_or(false);
-    read_dir_inner(safe_path.to_str().unwrap_or(""), 0, max_depth, show_all)
+    read_dir_inner(
+        path.to_str().unwrap_or(""),
+        0,
+        max_depth,
+        show_all,
+        include_meta,
+        observe,
+    )
 }
 
 fn read_dir_inner(
@@ -446,7 +774,10 @@ fn read_dir_inner(
     current_depth: u32,
     max_depth: u32,
     show_all: bool,
-) -> Result<Vec<FileEntry>, String> {
+    include_meta: bool,
+    observe: &mut dyn FnMut(Walked) -> Result<(), CommandError>,
+) -> Result<Vec<FileEntry>, CommandError> {
+    observe(Walked::Dir)?;
     let entries = fs::read_dir(path).map_err(sanitize_io_error)?;
 
     let mut result: Vec<FileEntry> = Vec::new();
@@ -455,10 +786,10 @@ fn read_dir_inner(
         let entry = entry.map_err(sanitize_io_error)?;
         let file_name = entry.file_name().to_string_lossy().to_string();
 
-        // Skip hidden files and common ignored directories
-        if file_name.starts_with('.') || file_name == "node_modules" || file_name == "target" {
+        if is_ignored_name(&file_name) {
             continue;
         }
+        observe(Walked::Entry)?;
 
         let file_path = entry.path();
 
@@ -479,6 +810,8 @@ fn read_dir_inner(
                 current_depth + 1,
                 max_depth,
                 show_all,
+                include_meta,
+                observe,
             )?)
         } else if is_dir {
             Some(Vec::new())
@@ -487,12 +820,16 @@ fn read_dir_inner(
         };
 
         // When show_all is false, only show markdown files and directories
-        if show_all || is_dir || file_name.ends_with(".md") || file_name.ends_with(".markdown") {
+        if show_all || is_dir || is_markdown_name(&file_name) {
+            // Unreadable tags shouldn't hide the file
+            let tags = include_meta
+                .then(|| super::file_tags::read_tags(&file_path).unwrap_or_default());
             result.push(FileEntry {
                 name: file_name,
                 path: file_path.to_string_lossy().to_string(),
                 is_dir,
                 children,
+                tags,
             });
         }
     }
@@ -511,9 +848,11 @@ fn read_dir_inner(
     Ok(result)
 }
 
-/// Recursively copy directory contents from `src` into `dst`, skipping symlinks.
-fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
+/// Recursively copy directory contents from `src` into `dst`, skipping
+/// symlinks. File tags are copied along.
+pub(crate) fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), CommandError> {
     fs::create_dir_all(dst).map_err(sanitize_io_error)?;
+    super::file_tags::copy_tags(src, dst);
     let entries = fs::read_dir(src).map_err(sanitize_io_error)?;
     for entry in entries {
         let entry = entry.map_err(sanitize_io_error)?;
@@ -526,6 +865,7 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
             copy_dir_recursive(&entry.path(), &dst_path)?;
         } else {
             fs::copy(entry.path(), &dst_path).map_err(sanitize_io_error)?;
+            super::file_tags::copy_tags(&entry.path(), &dst_path);
         }
     }
     Ok(())
@@ -535,7 +875,7 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
 /// Called when the user changes the Voice Profile Sync Directory in settings.
 /// Both directories must reside within an allowed path (home dir or external mount).
 #[tauri::command]
-pub fn migrate_voice_profiles_dir(old_dir: String, new_dir: String) -> Result<(), String> {
+pub fn migrate_voice_profiles_dir(old_dir: String, new_dir: String) -> Result<(), CommandError> {
     let old_path = validate_path(&old_dir)?;
     let new_path = validate_path(&new_dir)?;
 
@@ -551,7 +891,7 @@ pub fn migrate_voice_profiles_dir(old_dir: String, new_dir: String) -> Result<()
 /// Batch-check file modification times for external change detection.
 /// Returns a list of (path, mtime_secs) pairs. Skips files that don't exist or fail validation.
 #[tauri::command]
-pub fn get_files_mtime(paths: Vec<String>) -> Result<Vec<(String, f64)>, String> {
+pub fn get_files_mtime(paths: Vec<String>) -> Result<Vec<(String, f64)>, CommandError> {
     let mut results = Vec::with_capacity(paths.len());
     for path_str in paths {
         let safe_path = match validate_path(&path_str) {
@@ -570,3 +910,219 @@ pub fn get_files_mtime(paths: Vec<String>) -> Result<Vec<(String, f64)>, String>
     }
     Ok(results)
 }
+
+/// Whether two path components name the same thing; Windows drive letters
+/// and names compare case-insensitively.
+fn same_component(a: std::path::Component<'_>, b: std::path::Component<'_>) -> bool {
+    if cfg!(windows) {
+        a.as_os_str()
+            .to_string_lossy()
+            .eq_ignore_ascii_case(&b.as_os_str().to_string_lossy())
+    } else {
+        a == b
+    }
+}
+
+/// `path` relative to `root`, climbing with `..` where needed. None when
+/// either is relative or they are on different volumes.
+pub(crate) fn relative_path(path: &Path, root: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() || !root.is_absolute() {
+        return None;
+    }
+    let path: Vec<_> = path.components().collect();
+    let root: Vec<_> = root.components().collect();
+    // Drive letter / UNC share on Windows, `/` elsewhere
+    if !same_component(path[0], root[0]) {
+        return None;
+    }
+    let common = path
+        .iter()
+        .zip(&root)
+        .take_while(|(a, b)| same_component(**a, **b))
+        .count();
+    let mut rel = PathBuf::new();
+    for _ in common..root.len() {
+        rel.push("..");
+    }
+    for c in &path[common..] {
+        rel.push(c.as_os_str());
+    }
+    if rel.as_os_str().is_empty() {
+        rel.push(".");
+    }
+    Some(rel)
+}
+
+/// Path of `path` relative to the workspace `root`, for "Copy Relative
+/// Path". Falls back to `path` itself when there is no relative form
+/// (another drive or share).
+#[tauri::command]
+pub fn get_relative_path(path: String, root: String) -> String {
+    match relative_path(Path::new(&path), Path::new(&root)) {
+        Some(rel) => rel.to_string_lossy().into_owned(),
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_map_io_errors_to_codes() {
+        use std::io::{Error, ErrorKind};
+        let code = |kind| sanitize_io_error(Error::from(kind)).code;
+        assert_eq!(code(ErrorKind::NotFound), ErrorCode::FileNotFound);
+        assert_eq!(code(ErrorKind::PermissionDenied), ErrorCode::PermissionDenied);
+        assert_eq!(code(ErrorKind::AlreadyExists), ErrorCode::FileExists);
+        assert_eq!(code(ErrorKind::Other), ErrorCode::IoError);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_deny_paths_outside_allowed_roots() {
+        assert_eq!(
+            validate_path("/etc/hosts").unwrap_err().code,
+            ErrorCode::AccessDenied
+        );
+        assert_eq!(
+            read_file("/etc/hosts".to_string()).unwrap_err().code,
+            ErrorCode::AccessDenied
+        );
+    }
+
+    #[test]
+    fn should_resolve_references_against_document_folder() {
+        let base = std::env::temp_dir().join("moraya-doc-refs");
+        assert_eq!(
+            resolve_document_reference("img/a.png", Some(&base)),
+            Some(base.join("img/a.png"))
+        );
+        assert_eq!(
+            resolve_document_reference("my%20pic.png", Some(&base)),
+            Some(base.join("my pic.png"))
+        );
+        assert_eq!(
+            resolve_document_reference("https://example.com/a.png", Some(&base)),
+            None
+        );
+        assert_eq!(resolve_document_reference("a.png", None), None);
+    }
+
+    #[test]
+    fn should_write_atomically_without_leaving_temp_files() {
+        let dir = std::env::temp_dir().join(format!("moraya-atomic-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("note.md");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_keep_file_tags_across_saves() {
+        let dir = std::env::temp_dir().join(format!("moraya-save-tags-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        crate::commands::settings::allow_root(&dir);
+        let path = dir.join("note.md").to_string_lossy().to_string();
+        write_file(path.clone(), "first".into(), None).unwrap();
+
+        let tags = vec!["Work".to_string()];
+        // Some filesystems, like tmpfs on older kernels, have no user attributes
+        if crate::commands::file_tags::set_file_tags(path.clone(), tags.clone()).is_ok() {
+            write_file(path.clone(), "second".into(), None).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+            assert_eq!(crate::commands::file_tags::get_file_tags(path).unwrap().tags, tags);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_reject_read_only_files_without_touching_them() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("moraya-readonly-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("locked.md");
+        fs::write(&path, "original").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o444)).unwrap();
+
+        assert_eq!(check_writable(&path).unwrap_err().code, ErrorCode::ReadOnlyFile);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // What make_writable does once the user agrees
+        let mut perms = fs::metadata(&path).unwrap().permissions();
+        perms.set_mode(perms.mode() | 0o200);
+        fs::set_permissions(&path, perms).unwrap();
+        assert!(check_writable(&path).is_ok());
+        assert!(check_writable(&dir.join("new/note.md")).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_keep_the_original_when_an_atomic_write_fails() {
+        let dir = std::env::temp_dir().join(format!("moraya-atomic-fail-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("target.md")).unwrap();
+        fs::write(dir.join("target.md/keep.md"), "original").unwrap();
+
+        // Renaming a file over a non-empty directory fails after the temp
+        // file was fully written
+        assert!(write_atomic(&dir.join("target.md"), b"new").is_err());
+        assert_eq!(fs::read_to_string(dir.join("target.md/keep.md")).unwrap(), "original");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_compute_paths_relative_to_the_workspace() {
+        let rel = |p: &str, r: &str| get_relative_path(p.to_string(), r.to_string());
+        assert_eq!(rel("/home/me/notes/a/b.md", "/home/me/notes"), "a/b.md");
+        assert_eq!(rel("/home/me/notes/a/b.md", "/home/me/notes/"), "a/b.md");
+        assert_eq!(rel("/home/me/other/c.md", "/home/me/notes"), "../other/c.md");
+        assert_eq!(rel("/home/me/notes", "/home/me/notes"), ".");
+        assert_eq!(rel("relative.md", "/home/me/notes"), "relative.md");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn should_keep_absolute_paths_across_volumes() {
+        let rel = |p: &str, r: &str| get_relative_path(p.to_string(), r.to_string());
+        assert_eq!(rel(r"D:\notes\a.md", r"C:\notes"), r"D:\notes\a.md");
+        assert_eq!(rel(r"c:\Notes\a.md", r"C:\notes"), "a.md");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_map_read_only_file_system_errors() {
+        let e = std::io::Error::from_raw_os_error(libc::EROFS);
+        assert_eq!(sanitize_io_error(e).code, ErrorCode::ReadOnlyVolume);
+    }
+
+    #[test]
+    fn should_expand_template_placeholders() {
+        let now = chrono::NaiveDate::from_ymd_opt(2026, 3, 7)
+            .unwrap()
+            .and_hms_opt(9, 5, 0)
+            .unwrap();
+        assert_eq!(
+            expand_template("# {{title}}\n{{weekday}} {{date}} {{time}} ({{year}}/{{month}}/{{day}})", now, "Log"),
+            "# Log\nSaturday 2026-03-07 09:05 (2026/03/07)"
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_resource() {
+        assert_eq!(
+            read_resource_file("secrets.json".to_string()).unwrap_err().code,
+            ErrorCode::FileNotFound
+        );
+    }
+}
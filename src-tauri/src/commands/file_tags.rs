@@ -0,0 +1,300 @@
+//! Tags stored with the file itself, so file managers show the same ones:
+//! the `_kMDItemUserTags` attribute Finder uses on macOS and the
+//! `user.xdg.tags` attribute on Linux. Windows has no shared convention,
+//! so tags go in an NTFS alternate data stream only Moraya reads, and the
+//! response says so.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{sanitize_io_error, validate_path};
+
+#[derive(Debug, Serialize)]
+pub struct FileTags {
+    pub tags: Vec<String>,
+    /// Where the tags are kept: "finder", "xdg" or "ads".
+    pub store: &'static str,
+    /// Set when other apps won't see the tags.
+    pub limitation: Option<&'static str>,
+}
+
+impl FileTags {
+    fn new(tags: Vec<String>) -> Self {
+        Self {
+            tags,
+            store: platform::STORE,
+            limitation: platform::LIMITATION,
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_file_tags(path: String) -> Result<FileTags, CommandError> {
+    let safe_path = validate_path(&path)?;
+    if !safe_path.exists() {
+        return Err(CommandError::new(ErrorCode::FileNotFound, "File not found"));
+    }
+    Ok(FileTags::new(read_tags(&safe_path)?))
+}
+
+/// Replace the tags of `path`; an empty list removes them.
+#[tauri::command]
+pub fn set_file_tags(path: String, tags: Vec<String>) -> Result<FileTags, CommandError> {
+    let safe_path = validate_path(&path)?;
+    if !safe_path.exists() {
+        return Err(CommandError::new(ErrorCode::FileNotFound, "File not found"));
+    }
+    let tags = clean_tags(tags)?;
+    platform::write(&safe_path, &tags).map_err(tag_error)?;
+    Ok(FileTags::new(tags))
+}
+
+/// Tags of `path`, empty when it has none.
+pub(crate) fn read_tags(path: &Path) -> Result<Vec<String>, CommandError> {
+    platform::read(path).map_err(tag_error)
+}
+
+/// Give `dst` the tags of `src`, for copies that don't carry extended
+/// attributes along. Best effort: the copy itself has already succeeded.
+pub(crate) fn copy_tags(src: &Path, dst: &Path) {
+    if let Ok(tags) = platform::read(src) {
+        if !tags.is_empty() {
+            let _ = platform::write(dst, &tags);
+        }
+    }
+}
+
+/// Trim tags and drop empty ones and repeats, which Finder treats as the
+/// same tag regardless of case.
+fn clean_tags(tags: Vec<String>) -> Result<Vec<String>, CommandError> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            continue;
+        }
+        // The separators of the Linux and Windows formats
+        if tag.contains([',', '\n', '\r']) {
+            return Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                "Tags can't contain commas or line breaks",
+            ));
+        }
+        if !cleaned
+            .iter()
+            .any(|t| t.to_lowercase() == tag.to_lowercase())
+        {
+            cleaned.push(tag.to_string());
+        }
+    }
+    Ok(cleaned)
+}
+
+fn tag_error(e: io::Error) -> CommandError {
+    #[cfg(unix)]
+    let unsupported = e.raw_os_error() == Some(libc::ENOTSUP);
+    #[cfg(not(unix))]
+    let unsupported = false;
+    if unsupported || e.kind() == io::ErrorKind::Unsupported {
+        return CommandError::new(
+            ErrorCode::FileTagsUnsupported,
+            "This volume can't store file tags",
+        );
+    }
+    sanitize_io_error(e)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::Path;
+
+    const ATTR: &str = "com.apple.metadata:_kMDItemUserTags";
+    pub const STORE: &str = "finder";
+    pub const LIMITATION: Option<&str> = None;
+
+    /// Entries as stored: the name, then a newline and the colour index
+    /// for tags that have a colour.
+    fn read_raw(path: &Path) -> io::Result<Vec<String>> {
+        match xattr::get(path, ATTR)? {
+            Some(bytes) => {
+                plist::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn name(entry: &str) -> &str {
+        match entry.rsplit_once('\n') {
+            Some((name, colour)) if colour.parse::<u8>().is_ok() => name,
+            _ => entry,
+        }
+    }
+
+    pub fn read(path: &Path) -> io::Result<Vec<String>> {
+        Ok(read_raw(path)?
+            .iter()
+            .map(|e| name(e).to_string())
+            .collect())
+    }
+
+    pub fn write(path: &Path, tags: &[String]) -> io::Result<()> {
+        let existing = read_raw(path)?;
+        if tags.is_empty() {
+            return if existing.is_empty() {
+                Ok(())
+            } else {
+                xattr::remove(path, ATTR)
+            };
+        }
+        // Keep the colours Finder gave to tags that stay
+        let colours: HashMap<&str, &String> = existing.iter().map(|e| (name(e), e)).collect();
+        let entries: Vec<String> = tags
+            .iter()
+            .map(|t| {
+                colours
+                    .get(t.as_str())
+                    .map_or_else(|| t.clone(), |e| (*e).clone())
+            })
+            .collect();
+        let mut bytes = Vec::new();
+        plist::to_writer_binary(&mut bytes, &entries)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        xattr::set(path, ATTR, &bytes)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::io;
+    use std::path::Path;
+
+    const ATTR: &str = "user.xdg.tags";
+    pub const STORE: &str = "xdg";
+    pub const LIMITATION: Option<&str> = None;
+
+    pub fn read(path: &Path) -> io::Result<Vec<String>> {
+        Ok(match xattr::get(path, ATTR)? {
+            Some(bytes) => super::split_list(&String::from_utf8_lossy(&bytes), ','),
+            None => Vec::new(),
+        })
+    }
+
+    pub fn write(path: &Path, tags: &[String]) -> io::Result<()> {
+        if !tags.is_empty() {
+            return xattr::set(path, ATTR, tags.join(",").as_bytes());
+        }
+        match xattr::get(path, ATTR)? {
+            Some(_) => xattr::remove(path, ATTR),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    pub const STORE: &str = "ads";
+    pub const LIMITATION: Option<&str> = Some(
+        "Windows has no shared file tags. They are kept in an NTFS alternate data stream \
+         that only Moraya reads, and are lost when the file is copied to a drive that isn't \
+         NTFS or synced by most cloud services.",
+    );
+
+    fn stream(path: &Path) -> PathBuf {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(":moraya.tags");
+        PathBuf::from(name)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Vec<String>> {
+        match fs::read_to_string(stream(path)) {
+            Ok(text) => Ok(super::split_list(&text, '\n')),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write(path: &Path, tags: &[String]) -> io::Result<()> {
+        if !tags.is_empty() {
+            return fs::write(stream(path), tags.join("\n"));
+        }
+        match fs::remove_file(stream(path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+mod platform {
+    use std::io;
+    use std::path::Path;
+
+    pub const STORE: &str = "none";
+    pub const LIMITATION: Option<&str> = Some("File tags aren't supported on this platform.");
+
+    pub fn read(_path: &Path) -> io::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    pub fn write(_path: &Path, _tags: &[String]) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg_attr(not(any(target_os = "linux", windows)), allow(dead_code))]
+fn split_list(text: &str, separator: char) -> Vec<String> {
+    text.split(separator)
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_trim_and_dedupe_tags() {
+        let tags = clean_tags(vec![
+            " Work ".into(),
+            "".into(),
+            "work".into(),
+            "Ideas".into(),
+        ]);
+        assert_eq!(tags.unwrap(), ["Work", "Ideas"]);
+        let err = clean_tags(vec!["a,b".into()]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn should_round_trip_and_copy_tags() {
+        let dir = std::env::temp_dir().join(format!("moraya-file-tags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("a.md");
+        let dst = dir.join("b.md");
+        std::fs::write(&src, "a").unwrap();
+        std::fs::write(&dst, "b").unwrap();
+
+        let tags = vec!["Work".to_string(), "Ideas".to_string()];
+        // Some filesystems, like tmpfs on older kernels, have no user attributes
+        if platform::write(&src, &tags).is_ok() {
+            assert_eq!(read_tags(&src).unwrap(), tags);
+            copy_tags(&src, &dst);
+            assert_eq!(read_tags(&dst).unwrap(), tags);
+            platform::write(&src, &[]).unwrap();
+            assert!(read_tags(&src).unwrap().is_empty());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
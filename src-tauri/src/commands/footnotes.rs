@@ -0,0 +1,675 @@
+//! Tidy a note's footnotes and reference-style links: number footnotes in
+//! the order they are first referenced, gather the definitions at the end
+//! of the note in that order, and report definitions nothing uses and
+//! references to labels that are never defined.
+//!
+//! Code blocks, inline code and frontmatter are left as written, and
+//! running the transform on its own output changes nothing.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+use super::invisible_chars::code_spans;
+use super::markdown_format::verbatim_lines;
+use super::rewrap::{closes_fence, leading_columns, opens_fence, starts_block};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Dangling {
+    pub label: String,
+    /// 1-based line of the first reference.
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizedNotes {
+    pub content: String,
+    pub changed: bool,
+    /// Labels, as written, of footnotes nothing refers to. They are kept
+    /// after the referenced ones.
+    pub orphaned_footnotes: Vec<String>,
+    pub dangling_footnotes: Vec<Dangling>,
+    pub orphaned_links: Vec<String>,
+    pub dangling_links: Vec<Dangling>,
+}
+
+/// Renumber footnotes and reference links in `content`; see the module docs.
+#[tauri::command]
+pub fn normalize_footnotes(content: String) -> NormalizedNotes {
+    normalize(&content)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Footnote,
+    Link,
+}
+
+struct Def {
+    kind: Kind,
+    /// As written.
+    label: String,
+    /// Text after `]:` on the first line.
+    first: String,
+    /// Continuation lines of a footnote, as written.
+    rest: Vec<String>,
+    line: usize,
+}
+
+impl Def {
+    /// Lines of a footnote that can hold references: not its code blocks.
+    fn prose(&self) -> Vec<&str> {
+        let mut lines = vec![self.first.as_str()];
+        let mut fence: Option<(char, usize)> = None;
+        for line in &self.rest {
+            match fence {
+                Some((ch, len)) => {
+                    if closes_fence(line, ch, len) {
+                        fence = None;
+                    }
+                }
+                None => match opens_fence(line) {
+                    Some(open) => fence = Some(open),
+                    None => lines.push(line),
+                },
+            }
+        }
+        lines
+    }
+
+    fn has_blank_line(&self) -> bool {
+        self.rest.iter().any(|l| l.trim().is_empty())
+    }
+}
+
+struct LinkRef {
+    /// Label as written; for `[text]` and `[text][]` it is the text.
+    label: String,
+    /// Range of the label in `[text][label]`, the only form that can be renamed.
+    range: Option<Range<usize>>,
+}
+
+fn footnote_def_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^ {0,3}\[\^([^\]\s]+)\]:[ \t]?(.*)$").expect("valid regex"))
+}
+
+fn link_def_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^ {0,3}\[((?:[^\[\]\\]|\\.)+)\]:[ \t]*(\S.*)$").expect("valid regex")
+    })
+}
+
+fn footnote_ref_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\[\^([^\]\s]+)\]").expect("valid regex"))
+}
+
+fn link_ref_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\[((?:[^\[\]\\]|\\.)*)\](?:\[((?:[^\[\]\\]|\\.)*)\])?").expect("valid regex")
+    })
+}
+
+/// Labels match case-insensitively, with runs of whitespace as one space.
+fn key(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn is_number(label: &str) -> bool {
+    !label.is_empty() && label.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn in_code(spans: &[Range<usize>], range: &Range<usize>) -> bool {
+    spans
+        .iter()
+        .any(|s| s.start < range.end && range.start < s.end)
+}
+
+/// Footnote references in `text` outside code spans, with the range of each label.
+fn footnote_refs(text: &str) -> Vec<(Range<usize>, String)> {
+    let spans = code_spans(text);
+    footnote_ref_re()
+        .captures_iter(text)
+        .filter(|c| !in_code(&spans, &c.get(0).expect("match").range()))
+        .map(|c| {
+            let label = c.get(1).expect("label");
+            (label.range(), label.as_str().to_string())
+        })
+        .collect()
+}
+
+/// Bracketed text in `text` outside code spans that may refer to a link
+/// definition. Inline links and footnote references are skipped.
+fn link_refs(text: &str) -> Vec<LinkRef> {
+    let spans = code_spans(text);
+    let mut refs = Vec::new();
+    for c in link_ref_re().captures_iter(text) {
+        let whole = c.get(0).expect("match");
+        let inner = &c[1];
+        if inner.starts_with('^') || in_code(&spans, &whole.range()) {
+            continue;
+        }
+        match c.get(2) {
+            Some(label) if !label.as_str().is_empty() => refs.push(LinkRef {
+                label: label.as_str().to_string(),
+                range: Some(label.range()),
+            }),
+            Some(_) => refs.push(LinkRef {
+                label: inner.to_string(),
+                range: None,
+            }),
+            None => {
+                let after = &text[whole.end()..];
+                if !inner.is_empty() && !after.starts_with('(') && !after.starts_with(':') {
+                    refs.push(LinkRef {
+                        label: inner.to_string(),
+                        range: None,
+                    });
+                }
+            }
+        }
+    }
+    refs
+}
+
+/// Replace the labels of references in `text` that were given new ones.
+fn relabel(
+    text: &str,
+    footnotes: &HashMap<String, String>,
+    links: &HashMap<String, String>,
+) -> String {
+    let mut edits: Vec<(Range<usize>, &str)> = footnote_refs(text)
+        .into_iter()
+        .filter_map(|(range, label)| footnotes.get(&key(&label)).map(|new| (range, new.as_str())))
+        .collect();
+    for r in link_refs(text) {
+        if let (Some(range), Some(new)) = (r.range, links.get(&key(&r.label))) {
+            edits.push((range, new.as_str()));
+        }
+    }
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut out = String::with_capacity(text.len());
+    let mut at = 0;
+    for (range, new) in edits {
+        out.push_str(&text[at..range.start]);
+        out.push_str(new);
+        at = range.end;
+    }
+    out.push_str(&text[at..]);
+    out
+}
+
+/// Split `lines` into the note without its definitions, as (index, text),
+/// and the definitions.
+fn split_definitions(lines: &[&str], verbatim: &[bool]) -> (Vec<(usize, String)>, Vec<Def>) {
+    let blank = |l: &str| l.trim().is_empty();
+    let mut body: Vec<(usize, String)> = Vec::new();
+    let mut defs: Vec<Def> = Vec::new();
+    let mut after_def = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let mut def = None;
+        if !verbatim[i] {
+            if let Some(c) = footnote_def_re().captures(line) {
+                // Indented lines belong to the footnote, and so do lines
+                // continuing its paragraphs without indentation
+                let mut end = i + 1;
+                let mut j = i + 1;
+                while j < lines.len() {
+                    let l = lines[j];
+                    if blank(l) {
+                        j += 1;
+                        continue;
+                    }
+                    let lazy = !blank(lines[j - 1])
+                        && !verbatim[j]
+                        && !footnote_def_re().is_match(l)
+                        && !starts_block(l.trim_start());
+                    if leading_columns(l) < 4 && !lazy {
+                        break;
+                    }
+                    j += 1;
+                    end = j;
+                }
+                def = Some(Def {
+                    kind: Kind::Footnote,
+                    label: c[1].to_string(),
+                    first: c[2].trim_end().to_string(),
+                    rest: lines[i + 1..end].iter().map(|l| l.to_string()).collect(),
+                    line: i + 1,
+                });
+                i = end;
+            } else if i == 0 || blank(lines[i - 1]) || after_def {
+                // A link definition can't interrupt a paragraph
+                if let Some(c) = link_def_re()
+                    .captures(line)
+                    .filter(|c| !c[1].starts_with('^'))
+                {
+                    def = Some(Def {
+                        kind: Kind::Link,
+                        label: c[1].to_string(),
+                        first: c[2].trim_end().to_string(),
+                        rest: Vec::new(),
+                        line: i + 1,
+                    });
+                    i += 1;
+                }
+            }
+        }
+        match def {
+            Some(def) => {
+                defs.push(def);
+                after_def = true;
+                // Don't leave a double gap where the definition was
+                while i < lines.len()
+                    && blank(lines[i])
+                    && body.last().map_or(true, |(_, l)| blank(l))
+                {
+                    i += 1;
+                }
+            }
+            None => {
+                body.push((i, line.to_string()));
+                after_def = false;
+                i += 1;
+            }
+        }
+    }
+    (body, defs)
+}
+
+/// Definitions of `kind` in output order: referenced ones in the order of
+/// their first reference, then the rest as they were. Returns the indices
+/// and the labels of the unreferenced ones.
+fn ordered_defs(defs: &[Def], kind: Kind, order: &[String]) -> (Vec<usize>, Vec<String>) {
+    let mut first: HashMap<String, usize> = HashMap::new();
+    for (i, def) in defs.iter().enumerate().filter(|(_, d)| d.kind == kind) {
+        first.entry(key(&def.label)).or_insert(i);
+    }
+    let mut used: Vec<usize> = order.iter().filter_map(|k| first.get(k).copied()).collect();
+    let taken: HashSet<usize> = used.iter().copied().collect();
+    let mut orphans = Vec::new();
+    for (i, def) in defs.iter().enumerate().filter(|(_, d)| d.kind == kind) {
+        if !taken.contains(&i) {
+            orphans.push(def.label.clone());
+            used.push(i);
+        }
+    }
+    (used, orphans)
+}
+
+/// References in `order` to labels that aren't defined, as first written.
+fn dangling(
+    order: &[String],
+    seen: &HashMap<String, (String, usize)>,
+    defined: impl Fn(&str) -> bool,
+) -> Vec<Dangling> {
+    order
+        .iter()
+        .filter(|k| !defined(k))
+        .map(|k| {
+            let (label, line) = &seen[k];
+            Dangling {
+                label: label.clone(),
+                line: *line,
+            }
+        })
+        .collect()
+}
+
+fn normalize(content: &str) -> NormalizedNotes {
+    let eol = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let verbatim = verbatim_lines(content);
+    let (body, defs) = split_definitions(&lines, &verbatim);
+    // Lines of the note outside code, numbered from 1
+    let prose: Vec<(usize, &str)> = body
+        .iter()
+        .filter(|(i, _)| !verbatim[*i])
+        .map(|(i, text)| (i + 1, text.as_str()))
+        .collect();
+
+    // Footnotes, in order of first reference: the note itself, then the
+    // footnotes it references, since they are shown after it
+    let mut footnote_order: Vec<String> = Vec::new();
+    let mut footnote_seen: HashMap<String, (String, usize)> = HashMap::new();
+    let mut note_footnote = |label: String, line: usize, order: &mut Vec<String>| {
+        if let Entry::Vacant(entry) = footnote_seen.entry(key(&label)) {
+            order.push(entry.key().clone());
+            entry.insert((label, line));
+        }
+    };
+    for &(line, text) in &prose {
+        for (_, label) in footnote_refs(text) {
+            note_footnote(label, line, &mut footnote_order);
+        }
+    }
+    let footnote_defs: HashMap<String, usize> = defs
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.kind == Kind::Footnote)
+        .rev()
+        .map(|(i, d)| (key(&d.label), i))
+        .collect();
+    let mut next = 0;
+    while next < footnote_order.len() {
+        if let Some(&d) = footnote_defs.get(&footnote_order[next]) {
+            for text in defs[d].prose() {
+                for (_, label) in footnote_refs(text) {
+                    note_footnote(label, defs[d].line, &mut footnote_order);
+                }
+            }
+        }
+        next += 1;
+    }
+
+    // Reference links. Bracketed text only counts as one when its label
+    // is defined, unless it has an explicit `[text][label]`
+    let link_defs: HashSet<String> = defs
+        .iter()
+        .filter(|d| d.kind == Kind::Link)
+        .map(|d| key(&d.label))
+        .collect();
+    let mut link_order: Vec<String> = Vec::new();
+    let mut link_seen: HashMap<String, (String, usize)> = HashMap::new();
+    // Labels that are also the visible text somewhere can't be renamed
+    let mut shown: HashSet<String> = HashSet::new();
+    let footnote_prose = defs
+        .iter()
+        .filter(|d| d.kind == Kind::Footnote)
+        .flat_map(|d| d.prose().into_iter().map(move |text| (d.line, text)));
+    for (line, text) in prose.iter().copied().chain(footnote_prose) {
+        for r in link_refs(text) {
+            let k = key(&r.label);
+            if r.range.is_none() {
+                if !link_defs.contains(&k) {
+                    continue;
+                }
+                shown.insert(k.clone());
+            }
+            if let Entry::Vacant(entry) = link_seen.entry(k) {
+                link_order.push(entry.key().clone());
+                entry.insert((r.label, line));
+            }
+        }
+    }
+
+    // Numeric labels become their position, so they match the numbers
+    // readers see; named ones are kept
+    let (footnote_out, orphaned_footnotes) = ordered_defs(&defs, Kind::Footnote, &footnote_order);
+    let mut footnote_labels: HashMap<String, String> = HashMap::new();
+    for (pos, k) in footnote_order.iter().enumerate() {
+        if is_number(k) {
+            footnote_labels.insert(k.clone(), (pos + 1).to_string());
+        }
+    }
+    // Unreferenced footnotes are numbered after the rest so they can't clash
+    let referenced = footnote_out.len() - orphaned_footnotes.len();
+    let mut orphan_footnote_labels: HashMap<usize, String> = HashMap::new();
+    for &d in &footnote_out[referenced..] {
+        if is_number(&defs[d].label) {
+            let number = footnote_order.len() + orphan_footnote_labels.len() + 1;
+            orphan_footnote_labels.insert(d, number.to_string());
+        }
+    }
+
+    let (link_out, orphaned_links) = ordered_defs(&defs, Kind::Link, &link_order);
+    // Numbers still in use as visible text or by undefined references
+    let reserved: HashSet<String> = link_order
+        .iter()
+        .filter(|k| is_number(k) && (shown.contains(*k) || !link_defs.contains(*k)))
+        .cloned()
+        .collect();
+    let mut number = 0;
+    let mut next_number = || loop {
+        number += 1;
+        if !reserved.contains(&number.to_string()) {
+            return number.to_string();
+        }
+    };
+    let mut link_labels: HashMap<String, String> = HashMap::new();
+    for k in &link_order {
+        if is_number(k) && !reserved.contains(k) {
+            link_labels.insert(k.clone(), next_number());
+        }
+    }
+    let mut orphan_link_labels: HashMap<usize, String> = HashMap::new();
+    for &d in &link_out {
+        let k = key(&defs[d].label);
+        if is_number(&k) && !link_order.contains(&k) {
+            orphan_link_labels.insert(d, next_number());
+        }
+    }
+
+    let dangling_footnotes = dangling(&footnote_order, &footnote_seen, |k| {
+        footnote_defs.contains_key(k)
+    });
+    let dangling_links = dangling(&link_order, &link_seen, |k| link_defs.contains(k));
+
+    let mut out_lines: Vec<String> = body
+        .iter()
+        .map(|(i, text)| {
+            if verbatim[*i] {
+                text.clone()
+            } else {
+                relabel(text, &footnote_labels, &link_labels)
+            }
+        })
+        .collect();
+
+    let label_of =
+        |d: usize, renamed: &HashMap<String, String>, orphans: &HashMap<usize, String>| {
+            orphans
+                .get(&d)
+                .or_else(|| renamed.get(&key(&defs[d].label)))
+                .cloned()
+                .unwrap_or_else(|| defs[d].label.clone())
+        };
+    let link_block: Vec<String> = link_out
+        .iter()
+        .map(|&d| {
+            format!(
+                "[{}]: {}",
+                label_of(d, &link_labels, &orphan_link_labels),
+                defs[d].first
+            )
+        })
+        .collect();
+    let mut footnote_block: Vec<String> = Vec::new();
+    for (n, &d) in footnote_out.iter().enumerate() {
+        let def = &defs[d];
+        if n > 0 && (def.has_blank_line() || defs[footnote_out[n - 1]].has_blank_line()) {
+            footnote_block.push(String::new());
+        }
+        let label = label_of(d, &footnote_labels, &orphan_footnote_labels);
+        let first = relabel(&def.first, &footnote_labels, &link_labels);
+        footnote_block.push(if first.is_empty() {
+            format!("[^{}]:", label)
+        } else {
+            format!("[^{}]: {}", label, first)
+        });
+        let mut fence: Option<(char, usize)> = None;
+        for line in &def.rest {
+            // Code inside a footnote is copied as written
+            let in_fence = match fence {
+                Some((ch, len)) => {
+                    if closes_fence(line, ch, len) {
+                        fence = None;
+                    }
+                    true
+                }
+                None => {
+                    fence = opens_fence(line);
+                    fence.is_some()
+                }
+            };
+            footnote_block.push(if in_fence {
+                line.clone()
+            } else {
+                relabel(line, &footnote_labels, &link_labels)
+            });
+        }
+    }
+
+    let normalized = if defs.is_empty() {
+        let mut text = out_lines.join(eol);
+        if content.ends_with('\n') {
+            text.push_str(eol);
+        }
+        text
+    } else {
+        while out_lines.last().is_some_and(|l| l.trim().is_empty()) {
+            out_lines.pop();
+        }
+        let sections: Vec<String> = [out_lines, link_block, footnote_block]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.join(eol))
+            .collect();
+        let mut text = sections.join(&format!("{eol}{eol}"));
+        text.push_str(eol);
+        text
+    };
+
+    NormalizedNotes {
+        changed: normalized != content,
+        content: normalized,
+        orphaned_footnotes,
+        dangling_footnotes,
+        orphaned_links,
+        dangling_links,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_idempotent(content: &str) {
+        let once = normalize(content);
+        let twice = normalize(&once.content);
+        assert_eq!(twice.content, once.content);
+        assert!(!twice.changed);
+    }
+
+    #[test]
+    fn should_number_footnotes_in_reference_order() {
+        let doc = "Text[^2] more[^1].\n\n[^1]: One.\n[^2]: Two.\n";
+        let result = normalize(doc);
+        assert_eq!(
+            result.content,
+            "Text[^1] more[^2].\n\n[^1]: Two.\n[^2]: One.\n"
+        );
+        assert!(result.changed);
+        assert_idempotent(doc);
+
+        let plain = "Plain [x] text and [^]\n";
+        let result = normalize(plain);
+        assert_eq!(result.content, plain);
+        assert!(!result.changed);
+    }
+
+    #[test]
+    fn should_gather_multi_paragraph_footnotes_and_skip_code() {
+        let doc = "\
+Intro[^b] and `[^x]` code and [link][7].
+
+[^1]: One.
+[^b]: Bee with ref[^3].
+
+    Bee second paragraph.
+[^9]: Orphan.
+
+Second[^1] again[^b], see [docs].
+
+```
+[^1] in fence
+```
+
+[7]: https://a.example
+[docs]: https://docs.example
+[unused]: https://u.example
+";
+        let result = normalize(doc);
+        assert_eq!(
+            result.content,
+            "\
+Intro[^b] and `[^x]` code and [link][1].
+
+Second[^2] again[^b], see [docs].
+
+```
+[^1] in fence
+```
+
+[1]: https://a.example
+[docs]: https://docs.example
+[unused]: https://u.example
+
+[^b]: Bee with ref[^3].
+
+    Bee second paragraph.
+
+[^2]: One.
+[^4]: Orphan.
+"
+        );
+        assert_eq!(result.orphaned_footnotes, ["9"]);
+        assert_eq!(
+            result.dangling_footnotes,
+            [Dangling {
+                label: "3".into(),
+                line: 4
+            }]
+        );
+        assert_eq!(result.orphaned_links, ["unused"]);
+        assert!(result.dangling_links.is_empty());
+        assert_idempotent(doc);
+    }
+
+    #[test]
+    fn should_renumber_reference_links_without_renaming_visible_labels() {
+        let doc = "See [a][3], [b][1] and [2].\n\n[1]: https://b.example\n[2]: https://two.example\n[3]: https://a.example\n\nAlso [c][9].\n";
+        let result = normalize(doc);
+        assert_eq!(
+            result.content,
+            "See [a][1], [b][3] and [2].\n\nAlso [c][9].\n\n[1]: https://a.example\n[3]: https://b.example\n[2]: https://two.example\n"
+        );
+        assert_eq!(
+            result.dangling_links,
+            [Dangling {
+                label: "9".into(),
+                line: 7
+            }]
+        );
+        assert_idempotent(doc);
+    }
+
+    #[test]
+    fn should_keep_crlf_and_frontmatter() {
+        let doc = "---\r\nnote: \"[^1]\"\r\n---\r\nA[^x][^1]\r\n\r\n[^1]: one\r\n[^x]: ex\r\n";
+        let result = normalize(doc);
+        assert_eq!(
+            result.content,
+            "---\r\nnote: \"[^1]\"\r\n---\r\nA[^x][^2]\r\n\r\n[^x]: ex\r\n[^2]: one\r\n"
+        );
+        assert_idempotent(doc);
+    }
+}
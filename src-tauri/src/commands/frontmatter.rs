@@ -0,0 +1,275 @@
+//! The `---` frontmatter block at the top of a note, parsed into JSON.
+//!
+//! Only the YAML that notes actually use is understood: nested mappings,
+//! block and flow lists, and plain, quoted, numeric, boolean and null
+//! scalars. Lines the parser doesn't follow (anchors, multi-line strings)
+//! are skipped rather than failing the whole block.
+
+use serde_json::{Map, Number, Value};
+
+/// Offset where the body starts, after a `---` frontmatter block.
+pub(crate) fn body_start(content: &str) -> usize {
+    let Some(first_end) = content.find('\n') else {
+        return 0;
+    };
+    if content[..first_end].trim_end() != "---" {
+        return 0;
+    }
+    let mut offset = first_end + 1;
+    for line in content[offset..].split_inclusive('\n') {
+        offset += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            return offset;
+        }
+    }
+    // Unclosed: not frontmatter
+    0
+}
+
+/// The frontmatter of `content` as a JSON object; `None` when there is no
+/// frontmatter or it isn't a mapping.
+pub(crate) fn parse(content: &str) -> Option<Value> {
+    let end = body_start(content);
+    if end == 0 {
+        return None;
+    }
+    let start = content.find('\n')? + 1;
+    let block = &content[start..end];
+    // Drop the closing delimiter line
+    let block = &block[..block.trim_end().rfind('\n').map_or(0, |i| i + 1)];
+    let lines: Vec<(usize, &str)> = block
+        .lines()
+        .map(|l| l.trim_end())
+        .filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .map(|l| {
+            (
+                l.len() - l.trim_start_matches(' ').len(),
+                l.trim_start_matches(' '),
+            )
+        })
+        .collect();
+    let first = lines.first()?.0;
+    let mut parser = Parser { lines, pos: 0 };
+    match parser.block(first) {
+        value @ Value::Object(_) => Some(value),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    /// Indentation and text of each non-blank, non-comment line.
+    lines: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn block(&mut self, indent: usize) -> Value {
+        match self.lines.get(self.pos) {
+            Some(&(_, text)) if is_item(text) => self.list(indent),
+            Some(_) => self.mapping(indent),
+            None => Value::Null,
+        }
+    }
+
+    fn mapping(&mut self, indent: usize) -> Value {
+        let mut map = Map::new();
+        while let Some(&(at, text)) = self.lines.get(self.pos) {
+            if at < indent {
+                break;
+            }
+            self.pos += 1;
+            let Some((key, rest)) = split_key(text).filter(|_| at == indent) else {
+                continue;
+            };
+            let value = if !rest.is_empty() {
+                scalar_or_flow(rest)
+            } else {
+                match self.lines.get(self.pos) {
+                    Some(&(next, _)) if next > indent => self.block(next),
+                    // `key:` followed by a list at the same indentation
+                    Some(&(next, item)) if next == indent && is_item(item) => self.list(indent),
+                    _ => Value::Null,
+                }
+            };
+            map.insert(key, value);
+        }
+        Value::Object(map)
+    }
+
+    fn list(&mut self, indent: usize) -> Value {
+        let mut items = Vec::new();
+        while let Some(&(at, text)) = self.lines.get(self.pos) {
+            if at != indent || !is_item(text) {
+                break;
+            }
+            let rest = text[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(match self.lines.get(self.pos) {
+                    Some(&(next, _)) if next > indent => self.block(next),
+                    _ => Value::Null,
+                });
+            } else if split_key(rest).is_some() {
+                // `- key: value` starts a mapping indented to its key
+                let inner = indent + text.len() - rest.len();
+                self.lines[self.pos] = (inner, rest);
+                items.push(self.mapping(inner));
+            } else {
+                self.pos += 1;
+                items.push(scalar_or_flow(rest));
+            }
+        }
+        Value::Array(items)
+    }
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Split `key: value`, with the key optionally quoted.
+fn split_key(text: &str) -> Option<(String, &str)> {
+    let (key, after) = match text.chars().next()? {
+        q @ ('"' | '\'') => {
+            let close = text[1..].find(q)? + 1;
+            (text[1..close].to_string(), &text[close + 1..])
+        }
+        '-' | '[' | '{' | '#' => return None,
+        _ => {
+            let colon = text
+                .match_indices(':')
+                .map(|(i, _)| i)
+                .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))?;
+            (text[..colon].trim_end().to_string(), &text[colon..])
+        }
+    };
+    let rest = after.strip_prefix(':')?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((key, strip_comment(rest.trim())))
+}
+
+/// Remove a ` # comment` outside quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev == ' ' => return text[..i].trim_end(),
+            None => {}
+        }
+        prev = c;
+    }
+    text
+}
+
+/// Split a flow collection's contents at top-level commas.
+fn split_flow(inner: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    parts.push(inner[start..i].trim());
+                    start = i + 1;
+                }
+                _ => {}
+            },
+        }
+    }
+    parts.push(inner[start..].trim());
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn scalar_or_flow(text: &str) -> Value {
+    if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return Value::Array(split_flow(inner).into_iter().map(scalar_or_flow).collect());
+    }
+    if let Some(inner) = text.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+        let map = split_flow(inner)
+            .into_iter()
+            .filter_map(|entry| split_key(entry).map(|(k, v)| (k, scalar_or_flow(v))))
+            .collect();
+        return Value::Object(map);
+    }
+    scalar(text)
+}
+
+fn scalar(text: &str) -> Value {
+    if let Some(inner) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        return Value::String(
+            inner
+                .replace("\\\"", "\"")
+                .replace("\\n", "\n")
+                .replace("\\\\", "\\"),
+        );
+    }
+    if let Some(inner) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        return Value::String(inner.replace("''", "'"));
+    }
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => {}
+    }
+    if let Ok(n) = text.parse::<i64>() {
+        return Value::from(n);
+    }
+    if text.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') {
+        if let Some(n) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_parse_nested_frontmatter() {
+        let doc = "---\ntitle: \"Notes: draft\" # working title\ntags: [a, 'b c']\nmoraya:\n  export:\n    paperSize: letter\n    margins: { top: 25, bottom: 12.5 }\n    toc: true\nauthors:\n- name: Ada\n  role: editor\n- Grace\nurl: https://example.com/a#b\n---\n# Body\n";
+        assert_eq!(
+            parse(doc).unwrap(),
+            json!({
+                "title": "Notes: draft",
+                "tags": ["a", "b c"],
+                "moraya": { "export": {
+                    "paperSize": "letter",
+                    "margins": { "top": 25, "bottom": 12.5 },
+                    "toc": true,
+                }},
+                "authors": [{ "name": "Ada", "role": "editor" }, "Grace"],
+                "url": "https://example.com/a#b",
+            })
+        );
+        assert_eq!(&doc[body_start(doc)..], "# Body\n");
+    }
+
+    #[test]
+    fn should_ignore_missing_or_unclosed_frontmatter() {
+        assert!(parse("# Title\n").is_none());
+        assert!(parse("---\ntitle: x\n").is_none());
+        assert!(parse("---\n- a\n- b\n---\n").is_none());
+        assert_eq!(
+            parse("---\r\nkey: v\r\n...\r\n"),
+            Some(json!({ "key": "v" }))
+        );
+    }
+}
@@ -0,0 +1,696 @@
+//! Publish a markdown document into a local git repository (e.g. a GitHub
+//! Pages / Hugo / Jekyll site checkout): copy the document and the local
+//! files it references, rewrite those links, commit, and optionally push.
+//!
+//! Everything is written through `repo_dir`-relative paths that are checked
+//! component by component, so symlinks or `..` can never lead outside it.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+use serde::Serialize;
+
+use crate::commands::error::{CommandError, ErrorCode};
+use crate::commands::file::{self as file_cmd, resolve_document_reference};
+use crate::commands::mcp::{is_safe_env_var, sanitize_stderr};
+
+const GIT_TIMEOUT: Duration = Duration::from_secs(30);
+const PUSH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Inherited variables that would point git at a different repository.
+const REPO_OVERRIDE_VARS: &[&str] = &[
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_INDEX_FILE",
+    "GIT_OBJECT_DIRECTORY",
+    "GIT_COMMON_DIR",
+];
+
+/// Same markers `git_in_merge` looks for.
+const CONFLICT_MARKERS: &[&str] = &[
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+    "rebase-merge",
+    "rebase-apply",
+];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishResult {
+    /// `HEAD` after publishing; the previous `HEAD` when nothing changed.
+    pub commit: String,
+    pub committed: bool,
+    pub pushed: bool,
+    /// Written files, relative to the repository, with `/` separators.
+    pub files: Vec<String>,
+}
+
+fn git(repo: &Path, args: &[&str], timeout: Duration) -> Result<String, CommandError> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(repo).args(args);
+    cmd.env_clear();
+    for (key, value) in std::env::vars() {
+        if is_safe_env_var(&key) && !REPO_OVERRIDE_VARS.contains(&key.as_str()) {
+            cmd.env(&key, &value);
+        }
+    }
+    cmd.env("GIT_TERMINAL_PROMPT", "0");
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                CommandError::new(ErrorCode::CommandNotFound, "Command 'git' not found")
+            } else {
+                CommandError::new(ErrorCode::Internal, "Failed to run git")
+            }
+        })?;
+
+    // Drain both pipes on threads so a chatty git can't block on a full pipe
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let out_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        buf
+    });
+    let err_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(CommandError::new(
+                    ErrorCode::GitTimeout,
+                    format!("git {} timed out", args[0]),
+                )
+                .with_details(serde_json::json!({ "command": args[0] })));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(_) => return Err(CommandError::new(ErrorCode::Internal, "Failed to run git")),
+        }
+    };
+
+    let stdout = out_reader.join().unwrap_or_default();
+    let stderr = err_reader.join().unwrap_or_default();
+    if status.success() {
+        Ok(String::from_utf8_lossy(&stdout).into_owned())
+    } else {
+        let stderr = sanitize_stderr(String::from_utf8_lossy(&stderr).trim());
+        Err(CommandError::new(
+            ErrorCode::GitCommandFailed,
+            format!("git {} failed: {}", args[0], stderr),
+        )
+        .with_details(serde_json::json!({ "command": args[0], "stderr": stderr })))
+    }
+}
+
+fn ensure_clean_repo(repo: &Path) -> Result<(), CommandError> {
+    let git_dir = git(repo, &["rev-parse", "--absolute-git-dir"], GIT_TIMEOUT).map_err(|e| {
+        if e.code == ErrorCode::GitCommandFailed {
+            CommandError::new(ErrorCode::GitNotRepository, "Not a git repository")
+        } else {
+            e
+        }
+    })?;
+    let git_dir = PathBuf::from(git_dir.trim());
+    let in_progress = CONFLICT_MARKERS.iter().any(|m| git_dir.join(m).exists());
+    if in_progress
+        || !git(repo, &["ls-files", "-u"], GIT_TIMEOUT)?
+            .trim()
+            .is_empty()
+    {
+        return Err(CommandError::new(
+            ErrorCode::GitConflictInProgress,
+            "The repository has a merge, rebase or conflict in progress",
+        ));
+    }
+    Ok(())
+}
+
+fn outside_repo() -> CommandError {
+    CommandError::new(
+        ErrorCode::AccessDenied,
+        "Destination must stay inside the repository",
+    )
+}
+
+/// Parse a repository-relative path: plain components only, never `.git`.
+fn check_relative(rel: &str) -> Result<PathBuf, CommandError> {
+    let rel = rel.replace('\\', "/");
+    let mut out = PathBuf::new();
+    for part in rel.split('/').filter(|p| !p.is_empty() && *p != ".") {
+        match Path::new(part).components().next() {
+            Some(Component::Normal(name)) if !name.eq_ignore_ascii_case(".git") => out.push(name),
+            _ => return Err(outside_repo()),
+        }
+    }
+    if rel.starts_with('/') || rel.contains(':') {
+        return Err(outside_repo());
+    }
+    Ok(out)
+}
+
+/// Where the document goes. Empty, a trailing `/` or an existing directory
+/// mean "inside this directory, under the document's own file name".
+fn destination(repo: &Path, dest: &str, doc_name: &str) -> Result<PathBuf, CommandError> {
+    let rel = check_relative(dest)?;
+    let dest = dest.trim();
+    if rel.as_os_str().is_empty() || dest.ends_with('/') || dest.ends_with('\\') {
+        return Ok(rel.join(doc_name));
+    }
+    match fs::symlink_metadata(repo.join(&rel)) {
+        Ok(m) if m.is_dir() => Ok(rel.join(doc_name)),
+        _ => Ok(rel),
+    }
+}
+
+/// Create the parent directories of `repo/rel` and return the target path,
+/// refusing to pass through (or write to) any symlink.
+fn prepare_target(repo: &Path, rel: &Path) -> Result<PathBuf, CommandError> {
+    let mut dir = repo.to_path_buf();
+    for part in rel.parent().into_iter().flat_map(Path::components) {
+        dir.push(part);
+        match fs::symlink_metadata(&dir) {
+            Ok(m) if m.file_type().is_symlink() => return Err(outside_repo()),
+            Ok(m) if m.is_dir() => {}
+            Ok(_) => {
+                return Err(CommandError::new(
+                    ErrorCode::NotADirectory,
+                    "Destination parent is not a directory",
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                fs::create_dir(&dir).map_err(file_cmd::sanitize_io_error)?
+            }
+            Err(e) => return Err(file_cmd::sanitize_io_error(e)),
+        }
+    }
+    let target = repo.join(rel);
+    if fs::symlink_metadata(&target).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(outside_repo());
+    }
+    Ok(target)
+}
+
+fn is_markdown(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref(),
+        Some("md" | "markdown" | "mdx")
+    )
+}
+
+fn encode_url_path(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+}
+
+/// A local file referenced by the document and where it lands in the repo.
+#[derive(Debug, PartialEq)]
+struct Asset {
+    source: PathBuf,
+    /// Relative to the repository root.
+    dest: PathBuf,
+}
+
+struct AssetPlanner<'a, F> {
+    doc_dir: Option<PathBuf>,
+    dest_dir: PathBuf,
+    access: &'a F,
+    /// `None` when the original URL already works from the new location.
+    by_source: HashMap<PathBuf, Option<String>>,
+    taken: HashSet<PathBuf>,
+    assets: Vec<Asset>,
+}
+
+impl<F: Fn(&Path) -> Option<PathBuf>> AssetPlanner<'_, F> {
+    /// The URL to use in the published copy, or `None` to leave it alone.
+    fn plan(&mut self, url: &str, image: bool) -> Option<String> {
+        let (path_part, fragment) = match url.find('#') {
+            Some(i) if !image => (&url[..i], &url[i..]),
+            _ => (url, ""),
+        };
+        if path_part.is_empty() {
+            return None;
+        }
+        let resolved = resolve_document_reference(path_part, self.doc_dir.as_deref())?;
+        let source = (self.access)(&resolved).filter(|p| p.is_file())?;
+        if !image && is_markdown(&source) {
+            return None;
+        }
+        if let Some(existing) = self.by_source.get(&source) {
+            return existing.as_ref().map(|u| format!("{}{}", u, fragment));
+        }
+
+        let beside = self
+            .doc_dir
+            .as_ref()
+            .and_then(|dir| source.strip_prefix(dir).ok())
+            .map(Path::to_path_buf);
+        let keep_url = beside.is_some()
+            && !Path::new(path_part).is_absolute()
+            && !path_part.starts_with("file:");
+        let url_rel = match beside {
+            Some(rel) => rel,
+            None => self.unique_asset_name(&source)?,
+        };
+        let dest = self.dest_dir.join(&url_rel);
+        self.taken.insert(dest.clone());
+        self.assets.push(Asset {
+            source: source.clone(),
+            dest,
+        });
+
+        let new_url = (!keep_url).then(|| encode_url_path(&url_rel));
+        self.by_source.insert(source, new_url.clone());
+        new_url.map(|u| format!("{}{}", u, fragment))
+    }
+
+    fn unique_asset_name(&self, source: &Path) -> Option<PathBuf> {
+        let name = Path::new(source.file_name()?);
+        let stem = name.file_stem()?.to_string_lossy().into_owned();
+        let ext = name
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        (1..)
+            .map(|n| match n {
+                1 => PathBuf::from("assets").join(name),
+                n => PathBuf::from("assets").join(format!("{}-{}{}", stem, n, ext)),
+            })
+            .find(|rel| !self.taken.contains(&self.dest_dir.join(rel)))
+    }
+}
+
+/// Find the local files `markdown` links to and rewrite the links that need
+/// a new location. `dest_dir` is the published document's directory,
+/// relative to the repository.
+fn plan_assets(
+    markdown: &str,
+    doc_dir: Option<&Path>,
+    dest_dir: &Path,
+    access: &impl Fn(&Path) -> Option<PathBuf>,
+) -> (String, Vec<Asset>) {
+    let mut planner = AssetPlanner {
+        doc_dir: doc_dir.map(Path::to_path_buf),
+        dest_dir: dest_dir.to_path_buf(),
+        access,
+        by_source: HashMap::new(),
+        taken: HashSet::new(),
+        assets: Vec::new(),
+    };
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut parser = Parser::new_ext(markdown, options).into_offset_iter();
+    for (event, range) in parser.by_ref() {
+        let (url, image) = match event {
+            Event::Start(Tag::Image {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }) => (dest_url, true),
+            Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }) => (dest_url, false),
+            _ => continue,
+        };
+        if let Some(new_url) = planner.plan(&url, image) {
+            // The destination follows the last `](` of the link source
+            let text = &markdown[range.clone()];
+            let Some(open) = text.rfind("](") else {
+                continue;
+            };
+            if let Some(at) = text[open..].find(url.as_ref()) {
+                let start = range.start + open + at;
+                edits.push((start..start + url.len(), new_url));
+            }
+        }
+    }
+    for (_, def) in parser.reference_definitions().iter() {
+        // Reference definitions may point at images or plain files; only
+        // files that are not markdown get copied either way.
+        if let Some(new_url) = planner.plan(&def.dest, false) {
+            let text = &markdown[def.span.clone()];
+            let after_label = text.find("]:").map_or(0, |i| i + 2);
+            if let Some(at) = text[after_label..].find(def.dest.as_ref()) {
+                let start = def.span.start + after_label + at;
+                edits.push((start..start + def.dest.len(), new_url));
+            }
+        }
+    }
+
+    edits.sort_by_key(|(r, _)| r.start);
+    let mut out = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for (range, url) in edits {
+        if range.start < last {
+            continue;
+        }
+        out.push_str(&markdown[last..range.start]);
+        out.push_str(&url);
+        last = range.end;
+    }
+    out.push_str(&markdown[last..]);
+    (out, planner.assets)
+}
+
+fn rel_string(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `doc` and `repo` are canonical; `access` resolves referenced files the
+/// same way (or rejects them).
+pub(crate) fn publish(
+    doc: &Path,
+    repo: &Path,
+    dest_relative_path: &str,
+    commit_message: &str,
+    push: bool,
+    access: impl Fn(&Path) -> Option<PathBuf>,
+) -> Result<PublishResult, CommandError> {
+    ensure_clean_repo(repo)?;
+
+    let doc_name = doc
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| CommandError::new(ErrorCode::InvalidPath, "Invalid document path"))?;
+    let dest = destination(repo, dest_relative_path, &doc_name)?;
+    let dest_dir = dest.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let markdown = fs::read_to_string(doc).map_err(file_cmd::sanitize_io_error)?;
+    let (rewritten, assets) = plan_assets(&markdown, doc.parent(), &dest_dir, &access);
+
+    let mut files = Vec::new();
+    for asset in &assets {
+        let target = prepare_target(repo, &asset.dest)?;
+        // Publishing in place: don't copy a file onto itself
+        if fs::canonicalize(&target).ok().as_deref() != Some(asset.source.as_path()) {
+            fs::copy(&asset.source, &target).map_err(file_cmd::sanitize_io_error)?;
+        }
+        files.push(rel_string(&asset.dest));
+    }
+    let target = prepare_target(repo, &dest)?;
+    fs::write(&target, rewritten).map_err(file_cmd::sanitize_io_error)?;
+    files.push(rel_string(&dest));
+
+    let mut add = vec!["add", "--"];
+    add.extend(files.iter().map(String::as_str));
+    git(repo, &add, GIT_TIMEOUT)?;
+
+    let mut status = vec!["status", "--porcelain", "--"];
+    status.extend(files.iter().map(String::as_str));
+    let committed = !git(repo, &status, GIT_TIMEOUT)?.trim().is_empty();
+    if committed {
+        let mut commit = vec!["commit", "-m", commit_message, "--"];
+        commit.extend(files.iter().map(String::as_str));
+        git(repo, &commit, GIT_TIMEOUT)?;
+    }
+    let commit = git(repo, &["rev-parse", "HEAD"], GIT_TIMEOUT)?
+        .trim()
+        .to_string();
+
+    if push {
+        git(repo, &["push"], PUSH_TIMEOUT).map_err(|mut e| {
+            // The commit is already made; tell the caller which one
+            if let Some(serde_json::Value::Object(details)) = e.details.as_mut() {
+                details.insert("commit".into(), commit.clone().into());
+            }
+            e
+        })?;
+    }
+
+    Ok(PublishResult {
+        commit,
+        committed,
+        pushed: push,
+        files,
+    })
+}
+
+/// Copy a markdown document and the local files it references into
+/// `repo_dir/dest_relative_path`, commit them and optionally `git push`.
+#[tauri::command]
+pub async fn publish_to_git_repo(
+    document_path: String,
+    repo_dir: String,
+    dest_relative_path: String,
+    commit_message: String,
+    push: bool,
+) -> Result<PublishResult, CommandError> {
+    let doc = file_cmd::validate_path(&document_path)?;
+    let repo = file_cmd::validate_path(&repo_dir)?;
+    if !repo.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotADirectory,
+            "Repository path is not a directory",
+        ));
+    }
+    if commit_message.trim().is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Commit message is empty",
+        ));
+    }
+    tokio::task::spawn_blocking(move || {
+        publish(
+            &doc,
+            &repo,
+            &dest_relative_path,
+            &commit_message,
+            push,
+            |p| file_cmd::validate_path(&p.to_string_lossy()).ok(),
+        )
+    })
+    .await
+    .map_err(|e| CommandError::from(format!("Publish task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(p: &Path) -> Option<PathBuf> {
+        fs::canonicalize(p).ok()
+    }
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let out = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .expect("git runs");
+        assert!(
+            out.status.success(),
+            "git {:?}: {}",
+            args,
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    }
+
+    /// A document folder and an initialized repository, or `None` without git.
+    fn fixture(name: &str) -> Option<(PathBuf, PathBuf, PathBuf)> {
+        if Command::new("git").arg("--version").output().is_err() {
+            return None;
+        }
+        let root =
+            std::env::temp_dir().join(format!("moraya-publish-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let docs = root.join("docs");
+        let repo = root.join("site");
+        fs::create_dir_all(docs.join("img")).unwrap();
+        fs::create_dir_all(&repo).unwrap();
+        run(&repo, &["init", "-q"]);
+        run(&repo, &["config", "user.name", "Moraya Test"]);
+        run(&repo, &["config", "user.email", "test@example.com"]);
+        run(&repo, &["config", "commit.gpgsign", "false"]);
+        Some((
+            fs::canonicalize(&root).unwrap(),
+            fs::canonicalize(&docs).unwrap(),
+            fs::canonicalize(&repo).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn should_reject_destinations_outside_the_repo() {
+        for bad in [
+            "../x.md",
+            "/etc/x.md",
+            "a/../../x.md",
+            ".git/hooks/x",
+            "C:/x.md",
+        ] {
+            assert_eq!(
+                check_relative(bad).unwrap_err().code,
+                ErrorCode::AccessDenied,
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(
+            check_relative("_posts/./a.md").unwrap(),
+            PathBuf::from("_posts/a.md")
+        );
+    }
+
+    #[test]
+    fn should_copy_assets_rewrite_links_and_commit() {
+        let Some((root, docs, repo)) = fixture("commit") else {
+            return;
+        };
+        fs::write(docs.join("img/a b.png"), b"png").unwrap();
+        fs::write(root.join("shared.pdf"), b"pdf").unwrap();
+        fs::write(docs.join("other.md"), "x").unwrap();
+        fs::write(
+            docs.join("post.md"),
+            "# Post\n\n![A](img/a%20b.png) [pdf](../shared.pdf#page=2) [next](other.md)\n\n[ref]: ../shared.pdf\n\n![r][ref] ![web](https://example.com/x.png)\n",
+        )
+        .unwrap();
+
+        let result = publish(
+            &docs.join("post.md"),
+            &repo,
+            "_posts/",
+            "Publish post",
+            false,
+            canonical,
+        )
+        .unwrap();
+        assert!(result.committed);
+        assert_eq!(result.commit.len(), 40);
+        assert_eq!(run(&repo, &["rev-parse", "HEAD"]), result.commit);
+        assert_eq!(run(&repo, &["log", "-1", "--format=%s"]), "Publish post");
+        assert_eq!(
+            result.files,
+            vec![
+                "_posts/img/a b.png",
+                "_posts/assets/shared.pdf",
+                "_posts/post.md"
+            ]
+        );
+
+        let published = fs::read_to_string(repo.join("_posts/post.md")).unwrap();
+        assert!(published.contains("![A](img/a%20b.png)"));
+        assert!(published.contains("[pdf](assets/shared.pdf#page=2)"));
+        assert!(published.contains("[next](other.md)"));
+        assert!(published.contains("[ref]: assets/shared.pdf"));
+        assert!(published.contains("https://example.com/x.png"));
+        assert_eq!(
+            fs::read(repo.join("_posts/assets/shared.pdf")).unwrap(),
+            b"pdf"
+        );
+        assert!(!repo.join("_posts/other.md").exists());
+
+        // Publishing unchanged content makes no new commit
+        let again = publish(
+            &docs.join("post.md"),
+            &repo,
+            "_posts",
+            "Again",
+            false,
+            canonical,
+        )
+        .unwrap();
+        assert!(!again.committed);
+        assert_eq!(again.commit, result.commit);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn should_refuse_while_a_merge_is_in_progress() {
+        let Some((root, docs, repo)) = fixture("merge") else {
+            return;
+        };
+        fs::write(docs.join("post.md"), "hi").unwrap();
+        let git_dir = PathBuf::from(run(&repo, &["rev-parse", "--absolute-git-dir"]));
+        fs::write(
+            git_dir.join("MERGE_HEAD"),
+            "0000000000000000000000000000000000000000\n",
+        )
+        .unwrap();
+        let err = publish(&docs.join("post.md"), &repo, "", "msg", false, canonical).unwrap_err();
+        assert_eq!(err.code, ErrorCode::GitConflictInProgress);
+        assert!(!repo.join("post.md").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn should_report_not_a_repository_and_push_failures() {
+        let Some((root, docs, repo)) = fixture("errors") else {
+            return;
+        };
+        fs::write(docs.join("post.md"), "hi").unwrap();
+        let err = publish(&docs.join("post.md"), &docs, "", "msg", false, canonical).unwrap_err();
+        assert_eq!(err.code, ErrorCode::GitNotRepository);
+
+        // No remote configured: the commit exists, the push error carries git's stderr
+        let err = publish(&docs.join("post.md"), &repo, "", "msg", true, canonical).unwrap_err();
+        assert_eq!(err.code, ErrorCode::GitCommandFailed);
+        let details = err.details.unwrap();
+        assert_eq!(details["command"], "push");
+        assert!(!details["stderr"].as_str().unwrap().is_empty());
+        assert_eq!(
+            details["commit"],
+            run(&repo, &["rev-parse", "HEAD"]).as_str()
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_not_follow_symlinks_out_of_the_repo() {
+        let Some((root, docs, repo)) = fixture("symlink") else {
+            return;
+        };
+        fs::write(docs.join("post.md"), "hi").unwrap();
+        let outside = root.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, repo.join("blog")).unwrap();
+        let err = publish(
+            &docs.join("post.md"),
+            &repo,
+            "blog/post.md",
+            "msg",
+            false,
+            canonical,
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::AccessDenied);
+        assert!(!outside.join("post.md").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
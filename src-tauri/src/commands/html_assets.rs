@@ -0,0 +1,478 @@
+//! Self-contained HTML exports: local files referenced by `<img src>` and
+//! CSS `url()` (in `<style>` blocks and `style` attributes) are embedded as
+//! base64 data URIs, so the exported file still shows its images when
+//! emailed or moved. SVG images are inlined as `<svg>` markup instead, so
+//! they stay vector.
+//!
+//! Remote URLs and existing data URIs are left alone. Files over the
+//! per-file cap, or past the total cap, keep their path and are listed in
+//! the result.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use base64::Engine;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use super::epub_export::image_type;
+use super::error::{CommandError, ErrorCode};
+use super::file::{resolve_document_reference, validate_path};
+use super::link_metadata::{attr, attributes};
+
+const DEFAULT_MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+const DEFAULT_MAX_TOTAL_BYTES: u64 = 25 * 1024 * 1024;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InlineOptions {
+    /// Largest file embedded, in bytes.
+    pub max_file_bytes: Option<u64>,
+    /// Most bytes embedded across the whole document.
+    pub max_total_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason {
+    /// Over `maxFileBytes`.
+    TooLarge,
+    /// Would push the document past `maxTotalBytes`.
+    TotalLimit,
+    NotFound,
+    AccessDenied,
+    /// Not an image or font type a browser can show from a data URI.
+    Unsupported,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedAsset {
+    /// The reference as written in the HTML.
+    pub src: String,
+    pub reason: SkipReason,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlinedHtml {
+    pub html: String,
+    /// References replaced by embedded content.
+    pub inlined: usize,
+    /// File bytes embedded, before base64.
+    pub total_bytes: u64,
+    pub skipped: Vec<SkippedAsset>,
+}
+
+/// A local file ready to embed.
+#[derive(Clone)]
+struct Asset {
+    mime: &'static str,
+    data: Vec<u8>,
+}
+
+impl Asset {
+    fn is_svg(&self) -> bool {
+        self.mime == "image/svg+xml"
+    }
+
+    fn data_uri(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime,
+            base64::engine::general_purpose::STANDARD.encode(&self.data)
+        )
+    }
+}
+
+fn img_tag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)<img\b[^>]*>").expect("valid img regex"))
+}
+
+fn style_block_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?is)(<style\b[^>]*>)(.*?)(</style>)").expect("valid style regex")
+    })
+}
+
+fn style_attr_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?i)(\sstyle\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).expect("valid style regex")
+    })
+}
+
+fn css_url_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)'"\s]*))\s*\)"#)
+            .expect("valid url() regex")
+    })
+}
+
+fn svg_start_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)<svg\b").expect("valid svg regex"))
+}
+
+fn svg_script_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?is)<script\b.*?</script\s*>|\son[a-z]+\s*=\s*("[^"]*"|'[^']*')"#)
+            .expect("valid script regex")
+    })
+}
+
+/// Whether `src` names something other than a local file.
+fn is_remote(src: &str) -> bool {
+    let src = src.trim();
+    src.is_empty()
+        || src.starts_with('#')
+        || src.starts_with("//")
+        || src.starts_with("data:")
+        || (src.contains(':') && !src.starts_with("file:") && !looks_like_drive_path(src))
+}
+
+/// `C:\img.png` or `C:/img.png`.
+fn looks_like_drive_path(src: &str) -> bool {
+    let b = src.as_bytes();
+    b.len() > 2 && b[0].is_ascii_alphabetic() && b[1] == b':' && matches!(b[2], b'\\' | b'/')
+}
+
+fn font_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "woff2" => "font/woff2",
+        "woff" => "font/woff",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "avif" => "image/avif",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        _ => return None,
+    })
+}
+
+struct Inliner<'a, F> {
+    base_dir: Option<&'a Path>,
+    access: &'a F,
+    max_file: u64,
+    max_total: u64,
+    cache: HashMap<PathBuf, Result<Asset, (SkipReason, Option<u64>)>>,
+    total: u64,
+    inlined: usize,
+    skipped: Vec<SkippedAsset>,
+}
+
+impl<F: Fn(&Path) -> Option<PathBuf>> Inliner<'_, F> {
+    fn load(&mut self, path: PathBuf) -> Result<Asset, (SkipReason, Option<u64>)> {
+        if let Some(cached) = self.cache.get(&path) {
+            return cached.clone();
+        }
+        let max_file = self.max_file;
+        let load = || {
+            let safe = (self.access)(&path).ok_or((SkipReason::AccessDenied, None))?;
+            let meta = fs::metadata(&safe).map_err(|_| (SkipReason::NotFound, None))?;
+            if !meta.is_file() {
+                return Err((SkipReason::NotFound, None));
+            }
+            if meta.len() > max_file {
+                return Err((SkipReason::TooLarge, Some(meta.len())));
+            }
+            let data = fs::read(&safe).map_err(|_| (SkipReason::NotFound, None))?;
+            let mime = image_type(&data)
+                .map(|(mime, _)| mime)
+                .or_else(|| font_type(&safe))
+                .ok_or((SkipReason::Unsupported, Some(meta.len())))?;
+            Ok(Asset { mime, data })
+        };
+        let result = load();
+        self.cache.insert(path, result.clone());
+        result
+    }
+
+    /// The file `src` refers to, or `None` to leave the reference alone.
+    /// Skips are recorded.
+    fn asset(&mut self, src: &str) -> Option<Asset> {
+        if is_remote(src) {
+            return None;
+        }
+        let path = src.split(['?', '#']).next().unwrap_or(src);
+        let result = match resolve_document_reference(path, self.base_dir) {
+            Some(path) => self.load(path),
+            None => Err((SkipReason::NotFound, None)),
+        };
+        let result = result.and_then(|asset| {
+            let size = asset.data.len() as u64;
+            if self.total + size > self.max_total {
+                return Err((SkipReason::TotalLimit, Some(size)));
+            }
+            self.total += size;
+            Ok(asset)
+        });
+        match result {
+            Ok(asset) => {
+                self.inlined += 1;
+                Some(asset)
+            }
+            Err((reason, size)) => {
+                self.skipped.push(SkippedAsset {
+                    src: src.to_string(),
+                    reason,
+                    size,
+                });
+                None
+            }
+        }
+    }
+
+    /// Replace `url()` references in a CSS fragment.
+    fn inline_css(&mut self, css: &str) -> String {
+        css_url_re()
+            .replace_all(css, |c: &Captures| {
+                let src = c
+                    .get(1)
+                    .or(c.get(2))
+                    .or(c.get(3))
+                    .map_or("", |m| m.as_str());
+                let src = super::link_metadata::decode_entities(src);
+                match self.asset(&src) {
+                    // base64 needs no quoting, so it fits any attribute quote
+                    Some(asset) => format!("url({})", asset.data_uri()),
+                    None => c[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    fn inline_img(&mut self, tag: &str) -> String {
+        let attrs = attributes(tag);
+        let Some(src) = attr(&attrs, "src").map(String::from) else {
+            return tag.to_string();
+        };
+        let Some(asset) = self.asset(&src) else {
+            return tag.to_string();
+        };
+        if asset.is_svg() {
+            if let Some(svg) = svg_markup(&asset.data, &attrs) {
+                return svg;
+            }
+        }
+        let uri = asset.data_uri();
+        match src_value_range(tag) {
+            Some(range) => format!("{}{}{}", &tag[..range.start], uri, &tag[range.end..]),
+            None => tag.to_string(),
+        }
+    }
+
+    fn run(&mut self, html: &str) -> String {
+        let html = img_tag_re().replace_all(html, |c: &Captures| self.inline_img(&c[0]));
+        let html = style_block_re().replace_all(&html, |c: &Captures| {
+            format!("{}{}{}", &c[1], self.inline_css(&c[2]), &c[3])
+        });
+        let html = style_attr_re().replace_all(&html, |c: &Captures| match c.get(2) {
+            Some(css) => format!("{}\"{}\"", &c[1], self.inline_css(css.as_str())),
+            None => format!("{}'{}'", &c[1], self.inline_css(&c[3])),
+        });
+        html.into_owned()
+    }
+}
+
+/// Byte range of the `src` attribute's value inside an `<img>` tag.
+fn src_value_range(tag: &str) -> Option<std::ops::Range<usize>> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r#"(?i)\ssrc\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .expect("valid src regex")
+    });
+    let c = re.captures(tag)?;
+    let value = c.get(1).or(c.get(2)).or(c.get(3))?;
+    Some(value.range())
+}
+
+/// The `<svg>` element of an SVG file, without prolog or scripts, carrying
+/// the `<img>` tag's size, class, style and alt text.
+fn svg_markup(data: &[u8], img_attrs: &[(String, String)]) -> Option<String> {
+    let text = std::str::from_utf8(data).ok()?;
+    let start = svg_start_re().find(text)?.start();
+    let svg = svg_script_re().replace_all(&text[start..], "");
+    let svg = svg.trim_end();
+    let open_end = svg.find('>')?;
+    let mut extra = String::new();
+    for name in ["width", "height", "class", "style"] {
+        if let Some(value) = attr(img_attrs, name) {
+            extra.push_str(&format!(" {}=\"{}\"", name, escape_attr(value)));
+        }
+    }
+    if let Some(alt) = attr(img_attrs, "alt").filter(|a| !a.is_empty()) {
+        extra.push_str(&format!(" role=\"img\" aria-label=\"{}\"", escape_attr(alt)));
+    }
+    // The file's own width/height come first, so the <img> size wins
+    let (head, rest) = svg.split_at(open_end);
+    let head = head.trim_end_matches('/');
+    let self_closing = head.len() != svg[..open_end].len();
+    Some(format!(
+        "{}{}{}{}",
+        head,
+        extra,
+        if self_closing { "/" } else { "" },
+        rest
+    ))
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// Embed the local files `html` references. `base_dir` is the folder of
+/// the exported document; `access` validates each file path.
+fn inline_assets(
+    html: &str,
+    base_dir: Option<&Path>,
+    options: &InlineOptions,
+    access: &impl Fn(&Path) -> Option<PathBuf>,
+) -> InlinedHtml {
+    let mut inliner = Inliner {
+        base_dir,
+        access,
+        max_file: options.max_file_bytes.unwrap_or(DEFAULT_MAX_FILE_BYTES),
+        max_total: options.max_total_bytes.unwrap_or(DEFAULT_MAX_TOTAL_BYTES),
+        cache: HashMap::new(),
+        total: 0,
+        inlined: 0,
+        skipped: Vec::new(),
+    };
+    let html = inliner.run(html);
+    InlinedHtml {
+        html,
+        inlined: inliner.inlined,
+        total_bytes: inliner.total,
+        skipped: inliner.skipped,
+    }
+}
+
+/// Make an exported HTML document self-contained; see the module docs.
+/// `base_dir` is the folder of the document being exported.
+#[tauri::command]
+pub async fn inline_html_assets(
+    html: String,
+    base_dir: Option<String>,
+    options: Option<InlineOptions>,
+) -> Result<InlinedHtml, CommandError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let base_dir = base_dir.map(PathBuf::from);
+        inline_assets(
+            &html,
+            base_dir.as_deref(),
+            &options.unwrap_or_default(),
+            &|p: &Path| validate_path(&p.to_string_lossy()).ok(),
+        )
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+
+    fn setup(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("moraya-inline-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("img")).unwrap();
+        fs::write(dir.join("img/a b.png"), PNG).unwrap();
+        fs::write(
+            dir.join("img/icon.svg"),
+            "<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 8 8\">\
+             <script>alert(1)</script><rect onclick=\"x()\" width=\"8\" height=\"8\"/></svg>\n",
+        )
+        .unwrap();
+        fs::write(dir.join("img/big.png"), [PNG, &[0u8; 64]].concat()).unwrap();
+        dir
+    }
+
+    fn run(html: &str, dir: &Path, options: InlineOptions) -> InlinedHtml {
+        inline_assets(html, Some(dir), &options, &|p: &Path| Some(p.to_path_buf()))
+    }
+
+    #[test]
+    fn should_embed_images_and_css_urls_and_leave_remote_ones() {
+        let dir = setup("embed");
+        let html = "<img src=\"img/a%20b.png\" alt=\"A\">\
+                    <img src=\"https://example.com/x.png\">\
+                    <p style=\"background: url('img/a b.png')\">x</p>\
+                    <style>body { background: url(img/a%20b.png) }</style>\
+                    <code>url(img/a b.png)</code>";
+        let result = run(html, &dir, InlineOptions::default());
+        let uri = format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(PNG)
+        );
+        assert_eq!(result.inlined, 3);
+        assert_eq!(result.total_bytes, 3 * PNG.len() as u64);
+        assert!(result.skipped.is_empty());
+        assert_eq!(
+            result.html,
+            format!(
+                "<img src=\"{uri}\" alt=\"A\">\
+                 <img src=\"https://example.com/x.png\">\
+                 <p style=\"background: url({uri})\">x</p>\
+                 <style>body {{ background: url({uri}) }}</style>\
+                 <code>url(img/a b.png)</code>"
+            )
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_inline_svg_markup_without_scripts() {
+        let dir = setup("svg");
+        let result = run(
+            "<img src=\"img/icon.svg\" alt=\"Logo\" width=\"32\">",
+            &dir,
+            InlineOptions::default(),
+        );
+        assert_eq!(
+            result.html,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 8 8\" width=\"32\" \
+             role=\"img\" aria-label=\"Logo\"><rect width=\"8\" height=\"8\"/></svg>"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_skip_files_over_the_caps() {
+        let dir = setup("caps");
+        let options = InlineOptions {
+            max_file_bytes: Some(32),
+            max_total_bytes: Some(PNG.len() as u64),
+        };
+        let html = "<img src=\"img/big.png\"><img src=\"img/a b.png\">\
+                    <img src=\"img/a b.png\"><img src=\"img/missing.png\">";
+        let result = run(html, &dir, options);
+        assert_eq!(result.inlined, 1);
+        let reasons: Vec<SkipReason> = result.skipped.iter().map(|s| s.reason).collect();
+        assert_eq!(
+            reasons,
+            [
+                SkipReason::TooLarge,
+                SkipReason::TotalLimit,
+                SkipReason::NotFound
+            ]
+        );
+        assert_eq!(result.skipped[0].size, Some(PNG.len() as u64 + 64));
+        assert!(result.html.starts_with("<img src=\"img/big.png\">"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,491 @@
+//! HTML → Markdown for pasted and clipped web content. Covers what article
+//! bodies use — headings, paragraphs, emphasis, links, images, lists,
+//! quotes, code and simple tables — and drops scripts, styles and unknown
+//! markup, keeping their text.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use url::Url;
+
+use super::link_metadata::{attr, attributes, decode_entities};
+
+/// Elements whose content is never shown.
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "head", "svg", "iframe", "object",
+];
+/// Elements that start and end a paragraph.
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "header",
+    "footer",
+    "aside",
+    "nav",
+    "figure",
+    "figcaption",
+    "dl",
+    "dt",
+    "dd",
+    "address",
+    "details",
+    "summary",
+    "form",
+    "fieldset",
+];
+
+enum Frame {
+    Root,
+    Inline(&'static str),
+    Link(Option<String>),
+    Heading(usize),
+    Quote,
+    Item { marker: String },
+    Pre { lang: Option<String> },
+    Cell,
+}
+
+struct Converter {
+    base: Option<Url>,
+    /// Open frames with their output; the last one receives text.
+    stack: Vec<(Frame, String)>,
+    /// `Some(next number)` for `<ol>`, `None` for `<ul>`.
+    lists: Vec<Option<usize>>,
+    /// Rows of the table being built, and the cells of the current row.
+    rows: Vec<Vec<String>>,
+    cells: Vec<String>,
+    in_pre: bool,
+}
+
+fn ends_with_space(buf: &str) -> bool {
+    buf.is_empty() || buf.ends_with(|c: char| c.is_whitespace())
+}
+
+/// End the current paragraph: the next content starts after a blank line.
+fn paragraph_break(buf: &mut String) {
+    let trimmed = buf.trim_end_matches([' ', '\t']).len();
+    buf.truncate(trimmed);
+    if buf.is_empty() || buf.ends_with("\n\n") {
+        return;
+    }
+    buf.push_str(if buf.ends_with('\n') { "\n" } else { "\n\n" });
+}
+
+fn line_break(buf: &mut String) {
+    let trimmed = buf.trim_end_matches([' ', '\t']).len();
+    buf.truncate(trimmed);
+    if !buf.is_empty() && !buf.ends_with('\n') {
+        buf.push('\n');
+    }
+}
+
+/// Prefix every line of `text` with `first` (first line) or `rest`.
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Converter {
+    fn buf(&mut self) -> &mut String {
+        &mut self.stack.last_mut().expect("root frame").1
+    }
+
+    fn url(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with("javascript:") {
+            return None;
+        }
+        match &self.base {
+            Some(base) => base.join(href).ok().map(|u| u.to_string()),
+            None => Some(href.to_string()),
+        }
+    }
+
+    fn text(&mut self, raw: &str) {
+        let text = decode_entities(raw);
+        if self.in_pre {
+            self.buf().push_str(&text);
+            return;
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let buf = self.buf();
+        if words.is_empty() {
+            if !text.is_empty() && !ends_with_space(buf) {
+                buf.push(' ');
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace) && !ends_with_space(buf) {
+            buf.push(' ');
+        }
+        buf.push_str(&words.join(" "));
+        if text.ends_with(char::is_whitespace) {
+            buf.push(' ');
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &[(String, String)]) {
+        match name {
+            "br" => {
+                let line_end = if self.in_pre { "\n" } else { "  \n" };
+                let buf = self.buf();
+                let trimmed = buf.trim_end_matches(' ').len();
+                buf.truncate(trimmed);
+                buf.push_str(line_end);
+            }
+            "hr" => {
+                let buf = self.buf();
+                paragraph_break(buf);
+                buf.push_str("---\n\n");
+            }
+            "img" => {
+                let Some(src) = attr(attrs, "src").and_then(|s| self.url(s)) else {
+                    return;
+                };
+                let alt = attr(attrs, "alt").unwrap_or("").replace(['[', ']'], "");
+                self.buf().push_str(&format!("![{}]({})", alt, src));
+            }
+            "strong" | "b" => self.push(Frame::Inline("**")),
+            "em" | "i" => self.push(Frame::Inline("*")),
+            "del" | "s" | "strike" => self.push(Frame::Inline("~~")),
+            "code" if self.in_pre => {
+                let lang = attr(attrs, "class").and_then(|c| {
+                    c.split_whitespace()
+                        .find_map(|c| c.strip_prefix("language-").or(c.strip_prefix("lang-")))
+                        .map(String::from)
+                });
+                if let Some((Frame::Pre { lang: slot }, _)) = self.stack.last_mut() {
+                    if slot.is_none() {
+                        *slot = lang;
+                    }
+                }
+            }
+            "code" | "kbd" | "samp" => self.push(Frame::Inline("`")),
+            "a" => {
+                let href = attr(attrs, "href").and_then(|h| self.url(h));
+                self.push(Frame::Link(href));
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                paragraph_break(self.buf());
+                self.push(Frame::Heading(usize::from(name.as_bytes()[1] - b'0')));
+            }
+            "blockquote" => {
+                paragraph_break(self.buf());
+                self.push(Frame::Quote);
+            }
+            "pre" => {
+                paragraph_break(self.buf());
+                self.in_pre = true;
+                self.push(Frame::Pre { lang: None });
+            }
+            "ul" | "ol" => {
+                let start = attr(attrs, "start").and_then(|s| s.parse().ok());
+                let list = (name == "ol").then(|| start.unwrap_or(1));
+                if self.lists.is_empty() {
+                    paragraph_break(self.buf());
+                } else {
+                    line_break(self.buf());
+                }
+                self.lists.push(list);
+            }
+            "li" => {
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.push(Frame::Item { marker });
+            }
+            "tr" => self.cells.clear(),
+            "td" | "th" => self.push(Frame::Cell),
+            _ if BLOCKS.contains(&name) => paragraph_break(self.buf()),
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, frame: Frame) {
+        self.stack.push((frame, String::new()));
+    }
+
+    /// Close the innermost frame opened by `name`, if it is on the stack.
+    fn close(&mut self, name: &str) {
+        match name {
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    paragraph_break(self.buf());
+                }
+                return;
+            }
+            "tr" => {
+                if !self.cells.is_empty() {
+                    self.rows.push(std::mem::take(&mut self.cells));
+                }
+                return;
+            }
+            "table" => {
+                self.table();
+                return;
+            }
+            _ if BLOCKS.contains(&name) => {
+                paragraph_break(self.buf());
+                return;
+            }
+            _ => {}
+        }
+        let matches = |frame: &Frame| match frame {
+            Frame::Inline(mark) => match *mark {
+                "**" => matches!(name, "strong" | "b"),
+                "*" => matches!(name, "em" | "i"),
+                "~~" => matches!(name, "del" | "s" | "strike"),
+                _ => matches!(name, "code" | "kbd" | "samp"),
+            },
+            Frame::Link(_) => name == "a",
+            Frame::Heading(_) => name.len() == 2 && name.starts_with('h'),
+            Frame::Quote => name == "blockquote",
+            Frame::Item { .. } => name == "li",
+            Frame::Pre { .. } => name == "pre",
+            Frame::Cell => matches!(name, "td" | "th"),
+            Frame::Root => false,
+        };
+        let Some(depth) = self.stack.iter().rposition(|(f, _)| matches(f)) else {
+            return;
+        };
+        // Close anything left open inside it (unbalanced markup)
+        while self.stack.len() > depth {
+            self.pop();
+        }
+    }
+
+    fn pop(&mut self) {
+        let Some((frame, content)) = self.stack.pop() else {
+            return;
+        };
+        match frame {
+            Frame::Root => {}
+            Frame::Inline(mark) => {
+                let inner = content.trim();
+                let buf = self.buf();
+                if content.starts_with(' ') && !ends_with_space(buf) {
+                    buf.push(' ');
+                }
+                if !inner.is_empty() {
+                    buf.push_str(&format!("{}{}{}", mark, inner, mark));
+                }
+                if content.ends_with(' ') && !inner.is_empty() {
+                    buf.push(' ');
+                }
+            }
+            Frame::Link(href) => {
+                let text = content.trim();
+                let buf = self.buf();
+                match href {
+                    Some(href) if text.is_empty() => buf.push_str(&format!("<{}>", href)),
+                    Some(href) => buf.push_str(&format!("[{}]({})", text, href)),
+                    None => buf.push_str(text),
+                }
+            }
+            Frame::Heading(level) => {
+                let text = content.split_whitespace().collect::<Vec<_>>().join(" ");
+                let buf = self.buf();
+                if !text.is_empty() {
+                    buf.push_str(&format!("{} {}", "#".repeat(level), text));
+                }
+                paragraph_break(buf);
+            }
+            Frame::Quote => {
+                let text = content.trim();
+                let buf = self.buf();
+                if !text.is_empty() {
+                    buf.push_str(&indent(text, "> ", "> "));
+                }
+                paragraph_break(buf);
+            }
+            Frame::Item { marker } => {
+                let text = content.trim();
+                let rest = " ".repeat(marker.len());
+                let buf = self.buf();
+                line_break(buf);
+                buf.push_str(&indent(text, &marker, &rest));
+                buf.push('\n');
+            }
+            Frame::Pre { lang } => {
+                self.in_pre = self
+                    .stack
+                    .iter()
+                    .any(|(f, _)| matches!(f, Frame::Pre { .. }));
+                let code = content.trim_matches('\n');
+                let fence = if code.contains("```") { "````" } else { "```" };
+                let buf = self.buf();
+                buf.push_str(&format!(
+                    "{}{}\n{}\n{}",
+                    fence,
+                    lang.unwrap_or_default(),
+                    code,
+                    fence
+                ));
+                paragraph_break(buf);
+            }
+            Frame::Cell => {
+                let text = content.split_whitespace().collect::<Vec<_>>().join(" ");
+                self.cells.push(text.replace('|', "\\|"));
+            }
+        }
+    }
+
+    fn table(&mut self) {
+        if !self.cells.is_empty() {
+            self.rows.push(std::mem::take(&mut self.cells));
+        }
+        let rows = std::mem::take(&mut self.rows);
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(width, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); width])];
+        lines.extend(rows[1..].iter().map(|r| line(r)));
+        let buf = self.buf();
+        paragraph_break(buf);
+        buf.push_str(&lines.join("\n"));
+        paragraph_break(buf);
+    }
+}
+
+/// Convert an HTML document or fragment. Relative links and images are
+/// resolved against `base_url` when given.
+pub(crate) fn convert(html: &str, base_url: Option<&str>) -> String {
+    static TOKEN_RE: OnceLock<Regex> = OnceLock::new();
+    let token_re = TOKEN_RE.get_or_init(|| {
+        Regex::new(r"(?s)<!--.*?-->|<![^>]*>|<\?[^>]*>|<(/?)([A-Za-z][A-Za-z0-9-]*)([^>]*)>")
+            .expect("valid tag regex")
+    });
+    let mut conv = Converter {
+        base: base_url.and_then(|u| Url::parse(u).ok()),
+        stack: vec![(Frame::Root, String::new())],
+        lists: Vec::new(),
+        rows: Vec::new(),
+        cells: Vec::new(),
+        in_pre: false,
+    };
+    let mut pos = 0;
+    // Name of the element whose content is being skipped
+    let mut skipping: Option<String> = None;
+    for caps in token_re.captures_iter(html) {
+        let whole = caps.get(0).expect("match");
+        if skipping.is_none() {
+            conv.text(&html[pos..whole.start()]);
+        }
+        pos = whole.end();
+        let Some(name) = caps.get(2) else {
+            continue;
+        };
+        let name = name.as_str().to_ascii_lowercase();
+        let closing = !caps[1].is_empty();
+        if let Some(skipped) = &skipping {
+            if closing && *skipped == name {
+                skipping = None;
+            }
+            continue;
+        }
+        if closing {
+            conv.close(&name);
+        } else if SKIPPED.contains(&name.as_str()) {
+            if !caps[3].trim_end().ends_with('/') {
+                skipping = Some(name);
+            }
+        } else {
+            conv.open(&name, &attributes(&caps[3]));
+        }
+    }
+    if skipping.is_none() {
+        conv.text(&html[pos..]);
+    }
+    while conv.stack.len() > 1 {
+        conv.pop();
+    }
+    let out = conv.stack.pop().map(|(_, buf)| buf).unwrap_or_default();
+    let mut result = String::with_capacity(out.len());
+    let mut blank_lines = 0;
+    for line in out.trim().lines() {
+        let line = if line.trim().is_empty() { "" } else { line };
+        blank_lines = if line.is_empty() { blank_lines + 1 } else { 0 };
+        if blank_lines < 2 {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+/// Convert HTML (e.g. a web page selection) to Markdown.
+#[tauri::command]
+pub fn html_to_markdown(html: String, base_url: Option<String>) -> String {
+    convert(&html, base_url.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_convert_article_markup() {
+        let html = r#"<html><head><title>x</title><style>p{}</style></head><body>
+            <h2>Intro <small>draft</small></h2>
+            <p>Some <b>bold</b> and <em>italic</em> text with a
+               <a href="/docs?a=1&amp;b=2">relative link</a>.<br>Next line</p>
+            <script>alert(1)</script>
+            <ul><li>one</li><li>two<ol start="3"><li>three</li></ol></li></ul>
+            <blockquote><p>Quoted</p><p>twice</p></blockquote>
+            <pre><code class="language-rust">fn main() {
+    println!("&lt;hi&gt;");
+}</code></pre>
+            <p><img src="a.png" alt="A [pic]"></p>
+            </body></html>"#;
+        let md = convert(html, Some("https://example.com/blog/post"));
+        assert_eq!(
+            md,
+            "## Intro draft\n\n\
+             Some **bold** and *italic* text with a [relative link](https://example.com/docs?a=1&b=2).  \n\
+             Next line\n\n\
+             - one\n\
+             - two\n  3. three\n\n\
+             > Quoted\n>\n> twice\n\n\
+             ```rust\nfn main() {\n    println!(\"<hi>\");\n}\n```\n\n\
+             ![A pic](https://example.com/blog/a.png)\n"
+        );
+    }
+
+    #[test]
+    fn should_convert_tables_and_tolerate_broken_markup() {
+        let md = convert(
+            "<table><tr><th>Name</th><th>Value</th></tr><tr><td>a|b</td><td>1</td></tr></table>",
+            None,
+        );
+        assert_eq!(md, "| Name | Value |\n| --- | --- |\n| a\\|b | 1 |\n");
+        assert_eq!(
+            convert("<p><b>unclosed <i>tags", None),
+            "**unclosed *tags***\n"
+        );
+        assert_eq!(convert("a < b & c", None), "a < b & c\n");
+    }
+}
@@ -0,0 +1,507 @@
+//! Full-document PNG export through a hidden WebView.
+//!
+//! Flow:
+//!   1. Open the /print route in a hidden window at the export width.
+//!   2. eval `window.__moraya_print.capture(payload)`; the route renders the
+//!      document and reports its laid-out height via `capture_document_ready`.
+//!   3. Size the window to the document (at most `MAX_SLICE_HEIGHT` CSS px)
+//!      and snapshot it with the platform WebView API, scrolling slice by
+//!      slice when the document is taller than one capture can be.
+//!   4. Stitch the slices into one image and write it like
+//!      `write_file_binary` does.
+//!
+//! The command is async, so building the window runs off the main thread
+//! and doesn't hit the WebView2 deadlock described in `create_new_window`.
+//! Errors are recoverable: the frontend falls back to its canvas capture.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use image::{imageops, ImageFormat, RgbaImage};
+use tauri::ipc::Channel;
+use tauri::{AppHandle, LogicalSize, State, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+use crate::commands::file as file_cmd;
+use crate::commands::pdf_export::ProgressEvent;
+
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(20);
+/// Tallest single capture, in CSS px. At 2x that is 8192 device px, within
+/// the texture limits of every WebView we ship on.
+const MAX_SLICE_HEIGHT: u32 = 4096;
+/// Largest stitched image (~480 MB of RGBA).
+const MAX_IMAGE_PIXELS: u64 = 120_000_000;
+/// Time for the WebView to repaint after a scroll or resize.
+const SETTLE_DELAY: Duration = Duration::from_millis(150);
+
+/// Pending height handshakes, keyed by capture label.
+pub struct ImageCaptureState {
+    ready: Mutex<HashMap<String, tokio::sync::oneshot::Sender<f64>>>,
+}
+
+impl ImageCaptureState {
+    pub fn new() -> Self {
+        Self {
+            ready: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for ImageCaptureState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One viewport capture: scroll to `scroll_y`, drop the first `skip` rows
+/// (already covered by the previous slice) and keep `height` rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slice {
+    scroll_y: u32,
+    skip: u32,
+    height: u32,
+}
+
+/// Cover `total` CSS px with `viewport`-high captures. The last capture is
+/// scrolled flush with the bottom rather than past it, so it overlaps the
+/// previous one instead of showing blank space.
+fn plan_slices(total: u32, viewport: u32) -> Vec<Slice> {
+    let viewport = viewport.max(1);
+    let mut slices = Vec::new();
+    let mut y = 0;
+    while y < total {
+        let scroll_y = y.min(total.saturating_sub(viewport));
+        let height = viewport.min(total - y);
+        slices.push(Slice {
+            scroll_y,
+            skip: y - scroll_y,
+            height,
+        });
+        y += height;
+    }
+    slices
+}
+
+/// Paste `slices` (decoded captures, in `plan` order) below each other.
+/// `scale` is device px per CSS px, taken from the first capture.
+fn stitch(slices: &[RgbaImage], plan: &[Slice], total: u32) -> Result<RgbaImage, String> {
+    let first = slices.first().ok_or("no slices were captured")?;
+    let width = first.width();
+    let scale = first.height() as f64 / plan[0].height.max(1) as f64;
+    let px = |css: u32| (css as f64 * scale).round() as u32;
+
+    let height = px(total);
+    if width as u64 * height as u64 > MAX_IMAGE_PIXELS {
+        return Err("The document is too long to export as a single image".to_string());
+    }
+    let mut canvas = RgbaImage::new(width, height);
+    let mut dest_y = 0;
+    for (img, slice) in slices.iter().zip(plan) {
+        let skip = px(slice.skip).min(img.height());
+        let rows = px(slice.height)
+            .min(img.height() - skip)
+            .min(height.saturating_sub(dest_y));
+        let part = imageops::crop_imm(img, 0, skip, width.min(img.width()), rows).to_image();
+        imageops::replace(&mut canvas, &part, 0, dest_y as i64);
+        dest_y += rows;
+    }
+    Ok(canvas)
+}
+
+/// Render `markdown` off-screen at `width` CSS px and save the whole
+/// document as one PNG at `output_path`. `label` names the job (letters,
+/// digits, `-` and `_`); slice progress streams as `Paginating`.
+#[tauri::command]
+pub async fn capture_document_png(
+    app: AppHandle,
+    state: State<'_, ImageCaptureState>,
+    label: String,
+    output_path: String,
+    width: u32,
+    markdown: String,
+    on_progress: Channel<ProgressEvent>,
+) -> Result<(), String> {
+    let _ = file_cmd::validate_path(&output_path)?;
+    if label.is_empty()
+        || !label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Invalid capture label".to_string());
+    }
+    let width = width.clamp(320, 2400);
+    let _ = on_progress.send(ProgressEvent::Preparing);
+
+    let result = capture(&app, &state, &label, width, &markdown, &on_progress).await;
+    if let Ok(mut ready) = state.ready.lock() {
+        ready.remove(&label);
+    }
+    let png = result.map_err(|e| {
+        let _ = on_progress.send(ProgressEvent::Fallback { reason: e.clone() });
+        e
+    })?;
+
+    let _ = on_progress.send(ProgressEvent::Writing);
+    file_cmd::write_binary(&output_path, &png)?;
+    let _ = on_progress.send(ProgressEvent::Done);
+    Ok(())
+}
+
+/// Called by the /print route once the capture layout is final.
+#[tauri::command]
+pub fn capture_document_ready(
+    state: State<'_, ImageCaptureState>,
+    label: String,
+    height: f64,
+) -> Result<(), String> {
+    let tx = state
+        .ready
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?
+        .remove(&label)
+        .ok_or_else(|| format!("no capture waiting for {label}"))?;
+    let _ = tx.send(height);
+    Ok(())
+}
+
+async fn capture(
+    app: &AppHandle,
+    state: &ImageCaptureState,
+    label: &str,
+    width: u32,
+    markdown: &str,
+    progress: &Channel<ProgressEvent>,
+) -> Result<Vec<u8>, String> {
+    let window = WebviewWindowBuilder::new(
+        app,
+        format!("moraya-capture-{label}"),
+        WebviewUrl::App("print/".into()),
+    )
+    // WebView2 and WebKitGTK only paint windows that are shown, so those
+    // are shown off-screen instead of hidden
+    .visible(!cfg!(target_os = "macos"))
+    .focused(false)
+    .inner_size(width as f64, 900.0)
+    .position(-20000.0, -20000.0)
+    .decorations(false)
+    .resizable(false)
+    .skip_taskbar(true)
+    .build()
+    .map_err(|e| format!("hidden window build failed: {e}"))?;
+    let _cleanup = WindowGuard(window.clone());
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state
+        .ready
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?
+        .insert(label.to_string(), tx);
+
+    let _ = progress.send(ProgressEvent::Rendering);
+    // Let the route mount and expose `__moraya_print`
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let payload = serde_json::json!({ "label": label, "markdown": markdown });
+    window
+        .eval(&format!(
+            "(async()=>{{try{{await window.__moraya_print.capture({payload});}}catch(e){{console.error('capture render failed',e);}}}})()"
+        ))
+        .map_err(|e| format!("eval capture: {e}"))?;
+
+    let height = match tokio::time::timeout(READY_TIMEOUT, rx).await {
+        Ok(Ok(h)) if h.is_finite() && h > 0.0 => h.ceil() as u32,
+        Ok(Ok(_)) => return Err("the document has no height".to_string()),
+        Ok(Err(_)) => return Err("capture handshake cancelled".to_string()),
+        Err(_) => return Err("render handshake timeout (30s)".to_string()),
+    };
+
+    let viewport = height.min(MAX_SLICE_HEIGHT);
+    window
+        .set_size(LogicalSize::new(width as f64, viewport as f64))
+        .map_err(|e| format!("resize capture window: {e}"))?;
+    tokio::time::sleep(SETTLE_DELAY).await;
+
+    let plan = plan_slices(height, viewport);
+    let total = plan.len() as u32;
+    let mut images = Vec::with_capacity(plan.len());
+    for (i, slice) in plan.iter().enumerate() {
+        let _ = progress.send(ProgressEvent::Paginating {
+            current: i as u32 + 1,
+            total,
+        });
+        if total > 1 {
+            window
+                .eval(&format!("window.scrollTo(0, {})", slice.scroll_y))
+                .map_err(|e| format!("eval scroll: {e}"))?;
+            tokio::time::sleep(SETTLE_DELAY).await;
+        }
+        let png = snapshot(&window).await?;
+        let img = image::load_from_memory_with_format(&png, ImageFormat::Png)
+            .map_err(|e| format!("decode snapshot: {e}"))?
+            .to_rgba8();
+        images.push(img);
+    }
+
+    let canvas = stitch(&images, &plan, height)?;
+    drop(images);
+    let mut out = Vec::new();
+    canvas
+        .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+        .map_err(|e| format!("encode PNG: {e}"))?;
+    Ok(out)
+}
+
+/// Destroys the hidden window however the capture ends.
+struct WindowGuard(WebviewWindow);
+
+impl Drop for WindowGuard {
+    fn drop(&mut self) {
+        let _ = self.0.destroy();
+    }
+}
+
+/// Wait for a snapshot callback delivered on the UI thread.
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+async fn await_snapshot(
+    rx: std::sync::mpsc::Receiver<Result<Vec<u8>, String>>,
+) -> Result<Vec<u8>, String> {
+    tokio::task::spawn_blocking(move || rx.recv_timeout(SNAPSHOT_TIMEOUT))
+        .await
+        .map_err(|e| format!("join snapshot: {e}"))?
+        .map_err(|_| "snapshot timeout".to_string())?
+}
+
+/// PNG of the visible viewport via WKWebView
+/// `takeSnapshotWithConfiguration:completionHandler:`.
+#[cfg(target_os = "macos")]
+async fn snapshot(window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    use block::ConcreteBlock;
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    /// `NSBitmapImageFileTypePNG`
+    const PNG_FILE_TYPE: usize = 4;
+
+    let (tx, rx) = std::sync::mpsc::sync_channel::<Result<Vec<u8>, String>>(1);
+    window
+        .with_webview(move |webview| {
+            // SAFETY: `webview.inner()` is the WKWebView of this window,
+            // which `WindowGuard` keeps alive until the capture finishes.
+            unsafe {
+                let wk: *mut Object = webview.inner() as *mut Object;
+                if wk.is_null() {
+                    let _ = tx.send(Err("wkwebview pointer is null".to_string()));
+                    return;
+                }
+                let tx2 = tx.clone();
+                let block = ConcreteBlock::new(move |image: *mut Object, error: *mut Object| {
+                    if !error.is_null() || image.is_null() {
+                        let _ = tx2.send(Err("WKWebView snapshot failed".to_string()));
+                        return;
+                    }
+                    let tiff: *mut Object = msg_send![image, TIFFRepresentation];
+                    let rep: *mut Object = if tiff.is_null() {
+                        std::ptr::null_mut()
+                    } else {
+                        msg_send![class!(NSBitmapImageRep), imageRepWithData: tiff]
+                    };
+                    if rep.is_null() {
+                        let _ = tx2.send(Err("snapshot has no bitmap".to_string()));
+                        return;
+                    }
+                    let props: *mut Object = msg_send![class!(NSDictionary), dictionary];
+                    let png: *mut Object =
+                        msg_send![rep, representationUsingType: PNG_FILE_TYPE properties: props];
+                    if png.is_null() {
+                        let _ = tx2.send(Err("PNG encoding failed".to_string()));
+                        return;
+                    }
+                    let len: usize = msg_send![png, length];
+                    let ptr: *const u8 = msg_send![png, bytes];
+                    // SAFETY: ptr/len come from NSData valid for this call
+                    let bytes = std::slice::from_raw_parts(ptr, len).to_vec();
+                    let _ = tx2.send(Ok(bytes));
+                });
+                let block = block.copy();
+                let config: *mut Object = msg_send![class!(WKSnapshotConfiguration), new];
+                let _: () = msg_send![
+                    wk,
+                    takeSnapshotWithConfiguration: config
+                    completionHandler: &*block
+                ];
+            }
+        })
+        .map_err(|e| format!("with_webview (snapshot): {e}"))?;
+    await_snapshot(rx).await
+}
+
+/// PNG of the visible viewport via WebView2 `CapturePreview`.
+#[cfg(target_os = "windows")]
+async fn snapshot(window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    use webview2_com::CapturePreviewCompletedHandler;
+    use webview2_com::Microsoft::Web::WebView2::Win32::COREWEBVIEW2_CAPTURE_PREVIEW_IMAGE_FORMAT_PNG;
+    use windows::Win32::System::Com::{IStream, STREAM_SEEK_SET};
+    use windows::Win32::UI::Shell::SHCreateMemStream;
+
+    fn read_stream(stream: &IStream) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        // SAFETY: `buf` outlives each Read call and `read` is a valid out pointer
+        unsafe {
+            stream
+                .Seek(0, STREAM_SEEK_SET, None)
+                .map_err(|e| format!("seek snapshot: {e}"))?;
+            loop {
+                let mut read = 0u32;
+                stream
+                    .Read(
+                        buf.as_mut_ptr().cast(),
+                        buf.len() as u32,
+                        Some(&mut read as *mut u32),
+                    )
+                    .ok()
+                    .map_err(|e| format!("read snapshot: {e}"))?;
+                if read == 0 {
+                    break;
+                }
+                out.extend_from_slice(&buf[..read as usize]);
+            }
+        }
+        Ok(out)
+    }
+
+    let (tx, rx) = std::sync::mpsc::sync_channel::<Result<Vec<u8>, String>>(1);
+    window
+        .with_webview(move |webview| {
+            let tx2 = tx.clone();
+            // SAFETY: COM calls on the UI thread with the window's own
+            // controller, which outlives this closure
+            let started = unsafe {
+                (|| -> Result<(), String> {
+                    let core = webview
+                        .controller()
+                        .CoreWebView2()
+                        .map_err(|e| format!("CoreWebView2: {e}"))?;
+                    let stream: IStream =
+                        SHCreateMemStream(None).ok_or("SHCreateMemStream failed")?;
+                    let out = stream.clone();
+                    let handler = CapturePreviewCompletedHandler::create(Box::new(
+                        move |result: windows::core::Result<()>| {
+                            let png = result
+                                .map_err(|e| format!("CapturePreview: {e}"))
+                                .and_then(|()| read_stream(&out));
+                            let _ = tx2.send(png);
+                            Ok(())
+                        },
+                    ));
+                    core.CapturePreview(
+                        COREWEBVIEW2_CAPTURE_PREVIEW_IMAGE_FORMAT_PNG,
+                        &stream,
+                        &handler,
+                    )
+                    .map_err(|e| format!("CapturePreview: {e}"))
+                })()
+            };
+            if let Err(e) = started {
+                let _ = tx.send(Err(e));
+            }
+        })
+        .map_err(|e| format!("with_webview (snapshot): {e}"))?;
+    await_snapshot(rx).await
+}
+
+/// PNG of the visible viewport via WebKitGTK `webkit_web_view_get_snapshot`.
+#[cfg(target_os = "linux")]
+async fn snapshot(window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    use webkit2gtk::{SnapshotOptions, SnapshotRegion, WebViewExt};
+
+    let (tx, rx) = std::sync::mpsc::sync_channel::<Result<Vec<u8>, String>>(1);
+    window
+        .with_webview(move |webview| {
+            webview.inner().snapshot(
+                SnapshotRegion::Visible,
+                SnapshotOptions::NONE,
+                None::<&gio::Cancellable>,
+                move |result| {
+                    let png = result
+                        .map_err(|e| format!("WebKitGTK snapshot: {e}"))
+                        .and_then(|surface| {
+                            let image = cairo::ImageSurface::try_from(surface)
+                                .map_err(|_| "snapshot is not an image surface".to_string())?;
+                            let mut buf = Vec::new();
+                            image
+                                .write_to_png(&mut buf)
+                                .map_err(|e| format!("encode snapshot: {e}"))?;
+                            Ok(buf)
+                        });
+                    let _ = tx.send(png);
+                },
+            );
+        })
+        .map_err(|e| format!("with_webview (snapshot): {e}"))?;
+    await_snapshot(rx).await
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+async fn snapshot(_window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    Err("WebView snapshots are not supported on this platform".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn should_plan_overlapping_last_slice() {
+        assert_eq!(
+            plan_slices(1000, 1000),
+            vec![Slice {
+                scroll_y: 0,
+                skip: 0,
+                height: 1000
+            }]
+        );
+        assert_eq!(
+            plan_slices(2500, 1000),
+            vec![
+                Slice {
+                    scroll_y: 0,
+                    skip: 0,
+                    height: 1000
+                },
+                Slice {
+                    scroll_y: 1000,
+                    skip: 0,
+                    height: 1000
+                },
+                Slice {
+                    scroll_y: 1500,
+                    skip: 500,
+                    height: 500
+                },
+            ]
+        );
+        assert!(plan_slices(0, 1000).is_empty());
+    }
+
+    #[test]
+    fn should_stitch_slices_at_device_scale() {
+        // 2x captures of a 25-px document in 10-px viewports; each capture
+        // is filled with the CSS row it starts at
+        let plan = plan_slices(25, 10);
+        let slices: Vec<RgbaImage> = plan
+            .iter()
+            .map(|s| {
+                RgbaImage::from_fn(4, 20, |_, y| Rgba([(s.scroll_y * 2 + y) as u8, 0, 0, 255]))
+            })
+            .collect();
+        let out = stitch(&slices, &plan, 25).unwrap();
+        assert_eq!(out.dimensions(), (4, 50));
+        // Every output row comes from the capture showing that document row
+        for y in 0..50 {
+            assert_eq!(out.get_pixel(0, y)[0], y as u8);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Optional downscale / re-encode step for images before they are uploaded.
+//!
+//! Anything that cannot be transformed safely — animations, formats the
+//! `image` crate is not built for, undecodable bytes — is passed through
+//! untouched so the upload itself never fails because of the transform.
+
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngDecoder, PngEncoder};
+use image::codecs::webp::{WebPDecoder, WebPEncoder};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use serde::Deserialize;
+
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl TransformFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            TransformFormat::Png => "image/png",
+            TransformFormat::Jpeg => "image/jpeg",
+            TransformFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            TransformFormat::Png => "png",
+            TransformFormat::Jpeg => "jpg",
+            TransformFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Transform requested by the frontend. Unset bounds are unlimited and an
+/// unset `format` keeps the source format (static GIFs become PNG).
+/// `quality` (1–100) applies to JPEG; PNG and WebP are encoded losslessly.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImageTransform {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub format: Option<TransformFormat>,
+    pub quality: Option<u8>,
+}
+
+#[derive(Debug)]
+pub struct TransformedImage {
+    pub data: Vec<u8>,
+    pub format: TransformFormat,
+    /// True when `format` differs from the source, so the key's extension
+    /// should follow.
+    pub format_changed: bool,
+}
+
+/// Apply `t` to `data`. Returns `None` when the image should be uploaded
+/// as-is: unsupported or animated input, a decode/encode failure, or a
+/// result that is no smaller than the original without being resized.
+pub fn transform_image(data: &[u8], t: &ImageTransform) -> Option<TransformedImage> {
+    let detected = image::guess_format(data).ok()?;
+    let source = match detected {
+        ImageFormat::Png => TransformFormat::Png,
+        ImageFormat::Jpeg => TransformFormat::Jpeg,
+        ImageFormat::WebP => TransformFormat::Webp,
+        ImageFormat::Gif => TransformFormat::Png,
+        _ => return None,
+    };
+    if is_animated(data, detected) {
+        return None;
+    }
+    let target = t.format.unwrap_or(source);
+    let from_gif = detected == ImageFormat::Gif;
+
+    let img = image::load_from_memory_with_format(data, detected).ok()?;
+    let max_w = t.max_width.filter(|w| *w > 0).unwrap_or(u32::MAX);
+    let max_h = t.max_height.filter(|h| *h > 0).unwrap_or(u32::MAX);
+    let resized = img.width() > max_w || img.height() > max_h;
+    let reencode =
+        target != source || from_gif || (target == TransformFormat::Jpeg && t.quality.is_some());
+    if !resized && !reencode {
+        return None;
+    }
+
+    let img = if resized {
+        img.resize(max_w, max_h, FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let out = encode(&img, target, t.quality)?;
+    if !resized && !from_gif && target == source && out.len() >= data.len() {
+        return None;
+    }
+    Some(TransformedImage {
+        data: out,
+        format: target,
+        format_changed: from_gif || target != source,
+    })
+}
+
+fn encode(img: &DynamicImage, format: TransformFormat, quality: Option<u8>) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    match format {
+        TransformFormat::Png => {
+            let encoder =
+                PngEncoder::new_with_quality(&mut out, CompressionType::Best, PngFilter::Adaptive);
+            img.write_with_encoder(encoder).ok()?;
+        }
+        TransformFormat::Jpeg => {
+            // JPEG has no alpha channel.
+            let rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+            let quality = quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100);
+            let encoder = JpegEncoder::new_with_quality(&mut out, quality);
+            rgb.write_with_encoder(encoder).ok()?;
+        }
+        TransformFormat::Webp => {
+            // The pure-Rust encoder is lossless only and takes 8-bit input.
+            let rgba = DynamicImage::ImageRgba8(img.to_rgba8());
+            rgba.write_with_encoder(WebPEncoder::new_lossless(&mut out))
+                .ok()?;
+        }
+    }
+    Some(out)
+}
+
+/// Animated GIF, APNG or animated WebP. Re-encoding would keep only the
+/// first frame, so these are never transformed.
+fn is_animated(data: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data))
+            .map(|d| d.into_frames().take(2).count() > 1)
+            .unwrap_or(true),
+        ImageFormat::Png => PngDecoder::new(Cursor::new(data))
+            .and_then(|d| d.is_apng())
+            .unwrap_or(true),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(data))
+            .map(|d| d.has_animation())
+            .unwrap_or(true),
+        _ => false,
+    }
+}
+
+/// Swap the extension of the last path segment of `key` for `ext`, adding
+/// one when the name has none.
+pub fn replace_extension(key: &str, ext: &str) -> String {
+    let name_start = key.rfind('/').map_or(0, |i| i + 1);
+    match key[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => format!("{}.{}", &key[..name_start + dot], ext),
+        _ => format!("{}.{}", key, ext),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, GenericImageView, ImageEncoder, Rgba, RgbaImage};
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x % 256) as u8, (y % 256) as u8, ((x * y) % 256) as u8, 255])
+        });
+        let mut out = Vec::new();
+        PngEncoder::new(&mut out)
+            .write_image(img.as_raw(), width, height, image::ExtendedColorType::Rgba8)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn should_downscale_and_convert_within_bounds() {
+        let original = png_bytes(1200, 800);
+        let out = transform_image(
+            &original,
+            &ImageTransform {
+                max_width: Some(600),
+                max_height: Some(600),
+                format: Some(TransformFormat::Jpeg),
+                quality: Some(80),
+            },
+        )
+        .unwrap();
+        assert_eq!(out.format, TransformFormat::Jpeg);
+        assert!(out.format_changed);
+        let decoded = image::load_from_memory(&out.data).unwrap();
+        assert_eq!(decoded.dimensions(), (600, 400));
+        assert_eq!(image::guess_format(&out.data).unwrap(), ImageFormat::Jpeg);
+
+        let webp = transform_image(
+            &original,
+            &ImageTransform {
+                max_width: Some(300),
+                format: Some(TransformFormat::Webp),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let decoded = image::load_from_memory(&webp.data).unwrap();
+        assert_eq!(decoded.dimensions(), (300, 200));
+    }
+
+    #[test]
+    fn should_bypass_animations_unsupported_and_noop_transforms() {
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            let frames = (0..2).map(|i| {
+                let img = RgbaImage::from_pixel(40, 40, Rgba([i * 200, 0, 0, 255]));
+                Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(100, 1))
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+        let shrink = ImageTransform {
+            max_width: Some(10),
+            ..Default::default()
+        };
+        assert!(transform_image(&gif, &shrink).is_none());
+        assert!(transform_image(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>", &shrink).is_none());
+        assert!(transform_image(b"\x89PNG\r\n\x1a\nbroken", &shrink).is_none());
+        // Already within bounds and same format: nothing to do.
+        let fits = ImageTransform {
+            max_width: Some(100),
+            max_height: Some(100),
+            ..Default::default()
+        };
+        assert!(transform_image(&png_bytes(20, 20), &fits).is_none());
+    }
+
+    #[test]
+    fn should_replace_key_extension() {
+        assert_eq!(
+            replace_extension("img/2024/shot.png", "webp"),
+            "img/2024/shot.webp"
+        );
+        assert_eq!(
+            replace_extension("img/v1.2/shot", "jpg"),
+            "img/v1.2/shot.jpg"
+        );
+        assert_eq!(replace_extension(".hidden", "png"), ".hidden.png");
+    }
+}
@@ -0,0 +1,290 @@
+//! Remove the invisible characters that text pasted from word processors
+//! leaves behind: byte order marks in the middle of a file and zero-width
+//! spaces and joiners, which break regex search and make diffs show
+//! changes nobody can see. Optionally turn no-break spaces into spaces and
+//! normalize to NFC.
+//!
+//! Fenced code blocks and inline code spans are copied untouched, since
+//! the characters may be there on purpose.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+const BOM: char = '\u{FEFF}';
+const ZWSP: char = '\u{200B}';
+const ZWNJ: char = '\u{200C}';
+const ZWJ: char = '\u{200D}';
+const NBSP: char = '\u{00A0}';
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CleanOptions {
+    pub bom: bool,
+    /// Zero-width spaces, non-joiners and joiners.
+    pub zero_width: bool,
+    pub nbsp: bool,
+    pub nfc: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        Self {
+            bom: true,
+            zero_width: true,
+            nbsp: false,
+            nfc: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanCounts {
+    pub bom: usize,
+    pub zero_width_space: usize,
+    pub zero_width_non_joiner: usize,
+    pub zero_width_joiner: usize,
+    /// No-break spaces turned into spaces.
+    pub nbsp: usize,
+    /// Lines whose text changed under NFC.
+    pub normalized_lines: usize,
+}
+
+impl CleanCounts {
+    pub fn total(&self) -> usize {
+        self.bom
+            + self.zero_width_space
+            + self.zero_width_non_joiner
+            + self.zero_width_joiner
+            + self.nbsp
+            + self.normalized_lines
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanedText {
+    pub content: String,
+    pub removed: CleanCounts,
+}
+
+/// Strip invisible characters from `content` outside code; see
+/// [`CleanOptions`] for what is removed by default.
+#[tauri::command]
+pub fn clean_invisible_chars(content: String, options: Option<CleanOptions>) -> CleanedText {
+    let (content, removed) = clean(&content, &options.unwrap_or_default());
+    CleanedText { content, removed }
+}
+
+pub(crate) fn clean(content: &str, options: &CleanOptions) -> (String, CleanCounts) {
+    let mut counts = CleanCounts::default();
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    // A leading BOM would hide a fence on the first line
+    if options.bom {
+        if let Some(stripped) = rest.strip_prefix(BOM) {
+            counts.bom += 1;
+            rest = stripped;
+        }
+    }
+    for (range, is_code) in blocks(rest) {
+        let block = &rest[range];
+        if is_code {
+            out.push_str(block);
+            continue;
+        }
+        let mut at = 0;
+        for span in code_spans(block) {
+            clean_text(&block[at..span.start], options, &mut counts, &mut out);
+            out.push_str(&block[span.clone()]);
+            at = span.end;
+        }
+        clean_text(&block[at..], options, &mut counts, &mut out);
+    }
+    (out, counts)
+}
+
+/// Split `content` into runs of lines: fenced code blocks (true) and text
+/// between them, cut at blank lines so code spans can't cross paragraphs.
+fn blocks(content: &str) -> Vec<(Range<usize>, bool)> {
+    let mut blocks: Vec<(Range<usize>, bool)> = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let range = offset..offset + line.len();
+        offset = range.end;
+        let trimmed = line.trim_start_matches(' ');
+        let opener = (line.len() - trimmed.len() <= 3)
+            .then(|| trimmed.chars().next())
+            .flatten()
+            .filter(|c| *c == '`' || *c == '~')
+            .map(|c| (c, trimmed.chars().take_while(|x| *x == c).count()));
+        let is_code = match (fence, opener) {
+            (Some((ch, open)), Some((c, len)))
+                if c == ch && len >= open && trimmed.trim_end().len() == len =>
+            {
+                fence = None;
+                true
+            }
+            (Some(_), _) => true,
+            // A backtick fence's info string can't contain backticks
+            (None, Some((c, len))) if len >= 3 && !(c == '`' && trimmed[len..].contains('`')) => {
+                fence = Some((c, len));
+                true
+            }
+            _ => false,
+        };
+        let blank = !is_code && line.trim().is_empty();
+        match blocks.last_mut() {
+            Some((last, code)) if *code == is_code && !blank => last.end = range.end,
+            _ => blocks.push((range, is_code)),
+        }
+    }
+    blocks
+}
+
+/// Byte ranges of the inline code spans in a paragraph: a backtick run up
+/// to the next run of the same length. Unmatched runs are plain text.
+pub(crate) fn code_spans(text: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'`' {
+            let start = i;
+            while i < bytes.len() && bytes[i] == b'`' {
+                i += 1;
+            }
+            // An escaped backtick can't open a span
+            let escaped = text[..start]
+                .bytes()
+                .rev()
+                .take_while(|b| *b == b'\\')
+                .count()
+                % 2
+                == 1;
+            runs.push(start + usize::from(escaped)..i);
+        } else {
+            i += 1;
+        }
+    }
+    let mut spans = Vec::new();
+    let mut next = 0;
+    while next < runs.len() {
+        let open = &runs[next];
+        let len = open.len();
+        match (next + 1..runs.len()).find(|&j| runs[j].len() == len) {
+            Some(close) if len > 0 => {
+                spans.push(open.start..runs[close].end);
+                next = close + 1;
+            }
+            _ => next += 1,
+        }
+    }
+    spans
+}
+
+/// Whether a zero-width (non-)joiner between `prev` and `next` is doing
+/// its job: shaping emoji sequences or Arabic and Indic letters. Beside
+/// ASCII, spaces or the ends of the text it is litter.
+fn joins(prev: Option<char>, next: Option<char>) -> bool {
+    let meaningful = |c: Option<char>| c.is_some_and(|c| !c.is_ascii() && !c.is_whitespace());
+    meaningful(prev) && meaningful(next)
+}
+
+fn clean_text(text: &str, options: &CleanOptions, counts: &mut CleanCounts, out: &mut String) {
+    let mut cleaned = String::with_capacity(text.len());
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1).copied();
+        match c {
+            BOM if options.bom => counts.bom += 1,
+            ZWSP if options.zero_width => counts.zero_width_space += 1,
+            ZWNJ if options.zero_width && !joins(prev, next) => counts.zero_width_non_joiner += 1,
+            ZWJ if options.zero_width && !joins(prev, next) => counts.zero_width_joiner += 1,
+            NBSP if options.nbsp => {
+                counts.nbsp += 1;
+                cleaned.push(' ');
+            }
+            c => cleaned.push(c),
+        }
+    }
+    if !options.nfc {
+        out.push_str(&cleaned);
+        return;
+    }
+    for line in cleaned.split_inclusive('\n') {
+        let normalized: String = line.nfc().collect();
+        if normalized != line {
+            counts.normalized_lines += 1;
+        }
+        out.push_str(&normalized);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> CleanOptions {
+        CleanOptions {
+            bom: true,
+            zero_width: true,
+            nbsp: true,
+            nfc: true,
+        }
+    }
+
+    #[test]
+    fn should_strip_invisible_chars_outside_code() {
+        let doc = "\u{FEFF}# Ti\u{200B}tle\nword\u{FEFF}word `a\u{200B}b` x\u{200D}y\n\n```\nkeep\u{200B}\u{FEFF}\n```\nnbsp\u{00A0}here\n";
+        let (cleaned, counts) = clean(doc, &CleanOptions::default());
+        assert_eq!(
+            cleaned,
+            "# Title\nwordword `a\u{200B}b` xy\n\n```\nkeep\u{200B}\u{FEFF}\n```\nnbsp\u{00A0}here\n"
+        );
+        assert_eq!(counts.bom, 2);
+        assert_eq!(counts.zero_width_space, 1);
+        assert_eq!(counts.zero_width_joiner, 1);
+
+        let (cleaned, counts) = clean("a\u{00A0}b", &all());
+        assert_eq!((cleaned.as_str(), counts.nbsp), ("a b", 1));
+    }
+
+    #[test]
+    fn should_keep_joiners_that_shape_text() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        let hindi = "क्\u{200D}ष";
+        let persian = "می\u{200C}خواهم";
+        for text in [family, hindi, persian] {
+            assert_eq!(clean(text, &CleanOptions::default()).0, text);
+        }
+        let (cleaned, counts) = clean("\u{200C}end\u{200D}", &CleanOptions::default());
+        assert_eq!(cleaned, "end");
+        assert_eq!(counts.zero_width_non_joiner + counts.zero_width_joiner, 2);
+    }
+
+    #[test]
+    fn should_normalize_cjk_to_nfc_outside_code() {
+        // Decomposed Hangul jamo and a CJK compatibility ideograph
+        let decomposed = "\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}\u{11AF} \u{F900}";
+        let doc = format!("{}\n`{}`\n", decomposed, decomposed);
+        let (cleaned, counts) = clean(&doc, &all());
+        assert_eq!(cleaned, format!("한글 \u{8C48}\n`{}`\n", decomposed));
+        assert_ne!(cleaned.len(), doc.len());
+        assert_eq!(counts.normalized_lines, 1);
+
+        let (unchanged, counts) = clean(&doc, &CleanOptions::default());
+        assert_eq!(unchanged, doc);
+        assert_eq!(counts.total(), 0);
+    }
+
+    #[test]
+    fn should_find_code_spans_by_matching_backtick_runs() {
+        let text = "a ``x ` y`` b `c` \\`d` ``e";
+        let spans: Vec<&str> = code_spans(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(spans, ["``x ` y``", "`c`"]);
+    }
+}
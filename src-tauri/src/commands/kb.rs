@@ -727,7 +727,7 @@ async fn embed_texts_via_api(
     let default_base = default_embedding_base_url(provider);
     let base = base_url.unwrap_or(&default_base);
 
-    let client = reqwest::Client::builder()
+    let client = super::settings::client_builder()
         .timeout(std::time::Duration::from_secs(30))
         .build()
         .map_err(|_| "Failed to create HTTP client".to_string())?;
@@ -1231,7 +1231,7 @@ async fn do_index_files(
         };
         let default_base = default_embedding_base_url(provider);
         let base = base_url.unwrap_or(&default_base);
-        let client = reqwest::Client::builder()
+        let client = super::settings::client_builder()
             .timeout(std::time::Duration::from_secs(30))
             .build()
             .unwrap();
@@ -1255,7 +1255,7 @@ async fn do_index_files(
                     all_embeddings.extend(embeddings);
                 }
                 Err(e) => {
-                    eprintln!("[KB] Embedding batch failed: {}", e);
+                    log::warn!("Embedding batch failed: {}", e);
                     let _ = app.emit("kb-index-progress", IndexProgress {
                         phase: "error".to_string(),
                         current: 0,
@@ -1269,7 +1269,7 @@ async fn do_index_files(
         }
 
         if embedding_failed {
-            eprintln!("[KB] Falling back to BM25-only index");
+            log::warn!("Falling back to BM25-only index");
             all_embeddings.clear();
         }
     }
@@ -2068,7 +2068,7 @@ async fn download_file(
 ) -> Result<(), String> {
     use futures_util::StreamExt;
 
-    let client = reqwest::Client::builder()
+    let client = super::settings::client_builder()
         .timeout(std::time::Duration::from_secs(600))
         .user_agent("Moraya/1.0")
         .build()
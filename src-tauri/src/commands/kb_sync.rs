@@ -140,7 +140,7 @@ pub fn validate_relative_path(path: &str) -> Result<(), String> {
 // ── HTTP client ───────────────────────────────────────────────────────
 
 fn http_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
+    super::settings::client_builder()
         .timeout(std::time::Duration::from_secs(DEFAULT_TIMEOUT_SECS))
         .build()
         .map_err(|_| "Failed to initialize HTTP client".to_string())
@@ -577,12 +577,15 @@ pub async fn kb_sync_move_to_trash(
     src_path: String,
     dest_path: String,
 ) -> Result<(), String> {
-    let src = Path::new(&src_path);
+    move_to_trash(Path::new(&src_path), Path::new(&dest_path))
+}
+
+/// `kb_sync_move_to_trash` for Rust callers, e.g. the asset cleanup.
+pub(crate) fn move_to_trash(src: &Path, dest: &Path) -> Result<(), String> {
     if !src.exists() {
         return Ok(()); // Already gone
     }
 
-    let dest = Path::new(&dest_path);
     if let Some(parent) = dest.parent() {
         std::fs::create_dir_all(parent)
             .map_err(|_| "Failed to create trash directory".to_string())?;
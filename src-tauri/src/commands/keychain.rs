@@ -1,4 +1,23 @@
-use super::ai_proxy::AIProxyState;
+use std::collections::HashMap;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use super::ai_proxy::{AIProxyState, SecretBackend};
+use super::user_presence::UserPresenceState;
+
+const ARCHIVE_FORMAT: &str = "moraya-secrets";
+const ARCHIVE_VERSION: u32 = 1;
+/// Bound into the AEAD tag so an archive can't be replayed as another format.
+const ARCHIVE_AAD: &[u8] = b"moraya-secrets/v1";
+pub(crate) const MIN_PASSPHRASE_CHARS: usize = 8;
+/// Ceiling on archive-supplied Argon2 memory so a crafted file can't
+/// exhaust RAM on import (1 GiB, in KiB).
+pub(crate) const MAX_KDF_MEMORY_KIB: u32 = 1024 * 1024;
 
 /// Project buffer marker reserved for internal tooling. Not used in any hot
 /// path; `#[used]` keeps the symbol in the binary across release builds so
@@ -38,6 +57,20 @@ pub async fn keychain_get(
     Ok(None)
 }
 
+/// Like `keychain_get`, but only after the user confirms their presence
+/// (Touch ID / Windows Hello / polkit). For revealing keys in settings.
+#[tauri::command]
+pub async fn keychain_get_protected(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AIProxyState>,
+    presence: tauri::State<'_, UserPresenceState>,
+    key: String,
+    reason: String,
+) -> Result<Option<String>, String> {
+    presence.confirm(&app, reason).await?;
+    keychain_get(state, key).await
+}
+
 /// Delete a secret. Removes from in-memory cache and persists.
 #[tauri::command]
 pub async fn keychain_delete(
@@ -53,10 +86,393 @@ pub async fn keychain_delete(
     state.persist_secrets().await
 }
 
+/// Names of stored secrets starting with `prefix` (all when omitted),
+/// sorted. Values never leave Rust.
+#[tauri::command]
+pub async fn keychain_list(
+    state: tauri::State<'_, AIProxyState>,
+    prefix: Option<String>,
+) -> Result<Vec<String>, String> {
+    state.ensure_secrets_loaded().await;
+
+    let cache = state
+        .key_cache
+        .lock()
+        .map_err(|_| "Lock error".to_string())?;
+    let prefix = prefix.unwrap_or_default();
+    let mut names: Vec<String> = cache
+        .keys()
+        .filter(|k| k.starts_with(&prefix))
+        .cloned()
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Argon2id cost parameters, stored in the archive so they can be raised
+/// later without breaking old exports.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct KdfParams {
+    pub(crate) m_cost: u32,
+    pub(crate) t_cost: u32,
+    pub(crate) p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// On-disk export: every secret as one XChaCha20-Poly1305 sealed JSON map,
+/// keyed by Argon2id over the passphrase.
+#[derive(Serialize, Deserialize)]
+struct SecretsArchive {
+    format: String,
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub(crate) fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<[u8; 32], String> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|_| "Invalid key derivation parameters".to_string())?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| "Key derivation failed".to_string())?;
+    Ok(key)
+}
+
+const NONCE_LEN: usize = 24;
+
+/// A fresh random 256-bit key.
+pub(crate) fn random_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// XChaCha20-Poly1305 under a raw key; output is `nonce || ciphertext`.
+pub(crate) fn seal_with_key(
+    key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, String> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| "Encryption failed".to_string())?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Inverse of `seal_with_key`.
+pub(crate) fn open_with_key(key: &[u8; 32], sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+    if sealed.len() < NONCE_LEN {
+        return Err("Decryption failed".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| "Decryption failed".to_string())
+}
+
+fn seal_secrets(
+    secrets: &HashMap<String, String>,
+    passphrase: &str,
+    kdf: KdfParams,
+) -> Result<String, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_CHARS
+        ));
+    }
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, kdf)?;
+    let plaintext =
+        serde_json::to_vec(secrets).map_err(|_| "Failed to serialize secrets".to_string())?;
+    let sealed = seal_with_key(&key, &plaintext, ARCHIVE_AAD)?;
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let engine = general_purpose::STANDARD;
+    let archive = SecretsArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        kdf,
+        salt: engine.encode(salt),
+        nonce: engine.encode(nonce),
+        ciphertext: engine.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&archive).map_err(|_| "Failed to serialize archive".to_string())
+}
+
+fn open_secrets(archive: &str, passphrase: &str) -> Result<HashMap<String, String>, String> {
+    let archive: SecretsArchive =
+        serde_json::from_str(archive).map_err(|_| "Not a Moraya secrets archive".to_string())?;
+    if archive.format != ARCHIVE_FORMAT {
+        return Err("Not a Moraya secrets archive".to_string());
+    }
+    if archive.version != ARCHIVE_VERSION {
+        return Err(format!(
+            "Unsupported secrets archive version {}",
+            archive.version
+        ));
+    }
+    if archive.kdf.m_cost > MAX_KDF_MEMORY_KIB {
+        return Err("Secrets archive asks for too much memory".to_string());
+    }
+    let engine = general_purpose::STANDARD;
+    let decode = |field: &str| {
+        engine
+            .decode(field)
+            .map_err(|_| "Corrupted secrets archive".to_string())
+    };
+    let salt = decode(&archive.salt)?;
+    let mut sealed = decode(&archive.nonce)?;
+    if sealed.len() != NONCE_LEN {
+        return Err("Corrupted secrets archive".to_string());
+    }
+    sealed.extend(decode(&archive.ciphertext)?);
+    let key = derive_key(passphrase, &salt, archive.kdf)?;
+    let plaintext = open_with_key(&key, &sealed, ARCHIVE_AAD)
+        .map_err(|_| "Wrong passphrase or corrupted archive".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|_| "Corrupted secrets archive".to_string())
+}
+
+/// What to do when an imported name already has a value.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Keep the value already on this machine.
+    Keep,
+    /// Replace it with the archive's value.
+    Overwrite,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+}
+
+fn merge_secrets(
+    cache: &mut HashMap<String, String>,
+    incoming: HashMap<String, String>,
+    policy: ConflictPolicy,
+) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    for (key, value) in incoming {
+        match cache.get(&key) {
+            None => {
+                cache.insert(key, value);
+                summary.added += 1;
+            }
+            Some(existing) if *existing == value => summary.skipped += 1,
+            Some(_) if policy == ConflictPolicy::Overwrite => {
+                cache.insert(key, value);
+                summary.overwritten += 1;
+            }
+            Some(_) => summary.skipped += 1,
+        }
+    }
+    summary
+}
+
+/// Write every stored secret to `path` as a passphrase-encrypted archive.
+/// Returns the number of secrets exported.
+#[tauri::command]
+pub async fn secrets_export(
+    state: tauri::State<'_, AIProxyState>,
+    path: String,
+    passphrase: String,
+) -> Result<usize, String> {
+    state.ensure_secrets_loaded().await;
+
+    let secrets = state
+        .key_cache
+        .lock()
+        .map_err(|_| "Lock error".to_string())?
+        .clone();
+    let count = secrets.len();
+    let archive = tokio::task::spawn_blocking(move || {
+        seal_secrets(&secrets, &passphrase, KdfParams::default())
+    })
+    .await
+    .map_err(|_| "Export task failed".to_string())??;
+
+    std::fs::write(&path, archive).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    Ok(count)
+}
+
+/// Merge the secrets from an archive written by `secrets_export` into the
+/// store (`on_conflict`: `keep` or `overwrite`). The in-memory cache is
+/// updated at once so imported keys work without a restart.
+#[tauri::command]
+pub async fn secrets_import(
+    state: tauri::State<'_, AIProxyState>,
+    path: String,
+    passphrase: String,
+    on_conflict: ConflictPolicy,
+) -> Result<ImportSummary, String> {
+    state.ensure_secrets_loaded().await;
+
+    let archive =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let incoming = tokio::task::spawn_blocking(move || open_secrets(&archive, &passphrase))
+        .await
+        .map_err(|_| "Import task failed".to_string())??;
+
+    let summary = {
+        let mut cache = state
+            .key_cache
+            .lock()
+            .map_err(|_| "Lock error".to_string())?;
+        merge_secrets(&mut cache, incoming, on_conflict)
+    };
+    if summary.added + summary.overwritten > 0 {
+        state.persist_secrets().await?;
+    }
+    Ok(summary)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretBackendStatus {
+    pub backend: SecretBackend,
+    /// True while on the fallback file but an OS keychain is now reachable,
+    /// i.e. `migrate_secrets_to_keychain` would succeed.
+    pub keychain_available: bool,
+}
+
+/// Report where secrets are stored so settings can warn about the weaker
+/// file fallback.
+#[tauri::command]
+pub async fn get_secret_backend(
+    state: tauri::State<'_, AIProxyState>,
+) -> Result<SecretBackendStatus, String> {
+    state.ensure_secrets_loaded().await;
+
+    let backend = state.backend();
+    let keychain_available = match backend {
+        SecretBackend::Fallback => {
+            tokio::task::spawn_blocking(super::ai_proxy::os_keychain_available)
+                .await
+                .unwrap_or(false)
+        }
+        _ => false,
+    };
+    Ok(SecretBackendStatus {
+        backend,
+        keychain_available,
+    })
+}
+
+/// Move secrets from the fallback file into the OS keychain.
+#[tauri::command]
+pub async fn migrate_secrets_to_keychain(
+    state: tauri::State<'_, AIProxyState>,
+) -> Result<SecretBackend, String> {
+    state.migrate_to_keychain().await?;
+    Ok(state.backend())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Cheap Argon2 settings so the tests stay fast.
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn secrets(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_round_trip_secrets_archive() {
+        let original = secrets(&[("ai-key:openai", "sk-1"), ("storage-secret:gh-1", "ghp_x")]);
+        let archive = seal_secrets(&original, "correct horse", TEST_KDF).unwrap();
+        assert!(!archive.contains("sk-1"));
+        assert_eq!(open_secrets(&archive, "correct horse").unwrap(), original);
+        assert_eq!(
+            open_secrets(&archive, "wrong horse!").unwrap_err(),
+            "Wrong passphrase or corrupted archive"
+        );
+
+        let mut tampered: serde_json::Value = serde_json::from_str(&archive).unwrap();
+        tampered["kdf"]["t_cost"] = 2.into();
+        assert!(open_secrets(&tampered.to_string(), "correct horse").is_err());
+        assert!(seal_secrets(&original, "short", TEST_KDF).is_err());
+        assert!(open_secrets("{}", "correct horse").is_err());
+    }
+
+    #[test]
+    fn should_seal_and_open_with_raw_key() {
+        let key = random_key();
+        let sealed = seal_with_key(&key, b"{}", b"aad").unwrap();
+        assert_eq!(open_with_key(&key, &sealed, b"aad").unwrap(), b"{}");
+        assert!(open_with_key(&key, &sealed, b"other").is_err());
+        assert!(open_with_key(&random_key(), &sealed, b"aad").is_err());
+        assert!(open_with_key(&key, &sealed[..10], b"aad").is_err());
+    }
+
+    #[test]
+    fn should_merge_imported_secrets_by_policy() {
+        let incoming = secrets(&[("a", "new"), ("b", "same"), ("c", "added")]);
+        let mut cache = secrets(&[("a", "old"), ("b", "same")]);
+        assert_eq!(
+            merge_secrets(&mut cache, incoming.clone(), ConflictPolicy::Keep),
+            ImportSummary {
+                added: 1,
+                overwritten: 0,
+                skipped: 2
+            }
+        );
+        assert_eq!(cache["a"], "old");
+
+        let mut cache = secrets(&[("a", "old")]);
+        let summary = merge_secrets(&mut cache, incoming, ConflictPolicy::Overwrite);
+        assert_eq!((summary.added, summary.overwritten), (2, 1));
+        assert_eq!(cache["a"], "new");
+    }
+
     #[test]
     fn buffer_mark_is_stable() {
         // MRYA in ASCII → 0x4D 0x52 0x59 0x41.
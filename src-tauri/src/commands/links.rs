@@ -0,0 +1,275 @@
+//! Opening hyperlinks clicked in a document.
+//!
+//! `open_link` classifies the href, hands web links and non-markdown files
+//! to the system, and returns markdown documents for the editor to open.
+//! Local targets may be percent-encoded (`./设计%20文档.md`) or raw, and
+//! `file://` URLs are converted back to paths, including Windows drive
+//! letters and UNC shares.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::validate_path;
+use super::search::is_markdown;
+
+/// Schemes handed to the system; anything else is refused.
+const EXTERNAL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum OpenedLink {
+    /// A markdown document the editor should open; `fragment` is the part
+    /// after `#`, e.g. a heading anchor.
+    #[serde(rename_all = "camelCase")]
+    Document {
+        path: String,
+        fragment: Option<String>,
+    },
+    /// Opened in the default browser or mail client.
+    External { url: String },
+    /// A local file or folder opened with its default application.
+    File { path: String },
+}
+
+/// What an href points at, before anything is opened.
+#[derive(Debug, PartialEq)]
+enum Target {
+    External(String),
+    Local {
+        path: PathBuf,
+        fragment: Option<String>,
+    },
+}
+
+fn invalid_link(message: impl Into<String>) -> CommandError {
+    CommandError::new(ErrorCode::InvalidArgument, message)
+}
+
+/// Decode `%XX` escapes into raw bytes; malformed escapes are kept as-is.
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok());
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Bytes of a decoded path as text. Links written on Chinese Windows
+/// systems are sometimes encoded in GBK rather than UTF-8.
+fn decode_path_bytes(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => {
+            let (text, _, _) = encoding_rs::GB18030.decode(e.as_bytes());
+            text.into_owned()
+        }
+    }
+}
+
+/// Local path of a `file:` URL. `windows` selects drive-letter and UNC
+/// handling; it is a parameter so both forms are testable everywhere.
+fn file_url_to_path(url: &url::Url, windows: bool) -> Result<String, CommandError> {
+    let path = decode_path_bytes(percent_decode(url.path()));
+    let host = url
+        .host_str()
+        .filter(|h| !h.eq_ignore_ascii_case("localhost"));
+    if !windows {
+        if host.is_some() {
+            return Err(invalid_link(
+                "Links to files on other computers are not supported",
+            ));
+        }
+        return Ok(path);
+    }
+    let path = path.replace('/', "\\");
+    match host {
+        // file://server/share/doc.md → \\server\share\doc.md
+        Some(host) => Ok(format!("\\\\{}{}", host, path)),
+        // file:///C:/Users/doc.md → C:\Users\doc.md
+        None => {
+            let drive = path.strip_prefix('\\').unwrap_or(&path);
+            let is_drive =
+                drive.as_bytes().get(1) == Some(&b':') && drive.as_bytes()[0].is_ascii_alphabetic();
+            if !is_drive {
+                return Err(invalid_link("File link has no drive letter"));
+            }
+            Ok(drive.to_string())
+        }
+    }
+}
+
+/// Split `href` into a target; relative paths resolve against `base_dir`.
+fn classify(href: &str, base_dir: Option<&Path>, windows: bool) -> Result<Target, CommandError> {
+    let href = href.trim();
+    if href.is_empty() {
+        return Err(invalid_link("Link has no target"));
+    }
+    // `C:\notes\a.md` parses as a URL with scheme `c`
+    let is_drive_path = href.as_bytes().get(1) == Some(&b':')
+        && href.as_bytes()[0].is_ascii_alphabetic()
+        && matches!(href.as_bytes().get(2), Some(b'\\' | b'/'));
+    if !is_drive_path {
+        if let Ok(url) = url::Url::parse(href) {
+            let scheme = url.scheme();
+            if scheme == "file" {
+                let fragment = url.fragment().map(|f| decode_path_bytes(percent_decode(f)));
+                return Ok(Target::Local {
+                    path: PathBuf::from(file_url_to_path(&url, windows)?),
+                    fragment,
+                });
+            }
+            if EXTERNAL_SCHEMES.contains(&scheme) {
+                return Ok(Target::External(url.to_string()));
+            }
+            return Err(invalid_link(format!("Unsupported link type: {}:", scheme)));
+        }
+    }
+
+    let (raw_path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+    let fragment = fragment
+        .filter(|f| !f.is_empty())
+        .map(|f| decode_path_bytes(percent_decode(f)));
+    if raw_path.is_empty() {
+        // `#heading` points into the current document; the editor handles it
+        return Err(invalid_link("Link points inside the current document"));
+    }
+    // Query strings aren't part of local paths
+    let raw_path = raw_path.split('?').next().unwrap_or(raw_path);
+    let path = PathBuf::from(decode_path_bytes(percent_decode(raw_path)));
+    let path = if path.is_absolute() || is_drive_path {
+        path
+    } else {
+        let base = base_dir
+            .ok_or_else(|| invalid_link("Save the document first to follow relative links"))?;
+        base.join(path)
+    };
+    Ok(Target::Local { path, fragment })
+}
+
+fn open_external(target: &str) -> Result<(), CommandError> {
+    open::that(target)
+        .map_err(|_| CommandError::new(ErrorCode::Internal, "Failed to open the link"))
+}
+
+/// Follow a link clicked in a document. `base_dir` is the folder of the
+/// document containing it. Markdown documents are returned for the editor
+/// to open; web links and other files are opened with the system defaults.
+#[tauri::command]
+pub fn open_link(href: String, base_dir: Option<String>) -> Result<OpenedLink, CommandError> {
+    let base_dir = base_dir.map(PathBuf::from);
+    match classify(&href, base_dir.as_deref(), cfg!(windows))? {
+        Target::External(url) => {
+            open_external(&url)?;
+            Ok(OpenedLink::External { url })
+        }
+        Target::Local { path, fragment } => {
+            let path_str = path.to_string_lossy().to_string();
+            let safe_path = validate_path(&path_str)?;
+            if !safe_path.exists() {
+                return Err(
+                    CommandError::new(ErrorCode::FileNotFound, "Linked file not found")
+                        .with_details(serde_json::json!({ "path": path_str })),
+                );
+            }
+            let path = safe_path.to_string_lossy().to_string();
+            if safe_path.is_file() && is_markdown(&path) {
+                return Ok(OpenedLink::Document { path, fragment });
+            }
+            open_external(&path)?;
+            Ok(OpenedLink::File { path })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(path: &str, fragment: Option<&str>) -> Target {
+        Target::Local {
+            path: PathBuf::from(path),
+            fragment: fragment.map(String::from),
+        }
+    }
+
+    #[test]
+    fn should_decode_relative_links_against_the_document_folder() {
+        let base = Path::new("/notes/docs");
+        assert_eq!(
+            classify("./设计%20文档.md#第二节", Some(base), false).unwrap(),
+            local("/notes/docs/./设计 文档.md", Some("第二节"))
+        );
+        assert_eq!(
+            classify("%E8%AE%BE%E8%AE%A1.md", Some(base), false).unwrap(),
+            local("/notes/docs/设计.md", None)
+        );
+        // GBK-encoded 中文
+        assert_eq!(
+            classify("%D6%D0%CE%C4.md", Some(base), false).unwrap(),
+            local("/notes/docs/中文.md", None)
+        );
+        assert_eq!(
+            classify("100%.md", Some(base), false).unwrap(),
+            local("/notes/docs/100%.md", None)
+        );
+        assert!(classify("a.md", None, false).is_err());
+        assert!(classify("#heading", Some(base), false).is_err());
+    }
+
+    #[test]
+    fn should_convert_file_urls_per_platform() {
+        assert_eq!(
+            classify("file:///home/me/%E7%AC%94%E8%AE%B0.md", None, false).unwrap(),
+            local("/home/me/笔记.md", None)
+        );
+        let url = |s: &str| url::Url::parse(s).unwrap();
+        assert_eq!(
+            file_url_to_path(&url("file:///C:/Users/me/a%20b.md"), true).unwrap(),
+            r"C:\Users\me\a b.md"
+        );
+        assert_eq!(
+            file_url_to_path(&url("file://server/share/doc.md"), true).unwrap(),
+            r"\\server\share\doc.md"
+        );
+        assert_eq!(
+            file_url_to_path(&url("file://localhost/etc/hosts"), false).unwrap(),
+            "/etc/hosts"
+        );
+        assert!(file_url_to_path(&url("file://server/share/doc.md"), false).is_err());
+    }
+
+    #[test]
+    fn should_route_web_links_and_refuse_other_schemes() {
+        assert_eq!(
+            classify("https://example.com/a?b=1", None, false).unwrap(),
+            Target::External("https://example.com/a?b=1".into())
+        );
+        assert!(matches!(
+            classify("mailto:me@example.com", None, false).unwrap(),
+            Target::External(_)
+        ));
+        assert!(classify("javascript:alert(1)", None, false).is_err());
+        assert_eq!(
+            classify(r"C:\notes\a.md", None, true).unwrap(),
+            local(r"C:\notes\a.md", None)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Login keychain access through Security.framework.
+//!
+//! Replaces shelling out to `/usr/bin/security`, which put the whole secrets
+//! JSON on the command line (`-w`) where `ps` and process-audit logs can see
+//! it. New items get an "any application may decrypt" ACL — the same thing
+//! `security add-generic-password -A` sets up — so a reinstall or update
+//! (new code signature) doesn't trigger keychain password prompts. Service
+//! and account names are unchanged, so items written by the CLI still load.
+
+use std::ffi::c_void;
+use std::ptr;
+
+type CFTypeRef = *const c_void;
+type CFIndex = isize;
+type OSStatus = i32;
+
+const ERR_SEC_SUCCESS: OSStatus = 0;
+const ERR_SEC_ITEM_NOT_FOUND: OSStatus = -25300;
+const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+
+/// Only ever used by address.
+#[repr(C)]
+struct CFDictionaryCallBacks {
+    _opaque: [u8; 0],
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    static kCFBooleanTrue: CFTypeRef;
+    static kCFTypeDictionaryKeyCallBacks: CFDictionaryCallBacks;
+    static kCFTypeDictionaryValueCallBacks: CFDictionaryCallBacks;
+
+    fn CFRelease(cf: CFTypeRef);
+    fn CFStringCreateWithBytes(
+        alloc: CFTypeRef,
+        bytes: *const u8,
+        num_bytes: CFIndex,
+        encoding: u32,
+        is_external_representation: u8,
+    ) -> CFTypeRef;
+    fn CFDataCreate(alloc: CFTypeRef, bytes: *const u8, length: CFIndex) -> CFTypeRef;
+    fn CFDataGetBytePtr(data: CFTypeRef) -> *const u8;
+    fn CFDataGetLength(data: CFTypeRef) -> CFIndex;
+    fn CFDictionaryCreate(
+        alloc: CFTypeRef,
+        keys: *const CFTypeRef,
+        values: *const CFTypeRef,
+        num_values: CFIndex,
+        key_callbacks: *const CFDictionaryCallBacks,
+        value_callbacks: *const CFDictionaryCallBacks,
+    ) -> CFTypeRef;
+    fn CFArrayGetCount(array: CFTypeRef) -> CFIndex;
+    fn CFArrayGetValueAtIndex(array: CFTypeRef, idx: CFIndex) -> CFTypeRef;
+}
+
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    static kSecClass: CFTypeRef;
+    static kSecClassGenericPassword: CFTypeRef;
+    static kSecAttrService: CFTypeRef;
+    static kSecAttrAccount: CFTypeRef;
+    static kSecAttrLabel: CFTypeRef;
+    static kSecAttrAccess: CFTypeRef;
+    static kSecValueData: CFTypeRef;
+    static kSecReturnData: CFTypeRef;
+    static kSecMatchLimit: CFTypeRef;
+    static kSecMatchLimitOne: CFTypeRef;
+    static kSecUseAuthenticationUI: CFTypeRef;
+    static kSecUseAuthenticationUIFail: CFTypeRef;
+    static kSecACLAuthorizationDecrypt: CFTypeRef;
+
+    fn SecItemCopyMatching(query: CFTypeRef, result: *mut CFTypeRef) -> OSStatus;
+    fn SecItemAdd(attributes: CFTypeRef, result: *mut CFTypeRef) -> OSStatus;
+    fn SecItemUpdate(query: CFTypeRef, attributes: CFTypeRef) -> OSStatus;
+    fn SecItemDelete(query: CFTypeRef) -> OSStatus;
+    fn SecAccessCreate(
+        descriptor: CFTypeRef,
+        trusted_list: CFTypeRef,
+        access: *mut CFTypeRef,
+    ) -> OSStatus;
+    fn SecAccessCopyMatchingACLList(access: CFTypeRef, authorization_tag: CFTypeRef) -> CFTypeRef;
+    fn SecACLCopyContents(
+        acl: CFTypeRef,
+        application_list: *mut CFTypeRef,
+        description: *mut CFTypeRef,
+        prompt_selector: *mut u16,
+    ) -> OSStatus;
+    fn SecACLSetContents(
+        acl: CFTypeRef,
+        application_list: CFTypeRef,
+        description: CFTypeRef,
+        prompt_selector: u16,
+    ) -> OSStatus;
+}
+
+/// Owned CF object, released on drop.
+struct Cf(CFTypeRef);
+
+impl Drop for Cf {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { CFRelease(self.0) };
+        }
+    }
+}
+
+fn cf_string(s: &str) -> Cf {
+    Cf(unsafe {
+        CFStringCreateWithBytes(
+            ptr::null(),
+            s.as_ptr(),
+            s.len() as CFIndex,
+            CF_STRING_ENCODING_UTF8,
+            0,
+        )
+    })
+}
+
+fn cf_data(bytes: &[u8]) -> Cf {
+    Cf(unsafe { CFDataCreate(ptr::null(), bytes.as_ptr(), bytes.len() as CFIndex) })
+}
+
+fn dictionary(pairs: &[(CFTypeRef, CFTypeRef)]) -> Cf {
+    let keys: Vec<CFTypeRef> = pairs.iter().map(|(k, _)| *k).collect();
+    let values: Vec<CFTypeRef> = pairs.iter().map(|(_, v)| *v).collect();
+    Cf(unsafe {
+        CFDictionaryCreate(
+            ptr::null(),
+            keys.as_ptr(),
+            values.as_ptr(),
+            pairs.len() as CFIndex,
+            &kCFTypeDictionaryKeyCallBacks,
+            &kCFTypeDictionaryValueCallBacks,
+        )
+    })
+}
+
+/// A fresh access object whose decrypt ACLs trust every application
+/// (a null application list), matching `security ... -A`.
+fn any_app_access(label: &Cf) -> Result<Cf, String> {
+    let failed = |status: OSStatus| format!("Failed to create keychain access ({})", status);
+    unsafe {
+        let mut access: CFTypeRef = ptr::null();
+        let status = SecAccessCreate(label.0, ptr::null(), &mut access);
+        if status != ERR_SEC_SUCCESS {
+            return Err(failed(status));
+        }
+        let access = Cf(access);
+        let acls = Cf(SecAccessCopyMatchingACLList(
+            access.0,
+            kSecACLAuthorizationDecrypt,
+        ));
+        if acls.0.is_null() {
+            return Err(failed(0));
+        }
+        for i in 0..CFArrayGetCount(acls.0) {
+            let acl = CFArrayGetValueAtIndex(acls.0, i);
+            let mut apps: CFTypeRef = ptr::null();
+            let mut description: CFTypeRef = ptr::null();
+            let mut prompt: u16 = 0;
+            let status = SecACLCopyContents(acl, &mut apps, &mut description, &mut prompt);
+            let (_apps, description) = (Cf(apps), Cf(description));
+            if status != ERR_SEC_SUCCESS {
+                return Err(failed(status));
+            }
+            let status = SecACLSetContents(acl, ptr::null(), description.0, prompt);
+            if status != ERR_SEC_SUCCESS {
+                return Err(failed(status));
+            }
+        }
+        Ok(access)
+    }
+}
+
+/// Read a generic password. `Ok(None)` when no such item exists.
+pub(crate) fn read_password(service: &str, account: &str) -> Result<Option<String>, String> {
+    let service = cf_string(service);
+    let account = cf_string(account);
+    unsafe {
+        let query = dictionary(&[
+            (kSecClass, kSecClassGenericPassword),
+            (kSecAttrService, service.0),
+            (kSecAttrAccount, account.0),
+            (kSecReturnData, kCFBooleanTrue),
+            (kSecMatchLimit, kSecMatchLimitOne),
+        ]);
+        let mut result: CFTypeRef = ptr::null();
+        match SecItemCopyMatching(query.0, &mut result) {
+            ERR_SEC_SUCCESS => {
+                let data = Cf(result);
+                let len = CFDataGetLength(data.0);
+                let bytes = if len > 0 {
+                    std::slice::from_raw_parts(CFDataGetBytePtr(data.0), len as usize).to_vec()
+                } else {
+                    Vec::new()
+                };
+                String::from_utf8(bytes)
+                    .map(Some)
+                    .map_err(|_| "Keychain item is not valid UTF-8".to_string())
+            }
+            ERR_SEC_ITEM_NOT_FOUND => Ok(None),
+            status => Err(format!("Failed to read keychain ({})", status)),
+        }
+    }
+}
+
+/// Create or replace a generic password without it ever touching argv.
+pub(crate) fn write_password(service: &str, account: &str, password: &str) -> Result<(), String> {
+    let service = cf_string(service);
+    let account = cf_string(account);
+    let data = cf_data(password.as_bytes());
+    unsafe {
+        // Update in place first: keeps the existing ACL and never leaves a
+        // window with no item. UI is suppressed so an item still bound to an
+        // older binary's signature fails fast instead of prompting.
+        let query = dictionary(&[
+            (kSecClass, kSecClassGenericPassword),
+            (kSecAttrService, service.0),
+            (kSecAttrAccount, account.0),
+            (kSecUseAuthenticationUI, kSecUseAuthenticationUIFail),
+        ]);
+        let changes = dictionary(&[(kSecValueData, data.0)]);
+        if SecItemUpdate(query.0, changes.0) == ERR_SEC_SUCCESS {
+            return Ok(());
+        }
+
+        // Missing, or not updatable by this binary: recreate it with an
+        // any-app ACL (deleting works regardless of the old ACL).
+        let _ = SecItemDelete(query.0);
+        let access = any_app_access(&service)?;
+        let attributes = dictionary(&[
+            (kSecClass, kSecClassGenericPassword),
+            (kSecAttrService, service.0),
+            (kSecAttrAccount, account.0),
+            (kSecAttrLabel, service.0),
+            (kSecAttrAccess, access.0),
+            (kSecValueData, data.0),
+        ]);
+        match SecItemAdd(attributes.0, ptr::null_mut()) {
+            ERR_SEC_SUCCESS => Ok(()),
+            status => Err(format!("Failed to store in keychain ({})", status)),
+        }
+    }
+}
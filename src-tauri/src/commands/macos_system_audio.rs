@@ -612,7 +612,7 @@ mod imp {
     }
 
     fn log_step(step: &str, message: &str) {
-        eprintln!("[macos_system_audio:{step}] {message}");
+        log::debug!("{step}: {message}");
     }
 }
 
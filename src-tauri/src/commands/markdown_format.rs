@@ -0,0 +1,762 @@
+//! `format_markdown` and `lint_markdown`: one set of rules that either
+//! rewrites a document or reports where it departs from them, so format on
+//! save and the editor's underlines always agree.
+//!
+//! Frontmatter, fenced code and math blocks and indented code are never
+//! touched, byte for byte. Every rule can be switched off in
+//! [`FormatConfig`].
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::rewrap::{closes_fence, display_width, is_delimiter_row, leading_columns, opens_fence};
+
+const TRAILING_WHITESPACE: &str = "trailing-whitespace";
+const HEADING_SPACING: &str = "heading-spacing";
+const LIST_MARKER: &str = "list-marker";
+const ORDERED_NUMBERING: &str = "ordered-numbering";
+const TABLE_PADDING: &str = "table-padding";
+const BLANK_LINES: &str = "blank-lines";
+const FINAL_NEWLINE: &str = "final-newline";
+/// Rules in the order they run and are reported as applied.
+const RULES: [&str; 7] = [
+    TRAILING_WHITESPACE,
+    HEADING_SPACING,
+    LIST_MARKER,
+    ORDERED_NUMBERING,
+    TABLE_PADDING,
+    BLANK_LINES,
+    FINAL_NEWLINE,
+];
+
+/// Block id of the frontmatter; other verbatim blocks count up from it.
+const FRONTMATTER: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BulletMarker {
+    #[serde(rename = "-")]
+    Dash,
+    #[serde(rename = "*")]
+    Star,
+    #[serde(rename = "+")]
+    Plus,
+}
+
+impl BulletMarker {
+    fn char(self) -> char {
+        match self {
+            Self::Dash => '-',
+            Self::Star => '*',
+            Self::Plus => '+',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatConfig {
+    /// Marker for bullet list items; None leaves them alone.
+    pub bullet: Option<BulletMarker>,
+    /// Number ordered list items on from the first item's number.
+    pub renumber_ordered: bool,
+    /// Pad table cells so the columns line up.
+    pub pad_tables: bool,
+    /// One space after the `#`s and a blank line around headings.
+    pub heading_spacing: bool,
+    /// Drop trailing whitespace, keeping two-space hard breaks.
+    pub trim_trailing_whitespace: bool,
+    /// Longest run of blank lines (at least 1); None for no limit.
+    pub max_blank_lines: Option<usize>,
+    /// End with exactly one line break.
+    pub final_newline: bool,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            bullet: Some(BulletMarker::Dash),
+            renumber_ordered: true,
+            pad_tables: true,
+            heading_spacing: true,
+            trim_trailing_whitespace: true,
+            max_blank_lines: Some(1),
+            final_newline: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    /// 1-based, in the document as given.
+    pub line: usize,
+    /// 1-based, in characters.
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Formatted {
+    pub content: String,
+    /// Rules that changed something.
+    pub applied: Vec<&'static str>,
+}
+
+/// Format `content` by the rules in `config` (all on by default).
+#[tauri::command]
+pub fn format_markdown(content: String, config: Option<FormatConfig>) -> Formatted {
+    let (content, diagnostics) = run(&content, &config.unwrap_or_default());
+    let applied = RULES
+        .into_iter()
+        .filter(|rule| diagnostics.iter().any(|d| d.rule == *rule))
+        .collect();
+    Formatted { content, applied }
+}
+
+/// Where `content` departs from the rules `format_markdown` would apply.
+#[tauri::command]
+pub fn lint_markdown(content: String, config: Option<FormatConfig>) -> Vec<Diagnostic> {
+    let (_, mut diagnostics) = run(&content, &config.unwrap_or_default());
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    text: String,
+    /// Id of the verbatim block the line is part of.
+    block: Option<usize>,
+    /// 1-based line in the input; inserted lines take their neighbour's.
+    orig: usize,
+}
+
+impl Line {
+    fn is_blank_text(&self) -> bool {
+        self.block.is_none() && self.text.trim().is_empty()
+    }
+}
+
+fn diag(rule: &'static str, line: &Line, column: usize, message: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        rule,
+        line: line.orig,
+        column,
+        message: message.into(),
+    }
+}
+
+fn run(content: &str, config: &FormatConfig) -> (String, Vec<Diagnostic>) {
+    let eol = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let had_newline = content.ends_with('\n');
+    let mut lines = parse(content);
+    let mut diags = Vec::new();
+    if config.trim_trailing_whitespace {
+        trim_trailing(&mut lines, &mut diags);
+    }
+    if config.heading_spacing {
+        heading_markers(&mut lines, &mut diags);
+    }
+    if let Some(marker) = config.bullet {
+        bullets(&mut lines, marker.char(), &mut diags);
+    }
+    if config.renumber_ordered {
+        renumber(&mut lines, &mut diags);
+    }
+    if config.pad_tables {
+        tables(&mut lines, &mut diags);
+    }
+    if config.heading_spacing {
+        heading_blank_lines(&mut lines, &mut diags);
+    }
+    if let Some(max) = config.max_blank_lines {
+        // No blank lines at all would merge every paragraph
+        limit_blank_lines(&mut lines, max.max(1), &mut diags);
+    }
+    if config.final_newline {
+        final_newline(&mut lines, had_newline, &mut diags);
+    }
+    let mut out = lines
+        .iter()
+        .map(|l| l.text.as_str())
+        .collect::<Vec<_>>()
+        .join(eol);
+    if !lines.is_empty() && (had_newline || config.final_newline) {
+        out.push_str(eol);
+    }
+    (out, diags)
+}
+
+fn parse(content: &str) -> Vec<Line> {
+    if content.is_empty() {
+        return Vec::new();
+    }
+    let body = content.strip_suffix('\n').unwrap_or(content);
+    let mut lines: Vec<Line> = body
+        .split('\n')
+        .enumerate()
+        .map(|(i, text)| Line {
+            text: text.strip_suffix('\r').unwrap_or(text).to_string(),
+            block: None,
+            orig: i + 1,
+        })
+        .collect();
+    mark_verbatim(&mut lines);
+    lines
+}
+
+/// For each line of `content`, whether it is frontmatter or code and so
+/// must be left as written.
+pub(crate) fn verbatim_lines(content: &str) -> Vec<bool> {
+    parse(content).iter().map(|l| l.block.is_some()).collect()
+}
+
+/// A fence opener; a backtick fence's info string can't contain backticks.
+fn fence_open(text: &str) -> Option<(char, usize)> {
+    let (ch, len) = opens_fence(text)?;
+    let rest = &text.trim_start()[len..];
+    (ch != '`' || !rest.contains('`')).then_some((ch, len))
+}
+
+/// Assign frontmatter, fenced blocks and indented code to verbatim blocks.
+fn mark_verbatim(lines: &mut [Line]) {
+    let mut start = 0;
+    if lines.first().is_some_and(|l| l.text.trim_end() == "---") {
+        let close = lines[1..]
+            .iter()
+            .position(|l| matches!(l.text.trim_end(), "---" | "..."));
+        if let Some(close) = close {
+            for line in &mut lines[..close + 2] {
+                line.block = Some(FRONTMATTER);
+            }
+            start = close + 2;
+        }
+    }
+
+    let mut next_id = FRONTMATTER + 1;
+    let mut fence: Option<(char, usize)> = None;
+    let mut indented: Option<usize> = None;
+    let mut prev_blank = true;
+    let mut in_list = false;
+    for line in &mut lines[start..] {
+        if let Some((ch, len)) = fence {
+            line.block = Some(next_id - 1);
+            if closes_fence(&line.text, ch, len) {
+                fence = None;
+            }
+            prev_blank = false;
+            continue;
+        }
+        if line.text.trim().is_empty() {
+            prev_blank = true;
+            continue;
+        }
+        let indent = leading_columns(&line.text);
+        if indent >= 4 && (indented.is_some() || (prev_blank && !in_list)) {
+            let id = *indented.get_or_insert_with(|| {
+                next_id += 1;
+                next_id - 1
+            });
+            line.block = Some(id);
+            prev_blank = false;
+            continue;
+        }
+        indented = None;
+        if let Some(opener) = fence_open(&line.text) {
+            fence = Some(opener);
+            line.block = Some(next_id);
+            next_id += 1;
+        } else if bullet(&line.text).is_some() || ordered(&line.text).is_some() {
+            in_list = true;
+        } else if indent == 0 && (prev_blank || atx(&line.text).is_some()) {
+            in_list = false;
+        }
+        prev_blank = false;
+    }
+
+    // Blank lines between two parts of an indented code block belong to it
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].is_blank_text() {
+            i += 1;
+            continue;
+        }
+        let end = (i..lines.len())
+            .find(|&j| !lines[j].is_blank_text())
+            .unwrap_or(lines.len());
+        let before = i.checked_sub(1).and_then(|b| lines[b].block);
+        let after = lines.get(end).and_then(|l| l.block);
+        if before.is_some() && before == after {
+            for line in &mut lines[i..end] {
+                line.block = before;
+            }
+        }
+        i = end;
+    }
+}
+
+/// Level and the text after the `#`s of an ATX heading.
+fn atx(text: &str) -> Option<(usize, &str)> {
+    let rest = text.trim_start_matches(' ');
+    if text.len() - rest.len() > 3 {
+        return None;
+    }
+    let level = rest.bytes().take_while(|b| *b == b'#').count();
+    let after = &rest[level..];
+    ((1..=6).contains(&level) && (after.is_empty() || after.starts_with([' ', '\t'])))
+        .then_some((level, after))
+}
+
+fn is_thematic_break(s: &str) -> bool {
+    let first = s.chars().next();
+    s.chars()
+        .filter(|c| *c != ' ' && *c != '\t')
+        .all(|c| Some(c) == first)
+        && s.chars().filter(|c| Some(*c) == first).count() >= 3
+}
+
+/// Byte offset and character of a bullet list marker.
+fn bullet(text: &str) -> Option<(usize, char)> {
+    let rest = text.trim_start_matches([' ', '\t']);
+    let marker = rest
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '-' | '*' | '+'))?;
+    let after = &rest[1..];
+    if !after.starts_with([' ', '\t']) || after.trim().is_empty() || is_thematic_break(rest) {
+        return None;
+    }
+    Some((text.len() - rest.len(), marker))
+}
+
+/// Byte range of the number and the delimiter of an ordered list marker.
+fn ordered(text: &str) -> Option<(Range<usize>, char)> {
+    let rest = text.trim_start_matches([' ', '\t']);
+    let start = text.len() - rest.len();
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    if !(1..=9).contains(&digits) {
+        return None;
+    }
+    let delim = rest[digits..]
+        .chars()
+        .next()
+        .filter(|c| *c == '.' || *c == ')')?;
+    let after = &rest[digits + 1..];
+    if !after.starts_with([' ', '\t']) || after.trim().is_empty() {
+        return None;
+    }
+    Some((start..start + digits, delim))
+}
+
+fn trim_trailing(lines: &mut [Line], diags: &mut Vec<Diagnostic>) {
+    for i in 0..lines.len() {
+        if lines[i].block.is_some() {
+            continue;
+        }
+        let text = &lines[i].text;
+        let trimmed = text.trim_end();
+        if trimmed.len() == text.len() {
+            continue;
+        }
+        let trailing = &text[trimmed.len()..];
+        let next_is_text = lines
+            .get(i + 1)
+            .is_some_and(|l| l.block.is_none() && !l.text.trim().is_empty());
+        let hard_break = next_is_text
+            && !trimmed.is_empty()
+            && atx(trimmed).is_none()
+            && trailing.len() >= 2
+            && trailing.bytes().all(|b| b == b' ');
+        let wanted = if hard_break {
+            format!("{}  ", trimmed)
+        } else {
+            trimmed.to_string()
+        };
+        if wanted != *text {
+            let column = trimmed.chars().count() + 1;
+            diags.push(diag(
+                TRAILING_WHITESPACE,
+                &lines[i],
+                column,
+                "Trailing whitespace",
+            ));
+            lines[i].text = wanted;
+        }
+    }
+}
+
+fn heading_markers(lines: &mut [Line], diags: &mut Vec<Diagnostic>) {
+    for line in lines.iter_mut().filter(|l| l.block.is_none()) {
+        let Some((level, rest)) = atx(&line.text) else {
+            continue;
+        };
+        let text = rest.trim();
+        let wanted = if text.is_empty() {
+            "#".repeat(level)
+        } else {
+            format!("{} {}", "#".repeat(level), text)
+        };
+        if wanted != line.text {
+            diags.push(diag(
+                HEADING_SPACING,
+                line,
+                1,
+                "Put one space between the heading marker and its text",
+            ));
+            line.text = wanted;
+        }
+    }
+}
+
+fn bullets(lines: &mut [Line], marker: char, diags: &mut Vec<Diagnostic>) {
+    for line in lines.iter_mut().filter(|l| l.block.is_none()) {
+        let Some((at, found)) = bullet(&line.text) else {
+            continue;
+        };
+        if found == marker {
+            continue;
+        }
+        let column = line.text[..at].chars().count() + 1;
+        diags.push(diag(
+            LIST_MARKER,
+            line,
+            column,
+            format!("Use `{}` for bullet lists", marker),
+        ));
+        line.text
+            .replace_range(at..at + 1, marker.encode_utf8(&mut [0; 4]));
+    }
+}
+
+fn renumber(lines: &mut [Line], diags: &mut Vec<Diagnostic>) {
+    // Marker column, delimiter and next number of each list still open
+    let mut open: Vec<(usize, char, u64)> = Vec::new();
+    let mut prev_blank = false;
+    let mut prev_block = None;
+    for line in lines.iter_mut() {
+        if let Some(block) = line.block {
+            // Code less indented than a list's items ends the list
+            if prev_block != Some(block) {
+                let indent = leading_columns(&line.text);
+                open.retain(|l| l.0 < indent);
+            }
+            prev_block = Some(block);
+            prev_blank = false;
+            continue;
+        }
+        prev_block = None;
+        if line.text.trim().is_empty() {
+            prev_blank = true;
+            continue;
+        }
+        let indent = leading_columns(&line.text);
+        if let Some((digits, delim)) = ordered(&line.text) {
+            open.retain(|l| l.0 <= indent);
+            let number: u64 = line.text[digits.clone()].parse().unwrap_or(1);
+            let expected = match open.last_mut() {
+                Some(list) if list.0 == indent && list.1 == delim => {
+                    list.2 += 1;
+                    list.2 - 1
+                }
+                Some(list) if list.0 == indent => {
+                    *list = (indent, delim, number + 1);
+                    number
+                }
+                _ => {
+                    open.push((indent, delim, number + 1));
+                    number
+                }
+            };
+            if expected != number {
+                let column = line.text[..digits.start].chars().count() + 1;
+                diags.push(diag(
+                    ORDERED_NUMBERING,
+                    line,
+                    column,
+                    format!("Number this item {}", expected),
+                ));
+                line.text.replace_range(digits, &expected.to_string());
+            }
+        } else if prev_blank || bullet(&line.text).is_some() || atx(&line.text).is_some() {
+            // Right after an item, unindented text is a lazy continuation
+            open.retain(|l| l.0 < indent);
+        }
+        prev_blank = false;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// Cells of a table row, split on unescaped pipes.
+fn split_cells(row: &str) -> Vec<String> {
+    let mut row = row.trim();
+    row = row.strip_prefix('|').unwrap_or(row);
+    if row.ends_with('|') && !row.ends_with("\\|") {
+        row = &row[..row.len() - 1];
+    }
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut escaped = false;
+    for c in row.chars() {
+        if c == '|' && !escaped {
+            cells.push(cell.trim().to_string());
+            cell.clear();
+        } else {
+            cell.push(c);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+fn align_of(cell: &str) -> Align {
+    match (cell.starts_with(':'), cell.ends_with(':')) {
+        (true, true) => Align::Center,
+        (true, false) => Align::Left,
+        (false, true) => Align::Right,
+        (false, false) => Align::None,
+    }
+}
+
+fn pad(cell: &str, width: usize, align: Align) -> String {
+    let gap = width.saturating_sub(display_width(cell));
+    let left = match align {
+        Align::Right => gap,
+        Align::Center => gap / 2,
+        _ => 0,
+    };
+    format!("{}{}{}", " ".repeat(left), cell, " ".repeat(gap - left))
+}
+
+fn render_row(prefix: &str, cells: &[String], widths: &[usize], aligns: &[Align]) -> String {
+    let mut out = format!("{}|", prefix);
+    for (c, width) in widths.iter().enumerate() {
+        let cell = cells.get(c).map_or("", String::as_str);
+        out.push(' ');
+        out.push_str(&pad(cell, *width, aligns[c]));
+        out.push_str(" |");
+    }
+    // Cells past the header's count aren't shown, but aren't dropped
+    for extra in cells.iter().skip(widths.len()) {
+        out.push(' ');
+        out.push_str(extra);
+        out.push_str(" |");
+    }
+    out
+}
+
+fn tables(lines: &mut [Line], diags: &mut Vec<Diagnostic>) {
+    let is_row = |l: &Line| l.block.is_none() && l.text.contains('|');
+    let mut i = 0;
+    while i + 1 < lines.len() {
+        if !(is_row(&lines[i]) && is_row(&lines[i + 1]) && is_delimiter_row(&lines[i + 1].text)) {
+            i += 1;
+            continue;
+        }
+        let aligns: Vec<Align> = split_cells(&lines[i + 1].text)
+            .iter()
+            .map(|c| align_of(c))
+            .collect();
+        let header = split_cells(&lines[i].text);
+        if header.len() != aligns.len() {
+            i += 1;
+            continue;
+        }
+        let end = (i + 2..lines.len())
+            .find(|&j| !is_row(&lines[j]))
+            .unwrap_or(lines.len());
+        let mut rows = vec![header];
+        rows.extend(lines[i + 2..end].iter().map(|l| split_cells(&l.text)));
+        let widths: Vec<usize> = (0..aligns.len())
+            .map(|c| {
+                rows.iter()
+                    .filter_map(|r| r.get(c))
+                    .map(|s| display_width(s))
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+        let dashes: Vec<String> = aligns
+            .iter()
+            .zip(&widths)
+            .map(|(align, w)| match align {
+                Align::None => "-".repeat(*w),
+                Align::Left => format!(":{}", "-".repeat(w - 1)),
+                Align::Right => format!("{}:", "-".repeat(w - 1)),
+                Align::Center => format!(":{}:", "-".repeat(w - 2)),
+            })
+            .collect();
+        // Keep the indentation of a table inside a list item
+        let prefix: String = lines[i]
+            .text
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let mut formatted = vec![
+            render_row(&prefix, &rows[0], &widths, &aligns),
+            render_row(&prefix, &dashes, &widths, &vec![Align::None; aligns.len()]),
+        ];
+        formatted.extend(
+            rows[1..]
+                .iter()
+                .map(|r| render_row(&prefix, r, &widths, &aligns)),
+        );
+        if formatted
+            .iter()
+            .zip(&lines[i..end])
+            .any(|(f, l)| *f != l.text)
+        {
+            diags.push(diag(
+                TABLE_PADDING,
+                &lines[i],
+                1,
+                "Table columns are not aligned",
+            ));
+            for (line, text) in lines[i..end].iter_mut().zip(formatted) {
+                line.text = text;
+            }
+        }
+        i = end;
+    }
+}
+
+fn heading_blank_lines(lines: &mut Vec<Line>, diags: &mut Vec<Diagnostic>) {
+    let old = std::mem::take(lines);
+    let blank = |orig| Line {
+        text: String::new(),
+        block: None,
+        orig,
+    };
+    for (i, line) in old.iter().enumerate() {
+        let heading = line.block.is_none() && atx(&line.text).is_some();
+        if heading {
+            let crowded = lines
+                .last()
+                .is_some_and(|p| !p.text.trim().is_empty() && p.block != Some(FRONTMATTER));
+            if crowded {
+                diags.push(diag(
+                    HEADING_SPACING,
+                    line,
+                    1,
+                    "Add a blank line before the heading",
+                ));
+                lines.push(blank(line.orig));
+            }
+        }
+        lines.push(line.clone());
+        if heading && old.get(i + 1).is_some_and(|n| !n.text.trim().is_empty()) {
+            diags.push(diag(
+                HEADING_SPACING,
+                line,
+                1,
+                "Add a blank line after the heading",
+            ));
+            lines.push(blank(line.orig));
+        }
+    }
+}
+
+fn limit_blank_lines(lines: &mut Vec<Line>, max: usize, diags: &mut Vec<Diagnostic>) {
+    let mut run = 0;
+    lines.retain(|line| {
+        if !line.is_blank_text() {
+            run = 0;
+            return true;
+        }
+        run += 1;
+        if run == max + 1 {
+            diags.push(diag(
+                BLANK_LINES,
+                line,
+                1,
+                format!("More than {} blank line(s) in a row", max),
+            ));
+        }
+        run <= max
+    });
+}
+
+fn final_newline(lines: &mut Vec<Line>, had_newline: bool, diags: &mut Vec<Diagnostic>) {
+    let trailing = lines.iter().rev().take_while(|l| l.is_blank_text()).count();
+    if trailing > 0 {
+        let first = &lines[lines.len() - trailing];
+        diags.push(diag(FINAL_NEWLINE, first, 1, "Blank lines at the end"));
+        lines.truncate(lines.len() - trailing);
+    } else if let Some(last) = lines.last().filter(|_| !had_newline) {
+        let column = last.text.chars().count() + 1;
+        diags.push(diag(
+            FINAL_NEWLINE,
+            last,
+            column,
+            "No line break at the end",
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(content: &str) -> Formatted {
+        format_markdown(content.to_string(), None)
+    }
+
+    #[test]
+    fn should_format_every_rule_and_leave_verbatim_blocks_alone() {
+        let doc = "---\ntitle: x  \n---\n#   Title\nIntro text   \n* one\n* two\n+ three\n\n1. a\n1. b\n1. c\n\n\n\n| a | b |\n|:-|-:|\n| long cell | 1 |\n\n```js\n* keep  \n1. keep\n```";
+        let result = format(doc);
+        assert_eq!(
+            result.content,
+            "---\ntitle: x  \n---\n# Title\n\nIntro text  \n- one\n- two\n- three\n\n1. a\n2. b\n3. c\n\n| a         |   b |\n| :-------- | --: |\n| long cell |   1 |\n\n```js\n* keep  \n1. keep\n```\n"
+        );
+        assert_eq!(result.applied, RULES);
+        assert_eq!(format(&result.content).applied, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn should_lint_with_positions_in_the_original() {
+        let diagnostics = lint_markdown("# Title  \ntext\n*  item\n".to_string(), None);
+        let found: Vec<(&str, usize, usize)> = diagnostics
+            .iter()
+            .map(|d| (d.rule, d.line, d.column))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (HEADING_SPACING, 1, 1),
+                (TRAILING_WHITESPACE, 1, 8),
+                (LIST_MARKER, 3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn should_pad_wide_cells_and_keep_crlf_and_indented_code() {
+        let doc = "Para\r\n\r\n    code  \r\n\r\n    * more\r\n\r\n|名前|x|\r\n|:-:|--|\r\n|山田太郎|y|\r\n";
+        assert_eq!(
+            format(doc).content,
+            "Para\r\n\r\n    code  \r\n\r\n    * more\r\n\r\n|   名前   | x   |\r\n| :------: | --- |\r\n| 山田太郎 | y   |\r\n"
+        );
+    }
+
+    #[test]
+    fn should_renumber_nested_lists_separately() {
+        let doc = "3. a\n   1. x\n   1. y\nlazy\n4. b\n\n* * *\n\n1) p\n1) q\n";
+        assert_eq!(
+            format(doc).content,
+            "3. a\n   1. x\n   2. y\nlazy\n4. b\n\n* * *\n\n1) p\n2) q\n"
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
 use std::sync::Mutex;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
 use tauri::State;
 
+use super::error::{CommandError, ErrorCode};
+
 /// Maximum line length for MCP responses (256 KB).
 /// Servers like git-mcp-server register 28+ tools, producing large tools/list responses.
 const MAX_LINE_LENGTH: usize = 256 * 1024;
@@ -16,6 +22,13 @@ const READ_LINE_TIMEOUT: Duration = Duration::from_secs(20);
 /// Total wall-clock cap per request — prevents indefinite wait when a server
 /// outputs many non-JSON progress lines (each line would otherwise reset READ_LINE_TIMEOUT)
 const TOTAL_RESPONSE_TIMEOUT: Duration = Duration::from_secs(25);
+/// Maximum `resources/read` response line (2 MB); the contents arrive inline.
+const MAX_RESOURCE_LENGTH: usize = 2 * 1024 * 1024;
+/// How long a cached `prompts/list` / `resources/list` result is served
+/// when the server sends no `list_changed` notification.
+const LIST_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Cursor pages followed when filling the list cache
+const MAX_LIST_PAGES: usize = 20;
 
 /// Dangerous or interfering environment variable prefixes that must not be passed to child processes
 const BLOCKED_ENV_PREFIXES: &[&str] = &[
@@ -51,6 +64,45 @@ struct MCPProcess {
     line_rx: Receiver<ReadResult>,
 }
 
+/// A server list served from the cache by `mcp_get_cached`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpListKind {
+    Prompts,
+    Resources,
+}
+
+impl McpListKind {
+    fn method(self) -> &'static str {
+        match self {
+            McpListKind::Prompts => "prompts/list",
+            McpListKind::Resources => "resources/list",
+        }
+    }
+
+    /// Field of the list result holding the items.
+    fn field(self) -> &'static str {
+        match self {
+            McpListKind::Prompts => "prompts",
+            McpListKind::Resources => "resources",
+        }
+    }
+
+    /// The list a server notification says has changed, if any.
+    fn from_list_changed(method: &str) -> Option<Self> {
+        match method {
+            "notifications/prompts/list_changed" => Some(McpListKind::Prompts),
+            "notifications/resources/list_changed" => Some(McpListKind::Resources),
+            _ => None,
+        }
+    }
+}
+
+struct CachedList {
+    items: Value,
+    fetched_at: Instant,
+}
+
 /// Manages stdio-based MCP server processes.
 ///
 /// The `processes` Mutex is held only briefly for HashMap operations and stdin writes.
@@ -59,6 +111,12 @@ struct MCPProcess {
 pub struct MCPProcessManager {
     processes: Mutex<HashMap<String, MCPProcess>>,
     pids: Mutex<HashMap<String, u32>>,
+    /// Prompt and resource lists per server; dropped on (re)connect, on
+    /// `list_changed` notifications and when the server fails.
+    lists: Mutex<HashMap<(String, McpListKind), CachedList>>,
+    /// Ids of requests the backend issues itself, kept apart from the
+    /// frontend's numeric ids.
+    next_request_id: AtomicU64,
 }
 
 impl MCPProcessManager {
@@ -66,6 +124,35 @@ impl MCPProcessManager {
         Self {
             processes: Mutex::new(HashMap::new()),
             pids: Mutex::new(HashMap::new()),
+            lists: Mutex::new(HashMap::new()),
+            next_request_id: AtomicU64::new(1),
+        }
+    }
+
+    fn cached_list(&self, server_id: &str, kind: McpListKind) -> Option<Value> {
+        let lists = self.lists.lock().ok()?;
+        lists
+            .get(&(server_id.to_string(), kind))
+            .filter(|cached| cached.fetched_at.elapsed() < LIST_CACHE_TTL)
+            .map(|cached| cached.items.clone())
+    }
+
+    fn store_list(&self, server_id: &str, kind: McpListKind, items: Value) {
+        if let Ok(mut lists) = self.lists.lock() {
+            lists.insert(
+                (server_id.to_string(), kind),
+                CachedList {
+                    items,
+                    fetched_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Drop one cached list of `server_id`, or all of them for `None`.
+    fn invalidate_lists(&self, server_id: &str, kind: Option<McpListKind>) {
+        if let Ok(mut lists) = self.lists.lock() {
+            lists.retain(|(id, k), _| id != server_id || kind.is_some_and(|kind| kind != *k));
         }
     }
 }
@@ -77,20 +164,69 @@ impl Default for MCPProcessManager {
 }
 
 /// Validate that a command is a simple executable name (no paths, no shell metacharacters).
-fn validate_command(command: &str) -> Result<(), String> {
+fn validate_command(command: &str) -> Result<(), CommandError> {
     if command.is_empty() {
-        return Err("Command must not be empty".to_string());
+        return Err(CommandError::new(
+            ErrorCode::McpInvalidCommand,
+            "Command must not be empty",
+        ));
     }
     for c in command.chars() {
         if !c.is_alphanumeric() && c != '-' && c != '_' && c != '.' {
-            return Err("Invalid command: must be a simple executable name".to_string());
+            return Err(CommandError::new(
+                ErrorCode::McpInvalidCommand,
+                "Invalid command: must be a simple executable name",
+            ));
         }
     }
     Ok(())
 }
 
+fn not_connected() -> CommandError {
+    CommandError::new(ErrorCode::McpNotConnected, "MCP server not connected")
+}
+
+fn lock_failed() -> CommandError {
+    CommandError::new(ErrorCode::Internal, "Lock error")
+}
+
+/// Prefer the server's own stderr output as the message when there is
+/// any; the code stays that of `e`.
+fn with_stderr(e: CommandError, stderr_msg: String) -> CommandError {
+    if stderr_msg.is_empty() {
+        return e;
+    }
+    CommandError::new(e.code, format!("MCP server error: {}", stderr_msg))
+        .with_details(serde_json::json!({ "stderr": stderr_msg }))
+}
+
+fn write_failed() -> CommandError {
+    CommandError::new(
+        ErrorCode::McpWriteFailed,
+        "Failed to write to MCP server (process may have exited)",
+    )
+}
+
+fn server_exited() -> CommandError {
+    CommandError::new(
+        ErrorCode::McpServerExited,
+        "MCP server process ended unexpectedly",
+    )
+}
+
+fn response_timeout() -> CommandError {
+    CommandError::new(ErrorCode::McpTimeout, "MCP response timeout")
+}
+
+fn capture_failed(stream: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::McpStartFailed,
+        format!("Failed to capture MCP server {}", stream),
+    )
+}
+
 /// Check if an environment variable name is safe to pass to child processes.
-fn is_safe_env_var(key: &str) -> bool {
+pub(crate) fn is_safe_env_var(key: &str) -> bool {
     !BLOCKED_ENV_PREFIXES
         .iter()
         .any(|prefix| key.starts_with(prefix))
@@ -181,7 +317,7 @@ fn try_read_stderr(stderr: &mut ChildStderr) -> String {
 
 /// Truncate and sanitize stderr output for error messages.
 /// Strips home directory paths for privacy but preserves overall message structure.
-fn sanitize_stderr(stderr_msg: &str) -> String {
+pub(crate) fn sanitize_stderr(stderr_msg: &str) -> String {
     let truncated = if stderr_msg.len() > 500 {
         &stderr_msg[..500]
     } else {
@@ -248,23 +384,27 @@ pub fn mcp_connect_stdio(
     command: String,
     args: Vec<String>,
     env: HashMap<String, String>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     #[cfg(target_os = "ios")]
     {
         let _ = (&state, &server_id, &command, &args, &env);
-        return Err("stdio transport is not available on iPad".to_string());
+        return Err(CommandError::new(
+            ErrorCode::McpUnsupported,
+            "stdio transport is not available on iPad",
+        ));
     }
 
     #[cfg(not(target_os = "ios"))]
     validate_command(&command)?;
 
-    let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
+    let mut processes = state.processes.lock().map_err(|_| lock_failed())?;
 
     // Kill existing process if any, and wait to prevent zombies
     if let Some(mut proc) = processes.remove(&server_id) {
         let _ = proc.child.kill();
         let _ = proc.child.wait();
     }
+    state.invalidate_lists(&server_id, None);
 
     let mut cmd = Command::new(&command);
     cmd.args(&args)
@@ -299,24 +439,24 @@ pub fn mcp_connect_stdio(
     // by pointing npm's user config to a non-existent file
     cmd.env("npm_config_userconfig", "/dev/null");
 
-    let mut child = cmd
-        .spawn()
-        .map_err(|_| "Failed to start MCP server".to_string())?;
+    let mut child = cmd.spawn().map_err(|_| {
+        CommandError::new(ErrorCode::McpStartFailed, "Failed to start MCP server")
+    })?;
 
     let pid = child.id();
 
     let stdin = child
         .stdin
         .take()
-        .ok_or("Failed to capture MCP server stdin")?;
+        .ok_or_else(|| capture_failed("stdin"))?;
     let stdout = child
         .stdout
         .take()
-        .ok_or("Failed to capture MCP server stdout")?;
+        .ok_or_else(|| capture_failed("stdout"))?;
     let stderr = child
         .stderr
         .take()
-        .ok_or("Failed to capture MCP server stderr")?;
+        .ok_or_else(|| capture_failed("stderr"))?;
 
     // Set stderr to non-blocking so diagnostic reads never block while holding the Mutex
     set_nonblocking(&stderr);
@@ -359,49 +499,59 @@ pub async fn mcp_send_request(
     state: State<'_, MCPProcessManager>,
     server_id: String,
     request: String,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
+    exchange(&state, &server_id, request, MAX_LINE_LENGTH).await
+}
+
+/// Write `request` and wait for the response line, at most `max_len` long.
+/// `list_changed` notifications read along the way invalidate the list cache.
+async fn exchange(
+    state: &MCPProcessManager,
+    server_id: &str,
+    request: String,
+    max_len: usize,
+) -> Result<String, CommandError> {
     // Step 1: Lock Mutex briefly — write request and take out the process
     let mut proc = {
-        let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
+        let mut processes = state.processes.lock().map_err(|_| lock_failed())?;
 
-        let proc = processes
-            .get_mut(&server_id)
-            .ok_or("MCP server not connected")?;
+        let proc = processes.get_mut(server_id).ok_or_else(not_connected)?;
 
         if let Err(_) = writeln!(proc.stdin, "{}", request) {
             let stderr_msg = try_read_stderr(&mut proc.stderr);
-            return if stderr_msg.is_empty() {
-                Err("Failed to write to MCP server (process may have exited)".to_string())
-            } else {
-                Err(format!("MCP server error: {}", stderr_msg))
-            };
+            return Err(with_stderr(write_failed(), stderr_msg));
         }
-        proc.stdin
-            .flush()
-            .map_err(|_| "Failed to flush MCP server stdin".to_string())?;
+        proc.stdin.flush().map_err(|_| {
+            CommandError::new(ErrorCode::McpWriteFailed, "Failed to flush MCP server stdin")
+        })?;
 
         // Remove from HashMap so the Mutex is released during channel read
-        processes
-            .remove(&server_id)
-            .ok_or("MCP server not connected")?
+        processes.remove(server_id).ok_or_else(not_connected)?
     };
     // Mutex is now released — other commands (including disconnect) can proceed
 
     // Step 2: Read response on a blocking thread so we don't freeze the Tauri IPC handler.
     // MCPProcess is Send (Child, ChildStdin, etc. are Send), so it can move across threads.
-    let (result, returned_proc) = tokio::task::spawn_blocking(move || {
-        let result = read_response_channel(&mut proc);
-        (result, proc)
+    let (result, notifications, returned_proc) = tokio::task::spawn_blocking(move || {
+        let mut notifications = Vec::new();
+        let result = read_response_channel(&mut proc, max_len, &mut notifications);
+        (result, notifications, proc)
     })
     .await
-    .map_err(|e| format!("Task failed: {}", e))?;
+    .map_err(|e| CommandError::new(ErrorCode::Internal, format!("Task failed: {}", e)))?;
+
+    for method in &notifications {
+        if let Some(kind) = McpListKind::from_list_changed(method) {
+            state.invalidate_lists(server_id, Some(kind));
+        }
+    }
 
     // Step 3: Put the process back or clean up
     match &result {
         Ok(_) => {
             // Success — put the process back for future requests
             if let Ok(mut processes) = state.processes.lock() {
-                processes.insert(server_id, returned_proc);
+                processes.insert(server_id.to_string(), returned_proc);
             }
         }
         Err(_) => {
@@ -411,35 +561,100 @@ pub async fn mcp_send_request(
             let _ = proc.child.try_wait();
             drop(proc);
             if let Ok(mut pids) = state.pids.lock() {
-                pids.remove(&server_id);
+                pids.remove(server_id);
             }
+            state.invalidate_lists(server_id, None);
         }
     }
 
     result
 }
 
+/// Send a request issued by the backend itself and return its `result`.
+async fn call(
+    state: &MCPProcessManager,
+    server_id: &str,
+    method: &str,
+    params: Value,
+    max_len: usize,
+) -> Result<Value, CommandError> {
+    let id = state.next_request_id.fetch_add(1, Ordering::Relaxed);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": format!("moraya-{}", id),
+        "method": method,
+        "params": params,
+    });
+    let response = exchange(state, server_id, request.to_string(), max_len).await?;
+    rpc_result(&response)
+}
+
+fn rpc_result(response: &str) -> Result<Value, CommandError> {
+    let mut response: Value = serde_json::from_str(response).map_err(|_| {
+        CommandError::new(ErrorCode::McpProtocolError, "Invalid JSON-RPC response")
+    })?;
+    if let Some(error) = response.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("Unknown error");
+        return Err(CommandError::new(
+            ErrorCode::McpProtocolError,
+            format!("MCP server error: {}", message),
+        )
+        .with_details(error.clone()));
+    }
+    Ok(response
+        .get_mut("result")
+        .map(Value::take)
+        .unwrap_or(Value::Null))
+}
+
+/// Method of a JSON-RPC notification line (no `id`); `None` for responses
+/// and requests.
+fn notification_method(line: &str) -> Option<String> {
+    let message: Value = serde_json::from_str(line).ok()?;
+    if message.get("id").is_some() {
+        return None;
+    }
+    message
+        .get("method")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
 /// Read a JSON response from the channel (fed by the background reader thread).
 /// Uses recv_timeout (per-line) and a total wall-clock cap to ensure this never blocks forever.
-fn read_response_channel(proc: &mut MCPProcess) -> Result<String, String> {
+/// Notifications arriving before the response are collected in `notifications`.
+fn read_response_channel(
+    proc: &mut MCPProcess,
+    max_len: usize,
+    notifications: &mut Vec<String>,
+) -> Result<String, CommandError> {
     let start = std::time::Instant::now();
     let mut iterations = 0;
     loop {
         iterations += 1;
         if iterations > MAX_READ_ITERATIONS {
-            return Err("MCP response exceeded iteration limit".to_string());
+            return Err(CommandError::new(
+                ErrorCode::McpProtocolError,
+                "MCP response exceeded iteration limit",
+            ));
         }
 
         // Total wall-clock cap: prevents indefinite wait when the server outputs many
         // non-JSON lines (e.g. progress logs), each of which would otherwise reset READ_LINE_TIMEOUT
         if start.elapsed() > TOTAL_RESPONSE_TIMEOUT {
-            return Err("MCP response timeout".to_string());
+            return Err(response_timeout());
         }
 
         match proc.line_rx.recv_timeout(READ_LINE_TIMEOUT) {
             Ok(ReadResult::Line(line)) => {
-                if line.len() > MAX_LINE_LENGTH {
-                    return Err("MCP response line exceeded size limit".to_string());
+                if line.len() > max_len {
+                    return Err(CommandError::new(
+                        ErrorCode::McpProtocolError,
+                        "MCP response line exceeded size limit",
+                    ));
                 }
 
                 let trimmed = line.trim();
@@ -448,30 +663,25 @@ fn read_response_channel(proc: &mut MCPProcess) -> Result<String, String> {
                 }
 
                 if trimmed.starts_with('{') {
+                    if let Some(method) = notification_method(trimmed) {
+                        notifications.push(method);
+                        continue;
+                    }
                     return Ok(trimmed.to_string());
                 }
             }
-            Ok(ReadResult::Eof) => {
+            Ok(ReadResult::Eof) | Err(RecvTimeoutError::Disconnected) => {
                 let stderr_msg = try_read_stderr(&mut proc.stderr);
-                return if stderr_msg.is_empty() {
-                    Err("MCP server process ended unexpectedly".to_string())
-                } else {
-                    Err(format!("MCP server error: {}", stderr_msg))
-                };
+                return Err(with_stderr(server_exited(), stderr_msg));
             }
             Ok(ReadResult::Error(e)) => {
-                return Err(format!("Failed to read from MCP server: {}", e));
+                return Err(CommandError::new(
+                    ErrorCode::McpServerExited,
+                    format!("Failed to read from MCP server: {}", e),
+                ));
             }
             Err(RecvTimeoutError::Timeout) => {
-                return Err("MCP response timeout".to_string());
-            }
-            Err(RecvTimeoutError::Disconnected) => {
-                let stderr_msg = try_read_stderr(&mut proc.stderr);
-                return if stderr_msg.is_empty() {
-                    Err("MCP server process ended unexpectedly".to_string())
-                } else {
-                    Err(format!("MCP server error: {}", stderr_msg))
-                };
+                return Err(response_timeout());
             }
         }
     }
@@ -483,24 +693,18 @@ pub fn mcp_send_notification(
     state: State<'_, MCPProcessManager>,
     server_id: String,
     notification: String,
-) -> Result<(), String> {
-    let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
+) -> Result<(), CommandError> {
+    let mut processes = state.processes.lock().map_err(|_| lock_failed())?;
 
-    let proc = processes
-        .get_mut(&server_id)
-        .ok_or("MCP server not connected")?;
+    let proc = processes.get_mut(&server_id).ok_or_else(not_connected)?;
 
     if let Err(_) = writeln!(proc.stdin, "{}", notification) {
         let stderr_msg = try_read_stderr(&mut proc.stderr);
-        return if stderr_msg.is_empty() {
-            Err("Failed to write to MCP server (process may have exited)".to_string())
-        } else {
-            Err(format!("MCP server error: {}", stderr_msg))
-        };
+        return Err(with_stderr(write_failed(), stderr_msg));
     }
-    proc.stdin
-        .flush()
-        .map_err(|_| "Failed to flush MCP server stdin".to_string())?;
+    proc.stdin.flush().map_err(|_| {
+        CommandError::new(ErrorCode::McpWriteFailed, "Failed to flush MCP server stdin")
+    })?;
 
     Ok(())
 }
@@ -514,7 +718,7 @@ pub fn mcp_send_notification(
 pub fn mcp_disconnect(
     state: State<'_, MCPProcessManager>,
     server_id: String,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     // Gracefully terminate the process group (SIGTERM → wait → SIGKILL)
     if let Ok(mut pids) = state.pids.lock() {
         if let Some(pid) = pids.remove(&server_id) {
@@ -530,17 +734,88 @@ pub fn mcp_disconnect(
             drop(proc);
         }
     }
+    state.invalidate_lists(&server_id, None);
 
     Ok(())
 }
 
+/// All pages of a stdio server's `prompts/list` or `resources/list`.
+///
+/// Served from the cache until the server sends the matching `list_changed`
+/// notification, `LIST_CACHE_TTL` passes, or the server reconnects.
+#[tauri::command]
+pub async fn mcp_get_cached(
+    state: State<'_, MCPProcessManager>,
+    server_id: String,
+    kind: McpListKind,
+) -> Result<Value, CommandError> {
+    if let Some(items) = state.cached_list(&server_id, kind) {
+        return Ok(items);
+    }
+    let mut items = Vec::new();
+    let mut cursor: Option<String> = None;
+    for _ in 0..MAX_LIST_PAGES {
+        let params = match &cursor {
+            Some(cursor) => json!({ "cursor": cursor }),
+            None => json!({}),
+        };
+        let mut result = call(&state, &server_id, kind.method(), params, MAX_LINE_LENGTH).await?;
+        if let Some(Value::Array(page)) = result.get_mut(kind.field()).map(Value::take) {
+            items.extend(page);
+        }
+        cursor = result
+            .get("nextCursor")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        if cursor.is_none() {
+            break;
+        }
+    }
+    let items = Value::Array(items);
+    state.store_list(&server_id, kind, items.clone());
+    Ok(items)
+}
+
+/// Read a resource of a stdio server and return its `contents`.
+///
+/// Goes through the same per-line and total timeouts as other requests; the
+/// response may be up to `MAX_RESOURCE_LENGTH` long.
+#[tauri::command]
+pub async fn mcp_read_resource(
+    state: State<'_, MCPProcessManager>,
+    server_id: String,
+    uri: String,
+) -> Result<Value, CommandError> {
+    if uri.trim().is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Resource URI must not be empty",
+        ));
+    }
+    let mut result = call(
+        &state,
+        &server_id,
+        "resources/read",
+        json!({ "uri": uri }),
+        MAX_RESOURCE_LENGTH,
+    )
+    .await?;
+    Ok(result
+        .get_mut("contents")
+        .map(Value::take)
+        .unwrap_or_else(|| Value::Array(Vec::new())))
+}
+
 /// Check if an external command exists and return its --version output
 #[tauri::command]
-pub fn check_command_exists(command: String) -> Result<String, String> {
+pub fn check_command_exists(command: String) -> Result<String, CommandError> {
     #[cfg(target_os = "ios")]
     {
         let _ = &command;
-        return Err("Command execution is not available on iPad".to_string());
+        return Err(CommandError::new(
+            ErrorCode::McpUnsupported,
+            "Command execution is not available on iPad",
+        ));
     }
 
     #[cfg(not(target_os = "ios"))]
@@ -553,7 +828,12 @@ pub fn check_command_exists(command: String) -> Result<String, String> {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
-            .map_err(|_| format!("Command '{}' not found", command))?;
+            .map_err(|_| {
+                CommandError::new(
+                    ErrorCode::CommandNotFound,
+                    format!("Command '{}' not found", command),
+                )
+            })?;
 
         let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
         Ok(if !stdout.is_empty() {
@@ -563,3 +843,108 @@ pub fn check_command_exists(command: String) -> Result<String, String> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_reject_non_plain_commands_with_code() {
+        assert!(validate_command("npx").is_ok());
+        assert_eq!(
+            validate_command("").unwrap_err().code,
+            ErrorCode::McpInvalidCommand
+        );
+        assert_eq!(
+            validate_command("/bin/sh").unwrap_err().code,
+            ErrorCode::McpInvalidCommand
+        );
+        assert_eq!(
+            validate_command("npx; rm -rf ~").unwrap_err().code,
+            ErrorCode::McpInvalidCommand
+        );
+    }
+
+    #[test]
+    fn should_keep_code_when_adding_stderr() {
+        let e = with_stderr(server_exited(), String::new());
+        assert_eq!(e, server_exited());
+        let e = with_stderr(server_exited(), "module not found".to_string());
+        assert_eq!(e.code, ErrorCode::McpServerExited);
+        assert_eq!(e.message, "MCP server error: module not found");
+    }
+
+    #[test]
+    fn should_pick_out_notifications() {
+        assert_eq!(
+            notification_method(
+                r#"{"jsonrpc":"2.0","method":"notifications/resources/list_changed"}"#
+            )
+            .as_deref(),
+            Some("notifications/resources/list_changed")
+        );
+        assert_eq!(
+            notification_method(r#"{"jsonrpc":"2.0","id":1,"result":{}}"#),
+            None
+        );
+        assert_eq!(
+            notification_method(r#"{"jsonrpc":"2.0","id":"a","method":"ping"}"#),
+            None
+        );
+        assert_eq!(
+            McpListKind::from_list_changed("notifications/prompts/list_changed"),
+            Some(McpListKind::Prompts)
+        );
+        assert_eq!(
+            McpListKind::from_list_changed("notifications/tools/list_changed"),
+            None
+        );
+    }
+
+    #[test]
+    fn should_invalidate_cached_lists_per_server_and_kind() {
+        let manager = MCPProcessManager::new();
+        manager.store_list("a", McpListKind::Prompts, json!([1]));
+        manager.store_list("a", McpListKind::Resources, json!([2]));
+        manager.store_list("b", McpListKind::Prompts, json!([3]));
+
+        manager.invalidate_lists("a", Some(McpListKind::Prompts));
+        assert_eq!(manager.cached_list("a", McpListKind::Prompts), None);
+        assert_eq!(
+            manager.cached_list("a", McpListKind::Resources),
+            Some(json!([2]))
+        );
+
+        manager.invalidate_lists("a", None);
+        assert_eq!(manager.cached_list("a", McpListKind::Resources), None);
+        assert_eq!(
+            manager.cached_list("b", McpListKind::Prompts),
+            Some(json!([3]))
+        );
+    }
+
+    #[test]
+    fn should_surface_json_rpc_errors() {
+        assert_eq!(
+            rpc_result(r#"{"jsonrpc":"2.0","id":1,"result":{"contents":[]}}"#).unwrap(),
+            json!({ "contents": [] })
+        );
+        let e = rpc_result(
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32002,"message":"Resource not found"}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(e.code, ErrorCode::McpProtocolError);
+        assert_eq!(e.message, "MCP server error: Resource not found");
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn should_report_missing_command() {
+        assert_eq!(
+            check_command_exists("moraya-no-such-command".to_string())
+                .unwrap_err()
+                .code,
+            ErrorCode::CommandNotFound
+        );
+    }
+}
@@ -1,19 +1,65 @@
 pub mod ai_proxy;
+pub mod ai_usage;
+pub mod app_log;
+pub mod assets;
+pub mod backlinks;
+pub mod backup;
+pub mod clipboard;
+pub mod clipper;
+pub mod cloud_files;
+pub mod crash_report;
+pub mod daily_note;
+pub mod diagram;
+pub mod dir_scan;
+pub mod docx_export;
+pub mod drafts;
+pub mod encryption;
+pub mod epub_export;
+pub mod error;
+pub mod export_preset;
 pub mod file;
+pub mod file_tags;
+pub mod footnotes;
+pub mod frontmatter;
 pub mod git;
+pub mod git_publish;
+pub mod html_assets;
+pub mod html_markdown;
+pub mod image_capture;
 pub mod image_hosting_picora;
+pub mod image_transform;
+pub mod invisible_chars;
 pub mod kb;
 pub mod picora_account;
 pub mod picora_media;
 pub mod kb_sync;
 pub mod keychain;
+pub mod link_metadata;
+pub mod links;
+#[cfg(target_os = "macos")]
+mod macos_keychain;
 pub mod macos_system_audio;
+pub mod markdown_format;
 pub mod mcp;
 pub mod object_storage;
 pub mod pdf_export;
+pub mod rewrap;
 pub mod plugin_manager;
+pub mod search;
+pub mod search_index;
+pub mod settings;
 pub mod speech_proxy;
+pub mod speech_transcript;
+pub mod tags;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod title_sync;
+pub mod tts_proxy;
+pub mod undo_delete;
 pub mod update;
+pub mod user_presence;
+pub mod window_access;
+pub mod workspace_watch;
 
 #[cfg(feature = "diagnostics")]
 pub mod keychain_diagnostics;
@@ -9,13 +9,20 @@
 //!
 //! The frontend orchestrator falls back to the v0.59.x canvas-based path when
 //! this command returns Err, so any failure here is recoverable.
+//!
+//! `export_pdf` is the WebView-free path: markdown is parsed, laid out and
+//! written to PDF entirely in Rust (`document` → `typeset` → `writer`), with
+//! system fonts embedded and header/footer templates applied per page.
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::ipc::Channel;
 use tauri::{AppHandle, State};
 
+use crate::commands::error::CommandError;
+use crate::commands::export_preset::{Preset, PresetUsage};
 use crate::commands::file as file_cmd;
 
 #[cfg(target_os = "macos")]
@@ -26,6 +33,11 @@ mod subprocess;
 
 pub mod child_mode;
 
+mod document;
+mod fonts;
+mod typeset;
+mod writer;
+
 /// Per-job ready signal. Frontend's /print route calls `export_print_ready`
 /// after rendering completes; that handler resolves the matching oneshot so
 /// the native printToPDF call can proceed.
@@ -181,6 +193,15 @@ pub enum ProgressEvent {
     Error { message: String },
 }
 
+/// Merge the document's `moraya.export` frontmatter over `options` and add
+/// the table of contents it may ask for.
+fn apply_preset(markdown: String, options: &mut PdfExportOptions) -> (String, PresetUsage) {
+    let mut preset = Preset::read(&markdown);
+    preset.apply_pdf(options);
+    let markdown = preset.apply_toc(markdown);
+    (markdown, preset.finish("PDF"))
+}
+
 /// Top-level export command. Dispatches to macOS direct path or subprocess
 /// path based on target_os. Returns Err if native path fails — caller handles
 /// fallback to canvas path on the frontend.
@@ -188,20 +209,24 @@ pub enum ProgressEvent {
 pub async fn export_pdf_native(
     app: AppHandle,
     state: State<'_, PdfExportState>,
-    job: JobConfig,
+    mut job: JobConfig,
     on_progress: Channel<ProgressEvent>,
-) -> Result<(), String> {
+) -> Result<PresetUsage, String> {
     // Validate output path safety up-front (rejects path traversal, symlinks
     // pointing outside the home dir, etc.).
     let _ = file_cmd::validate_path(&job.output_path)?;
 
     let _ = on_progress.send(ProgressEvent::Preparing);
 
+    let (markdown, usage) = apply_preset(std::mem::take(&mut job.markdown), &mut job.options);
+    job.markdown = markdown;
+
     #[cfg(target_os = "macos")]
     {
         let _ = state; // unused on macOS path
         return macos::run(app, &job, &on_progress)
             .await
+            .map(|()| usage)
             .map_err(|e| {
                 let _ = on_progress.send(ProgressEvent::Fallback {
                     reason: e.clone(),
@@ -213,7 +238,7 @@ pub async fn export_pdf_native(
     #[cfg(any(target_os = "windows", target_os = "linux"))]
     {
         let _ = (app, state);
-        return subprocess::run(&job, &on_progress).await.map_err(|e| {
+        return subprocess::run(&job, &on_progress).await.map(|()| usage).map_err(|e| {
             let _ = on_progress.send(ProgressEvent::Fallback {
                 reason: e.clone(),
             });
@@ -223,7 +248,7 @@ pub async fn export_pdf_native(
 
     #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
     {
-        let _ = (app, state, job);
+        let _ = (app, state, job, usage);
         let _ = on_progress.send(ProgressEvent::Fallback {
             reason: "unsupported platform".to_string(),
         });
@@ -231,6 +256,81 @@ pub async fn export_pdf_native(
     }
 }
 
+/// Markdown to export: a file on disk, or editor content plus the folder
+/// its relative image paths resolve against.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkdownSource {
+    Path(String),
+    Content {
+        markdown: String,
+        #[serde(default)]
+        base_dir: Option<String>,
+    },
+}
+
+impl MarkdownSource {
+    /// The markdown text and the (validated) folder relative references
+    /// resolve against.
+    pub(crate) fn load(self) -> Result<(String, Option<PathBuf>), CommandError> {
+        match self {
+            MarkdownSource::Path(path) => {
+                let path = file_cmd::validate_path(&path)?;
+                let markdown =
+                    std::fs::read_to_string(&path).map_err(file_cmd::sanitize_io_error)?;
+                Ok((markdown, path.parent().map(Path::to_path_buf)))
+            }
+            MarkdownSource::Content { markdown, base_dir } => {
+                let base_dir = base_dir
+                    .filter(|d| !d.is_empty())
+                    .map(|d| file_cmd::validate_path(&d))
+                    .transpose()?;
+                Ok((markdown, base_dir))
+            }
+        }
+    }
+}
+
+/// Typeset markdown to a PDF at `output_path` without a WebView. Progress
+/// streams over `on_progress`; layout runs on a blocking thread.
+#[tauri::command]
+pub async fn export_pdf(
+    source: MarkdownSource,
+    output_path: String,
+    mut options: PdfExportOptions,
+    on_progress: Channel<ProgressEvent>,
+) -> Result<PresetUsage, String> {
+    let output = file_cmd::validate_path(&output_path)?;
+    let _ = on_progress.send(ProgressEvent::Preparing);
+
+    let (markdown, base_dir) = source.load()?;
+    let (markdown, usage) = apply_preset(markdown, &mut options);
+
+    let progress = on_progress.clone();
+    let bytes = tokio::task::spawn_blocking(move || {
+        typeset::render(&markdown, base_dir.as_deref(), &options, |event| {
+            let _ = progress.send(event);
+        })
+    })
+    .await
+    .map_err(|e| format!("PDF export task failed: {}", e))?
+    .map_err(|e| {
+        let _ = on_progress.send(ProgressEvent::Error { message: e.clone() });
+        e
+    })?;
+
+    let _ = on_progress.send(ProgressEvent::Writing);
+    std::fs::write(&output, bytes).map_err(|e| {
+        let message = format!("Failed to write PDF: {}", e);
+        let _ = on_progress.send(ProgressEvent::Error {
+            message: message.clone(),
+        });
+        message
+    })?;
+    let _ = on_progress.send(ProgressEvent::Done);
+    Ok(usage)
+}
+
 /// Called by the /print SvelteKit route once rendering (Mermaid/hljs/images)
 /// has completed. The matching `oneshot` is resolved so the native printToPDF
 /// path can proceed.
@@ -294,6 +394,18 @@ mod tests {
         assert_eq!(parsed.options.paper_size, PaperSize::A4);
     }
 
+    #[test]
+    fn markdown_source_serde() {
+        let s: MarkdownSource = serde_json::from_str(r#"{"path":"/tmp/a.md"}"#).unwrap();
+        assert!(matches!(s, MarkdownSource::Path(p) if p == "/tmp/a.md"));
+        let s: MarkdownSource =
+            serde_json::from_str(r##"{"content":{"markdown":"# Hi"}}"##).unwrap();
+        assert!(matches!(
+            s,
+            MarkdownSource::Content { markdown, base_dir: None } if markdown == "# Hi"
+        ));
+    }
+
     #[test]
     fn progress_event_serde_tag() {
         let ev = ProgressEvent::Paginating {
@@ -0,0 +1,439 @@
+//! Markdown → block tree for the typeset PDF path (`export_pdf`).
+//!
+//! Only the structure the typesetter lays out is kept: headings, paragraphs,
+//! lists, quotes, code, rules, images and tables. Anything else (HTML, math,
+//! front matter) degrades to its text or is dropped.
+
+use pulldown_cmark::{Alignment, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub strike: bool,
+    pub link: bool,
+}
+
+/// Run of inline text in one style. A hard line break is a `"\n"` span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    /// `Some` for task list items.
+    pub checked: Option<bool>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading {
+        level: u8,
+        spans: Vec<Span>,
+    },
+    Paragraph(Vec<Span>),
+    List {
+        /// First number of an ordered list; `None` for bullets.
+        start: Option<u64>,
+        items: Vec<ListItem>,
+    },
+    Quote(Vec<Block>),
+    Code(String),
+    Rule,
+    Image {
+        src: String,
+        alt: String,
+    },
+    Table {
+        align: Vec<Alignment>,
+        /// First row is the header.
+        rows: Vec<Vec<Vec<Span>>>,
+    },
+}
+
+enum Frame {
+    Root(Vec<Block>),
+    Quote(Vec<Block>),
+    List {
+        start: Option<u64>,
+        items: Vec<ListItem>,
+    },
+    Item(ListItem),
+    Table {
+        align: Vec<Alignment>,
+        rows: Vec<Vec<Vec<Span>>>,
+        row: Vec<Vec<Span>>,
+    },
+}
+
+#[derive(Default)]
+struct Builder {
+    stack: Vec<Frame>,
+    spans: Vec<Span>,
+    heading: Option<u8>,
+    bold: u32,
+    italic: u32,
+    strike: u32,
+    link: u32,
+    /// Open block image: (src, alt collected so far).
+    image: Option<(String, String)>,
+    code: Option<String>,
+    in_cell: bool,
+    /// Inside front matter or an HTML block.
+    skip: bool,
+}
+
+pub fn parse(markdown: &str) -> Vec<Block> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut b = Builder {
+        stack: vec![Frame::Root(Vec::new())],
+        ..Default::default()
+    };
+    for event in Parser::new_ext(markdown, options) {
+        b.event(event);
+    }
+    b.flush();
+    match b.stack.into_iter().next() {
+        Some(Frame::Root(blocks)) => blocks,
+        _ => Vec::new(),
+    }
+}
+
+impl Builder {
+    fn style(&self) -> SpanStyle {
+        SpanStyle {
+            bold: self.bold > 0,
+            italic: self.italic > 0,
+            code: false,
+            strike: self.strike > 0,
+            link: self.link > 0,
+        }
+    }
+
+    fn push_span(&mut self, text: &str, style: SpanStyle) {
+        if let Some((_, alt)) = &mut self.image {
+            alt.push_str(text);
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.style == style && last.text != "\n" && text != "\n" => {
+                last.text.push_str(text)
+            }
+            _ => self.spans.push(Span {
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+
+    fn push_block(&mut self, block: Block) {
+        match self.stack.last_mut() {
+            Some(Frame::Root(blocks)) | Some(Frame::Quote(blocks)) => blocks.push(block),
+            Some(Frame::Item(item)) => item.blocks.push(block),
+            _ => {}
+        }
+    }
+
+    /// Turn the pending inline spans into a heading or paragraph.
+    fn flush(&mut self) {
+        if self.in_cell {
+            return;
+        }
+        let spans = trim_spans(std::mem::take(&mut self.spans));
+        if let Some(level) = self.heading {
+            self.push_block(Block::Heading { level, spans });
+        } else if !spans.is_empty() {
+            self.push_block(Block::Paragraph(spans));
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        if self.skip {
+            if matches!(
+                event,
+                Event::End(TagEnd::MetadataBlock(_) | TagEnd::HtmlBlock)
+            ) {
+                self.skip = false;
+            }
+            return;
+        }
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some(code) => code.push_str(&text),
+                None => self.push_span(&text, self.style()),
+            },
+            Event::Code(text) => {
+                let style = SpanStyle {
+                    code: true,
+                    ..self.style()
+                };
+                self.push_span(&text, style);
+            }
+            Event::InlineMath(text) | Event::DisplayMath(text) => {
+                self.push_span(&text, self.style())
+            }
+            Event::SoftBreak => self.push_span(" ", self.style()),
+            Event::HardBreak => self.push_span("\n", self.style()),
+            Event::InlineHtml(html) => {
+                let tag = html.trim().to_ascii_lowercase();
+                if tag == "<br>" || tag == "<br/>" || tag == "<br />" {
+                    self.push_span("\n", self.style());
+                }
+            }
+            Event::FootnoteReference(label) => {
+                self.push_span(&format!("[{}]", label), self.style())
+            }
+            Event::Rule => {
+                self.flush();
+                self.push_block(Block::Rule);
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(Frame::Item(item)) = self.stack.last_mut() {
+                    item.checked = Some(checked);
+                }
+            }
+            Event::Html(_) => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.flush(),
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.heading = Some(heading_level(level));
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.stack.push(Frame::Quote(Vec::new()));
+            }
+            Tag::CodeBlock(_) => {
+                self.flush();
+                self.code = Some(String::new());
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.stack.push(Frame::List {
+                    start,
+                    items: Vec::new(),
+                });
+            }
+            Tag::Item => {
+                self.flush();
+                self.stack.push(Frame::Item(ListItem {
+                    checked: None,
+                    blocks: Vec::new(),
+                }));
+            }
+            Tag::Table(align) => {
+                self.flush();
+                self.stack.push(Frame::Table {
+                    align,
+                    rows: Vec::new(),
+                    row: Vec::new(),
+                });
+            }
+            Tag::TableCell => {
+                self.spans.clear();
+                self.in_cell = true;
+            }
+            Tag::Emphasis => self.italic += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Strikethrough => self.strike += 1,
+            Tag::Link { .. } => self.link += 1,
+            // Images in headings and table cells keep only their alt text
+            Tag::Image { dest_url, .. }
+                if self.heading.is_none() && !self.in_cell && self.image.is_none() =>
+            {
+                self.flush();
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Tag::MetadataBlock(_) | Tag::HtmlBlock => {
+                self.flush();
+                self.skip = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => self.flush(),
+            TagEnd::Heading(_) => {
+                self.flush();
+                self.heading = None;
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                if let Some(Frame::Quote(blocks)) = self.stack.pop() {
+                    self.push_block(Block::Quote(blocks));
+                }
+            }
+            TagEnd::CodeBlock => {
+                if let Some(mut code) = self.code.take() {
+                    if code.ends_with('\n') {
+                        code.pop();
+                    }
+                    self.push_block(Block::Code(code));
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                if let Some(Frame::List { start, items }) = self.stack.pop() {
+                    self.push_block(Block::List { start, items });
+                }
+            }
+            TagEnd::Item => {
+                self.flush();
+                if let Some(Frame::Item(item)) = self.stack.pop() {
+                    if let Some(Frame::List { items, .. }) = self.stack.last_mut() {
+                        items.push(item);
+                    }
+                }
+            }
+            TagEnd::TableCell => {
+                self.in_cell = false;
+                let cell = trim_spans(std::mem::take(&mut self.spans));
+                if let Some(Frame::Table { row, .. }) = self.stack.last_mut() {
+                    row.push(cell);
+                }
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                if let Some(Frame::Table { rows, row, .. }) = self.stack.last_mut() {
+                    rows.push(std::mem::take(row));
+                }
+            }
+            TagEnd::Table => {
+                if let Some(Frame::Table { align, rows, .. }) = self.stack.pop() {
+                    self.push_block(Block::Table { align, rows });
+                }
+            }
+            TagEnd::Emphasis => self.italic = self.italic.saturating_sub(1),
+            TagEnd::Strong => self.bold = self.bold.saturating_sub(1),
+            TagEnd::Strikethrough => self.strike = self.strike.saturating_sub(1),
+            TagEnd::Link => self.link = self.link.saturating_sub(1),
+            TagEnd::Image => {
+                if let Some((src, alt)) = self.image.take() {
+                    self.push_block(Block::Image { src, alt });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// Drop whitespace around a paragraph (left over from soft breaks next to
+/// block images) and the spans that end up empty.
+fn trim_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    if let Some(first) = spans.first_mut() {
+        first.text = first.text.trim_start().to_string();
+    }
+    if let Some(last) = spans.last_mut() {
+        last.text = last.text.trim_end().to_string();
+    }
+    spans.retain(|s| !s.text.is_empty());
+    spans
+}
+
+/// Plain text of a span list, for titles and alt text.
+pub fn plain_text(spans: &[Span]) -> String {
+    spans.iter().map(|s| s.text.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(blocks: &[Block]) -> Vec<String> {
+        blocks
+            .iter()
+            .map(|b| match b {
+                Block::Heading { spans, .. } | Block::Paragraph(spans) => plain_text(spans),
+                Block::Code(code) => code.clone(),
+                Block::Image { src, .. } => src.clone(),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_parse_headings_paragraphs_and_styles() {
+        let blocks = parse("---\ntitle: x\n---\n# Title\n\nSome **bold** and `code`.\nNext line");
+        assert_eq!(text(&blocks), ["Title", "Some bold and code. Next line"]);
+        let Block::Paragraph(spans) = &blocks[1] else {
+            panic!("expected paragraph");
+        };
+        assert!(spans[1].style.bold);
+        assert!(spans[3].style.code);
+    }
+
+    #[test]
+    fn should_split_paragraph_around_images() {
+        let blocks = parse("Before ![alt **x**](img/a.png) after");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(vec![Span {
+                    text: "Before".into(),
+                    style: SpanStyle::default()
+                }]),
+                Block::Image {
+                    src: "img/a.png".into(),
+                    alt: "alt x".into()
+                },
+                Block::Paragraph(vec![Span {
+                    text: "after".into(),
+                    style: SpanStyle::default()
+                }]),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_nest_lists_quotes_and_tasks() {
+        let blocks = parse("3. one\n   - [x] done\n4. two\n\n> quoted\n\n```rs\nfn main() {}\n```");
+        let Block::List { start, items } = &blocks[0] else {
+            panic!("expected list");
+        };
+        assert_eq!(*start, Some(3));
+        assert_eq!(items.len(), 2);
+        let Block::List { items: inner, .. } = &items[0].blocks[1] else {
+            panic!("expected nested list");
+        };
+        assert_eq!(inner[0].checked, Some(true));
+        assert!(matches!(&blocks[1], Block::Quote(inner) if text(inner) == ["quoted"]));
+        assert_eq!(blocks[2], Block::Code("fn main() {}".into()));
+    }
+
+    #[test]
+    fn should_collect_table_rows() {
+        let blocks = parse("| A | B |\n|:--|--:|\n| 1 | ![i](x.png) |");
+        let Block::Table { align, rows } = &blocks[0] else {
+            panic!("expected table");
+        };
+        assert_eq!(align, &[Alignment::Left, Alignment::Right]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(plain_text(&rows[0][1]), "B");
+        assert_eq!(plain_text(&rows[1][1]), "i");
+    }
+}
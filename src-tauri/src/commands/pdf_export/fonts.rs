@@ -0,0 +1,319 @@
+//! Font lookup for the typeset PDF path. Faces come from the system font
+//! database; characters the body face lacks (CJK, mostly) fall back to the
+//! first installed CJK family. Bold/italic are synthesized by the writer
+//! when a family has no such face.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use fontdb::{Database, Family, Query, Style, Weight, ID};
+
+const SANS_FAMILIES: &[&str] = &[
+    "Helvetica Neue",
+    "Helvetica",
+    "Arial",
+    "Segoe UI",
+    "Noto Sans",
+    "DejaVu Sans",
+    "Liberation Sans",
+];
+
+const MONO_FAMILIES: &[&str] = &[
+    "SF Mono",
+    "Menlo",
+    "Consolas",
+    "Cascadia Mono",
+    "Noto Sans Mono",
+    "DejaVu Sans Mono",
+    "Liberation Mono",
+];
+
+const CJK_FAMILIES: &[&str] = &[
+    "PingFang SC",
+    "Hiragino Sans GB",
+    "Hiragino Sans",
+    "Microsoft YaHei",
+    "Yu Gothic",
+    "Malgun Gothic",
+    "SimHei",
+    "Noto Sans CJK SC",
+    "Source Han Sans SC",
+    "WenQuanYi Micro Hei",
+    "Droid Sans Fallback",
+    "Arial Unicode MS",
+];
+
+/// CSS generic names that may appear in the `font_family` setting but
+/// don't name an installed family.
+const GENERIC_FAMILIES: &[&str] = &[
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "ui-sans-serif",
+    "ui-serif",
+    "ui-monospace",
+    "-apple-system",
+    "blinkmacsystemfont",
+];
+
+static DATABASE: OnceLock<Database> = OnceLock::new();
+
+fn database() -> &'static Database {
+    DATABASE.get_or_init(|| {
+        let mut db = Database::new();
+        db.load_system_fonts();
+        db
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FontStyle {
+    pub mono: bool,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+/// A face plus the styling the writer has to fake because the face lacks it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub face: usize,
+    pub fake_bold: bool,
+    pub fake_italic: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub slot: Slot,
+    pub id: u16,
+    /// In ems.
+    pub advance: f32,
+}
+
+/// A loaded face and the glyphs the document uses from it.
+pub struct Face {
+    pub data: Vec<u8>,
+    /// Face index within a collection (`.ttc`).
+    pub index: u32,
+    pub name: String,
+    pub units_per_em: f32,
+    pub ascender: f32,
+    pub descender: f32,
+    pub cap_height: f32,
+    pub bbox: [f32; 4],
+    pub italic_angle: f32,
+    pub is_cff: bool,
+    pub is_monospaced: bool,
+    pub is_italic: bool,
+    cmap: HashMap<char, Option<u16>>,
+    advances: HashMap<u16, f32>,
+    /// Used glyph → the text it stands for (for the ToUnicode map).
+    pub used: BTreeMap<u16, char>,
+}
+
+impl Face {
+    fn load(db: &Database, id: ID) -> Option<Self> {
+        let (data, index) = db.with_face_data(id, |data, index| (data.to_vec(), index))?;
+        let face = ttf_parser::Face::parse(&data, index).ok()?;
+        let bbox = face.global_bounding_box();
+        let name: String = db
+            .face(id)
+            .map(|info| info.post_script_name.as_str())
+            .filter(|n| !n.is_empty())
+            .map_or_else(|| format!("Font{}", index), str::to_string)
+            .chars()
+            .filter(|c| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(*c))
+            .collect();
+        let units_per_em = face.units_per_em() as f32;
+        let ascender = face.ascender() as f32;
+        let descender = face.descender() as f32;
+        let cap_height = face.capital_height().unwrap_or(face.ascender()) as f32;
+        let italic_angle = face.italic_angle();
+        let is_cff = face.tables().cff.is_some() || face.tables().cff2.is_some();
+        let is_monospaced = face.is_monospaced();
+        let is_italic = face.is_italic();
+        Some(Self {
+            data,
+            index,
+            name,
+            units_per_em,
+            ascender,
+            descender,
+            cap_height,
+            bbox: [
+                bbox.x_min as f32,
+                bbox.y_min as f32,
+                bbox.x_max as f32,
+                bbox.y_max as f32,
+            ],
+            italic_angle,
+            is_cff,
+            is_monospaced,
+            is_italic,
+            cmap: HashMap::new(),
+            advances: HashMap::new(),
+            used: BTreeMap::new(),
+        })
+    }
+
+    fn glyph(&mut self, c: char) -> Option<u16> {
+        if let Some(id) = self.cmap.get(&c) {
+            return *id;
+        }
+        let face = ttf_parser::Face::parse(&self.data, self.index).ok()?;
+        let id = face.glyph_index(c).map(|g| g.0).filter(|&g| g != 0);
+        if let Some(g) = id {
+            let advance = face.glyph_hor_advance(ttf_parser::GlyphId(g)).unwrap_or(0);
+            self.advances.insert(g, advance as f32 / self.units_per_em);
+        }
+        self.cmap.insert(c, id);
+        id
+    }
+
+    /// Advance of a glyph the document uses, in 1/1000 em (PDF widths).
+    pub fn width(&self, id: u16) -> f32 {
+        self.advances.get(&id).copied().unwrap_or(0.0) * 1000.0
+    }
+
+    /// Scale a font-unit metric to 1/1000 em.
+    pub fn to_pdf_units(&self, v: f32) -> f32 {
+        v * 1000.0 / self.units_per_em
+    }
+}
+
+pub struct Fonts {
+    pub faces: Vec<Face>,
+    by_id: HashMap<ID, usize>,
+    preferred: Vec<String>,
+    chains: HashMap<FontStyle, Vec<Slot>>,
+}
+
+impl Fonts {
+    /// `font_family` is the CSS-style list from the export settings; its
+    /// installed entries are tried before the built-in sans families.
+    pub fn new(font_family: &str) -> Result<Self, String> {
+        let preferred = font_family
+            .split(',')
+            .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+            .filter(|f| !f.is_empty() && !GENERIC_FAMILIES.contains(&f.to_lowercase().as_str()))
+            .collect();
+        let mut fonts = Self {
+            faces: Vec::new(),
+            by_id: HashMap::new(),
+            preferred,
+            chains: HashMap::new(),
+        };
+        if fonts.chain(FontStyle::default()).is_empty() {
+            return Err("No usable font found for PDF export".to_string());
+        }
+        Ok(fonts)
+    }
+
+    #[cfg(test)]
+    pub fn empty() -> Self {
+        Self {
+            faces: Vec::new(),
+            by_id: HashMap::new(),
+            preferred: Vec::new(),
+            chains: HashMap::new(),
+        }
+    }
+
+    /// Faces tried in order for a style: the body (or mono) face, then CJK.
+    fn chain(&mut self, style: FontStyle) -> Vec<Slot> {
+        if let Some(chain) = self.chains.get(&style) {
+            return chain.clone();
+        }
+        let db = database();
+        let mut families: Vec<&str> = Vec::new();
+        if style.mono {
+            families.extend(MONO_FAMILIES);
+        } else {
+            families.extend(self.preferred.iter().map(String::as_str));
+            families.extend(SANS_FAMILIES);
+        }
+        let primary = find_face(db, &families, style);
+        let cjk = find_face(db, CJK_FAMILIES, style);
+        let mut chain = Vec::new();
+        for id in [primary, cjk].into_iter().flatten() {
+            if let Some(slot) = self.slot(db, id, style) {
+                if !chain.contains(&slot) {
+                    chain.push(slot);
+                }
+            }
+        }
+        if chain.is_empty() && style != FontStyle::default() {
+            // e.g. no monospace family at all: reuse the body face
+            chain = self.chain(FontStyle::default());
+        }
+        self.chains.insert(style, chain.clone());
+        chain
+    }
+
+    fn slot(&mut self, db: &Database, id: ID, style: FontStyle) -> Option<Slot> {
+        let info = db.face(id)?;
+        let face = match self.by_id.get(&id) {
+            Some(&i) => i,
+            None => {
+                let face = Face::load(db, id)?;
+                self.faces.push(face);
+                self.by_id.insert(id, self.faces.len() - 1);
+                self.faces.len() - 1
+            }
+        };
+        Some(Slot {
+            face,
+            fake_bold: style.bold && info.weight < Weight::SEMIBOLD,
+            fake_italic: style.italic && info.style == Style::Normal,
+        })
+    }
+
+    /// Map text to glyphs, one per char, falling back along the style's
+    /// face chain. Characters no face has render as the body face's .notdef.
+    pub fn shape(&mut self, text: &str, style: FontStyle) -> Vec<Glyph> {
+        let chain = self.chain(style);
+        let mut glyphs = Vec::with_capacity(text.len());
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let found = chain.iter().find_map(|slot| {
+                let face = &mut self.faces[slot.face];
+                face.glyph(c).map(|id| (*slot, id))
+            });
+            let Some((slot, id)) = found.or_else(|| chain.first().map(|s| (*s, 0))) else {
+                continue;
+            };
+            let face = &mut self.faces[slot.face];
+            if id != 0 {
+                face.used.entry(id).or_insert(c);
+            }
+            glyphs.push(Glyph {
+                slot,
+                id,
+                advance: face.width(id) / 1000.0,
+            });
+        }
+        glyphs
+    }
+}
+
+fn find_face(db: &Database, families: &[&str], style: FontStyle) -> Option<ID> {
+    let query_style = if style.italic {
+        Style::Italic
+    } else {
+        Style::Normal
+    };
+    let weight = if style.bold {
+        Weight::BOLD
+    } else {
+        Weight::NORMAL
+    };
+    families.iter().find_map(|name| {
+        db.query(&Query {
+            families: &[Family::Name(name)],
+            weight,
+            style: query_style,
+            ..Default::default()
+        })
+    })
+}
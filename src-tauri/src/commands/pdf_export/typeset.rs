@@ -0,0 +1,1036 @@
+//! Layout for the typeset PDF path: block tree → glyph runs, rules and
+//! images positioned on fixed-size pages, plus header/footer lines.
+//!
+//! Coordinates are points from the top-left of the page; text `y` is the
+//! baseline. Line breaking follows UAX #14, so CJK text wraps between
+//! characters without spaces.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use pulldown_cmark::Alignment;
+
+use super::document::{self, Block, ListItem, Span, SpanStyle};
+use super::fonts::{FontStyle, Fonts, Slot};
+use super::{writer, Orientation, PdfExportOptions, ProgressEvent};
+use crate::commands::file as file_cmd;
+
+const PT_PER_MM: f32 = 72.0 / 25.4;
+/// Images are sized at CSS 96 dpi, like in the editor.
+const PT_PER_PX: f32 = 0.75;
+/// Longest image edge embedded, in pixels; larger images are downscaled.
+const MAX_IMAGE_PX: u32 = 2400;
+const LINE_HEIGHT: f32 = 1.5;
+const HEADING_SCALE: [f32; 6] = [1.9, 1.5, 1.25, 1.1, 1.0, 0.9];
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+
+pub type Color = [f32; 3];
+
+const TEXT: Color = [0.13, 0.13, 0.13];
+const MUTED: Color = [0.42, 0.42, 0.42];
+const LINK: Color = [0.05, 0.4, 0.8];
+const RULE: Color = [0.82, 0.82, 0.82];
+const CODE_BG: Color = [0.95, 0.95, 0.96];
+const TABLE_HEAD_BG: Color = [0.94, 0.94, 0.94];
+const WHITE: Color = [1.0, 1.0, 1.0];
+
+pub enum Item {
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        color: Color,
+        slot: Slot,
+        glyphs: Vec<u16>,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: Color,
+    },
+    Line {
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        color: Color,
+    },
+    Image {
+        index: usize,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    },
+}
+
+#[derive(Default)]
+pub struct Page {
+    pub items: Vec<Item>,
+}
+
+/// Decoded image, ready to embed as 8-bit RGB plus optional alpha.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+    pub alpha: Option<Vec<u8>>,
+    /// Size on the page before fitting to the content box, in points.
+    display: (f32, f32),
+}
+
+/// Images referenced by the document. Local files resolve against the
+/// document's folder and go through the same access rules as file commands;
+/// remote URLs aren't fetched.
+pub struct Images {
+    base_dir: Option<PathBuf>,
+    pub loaded: Vec<Image>,
+    by_src: HashMap<String, Option<usize>>,
+}
+
+impl Images {
+    pub fn new(base_dir: Option<&Path>) -> Self {
+        Self {
+            base_dir: base_dir.map(Path::to_path_buf),
+            loaded: Vec::new(),
+            by_src: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, src: &str) -> Option<usize> {
+        if let Some(index) = self.by_src.get(src) {
+            return *index;
+        }
+        let index = image_bytes(src, self.base_dir.as_deref())
+            .and_then(|bytes| decode_image(&bytes))
+            .map(|image| {
+                self.loaded.push(image);
+                self.loaded.len() - 1
+            });
+        if index.is_none() {
+            log::warn!("PDF export: skipping image {}", src);
+        }
+        self.by_src.insert(src.to_string(), index);
+        index
+    }
+}
+
+fn image_bytes(src: &str, base_dir: Option<&Path>) -> Option<Vec<u8>> {
+    if let Some(data) = src.trim().strip_prefix("data:") {
+        let (meta, payload) = data.split_once(',')?;
+        if !meta.ends_with(";base64") {
+            return None;
+        }
+        return base64::engine::general_purpose::STANDARD
+            .decode(payload.trim())
+            .ok();
+    }
+    let path = file_cmd::resolve_document_reference(src, base_dir)?;
+    let path = file_cmd::validate_path(&path.to_string_lossy()).ok()?;
+    std::fs::read(path).ok()
+}
+
+fn decode_image(bytes: &[u8]) -> Option<Image> {
+    let image = image::load_from_memory(bytes).ok()?;
+    let display = (
+        image.width() as f32 * PT_PER_PX,
+        image.height() as f32 * PT_PER_PX,
+    );
+    let image = if image.width().max(image.height()) > MAX_IMAGE_PX {
+        image.resize(
+            MAX_IMAGE_PX,
+            MAX_IMAGE_PX,
+            image::imageops::FilterType::Triangle,
+        )
+    } else {
+        image
+    };
+    let rgba = image.to_rgba8();
+    let has_alpha = rgba.pixels().any(|p| p[3] < 255);
+    let mut rgb = Vec::with_capacity(rgba.len() / 4 * 3);
+    let mut alpha = Vec::new();
+    for p in rgba.pixels() {
+        rgb.extend_from_slice(&p.0[..3]);
+        if has_alpha {
+            alpha.push(p[3]);
+        }
+    }
+    Some(Image {
+        width: rgba.width(),
+        height: rgba.height(),
+        rgb,
+        alpha: has_alpha.then_some(alpha),
+        display,
+    })
+}
+
+/// Render markdown to PDF bytes. `base_dir` is where relative image paths
+/// resolve; `progress` receives `Rendering` and per-page `Paginating`.
+pub fn render(
+    markdown: &str,
+    base_dir: Option<&Path>,
+    options: &PdfExportOptions,
+    mut progress: impl FnMut(ProgressEvent),
+) -> Result<Vec<u8>, String> {
+    progress(ProgressEvent::Rendering);
+    let blocks = document::parse(markdown);
+    let title = if options.document_title.trim().is_empty() {
+        blocks
+            .iter()
+            .find_map(|b| match b {
+                Block::Heading { spans, .. } => Some(document::plain_text(spans)),
+                _ => None,
+            })
+            .unwrap_or_default()
+    } else {
+        options.document_title.trim().to_string()
+    };
+
+    let mut fonts = Fonts::new(&options.font_family)?;
+    let mut images = Images::new(base_dir);
+    let (pages, page_size) = {
+        let mut ts = Typesetter::new(&mut fonts, &mut images, options)?;
+        let (x, width) = (ts.left, ts.right - ts.left);
+        ts.blocks(&blocks, x, width, TEXT, ts.size * 0.7);
+        ts.decorate(options, &title);
+        (ts.pages, (ts.page_width, ts.page_height))
+    };
+    writer::write(
+        &pages,
+        page_size,
+        &fonts,
+        &images.loaded,
+        &title,
+        |current, total| progress(ProgressEvent::Paginating { current, total }),
+    )
+}
+
+/// Replace `{page}`, `{total}` and `{title}` in a header/footer template.
+fn expand_template(template: &str, page: usize, total: usize, title: &str) -> String {
+    template
+        .replace("{page}", &page.to_string())
+        .replace("{total}", &total.to_string())
+        .replace("{title}", title)
+}
+
+/// Greedy line breaking over `(width, trailing glue, forced break)` items.
+/// Glue only counts between items on the same line.
+fn break_lines(items: &[(f32, f32, bool)], max: f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut width = 0.0;
+    for (i, &(w, _, forced)) in items.iter().enumerate() {
+        if i > start {
+            let glue = items[i - 1].1;
+            if width + glue + w > max {
+                lines.push(start..i);
+                start = i;
+                width = 0.0;
+            } else {
+                width += glue;
+            }
+        }
+        width += w;
+        if forced {
+            lines.push(start..i + 1);
+            start = i + 1;
+            width = 0.0;
+        }
+    }
+    if start < items.len() {
+        lines.push(start..items.len());
+    }
+    lines
+}
+
+#[derive(Clone, Copy)]
+struct InlineStyle {
+    size: f32,
+    bold: bool,
+    mono: bool,
+    color: Color,
+}
+
+/// Glyphs of one slot and style, laid out left to right.
+struct Run {
+    slot: Slot,
+    size: f32,
+    color: Color,
+    style: SpanStyle,
+    glyphs: Vec<u16>,
+    width: f32,
+}
+
+/// Shaped text between two line-break opportunities.
+struct Atom {
+    runs: Vec<Run>,
+    width: f32,
+    /// Width of the trailing spaces, dropped at the end of a line.
+    glue: f32,
+    forced: bool,
+}
+
+struct Line {
+    atoms: Vec<Atom>,
+    width: f32,
+    size: f32,
+}
+
+impl Line {
+    fn height(&self) -> f32 {
+        self.size * LINE_HEIGHT
+    }
+}
+
+/// Position on the flow, to draw backgrounds and bars under content that
+/// may have crossed page breaks since.
+struct Mark {
+    page: usize,
+    y: f32,
+    item: usize,
+}
+
+struct Typesetter<'a> {
+    fonts: &'a mut Fonts,
+    images: &'a mut Images,
+    size: f32,
+    page_width: f32,
+    page_height: f32,
+    left: f32,
+    right: f32,
+    top: f32,
+    bottom: f32,
+    pages: Vec<Page>,
+    y: f32,
+    list_depth: usize,
+}
+
+impl<'a> Typesetter<'a> {
+    fn new(
+        fonts: &'a mut Fonts,
+        images: &'a mut Images,
+        options: &PdfExportOptions,
+    ) -> Result<Self, String> {
+        let (w, h) = options.paper_size.dimensions_mm();
+        let (w, h) = match options.orientation {
+            Orientation::Portrait => (w, h),
+            Orientation::Landscape => (h, w),
+        };
+        let (page_width, page_height) = (w as f32 * PT_PER_MM, h as f32 * PT_PER_MM);
+        let m = &options.margins;
+        let margin = |mm: f64| (mm.max(0.0) as f32) * PT_PER_MM;
+        let (left, right) = (margin(m.left), page_width - margin(m.right));
+        let (top, bottom) = (margin(m.top), page_height - margin(m.bottom));
+        // Leave room for at least a couple of inches of content
+        if right - left < 144.0 || bottom - top < 144.0 {
+            return Err("Page margins leave no room for content".to_string());
+        }
+        Ok(Self {
+            fonts,
+            images,
+            size: (options.font_size as f32).clamp(6.0, 48.0),
+            page_width,
+            page_height,
+            left,
+            right,
+            top,
+            bottom,
+            pages: vec![Page::default()],
+            y: top,
+            list_depth: 0,
+        })
+    }
+
+    fn push(&mut self, item: Item) {
+        if let Some(page) = self.pages.last_mut() {
+            page.items.push(item);
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.y = self.top;
+    }
+
+    /// Start a new page unless `height` still fits (or the page is empty).
+    fn reserve(&mut self, height: f32) {
+        if self.y + height > self.bottom && self.y > self.top {
+            self.new_page();
+        }
+    }
+
+    /// Vertical space that is dropped at the top of a page.
+    fn gap(&mut self, height: f32) {
+        if self.y > self.top {
+            self.y += height;
+        }
+    }
+
+    fn mark(&self) -> Mark {
+        Mark {
+            page: self.pages.len() - 1,
+            y: self.y,
+            item: self.pages.last().map_or(0, |p| p.items.len()),
+        }
+    }
+
+    /// Fill `x..x + w` from `from` down to the current position, on every
+    /// page in between, underneath what was laid out since.
+    fn band(&mut self, from: Mark, x: f32, w: f32, color: Color) {
+        let last = self.pages.len() - 1;
+        for page in from.page..=last {
+            let y0 = if page == from.page { from.y } else { self.top };
+            let y1 = if page == last { self.y } else { self.bottom };
+            if y1 <= y0 {
+                continue;
+            }
+            let at = if page == from.page { from.item } else { 0 };
+            let rect = Item::Rect {
+                x,
+                y: y0,
+                w,
+                h: y1 - y0,
+                color,
+            };
+            self.pages[page].items.insert(at, rect);
+        }
+    }
+
+    fn blocks(&mut self, blocks: &[Block], x: f32, width: f32, color: Color, gap: f32) {
+        for (i, block) in blocks.iter().enumerate() {
+            if i > 0 {
+                self.gap(gap);
+            }
+            self.block(block, x, width, color);
+        }
+    }
+
+    fn block(&mut self, block: &Block, x: f32, width: f32, color: Color) {
+        let body = InlineStyle {
+            size: self.size,
+            bold: false,
+            mono: false,
+            color,
+        };
+        match block {
+            Block::Heading { level, spans } => {
+                let size = self.size * HEADING_SCALE[(*level as usize).clamp(1, 6) - 1];
+                self.gap(size * 0.6);
+                let style = InlineStyle {
+                    size,
+                    bold: true,
+                    ..body
+                };
+                let lines = self.lines(spans, style, width);
+                // Keep the heading on the same page as the line after it
+                let height: f32 = lines.iter().map(Line::height).sum();
+                self.reserve(height + self.size * LINE_HEIGHT);
+                self.flow(lines, x, width, Alignment::None);
+                if *level <= 2 {
+                    self.y += size * 0.15;
+                    self.push(Item::Line {
+                        x1: x,
+                        y1: self.y,
+                        x2: x + width,
+                        y2: self.y,
+                        width: 0.6,
+                        color: RULE,
+                    });
+                    self.y += size * 0.2;
+                }
+            }
+            Block::Paragraph(spans) => {
+                let lines = self.lines(spans, body, width);
+                self.flow(lines, x, width, Alignment::None);
+            }
+            Block::List { start, items } => self.list(*start, items, x, width, color),
+            Block::Quote(blocks) => {
+                let indent = self.size * 1.2;
+                let from = self.mark();
+                self.blocks(blocks, x + indent, width - indent, MUTED, self.size * 0.7);
+                self.band(from, x, self.size * 0.25, RULE);
+            }
+            Block::Code(code) => {
+                let size = self.size * 0.88;
+                let pad = size * 0.8;
+                let style = InlineStyle {
+                    size,
+                    mono: true,
+                    ..body
+                };
+                let span = Span {
+                    text: code.replace('\t', "    "),
+                    style: SpanStyle::default(),
+                };
+                let lines = self.lines(&[span], style, width - 2.0 * pad);
+                let first = lines.first().map_or(0.0, Line::height);
+                self.reserve(first + 2.0 * pad);
+                let from = self.mark();
+                self.y += pad;
+                self.flow(lines, x + pad, width - 2.0 * pad, Alignment::None);
+                self.y += pad;
+                self.band(from, x, width, CODE_BG);
+            }
+            Block::Rule => {
+                self.reserve(self.size);
+                self.y += self.size * 0.5;
+                self.push(Item::Line {
+                    x1: x,
+                    y1: self.y,
+                    x2: x + width,
+                    y2: self.y,
+                    width: 0.8,
+                    color: RULE,
+                });
+                self.y += self.size * 0.5;
+            }
+            Block::Image { src, alt } => self.image(src, alt, x, width, color),
+            Block::Table { align, rows } => self.table(align, rows, x, width, color),
+        }
+    }
+
+    fn list(&mut self, start: Option<u64>, items: &[ListItem], x: f32, width: f32, color: Color) {
+        let indent = self.size * 1.8;
+        let line_height = self.size * LINE_HEIGHT;
+        let marker_style = InlineStyle {
+            size: self.size,
+            bold: false,
+            mono: false,
+            color,
+        };
+        self.list_depth += 1;
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.gap(self.size * 0.3);
+            }
+            self.reserve(line_height);
+            let marker_width = indent - self.size * 0.5;
+            match (item.checked, start) {
+                (Some(checked), _) => self.checkbox(checked, x + marker_width, line_height),
+                (None, start) => {
+                    let text = match start {
+                        Some(n) => format!("{}.", n + i as u64),
+                        None => BULLETS[(self.list_depth - 1) % BULLETS.len()].to_string(),
+                    };
+                    let span = Span {
+                        text,
+                        style: SpanStyle::default(),
+                    };
+                    if let Some(line) = self.lines(&[span], marker_style, indent).pop() {
+                        let items = line_items(line, x, self.y, marker_width, Alignment::Right);
+                        for item in items {
+                            self.push(item);
+                        }
+                    }
+                }
+            }
+            let gap = self.size * 0.3;
+            self.blocks(&item.blocks, x + indent, width - indent, color, gap);
+            if item.blocks.is_empty() {
+                self.y += line_height;
+            }
+        }
+        self.list_depth -= 1;
+    }
+
+    /// Task list box, right-aligned at `right` on the current line.
+    fn checkbox(&mut self, checked: bool, right: f32, line_height: f32) {
+        let side = self.size * 0.75;
+        let (x, y) = (right - side, self.y + (line_height - side) / 2.0);
+        let border = if checked { LINK } else { MUTED };
+        self.push(Item::Rect {
+            x,
+            y,
+            w: side,
+            h: side,
+            color: border,
+        });
+        if checked {
+            let tick = |(px, py): (f32, f32)| (x + side * px, y + side * py);
+            let points = [tick((0.22, 0.52)), tick((0.42, 0.72)), tick((0.78, 0.3))];
+            for pair in points.windows(2) {
+                self.push(Item::Line {
+                    x1: pair[0].0,
+                    y1: pair[0].1,
+                    x2: pair[1].0,
+                    y2: pair[1].1,
+                    width: side * 0.14,
+                    color: WHITE,
+                });
+            }
+        } else {
+            let inset = 0.8;
+            self.push(Item::Rect {
+                x: x + inset,
+                y: y + inset,
+                w: side - 2.0 * inset,
+                h: side - 2.0 * inset,
+                color: WHITE,
+            });
+        }
+    }
+
+    fn image(&mut self, src: &str, alt: &str, x: f32, width: f32, color: Color) {
+        let Some(index) = self.images.get(src) else {
+            // Keep a visible trace of what is missing
+            let text = if alt.trim().is_empty() { src } else { alt };
+            let span = Span {
+                text: format!("[{}]", text.trim()),
+                style: SpanStyle {
+                    italic: true,
+                    ..SpanStyle::default()
+                },
+            };
+            let style = InlineStyle {
+                size: self.size,
+                bold: false,
+                mono: false,
+                color: if color == TEXT { MUTED } else { color },
+            };
+            let lines = self.lines(&[span], style, width);
+            self.flow(lines, x, width, Alignment::None);
+            return;
+        };
+        let (w, h) = self.images.loaded[index].display;
+        let scale = (width / w).min((self.bottom - self.top) / h).min(1.0);
+        let (w, h) = (w * scale, h * scale);
+        self.reserve(h);
+        self.push(Item::Image {
+            index,
+            x,
+            y: self.y,
+            w,
+            h,
+        });
+        self.y += h;
+    }
+
+    fn table(
+        &mut self,
+        align: &[Alignment],
+        rows: &[Vec<Vec<Span>>],
+        x: f32,
+        width: f32,
+        color: Color,
+    ) {
+        let cols = rows
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(align.len());
+        if cols == 0 {
+            return;
+        }
+        let (size, pad) = (self.size, self.size * 0.45);
+        let style = |header: bool| InlineStyle {
+            size,
+            bold: header,
+            mono: false,
+            color,
+        };
+
+        // Column widths: natural widths if they fit, else shrink toward the
+        // longest unbreakable word (capped at an even share)
+        let share = width / cols as f32;
+        let mut natural = vec![0.0f32; cols];
+        let mut minimum = vec![0.0f32; cols];
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                let atoms = self.atoms(cell, style(r == 0));
+                let line: f32 = atoms.iter().map(|a| a.width + a.glue).sum();
+                let longest = atoms.iter().map(|a| a.width).fold(0.0, f32::max);
+                natural[c] = natural[c].max(line + 2.0 * pad);
+                minimum[c] = minimum[c].max((longest + 2.0 * pad).min(share));
+            }
+        }
+        let widths = column_widths(&natural, &minimum, width);
+
+        let border = |ts: &mut Self, x1: f32, y1: f32, x2: f32, y2: f32| {
+            ts.push(Item::Line {
+                x1,
+                y1,
+                x2,
+                y2,
+                width: 0.6,
+                color: RULE,
+            })
+        };
+        for (r, row) in rows.iter().enumerate() {
+            let mut cells = Vec::with_capacity(cols);
+            for (c, col_width) in widths.iter().enumerate() {
+                let spans = row.get(c).map(Vec::as_slice).unwrap_or(&[]);
+                cells.push(self.lines(spans, style(r == 0), col_width - 2.0 * pad));
+            }
+            let content = cells
+                .iter()
+                .map(|lines| lines.iter().map(Line::height).sum::<f32>())
+                .fold(self.size * LINE_HEIGHT, f32::max);
+            let row_height = content + 2.0 * pad;
+            self.reserve(row_height);
+            let top = self.y;
+            if r == 0 {
+                self.push(Item::Rect {
+                    x,
+                    y: top,
+                    w: widths.iter().sum(),
+                    h: row_height,
+                    color: TABLE_HEAD_BG,
+                });
+            }
+            let mut cx = x;
+            for (c, lines) in cells.into_iter().enumerate() {
+                let cell_align = align.get(c).copied().unwrap_or(Alignment::None);
+                let mut cy = top + pad;
+                for line in lines {
+                    let height = line.height();
+                    for item in line_items(line, cx + pad, cy, widths[c] - 2.0 * pad, cell_align) {
+                        self.push(item);
+                    }
+                    cy += height;
+                }
+                border(self, cx, top, cx, top + row_height);
+                cx += widths[c];
+            }
+            border(self, cx, top, cx, top + row_height);
+            border(self, x, top, cx, top);
+            border(self, x, top + row_height, cx, top + row_height);
+            self.y += row_height;
+        }
+    }
+
+    /// Header and footer lines on every page, once the page count is known.
+    fn decorate(&mut self, options: &PdfExportOptions, title: &str) {
+        let total = self.pages.len();
+        let size = self.size * 0.8;
+        let width = self.right - self.left;
+        let style = InlineStyle {
+            size,
+            bold: false,
+            mono: false,
+            color: MUTED,
+        };
+        let slots = [
+            (
+                options.header_enabled,
+                &options.header_template,
+                self.top / 2.0,
+            ),
+            (
+                options.footer_enabled,
+                &options.footer_template,
+                self.bottom + (self.page_height - self.bottom) / 2.0,
+            ),
+        ];
+        for (enabled, template, center) in slots {
+            if !enabled || template.trim().is_empty() {
+                continue;
+            }
+            for page in 0..total {
+                let span = Span {
+                    text: expand_template(template, page + 1, total, title),
+                    style: SpanStyle::default(),
+                };
+                // One line only; the margin has no room for more
+                let Some(line) = self.lines(&[span], style, width).into_iter().next() else {
+                    continue;
+                };
+                let y = center - line.height() / 2.0;
+                let items = line_items(line, self.left, y, width, Alignment::Center);
+                self.pages[page].items.extend(items);
+            }
+        }
+    }
+
+    fn font_style(&self, base: InlineStyle, span: SpanStyle) -> FontStyle {
+        FontStyle {
+            mono: base.mono || span.code,
+            bold: base.bold || span.bold,
+            italic: span.italic,
+        }
+    }
+
+    fn shape(&mut self, text: &str, base: InlineStyle, span: SpanStyle, runs: &mut Vec<Run>) {
+        let font_style = self.font_style(base, span);
+        let size = if span.code && !base.mono {
+            base.size * 0.9
+        } else {
+            base.size
+        };
+        let color = if span.link { LINK } else { base.color };
+        for glyph in self.fonts.shape(text, font_style) {
+            let advance = glyph.advance * size;
+            match runs.last_mut() {
+                Some(run) if run.slot == glyph.slot && run.size == size && run.style == span => {
+                    run.glyphs.push(glyph.id);
+                    run.width += advance;
+                }
+                _ => runs.push(Run {
+                    slot: glyph.slot,
+                    size,
+                    color,
+                    style: span,
+                    glyphs: vec![glyph.id],
+                    width: advance,
+                }),
+            }
+        }
+    }
+
+    fn atoms(&mut self, spans: &[Span], base: InlineStyle) -> Vec<Atom> {
+        let text: String = spans.iter().map(|s| s.text.as_str()).collect();
+        let mut bounds = Vec::with_capacity(spans.len());
+        let mut pos = 0;
+        for span in spans {
+            bounds.push((pos..pos + span.text.len(), span.style));
+            pos += span.text.len();
+        }
+        let style_at = |at: usize| {
+            bounds
+                .iter()
+                .find(|(range, _)| range.contains(&at))
+                .map_or(SpanStyle::default(), |(_, style)| *style)
+        };
+
+        let mut atoms = Vec::new();
+        let mut prev = 0;
+        for (pos, _) in unicode_linebreak::linebreaks(&text) {
+            let segment = &text[prev..pos];
+            let content_end = prev + segment.trim_end().len();
+            let trailing = &text[content_end..pos];
+            let mut runs = Vec::new();
+            for (range, style) in &bounds {
+                let (start, end) = (range.start.max(prev), range.end.min(content_end));
+                if start < end {
+                    self.shape(&text[start..end], base, *style, &mut runs);
+                }
+            }
+            let spaces = trailing.chars().filter(|c| *c == ' ' || *c == '\t').count();
+            let glue = if spaces > 0 {
+                let mut space = Vec::new();
+                self.shape(" ", base, style_at(content_end), &mut space);
+                space.iter().map(|r| r.width).sum::<f32>() * spaces as f32
+            } else {
+                0.0
+            };
+            atoms.push(Atom {
+                width: runs.iter().map(|r| r.width).sum(),
+                runs,
+                glue,
+                forced: trailing.contains('\n'),
+            });
+            prev = pos;
+        }
+        atoms
+    }
+
+    fn lines(&mut self, spans: &[Span], base: InlineStyle, max_width: f32) -> Vec<Line> {
+        let mut atoms = Vec::new();
+        for atom in self.atoms(spans, base) {
+            if atom.width > max_width {
+                split_atom(atom, &mut atoms);
+            } else {
+                atoms.push(atom);
+            }
+        }
+        let metrics: Vec<_> = atoms.iter().map(|a| (a.width, a.glue, a.forced)).collect();
+        let mut atoms = atoms.into_iter();
+        break_lines(&metrics, max_width)
+            .into_iter()
+            .map(|range| {
+                let atoms: Vec<Atom> = atoms.by_ref().take(range.len()).collect();
+                let glue: f32 = atoms.iter().rev().skip(1).map(|a| a.glue).sum();
+                let size = atoms
+                    .iter()
+                    .flat_map(|a| a.runs.iter().map(|r| r.size))
+                    .fold(0.0, f32::max);
+                Line {
+                    width: atoms.iter().map(|a| a.width).sum::<f32>() + glue,
+                    size: if size > 0.0 { size } else { base.size },
+                    atoms,
+                }
+            })
+            .collect()
+    }
+
+    /// Lay lines out at the current position, breaking pages between lines.
+    fn flow(&mut self, lines: Vec<Line>, x: f32, width: f32, align: Alignment) {
+        for line in lines {
+            let height = line.height();
+            self.reserve(height);
+            for item in line_items(line, x, self.y, width, align) {
+                self.push(item);
+            }
+            self.y += height;
+        }
+    }
+}
+
+/// Break an atom wider than a line into one atom per glyph.
+fn split_atom(atom: Atom, out: &mut Vec<Atom>) {
+    let (glue, forced) = (atom.glue, atom.forced);
+    let start = out.len();
+    for run in atom.runs {
+        let advance = run.width / run.glyphs.len().max(1) as f32;
+        for id in run.glyphs {
+            out.push(Atom {
+                runs: vec![Run {
+                    glyphs: vec![id],
+                    width: advance,
+                    ..run
+                }],
+                width: advance,
+                glue: 0.0,
+                forced: false,
+            });
+        }
+    }
+    match out.get_mut(start..).and_then(|a| a.last_mut()) {
+        Some(last) => {
+            last.glue = glue;
+            last.forced = forced;
+        }
+        None => out.push(Atom {
+            runs: Vec::new(),
+            width: 0.0,
+            glue,
+            forced,
+        }),
+    }
+}
+
+/// Page items for one line whose box starts at `top`.
+fn line_items(line: Line, x: f32, top: f32, width: f32, align: Alignment) -> Vec<Item> {
+    let offset = match align {
+        Alignment::Center => (width - line.width) / 2.0,
+        Alignment::Right => width - line.width,
+        _ => 0.0,
+    }
+    .max(0.0);
+    let baseline = top + (line.height() + line.size * 0.7) / 2.0;
+    let mut items = Vec::new();
+    let mut cx = x + offset;
+    let count = line.atoms.len();
+    for (i, atom) in line.atoms.into_iter().enumerate() {
+        for run in atom.runs {
+            let size = run.size;
+            if run.style.code {
+                items.push(Item::Rect {
+                    x: cx - 1.0,
+                    y: baseline - size * 0.85,
+                    w: run.width + 2.0,
+                    h: size * 1.15,
+                    color: CODE_BG,
+                });
+            }
+            let decoration = |y: f32, color: Color| Item::Line {
+                x1: cx,
+                y1: y,
+                x2: cx + run.width,
+                y2: y,
+                width: size * 0.06,
+                color,
+            };
+            if run.style.strike {
+                items.push(decoration(baseline - size * 0.3, run.color));
+            }
+            if run.style.link {
+                items.push(decoration(baseline + size * 0.12, LINK));
+            }
+            items.push(Item::Text {
+                x: cx,
+                y: baseline,
+                size,
+                color: run.color,
+                slot: run.slot,
+                glyphs: run.glyphs,
+            });
+            cx += run.width;
+        }
+        if i + 1 < count {
+            cx += atom.glue;
+        }
+    }
+    items
+}
+
+/// Fit columns into `available`: natural widths when they fit, else each
+/// column gives up space in proportion to how far it can shrink.
+fn column_widths(natural: &[f32], minimum: &[f32], available: f32) -> Vec<f32> {
+    let total: f32 = natural.iter().sum();
+    if total <= available {
+        return natural.to_vec();
+    }
+    let floor: f32 = minimum.iter().sum();
+    if floor >= available {
+        return minimum.iter().map(|m| m * available / floor).collect();
+    }
+    let slack = (available - floor) / (total - floor);
+    natural
+        .iter()
+        .zip(minimum)
+        .map(|(n, m)| m + (n - m) * slack)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_break_lines_greedily_dropping_glue_at_line_ends() {
+        let items = [(30.0, 5.0, false), (30.0, 5.0, false), (30.0, 5.0, false)];
+        assert_eq!(break_lines(&items, 65.0), vec![0..2, 2..3]);
+        assert_eq!(break_lines(&items, 64.0), vec![0..1, 1..2, 2..3]);
+        assert_eq!(break_lines(&items, 200.0), vec![0..3]);
+    }
+
+    #[test]
+    fn should_honor_forced_breaks() {
+        let items = [(10.0, 0.0, true), (0.0, 0.0, true), (10.0, 0.0, false)];
+        assert_eq!(break_lines(&items, 100.0), vec![0..1, 1..2, 2..3]);
+        assert!(break_lines(&[], 100.0).is_empty());
+    }
+
+    #[test]
+    fn should_expand_header_footer_templates() {
+        assert_eq!(expand_template("{page} / {total}", 2, 9, "Notes"), "2 / 9");
+        assert_eq!(
+            expand_template("{title} — {page}", 1, 1, "Notes"),
+            "Notes — 1"
+        );
+    }
+
+    #[test]
+    fn should_fit_table_columns() {
+        assert_eq!(
+            column_widths(&[50.0, 30.0], &[20.0, 10.0], 100.0),
+            [50.0, 30.0]
+        );
+        let widths = column_widths(&[200.0, 100.0], &[50.0, 50.0], 200.0);
+        assert!((widths.iter().sum::<f32>() - 200.0).abs() < 0.01);
+        assert!(widths[0] > widths[1]);
+        assert_eq!(
+            column_widths(&[300.0, 300.0], &[150.0, 50.0], 100.0),
+            [75.0, 25.0]
+        );
+    }
+
+    #[test]
+    fn should_decode_data_uri_images() {
+        // 1×1 PNG
+        let png = concat!(
+            "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGA",
+            "hKmMIQAAAABJRU5ErkJggg=="
+        );
+        let bytes = image_bytes(&format!("data:image/png;base64,{}", png), None).unwrap();
+        let image = decode_image(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (1, 1));
+        assert_eq!(image.rgb.len(), 3);
+    }
+}
@@ -0,0 +1,328 @@
+//! Serializes laid-out pages to PDF. Fonts are embedded as subsetted CID
+//! fonts (Identity-H, glyph id = CID) with a ToUnicode map so text stays
+//! searchable and copyable, including CJK.
+
+use std::io::Write;
+
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, TextRenderingMode};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use super::fonts::{Face, Fonts, Slot};
+use super::typeset::{Color, Image, Item, Page};
+
+/// Horizontal skew for synthesized italics.
+const FAKE_ITALIC_SKEW: f32 = 0.2;
+/// Outline width for synthesized bold, relative to the font size.
+const FAKE_BOLD_STROKE: f32 = 0.03;
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    // Writing into a Vec can't fail
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+fn font_name(face: usize) -> Vec<u8> {
+    format!("F{}", face).into_bytes()
+}
+
+fn image_name(index: usize) -> Vec<u8> {
+    format!("Im{}", index).into_bytes()
+}
+
+/// Write `pages` of `size` (points) as a PDF. `on_page` is called with
+/// `(current, total)` as each page is serialized.
+pub fn write(
+    pages: &[Page],
+    size: (f32, f32),
+    fonts: &Fonts,
+    images: &[Image],
+    title: &str,
+    mut on_page: impl FnMut(u32, u32),
+) -> Result<Vec<u8>, String> {
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let tree_id = alloc.bump();
+    let info_id = alloc.bump();
+    let mut pdf = Pdf::new();
+
+    // Only faces that ended up with glyphs are embedded
+    let mut font_refs = vec![None; fonts.faces.len()];
+    for (i, face) in fonts.faces.iter().enumerate() {
+        if !face.used.is_empty() {
+            font_refs[i] = Some(write_font(&mut pdf, &mut alloc, face, i)?);
+        }
+    }
+    let image_refs: Vec<Ref> = images
+        .iter()
+        .map(|image| write_image(&mut pdf, &mut alloc, image))
+        .collect();
+
+    let total = pages.len() as u32;
+    let mut page_ids = Vec::with_capacity(pages.len());
+    for (n, page) in pages.iter().enumerate() {
+        let page_id = alloc.bump();
+        let content_id = alloc.bump();
+        page_ids.push(page_id);
+
+        let content = deflate(&page_content(page, size.1, images));
+        pdf.stream(content_id, &content).filter(Filter::FlateDecode);
+
+        let mut writer = pdf.page(page_id);
+        writer
+            .parent(tree_id)
+            .media_box(Rect::new(0.0, 0.0, size.0, size.1))
+            .contents(content_id);
+        let mut resources = writer.resources();
+        let mut font_dict = resources.fonts();
+        for (i, id) in font_refs.iter().enumerate() {
+            if let Some(id) = id {
+                font_dict.pair(Name(&font_name(i)), *id);
+            }
+        }
+        font_dict.finish();
+        let mut x_objects = resources.x_objects();
+        for (i, id) in image_refs.iter().enumerate() {
+            x_objects.pair(Name(&image_name(i)), *id);
+        }
+        x_objects.finish();
+        resources.finish();
+        writer.finish();
+        on_page(n as u32 + 1, total);
+    }
+
+    pdf.pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    pdf.catalog(catalog_id).pages(tree_id);
+    let mut info = pdf.document_info(info_id);
+    if !title.is_empty() {
+        info.title(TextStr(title));
+    }
+    info.producer(TextStr("Moraya"));
+    info.finish();
+    Ok(pdf.finish())
+}
+
+fn set_fill(content: &mut Content, color: Color) {
+    content.set_fill_rgb(color[0], color[1], color[2]);
+}
+
+fn page_content(page: &Page, height: f32, images: &[Image]) -> Vec<u8> {
+    let mut content = Content::new();
+    for item in &page.items {
+        match item {
+            Item::Text {
+                x,
+                y,
+                size,
+                color,
+                slot,
+                glyphs,
+            } => text(&mut content, height, *x, *y, *size, *color, *slot, glyphs),
+            Item::Rect { x, y, w, h, color } => {
+                set_fill(&mut content, *color);
+                content.rect(*x, height - y - h, *w, *h).fill_nonzero();
+            }
+            Item::Line {
+                x1,
+                y1,
+                x2,
+                y2,
+                width,
+                color,
+            } => {
+                content
+                    .set_stroke_rgb(color[0], color[1], color[2])
+                    .set_line_width(*width)
+                    .move_to(*x1, height - y1)
+                    .line_to(*x2, height - y2)
+                    .stroke();
+            }
+            Item::Image { index, x, y, w, h } => {
+                if *index >= images.len() {
+                    continue;
+                }
+                content
+                    .save_state()
+                    .transform([*w, 0.0, 0.0, *h, *x, height - y - h])
+                    .x_object(Name(&image_name(*index)))
+                    .restore_state();
+            }
+        }
+    }
+    content.finish()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn text(
+    content: &mut Content,
+    height: f32,
+    x: f32,
+    y: f32,
+    size: f32,
+    color: Color,
+    slot: Slot,
+    glyphs: &[u16],
+) {
+    let skew = if slot.fake_italic {
+        FAKE_ITALIC_SKEW
+    } else {
+        0.0
+    };
+    let bytes: Vec<u8> = glyphs.iter().flat_map(|g| g.to_be_bytes()).collect();
+    set_fill(content, color);
+    content.begin_text();
+    if slot.fake_bold {
+        content
+            .set_stroke_rgb(color[0], color[1], color[2])
+            .set_line_width(size * FAKE_BOLD_STROKE)
+            .set_text_rendering_mode(TextRenderingMode::FillStroke);
+    } else {
+        content.set_text_rendering_mode(TextRenderingMode::Fill);
+    }
+    content
+        .set_font(Name(&font_name(slot.face)), size)
+        .set_text_matrix([1.0, 0.0, skew, 1.0, x, height - y])
+        .show(Str(&bytes))
+        .end_text();
+}
+
+fn write_font(pdf: &mut Pdf, alloc: &mut Ref, face: &Face, index: usize) -> Result<Ref, String> {
+    let type0_id = alloc.bump();
+    let cid_id = alloc.bump();
+    let descriptor_id = alloc.bump();
+    let cmap_id = alloc.bump();
+    let data_id = alloc.bump();
+
+    let mut glyphs: Vec<u16> = face.used.keys().copied().collect();
+    glyphs.insert(0, 0);
+    let subset = subsetter::subset(&face.data, face.index, subsetter::Profile::pdf(&glyphs))
+        .map_err(|e| format!("Failed to subset font {}: {:?}", face.name, e))?;
+    // Subset fonts need a six-letter tag unique within the document
+    let tag: String = (0..6)
+        .map(|i| (b'A' + ((index * 7 + i * 3) % 26) as u8) as char)
+        .collect();
+    let base_font = format!("{}+{}", tag, face.name);
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+
+    pdf.type0_font(type0_id)
+        .base_font(Name(base_font.as_bytes()))
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_id)
+        .to_unicode(cmap_id);
+
+    let mut cid = pdf.cid_font(cid_id);
+    cid.subtype(if face.is_cff {
+        CidFontType::Type0
+    } else {
+        CidFontType::Type2
+    })
+    .base_font(Name(base_font.as_bytes()))
+    .system_info(system_info)
+    .font_descriptor(descriptor_id)
+    .default_width(0.0);
+    if !face.is_cff {
+        cid.cid_to_gid_map_predefined(Name(b"Identity"));
+    }
+    let mut widths = cid.widths();
+    for &id in face.used.keys() {
+        widths.consecutive(id, [face.width(id)]);
+    }
+    widths.finish();
+    cid.finish();
+
+    let mut flags = FontFlags::SYMBOLIC;
+    if face.is_monospaced {
+        flags |= FontFlags::FIXED_PITCH;
+    }
+    if face.is_italic {
+        flags |= FontFlags::ITALIC;
+    }
+    let [x_min, y_min, x_max, y_max] = face.bbox.map(|v| face.to_pdf_units(v));
+    let mut descriptor = pdf.font_descriptor(descriptor_id);
+    descriptor
+        .name(Name(base_font.as_bytes()))
+        .flags(flags)
+        .bbox(Rect::new(x_min, y_min, x_max, y_max))
+        .italic_angle(face.italic_angle)
+        .ascent(face.to_pdf_units(face.ascender))
+        .descent(face.to_pdf_units(face.descender))
+        .cap_height(face.to_pdf_units(face.cap_height))
+        .stem_v(80.0);
+    if face.is_cff {
+        descriptor.font_file3(data_id);
+    } else {
+        descriptor.font_file2(data_id);
+    }
+    descriptor.finish();
+
+    let mut cmap = pdf_writer::types::UnicodeCmap::new(Name(b"Custom"), system_info);
+    for (&id, &c) in &face.used {
+        cmap.pair(id, c);
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+
+    let compressed = deflate(&subset);
+    let mut stream = pdf.stream(data_id, &compressed);
+    stream.filter(Filter::FlateDecode);
+    if face.is_cff {
+        stream.pair(Name(b"Subtype"), Name(b"OpenType"));
+    }
+    stream.finish();
+    Ok(type0_id)
+}
+
+fn write_image(pdf: &mut Pdf, alloc: &mut Ref, image: &Image) -> Ref {
+    let id = alloc.bump();
+    let mask_id = image.alpha.as_ref().map(|alpha| {
+        let mask_id = alloc.bump();
+        let data = deflate(alpha);
+        let mut mask = pdf.image_xobject(mask_id, &data);
+        mask.filter(Filter::FlateDecode);
+        mask.width(image.width as i32)
+            .height(image.height as i32)
+            .color_space()
+            .device_gray();
+        mask.bits_per_component(8);
+        mask_id
+    });
+    let data = deflate(&image.rgb);
+    let mut xobject = pdf.image_xobject(id, &data);
+    xobject.filter(Filter::FlateDecode);
+    xobject
+        .width(image.width as i32)
+        .height(image.height as i32)
+        .color_space()
+        .device_rgb();
+    xobject.bits_per_component(8).interpolate(true);
+    if let Some(mask_id) = mask_id {
+        xobject.s_mask(mask_id);
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_write_empty_document_with_pages() {
+        let fonts = Fonts::empty();
+        let pages = vec![Page::default(), Page::default()];
+        let mut seen = Vec::new();
+        let pdf = write(&pages, (595.0, 842.0), &fonts, &[], "Notes", |c, t| {
+            seen.push((c, t))
+        })
+        .unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert_eq!(seen, vec![(1, 2), (2, 2)]);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/Title (Notes)"));
+    }
+}
@@ -18,7 +18,7 @@ const DEFAULT_TIMEOUT_SECS: u64 = 20;
 // ── HTTP helpers ──────────────────────────────────────────────────────
 
 fn http_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
+    super::settings::client_builder()
         .timeout(std::time::Duration::from_secs(DEFAULT_TIMEOUT_SECS))
         .build()
         .map_err(|_| "Failed to initialize HTTP client".to_string())
@@ -1,27 +1,85 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{BufRead, BufReader, Write};
 use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio};
-use std::sync::mpsc::{Receiver, SyncSender};
-use std::sync::Mutex;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tauri::{Emitter, Manager, State};
 
+use super::error::{CommandError, ErrorCode};
+use super::file as file_cmd;
+use super::update::TransferMeter;
+
 // ---------------------------------------------------------------------------
 // Constants
 // ---------------------------------------------------------------------------
 
 const PLUGIN_READ_TIMEOUT: Duration = Duration::from_secs(10);
 const PLUGIN_MAX_LINE: usize = 64 * 1024; // 64 KB
+/// How often the monitor thread polls a plugin for exit.
+const PLUGIN_MONITOR_INTERVAL: Duration = Duration::from_millis(500);
+/// Auto-restart attempts (1s, 2s, 4s backoff) for manifests with `autoRestart`.
+const PLUGIN_MAX_RESTARTS: u32 = 3;
+/// stderr lines attached to `plugin:exited` events.
+const PLUGIN_STDERR_TAIL: usize = 20;
+/// stderr lines kept in memory per plugin for `plugin_read_logs`.
+const PLUGIN_LOG_BUFFER_LINES: usize = 1000;
+/// `plugins/<id>/logs/plugin.log` rotates to `plugin.log.1` past this size.
+const PLUGIN_LOG_FILE_MAX_BYTES: u64 = 1024 * 1024;
+/// `plugin:log` events per plugin per second; further lines are only buffered.
+const PLUGIN_LOG_EVENTS_PER_SEC: u32 = 20;
+/// Time a plugin gets to exit after the `shutdown` notification before it is killed.
+const PLUGIN_SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+/// Plugin API version announced in `MORAYA_API_VERSION` and `initialize`.
+const PLUGIN_API_VERSION: &str = "1";
+/// How long a freshly spawned plugin has to answer `initialize`.
+const PLUGIN_INIT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Id prefix of plugins loaded from a local folder with `plugin_dev_load`.
+const DEV_PLUGIN_PREFIX: &str = "dev:";
+/// File events closer together than this trigger a single dev reload.
+const DEV_RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+/// Uncompressed size cap for plugin packages.
+const PLUGIN_ZIP_MAX_BYTES: u64 = 256 * 1024 * 1024;
+/// Entry count cap for plugin packages.
+const PLUGIN_ZIP_MAX_FILES: usize = 10_000;
+/// Uncompressed size cap for `plugin_import_data`.
+const PLUGIN_DATA_IMPORT_MAX_BYTES: u64 = 512 * 1024 * 1024;
+/// Entry count cap for `plugin_import_data`.
+const PLUGIN_DATA_IMPORT_MAX_FILES: usize = 20_000;
 
 /// Registry index URL (pinned — not user-configurable to prevent hijacking)
 const REGISTRY_INDEX_URL: &str =
     "https://raw.githubusercontent.com/moraya-apps/moraya-plugin-registry/main/index.json";
+const REGISTRY_INDEX_SIG_URL: &str =
+    "https://raw.githubusercontent.com/moraya-apps/moraya-plugin-registry/main/index.json.sig";
+/// Minisign public key of the registry, embedded from
+/// `MORAYA_REGISTRY_PUBKEY` at build time. `build.rs` requires it for CI and
+/// release builds; local builds without it skip signature checks.
+const REGISTRY_PUBLIC_KEY: Option<&str> = option_env!("MORAYA_REGISTRY_PUBKEY");
+
+/// Base for registry-relative paths (e.g. an entry's `icon`).
+const REGISTRY_RAW_BASE: &str =
+    "https://raw.githubusercontent.com/moraya-apps/moraya-plugin-registry/main/";
 const REGISTRY_BLACKLIST_URL: &str =
     "https://raw.githubusercontent.com/moraya-apps/moraya-plugin-registry/main/blacklist.json";
 
+/// Allowlisted GitHub download mirrors (prefix proxies), tried in order when
+/// the direct connection fails. Only raw.githubusercontent.com and release
+/// asset URLs are rewritten. The index supplies the SHA256 every package is
+/// checked against, so it comes through a mirror only when its signature can
+/// be verified; without a registry key it is fetched directly or not at all.
+const REGISTRY_MIRRORS: &[&str] = &["https://ghfast.top/", "https://gh-proxy.com/"];
+/// Connect timeout per source before falling back to the next one.
+const REGISTRY_CONNECT_TIMEOUT: Duration = Duration::from_secs(6);
+/// Preferred mirror chosen with `plugin_set_registry_mirror`, in appData.
+const REGISTRY_MIRROR_FILE: &str = "plugin-registry-mirror.json";
+
+/// Installed-plugin state, stored next to the plugin directories.
+const PLUGIN_STATE_FILE: &str = "state.json";
+
 /// Cache TTL: 30 minutes
 const CACHE_TTL_MS: u64 = 30 * 60 * 1000;
 
@@ -36,6 +94,80 @@ const ALLOWED_PERMISSIONS: &[&str] = &[
     "net:external",
 ];
 
+/// Plugin→host methods and the permission each requires. Methods in the
+/// `editor.` / `ai.` / `net.` namespaces that are not listed are denied.
+const PLUGIN_METHOD_PERMISSIONS: &[(&str, &str)] = &[
+    ("editor.getContent", "editor:read"),
+    ("editor.getSelection", "editor:read"),
+    ("editor.getFilePath", "editor:read"),
+    ("editor.setContent", "editor:write"),
+    ("editor.insertText", "editor:write"),
+    ("editor.replaceSelection", "editor:write"),
+    ("ai.chat", "ai:chat"),
+    ("ai.generateImage", "ai:image"),
+    ("ai.transcribe", "ai:voice"),
+    ("ai.speak", "ai:voice"),
+    ("ai.startCapture", "ai:voice:capture"),
+    ("net.fetch", "net:external"),
+    ("host/fs.read", "editor:read"),
+    ("host/fs.write", "editor:write"),
+];
+const PRIVILEGED_NAMESPACES: &[&str] = &["editor.", "ai.", "net.", "host/"];
+
+/// Host→plugin methods and the permissions they imply (e.g. `run` hands the
+/// plugin the document, so it needs `editor:read`).
+const HOST_METHOD_PERMISSIONS: &[(&str, &[&str])] = &[("run", &["editor:read"])];
+
+/// JSON-RPC error code returned to plugins for permission violations.
+const RPC_PERMISSION_DENIED: i64 = -32001;
+/// JSON-RPC error code for `host/fs.*` calls that failed for other reasons.
+const RPC_HOST_ERROR: i64 = -32000;
+/// JSON-RPC "method not found", the reply of plugins that predate `initialize`.
+const RPC_METHOD_NOT_FOUND: i64 = -32601;
+
+/// Largest file `host/fs.read` returns.
+const HOST_FS_MAX_READ: u64 = 4 * 1024 * 1024;
+
+/// Plugins at this sandbox level lose direct filesystem access and reach
+/// workspace files only through `host/fs.read` / `host/fs.write`. On macOS
+/// the process runs under `sandbox-exec`. Elsewhere there is no OS sandbox:
+/// only its working directory and home are set to its data directory and
+/// the variables in `STRICT_BLOCKED_ENV` removed, so it can still reach any
+/// file the user can. Other levels keep direct access for now.
+const STRICT_SANDBOX_LEVEL: &str = "strict";
+
+/// Variables pointing into the user's files or loading code from outside
+/// the plugin, removed for strict plugins (as are all `XDG_*` ones).
+const STRICT_BLOCKED_ENV: &[&str] = &[
+    "OLDPWD",
+    "PWD",
+    "NODE_OPTIONS",
+    "NODE_PATH",
+    "PYTHONPATH",
+    "PYTHONHOME",
+    "PYTHONSTARTUP",
+    "PYTHONUSERBASE",
+    "DENO_DIR",
+];
+
+/// Runtime installs under the home directory that strict plugins may still
+/// read on macOS, so `node` from nvm and the like keep working.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const RUNTIME_HOME_DIRS: &[&str] = &[
+    ".nvm",
+    ".volta",
+    ".bun",
+    ".deno",
+    ".asdf",
+    ".pyenv",
+    ".local/bin",
+    ".local/lib",
+    ".local/share/fnm",
+];
+
+/// Interpreters accepted for `{"runtime": ..., "script": ...}` entries.
+const ALLOWED_RUNTIMES: &[&str] = &["node", "bun", "deno", "python3", "python"];
+
 /// Dangerous environment variable prefixes — same list as mcp.rs
 const BLOCKED_ENV_PREFIXES: &[&str] = &[
     "LD_PRELOAD",
@@ -74,6 +206,14 @@ pub struct PluginManifest {
     pub homepage: Option<String>,
     #[serde(default)]
     pub limits: Option<serde_json::Value>,
+    /// Restart the plugin (with backoff) if it exits unexpectedly.
+    #[serde(default)]
+    pub auto_restart: bool,
+    /// Translated names keyed by locale (`zh-CN`, `ja`, ...).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub name_localized: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub description_localized: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,6 +226,23 @@ pub struct PluginStateEntry {
     pub manifest: PluginManifest,
 }
 
+/// One entry of the registry's blacklist.json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlacklistEntry {
+    pub id: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Last successfully fetched blacklist, mirrored to `plugin-blacklist-cache.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlacklistCache {
+    entries: Vec<BlacklistEntry>,
+    fetched_at: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ValidationResult {
@@ -93,6 +250,74 @@ pub struct ValidationResult {
     pub manifest: Option<PluginManifest>,
     pub errors: Vec<String>,
     pub warnings: Vec<String>,
+    /// Manifest name/description resolved for the UI locale.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub display_description: Option<String>,
+}
+
+/// Outcome of the `initialize` handshake, returned by `plugin_get_capabilities`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginHandshake {
+    pub compatible: bool,
+    /// `result.capabilities` from the plugin's reply.
+    pub capabilities: serde_json::Value,
+    /// `result.methods` from the plugin's reply.
+    pub methods: Vec<String>,
+    pub error: Option<String>,
+    /// The plugin's reply verbatim when the handshake failed.
+    pub raw_response: Option<String>,
+}
+
+impl PluginHandshake {
+    fn incompatible(error: String, raw_response: Option<String>) -> Self {
+        Self {
+            compatible: false,
+            capabilities: serde_json::Value::Null,
+            methods: Vec::new(),
+            error: Some(error),
+            raw_response,
+        }
+    }
+
+    /// A plugin written before `initialize` existed: it runs as before,
+    /// announcing no capabilities.
+    fn legacy() -> Self {
+        Self {
+            compatible: true,
+            capabilities: serde_json::json!({}),
+            methods: Vec::new(),
+            error: None,
+            raw_response: None,
+        }
+    }
+}
+
+/// Entry of `plugin_list_running`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningPlugin {
+    pub id: String,
+    /// Loaded from a local folder via `plugin_dev_load`.
+    pub dev: bool,
+    pub pid: u32,
+    pub uptime_ms: u64,
+    /// Resident memory of the plugin process; `None` if it couldn't be read.
+    pub rss_bytes: Option<u64>,
+    /// Epoch ms of the last `plugin_invoke` call.
+    pub last_invoke_ms: Option<u64>,
+}
+
+/// Counts reported by `plugin_export_data` / `plugin_import_data`.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginDataTransfer {
+    pub files: usize,
+    /// Uncompressed bytes.
+    pub bytes: u64,
+    pub skipped_symlinks: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -100,66 +325,610 @@ pub struct ValidationResult {
 pub struct InstallResult {
     pub ok: bool,
     pub plugin: Option<PluginStateEntry>,
-    pub error: Option<String>,
+    pub error: Option<CommandError>,
+    /// Download source actually used (`direct` or a mirror) for URL installs.
+    pub source: Option<String>,
 }
 
 // ---------------------------------------------------------------------------
 // Plugin process (mirrors MCPProcess from mcp.rs)
 // ---------------------------------------------------------------------------
 
-enum ReadResult {
-    Line(String),
-    Eof,
-    Error(String),
+type RpcReply = Result<String, CommandError>;
+
+/// In-flight `plugin_invoke` calls keyed by the host-assigned JSON-RPC id.
+#[derive(Default)]
+struct PendingCalls {
+    /// Set once stdout closes; new calls fail immediately.
+    closed: bool,
+    /// host id → (caller's original id, waiter)
+    waiters: HashMap<u64, (serde_json::Value, tokio::sync::oneshot::Sender<RpcReply>)>,
+}
+
+type PendingMap = Arc<Mutex<PendingCalls>>;
+
+/// Fail every waiter and refuse new calls.
+fn close_pending(pending: &PendingMap, reason: CommandError) {
+    if let Ok(mut p) = pending.lock() {
+        p.closed = true;
+        for (_, (_, tx)) in p.waiters.drain() {
+            let _ = tx.send(Err(reason.clone()));
+        }
+    }
 }
 
 struct PluginProcess {
     child: Child,
-    stdin: ChildStdin,
-    #[allow(dead_code)]
-    stderr: ChildStderr,
-    line_rx: Receiver<ReadResult>,
+    /// Serializes writers; requests are multiplexed by JSON-RPC id.
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    pending: PendingMap,
+    /// Permissions declared in the manifest this instance was started with.
+    permissions: Arc<HashSet<String>>,
+    generation: u64,
+}
+
+/// Permission a plugin→host method needs, `Err` with the missing one if not granted.
+fn check_plugin_method(method: &str, granted: &HashSet<String>) -> Result<(), &'static str> {
+    let required = PLUGIN_METHOD_PERMISSIONS
+        .iter()
+        .find(|(m, _)| *m == method)
+        .map(|(_, p)| *p);
+    match required {
+        Some(perm) if granted.contains(perm) => Ok(()),
+        Some(perm) => Err(perm),
+        None if PRIVILEGED_NAMESPACES.iter().any(|ns| method.starts_with(ns)) => {
+            Err("unknown")
+        }
+        None => Ok(()),
+    }
+}
+
+/// Check a host→plugin call against the plugin's granted permissions.
+fn check_host_method(method: &str, granted: &HashSet<String>) -> Result<(), &'static str> {
+    let required = HOST_METHOD_PERMISSIONS
+        .iter()
+        .find(|(m, _)| *m == method)
+        .map(|(_, p)| *p)
+        .unwrap_or(&[]);
+    match required.iter().find(|p| !granted.contains(**p)) {
+        Some(missing) => Err(*missing),
+        None => Ok(()),
+    }
+}
+
+fn emit_permission_denied(app: &tauri::AppHandle, plugin_id: &str, method: &str, permission: &str) {
+    log::warn!("{}: {} denied (needs {})", plugin_id, method, permission);
+    let _ = app.emit(
+        "plugin:permission_denied",
+        serde_json::json!({
+            "pluginId": plugin_id,
+            "method": method,
+            "permission": permission,
+        }),
+    );
+}
+
+/// A started plugin, tracked separately from `processes` so a pending
+/// auto-restart still counts as "should be running".
+struct PluginInstance {
+    pid: u32,
+    generation: u64,
+    /// Process died and an auto-restart is pending.
+    exited: bool,
+    started_at: Instant,
+    /// Epoch ms of the last `plugin_invoke` call.
+    last_invoke_ms: Option<u64>,
+}
+
+type LogBuffer = Arc<Mutex<VecDeque<String>>>;
+
+/// Read one line of at most `max` bytes into `buf`. Longer lines are cut and
+/// the remainder skipped without buffering it. Returns `None` at EOF,
+/// otherwise whether the line was truncated.
+fn read_capped_line<R: BufRead>(
+    reader: &mut R,
+    max: usize,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<Option<bool>> {
+    buf.clear();
+    let mut truncated = false;
+    let mut read_any = false;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(if read_any { Some(truncated) } else { None });
+        }
+        read_any = true;
+        let (chunk, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(i) => (&available[..i], i + 1),
+            None => (available, available.len()),
+        };
+        let room = max.saturating_sub(buf.len());
+        if chunk.len() > room {
+            truncated = true;
+        }
+        buf.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        let found_newline = done > chunk.len();
+        reader.consume(done);
+        if found_newline {
+            return Ok(Some(truncated));
+        }
+    }
+}
+
+/// Destination for one plugin's stderr: ring buffer, rotating log file and
+/// throttled `plugin:log` events.
+struct PluginLogSink {
+    app: tauri::AppHandle,
+    plugin_id: String,
+    buffer: LogBuffer,
+    file_path: std::path::PathBuf,
+    file: Option<std::fs::File>,
+    file_len: u64,
+    window_start: Instant,
+    window_events: u32,
+    suppressed: u32,
+}
+
+impl PluginLogSink {
+    fn new(app: tauri::AppHandle, plugin_id: &str, logs_dir: std::path::PathBuf, buffer: LogBuffer) -> Self {
+        let _ = std::fs::create_dir_all(&logs_dir);
+        let file_path = logs_dir.join("plugin.log");
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file_path)
+            .ok();
+        let file_len = file
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        Self {
+            app,
+            plugin_id: plugin_id.to_string(),
+            buffer,
+            file_path,
+            file,
+            file_len,
+            window_start: Instant::now(),
+            window_events: 0,
+            suppressed: 0,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        push_bounded(&self.buffer, line.clone(), PLUGIN_LOG_BUFFER_LINES);
+        self.write_file(&line);
+
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            if self.suppressed > 0 {
+                self.emit(format!("[moraya] {} log lines not streamed", self.suppressed));
+            }
+            self.window_start = Instant::now();
+            self.window_events = 0;
+            self.suppressed = 0;
+        }
+        if self.window_events < PLUGIN_LOG_EVENTS_PER_SEC {
+            self.window_events += 1;
+            self.emit(line);
+        } else {
+            self.suppressed += 1;
+        }
+    }
+
+    fn emit(&self, line: String) {
+        let _ = self.app.emit(
+            "plugin:log",
+            serde_json::json!({ "pluginId": self.plugin_id, "line": line }),
+        );
+    }
+
+    fn write_file(&mut self, line: &str) {
+        if self.file_len >= PLUGIN_LOG_FILE_MAX_BYTES {
+            self.file = None;
+            let _ = std::fs::rename(&self.file_path, self.file_path.with_extension("log.1"));
+            self.file = std::fs::File::create(&self.file_path).ok();
+            self.file_len = 0;
+        }
+        if let Some(f) = self.file.as_mut() {
+            if writeln!(f, "{}", line).is_ok() {
+                self.file_len += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+fn push_bounded(buffer: &LogBuffer, line: String, cap: usize) {
+    if let Ok(mut buf) = buffer.lock() {
+        while buf.len() >= cap {
+            buf.pop_front();
+        }
+        buf.push_back(line);
+    }
+}
+
+/// Drain a plugin's stderr into its log sink until the pipe closes.
+fn spawn_stderr_thread(stderr: ChildStderr, mut sink: PluginLogSink) {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stderr);
+        let mut buf = Vec::new();
+        while let Ok(Some(truncated)) = read_capped_line(&mut reader, PLUGIN_MAX_LINE, &mut buf) {
+            let mut line = String::from_utf8_lossy(&buf).trim_end().to_string();
+            if truncated {
+                line.push_str(" …[truncated]");
+            }
+            sink.push(line);
+        }
+    });
+}
+
+enum Routed {
+    /// Response handed to its waiter (or unparseable / stale).
+    Handled,
+    /// Notification or plugin→host request for the frontend.
+    Forward(serde_json::Value),
+    /// Plugin→host call without the required permission.
+    Denied {
+        id: Option<serde_json::Value>,
+        method: String,
+        permission: &'static str,
+    },
+    /// Permitted `host/fs.*` call, answered by the host itself.
+    HostFs {
+        id: Option<serde_json::Value>,
+        method: String,
+        params: serde_json::Value,
+    },
+}
+
+/// Deliver one stdout line: responses resolve their waiter (with the
+/// caller's id restored); requests and notifications are permission-checked
+/// and forwarded.
+fn route_plugin_message(line: &str, pending: &PendingMap, granted: &HashSet<String>) -> Routed {
+    let Ok(mut msg) = serde_json::from_str::<serde_json::Value>(line) else {
+        return Routed::Handled;
+    };
+    let is_response = msg.get("result").is_some() || msg.get("error").is_some();
+    if is_response {
+        let host_id = msg.get("id").and_then(|v| v.as_u64());
+        let waiter = host_id
+            .and_then(|id| pending.lock().ok().and_then(|mut p| p.waiters.remove(&id)));
+        if let Some((original_id, tx)) = waiter {
+            msg["id"] = original_id;
+            let _ = tx.send(Ok(msg.to_string()));
+        }
+        return Routed::Handled;
+    }
+    if let Some(method) = msg.get("method").and_then(|m| m.as_str()) {
+        let id = msg.get("id").filter(|v| !v.is_null()).cloned();
+        if let Err(permission) = check_plugin_method(method, granted) {
+            return Routed::Denied {
+                id,
+                method: method.to_string(),
+                permission,
+            };
+        }
+        if method.starts_with("host/fs.") {
+            return Routed::HostFs {
+                id,
+                method: method.to_string(),
+                params: msg.get("params").cloned().unwrap_or_default(),
+            };
+        }
+    }
+    Routed::Forward(msg)
+}
+
+/// What one plugin's `host/fs.*` calls may touch: the open workspace
+/// roots and its own data directory.
+#[derive(Clone)]
+struct HostFsScope {
+    /// Shared with the manager, so it follows the open workspace.
+    workspace_roots: Arc<Mutex<Vec<std::path::PathBuf>>>,
+    data_dir: std::path::PathBuf,
+}
+
+impl HostFsScope {
+    /// Allowed roots; relative paths resolve against the first one.
+    fn roots(&self) -> Vec<std::path::PathBuf> {
+        let mut roots = self
+            .workspace_roots
+            .lock()
+            .map(|r| r.clone())
+            .unwrap_or_default();
+        roots.push(self.data_dir.clone());
+        roots
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum HostFsError {
+    /// Blocked by the path rules; logged to the plugin log.
+    Denied(String),
+    Failed(String),
+}
+
+/// Resolve a plugin-supplied path through `validate_path`, then require it
+/// to lie inside one of the scope's roots.
+fn resolve_host_path(
+    scope: &HostFsScope,
+    raw: &str,
+) -> Result<std::path::PathBuf, HostFsError> {
+    if raw.trim().is_empty() {
+        return Err(HostFsError::Failed("path is missing".to_string()));
+    }
+    let roots = scope.roots();
+    let candidate = if std::path::Path::new(raw).is_absolute() {
+        std::path::PathBuf::from(raw)
+    } else {
+        roots[0].join(raw)
+    };
+    let path = file_cmd::validate_path(&candidate.to_string_lossy())
+        .map_err(|e| HostFsError::Denied(format!("{}: {}", raw, e.message)))?;
+    if !roots.iter().any(|root| path.starts_with(root)) {
+        return Err(HostFsError::Denied(format!(
+            "{} is outside the workspace",
+            raw
+        )));
+    }
+    Ok(path)
+}
+
+/// Answer `host/fs.read` (`{path, encoding?}` → `{content, encoding}`) and
+/// `host/fs.write` (`{path, content, encoding?}` → `{bytes}`). `encoding` is
+/// `utf8` (default) or `base64`. Writes are saved like the editor's, with
+/// `file::save_document`.
+fn handle_host_fs(
+    scope: &HostFsScope,
+    method: &str,
+    params: &serde_json::Value,
+) -> Result<serde_json::Value, HostFsError> {
+    use base64::Engine;
+    let failed = |e: std::io::Error| HostFsError::Failed(file_cmd::sanitize_io_error(e).message);
+    let path = resolve_host_path(
+        scope,
+        params.get("path").and_then(|p| p.as_str()).unwrap_or(""),
+    )?;
+    let base64 = match params.get("encoding").and_then(|e| e.as_str()) {
+        None | Some("utf8") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err(HostFsError::Failed(format!("unknown encoding: {}", other)));
+        }
+    };
+    match method {
+        "host/fs.read" => {
+            let size = std::fs::metadata(&path).map_err(failed)?.len();
+            if size > HOST_FS_MAX_READ {
+                return Err(HostFsError::Failed(format!(
+                    "file is larger than {} MB",
+                    HOST_FS_MAX_READ / (1024 * 1024)
+                )));
+            }
+            let bytes = std::fs::read(&path).map_err(failed)?;
+            let content = if base64 {
+                base64::engine::general_purpose::STANDARD.encode(&bytes)
+            } else {
+                String::from_utf8(bytes).map_err(|_| {
+                    HostFsError::Failed("file is not UTF-8 text; use encoding base64".to_string())
+                })?
+            };
+            Ok(serde_json::json!({
+                "content": content,
+                "encoding": if base64 { "base64" } else { "utf8" },
+            }))
+        }
+        "host/fs.write" => {
+            let content = params
+                .get("content")
+                .and_then(|c| c.as_str())
+                .ok_or_else(|| HostFsError::Failed("content is missing".to_string()))?;
+            let bytes = if base64 {
+                base64::engine::general_purpose::STANDARD
+                    .decode(content)
+                    .map_err(|_| HostFsError::Failed("content is not valid base64".to_string()))?
+            } else {
+                content.as_bytes().to_vec()
+            };
+            if path.is_dir() {
+                return Err(HostFsError::Failed("path is a directory".to_string()));
+            }
+            file_cmd::save_document(&path, &bytes)
+                .map_err(|e| HostFsError::Failed(e.message))?;
+            Ok(serde_json::json!({ "bytes": bytes.len() }))
+        }
+        _ => Err(HostFsError::Failed(format!("unknown method: {}", method))),
+    }
 }
 
-fn spawn_reader_thread(stdout: ChildStdout) -> Receiver<ReadResult> {
-    let (tx, rx): (SyncSender<ReadResult>, Receiver<ReadResult>) =
-        std::sync::mpsc::sync_channel(32);
+/// Write one JSON-RPC message to a plugin's stdin.
+fn write_to_plugin(stdin: &Arc<Mutex<Option<ChildStdin>>>, msg: &serde_json::Value) {
+    if let Some(w) = stdin.lock().ok().as_mut().and_then(|g| g.as_mut()) {
+        let _ = writeln!(w, "{}", msg);
+        let _ = w.flush();
+    }
+}
+
+/// Record a sandbox enforcement event in the plugin's log and stream it.
+fn log_enforcement(app: &tauri::AppHandle, plugin_id: &str, buffer: &LogBuffer, message: &str) {
+    log::warn!("{}: {}", plugin_id, message);
+    let line = format!("[moraya] {}", message);
+    push_bounded(buffer, line.clone(), PLUGIN_LOG_BUFFER_LINES);
+    let _ = app.emit(
+        "plugin:log",
+        serde_json::json!({ "pluginId": plugin_id, "line": line }),
+    );
+}
+
+/// Read plugin stdout until it closes, dispatching responses to waiters and
+/// emitting everything else as `plugin:notification`.
+fn spawn_dispatcher_thread(
+    app: tauri::AppHandle,
+    plugin_id: String,
+    stdout: ChildStdout,
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    pending: PendingMap,
+    granted: Arc<HashSet<String>>,
+    fs_scope: HostFsScope,
+    log_buffer: LogBuffer,
+) {
     std::thread::spawn(move || {
         let mut reader = BufReader::new(stdout);
+        let mut buf = Vec::new();
         loop {
-            let mut line = String::new();
-            match reader.read_line(&mut line) {
-                Ok(0) => {
-                    let _ = tx.send(ReadResult::Eof);
-                    break;
-                }
-                Ok(_) => {
-                    if tx.send(ReadResult::Line(line)).is_err() {
-                        break;
+            match read_capped_line(&mut reader, PLUGIN_MAX_LINE, &mut buf) {
+                Ok(Some(false)) => {
+                    let line = String::from_utf8_lossy(&buf);
+                    let line = line.trim();
+                    if !line.starts_with('{') {
+                        continue;
+                    }
+                    match route_plugin_message(line, &pending, &granted) {
+                        Routed::Handled => {}
+                        Routed::Forward(msg) => {
+                            let _ = app.emit(
+                                "plugin:notification",
+                                serde_json::json!({ "pluginId": plugin_id, "message": msg }),
+                            );
+                        }
+                        Routed::Denied { id, method, permission } => {
+                            emit_permission_denied(&app, &plugin_id, &method, permission);
+                            if let Some(id) = id {
+                                let reply = serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "error": {
+                                        "code": RPC_PERMISSION_DENIED,
+                                        "message": format!("permission denied: {} requires {}", method, permission),
+                                    },
+                                });
+                                write_to_plugin(&stdin, &reply);
+                            }
+                        }
+                        Routed::HostFs { id, method, params } => {
+                            let outcome = handle_host_fs(&fs_scope, &method, &params);
+                            if let Err(HostFsError::Denied(reason)) = &outcome {
+                                log_enforcement(
+                                    &app,
+                                    &plugin_id,
+                                    &log_buffer,
+                                    &format!("{} denied: {}", method, reason),
+                                );
+                            }
+                            let Some(id) = id else {
+                                continue;
+                            };
+                            let reply = match outcome {
+                                Ok(result) => serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "result": result,
+                                }),
+                                Err(HostFsError::Denied(message)) => serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "error": { "code": RPC_PERMISSION_DENIED, "message": message },
+                                }),
+                                Err(HostFsError::Failed(message)) => serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "id": id,
+                                    "error": { "code": RPC_HOST_ERROR, "message": message },
+                                }),
+                            };
+                            write_to_plugin(&stdin, &reply);
+                        }
                     }
                 }
-                Err(e) => {
-                    let _ = tx.send(ReadResult::Error(e.to_string()));
-                    break;
+                Ok(Some(true)) => {
+                    log::warn!("{}: dropped stdout message over {} bytes", plugin_id, PLUGIN_MAX_LINE);
                 }
+                Ok(None) | Err(_) => break,
             }
         }
+        close_pending(&pending, plugin_exited());
     });
-    rx
 }
 
 /// Manages plugin sidecar processes (one per enabled plugin).
 pub struct PluginProcessManager {
     processes: Mutex<HashMap<String, PluginProcess>>,
-    pids: Mutex<HashMap<String, u32>>,
+    instances: Mutex<HashMap<String, PluginInstance>>,
+    next_generation: AtomicU64,
+    /// stderr ring buffers; kept after exit so crash output stays readable.
+    logs: Mutex<HashMap<String, LogBuffer>>,
+    /// `None` until loaded from disk or fetched.
+    blacklist: Mutex<Option<BlacklistCache>>,
+    /// Dev plugins of this session; never written to `plugins/state.json`.
+    dev_plugins: Mutex<HashMap<String, PluginStateEntry>>,
+    /// Entry file watchers for dev plugins with hot-restart on.
+    dev_watchers: Mutex<HashMap<String, notify::RecommendedWatcher>>,
+    /// Last `initialize` outcome per plugin; kept after exit for debugging.
+    handshakes: Mutex<HashMap<String, PluginHandshake>>,
+    /// UI locale set by the frontend; `LANG` is used until then.
+    locale: Mutex<Option<String>>,
+    /// Canonical workspace folders `host/fs.*` may reach, set by the frontend.
+    workspace_roots: Arc<Mutex<Vec<std::path::PathBuf>>>,
 }
 
 impl PluginProcessManager {
     pub fn new() -> Self {
         Self {
             processes: Mutex::new(HashMap::new()),
-            pids: Mutex::new(HashMap::new()),
+            instances: Mutex::new(HashMap::new()),
+            next_generation: AtomicU64::new(1),
+            logs: Mutex::new(HashMap::new()),
+            blacklist: Mutex::new(None),
+            dev_plugins: Mutex::new(HashMap::new()),
+            dev_watchers: Mutex::new(HashMap::new()),
+            handshakes: Mutex::new(HashMap::new()),
+            locale: Mutex::new(None),
+            workspace_roots: Arc::default(),
+        }
+    }
+
+    fn locale(&self) -> String {
+        self.locale
+            .lock()
+            .ok()
+            .and_then(|l| l.clone())
+            .unwrap_or_else(|| {
+                // "zh_CN.UTF-8" → "zh-CN"
+                std::env::var("LANG")
+                    .ok()
+                    .and_then(|l| l.split('.').next().map(|l| l.replace('_', "-")))
+                    .filter(|l| !l.is_empty() && l != "C" && l != "POSIX")
+                    .unwrap_or_else(|| "en".to_string())
+            })
+    }
+
+    /// Reason `plugin_id` is blacklisted, from the cached list (never hits the network).
+    fn blacklist_reason(&self, app: &tauri::AppHandle, plugin_id: &str) -> Option<String> {
+        let mut cache = self.blacklist.lock().ok()?;
+        if cache.is_none() {
+            *cache = Some(read_blacklist_cache(app).unwrap_or_default());
         }
+        cache
+            .as_ref()?
+            .entries
+            .iter()
+            .find(|e| e.id == plugin_id)
+            .map(|e| e.reason.clone())
+    }
+
+    fn log_buffer(&self, plugin_id: &str) -> LogBuffer {
+        self.logs
+            .lock()
+            .map(|mut logs| logs.entry(plugin_id.to_string()).or_default().clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether `generation` is still the live instance of `plugin_id`.
+    fn is_current(&self, plugin_id: &str, generation: u64) -> bool {
+        self.instances
+            .lock()
+            .map(|i| i.get(plugin_id).map(|i| i.generation) == Some(generation))
+            .unwrap_or(false)
     }
 }
 
@@ -179,6 +948,85 @@ fn is_safe_env_var(key: &str) -> bool {
         .any(|prefix| key.starts_with(prefix))
 }
 
+/// Plugin ids are lowercase ASCII, digits and `-`, optionally `dev:`-prefixed.
+fn check_plugin_id(plugin_id: &str) -> Result<(), CommandError> {
+    let bare_id = plugin_id.strip_prefix(DEV_PLUGIN_PREFIX).unwrap_or(plugin_id);
+    if bare_id.is_empty()
+        || !bare_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(CommandError::new(ErrorCode::PluginInvalidId, "Invalid plugin ID"));
+    }
+    Ok(())
+}
+
+fn is_dev_plugin(plugin_id: &str) -> bool {
+    plugin_id.starts_with(DEV_PLUGIN_PREFIX)
+}
+
+/// Pick the translation for `locale` from a `*Localized` map: exact tag,
+/// then its language (`zh-CN` → `zh`), then any tag of that language,
+/// then `base`.
+fn localized_text<'a>(map: &'a HashMap<String, String>, base: &'a str, locale: &str) -> &'a str {
+    let lang = locale.split(['-', '_']).next().unwrap_or(locale);
+    let same_lang = |tag: &str| {
+        tag.split(['-', '_'])
+            .next()
+            .is_some_and(|l| l.eq_ignore_ascii_case(lang))
+    };
+    map.iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case(locale))
+        .or_else(|| map.iter().find(|(tag, _)| tag.eq_ignore_ascii_case(lang)))
+        .or_else(|| map.iter().find(|(tag, _)| same_lang(tag)))
+        .map(|(_, text)| text.as_str())
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or(base)
+}
+
+/// Set `displayName` / `displayDescription` on a market entry for `locale`.
+fn localize_market_entry(entry: &mut serde_json::Value, locale: &str) {
+    let string_map = |key: &str| -> HashMap<String, String> {
+        entry
+            .get(key)
+            .and_then(|m| serde_json::from_value(m.clone()).ok())
+            .unwrap_or_default()
+    };
+    let names = string_map("nameLocalized");
+    let descriptions = string_map("descriptionLocalized");
+    let base_name = entry
+        .get("name")
+        .and_then(|n| n.as_str())
+        .or_else(|| entry.get("id").and_then(|i| i.as_str()))
+        .unwrap_or_default()
+        .to_string();
+    let base_description = entry
+        .get("description")
+        .and_then(|d| d.as_str())
+        .unwrap_or_default()
+        .to_string();
+    entry["displayName"] = serde_json::json!(localized_text(&names, &base_name, locale));
+    entry["displayDescription"] =
+        serde_json::json!(localized_text(&descriptions, &base_description, locale));
+}
+
+fn localize_registry_result(result: &mut serde_json::Value, locale: &str) {
+    if let Some(plugins) = result.get_mut("plugins").and_then(|p| p.as_array_mut()) {
+        for entry in plugins {
+            localize_market_entry(entry, locale);
+        }
+    }
+}
+
+/// `plugins/<id>` for installed plugins, `plugins/.dev/<id>` for dev plugins
+/// (keeps `:` out of paths and dev data out of `reconcile_state`).
+fn plugin_storage_dir(plugins_root: &std::path::Path, plugin_id: &str) -> std::path::PathBuf {
+    match plugin_id.strip_prefix(DEV_PLUGIN_PREFIX) {
+        Some(id) => plugins_root.join(".dev").join(id),
+        None => plugins_root.join(plugin_id),
+    }
+}
+
 fn epoch_ms() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -186,131 +1034,479 @@ fn epoch_ms() -> u64 {
         .as_millis() as u64
 }
 
-fn sha256_file(path: &std::path::Path) -> Result<String, String> {
-    let bytes = std::fs::read(path).map_err(|_| "Failed to read file for hash".to_string())?;
-    let hash = Sha256::digest(&bytes);
-    Ok(hex::encode(hash))
+/// `app_data_dir()/plugins`
+fn plugins_root(app: &tauri::AppHandle) -> Result<std::path::PathBuf, CommandError> {
+    app.path()
+        .app_data_dir()
+        .map(|d| d.join("plugins"))
+        .map_err(|_| app_data_unavailable())
 }
 
-fn current_platform() -> &'static str {
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    return "darwin-aarch64";
-    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-    return "darwin-x86_64";
-    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
-    return "win32-arm64";
-    #[cfg(all(target_os = "windows", not(target_arch = "aarch64")))]
-    return "win32";
-    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-    return "linux-x86_64";
-    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
-    return "linux-aarch64";
-    #[cfg(not(any(
-        target_os = "macos",
-        target_os = "windows",
-        target_os = "linux"
-    )))]
-    return "unknown";
+fn app_data_unavailable() -> CommandError {
+    CommandError::new(ErrorCode::Internal, "Cannot resolve the app data directory")
 }
 
-/// Validate an extracted plugin.json manifest.
-/// Returns a list of errors; empty = valid.
-fn validate_manifest(manifest: &PluginManifest) -> (Vec<String>, Vec<String>) {
-    let mut errors = Vec::new();
-    let mut warnings = Vec::new();
+fn plugin_exited() -> CommandError {
+    CommandError::new(ErrorCode::PluginExited, "Plugin process exited unexpectedly")
+}
 
-    // id format: ^[a-z][a-z0-9-]{2,63}$
-    if manifest.id.is_empty() {
-        errors.push("id 不能为空".to_string());
-    } else if !manifest
-        .id
-        .chars()
-        .next()
-        .map(|c| c.is_ascii_lowercase())
-        .unwrap_or(false)
-        || !manifest
-            .id
-            .chars()
-            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
-        || manifest.id.len() < 3
-        || manifest.id.len() > 64
-    {
-        errors.push("id 格式非法（仅小写字母、数字、连字符，长度 3-64）".to_string());
-    }
+fn plugin_stopped() -> CommandError {
+    CommandError::new(ErrorCode::PluginNotRunning, "Plugin was stopped")
+}
 
-    if manifest.name.trim().is_empty() {
-        errors.push("缺少插件名称 (name)".to_string());
-    }
+fn io_failed(message: &str) -> CommandError {
+    CommandError::new(ErrorCode::IoError, message)
+}
 
-    // semver check: x.y.z
-    let semver_ok = {
-        let parts: Vec<&str> = manifest.version.split('.').collect();
-        parts.len() == 3 && parts.iter().all(|p| p.parse::<u32>().is_ok())
+fn invalid_package(message: impl Into<String>) -> CommandError {
+    CommandError::new(ErrorCode::PluginInvalidPackage, message)
+}
+
+fn files_missing(message: &str) -> CommandError {
+    CommandError::new(ErrorCode::PluginFilesMissing, message)
+}
+
+fn http_client_failed() -> CommandError {
+    CommandError::new(ErrorCode::Internal, "Failed to initialize the HTTP client")
+}
+
+fn download_failed(status: u16) -> CommandError {
+    CommandError::new(ErrorCode::NetworkError, format!("Download failed, HTTP {}", status))
+        .with_details(serde_json::json!({ "status": status }))
+}
+
+/// Serializes read-modify-write cycles on `plugins/state.json`.
+static STATE_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Read `plugins/state.json`. A missing or corrupt file yields an empty list.
+fn read_state_file(root: &std::path::Path) -> Vec<PluginStateEntry> {
+    let path = root.join(PLUGIN_STATE_FILE);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Vec::new();
     };
-    if !semver_ok {
-        errors.push("版本号格式错误，需为 x.y.z".to_string());
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        log::warn!("state.json unreadable, ignoring: {}", e);
+        Vec::new()
+    })
+}
+
+/// Write `plugins/state.json` atomically (temp file + rename).
+fn write_state_file(
+    root: &std::path::Path,
+    entries: &[PluginStateEntry],
+) -> Result<(), CommandError> {
+    std::fs::create_dir_all(root).map_err(|_| io_failed("Cannot create the plugins directory"))?;
+    let json = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    let tmp = root.join(format!("{}.tmp", PLUGIN_STATE_FILE));
+    std::fs::write(&tmp, json).map_err(|_| io_failed("Failed to write plugin state"))?;
+    std::fs::rename(&tmp, root.join(PLUGIN_STATE_FILE))
+        .map_err(|_| io_failed("Failed to write plugin state"))
+}
+
+/// Apply `f` to the persisted state under the state-file lock.
+fn update_state_file(
+    root: &std::path::Path,
+    f: impl FnOnce(&mut Vec<PluginStateEntry>),
+) -> Result<(), CommandError> {
+    let _guard = STATE_FILE_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut entries = read_state_file(root);
+    f(&mut entries);
+    write_state_file(root, &entries)
+}
+
+/// Insert or replace the entry with the same id.
+fn upsert_entry(entries: &mut Vec<PluginStateEntry>, entry: PluginStateEntry) {
+    match entries.iter_mut().find(|e| e.id == entry.id) {
+        Some(existing) => *existing = entry,
+        None => entries.push(entry),
     }
+}
 
-    if manifest.api_version.is_empty() {
-        errors.push("缺少 apiVersion".to_string());
-    } else if manifest.api_version != "1" {
-        errors.push(format!(
-            "需要 API v{}，当前 Moraya 支持至 v1，请升级 Moraya",
-            manifest.api_version
-        ));
+/// Reconcile persisted entries with what is actually on disk:
+/// entries whose directory is gone are dropped, and plugin directories
+/// missing from the state (e.g. after an app reinstall) are re-registered
+/// from their plugin.json as disabled.
+fn reconcile_state(
+    root: &std::path::Path,
+    mut entries: Vec<PluginStateEntry>,
+) -> Vec<PluginStateEntry> {
+    entries.retain(|e| root.join(&e.id).join("plugin.json").is_file());
+    for e in entries.iter_mut() {
+        e.plugin_dir = root.join(&e.id).to_string_lossy().into_owned();
     }
 
-    if manifest.protocol != "jsonrpc-stdio" {
+    let Ok(dir) = std::fs::read_dir(root) else {
+        return entries;
+    };
+    for item in dir.flatten() {
+        let path = item.path();
+        let name = item.file_name().to_string_lossy().into_owned();
+        if !path.is_dir() || name.starts_with('.') || entries.iter().any(|e| e.id == name) {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(path.join("plugin.json")) else {
+            continue;
+        };
+        let Ok(manifest) = serde_json::from_str::<PluginManifest>(&content) else {
+            log::warn!("skipping {}: invalid plugin.json", name);
+            continue;
+        };
+        if manifest.id != name || !validate_manifest(&manifest).0.is_empty() {
+            log::warn!("skipping {}: manifest does not match directory", name);
+            continue;
+        }
+        let installed_at = item
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_else(epoch_ms);
+        entries.push(PluginStateEntry {
+            id: manifest.id.clone(),
+            enabled: false,
+            plugin_dir: path.to_string_lossy().into_owned(),
+            installed_at,
+            manifest,
+        });
+    }
+    entries
+}
+
+fn blacklist_cache_path(app: &tauri::AppHandle) -> Option<std::path::PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|d| d.join("plugin-blacklist-cache.json"))
+}
+
+fn read_blacklist_cache(app: &tauri::AppHandle) -> Option<BlacklistCache> {
+    let content = std::fs::read_to_string(blacklist_cache_path(app)?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn parse_blacklist(data: &serde_json::Value) -> Vec<BlacklistEntry> {
+    data.get("blacklist")
+        .and_then(|b| b.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|e| serde_json::from_value::<BlacklistEntry>(e.clone()).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn blacklisted_error(reason: &str) -> CommandError {
+    let message = if reason.is_empty() {
+        "plugin is blacklisted".to_string()
+    } else {
+        format!("plugin is blacklisted: {}", reason)
+    };
+    CommandError::new(ErrorCode::PluginBlacklisted, message)
+        .with_details(serde_json::json!({ "reason": reason }))
+}
+
+#[derive(Debug, PartialEq)]
+enum SignatureError {
+    Missing,
+    Invalid,
+}
+
+/// Verify a minisign signature over `data`.
+fn verify_signature(
+    data: &[u8],
+    signature: Option<&str>,
+    public_key: &str,
+) -> Result<(), SignatureError> {
+    let signature = signature
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .ok_or(SignatureError::Missing)?;
+    let pk = minisign_verify::PublicKey::from_base64(public_key.trim())
+        .map_err(|_| SignatureError::Invalid)?;
+    let sig =
+        minisign_verify::Signature::decode(signature).map_err(|_| SignatureError::Invalid)?;
+    pk.verify(data, &sig, false)
+        .map_err(|_| SignatureError::Invalid)
+}
+
+fn signature_error(e: SignatureError) -> CommandError {
+    match e {
+        SignatureError::Missing => CommandError::new(
+            ErrorCode::PluginSignatureMissing,
+            "Signature is missing; installation blocked",
+        ),
+        SignatureError::Invalid => CommandError::new(
+            ErrorCode::PluginSignatureInvalid,
+            "Signature is invalid; installation blocked",
+        ),
+    }
+}
+
+/// Whether `url` may be served through a mirror.
+fn is_mirrorable(url: &str) -> bool {
+    url.starts_with("https://raw.githubusercontent.com/")
+        || (url.starts_with("https://github.com/") && url.contains("/releases/download/"))
+}
+
+/// `url` as served by `source` (`None` = direct, `Some(i)` = `REGISTRY_MIRRORS[i]`).
+fn mirror_url(url: &str, source: Option<usize>) -> String {
+    match source.and_then(|i| REGISTRY_MIRRORS.get(i)) {
+        Some(mirror) if is_mirrorable(url) => format!("{}{}", mirror, url),
+        _ => url.to_string(),
+    }
+}
+
+fn source_label(source: Option<usize>) -> String {
+    source
+        .and_then(|i| REGISTRY_MIRRORS.get(i))
+        .map(|m| m.to_string())
+        .unwrap_or_else(|| "direct".to_string())
+}
+
+/// Sources to try for `url`: the preferred one first, then direct, then
+/// the remaining mirrors in order.
+fn source_order(url: &str, preferred: Option<usize>) -> Vec<Option<usize>> {
+    if !is_mirrorable(url) {
+        return vec![None];
+    }
+    let preferred = preferred.filter(|i| *i < REGISTRY_MIRRORS.len());
+    let mut order = vec![preferred];
+    order.extend(
+        std::iter::once(None)
+            .chain((0..REGISTRY_MIRRORS.len()).map(Some))
+            .filter(|s| *s != preferred),
+    );
+    order
+}
+
+fn read_registry_mirror(app: &tauri::AppHandle) -> Option<usize> {
+    let path = app.path().app_data_dir().ok()?.join(REGISTRY_MIRROR_FILE);
+    let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    value
+        .get("index")
+        .and_then(|i| i.as_u64())
+        .map(|i| i as usize)
+        .filter(|i| *i < REGISTRY_MIRRORS.len())
+}
+
+/// GET `url`, falling back through the mirrors when a source can't be
+/// reached and `allow_mirrors` is set. HTTP errors are returned as-is. Also
+/// returns the source used.
+async fn get_with_mirrors(
+    client: &reqwest::Client,
+    url: &str,
+    preferred: Option<usize>,
+    allow_mirrors: bool,
+) -> Result<(reqwest::Response, Option<usize>), CommandError> {
+    let order = if allow_mirrors {
+        source_order(url, preferred)
+    } else {
+        vec![None]
+    };
+    for source in order {
+        match client.get(mirror_url(url, source)).send().await {
+            Ok(resp) => return Ok((resp, source)),
+            Err(e) => log::warn!(
+                "{} unreachable via {}: {}",
+                url,
+                source_label(source),
+                e.without_url()
+            ),
+        }
+    }
+    Err(CommandError::new(
+        ErrorCode::NetworkError,
+        "Cannot reach GitHub or a mirror; check your network connection",
+    ))
+}
+
+async fn fetch_text(client: &reqwest::Client, url: &str) -> Option<String> {
+    let resp = client.get(url).send().await.ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    resp.text().await.ok()
+}
+
+/// How a plugin is launched, resolved from the manifest `entry` map.
+#[derive(Debug, PartialEq)]
+enum PluginEntry<'a> {
+    /// Platform-keyed self-contained binary (relative path).
+    Binary(&'a str),
+    /// Script run by an interpreter found on PATH.
+    Script { runtime: &'a str, script: &'a str },
+}
+
+fn is_safe_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains("..")
+        && !path.starts_with('/')
+        && !path.starts_with('\\')
+        && !path.contains(':')
+}
+
+/// Pick the entry for this platform. The interpreter form is recognized by a
+/// `runtime` key; otherwise the map is keyed by platform.
+fn resolve_entry(manifest: &PluginManifest) -> Result<PluginEntry<'_>, CommandError> {
+    if let Some(runtime) = manifest.entry.get("runtime") {
+        let script = manifest.entry.get("script").ok_or_else(|| {
+            CommandError::new(ErrorCode::PluginInvalidManifest, "entry is missing script")
+        })?;
+        return Ok(PluginEntry::Script { runtime, script });
+    }
+    let platform = current_platform();
+    manifest
+        .entry
+        .get(platform)
+        .map(|p| PluginEntry::Binary(p.as_str()))
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::PluginUnsupportedPlatform,
+                format!("This plugin does not support {}", platform),
+            )
+            .with_details(serde_json::json!({ "platform": platform }))
+        })
+}
+
+fn sha256_file(path: &std::path::Path) -> Result<String, CommandError> {
+    let bytes = std::fs::read(path).map_err(|_| io_failed("Failed to read file for hash"))?;
+    let hash = Sha256::digest(&bytes);
+    Ok(hex::encode(hash))
+}
+
+fn current_platform() -> &'static str {
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    return "darwin-aarch64";
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    return "darwin-x86_64";
+    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
+    return "win32-arm64";
+    #[cfg(all(target_os = "windows", not(target_arch = "aarch64")))]
+    return "win32";
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    return "linux-x86_64";
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    return "linux-aarch64";
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "windows",
+        target_os = "linux"
+    )))]
+    return "unknown";
+}
+
+/// Validate an extracted plugin.json manifest.
+/// Returns a list of errors; empty = valid.
+fn validate_manifest(manifest: &PluginManifest) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    // id format: ^[a-z][a-z0-9-]{2,63}$
+    if manifest.id.is_empty() {
+        errors.push("id must not be empty".to_string());
+    } else if !manifest
+        .id
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_lowercase())
+        .unwrap_or(false)
+        || !manifest
+            .id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        || manifest.id.len() < 3
+        || manifest.id.len() > 64
+    {
+        errors.push(
+            "id must be 3-64 lowercase letters, digits or hyphens, starting with a letter"
+                .to_string(),
+        );
+    }
+
+    if manifest.name.trim().is_empty() {
+        errors.push("name is missing".to_string());
+    }
+
+    // semver check: x.y.z
+    let semver_ok = {
+        let parts: Vec<&str> = manifest.version.split('.').collect();
+        parts.len() == 3 && parts.iter().all(|p| p.parse::<u32>().is_ok())
+    };
+    if !semver_ok {
+        errors.push("version must be in x.y.z format".to_string());
+    }
+
+    if manifest.api_version.is_empty() {
+        errors.push("apiVersion is missing".to_string());
+    } else if manifest.api_version != "1" {
         errors.push(format!(
-            "不支持的通信协议: {}",
-            manifest.protocol
+            "Requires plugin API v{}; this Moraya supports up to v1, please update Moraya",
+            manifest.api_version
         ));
     }
 
+    if manifest.protocol != "jsonrpc-stdio" {
+        errors.push(format!("Unsupported protocol: {}", manifest.protocol));
+    }
+
     if manifest.entry.is_empty() {
-        errors.push("缺少 entry 字段".to_string());
+        errors.push("entry is missing".to_string());
+    } else if let Some(runtime) = manifest.entry.get("runtime") {
+        if !ALLOWED_RUNTIMES.contains(&runtime.as_str()) {
+            errors.push(format!("Unsupported runtime: {}", runtime));
+        } else if super::mcp::check_command_exists(runtime.clone()).is_err() {
+            warnings.push(format!(
+                "Runtime {} is not installed; the plugin will not start",
+                runtime
+            ));
+        }
+        match manifest.entry.get("script") {
+            None => errors.push("entry is missing script".to_string()),
+            Some(script) if !is_safe_relative_path(script) => {
+                errors.push("entry[script] is not a safe relative path".to_string());
+            }
+            Some(_) => {}
+        }
     } else {
         // Check entry paths for directory traversal
         for (platform, path) in &manifest.entry {
             if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
-                errors.push(format!(
-                    "entry[{}] 路径存在安全风险，拒绝安装",
-                    platform
-                ));
+                errors.push(format!("entry[{}] is not a safe relative path", platform));
             }
         }
         // Check current platform is covered
         let platform = current_platform();
         if !manifest.entry.contains_key(platform) {
-            errors.push(format!("此插件暂不支持 {}", platform));
+            errors.push(format!("This plugin does not support {}", platform));
         }
     }
 
     // Permission whitelist check
     for perm in &manifest.permissions {
         if !ALLOWED_PERMISSIONS.contains(&perm.as_str()) {
-            errors.push(format!("声明了未知权限: {}，拒绝安装", perm));
+            errors.push(format!("Unknown permission: {}", perm));
         }
     }
 
     // sandboxLevel warning for system level
     if manifest.sandbox_level == "system" && !manifest.permissions.contains(&"net:external".to_string()) {
-        warnings.push("sandboxLevel 为 system 但未声明 net:external 权限".to_string());
+        warnings.push(
+            "sandboxLevel is system but net:external is not declared".to_string(),
+        );
     }
 
     (errors, warnings)
 }
 
 /// Extract plugin.json from a zip file (in memory, without writing to disk yet).
-fn read_manifest_from_zip(zip_path: &std::path::Path) -> Result<PluginManifest, String> {
-    let file = std::fs::File::open(zip_path).map_err(|_| "无法打开 zip 文件".to_string())?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|_| "zip 文件格式无效".to_string())?;
+fn read_manifest_from_zip(zip_path: &std::path::Path) -> Result<PluginManifest, CommandError> {
+    let mut archive = open_zip(zip_path)?;
 
     for i in 0..archive.len() {
         let mut entry = archive
             .by_index(i)
-            .map_err(|_| "读取 zip 条目失败".to_string())?;
+            .map_err(|_| invalid_package("Failed to read zip entry"))?;
         let name = entry.name().to_string();
         // Match plugin.json at the root (not nested)
         if name == "plugin.json" || name.ends_with("/plugin.json") {
@@ -318,76 +1514,292 @@ fn read_manifest_from_zip(zip_path: &std::path::Path) -> Result<PluginManifest,
             use std::io::Read;
             entry
                 .read_to_string(&mut content)
-                .map_err(|_| "plugin.json 读取失败".to_string())?;
-            let manifest: PluginManifest = serde_json::from_str(&content)
-                .map_err(|e| format!("plugin.json 格式错误，无法解析: {}", e))?;
-            return Ok(manifest);
+                .map_err(|_| invalid_package("Failed to read plugin.json"))?;
+            return parse_manifest(&content);
         }
     }
-    Err("zip 中未找到 plugin.json".to_string())
+    Err(invalid_package("plugin.json not found in the zip"))
+}
+
+fn open_zip(
+    zip_path: &std::path::Path,
+) -> Result<zip::ZipArchive<std::fs::File>, CommandError> {
+    let file = std::fs::File::open(zip_path)
+        .map_err(|_| CommandError::new(ErrorCode::FileNotFound, "Cannot open the zip file"))?;
+    zip::ZipArchive::new(file).map_err(|_| invalid_package("Invalid zip file"))
+}
+
+fn parse_manifest(json: &str) -> Result<PluginManifest, CommandError> {
+    serde_json::from_str(json).map_err(|e| {
+        CommandError::new(
+            ErrorCode::PluginInvalidManifest,
+            format!("plugin.json could not be parsed: {}", e),
+        )
+    })
 }
 
 /// Extract zip to a target directory with Zip Slip protection.
+/// Resolve a zip entry name under `target_dir`, rejecting Zip Slip paths.
+fn zip_entry_path(
+    target_dir: &std::path::Path,
+    raw_name: &str,
+) -> Result<std::path::PathBuf, CommandError> {
+    // Zip Slip protection: reject any path with .. or absolute paths
+    if raw_name.contains("..") || raw_name.starts_with('/') || raw_name.starts_with('\\') {
+        return Err(invalid_package("Zip contains an unsafe path"));
+    }
+
+    let out_path = target_dir.join(raw_name);
+
+    // Ensure the resolved path stays inside target_dir
+    let canonical_target = target_dir
+        .canonicalize()
+        .unwrap_or_else(|_| target_dir.to_path_buf());
+    if let Ok(canonical_out) = out_path.parent().map(|p| p.to_path_buf()).unwrap_or_default().canonicalize() {
+        if !canonical_out.starts_with(&canonical_target) {
+            return Err(invalid_package("Zip contains a path outside the target directory"));
+        }
+    }
+    Ok(out_path)
+}
+
+/// Caps and symlink policy for `extract_zip_checked`.
+struct ZipLimits {
+    max_bytes: u64,
+    max_files: usize,
+    /// Skip symlink entries instead of rejecting the archive.
+    skip_symlinks: bool,
+}
+
+const PLUGIN_ZIP_LIMITS: ZipLimits = ZipLimits {
+    max_bytes: PLUGIN_ZIP_MAX_BYTES,
+    max_files: PLUGIN_ZIP_MAX_FILES,
+    skip_symlinks: false,
+};
+
+const DATA_IMPORT_ZIP_LIMITS: ZipLimits = ZipLimits {
+    max_bytes: PLUGIN_DATA_IMPORT_MAX_BYTES,
+    max_files: PLUGIN_DATA_IMPORT_MAX_FILES,
+    skip_symlinks: true,
+};
+
 fn extract_zip_safe(
     zip_path: &std::path::Path,
     target_dir: &std::path::Path,
-) -> Result<(), String> {
-    let file = std::fs::File::open(zip_path).map_err(|_| "无法打开 zip 文件".to_string())?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|_| "zip 文件格式无效".to_string())?;
+) -> Result<(), CommandError> {
+    extract_zip_checked(zip_path, target_dir, &PLUGIN_ZIP_LIMITS).map(|_| ())
+}
+
+/// Extract with Zip Slip checks, Unix mode bits restored (minus setuid/
+/// setgid/sticky) and zip-bomb limits. Sizes are checked against the
+/// declared sizes up front and enforced again while reading.
+fn extract_zip_checked(
+    zip_path: &std::path::Path,
+    target_dir: &std::path::Path,
+    limits: &ZipLimits,
+) -> Result<PluginDataTransfer, CommandError> {
+    use std::io::Read;
+
+    let mut archive = open_zip(zip_path)?;
+    if archive.len() > limits.max_files {
+        return Err(invalid_package(format!(
+            "Zip has more than {} entries",
+            limits.max_files
+        ))
+        .with_details(serde_json::json!({ "limit": limits.max_files })));
+    }
+    let declared: u64 = (0..archive.len())
+        .filter_map(|i| archive.by_index(i).ok().map(|e| e.size()))
+        .sum();
+    if declared > limits.max_bytes {
+        return Err(too_large());
+    }
 
     std::fs::create_dir_all(target_dir)
-        .map_err(|_| "无法创建插件目录".to_string())?;
+        .map_err(|_| io_failed("Cannot create the plugin directory"))?;
 
+    let mut stats = PluginDataTransfer::default();
     for i in 0..archive.len() {
         let mut entry = archive
             .by_index(i)
-            .map_err(|_| "读取 zip 条目失败".to_string())?;
+            .map_err(|_| invalid_package("Failed to read zip entry"))?;
 
-        let raw_name = entry.name().to_string();
+        let out_path = zip_entry_path(target_dir, entry.name())?;
+        let mode = entry.unix_mode();
 
-        // Zip Slip protection: reject any path with .. or absolute paths
-        if raw_name.contains("..") || raw_name.starts_with('/') || raw_name.starts_with('\\') {
-            return Err("zip 文件包含非法路径，拒绝安装".to_string());
+        // S_IFLNK: a symlink entry could point reads outside the target dir
+        if mode.is_some_and(|m| m & 0o170000 == 0o120000) {
+            if limits.skip_symlinks {
+                stats.skipped_symlinks += 1;
+                continue;
+            }
+            return Err(invalid_package("Zip contains a symlink"));
         }
 
-        let out_path = target_dir.join(&raw_name);
-
-        // Ensure the resolved path stays inside target_dir
-        let canonical_target = target_dir
-            .canonicalize()
-            .unwrap_or_else(|_| target_dir.to_path_buf());
-        if let Ok(canonical_out) = out_path.parent().map(|p| p.to_path_buf()).unwrap_or_default().canonicalize() {
-            if !canonical_out.starts_with(&canonical_target) {
-                return Err("zip 文件包含路径穿越条目，拒绝安装".to_string());
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)
+                .map_err(|_| io_failed("Failed to create a directory"))?;
+            continue;
+        }
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|_| io_failed("Failed to create a directory"))?;
+        }
+        let mut out_file = std::fs::File::create(&out_path)
+            .map_err(|_| io_failed("Failed to create a file"))?;
+        // Declared sizes can lie; never write more than the remaining budget
+        let remaining = limits.max_bytes - stats.bytes;
+        let written = std::io::copy(&mut (&mut entry).take(remaining + 1), &mut out_file)
+            .map_err(|_| invalid_package("Failed to read zip contents"))?;
+        if written > remaining {
+            return Err(too_large());
+        }
+        stats.bytes += written;
+        stats.files += 1;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Some(mode) = mode {
+                let _ = std::fs::set_permissions(
+                    &out_path,
+                    std::fs::Permissions::from_mode(mode & 0o777),
+                );
             }
         }
+    }
+    Ok(stats)
+}
 
-        if entry.name().ends_with('/') {
-            std::fs::create_dir_all(&out_path)
-                .map_err(|_| "创建子目录失败".to_string())?;
-        } else {
-            if let Some(parent) = out_path.parent() {
-                std::fs::create_dir_all(parent)
-                    .map_err(|_| "创建父目录失败".to_string())?;
+fn too_large() -> CommandError {
+    invalid_package("Zip exceeds the size limit when extracted")
+}
+
+/// Extract `zip_path` into `root/<id>`, preserving an existing `data/` dir.
+///
+/// The archive is unpacked into a hidden staging dir first, so a corrupt zip
+/// never touches the installed version. The old directory is moved aside
+/// and restored if the swap fails.
+fn install_plugin_files(
+    root: &std::path::Path,
+    plugin_id: &str,
+    zip_path: &std::path::Path,
+) -> Result<std::path::PathBuf, CommandError> {
+    let plugin_dir = root.join(plugin_id);
+    let stamp = epoch_ms();
+    let staging = root.join(format!(".staging-{}-{}", plugin_id, stamp));
+    let backup = root.join(format!(".backup-{}-{}", plugin_id, stamp));
+
+    if let Err(e) = extract_zip_safe(zip_path, &staging) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let had_previous = plugin_dir.exists();
+    if had_previous {
+        if std::fs::rename(&plugin_dir, &backup).is_err() {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(io_failed("Cannot replace the previous version"));
+        }
+    }
+    if std::fs::rename(&staging, &plugin_dir).is_err() {
+        if had_previous {
+            let _ = std::fs::rename(&backup, &plugin_dir);
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(io_failed("Cannot install the plugin files"));
+    }
+
+    if had_previous {
+        let old_data = backup.join("data");
+        if old_data.is_dir() {
+            let new_data = plugin_dir.join("data");
+            let _ = std::fs::remove_dir_all(&new_data);
+            if std::fs::rename(&old_data, &new_data).is_err() {
+                // Keep the backup around rather than deleting user data
+                log::warn!(
+                    "failed to restore data/ for {}; left in {}",
+                    plugin_id,
+                    backup.display()
+                );
+                return Ok(plugin_dir);
             }
-            let mut out_file = std::fs::File::create(&out_path)
-                .map_err(|_| "创建文件失败".to_string())?;
-            use std::io::Read;
-            let mut buf = Vec::new();
-            entry
-                .read_to_end(&mut buf)
-                .map_err(|_| "读取 zip 内容失败".to_string())?;
-            use std::io::Write as IoWrite;
-            out_file
-                .write_all(&buf)
-                .map_err(|_| "写入文件失败".to_string())?;
         }
+        let _ = std::fs::remove_dir_all(&backup);
     }
-    Ok(())
+    Ok(plugin_dir)
 }
 
 /// Set executable bit on Unix for the plugin binary.
+/// Zip every regular file under `data_dir` into `output`. Symlinks are
+/// skipped so an export can't pull in files from outside the plugin.
+fn export_data_dir(
+    data_dir: &std::path::Path,
+    output: &std::path::Path,
+) -> Result<PluginDataTransfer, CommandError> {
+    fn walk(
+        dir: &std::path::Path,
+        prefix: &str,
+        zip: &mut zip::ZipWriter<std::fs::File>,
+        stats: &mut PluginDataTransfer,
+    ) -> Result<(), CommandError> {
+        let read_failed = || io_failed("Failed to read the plugin data directory");
+        let read = std::fs::read_dir(dir).map_err(|_| read_failed())?;
+        for item in read.flatten() {
+            let meta = std::fs::symlink_metadata(item.path()).map_err(|_| read_failed())?;
+            let name = format!("{}{}", prefix, item.file_name().to_string_lossy());
+            if meta.file_type().is_symlink() {
+                stats.skipped_symlinks += 1;
+            } else if meta.is_dir() {
+                zip.add_directory(format!("{}/", name), zip::write::SimpleFileOptions::default())
+                    .map_err(|_| io_failed("Failed to write the zip"))?;
+                walk(&item.path(), &format!("{}/", name), zip, stats)?;
+            } else if meta.is_file() {
+                #[allow(unused_mut)]
+                let mut options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated);
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    options = options.unix_permissions(meta.permissions().mode() & 0o777);
+                }
+                zip.start_file(name, options)
+                    .map_err(|_| io_failed("Failed to write the zip"))?;
+                let mut file = std::fs::File::open(item.path())
+                    .map_err(|_| io_failed("Failed to read a plugin data file"))?;
+                stats.bytes += std::io::copy(&mut file, zip)
+                    .map_err(|_| io_failed("Failed to write the zip"))?;
+                stats.files += 1;
+            }
+        }
+        Ok(())
+    }
+
+    let file = std::fs::File::create(output)
+        .map_err(|_| io_failed("Cannot create the export file"))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let mut stats = PluginDataTransfer::default();
+    let result = walk(data_dir, "", &mut zip, &mut stats)
+        .and_then(|_| {
+            zip.finish()
+                .map(|_| ())
+                .map_err(|_| io_failed("Failed to write the zip"))
+        });
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(output);
+        return Err(e);
+    }
+    Ok(stats)
+}
+
+/// Extract a data backup into `target_dir`; symlink entries are skipped.
+fn import_data_zip(
+    zip_path: &std::path::Path,
+    target_dir: &std::path::Path,
+) -> Result<PluginDataTransfer, CommandError> {
+    extract_zip_checked(zip_path, target_dir, &DATA_IMPORT_ZIP_LIMITS)
+}
+
 #[cfg(unix)]
 fn set_executable(path: &std::path::Path) {
     use std::os::unix::fs::PermissionsExt;
@@ -429,9 +1841,36 @@ fn kill_plugin(pid: u32) {
     }
 }
 
-#[cfg(not(unix))]
+/// Kill a plugin and its child processes (Windows: `taskkill /T /F`).
+#[cfg(windows)]
+fn kill_plugin(pid: u32) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .status();
+}
+#[cfg(not(any(unix, windows)))]
 fn kill_plugin(_pid: u32) {}
 
+/// Kill a plugin process group without another grace period (Unix:
+/// SIGKILL), for plugins that already ignored `shutdown`.
+#[cfg(unix)]
+fn force_kill_plugin(pid: u32) {
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// `taskkill /F` already skips any grace period.
+#[cfg(not(unix))]
+fn force_kill_plugin(pid: u32) {
+    kill_plugin(pid);
+}
+
 // ---------------------------------------------------------------------------
 // Tauri commands
 // ---------------------------------------------------------------------------
@@ -439,44 +1878,55 @@ fn kill_plugin(_pid: u32) {}
 /// Validate a plugin.json manifest from a GitHub URL (raw) or local path.
 /// Used for URL-import validation before showing the install confirmation UI.
 #[tauri::command]
-pub async fn plugin_validate_manifest(source: String) -> Result<ValidationResult, String> {
+pub async fn plugin_validate_manifest(
+    state: State<'_, PluginProcessManager>,
+    source: String,
+    locale: Option<String>,
+) -> Result<ValidationResult, CommandError> {
+    let locale = locale.unwrap_or_else(|| state.locale());
     let json_str = if source.starts_with("http://") || source.starts_with("https://") {
         // Fetch remote plugin.json
-        let client = reqwest::Client::builder()
+        let client = super::settings::client_builder()
             .timeout(Duration::from_secs(10))
             .user_agent("Moraya/0.16.0")
             .build()
-            .map_err(|_| "HTTP client 初始化失败".to_string())?;
+            .map_err(|_| http_client_failed())?;
         let resp = client
             .get(&source)
             .send()
             .await
             .map_err(|e| {
-                if e.is_timeout() {
-                    "连接超时，请检查网络或稍后重试".to_string()
+                let message = if e.is_timeout() {
+                    "Connection timed out; check your network or try again later".to_string()
                 } else if e.is_connect() {
-                    "无法连接到插件仓库，请检查网络连接".to_string()
+                    "Cannot reach the plugin repository; check your network connection"
+                        .to_string()
                 } else {
-                    format!("无法访问插件仓库: {}", e.without_url())
-                }
+                    format!("Cannot access the plugin repository: {}", e.without_url())
+                };
+                CommandError::new(ErrorCode::NetworkError, message)
             })?;
         if !resp.status().is_success() {
             return Ok(ValidationResult {
                 valid: false,
                 manifest: None,
                 errors: vec![format!(
-                    "无法获取 plugin.json，HTTP {}",
+                    "Cannot fetch plugin.json, HTTP {}",
                     resp.status().as_u16()
                 )],
                 warnings: vec![],
+                display_name: None,
+                display_description: None,
             });
         }
         resp.text()
             .await
-            .map_err(|_| "读取响应失败".to_string())?
+            .map_err(|_| CommandError::new(ErrorCode::NetworkError, "Failed to read the response"))?
     } else {
         // Local file
-        std::fs::read_to_string(&source).map_err(|_| "读取本地 plugin.json 失败".to_string())?
+        std::fs::read_to_string(&source).map_err(|_| {
+            CommandError::new(ErrorCode::FileNotFound, "Failed to read the local plugin.json")
+        })?
     };
 
     // Parse JSON
@@ -486,19 +1936,26 @@ pub async fn plugin_validate_manifest(source: String) -> Result<ValidationResult
             return Ok(ValidationResult {
                 valid: false,
                 manifest: None,
-                errors: vec![format!("plugin.json 格式错误，无法解析: {}", e)],
+                errors: vec![format!("plugin.json could not be parsed: {}", e)],
                 warnings: vec![],
+                display_name: None,
+                display_description: None,
             });
         }
     };
 
     let (errors, warnings) = validate_manifest(&manifest);
     let valid = errors.is_empty();
+    let display_name = localized_text(&manifest.name_localized, &manifest.name, &locale).to_string();
+    let display_description =
+        localized_text(&manifest.description_localized, &manifest.description, &locale).to_string();
     Ok(ValidationResult {
         valid,
         manifest: if valid { Some(manifest) } else { None },
         errors,
         warnings,
+        display_name: valid.then_some(display_name),
+        display_description: valid.then_some(display_description),
     })
 }
 
@@ -509,9 +1966,24 @@ pub async fn plugin_install_local(
     app: tauri::AppHandle,
     zip_path: String,
     expected_sha256: Option<String>,
-) -> Result<InstallResult, String> {
+    signature: Option<String>,
+) -> Result<InstallResult, CommandError> {
     let zip_p = std::path::Path::new(&zip_path);
 
+    // 0. Minisign signature over the zip (if the caller supplied one)
+    if let (Some(sig), Some(public_key)) = (signature.as_deref(), REGISTRY_PUBLIC_KEY) {
+        let bytes = std::fs::read(zip_p)
+            .map_err(|_| CommandError::new(ErrorCode::FileNotFound, "Cannot open the zip file"))?;
+        if let Err(e) = verify_signature(&bytes, Some(sig), public_key) {
+            return Ok(InstallResult {
+                ok: false,
+                plugin: None,
+                error: Some(signature_error(e)),
+                source: None,
+            });
+        }
+    }
+
     // 1. SHA256 verification (if expected hash provided)
     if let Some(expected) = &expected_sha256 {
         let actual = sha256_file(zip_p)?;
@@ -519,7 +1991,11 @@ pub async fn plugin_install_local(
             return Ok(InstallResult {
                 ok: false,
                 plugin: None,
-                error: Some("文件完整性验证失败，已阻止安装".to_string()),
+                error: Some(CommandError::new(
+                    ErrorCode::PluginIntegrityFailed,
+                    "File integrity check failed; installation blocked",
+                )),
+                source: None,
             });
         }
     }
@@ -532,6 +2008,7 @@ pub async fn plugin_install_local(
                 ok: false,
                 plugin: None,
                 error: Some(e),
+                source: None,
             });
         }
     };
@@ -541,28 +2018,35 @@ pub async fn plugin_install_local(
         return Ok(InstallResult {
             ok: false,
             plugin: None,
-            error: Some(errors.join("；")),
+            error: Some(
+                CommandError::new(ErrorCode::PluginInvalidManifest, errors.join("; "))
+                    .with_details(serde_json::json!({ "errors": errors })),
+            ),
+            source: None,
+        });
+    }
+    if let Some(reason) = app
+        .state::<PluginProcessManager>()
+        .blacklist_reason(&app, &manifest.id)
+    {
+        return Ok(InstallResult {
+            ok: false,
+            plugin: None,
+            error: Some(blacklisted_error(&reason)),
+            source: None,
         });
     }
 
-    // 3. Determine plugin directory
-    let app_data = app
-        .path()
-        .app_data_dir()
-        .map_err(|_| "无法获取 appData 目录".to_string())?;
-    let plugin_dir = app_data.join("plugins").join(&manifest.id);
+    // 3. Stop the running instance so its files can be replaced
+    stop_plugin(&app.state::<PluginProcessManager>(), &manifest.id);
 
-    // 4. Extract zip to plugin directory
-    if plugin_dir.exists() {
-        std::fs::remove_dir_all(&plugin_dir).map_err(|_| "无法清除旧版本目录".to_string())?;
-    }
-    extract_zip_safe(zip_p, &plugin_dir)?;
+    // 4. Extract via a staging directory, keeping the existing data/
+    let root = plugins_root(&app)?;
+    let plugin_dir = install_plugin_files(&root, &manifest.id, zip_p)?;
 
     // 5. Set executable bit on the platform binary
-    let platform = current_platform();
-    if let Some(entry_rel) = manifest.entry.get(platform) {
-        let bin_path = plugin_dir.join(entry_rel);
-        set_executable(&bin_path);
+    if let Ok(PluginEntry::Binary(entry_rel)) = resolve_entry(&manifest) {
+        set_executable(&plugin_dir.join(entry_rel));
     }
 
     // 6. Build state entry
@@ -573,11 +2057,16 @@ pub async fn plugin_install_local(
         installed_at: epoch_ms(),
         manifest,
     };
+    if let Some(root) = plugin_dir.parent() {
+        let persisted = entry.clone();
+        update_state_file(root, |entries| upsert_entry(entries, persisted))?;
+    }
 
     Ok(InstallResult {
         ok: true,
         plugin: Some(entry),
         error: None,
+        source: None,
     })
 }
 
@@ -587,26 +2076,34 @@ pub async fn plugin_install_from_url(
     app: tauri::AppHandle,
     download_url: String,
     expected_sha256: String,
+    signature_url: Option<String>,
     window: tauri::Window,
-) -> Result<InstallResult, String> {
+) -> Result<InstallResult, CommandError> {
     // 1. Download to a temp file with progress events
-    let client = reqwest::Client::builder()
+    let client = super::settings::client_builder()
         .timeout(Duration::from_secs(120))
+        .connect_timeout(REGISTRY_CONNECT_TIMEOUT)
         .user_agent("Moraya/0.16.0")
         .build()
-        .map_err(|_| "HTTP client 初始化失败".to_string())?;
+        .map_err(|_| http_client_failed())?;
 
-    let resp = client
-        .get(&download_url)
-        .send()
+    // The package is checked against the SHA256 from the index, which was
+    // either signed or fetched directly
+    let (resp, source) = get_with_mirrors(&client, &download_url, read_registry_mirror(&app), true)
         .await
-        .map_err(|_| "下载失败，请检查网络连接".to_string())?;
+        .map_err(|_| {
+            CommandError::new(
+                ErrorCode::NetworkError,
+                "Download failed; check your network connection",
+            )
+        })?;
 
     if !resp.status().is_success() {
         return Ok(InstallResult {
             ok: false,
             plugin: None,
-            error: Some(format!("下载失败，HTTP {}", resp.status().as_u16())),
+            error: Some(download_failed(resp.status().as_u16())),
+            source: Some(source_label(source)),
         });
     }
 
@@ -616,77 +2113,197 @@ pub async fn plugin_install_from_url(
     let tmp_path = std::env::temp_dir().join(format!("moraya-plugin-{}.zip", epoch_ms()));
     let mut file = tokio::fs::File::create(&tmp_path)
         .await
-        .map_err(|_| "无法创建临时文件".to_string())?;
+        .map_err(|_| io_failed("Cannot create a temporary file"))?;
 
     use futures_util::StreamExt;
     let mut stream = resp.bytes_stream();
     let mut downloaded: u64 = 0;
+    let mut meter = TransferMeter::new(Instant::now());
 
     while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|_| "下载中断".to_string())?;
+        let chunk = chunk
+            .map_err(|_| CommandError::new(ErrorCode::NetworkError, "Download interrupted"))?;
         tokio::io::AsyncWriteExt::write_all(&mut file, &chunk)
             .await
-            .map_err(|_| "写入临时文件失败".to_string())?;
+            .map_err(|_| io_failed("Failed to write the temporary file"))?;
         downloaded += chunk.len() as u64;
 
-        // Emit progress event to frontend
-        let _ = window.emit(
-            "plugin:download_progress",
-            serde_json::json!({
-                "downloaded": downloaded,
-                "total": total_size,
-            }),
-        );
+        // Emit progress event to frontend (throttled)
+        let now = Instant::now();
+        meter.record(now, downloaded);
+        if meter.should_emit(now, total_size > 0 && downloaded >= total_size) {
+            let _ = window.emit(
+                "plugin:download_progress",
+                serde_json::json!({
+                    "downloaded": downloaded,
+                    "total": total_size,
+                    "bytes_per_sec": meter.bytes_per_sec(),
+                    "eta_secs": meter.eta_secs(downloaded, total_size),
+                }),
+            );
+        }
     }
     drop(file);
 
     // 2. Delegate to plugin_install_local with SHA256 check
     let tmp_str = tmp_path.to_string_lossy().into_owned();
-    let result = plugin_install_local(app, tmp_str, Some(expected_sha256)).await;
+    // A declared signature that can't be fetched is treated as missing
+    let signature = match signature_url.filter(|u| !u.is_empty()) {
+        Some(url) => match fetch_text(&client, &mirror_url(&url, source)).await {
+            Some(sig) => Some(sig),
+            None => {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Ok(InstallResult {
+                    ok: false,
+                    plugin: None,
+                    error: Some(signature_error(SignatureError::Missing)),
+                    source: Some(source_label(source)),
+                });
+            }
+        },
+        None => None,
+    };
+    let mut result = plugin_install_local(app, tmp_str, Some(expected_sha256), signature).await;
 
     // Cleanup temp file
     let _ = std::fs::remove_file(&tmp_path);
 
+    if let Ok(r) = &mut result {
+        r.source = Some(source_label(source));
+    }
     result
 }
 
-/// Start a plugin process.
+/// Remember which mirror to try first (`None` = direct connection first).
 #[tauri::command]
-pub fn plugin_enable(
+pub fn plugin_set_registry_mirror(
     app: tauri::AppHandle,
-    state: State<'_, PluginProcessManager>,
-    entry: PluginStateEntry,
-) -> Result<(), String> {
-    let platform = current_platform();
-    let bin_rel = entry
-        .manifest
-        .entry
-        .get(platform)
-        .ok_or_else(|| format!("此插件不支持 {}", platform))?;
-
-    let bin_path = std::path::Path::new(&entry.plugin_dir).join(bin_rel);
-    if !bin_path.exists() {
-        return Err("插件二进制文件不存在，请重新安装".to_string());
-    }
-
-    // Kill existing process if any
-    {
-        let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
-        if let Some(mut proc) = processes.remove(&entry.id) {
-            let _ = proc.child.kill();
-            let _ = proc.child.wait();
-        }
+    index: Option<usize>,
+) -> Result<(), CommandError> {
+    if index.is_some_and(|i| i >= REGISTRY_MIRRORS.len()) {
+        return Err(CommandError::new(ErrorCode::InvalidArgument, "Invalid mirror index"));
     }
-
-    let app_data = app
+    let path = app
         .path()
         .app_data_dir()
-        .map_err(|_| "无法获取 appData 目录".to_string())?;
-    let plugin_data_dir = app_data.join("plugins").join(&entry.id).join("data");
-    let _ = std::fs::create_dir_all(&plugin_data_dir);
+        .map_err(|_| app_data_unavailable())?
+        .join(REGISTRY_MIRROR_FILE);
+    std::fs::write(path, serde_json::json!({ "index": index }).to_string())
+        .map_err(|_| io_failed("Failed to save the mirror setting"))
+}
 
-    let mut cmd = Command::new(&bin_path);
-    cmd.stdin(Stdio::piped())
+/// Allowlisted mirrors and the index of the preferred one.
+#[tauri::command]
+pub fn plugin_registry_mirrors(app: tauri::AppHandle) -> serde_json::Value {
+    serde_json::json!({
+        "mirrors": REGISTRY_MIRRORS,
+        "selected": read_registry_mirror(&app),
+    })
+}
+
+/// Start a plugin process and mark it enabled in `plugins/state.json`.
+#[tauri::command]
+pub async fn plugin_enable(
+    app: tauri::AppHandle,
+    entry: PluginStateEntry,
+) -> Result<(), CommandError> {
+    start_plugin_blocking(&app, entry.clone()).await?;
+    if is_dev_plugin(&entry.id) {
+        return Ok(());
+    }
+    let root = plugins_root(&app)?;
+    let persisted = PluginStateEntry {
+        enabled: true,
+        ..entry
+    };
+    update_state_file(&root, |entries| upsert_entry(entries, persisted))
+}
+
+fn start_plugin(
+    app: &tauri::AppHandle,
+    state: &PluginProcessManager,
+    entry: &PluginStateEntry,
+) -> Result<(), CommandError> {
+    if !is_dev_plugin(&entry.id) {
+        if let Some(reason) = state.blacklist_reason(app, &entry.id) {
+            return Err(blacklisted_error(&reason));
+        }
+    }
+    spawn_plugin(app, state, entry, 0)
+}
+
+/// `start_plugin` off the async runtime, for commands: the `initialize`
+/// handshake can wait up to `PLUGIN_INIT_TIMEOUT`.
+async fn start_plugin_blocking(
+    app: &tauri::AppHandle,
+    entry: PluginStateEntry,
+) -> Result<(), CommandError> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        start_plugin(&app, &app.state::<PluginProcessManager>(), &entry)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+fn spawn_plugin(
+    app: &tauri::AppHandle,
+    state: &PluginProcessManager,
+    entry: &PluginStateEntry,
+    restarts: u32,
+) -> Result<(), CommandError> {
+    let plugin_dir = std::path::Path::new(&entry.plugin_dir);
+    let (program, args): (std::path::PathBuf, Vec<std::path::PathBuf>) =
+        match resolve_entry(&entry.manifest)? {
+            PluginEntry::Binary(bin_rel) => {
+                let bin_path = plugin_dir.join(bin_rel);
+                if !bin_path.exists() {
+                    return Err(files_missing("Plugin binary is missing; please reinstall"));
+                }
+                (bin_path, Vec::new())
+            }
+            PluginEntry::Script { runtime, script } => {
+                if !ALLOWED_RUNTIMES.contains(&runtime) {
+                    return Err(CommandError::new(
+                        ErrorCode::PluginInvalidManifest,
+                        format!("Unsupported runtime: {}", runtime),
+                    ));
+                }
+                super::mcp::check_command_exists(runtime.to_string()).map_err(|_| {
+                    CommandError::new(
+                        ErrorCode::PluginRuntimeMissing,
+                        format!("Runtime {} not found; install it and add it to PATH", runtime),
+                    )
+                    .with_details(serde_json::json!({ "runtime": runtime }))
+                })?;
+                // Resolve symlinks so the script can't point outside the plugin dir
+                let script_path = plugin_dir
+                    .join(script)
+                    .canonicalize()
+                    .map_err(|_| files_missing("Plugin script is missing; please reinstall"))?;
+                let root = plugin_dir
+                    .canonicalize()
+                    .map_err(|_| files_missing("Plugin directory is missing; please reinstall"))?;
+                if !is_safe_relative_path(script) || !script_path.starts_with(&root) {
+                    return Err(CommandError::new(
+                        ErrorCode::PluginInvalidManifest,
+                        "Plugin script is outside the plugin directory",
+                    ));
+                }
+                (std::path::PathBuf::from(runtime), vec![script_path])
+            }
+        };
+
+    // Kill existing process if any
+    stop_plugin(state, &entry.id);
+
+    let storage_dir = plugin_storage_dir(&plugins_root(app)?, &entry.id);
+    let plugin_data_dir = storage_dir.join("data");
+    let _ = std::fs::create_dir_all(&plugin_data_dir);
+
+    let log_buffer = state.log_buffer(&entry.id);
+    let mut cmd = sandboxed_command(app, entry, &log_buffer, &program, &args, &plugin_data_dir);
+    cmd.stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .current_dir(&plugin_data_dir);
@@ -704,165 +2321,1225 @@ pub fn plugin_enable(
             cmd.env(&key, &value);
         }
     }
+    if entry.manifest.sandbox_level == STRICT_SANDBOX_LEVEL {
+        strip_strict_env(&mut cmd, &plugin_data_dir);
+    }
     cmd.env("MORAYA_PLUGIN_ID", &entry.id);
-    cmd.env("MORAYA_API_VERSION", "1");
+    cmd.env("MORAYA_API_VERSION", PLUGIN_API_VERSION);
 
-    let mut child = cmd.spawn().map_err(|_| "插件启动失败".to_string())?;
+    let start_failed = |message: &str| CommandError::new(ErrorCode::PluginStartFailed, message);
+    let mut child = cmd.spawn().map_err(|_| start_failed("Failed to start the plugin"))?;
     let pid = child.id();
 
-    let stdin = child.stdin.take().ok_or("无法获取插件 stdin")?;
-    let stdout = child.stdout.take().ok_or("无法获取插件 stdout")?;
-    let stderr = child.stderr.take().ok_or("无法获取插件 stderr")?;
+    let stdin = child.stdin.take().ok_or_else(|| start_failed("Cannot open plugin stdin"))?;
+    let stdout = child.stdout.take().ok_or_else(|| start_failed("Cannot open plugin stdout"))?;
+    let stderr = child.stderr.take().ok_or_else(|| start_failed("Cannot open plugin stderr"))?;
 
-    let line_rx = spawn_reader_thread(stdout);
+    let pending: PendingMap = Arc::default();
+    let stdin = Arc::new(Mutex::new(Some(stdin)));
+    let fs_scope = HostFsScope {
+        workspace_roots: state.workspace_roots.clone(),
+        data_dir: plugin_data_dir
+            .canonicalize()
+            .unwrap_or_else(|_| plugin_data_dir.clone()),
+    };
+    let workspace_roots = state
+        .workspace_roots
+        .lock()
+        .map(|r| r.clone())
+        .unwrap_or_default();
+    let init_params = serde_json::json!({
+        "hostVersion": app.package_info().version.to_string(),
+        "apiVersion": PLUGIN_API_VERSION,
+        "permissions": entry.manifest.permissions,
+        "dataDir": plugin_data_dir,
+        "workspaceRoots": workspace_roots,
+        "sandboxLevel": entry.manifest.sandbox_level,
+        "locale": state.locale(),
+    });
+    let permissions: Arc<HashSet<String>> =
+        Arc::new(entry.manifest.permissions.iter().cloned().collect());
+    spawn_dispatcher_thread(
+        app.clone(),
+        entry.id.clone(),
+        stdout,
+        stdin.clone(),
+        pending.clone(),
+        permissions.clone(),
+        fs_scope,
+        log_buffer.clone(),
+    );
+    push_bounded(
+        &log_buffer,
+        format!("[moraya] started {} v{} (pid {})", entry.id, entry.manifest.version, pid),
+        PLUGIN_LOG_BUFFER_LINES,
+    );
+    let logs_dir = storage_dir.join("logs");
+    spawn_stderr_thread(
+        stderr,
+        PluginLogSink::new(app.clone(), &entry.id, logs_dir, log_buffer.clone()),
+    );
+    let generation = state.next_generation.fetch_add(1, Ordering::SeqCst);
+
+    if let Ok(mut instances) = state.instances.lock() {
+        instances.insert(
+            entry.id.clone(),
+            PluginInstance {
+                pid,
+                generation,
+                exited: false,
+                started_at: Instant::now(),
+                last_invoke_ms: None,
+            },
+        );
+    }
 
-    if let Ok(mut pids) = state.pids.lock() {
-        pids.insert(entry.id.clone(), pid);
+    {
+        let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
+        processes.insert(
+            entry.id.clone(),
+            PluginProcess {
+                child,
+                stdin: stdin.clone(),
+                pending: pending.clone(),
+                permissions,
+                generation,
+            },
+        );
     }
 
-    let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
-    processes.insert(
-        entry.id.clone(),
-        PluginProcess { child, stdin, stderr, line_rx },
-    );
+    let handshake = initialize_plugin(&stdin, &pending, init_params, &log_buffer);
+    if let Ok(mut handshakes) = state.handshakes.lock() {
+        handshakes.insert(entry.id.clone(), handshake.clone());
+    }
+    if !handshake.compatible {
+        stop_plugin(state, &entry.id);
+        let error = handshake.error.unwrap_or_default();
+        push_bounded(
+            &log_buffer,
+            format!("[moraya] initialize failed: {}", error),
+            PLUGIN_LOG_BUFFER_LINES,
+        );
+        let _ = app.emit(
+            "plugin:incompatible",
+            serde_json::json!({
+                "pluginId": entry.id,
+                "error": error,
+                "rawResponse": handshake.raw_response,
+            }),
+        );
+        return Err(CommandError::new(
+            ErrorCode::PluginIncompatible,
+            format!("Plugin is incompatible: {}", error),
+        )
+        .with_details(serde_json::json!({ "error": error })));
+    }
 
+    spawn_monitor_thread(app.clone(), entry.clone(), generation, restarts, log_buffer);
     Ok(())
 }
 
-/// Stop a plugin process.
+/// Command running a plugin's entry. Strict plugins run under
+/// `sandbox-exec` on macOS; where that is not possible the fallback is
+/// noted in the plugin log.
+fn sandboxed_command(
+    app: &tauri::AppHandle,
+    entry: &PluginStateEntry,
+    log_buffer: &LogBuffer,
+    program: &std::path::Path,
+    args: &[std::path::PathBuf],
+    data_dir: &std::path::Path,
+) -> Command {
+    if entry.manifest.sandbox_level == STRICT_SANDBOX_LEVEL {
+        #[cfg(target_os = "macos")]
+        {
+            const SANDBOX_EXEC: &str = "/usr/bin/sandbox-exec";
+            if std::path::Path::new(SANDBOX_EXEC).exists() {
+                let plugin_dir = std::path::Path::new(&entry.plugin_dir);
+                let profile = sandbox_profile(
+                    &plugin_dir.canonicalize().unwrap_or_else(|_| plugin_dir.to_path_buf()),
+                    &data_dir.canonicalize().unwrap_or_else(|_| data_dir.to_path_buf()),
+                    dirs::home_dir().as_deref(),
+                );
+                let mut cmd = Command::new(SANDBOX_EXEC);
+                cmd.arg("-p").arg(profile).arg(program).args(args);
+                return cmd;
+            }
+        }
+        let _ = data_dir;
+        log_enforcement(
+            app,
+            &entry.id,
+            log_buffer,
+            "no OS sandbox; only working directory and env were restricted",
+        );
+    }
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd
+}
+
+/// `sandbox-exec` profile for strict plugins: writes only into the data
+/// directory and temp folders; nothing under the home directory is
+/// readable except the plugin itself, its data and runtime installs.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn sandbox_profile(
+    plugin_dir: &std::path::Path,
+    data_dir: &std::path::Path,
+    home: Option<&std::path::Path>,
+) -> String {
+    let subpath = |p: &std::path::Path| {
+        let escaped = p
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        format!("(subpath \"{}\")", escaped)
+    };
+    let mut profile = String::from("(version 1)\n(allow default)\n(deny file-write*)\n");
+    profile.push_str(&format!(
+        "(allow file-write* {} (subpath \"/private/tmp\") (subpath \"/private/var/folders\") (literal \"/dev/null\"))\n",
+        subpath(data_dir)
+    ));
+    if let Some(home) = home {
+        let mut readable = vec![subpath(plugin_dir), subpath(data_dir)];
+        readable.extend(RUNTIME_HOME_DIRS.iter().map(|d| subpath(&home.join(d))));
+        profile.push_str(&format!("(deny file-read-data {})\n", subpath(home)));
+        profile.push_str(&format!("(allow file-read-data {})\n", readable.join(" ")));
+    }
+    profile
+}
+
+/// Remove variables that point strict plugins at the user's files, and make
+/// the data directory their home and temp folder.
+fn strip_strict_env(cmd: &mut Command, data_dir: &std::path::Path) {
+    for (key, _) in std::env::vars() {
+        if key.starts_with("XDG_") || STRICT_BLOCKED_ENV.contains(&key.as_str()) {
+            cmd.env_remove(&key);
+        }
+    }
+    let tmp = data_dir.join("tmp");
+    let _ = std::fs::create_dir_all(&tmp);
+    cmd.env("HOME", data_dir)
+        .env("USERPROFILE", data_dir)
+        .env("TMPDIR", &tmp)
+        .env("TEMP", &tmp)
+        .env("TMP", &tmp);
+}
+
+/// Send `initialize` and block until the reply arrives or
+/// `PLUGIN_INIT_TIMEOUT` passes. Plugins that don't know the method, or
+/// don't answer in time, keep running as legacy plugins; an exit or a
+/// malformed reply marks the plugin incompatible. Must not run on the main
+/// thread or the async runtime.
+fn initialize_plugin(
+    stdin: &Arc<Mutex<Option<ChildStdin>>>,
+    pending: &PendingMap,
+    params: serde_json::Value,
+    log_buffer: &LogBuffer,
+) -> PluginHandshake {
+    let host_id = NEXT_RPC_ID.fetch_add(1, Ordering::SeqCst);
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    if let Ok(mut p) = pending.lock() {
+        p.waiters.insert(host_id, (serde_json::Value::from(host_id), tx));
+    }
+    let msg = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": host_id,
+        "method": "initialize",
+        "params": params,
+    });
+    let written = stdin
+        .lock()
+        .ok()
+        .as_mut()
+        .and_then(|g| g.as_mut())
+        .map(|w| writeln!(w, "{}", msg).and_then(|_| w.flush()).is_ok())
+        .unwrap_or(false);
+    if !written {
+        return PluginHandshake::incompatible("Failed to write to plugin stdin".to_string(), None);
+    }
+
+    // The waiter is a tokio channel; forward it so this thread can block
+    // with a timeout. Dropping the waiter on timeout ends the forwarder.
+    let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+    tauri::async_runtime::spawn(async move {
+        let _ = reply_tx.send(rx.await);
+    });
+    match reply_rx.recv_timeout(PLUGIN_INIT_TIMEOUT) {
+        Ok(Ok(Ok(raw))) => parse_initialize_reply(&raw),
+        Ok(Ok(Err(e))) => PluginHandshake::incompatible(e.message, None),
+        Ok(Err(_)) | Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+            PluginHandshake::incompatible(plugin_exited().message, None)
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            if let Ok(mut p) = pending.lock() {
+                p.waiters.remove(&host_id);
+            }
+            push_bounded(
+                log_buffer,
+                "[moraya] no initialize reply; running as a legacy plugin".to_string(),
+                PLUGIN_LOG_BUFFER_LINES,
+            );
+            PluginHandshake::legacy()
+        }
+    }
+}
+
+fn parse_initialize_reply(raw: &str) -> PluginHandshake {
+    let parsed: Option<serde_json::Value> = serde_json::from_str(raw).ok();
+    let Some(result) = parsed
+        .as_ref()
+        .and_then(|v| v.get("result"))
+        .filter(|r| r.is_object())
+    else {
+        let code = parsed
+            .as_ref()
+            .and_then(|v| v.pointer("/error/code"))
+            .and_then(|c| c.as_i64());
+        if code == Some(RPC_METHOD_NOT_FOUND) {
+            return PluginHandshake::legacy();
+        }
+        let error = parsed
+            .as_ref()
+            .and_then(|v| v.pointer("/error/message"))
+            .and_then(|m| m.as_str())
+            .unwrap_or("Invalid initialize response");
+        return PluginHandshake::incompatible(error.to_string(), Some(raw.to_string()));
+    };
+    PluginHandshake {
+        compatible: true,
+        capabilities: result
+            .get("capabilities")
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!({})),
+        methods: result
+            .get("methods")
+            .and_then(|m| m.as_array())
+            .map(|m| m.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+            .unwrap_or_default(),
+        error: None,
+        raw_response: None,
+    }
+}
+
+/// Watch a plugin process until it exits or is replaced/stopped. On an
+/// unexpected exit, emit `plugin:exited` and optionally restart with backoff.
+fn spawn_monitor_thread(
+    app: tauri::AppHandle,
+    entry: PluginStateEntry,
+    generation: u64,
+    restarts: u32,
+    log_buffer: LogBuffer,
+) {
+    std::thread::spawn(move || {
+        let status = loop {
+            std::thread::sleep(PLUGIN_MONITOR_INTERVAL);
+            let state = app.state::<PluginProcessManager>();
+            if !state.is_current(&entry.id, generation) {
+                return; // stopped or restarted by someone else
+            }
+            let Ok(mut processes) = state.processes.lock() else {
+                return;
+            };
+            let Some(proc) = processes.get_mut(&entry.id) else {
+                return;
+            };
+            if proc.generation != generation {
+                return;
+            }
+            match proc.child.try_wait() {
+                Ok(None) => continue,
+                Ok(Some(status)) => {
+                    if let Some(proc) = processes.remove(&entry.id) {
+                        close_pending(&proc.pending, plugin_exited());
+                    }
+                    break Some(status);
+                }
+                Err(_) => {
+                    if let Some(proc) = processes.remove(&entry.id) {
+                        close_pending(&proc.pending, plugin_exited());
+                    }
+                    break None;
+                }
+            }
+        };
+
+        let restarting = entry.manifest.auto_restart
+            && super::settings::plugin_auto_restart()
+            && restarts < PLUGIN_MAX_RESTARTS;
+        {
+            let state = app.state::<PluginProcessManager>();
+            let Ok(mut instances) = state.instances.lock() else {
+                return;
+            };
+            if instances.get(&entry.id).map(|i| i.generation) != Some(generation) {
+                return;
+            }
+            if restarting {
+                if let Some(inst) = instances.get_mut(&entry.id) {
+                    inst.exited = true;
+                }
+            } else {
+                instances.remove(&entry.id);
+            }
+        }
+
+        // Give the stderr thread a moment to drain the final lines
+        std::thread::sleep(Duration::from_millis(100));
+        let stderr: Vec<String> = log_buffer
+            .lock()
+            .map(|b| {
+                let skip = b.len().saturating_sub(PLUGIN_STDERR_TAIL);
+                b.iter().skip(skip).cloned().collect()
+            })
+            .unwrap_or_default();
+        let code = status.and_then(|s| s.code());
+        log::warn!("{} exited (code {:?})", entry.id, code);
+        let _ = app.emit(
+            "plugin:exited",
+            serde_json::json!({
+                "pluginId": entry.id,
+                "code": code,
+                "stderr": stderr,
+                "restarting": restarting,
+            }),
+        );
+
+        if restarting {
+            std::thread::sleep(Duration::from_secs(1 << restarts));
+            let state = app.state::<PluginProcessManager>();
+            // Disabled or manually restarted during the backoff
+            if !state.is_current(&entry.id, generation) {
+                return;
+            }
+            if let Err(e) = spawn_plugin(&app, &state, &entry, restarts + 1) {
+                log::warn!("restart of {} failed: {}", entry.id, e);
+                if let Ok(mut instances) = state.instances.lock() {
+                    instances.remove(&entry.id);
+                }
+            }
+        }
+    });
+}
+
+/// Stop a plugin process and mark it disabled in `plugins/state.json`.
 #[tauri::command]
-pub fn plugin_disable(
-    state: State<'_, PluginProcessManager>,
-    plugin_id: String,
-) -> Result<(), String> {
-    if let Ok(mut pids) = state.pids.lock() {
-        if let Some(pid) = pids.remove(&plugin_id) {
-            kill_plugin(pid);
+pub async fn plugin_disable(app: tauri::AppHandle, plugin_id: String) -> Result<(), CommandError> {
+    stop_plugin_blocking(&app, &plugin_id).await?;
+    if is_dev_plugin(&plugin_id) {
+        return Ok(());
+    }
+    let root = plugins_root(&app)?;
+    update_state_file(&root, |entries| {
+        if let Some(e) = entries.iter_mut().find(|e| e.id == plugin_id) {
+            e.enabled = false;
+        }
+    })
+}
+
+/// `stop_plugin` off the async runtime, for commands: a plugin that
+/// ignores `shutdown` takes `PLUGIN_SHUTDOWN_GRACE` to stop.
+async fn stop_plugin_blocking(app: &tauri::AppHandle, plugin_id: &str) -> Result<(), CommandError> {
+    let app = app.clone();
+    let plugin_id = plugin_id.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        stop_plugin(&app.state::<PluginProcessManager>(), &plugin_id)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+fn stop_plugin(state: &PluginProcessManager, plugin_id: &str) {
+    // Removing the instance first tells the monitor thread this exit is expected
+    let inst = state
+        .instances
+        .lock()
+        .ok()
+        .and_then(|mut instances| instances.remove(plugin_id));
+    let proc = state
+        .processes
+        .lock()
+        .ok()
+        .and_then(|mut processes| processes.remove(plugin_id));
+    if let Some(mut proc) = proc {
+        close_pending(&proc.pending, plugin_stopped());
+        shutdown_plugin(&mut proc);
+    } else if let Some(inst) = inst.filter(|i| !i.exited) {
+        kill_plugin(inst.pid);
+    }
+}
+
+/// Ask a plugin to exit: `shutdown` notification, close stdin, wait up to
+/// `PLUGIN_SHUTDOWN_GRACE`, then kill it outright.
+fn shutdown_plugin(proc: &mut PluginProcess) {
+    if let Ok(mut guard) = proc.stdin.lock() {
+        // Dropping the handle closes the pipe, so plugins that ignore the
+        // notification still see EOF on stdin.
+        if let Some(mut stdin) = guard.take() {
+            let msg = serde_json::json!({ "jsonrpc": "2.0", "method": "shutdown" });
+            let _ = writeln!(stdin, "{}", msg);
+            let _ = stdin.flush();
         }
     }
-    if let Ok(mut processes) = state.processes.lock() {
-        if let Some(proc) = processes.remove(&plugin_id) {
-            drop(proc);
+
+    let deadline = Instant::now() + PLUGIN_SHUTDOWN_GRACE;
+    while Instant::now() < deadline {
+        if matches!(proc.child.try_wait(), Ok(Some(_))) {
+            return;
         }
+        std::thread::sleep(Duration::from_millis(50));
     }
-    Ok(())
+
+    force_kill_plugin(proc.child.id());
+    let _ = proc.child.kill();
+    let _ = proc.child.wait();
 }
 
 /// Uninstall a plugin: stop process + delete directory.
 #[tauri::command]
-pub fn plugin_uninstall(
+pub async fn plugin_uninstall(
     app: tauri::AppHandle,
     state: State<'_, PluginProcessManager>,
     plugin_id: String,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     // Stop process first
-    let _ = plugin_disable(state, plugin_id.clone());
+    stop_plugin_blocking(&app, &plugin_id).await?;
+
+    // Dev plugins only get unregistered; their source folder is left alone
+    if is_dev_plugin(&plugin_id) {
+        if let Ok(mut watchers) = state.dev_watchers.lock() {
+            watchers.remove(&plugin_id);
+        }
+        if let Ok(mut dev) = state.dev_plugins.lock() {
+            dev.remove(&plugin_id);
+        }
+        return Ok(());
+    }
 
     // Delete plugin directory
-    let app_data = app
-        .path()
-        .app_data_dir()
-        .map_err(|_| "无法获取 appData 目录".to_string())?;
-    let plugin_dir = app_data.join("plugins").join(&plugin_id);
+    let root = plugins_root(&app)?;
+    let plugin_dir = root.join(&plugin_id);
     if plugin_dir.exists() {
-        std::fs::remove_dir_all(&plugin_dir).map_err(|_| "删除插件目录失败".to_string())?;
+        std::fs::remove_dir_all(&plugin_dir)
+            .map_err(|_| io_failed("Failed to delete the plugin directory"))?;
+    }
+    update_state_file(&root, |entries| entries.retain(|e| e.id != plugin_id))
+}
+
+/// Zip `plugins/<id>/data` to `output_zip` for backup or migration.
+#[tauri::command]
+pub async fn plugin_export_data(
+    app: tauri::AppHandle,
+    plugin_id: String,
+    output_zip: String,
+) -> Result<PluginDataTransfer, CommandError> {
+    check_plugin_id(&plugin_id)?;
+    let output = file_cmd::validate_path(&output_zip)?;
+    let data_dir = plugin_storage_dir(&plugins_root(&app)?, &plugin_id).join("data");
+    if !data_dir.is_dir() {
+        return Err(CommandError::new(ErrorCode::PluginNoData, "Plugin has no data to export"));
     }
+    tauri::async_runtime::spawn_blocking(move || export_data_dir(&data_dir, &output))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Replace `plugins/<id>/data` with the contents of a backup zip. A running
+/// plugin is stopped for the swap and started again afterwards.
+#[tauri::command]
+pub async fn plugin_import_data(
+    app: tauri::AppHandle,
+    state: State<'_, PluginProcessManager>,
+    plugin_id: String,
+    zip_path: String,
+) -> Result<PluginDataTransfer, CommandError> {
+    check_plugin_id(&plugin_id)?;
+    let zip_path = file_cmd::validate_path(&zip_path)?;
+    let root = plugins_root(&app)?;
+    let storage_dir = plugin_storage_dir(&root, &plugin_id);
+    let stamp = epoch_ms();
+    let staging = storage_dir.join(format!(".data-import-{}", stamp));
+    let backup = storage_dir.join(format!(".data-backup-{}", stamp));
+
+    // Extract before touching the live data so a bad zip changes nothing
+    let target = staging.clone();
+    let extracted =
+        tauri::async_runtime::spawn_blocking(move || import_data_zip(&zip_path, &target))
+            .await
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+            .and_then(|r| r);
+    let stats = match extracted {
+        Ok(stats) => stats,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+
+    let was_running = running_ids(&state).contains(&plugin_id);
+    if was_running {
+        let _ = stop_plugin_blocking(&app, &plugin_id).await;
+    }
+
+    let data_dir = storage_dir.join("data");
+    let had_data = data_dir.exists();
+    let swapped = (!had_data || std::fs::rename(&data_dir, &backup).is_ok())
+        && std::fs::rename(&staging, &data_dir).is_ok();
+    if swapped {
+        let _ = std::fs::remove_dir_all(&backup);
+    } else {
+        if had_data && !data_dir.exists() {
+            let _ = std::fs::rename(&backup, &data_dir);
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+
+    if was_running {
+        let entry = if is_dev_plugin(&plugin_id) {
+            state.dev_plugins.lock().ok().and_then(|d| d.get(&plugin_id).cloned())
+        } else {
+            read_state_file(&root).into_iter().find(|e| e.id == plugin_id)
+        };
+        if let Some(entry) = entry {
+            if let Err(e) = start_plugin_blocking(&app, entry).await {
+                log::warn!("restart of {} after data import failed: {}", plugin_id, e);
+            }
+        }
+    }
+
+    if swapped {
+        Ok(stats)
+    } else {
+        Err(io_failed("Cannot replace the plugin data directory"))
+    }
+}
+
+/// Load the persisted plugin state (`plugins/state.json`).
+#[tauri::command]
+pub fn plugin_state_load(app: tauri::AppHandle) -> Result<Vec<PluginStateEntry>, CommandError> {
+    let root = plugins_root(&app)?;
+    Ok(read_state_file(&root))
+}
+
+/// Replace the persisted plugin state.
+#[tauri::command]
+pub fn plugin_state_save(
+    app: tauri::AppHandle,
+    entries: Vec<PluginStateEntry>,
+) -> Result<(), CommandError> {
+    let root = plugins_root(&app)?;
+    let _guard = STATE_FILE_LOCK.lock().map_err(|e| e.to_string())?;
+    write_state_file(&root, &entries)
+}
+
+/// Reconcile `plugins/state.json` with the plugin directories on disk,
+/// persist the result and return it.
+#[tauri::command]
+pub fn plugin_scan_installed(
+    app: tauri::AppHandle,
+) -> Result<Vec<PluginStateEntry>, CommandError> {
+    let root = plugins_root(&app)?;
+    let _guard = STATE_FILE_LOCK.lock().map_err(|e| e.to_string())?;
+    let entries = reconcile_state(&root, read_state_file(&root));
+    if root.exists() {
+        write_state_file(&root, &entries)?;
+    }
+    Ok(entries)
+}
+
+/// Start every plugin marked enabled in `plugins/state.json`.
+/// Called once from `setup()`; failures are logged and otherwise ignored.
+pub fn autostart_enabled_plugins(app: &tauri::AppHandle) {
+    let Ok(root) = plugins_root(app) else {
+        return;
+    };
+    let entries = {
+        let Ok(_guard) = STATE_FILE_LOCK.lock() else {
+            return;
+        };
+        reconcile_state(&root, read_state_file(&root))
+    };
+    let state = app.state::<PluginProcessManager>();
+    for entry in entries.iter().filter(|e| e.enabled) {
+        if let Err(e) = start_plugin(app, &state, entry) {
+            log::warn!("autostart {} failed: {}", entry.id, e);
+        }
+    }
+}
+
+/// List running plugins with pid, uptime and memory (used to populate
+/// processState and the resource view in the frontend).
+#[tauri::command]
+pub fn plugin_list_running(state: State<'_, PluginProcessManager>) -> Vec<RunningPlugin> {
+    let ids = running_ids(&state);
+    let Ok(instances) = state.instances.lock() else {
+        return Vec::new();
+    };
+    ids.into_iter()
+        .filter_map(|id| {
+            let inst = instances.get(&id)?;
+            Some(RunningPlugin {
+                dev: is_dev_plugin(&id),
+                pid: inst.pid,
+                uptime_ms: inst.started_at.elapsed().as_millis() as u64,
+                rss_bytes: process_rss_bytes(inst.pid),
+                last_invoke_ms: inst.last_invoke_ms,
+                id,
+            })
+        })
+        .collect()
+}
+
+/// Legacy variant of `plugin_list_running` returning only ids.
+#[tauri::command]
+pub fn plugin_list_running_ids(state: State<'_, PluginProcessManager>) -> Vec<String> {
+    running_ids(&state)
+}
+
+/// Resident set size of `pid` in bytes.
+#[cfg(target_os = "linux")]
+fn process_rss_bytes(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "macos")]
+fn process_rss_bytes(pid: u32) -> Option<u64> {
+    let mut info: libc::proc_taskinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    // SAFETY: `info` is a properly sized, writable proc_taskinfo
+    let ret = unsafe {
+        libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDTASKINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        )
+    };
+    (ret == size).then_some(info.pti_resident_size)
+}
+
+#[cfg(windows)]
+fn process_rss_bytes(pid: u32) -> Option<u64> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    // SAFETY: the handle is checked and closed; counters is sized via `cb`
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut counters = PROCESS_MEMORY_COUNTERS {
+            cb: std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+            ..Default::default()
+        };
+        let ok = GetProcessMemoryInfo(handle, &mut counters, counters.cb).is_ok();
+        let _ = CloseHandle(handle);
+        ok.then_some(counters.WorkingSetSize as u64)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn process_rss_bytes(_pid: u32) -> Option<u64> {
+    None
+}
+
+fn running_ids(state: &PluginProcessManager) -> Vec<String> {
+    let Ok(instances) = state.instances.lock() else {
+        return Vec::new();
+    };
+    let Ok(mut processes) = state.processes.lock() else {
+        return Vec::new();
+    };
+    instances
+        .iter()
+        .filter(|(_, inst)| !inst.exited)
+        .filter(|(id, _)| match processes.get_mut(id.as_str()) {
+            None => false,
+            Some(proc) => matches!(proc.child.try_wait(), Ok(None)),
+        })
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Load an unpacked plugin from `dir_path` for development and start it.
+/// It is registered as `dev:<id>` for this session only and skips the
+/// blacklist; the returned entry is not persisted.
+#[tauri::command]
+pub async fn plugin_dev_load(
+    app: tauri::AppHandle,
+    state: State<'_, PluginProcessManager>,
+    dir_path: String,
+) -> Result<PluginStateEntry, CommandError> {
+    let dir = std::path::Path::new(&dir_path)
+        .canonicalize()
+        .map_err(|_| CommandError::new(ErrorCode::FileNotFound, "Plugin folder not found"))?;
+    let json = std::fs::read_to_string(dir.join("plugin.json")).map_err(|_| {
+        CommandError::new(ErrorCode::PluginInvalidManifest, "The folder has no plugin.json")
+    })?;
+    let mut manifest = parse_manifest(&json)?;
+    let (errors, _) = validate_manifest(&manifest);
+    if !errors.is_empty() {
+        return Err(
+            CommandError::new(ErrorCode::PluginInvalidManifest, errors.join("; "))
+                .with_details(serde_json::json!({ "errors": errors })),
+        );
+    }
+
+    let id = format!("{}{}", DEV_PLUGIN_PREFIX, manifest.id);
+    manifest.id = id.clone();
+    let entry = PluginStateEntry {
+        id: id.clone(),
+        enabled: true,
+        plugin_dir: dir.to_string_lossy().to_string(),
+        installed_at: epoch_ms(),
+        manifest,
+    };
+    start_plugin_blocking(&app, entry.clone()).await?;
+    state
+        .dev_plugins
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(id, entry.clone());
+    Ok(entry)
+}
+
+fn invalid_entry_path() -> CommandError {
+    CommandError::new(ErrorCode::PluginInvalidManifest, "Invalid plugin entry path")
+}
+
+fn watch_failed(e: &notify::Error) -> CommandError {
+    io_failed(&format!("Cannot watch plugin files: {}", e))
+}
+
+/// Turn hot-restart for a dev plugin on or off. While on, changes to the
+/// entry binary/script restart the plugin and emit `plugin:dev_reloaded`.
+#[tauri::command]
+pub fn plugin_dev_watch(
+    app: tauri::AppHandle,
+    state: State<'_, PluginProcessManager>,
+    plugin_id: String,
+    enabled: bool,
+) -> Result<(), CommandError> {
+    if !enabled {
+        // Dropping the watcher also ends its reload thread
+        if let Ok(mut watchers) = state.dev_watchers.lock() {
+            watchers.remove(&plugin_id);
+        }
+        return Ok(());
+    }
+
+    let entry = state
+        .dev_plugins
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(&plugin_id)
+        .cloned()
+        .ok_or_else(|| {
+            CommandError::new(ErrorCode::PluginNotFound, "Not a loaded development plugin")
+        })?;
+    let target = std::path::Path::new(&entry.plugin_dir).join(match resolve_entry(&entry.manifest)? {
+        PluginEntry::Binary(bin) => bin,
+        PluginEntry::Script { script, .. } => script,
+    });
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_os_string())
+        .ok_or_else(invalid_entry_path)?;
+    // Watch the parent: editors and compilers often replace the file instead of writing it
+    let watch_dir = target
+        .parent()
+        .ok_or_else(invalid_entry_path)?
+        .to_path_buf();
+
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        let relevant = matches!(
+            event.kind,
+            notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+        ) && event
+            .paths
+            .iter()
+            .any(|p| p.file_name() == Some(file_name.as_os_str()));
+        if relevant {
+            let _ = tx.send(());
+        }
+    })
+    .map_err(|e| watch_failed(&e))?;
+    notify::Watcher::watch(&mut watcher, &watch_dir, notify::RecursiveMode::NonRecursive)
+        .map_err(|e| watch_failed(&e))?;
+    state
+        .dev_watchers
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(plugin_id.clone(), watcher);
+
+    std::thread::spawn(move || {
+        while rx.recv().is_ok() {
+            // A single save usually produces a burst of events
+            while rx.recv_timeout(DEV_RELOAD_DEBOUNCE).is_ok() {}
+            let state = app.state::<PluginProcessManager>();
+            let watching = state
+                .dev_watchers
+                .lock()
+                .map(|w| w.contains_key(&plugin_id))
+                .unwrap_or(false);
+            let entry = state
+                .dev_plugins
+                .lock()
+                .ok()
+                .and_then(|d| d.get(&plugin_id).cloned());
+            let Some(entry) = entry.filter(|_| watching) else {
+                break;
+            };
+            let result = spawn_plugin(&app, &state, &entry, 0);
+            let _ = app.emit(
+                "plugin:dev_reloaded",
+                serde_json::json!({
+                    "pluginId": plugin_id,
+                    "ok": result.is_ok(),
+                    "error": result.err(),
+                }),
+            );
+        }
+    });
     Ok(())
 }
 
-/// List running plugin IDs (used to populate processState in frontend).
+/// Capabilities and methods a plugin declared in its `initialize` reply.
+/// For incompatible plugins the error and raw reply are included instead.
 #[tauri::command]
-pub fn plugin_list_running(state: State<'_, PluginProcessManager>) -> Vec<String> {
+pub fn plugin_get_capabilities(
+    state: State<'_, PluginProcessManager>,
+    plugin_id: String,
+) -> Result<PluginHandshake, CommandError> {
     state
-        .processes
+        .handshakes
         .lock()
-        .map(|p| p.keys().cloned().collect())
-        .unwrap_or_default()
+        .map_err(|e| e.to_string())?
+        .get(&plugin_id)
+        .cloned()
+        .ok_or_else(|| {
+            CommandError::new(ErrorCode::PluginNotRunning, "Plugin has not completed the handshake")
+        })
+}
+
+/// Set the UI locale passed to plugins in `initialize`.
+#[tauri::command]
+pub fn plugin_set_locale(
+    state: State<'_, PluginProcessManager>,
+    locale: String,
+) -> Result<(), CommandError> {
+    *state.locale.lock().map_err(|e| e.to_string())? = Some(locale);
+    Ok(())
 }
 
+/// Set the workspace folders plugins may reach through `host/fs.*`. They are
+/// passed in `initialize` and announced to running plugins with a
+/// `workspace/rootsChanged` notification. Folders outside the allowed file
+/// roots are skipped.
+#[tauri::command]
+pub fn plugin_set_workspace_roots(
+    state: State<'_, PluginProcessManager>,
+    roots: Vec<String>,
+) -> Result<(), CommandError> {
+    let mut accepted = Vec::new();
+    for root in &roots {
+        match file_cmd::validate_path(root) {
+            Ok(path) if path.is_dir() => accepted.push(path),
+            _ => log::warn!("Ignoring plugin workspace root {}", root),
+        }
+    }
+    let notification = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "workspace/rootsChanged",
+        "params": { "workspaceRoots": accepted },
+    });
+    *state.workspace_roots.lock().map_err(|e| e.to_string())? = accepted;
+    if let Ok(processes) = state.processes.lock() {
+        for proc in processes.values() {
+            write_to_plugin(&proc.stdin, &notification);
+        }
+    }
+    Ok(())
+}
+
+/// Last `tail_lines` (default 200) stderr lines of a plugin. Falls back to
+/// the log file when nothing is buffered in memory (e.g. after an app restart).
+#[tauri::command]
+pub fn plugin_read_logs(
+    app: tauri::AppHandle,
+    state: State<'_, PluginProcessManager>,
+    plugin_id: String,
+    tail_lines: Option<usize>,
+) -> Result<Vec<String>, CommandError> {
+    check_plugin_id(&plugin_id)?;
+    let tail = tail_lines.unwrap_or(200).clamp(1, PLUGIN_LOG_BUFFER_LINES);
+
+    let buffered = state.logs.lock().ok().and_then(|l| l.get(&plugin_id).cloned());
+    let lines: Vec<String> = match buffered {
+        Some(buf) => buf
+            .lock()
+            .map(|b| b.iter().cloned().collect())
+            .unwrap_or_default(),
+        None => {
+            let path = plugin_storage_dir(&plugins_root(&app)?, &plugin_id)
+                .join("logs")
+                .join("plugin.log");
+            std::fs::read(&path)
+                .map(|bytes| {
+                    String::from_utf8_lossy(&bytes)
+                        .lines()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    };
+    let skip = lines.len().saturating_sub(tail);
+    Ok(lines.into_iter().skip(skip).collect())
+}
+
+static NEXT_RPC_ID: AtomicU64 = AtomicU64::new(1);
+
 /// Send a JSON-RPC request to a running plugin and return the response.
+///
+/// Calls are multiplexed: the request id is swapped for a host-unique one
+/// and restored in the response, so concurrent callers never block each
+/// other. Requests without an id are sent as notifications and return "".
 #[tauri::command]
 pub async fn plugin_invoke(
+    app: tauri::AppHandle,
     state: State<'_, PluginProcessManager>,
     plugin_id: String,
     request: String,
-) -> Result<String, String> {
-    // Write request and take process out of the map (same pattern as mcp_send_request)
-    let mut proc = {
-        let mut processes = state.processes.lock().map_err(|e| e.to_string())?;
-        let proc = processes
-            .get_mut(&plugin_id)
-            .ok_or("插件未运行")?;
-        if writeln!(proc.stdin, "{}", request).is_err() {
-            return Err("写入插件 stdin 失败".to_string());
-        }
-        proc.stdin.flush().map_err(|_| "刷新插件 stdin 失败".to_string())?;
-        processes.remove(&plugin_id).ok_or("插件未运行")?
+    timeout_ms: Option<u64>,
+) -> Result<String, CommandError> {
+    let mut msg: serde_json::Value = serde_json::from_str(&request).map_err(|_| {
+        CommandError::new(ErrorCode::InvalidArgument, "Plugin request is not valid JSON")
+    })?;
+    let original_id = msg.get("id").filter(|v| !v.is_null()).cloned();
+
+    let (stdin, pending, permissions) = {
+        let processes = state.processes.lock().map_err(|e| e.to_string())?;
+        let proc = processes.get(&plugin_id).ok_or_else(|| {
+            CommandError::new(ErrorCode::PluginNotRunning, "Plugin is not running")
+        })?;
+        (proc.stdin.clone(), proc.pending.clone(), proc.permissions.clone())
     };
+    let method = msg.get("method").and_then(|m| m.as_str()).unwrap_or_default();
+    if let Err(permission) = check_host_method(method, &permissions) {
+        emit_permission_denied(&app, &plugin_id, method, permission);
+        return Err(CommandError::new(
+            ErrorCode::PluginPermissionDenied,
+            format!("Plugin has not declared the {} permission", permission),
+        )
+        .with_details(serde_json::json!({ "permission": permission })));
+    }
+    if let Ok(mut instances) = state.instances.lock() {
+        if let Some(inst) = instances.get_mut(&plugin_id) {
+            inst.last_invoke_ms = Some(epoch_ms());
+        }
+    }
 
-    // Read response on a blocking thread
-    let (result, returned_proc) = tokio::task::spawn_blocking(move || {
-        let result = read_plugin_response(&mut proc);
-        (result, proc)
+    let mut reply_rx = None;
+    let host_id = NEXT_RPC_ID.fetch_add(1, Ordering::SeqCst);
+    if let Some(original_id) = original_id {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let mut p = pending.lock().map_err(|e| e.to_string())?;
+        if p.closed {
+            return Err(plugin_exited());
+        }
+        p.waiters.insert(host_id, (original_id, tx));
+        msg["id"] = serde_json::Value::from(host_id);
+        reply_rx = Some(rx);
+    }
+
+    let line = msg.to_string();
+    let write_result = tokio::task::spawn_blocking(move || {
+        let mut guard = stdin.lock().map_err(|e| e.to_string())?;
+        let stdin = guard.as_mut().ok_or_else(plugin_stopped)?;
+        writeln!(stdin, "{}", line)
+            .and_then(|_| stdin.flush())
+            .map_err(|_| plugin_exited())
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?;
 
-    // Put process back or clean up
-    match &result {
-        Ok(_) => {
-            if let Ok(mut processes) = state.processes.lock() {
-                processes.insert(plugin_id, returned_proc);
-            }
-        }
-        Err(_) => {
-            let mut proc = returned_proc;
-            let _ = proc.child.kill();
-            let _ = proc.child.try_wait();
-        }
+    let forget = |pending: &PendingMap| {
+        if let Ok(mut p) = pending.lock() {
+            p.waiters.remove(&host_id);
+        }
+    };
+    if let Err(e) = write_result {
+        forget(&pending);
+        return Err(e);
+    }
+    let Some(rx) = reply_rx else {
+        return Ok(String::new());
+    };
+
+    let timeout = timeout_ms.map(Duration::from_millis).unwrap_or(PLUGIN_READ_TIMEOUT);
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(_)) => Err(plugin_exited()),
+        Err(_) => {
+            forget(&pending);
+            Err(CommandError::new(ErrorCode::PluginTimeout, "Plugin did not respond in time")
+                .with_details(serde_json::json!({ "timeoutMs": timeout.as_millis() as u64 })))
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Registry & Market commands
+// ---------------------------------------------------------------------------
+
+/// Conditional-request cache for registry/GitHub URLs (`plugin-registry-etags.json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EtagEntry {
+    etag: String,
+    body: serde_json::Value,
+}
+
+type EtagStore = Arc<Mutex<HashMap<String, EtagEntry>>>;
+
+enum RegistryFetch {
+    /// Fresh (200) or unchanged (304, served from the ETag cache).
+    Ok(serde_json::Value),
+    /// GitHub rate limit hit; carries the reset time (epoch ms) and any cached body.
+    RateLimited {
+        reset_at: u64,
+        cached: Option<serde_json::Value>,
+    },
+    Failed(String),
+}
+
+/// Platform key for a release zip asset name.
+fn platform_for_asset(name: &str) -> Option<&'static str> {
+    if name.ends_with("macos-arm64.zip") {
+        Some("darwin-aarch64")
+    } else if name.ends_with("macos-x64.zip") {
+        Some("darwin-x86_64")
+    } else if name.ends_with("windows.zip") {
+        Some("win32")
+    } else if name.ends_with("linux.zip") {
+        Some("linux-x86_64")
+    } else {
+        None
     }
+}
 
-    result
+fn icon_cache_key(entry: &serde_json::Value) -> Option<String> {
+    let id = entry.get("id")?.as_str()?;
+    let version = entry.get("pinnedVersion").and_then(|v| v.as_str()).unwrap_or("main");
+    Some(format!("{}@{}", id, version))
 }
 
-fn read_plugin_response(proc: &mut PluginProcess) -> Result<String, String> {
-    loop {
-        match proc.line_rx.recv_timeout(PLUGIN_READ_TIMEOUT) {
-            Ok(ReadResult::Line(line)) => {
-                if line.len() > PLUGIN_MAX_LINE {
-                    return Err("插件响应超过长度限制".to_string());
-                }
-                let trimmed = line.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-                if trimmed.starts_with('{') {
-                    return Ok(trimmed.to_string());
-                }
-            }
-            Ok(ReadResult::Eof) => return Err("插件进程意外退出".to_string()),
-            Ok(ReadResult::Error(e)) => return Err(format!("读取插件响应失败: {}", e)),
-            Err(_) => return Err("插件响应超时".to_string()),
-        }
+/// Rate-limit reset time (epoch ms) if this response is a GitHub rate-limit rejection.
+fn rate_limit_reset(status: u16, headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    if status != 403 && status != 429 {
+        return None;
     }
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if header("x-ratelimit-remaining") != Some("0") && header("retry-after").is_none() {
+        return None;
+    }
+    if let Some(reset) = header("x-ratelimit-reset").and_then(|v| v.parse::<u64>().ok()) {
+        return Some(reset * 1000);
+    }
+    let retry_after = header("retry-after")
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(60);
+    Some(epoch_ms() + retry_after * 1000)
 }
 
-// ---------------------------------------------------------------------------
-// Registry & Market commands
-// ---------------------------------------------------------------------------
+/// GET a JSON document with `If-None-Match`, reusing the cached body on 304.
+async fn fetch_json_cached(
+    client: &reqwest::Client,
+    url: &str,
+    github_token: Option<&str>,
+    etags: &EtagStore,
+) -> RegistryFetch {
+    let cached = etags.lock().ok().and_then(|e| e.get(url).cloned());
+    let mut req = client.get(url).header("Accept", "application/vnd.github.v3+json");
+    if let Some(c) = &cached {
+        req = req.header("If-None-Match", &c.etag);
+    }
+    if let Some(token) = github_token.filter(|_| url.starts_with("https://api.github.com/")) {
+        req = req.bearer_auth(token);
+    }
+
+    let resp = match req.send().await {
+        Ok(r) => r,
+        Err(_) => return RegistryFetch::Failed("network error".to_string()),
+    };
+    let status = resp.status().as_u16();
+    if status == 304 {
+        if let Some(c) = cached {
+            return RegistryFetch::Ok(c.body);
+        }
+    }
+    if let Some(reset_at) = rate_limit_reset(status, resp.headers()) {
+        return RegistryFetch::RateLimited {
+            reset_at,
+            cached: cached.map(|c| c.body),
+        };
+    }
+    if !resp.status().is_success() {
+        return RegistryFetch::Failed(format!("HTTP {}", status));
+    }
+
+    let etag = resp
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = match resp.json::<serde_json::Value>().await {
+        Ok(b) => b,
+        Err(_) => return RegistryFetch::Failed("invalid JSON".to_string()),
+    };
+    if let (Some(etag), Ok(mut store)) = (etag, etags.lock()) {
+        store.insert(
+            url.to_string(),
+            EtagEntry {
+                etag,
+                body: body.clone(),
+            },
+        );
+    }
+    RegistryFetch::Ok(body)
+}
 
 /// Fetch the plugin registry and GitHub metadata.
 /// Returns cached data immediately if fresh enough; fetches in parallel if stale.
+/// Uses ETags so unchanged resources don't count against GitHub's anonymous
+/// rate limit; a `github-token` secret, when present, raises that limit.
 #[tauri::command]
 pub async fn plugin_registry_fetch(
     app: tauri::AppHandle,
+    state: State<'_, PluginProcessManager>,
+    key_state: State<'_, super::ai_proxy::AIProxyState>,
     force_refresh: bool,
-) -> Result<serde_json::Value, String> {
+    locale: Option<String>,
+) -> Result<serde_json::Value, CommandError> {
+    // Display names are resolved on every return so cached results follow the UI language
+    let locale = locale.unwrap_or_else(|| state.locale());
     let app_data = app
         .path()
         .app_data_dir()
-        .map_err(|_| "无法获取 appData 目录".to_string())?;
+        .map_err(|_| app_data_unavailable())?;
     let cache_path = app_data.join("plugin-registry-cache.json");
+    let etag_path = app_data.join("plugin-registry-etags.json");
 
     // Check if cache is still fresh
     if !force_refresh && cache_path.exists() {
@@ -876,29 +3553,117 @@ pub async fn plugin_registry_fetch(
                 if age_ms < CACHE_TTL_MS {
                     let mut result = cache.clone();
                     result["fromCache"] = serde_json::Value::Bool(true);
+                    localize_registry_result(&mut result, &locale);
                     return Ok(result);
                 }
             }
         }
     }
 
+    // Icons resolved last time, keyed by "<id>@<pinnedVersion>"
+    let known_icons: HashMap<String, String> = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+        .and_then(|c| c.get("plugins").and_then(|p| p.as_array()).cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|p| {
+            let icon = p.get("iconUrl")?.as_str().filter(|u| !u.is_empty())?;
+            Some((icon_cache_key(p)?, icon.to_string()))
+        })
+        .collect();
+
+    key_state.ensure_secrets_loaded().await;
+    let github_token = key_state
+        .key_cache
+        .lock()
+        .ok()
+        .and_then(|c| c.get("github-token").cloned())
+        .filter(|t| !t.trim().is_empty());
+
+    let etags: EtagStore = Arc::new(Mutex::new(
+        std::fs::read_to_string(&etag_path)
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default(),
+    ));
+
+    if REGISTRY_PUBLIC_KEY.is_none() && !cfg!(debug_assertions) {
+        log::warn!(
+            "This build has no MORAYA_REGISTRY_PUBKEY; plugin registry signatures are NOT verified"
+        );
+    }
+
     // Fetch index.json from registry
-    let client = reqwest::Client::builder()
+    let client = super::settings::client_builder()
         .timeout(Duration::from_secs(15))
+        .connect_timeout(REGISTRY_CONNECT_TIMEOUT)
         .user_agent("Moraya/0.16.0")
         .build()
-        .map_err(|_| "HTTP client 初始化失败".to_string())?;
-
-    let index_resp = client
-        .get(REGISTRY_INDEX_URL)
-        .send()
-        .await
-        .map_err(|_| "无法访问插件注册表".to_string())?;
-
-    let index = index_resp
-        .json::<serde_json::Value>()
+        .map_err(|_| http_client_failed())?;
+
+    // The index is fetched as raw bytes (not via the ETag cache) so its
+    // signature can be checked against exactly what was served.
+    // Whichever source answers is reused for the other raw GitHub requests
+    // A mirror could serve a forged index with matching package hashes, so
+    // mirrors are used only when the index signature will be checked
+    let (index_resp, source) = get_with_mirrors(
+        &client,
+        REGISTRY_INDEX_URL,
+        read_registry_mirror(&app),
+        REGISTRY_PUBLIC_KEY.is_some(),
+    )
+    .await
+    .map_err(|e| {
+        if REGISTRY_PUBLIC_KEY.is_some() {
+            return e;
+        }
+        CommandError::new(
+            ErrorCode::NetworkError,
+            "Cannot reach GitHub. Mirrors are disabled because this build has no registry \
+             public key to verify an index they serve",
+        )
+    })?;
+    let registry_unreachable =
+        || CommandError::new(ErrorCode::NetworkError, "Cannot access the plugin registry");
+    if !index_resp.status().is_success() {
+        return Err(registry_unreachable());
+    }
+    let index_bytes = index_resp
+        .bytes()
         .await
-        .map_err(|_| "注册表 index.json 格式错误".to_string())?;
+        .map_err(|_| registry_unreachable())?;
+    if let Some(public_key) = REGISTRY_PUBLIC_KEY {
+        let sig = fetch_text(&client, &mirror_url(REGISTRY_INDEX_SIG_URL, source)).await;
+        if let Err(e) = verify_signature(&index_bytes, sig.as_deref(), public_key) {
+            let msg = match e {
+                SignatureError::Missing => "Registry signature is missing (index.json.sig)",
+                SignatureError::Invalid => "Registry signature is invalid; index rejected",
+            };
+            log::warn!("{}", msg);
+            // Fall back to the last verified result
+            let cached = std::fs::read_to_string(&cache_path)
+                .ok()
+                .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok());
+            return match cached {
+                Some(mut cache) => {
+                    cache["fromCache"] = serde_json::Value::Bool(true);
+                    cache["signatureError"] = serde_json::json!(msg);
+                    cache["source"] = serde_json::json!(source_label(source));
+                    localize_registry_result(&mut cache, &locale);
+                    Ok(cache)
+                }
+                None => Err(CommandError {
+                    message: msg.to_string(),
+                    ..signature_error(e)
+                }),
+            };
+        }
+    } else {
+        log::warn!("Debug build without a registry public key; skipping index signature check");
+    }
+    let index: serde_json::Value = serde_json::from_slice(&index_bytes)
+        .map_err(|_| CommandError::new(ErrorCode::Internal, "Registry index.json is malformed"))?;
 
     let plugins_arr = index
         .get("plugins")
@@ -910,110 +3675,208 @@ pub async fn plugin_registry_fetch(
     let mut handles = Vec::new();
     for plugin_entry in plugins_arr {
         let client = client.clone();
+        let etags = etags.clone();
+        let token = github_token.clone();
+        let fallback = plugin_entry.clone();
+        let known_icon = icon_cache_key(&plugin_entry).and_then(|k| known_icons.get(&k).cloned());
         let handle = tokio::spawn(async move {
-            enrich_plugin_entry(client, plugin_entry).await
+            enrich_plugin_entry(client, plugin_entry, token.as_deref(), &etags, known_icon, source)
+                .await
         });
-        handles.push(handle);
+        handles.push((handle, fallback));
     }
 
+    // A failed enrichment keeps the bare registry entry (flagged) instead of
+    // dropping it, so plugins never silently vanish from the market.
     let mut enriched_plugins = Vec::new();
-    for handle in handles {
+    let mut rate_limited_until: Option<u64> = None;
+    for (handle, mut fallback) in handles {
         match handle.await {
-            Ok(Ok(plugin)) => enriched_plugins.push(plugin),
-            _ => {} // Skip failed entries (network error, etc.)
+            Ok((plugin, reset_at)) => {
+                if let Some(reset_at) = reset_at {
+                    rate_limited_until = Some(rate_limited_until.unwrap_or(0).max(reset_at));
+                }
+                enriched_plugins.push(plugin);
+            }
+            Err(_) => {
+                fallback["enrichError"] = serde_json::json!("task failed");
+                enriched_plugins.push(fallback);
+            }
+        }
+    }
+
+    if let Ok(store) = etags.lock() {
+        if let Ok(content) = serde_json::to_string(&*store) {
+            let _ = std::fs::write(&etag_path, content);
         }
     }
 
     let now = epoch_ms();
-    let result = serde_json::json!({
+    let mut result = serde_json::json!({
         "fetchedAt": now,
         "plugins": enriched_plugins,
         "fromCache": false,
+        "rateLimitedUntil": rate_limited_until,
+        "source": source_label(source),
     });
 
-    // Write cache
-    if let Ok(content) = serde_json::to_string(&result) {
+    // Write cache (a rate-limited result is partial, so let it expire right away)
+    let mut cached = result.clone();
+    if rate_limited_until.is_some() {
+        cached["fetchedAt"] = serde_json::json!(0);
+    }
+    if let Ok(content) = serde_json::to_string(&cached) {
         let _ = std::fs::write(&cache_path, content);
     }
 
+    localize_registry_result(&mut result, &locale);
     Ok(result)
 }
 
+/// Icon sources that need no probing, in priority order: the registry
+/// entry's `icon` (absolute, or relative to the registry repo), then a
+/// release asset named `icon.png`.
+fn declared_icon_url(entry: &serde_json::Value, release: Option<&serde_json::Value>) -> Option<String> {
+    if let Some(icon) = entry.get("icon").and_then(|i| i.as_str()).filter(|i| !i.is_empty()) {
+        if icon.starts_with("https://") {
+            return Some(icon.to_string());
+        }
+        if !icon.contains("..") && !icon.contains("://") {
+            return Some(format!("{}{}", REGISTRY_RAW_BASE, icon.trim_start_matches('/')));
+        }
+    }
+    release?
+        .get("assets")?
+        .as_array()?
+        .iter()
+        .find(|a| a.get("name").and_then(|n| n.as_str()) == Some("icon.png"))
+        .and_then(|a| a.get("browser_download_url"))
+        .and_then(|u| u.as_str())
+        .map(str::to_string)
+}
+
+/// `icon.png` at the root of the plugin repo at its pinned version.
+fn repo_icon_url(repo: &str, pinned_version: &str) -> String {
+    format!(
+        "https://raw.githubusercontent.com/{}/{}/icon.png",
+        repo, pinned_version
+    )
+}
+
+/// Pick the icon URL for a market entry. The repo fallback is verified with
+/// a HEAD request; `known_icon` (from the previous registry cache for the
+/// same version) skips that probe. Returns "" when no icon exists.
+async fn resolve_icon_url(
+    client: &reqwest::Client,
+    entry: &serde_json::Value,
+    release: Option<&serde_json::Value>,
+    repo: &str,
+    known_icon: Option<String>,
+) -> String {
+    if let Some(url) = declared_icon_url(entry, release) {
+        return url;
+    }
+    if let Some(url) = known_icon {
+        return url;
+    }
+    let pinned_version = entry
+        .get("pinnedVersion")
+        .and_then(|v| v.as_str())
+        .unwrap_or("main");
+    let url = repo_icon_url(repo, pinned_version);
+    match client.head(&url).send().await {
+        Ok(resp) if resp.status().is_success() => url,
+        _ => String::new(),
+    }
+}
+
 /// Fetch GitHub API data and plugin.json for a single registry entry.
+/// Never fails: problems are recorded in `enrichError` on the entry. Returns
+/// the rate-limit reset time if GitHub refused any request.
 async fn enrich_plugin_entry(
     client: reqwest::Client,
     mut entry: serde_json::Value,
-) -> Result<serde_json::Value, String> {
-    let repo = entry
-        .get("repo")
-        .and_then(|r| r.as_str())
-        .ok_or("missing repo")?
-        .to_string();
+    github_token: Option<&str>,
+    etags: &EtagStore,
+    known_icon: Option<String>,
+    source: Option<usize>,
+) -> (serde_json::Value, Option<u64>) {
+    let Some(repo) = entry.get("repo").and_then(|r| r.as_str()).map(str::to_string) else {
+        entry["enrichError"] = serde_json::json!("missing repo");
+        return (entry, None);
+    };
 
     // Fetch repo metadata from GitHub API
     let repo_url = format!("https://api.github.com/repos/{}", repo);
     let releases_url = format!("https://api.github.com/repos/{}/releases/latest", repo);
-    let (repo_parts, _) = repo.split_once('/').unwrap_or(("", ""));
     let (_, repo_name) = repo.split_once('/').unwrap_or(("", &repo));
-    let icon_url = format!(
-        "https://raw.githubusercontent.com/{}/main/icon.png",
-        format!("moraya-apps/moraya-plugin-registry/main/plugins/{}", repo_name)
-    );
+
+    let mut errors: Vec<String> = Vec::new();
+    let mut reset: Option<u64> = None;
+    let mut take = |what: &str, fetched: RegistryFetch| -> Option<serde_json::Value> {
+        match fetched {
+            RegistryFetch::Ok(v) => Some(v),
+            RegistryFetch::RateLimited { reset_at, cached } => {
+                reset = Some(reset.unwrap_or(0).max(reset_at));
+                if cached.is_none() {
+                    errors.push(format!("{}: rate limited", what));
+                }
+                cached
+            }
+            RegistryFetch::Failed(e) => {
+                errors.push(format!("{}: {}", what, e));
+                None
+            }
+        }
+    };
 
     // Concurrent GitHub requests
     let (repo_result, releases_result) = tokio::join!(
-        client.get(&repo_url)
-            .header("Accept", "application/vnd.github.v3+json")
-            .send(),
-        client.get(&releases_url)
-            .header("Accept", "application/vnd.github.v3+json")
-            .send(),
+        fetch_json_cached(&client, &repo_url, github_token, etags),
+        fetch_json_cached(&client, &releases_url, github_token, etags),
     );
 
     // Parse repo info
-    if let Ok(resp) = repo_result {
-        if let Ok(repo_data) = resp.json::<serde_json::Value>().await {
-            entry["stars"] = repo_data.get("stargazers_count").cloned().unwrap_or(serde_json::Value::Null);
-            entry["description"] = repo_data.get("description").cloned().unwrap_or(serde_json::json!(""));
-            entry["license"] = repo_data
-                .get("license")
-                .and_then(|l: &serde_json::Value| l.get("spdx_id"))
-                .cloned()
-                .unwrap_or(serde_json::Value::Null);
-            entry["updatedAt"] = repo_data.get("updated_at").cloned().unwrap_or(serde_json::Value::Null);
-            entry["name"] = repo_data.get("name").cloned().unwrap_or(serde_json::json!(repo_name));
-        }
+    if let Some(repo_data) = take("repo", repo_result) {
+        entry["stars"] = repo_data.get("stargazers_count").cloned().unwrap_or(serde_json::Value::Null);
+        entry["description"] = repo_data.get("description").cloned().unwrap_or(serde_json::json!(""));
+        entry["license"] = repo_data
+            .get("license")
+            .and_then(|l: &serde_json::Value| l.get("spdx_id"))
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        entry["updatedAt"] = repo_data.get("updated_at").cloned().unwrap_or(serde_json::Value::Null);
+        entry["name"] = repo_data.get("name").cloned().unwrap_or(serde_json::json!(repo_name));
     }
 
     // Parse latest release — build downloadUrls map
-    if let Ok(resp) = releases_result {
-        if let Ok(release_data) = resp.json::<serde_json::Value>().await {
-            entry["changelog"] = release_data
-                .get("body")
-                .cloned()
-                .unwrap_or(serde_json::json!(""));
-
-            let mut download_urls = serde_json::Map::new();
-            if let Some(assets) = release_data.get("assets").and_then(|a: &serde_json::Value| a.as_array()) {
-                for asset in assets {
-                    let name = asset.get("name").and_then(|n: &serde_json::Value| n.as_str()).unwrap_or("");
-                    let url = asset
-                        .get("browser_download_url")
-                        .and_then(|u: &serde_json::Value| u.as_str())
-                        .unwrap_or("");
-                    if name.ends_with("macos-arm64.zip") {
-                        download_urls.insert("darwin-aarch64".to_string(), serde_json::json!(url));
-                    } else if name.ends_with("macos-x64.zip") {
-                        download_urls.insert("darwin-x86_64".to_string(), serde_json::json!(url));
-                    } else if name.ends_with("windows.zip") {
-                        download_urls.insert("win32".to_string(), serde_json::json!(url));
-                    } else if name.ends_with("linux.zip") {
-                        download_urls.insert("linux-x86_64".to_string(), serde_json::json!(url));
+    let release = take("release", releases_result);
+    if let Some(release_data) = &release {
+        entry["changelog"] = release_data
+            .get("body")
+            .cloned()
+            .unwrap_or(serde_json::json!(""));
+
+        let mut download_urls = serde_json::Map::new();
+        let mut signature_urls = serde_json::Map::new();
+        if let Some(assets) = release_data.get("assets").and_then(|a: &serde_json::Value| a.as_array()) {
+            for asset in assets {
+                let name = asset.get("name").and_then(|n: &serde_json::Value| n.as_str()).unwrap_or("");
+                let url = asset
+                    .get("browser_download_url")
+                    .and_then(|u: &serde_json::Value| u.as_str())
+                    .unwrap_or("");
+                if let Some(zip_name) = name.strip_suffix(".sig") {
+                    if let Some(platform) = platform_for_asset(zip_name) {
+                        signature_urls.insert(platform.to_string(), serde_json::json!(url));
                     }
+                } else if let Some(platform) = platform_for_asset(name) {
+                    download_urls.insert(platform.to_string(), serde_json::json!(url));
                 }
             }
-            entry["downloadUrls"] = serde_json::Value::Object(download_urls);
         }
+        entry["downloadUrls"] = serde_json::Value::Object(download_urls);
+        entry["signatureUrls"] = serde_json::Value::Object(signature_urls);
     }
 
     // Fetch plugin.json from raw GitHub
@@ -1025,18 +3888,23 @@ async fn enrich_plugin_entry(
         "https://raw.githubusercontent.com/{}/{}/plugin.json",
         repo, pinned_version
     );
-    if let Ok(resp) = client.get(&raw_url).send().await {
-        if let Ok(text) = resp.text().await {
-            if let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&text) {
-                entry["manifest"] = manifest;
+    let manifest = fetch_json_cached(&client, &mirror_url(&raw_url, source), None, etags).await;
+    if let Some(manifest) = take("plugin.json", manifest) {
+        for key in ["nameLocalized", "descriptionLocalized"] {
+            if let Some(map) = manifest.get(key).filter(|m| m.is_object()) {
+                entry[key] = map.clone();
             }
         }
+        entry["manifest"] = manifest;
     }
 
+    let icon_url = resolve_icon_url(&client, &entry, release.as_ref(), &repo, known_icon).await;
     entry["iconUrl"] = serde_json::json!(icon_url);
-    let _ = repo_parts; // suppress unused warning
+    if !errors.is_empty() {
+        entry["enrichError"] = serde_json::json!(errors.join("; "));
+    }
 
-    Ok(entry)
+    (entry, reset)
 }
 
 // ---------------------------------------------------------------------------
@@ -1050,14 +3918,15 @@ const RENDERER_CDN_ALLOWLIST: &[&str] = &[
     "registry.npmjs.org",
 ];
 
-fn validate_renderer_url(url: &str) -> Result<(), String> {
-    let parsed = reqwest::Url::parse(url).map_err(|_| "Invalid URL".to_string())?;
+fn validate_renderer_url(url: &str) -> Result<(), CommandError> {
+    let invalid = |message: String| CommandError::new(ErrorCode::InvalidArgument, message);
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid("Invalid URL".to_string()))?;
     if parsed.scheme() != "https" {
-        return Err("Only HTTPS URLs are allowed".to_string());
+        return Err(invalid("Only HTTPS URLs are allowed".to_string()));
     }
     let host = parsed.host_str().unwrap_or("");
     if !RENDERER_CDN_ALLOWLIST.contains(&host) {
-        return Err(format!("CDN host '{}' is not in the allowlist", host));
+        return Err(invalid(format!("CDN host '{}' is not in the allowlist", host)));
     }
     Ok(())
 }
@@ -1069,22 +3938,20 @@ pub async fn download_renderer_plugin(
     app: tauri::AppHandle,
     plugin_id: String,
     url: String,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     // Validate plugin_id: alphanumeric, hyphen, underscore, dot only
-    if !plugin_id
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
-    {
-        return Err("Invalid plugin id".to_string());
+    if !is_renderer_plugin_id(&plugin_id) {
+        return Err(CommandError::new(ErrorCode::PluginInvalidId, "Invalid plugin id"));
     }
 
     // Validate URL against CDN allowlist
     validate_renderer_url(&url)?;
 
     // Resolve output path: {appDataDir}/renderer-plugins/{plugin_id}/index.js
-    let data_dir = app.path().app_data_dir().map_err(|_| "Cannot resolve app data dir".to_string())?;
+    let data_dir = app.path().app_data_dir().map_err(|_| app_data_unavailable())?;
     let plugin_dir = data_dir.join("renderer-plugins").join(&plugin_id);
-    std::fs::create_dir_all(&plugin_dir).map_err(|_| "Cannot create plugin directory".to_string())?;
+    std::fs::create_dir_all(&plugin_dir)
+        .map_err(|_| io_failed("Cannot create plugin directory"))?;
     let out_path = plugin_dir.join("index.js");
 
     // Skip download if the file already exists on disk (idempotent re-enable)
@@ -1093,58 +3960,63 @@ pub async fn download_renderer_plugin(
     }
 
     // Download with streaming
-    let client = reqwest::Client::builder()
+    let client = super::settings::client_builder()
         .timeout(Duration::from_secs(60))
         .user_agent("Moraya/0.22.0")
         .build()
-        .map_err(|_| "HTTP client error".to_string())?;
+        .map_err(|_| http_client_failed())?;
 
-    let resp = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?;
+    let network_error = |e: reqwest::Error| {
+        CommandError::new(ErrorCode::NetworkError, format!("Network error: {}", e))
+    };
+    let resp = client.get(&url).send().await.map_err(network_error)?;
 
     if !resp.status().is_success() {
-        return Err(format!("HTTP {} from {}", resp.status().as_u16(), url));
+        return Err(download_failed(resp.status().as_u16()));
     }
 
-    let bytes = resp.bytes().await.map_err(|e| format!("Read error: {}", e))?;
+    let bytes = resp.bytes().await.map_err(network_error)?;
 
-    std::fs::write(&out_path, &bytes).map_err(|e| format!("Write error: {}", e))?;
+    std::fs::write(&out_path, &bytes).map_err(|e| io_failed(&format!("Write error: {}", e)))?;
 
     Ok(out_path.to_string_lossy().into_owned())
 }
 
 /// Delete a downloaded renderer plugin bundle from disk.
 #[tauri::command]
-pub async fn delete_renderer_plugin(app: tauri::AppHandle, plugin_id: String) -> Result<(), String> {
-    if !plugin_id
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
-    {
-        return Err("Invalid plugin id".to_string());
+pub async fn delete_renderer_plugin(
+    app: tauri::AppHandle,
+    plugin_id: String,
+) -> Result<(), CommandError> {
+    if !is_renderer_plugin_id(&plugin_id) {
+        return Err(CommandError::new(ErrorCode::PluginInvalidId, "Invalid plugin id"));
     }
-    let data_dir = app.path().app_data_dir().map_err(|_| "Cannot resolve app data dir".to_string())?;
+    let data_dir = app.path().app_data_dir().map_err(|_| app_data_unavailable())?;
     let plugin_dir = data_dir.join("renderer-plugins").join(&plugin_id);
     if plugin_dir.exists() {
-        std::fs::remove_dir_all(&plugin_dir).map_err(|_| "Failed to delete plugin".to_string())?;
+        std::fs::remove_dir_all(&plugin_dir).map_err(|_| io_failed("Failed to delete plugin"))?;
     }
     Ok(())
 }
 
+fn is_renderer_plugin_id(plugin_id: &str) -> bool {
+    plugin_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
 /// Fetch the browser_download_url for the GitHub release asset matching the given platform.
 /// Called from the frontend URL-import flow (bypasses CSP connect-src restriction).
 #[tauri::command]
 pub async fn plugin_fetch_github_asset(
     owner_repo: String,
     platform: String,
-) -> Result<String, String> {
-    let client = reqwest::Client::builder()
+) -> Result<String, CommandError> {
+    let client = super::settings::client_builder()
         .timeout(Duration::from_secs(15))
         .user_agent("Moraya/1.0")
         .build()
-        .map_err(|_| "HTTP client 初始化失败".to_string())?;
+        .map_err(|_| http_client_failed())?;
 
     let url = format!("https://api.github.com/repos/{}/releases/latest", owner_repo);
     let resp = client
@@ -1152,16 +4024,20 @@ pub async fn plugin_fetch_github_asset(
         .header("Accept", "application/vnd.github.v3+json")
         .send()
         .await
-        .map_err(|_| "无法访问 GitHub API".to_string())?;
+        .map_err(|_| CommandError::new(ErrorCode::NetworkError, "Cannot access the GitHub API"))?;
 
     if !resp.status().is_success() {
-        return Err(format!("GitHub API 返回 HTTP {}", resp.status().as_u16()));
+        let status = resp.status().as_u16();
+        return Err(CommandError::new(
+            ErrorCode::NetworkError,
+            format!("GitHub API returned HTTP {}", status),
+        )
+        .with_details(serde_json::json!({ "status": status })));
     }
 
-    let release: serde_json::Value = resp
-        .json()
-        .await
-        .map_err(|_| "解析 GitHub 响应失败".to_string())?;
+    let release: serde_json::Value = resp.json().await.map_err(|_| {
+        CommandError::new(ErrorCode::NetworkError, "Failed to parse the GitHub response")
+    })?;
 
     let suffix = match platform.as_str() {
         "darwin-aarch64" => "macos-arm64.zip",
@@ -1173,7 +4049,7 @@ pub async fn plugin_fetch_github_asset(
     let assets = release
         .get("assets")
         .and_then(|a| a.as_array())
-        .ok_or_else(|| "发布版本中没有找到资产文件".to_string())?;
+        .ok_or_else(|| invalid_package("The release has no assets"))?;
 
     let asset = assets
         .iter()
@@ -1183,39 +4059,690 @@ pub async fn plugin_fetch_github_asset(
                 .map(|n| n.ends_with(suffix))
                 .unwrap_or(false)
         })
-        .ok_or_else(|| "未找到适用于当前平台的发布包".to_string())?;
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::PluginUnsupportedPlatform,
+                "No release package for this platform",
+            )
+            .with_details(serde_json::json!({ "platform": platform }))
+        })?;
 
     asset
         .get("browser_download_url")
         .and_then(|u| u.as_str())
         .map(|s| s.to_string())
-        .ok_or_else(|| "发布包缺少下载链接".to_string())
+        .ok_or_else(|| invalid_package("The release package has no download link"))
 }
 
-/// Fetch blacklist and return IDs that should be force-disabled.
+/// Fetch the blacklist, cache it, and force-disable any running plugin on it.
+/// Returns the blacklisted IDs; falls back to the last cached list when offline.
 #[tauri::command]
-pub async fn plugin_fetch_blacklist() -> Result<Vec<String>, String> {
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .user_agent("Moraya/0.16.0")
-        .build()
-        .map_err(|_| "HTTP client 初始化失败".to_string())?;
+pub async fn plugin_fetch_blacklist(
+    app: tauri::AppHandle,
+    state: State<'_, PluginProcessManager>,
+) -> Result<Vec<String>, CommandError> {
+    let fetched = async {
+        let client = super::settings::client_builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent("Moraya/0.16.0")
+            .build()
+            .ok()?;
+        let resp = client.get(REGISTRY_BLACKLIST_URL).send().await.ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+        let data = resp.json::<serde_json::Value>().await.ok()?;
+        Some(parse_blacklist(&data))
+    }
+    .await;
+
+    let entries = match fetched {
+        Some(entries) => {
+            let cache = BlacklistCache {
+                entries: entries.clone(),
+                fetched_at: epoch_ms(),
+            };
+            if let (Some(path), Ok(json)) =
+                (blacklist_cache_path(&app), serde_json::to_string(&cache))
+            {
+                let _ = std::fs::write(path, json);
+            }
+            if let Ok(mut c) = state.blacklist.lock() {
+                *c = Some(cache);
+            }
+            entries
+        }
+        None => {
+            // Best-effort: serve whatever we had before
+            let mut c = state.blacklist.lock().map_err(|e| e.to_string())?;
+            if c.is_none() {
+                *c = read_blacklist_cache(&app);
+            }
+            c.as_ref().map(|c| c.entries.clone()).unwrap_or_default()
+        }
+    };
 
-    let resp = client
-        .get(REGISTRY_BLACKLIST_URL)
-        .send()
-        .await
-        .map_err(|_| String::new())?; // Silently fail — blacklist is best-effort
+    let running = running_ids(&state);
+    for entry in entries
+        .iter()
+        .filter(|e| !is_dev_plugin(&e.id) && running.contains(&e.id))
+    {
+        let _ = stop_plugin_blocking(&app, &entry.id).await;
+        if let Ok(root) = plugins_root(&app) {
+            let _ = update_state_file(&root, |list| {
+                if let Some(e) = list.iter_mut().find(|e| e.id == entry.id) {
+                    e.enabled = false;
+                }
+            });
+        }
+        let _ = app.emit(
+            "plugin:force_disabled",
+            serde_json::json!({ "pluginId": entry.id, "reason": entry.reason }),
+        );
+    }
 
-    let data = resp.json::<serde_json::Value>().await.map_err(|_| String::new())?;
-    let ids = data
-        .get("blacklist")
-        .and_then(|b: &serde_json::Value| b.as_array())
-        .map(|arr: &Vec<serde_json::Value>| {
-            arr.iter()
-                .filter_map(|e: &serde_json::Value| e.get("id").and_then(|id: &serde_json::Value| id.as_str()).map(String::from))
-                .collect()
-        })
-        .unwrap_or_default();
-    Ok(ids)
+    Ok(entries.into_iter().map(|e| e.id).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    fn test_manifest(id: &str) -> PluginManifest {
+        PluginManifest {
+            id: id.to_string(),
+            name: "Test".to_string(),
+            version: "1.0.0".to_string(),
+            description: String::new(),
+            author: String::new(),
+            license: "MIT".to_string(),
+            api_version: "1".to_string(),
+            entry: HashMap::from([(current_platform().to_string(), "bin/plugin".to_string())]),
+            protocol: "jsonrpc-stdio".to_string(),
+            permissions: vec![],
+            permission_reasons: HashMap::new(),
+            sandbox_level: "standard".to_string(),
+            homepage: None,
+            limits: None,
+            auto_restart: false,
+            name_localized: HashMap::new(),
+            description_localized: HashMap::new(),
+        }
+    }
+
+    fn write_plugin_dir(root: &std::path::Path, id: &str) {
+        let dir = root.join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = serde_json::to_string(&test_manifest(id)).unwrap();
+        std::fs::write(dir.join("plugin.json"), json).unwrap();
+    }
+
+    fn write_plugin_zip(dir: &std::path::Path, id: &str, version: &str) -> std::path::PathBuf {
+        use zip::write::SimpleFileOptions;
+        let path = dir.join(format!("{}-{}.zip", id, version));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let opts = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let mut manifest = test_manifest(id);
+        manifest.version = version.to_string();
+        zip.start_file("plugin.json", opts).unwrap();
+        zip.write_all(serde_json::to_string(&manifest).unwrap().as_bytes()).unwrap();
+        zip.start_file("bin/plugin", opts).unwrap();
+        zip.write_all(version.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn should_keep_data_dir_across_reinstall() {
+        let root = TempDir::new("plugin-reinstall");
+        let v1 = write_plugin_zip(&root, "data-plugin", "1.0.0");
+        let v2 = write_plugin_zip(&root, "data-plugin", "1.1.0");
+
+        let dir = install_plugin_files(&root, "data-plugin", &v1).unwrap();
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        std::fs::write(dir.join("data").join("settings.json"), "{\"k\":1}").unwrap();
+
+        let dir = install_plugin_files(&root, "data-plugin", &v2).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("bin/plugin")).unwrap(), "1.1.0");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("data").join("settings.json")).unwrap(),
+            "{\"k\":1}"
+        );
+        let leftovers = std::fs::read_dir(&root)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with('.'))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn should_leave_installed_version_when_zip_is_corrupt() {
+        let root = TempDir::new("plugin-corrupt");
+        let v1 = write_plugin_zip(&root, "safe-plugin", "1.0.0");
+        let dir = install_plugin_files(&root, "safe-plugin", &v1).unwrap();
+        let bad = root.join("bad.zip");
+        std::fs::write(&bad, b"not a zip").unwrap();
+
+        assert!(install_plugin_files(&root, "safe-plugin", &bad).is_err());
+        assert_eq!(std::fs::read_to_string(dir.join("bin/plugin")).unwrap(), "1.0.0");
+    }
+
+    #[test]
+    fn should_truncate_long_stderr_lines_instead_of_dropping() {
+        let input = format!("short\n{}\ntail", "x".repeat(50));
+        let mut reader = std::io::BufReader::with_capacity(8, input.as_bytes());
+        let mut buf = Vec::new();
+
+        assert_eq!(read_capped_line(&mut reader, 10, &mut buf).unwrap(), Some(false));
+        assert_eq!(buf, b"short");
+        assert_eq!(read_capped_line(&mut reader, 10, &mut buf).unwrap(), Some(true));
+        assert_eq!(buf, b"xxxxxxxxxx");
+        assert_eq!(read_capped_line(&mut reader, 10, &mut buf).unwrap(), Some(false));
+        assert_eq!(buf, b"tail");
+        assert_eq!(read_capped_line(&mut reader, 10, &mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn should_route_responses_by_host_id_and_restore_caller_id() {
+        let pending: PendingMap = Arc::default();
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        pending
+            .lock()
+            .unwrap()
+            .waiters
+            .insert(42, (serde_json::json!("ui-7"), tx));
+
+        let granted = HashSet::new();
+        let note = route_plugin_message(
+            r#"{"jsonrpc":"2.0","method":"progress","params":{}}"#,
+            &pending,
+            &granted,
+        );
+        assert!(matches!(note, Routed::Forward(m) if m["method"] == "progress"));
+
+        let reply = route_plugin_message(r#"{"jsonrpc":"2.0","id":42,"result":1}"#, &pending, &granted);
+        assert!(matches!(reply, Routed::Handled));
+        let reply: serde_json::Value =
+            serde_json::from_str(&rx.try_recv().unwrap().unwrap()).unwrap();
+        assert_eq!(reply["id"], "ui-7");
+        assert_eq!(reply["result"], 1);
+    }
+
+    #[test]
+    fn should_block_calls_needing_undeclared_permissions() {
+        let pending: PendingMap = Arc::default();
+        let granted: HashSet<String> = ["editor:read".to_string()].into_iter().collect();
+
+        let routed = route_plugin_message(
+            r#"{"jsonrpc":"2.0","id":"p1","method":"ai.chat","params":{}}"#,
+            &pending,
+            &granted,
+        );
+        assert!(matches!(
+            routed,
+            Routed::Denied { id: Some(ref id), permission: "ai:chat", .. } if id == "p1"
+        ));
+        assert!(matches!(
+            route_plugin_message(r#"{"jsonrpc":"2.0","id":1,"method":"net.somethingNew"}"#, &pending, &granted),
+            Routed::Denied { permission: "unknown", .. }
+        ));
+        assert!(matches!(
+            route_plugin_message(r#"{"jsonrpc":"2.0","id":2,"method":"editor.getContent"}"#, &pending, &granted),
+            Routed::Forward(_)
+        ));
+
+        assert_eq!(check_host_method("run", &HashSet::new()), Err("editor:read"));
+        assert_eq!(check_host_method("run", &granted), Ok(()));
+    }
+
+    #[test]
+    fn should_answer_host_fs_calls_in_the_host() {
+        let pending: PendingMap = Arc::default();
+        let read_only: HashSet<String> = ["editor:read".to_string()].into_iter().collect();
+        assert!(matches!(
+            route_plugin_message(
+                r#"{"jsonrpc":"2.0","id":3,"method":"host/fs.read","params":{"path":"a.md"}}"#,
+                &pending,
+                &read_only,
+            ),
+            Routed::HostFs { ref method, ref params, .. }
+                if method == "host/fs.read" && params["path"] == "a.md"
+        ));
+        assert!(matches!(
+            route_plugin_message(
+                r#"{"jsonrpc":"2.0","id":4,"method":"host/fs.write","params":{}}"#,
+                &pending,
+                &read_only,
+            ),
+            Routed::Denied { permission: "editor:write", .. }
+        ));
+        assert!(matches!(
+            route_plugin_message(r#"{"jsonrpc":"2.0","id":5,"method":"host/exec"}"#, &pending, &read_only),
+            Routed::Denied { permission: "unknown", .. }
+        ));
+    }
+
+    #[test]
+    fn should_keep_host_fs_inside_the_scope() {
+        let dir = TempDir::new("plugin-fs");
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        let scope = HostFsScope {
+            workspace_roots: Arc::default(),
+            data_dir: dir.join("data").canonicalize().unwrap(),
+        };
+        std::fs::write(dir.join("secret.txt"), "x").unwrap();
+        assert!(matches!(
+            handle_host_fs(&scope, "host/fs.read", &serde_json::json!({ "path": "../secret.txt" })),
+            Err(HostFsError::Denied(_))
+        ));
+        assert_eq!(
+            handle_host_fs(&scope, "host/fs.read", &serde_json::json!({})),
+            Err(HostFsError::Failed("path is missing".to_string()))
+        );
+
+        // Writes are saves like the editor's: atomic, refused on read-only files
+        let note = dir.join("data/note.md");
+        std::fs::write(&note, "old").unwrap();
+        let write = serde_json::json!({ "path": "note.md", "content": "new" });
+        assert_eq!(
+            handle_host_fs(&scope, "host/fs.write", &write),
+            Ok(serde_json::json!({ "bytes": 3 }))
+        );
+        assert_eq!(std::fs::read_to_string(&note).unwrap(), "new");
+        let mut perms = std::fs::metadata(&note).unwrap().permissions();
+        perms.set_readonly(true);
+        std::fs::set_permissions(&note, perms).unwrap();
+        assert!(matches!(
+            handle_host_fs(&scope, "host/fs.write", &write),
+            Err(HostFsError::Failed(_))
+        ));
+    }
+
+    #[test]
+    fn should_confine_strict_plugins_in_the_sandbox_profile() {
+        let profile = sandbox_profile(
+            std::path::Path::new("/Users/a/Library/moraya/plugins/p"),
+            std::path::Path::new("/Users/a/Library/moraya/plugins/p/storage/data"),
+            Some(std::path::Path::new("/Users/a")),
+        );
+        assert!(profile.contains("(deny file-write*)"));
+        assert!(profile.contains(
+            "(allow file-write* (subpath \"/Users/a/Library/moraya/plugins/p/storage/data\")"
+        ));
+        assert!(profile.contains("(deny file-read-data (subpath \"/Users/a\"))"));
+        assert!(profile.contains("(subpath \"/Users/a/.nvm\")"));
+    }
+
+    #[test]
+    fn should_fail_pending_calls_when_closed() {
+        let pending: PendingMap = Arc::default();
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        pending.lock().unwrap().waiters.insert(1, (serde_json::json!(1), tx));
+        close_pending(&pending, plugin_exited());
+        assert_eq!(rx.try_recv().unwrap().unwrap_err().code, ErrorCode::PluginExited);
+        assert!(pending.lock().unwrap().closed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_let_plugin_exit_on_stdin_close_without_kill() {
+        let mut child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let mut proc = PluginProcess {
+            child,
+            stdin: Arc::new(Mutex::new(Some(stdin))),
+            pending: Arc::default(),
+            permissions: Arc::default(),
+            generation: 0,
+        };
+        let started = Instant::now();
+        shutdown_plugin(&mut proc);
+        assert!(started.elapsed() < PLUGIN_SHUTDOWN_GRACE);
+        assert!(proc.stdin.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn should_keep_dev_plugin_storage_out_of_installed_dirs() {
+        let root = std::path::Path::new("/tmp/plugins");
+        assert_eq!(plugin_storage_dir(root, "word-count"), root.join("word-count"));
+        assert_eq!(plugin_storage_dir(root, "dev:word-count"), root.join(".dev").join("word-count"));
+        assert!(is_dev_plugin("dev:word-count"));
+        assert!(!is_dev_plugin("word-count"));
+    }
+
+    #[test]
+    fn should_round_trip_plugin_data_without_symlinks() {
+        let dir = TempDir::new("plugin-data-roundtrip");
+        let data = dir.join("data");
+        std::fs::create_dir_all(data.join("cache")).unwrap();
+        std::fs::write(data.join("settings.json"), "{}").unwrap();
+        std::fs::write(data.join("cache").join("index.bin"), [1u8, 2, 3]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/passwd", data.join("leak")).unwrap();
+
+        let zip_path = dir.join("backup.zip");
+        let exported = export_data_dir(&data, &zip_path).unwrap();
+        assert_eq!(exported.files, 2);
+        assert_eq!(exported.bytes, 5);
+
+        let restored = dir.join("restored");
+        let imported = import_data_zip(&zip_path, &restored).unwrap();
+        assert_eq!(imported.files, 2);
+        assert_eq!(std::fs::read(restored.join("cache").join("index.bin")).unwrap(), [1, 2, 3]);
+        assert!(!restored.join("leak").exists());
+    }
+
+    #[test]
+    fn should_accept_legacy_plugins_and_reject_bad_initialize_replies() {
+        let ok = parse_initialize_reply(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":{"export":true},"methods":["wordCount"]}}"#,
+        );
+        assert!(ok.compatible);
+        assert_eq!(ok.methods, vec!["wordCount".to_string()]);
+        assert_eq!(ok.capabilities["export"], true);
+
+        // Plugins from before the handshake keep working, without capabilities
+        let legacy = parse_initialize_reply(
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method not found"}}"#,
+        );
+        assert!(legacy.compatible);
+        assert!(legacy.methods.is_empty());
+
+        let raw = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"Unsupported API"}}"#;
+        let failed = parse_initialize_reply(raw);
+        assert!(!failed.compatible);
+        assert_eq!(failed.error.as_deref(), Some("Unsupported API"));
+        assert_eq!(failed.raw_response.as_deref(), Some(raw));
+    }
+
+    #[test]
+    fn should_resolve_localized_names_with_fallback() {
+        let names = HashMap::from([
+            ("zh-CN".to_string(), "字数统计".to_string()),
+            ("ja".to_string(), "文字数カウント".to_string()),
+        ]);
+        assert_eq!(localized_text(&names, "Word Count", "zh-CN"), "字数统计");
+        assert_eq!(localized_text(&names, "Word Count", "zh-Hant"), "字数统计");
+        assert_eq!(localized_text(&names, "Word Count", "ja-JP"), "文字数カウント");
+        assert_eq!(localized_text(&names, "Word Count", "de"), "Word Count");
+
+        let mut entry = serde_json::json!({
+            "id": "word-count",
+            "name": "word-count",
+            "description": "Counts words",
+            "nameLocalized": { "zh-CN": "字数统计" },
+        });
+        localize_market_entry(&mut entry, "zh-CN");
+        assert_eq!(entry["displayName"], "字数统计");
+        assert_eq!(entry["displayDescription"], "Counts words");
+    }
+
+    #[test]
+    fn should_reject_symlink_entries_in_plugin_zip() {
+        use zip::write::SimpleFileOptions;
+        let dir = TempDir::new("plugin-zip-symlink");
+        let zip_path = dir.join("evil.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        zip.start_file("plugin.json", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"{}").unwrap();
+        zip.add_symlink("bin/helper", "/etc/passwd", SimpleFileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let err = extract_zip_safe(&zip_path, &dir.join("out")).unwrap_err();
+        assert_eq!(err.code, ErrorCode::PluginInvalidPackage);
+        assert!(err.message.contains("symlink"), "{}", err);
+    }
+
+    #[test]
+    fn should_reject_oversized_or_crowded_archives() {
+        use zip::write::SimpleFileOptions;
+        let dir = TempDir::new("plugin-zip-bomb");
+        let zip_path = dir.join("big.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        let opts = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for name in ["a.bin", "b.bin"] {
+            zip.start_file(name, opts).unwrap();
+            zip.write_all(&[0u8; 4096]).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let small = ZipLimits { max_bytes: 4096, max_files: 10, skip_symlinks: false };
+        let err = extract_zip_checked(&zip_path, &dir.join("out1"), &small).unwrap_err();
+        assert_eq!(err.code, ErrorCode::PluginInvalidPackage);
+        assert!(err.message.contains("size limit"), "{}", err);
+        let few = ZipLimits { max_bytes: 1 << 20, max_files: 1, skip_symlinks: false };
+        let err = extract_zip_checked(&zip_path, &dir.join("out2"), &few).unwrap_err();
+        assert!(err.message.contains("more than 1 entries"), "{}", err);
+        assert_eq!(err.details, Some(serde_json::json!({ "limit": 1 })));
+        let ok = ZipLimits { max_bytes: 1 << 20, max_files: 10, skip_symlinks: false };
+        assert_eq!(extract_zip_checked(&zip_path, &dir.join("out3"), &ok).unwrap().bytes, 8192);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_keep_unix_mode_bits_when_extracting() {
+        use std::os::unix::fs::PermissionsExt;
+        use zip::write::SimpleFileOptions;
+        let dir = TempDir::new("plugin-zip-mode");
+        let zip_path = dir.join("modes.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        zip.start_file("bin/helper", SimpleFileOptions::default().unix_permissions(0o755))
+            .unwrap();
+        zip.write_all(b"#!/bin/sh").unwrap();
+        zip.finish().unwrap();
+
+        let out = dir.join("out");
+        extract_zip_safe(&zip_path, &out).unwrap();
+        let mode = std::fs::metadata(out.join("bin/helper")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn should_only_rewrite_github_downloads_for_mirrors() {
+        let raw = "https://raw.githubusercontent.com/o/r/main/plugin.json";
+        let asset = "https://github.com/o/r/releases/download/v1/p-linux-x64.zip";
+        let api = "https://api.github.com/repos/o/r";
+        assert_eq!(mirror_url(raw, Some(0)), format!("{}{}", REGISTRY_MIRRORS[0], raw));
+        assert_eq!(mirror_url(asset, Some(0)), format!("{}{}", REGISTRY_MIRRORS[0], asset));
+        assert_eq!(mirror_url(api, Some(0)), api);
+        assert_eq!(mirror_url(raw, None), raw);
+
+        assert_eq!(source_order(api, Some(0)), vec![None]);
+        assert_eq!(source_order(raw, None), vec![None, Some(0), Some(1)]);
+        assert_eq!(source_order(raw, Some(1)), vec![Some(1), None, Some(0)]);
+    }
+
+    #[test]
+    fn should_parse_blacklist_reasons() {
+        let data = serde_json::json!({
+            "blacklist": [
+                { "id": "bad-plugin", "reason": "exfiltrates documents" },
+                { "id": "old-plugin" },
+                { "reason": "missing id" }
+            ]
+        });
+        let entries = parse_blacklist(&data);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            blacklisted_error(&entries[0].reason).message,
+            "plugin is blacklisted: exfiltrates documents"
+        );
+        assert_eq!(blacklisted_error(&entries[1].reason).message, "plugin is blacklisted");
+    }
+
+    #[test]
+    fn should_detect_github_rate_limit() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700000000".parse().unwrap());
+        assert_eq!(rate_limit_reset(403, &headers), Some(1_700_000_000_000));
+        assert_eq!(rate_limit_reset(404, &headers), None);
+
+        // A plain 403 (e.g. private repo) is not a rate limit
+        let mut plain = reqwest::header::HeaderMap::new();
+        plain.insert("x-ratelimit-remaining", "42".parse().unwrap());
+        assert_eq!(rate_limit_reset(403, &plain), None);
+    }
+
+    #[test]
+    fn should_resolve_icon_url_in_priority_order() {
+        let release = serde_json::json!({
+            "assets": [
+                { "name": "x-linux.zip", "browser_download_url": "https://github.com/o/r/releases/download/v1/x-linux.zip" },
+                { "name": "icon.png", "browser_download_url": "https://github.com/o/r/releases/download/v1/icon.png" }
+            ]
+        });
+        let with_icon = serde_json::json!({ "icon": "plugins/foo/icon.png" });
+        assert_eq!(
+            declared_icon_url(&with_icon, Some(&release)).unwrap(),
+            "https://raw.githubusercontent.com/moraya-apps/moraya-plugin-registry/main/plugins/foo/icon.png"
+        );
+        let bare = serde_json::json!({ "repo": "o/r" });
+        assert_eq!(
+            declared_icon_url(&bare, Some(&release)).unwrap(),
+            "https://github.com/o/r/releases/download/v1/icon.png"
+        );
+        assert_eq!(declared_icon_url(&bare, None), None);
+        assert_eq!(
+            repo_icon_url("owner/my-plugin", "v1.2.0"),
+            "https://raw.githubusercontent.com/owner/my-plugin/v1.2.0/icon.png"
+        );
+    }
+
+    #[test]
+    fn should_distinguish_missing_and_invalid_signatures() {
+        let key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        assert_eq!(verify_signature(b"data", None, key), Err(SignatureError::Missing));
+        assert_eq!(verify_signature(b"data", Some("  \n"), key), Err(SignatureError::Missing));
+        assert_eq!(
+            verify_signature(b"data", Some("untrusted comment: x\nnot-base64"), key),
+            Err(SignatureError::Invalid)
+        );
+    }
+
+    #[test]
+    fn should_map_plugin_failures_to_error_codes() {
+        assert_eq!(check_plugin_id("../x").unwrap_err().code, ErrorCode::PluginInvalidId);
+        assert!(check_plugin_id("dev:my-plugin").is_ok());
+
+        let err = blacklisted_error("malware");
+        assert_eq!(err.code, ErrorCode::PluginBlacklisted);
+        assert_eq!(err.details, Some(serde_json::json!({ "reason": "malware" })));
+
+        let mut other_os = test_manifest("other-os");
+        other_os.entry = HashMap::from([("plan9".to_string(), "bin/plugin".to_string())]);
+        assert_eq!(
+            resolve_entry(&other_os).unwrap_err().code,
+            ErrorCode::PluginUnsupportedPlatform
+        );
+
+        assert_eq!(
+            signature_error(SignatureError::Missing).code,
+            ErrorCode::PluginSignatureMissing
+        );
+        assert_eq!(
+            signature_error(SignatureError::Invalid).code,
+            ErrorCode::PluginSignatureInvalid
+        );
+    }
+
+    #[test]
+    fn should_report_missing_or_malformed_packages() {
+        use zip::write::SimpleFileOptions;
+        let dir = TempDir::new("plugin-zip-codes");
+        let missing = read_manifest_from_zip(&dir.join("missing.zip")).unwrap_err();
+        assert_eq!(missing.code, ErrorCode::FileNotFound);
+
+        let not_zip = dir.join("not.zip");
+        std::fs::write(&not_zip, b"plain text").unwrap();
+        assert_eq!(
+            read_manifest_from_zip(&not_zip).unwrap_err().code,
+            ErrorCode::PluginInvalidPackage
+        );
+
+        let no_manifest = dir.join("empty.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&no_manifest).unwrap());
+        zip.start_file("readme.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"hi").unwrap();
+        zip.start_file("nested/plugin.json", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"{ not json").unwrap();
+        zip.finish().unwrap();
+        assert_eq!(
+            read_manifest_from_zip(&no_manifest).unwrap_err().code,
+            ErrorCode::PluginInvalidManifest
+        );
+    }
+
+    #[test]
+    fn should_resolve_binary_and_script_entries() {
+        let binary = test_manifest("bin-plugin");
+        assert_eq!(resolve_entry(&binary), Ok(PluginEntry::Binary("bin/plugin")));
+
+        let mut script = test_manifest("node-plugin");
+        script.entry = HashMap::from([
+            ("runtime".to_string(), "node".to_string()),
+            ("script".to_string(), "index.js".to_string()),
+        ]);
+        assert_eq!(
+            resolve_entry(&script),
+            Ok(PluginEntry::Script { runtime: "node", script: "index.js" })
+        );
+        let (errors, _) = validate_manifest(&script);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        script.entry.insert("script".to_string(), "../escape.js".to_string());
+        assert!(!validate_manifest(&script).0.is_empty());
+        script.entry.insert("runtime".to_string(), "bash".to_string());
+        assert!(validate_manifest(&script).0.iter().any(|e| e.contains("bash")));
+    }
+
+    #[test]
+    fn should_bound_log_buffer() {
+        let buffer: LogBuffer = Arc::default();
+        for i in 0..25 {
+            push_bounded(&buffer, i.to_string(), 10);
+        }
+        let buf = buffer.lock().unwrap();
+        assert_eq!(buf.len(), 10);
+        assert_eq!(buf.front().map(String::as_str), Some("15"));
+    }
+
+    #[test]
+    fn should_reconcile_state_with_plugin_directories() {
+        let root = TempDir::new("plugin-reconcile");
+        write_plugin_dir(&root, "kept-plugin");
+        write_plugin_dir(&root, "orphan-plugin");
+        let stale = PluginStateEntry {
+            id: "removed-plugin".to_string(),
+            enabled: true,
+            plugin_dir: root.join("removed-plugin").to_string_lossy().into_owned(),
+            installed_at: 1,
+            manifest: test_manifest("removed-plugin"),
+        };
+        let kept = PluginStateEntry {
+            id: "kept-plugin".to_string(),
+            enabled: true,
+            plugin_dir: "/old/location".to_string(),
+            installed_at: 1,
+            manifest: test_manifest("kept-plugin"),
+        };
+        write_state_file(&root, &[stale, kept]).unwrap();
+
+        let mut entries = reconcile_state(&root, read_state_file(&root));
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, "kept-plugin");
+        assert!(entries[0].enabled);
+        assert_eq!(entries[0].plugin_dir, root.join("kept-plugin").to_string_lossy());
+        assert_eq!(entries[1].id, "orphan-plugin");
+        assert!(!entries[1].enabled);
+    }
 }
@@ -0,0 +1,450 @@
+//! Hard-wrap Markdown at a column for plain-text exports (mailing lists,
+//! commit-message style notes).
+//!
+//! Line breaks follow UAX #14, so CJK text breaks between ideographs and
+//! Latin text between words, and widths count East Asian wide characters as
+//! two columns. URLs, inline code and link destinations are never split.
+//! Frontmatter, fenced code and math, indented code, tables, headings and
+//! HTML lines are copied untouched; trailing-space and backslash hard breaks
+//! are kept where they were.
+
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RewrapOptions {
+    /// Join the lines of already wrapped paragraphs before wrapping, so
+    /// an edited paragraph comes out evenly filled.
+    pub reflow: bool,
+}
+
+/// Wrap `content` at `width` columns; 0 leaves it unchanged.
+#[tauri::command]
+pub fn rewrap_text(content: String, width: usize, options: Option<RewrapOptions>) -> String {
+    rewrap(&content, width, &options.unwrap_or_default())
+}
+
+/// A wrappable line split into its block prefix and text.
+struct Prose<'a> {
+    /// Indentation, `>` markers and list marker, as written.
+    first: String,
+    /// Prefix for continuation lines: the list marker turned into spaces.
+    cont: String,
+    /// Blockquote depth, so reflow only joins lines of the same quote.
+    depth: usize,
+    has_marker: bool,
+    text: &'a str,
+    /// `"  "` or `"\\"` when the line ends in a Markdown hard break.
+    hard_break: &'static str,
+}
+
+fn rewrap(content: &str, width: usize, options: &RewrapOptions) -> String {
+    if width == 0 {
+        return content.to_string();
+    }
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut i = 0;
+
+    // Frontmatter
+    if lines.first().map(|l| l.trim_end()) == Some("---") {
+        if let Some(end) = lines[1..]
+            .iter()
+            .position(|l| matches!(l.trim_end(), "---" | "..."))
+        {
+            out.extend(lines[..end + 2].iter().map(|l| l.to_string()));
+            i = end + 2;
+        }
+    }
+
+    let mut fence: Option<(char, usize)> = None;
+    let mut in_table = false;
+    let mut prev_blank = true;
+    while i < lines.len() {
+        let line = lines[i];
+        let body = strip_quotes(line);
+
+        if let Some((ch, len)) = fence {
+            if closes_fence(body, ch, len) {
+                fence = None;
+            }
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+        if let Some(open) = opens_fence(body) {
+            fence = Some(open);
+            out.push(line.to_string());
+            prev_blank = false;
+            i += 1;
+            continue;
+        }
+
+        let next_body = lines.get(i + 1).map(|l| strip_quotes(l));
+        in_table = (is_table_row(body) && (in_table || body.trim_start().starts_with('|')))
+            || is_delimiter_row(body)
+            || (body.contains('|') && next_body.is_some_and(is_delimiter_row));
+        let blank = body.trim().is_empty();
+        let indented_code = prev_blank && leading_columns(line) >= 4;
+        if blank || in_table || indented_code || is_block_line(body) {
+            out.push(line.to_string());
+            prev_blank = blank || indented_code;
+            i += 1;
+            continue;
+        }
+        prev_blank = false;
+
+        let first = parse_prose(line);
+        let mut group = vec![first];
+        i += 1;
+        if options.reflow {
+            while i < lines.len() {
+                let next = lines[i];
+                let next_body = strip_quotes(next);
+                let prev = group.last().expect("group starts non-empty");
+                if !prev.hard_break.is_empty()
+                    || next_body.trim().is_empty()
+                    || opens_fence(next_body).is_some()
+                    || is_block_line(next_body)
+                    || (is_table_row(next_body)
+                        && lines
+                            .get(i + 1)
+                            .is_some_and(|l| is_delimiter_row(strip_quotes(l))))
+                {
+                    break;
+                }
+                let prose = parse_prose(next);
+                if prose.has_marker || prose.depth != group[0].depth {
+                    break;
+                }
+                group.push(prose);
+                i += 1;
+            }
+        }
+        emit_group(&group, width, &mut out);
+    }
+    out.join("\n")
+}
+
+/// Wrap one paragraph (or one line without reflow) and push its lines.
+fn emit_group(group: &[Prose<'_>], width: usize, out: &mut Vec<String>) {
+    let head = &group[0];
+    let mut text = String::new();
+    for (n, prose) in group.iter().enumerate() {
+        let part = if n == 0 {
+            prose.text
+        } else {
+            prose.text.trim_start()
+        };
+        if n > 0 && !text.is_empty() && !part.is_empty() {
+            let joins_cjk = text.chars().next_back().is_some_and(is_wide)
+                && part.chars().next().is_some_and(is_wide);
+            if !joins_cjk {
+                text.push(' ');
+            }
+        }
+        text.push_str(part);
+    }
+
+    let prefix_width = display_width(&head.cont);
+    let avail = width.saturating_sub(prefix_width).max(1);
+    let wrapped = wrap_line(&text, avail);
+    let last = wrapped.len() - 1;
+    for (n, line) in wrapped.into_iter().enumerate() {
+        let prefix = if n == 0 { &head.first } else { &head.cont };
+        let mut s = format!("{}{}", prefix, line);
+        if n == last {
+            s.push_str(group.last().expect("non-empty group").hard_break);
+        }
+        out.push(s);
+    }
+}
+
+/// Greedy fill of `text` into lines of at most `width` columns. Pieces
+/// wider than `width` (long URLs, unbroken CJK-free words) get their own
+/// line rather than being cut.
+fn wrap_line(text: &str, width: usize) -> Vec<String> {
+    let protected = protected_ranges(text);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    let mut prev = 0;
+    for (pos, _) in unicode_linebreak::linebreaks(text) {
+        if pos < text.len() && protected.iter().any(|r| r.start < pos && pos < r.end) {
+            continue;
+        }
+        let piece = &text[prev..pos];
+        prev = pos;
+        let content = piece.trim_end();
+        let content_width = display_width(content);
+        if !current.is_empty() && current_width + content_width > width && !starts_block(content) {
+            lines.push(current.trim_end().to_string());
+            current.clear();
+            current_width = 0;
+        }
+        current.push_str(piece);
+        current_width = display_width(current.trim_end()) + (piece.len() - content.len());
+    }
+    lines.push(current.trim_end().to_string());
+    lines
+}
+
+/// Byte ranges that must stay on one line: inline code, URLs and link
+/// destinations.
+fn protected_ranges(text: &str) -> Vec<Range<usize>> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"`[^`]*`|\]\([^)]*\)|<[a-zA-Z][a-zA-Z0-9+.-]*:[^>\s]*>|\b[a-zA-Z][a-zA-Z0-9+.-]*://\S+|\bwww\.\S+")
+            .expect("valid regex")
+    });
+    re.find_iter(text).map(|m| m.range()).collect()
+}
+
+/// Whether a continuation line starting with `s` would turn into a list
+/// item, heading, quote or rule instead of paragraph text.
+pub(crate) fn starts_block(s: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:[-+*](?:\s|$)|\d{1,9}[.)](?:\s|$)|#{1,6}(?:\s|$)|>|=+\s*$|-+\s*$|```|~~~)")
+            .expect("valid regex")
+    });
+    re.is_match(s)
+}
+
+fn parse_prose(line: &str) -> Prose<'_> {
+    static MARKER: OnceLock<Regex> = OnceLock::new();
+    let marker = MARKER.get_or_init(|| {
+        Regex::new(r"^(?:[-+*]|\d{1,9}[.)])( {1,4}|\t)(?:\[[ xX]\] )?").expect("valid regex")
+    });
+
+    let mut first = String::new();
+    let mut depth = 0;
+    let mut rest = line;
+    loop {
+        let trimmed = rest.trim_start_matches([' ', '\t']);
+        let indent = &rest[..rest.len() - trimmed.len()];
+        if let Some(after) = trimmed.strip_prefix('>') {
+            first.push_str(indent);
+            first.push('>');
+            depth += 1;
+            let after_space = after.strip_prefix(' ').unwrap_or(after);
+            first.push_str(&after[..after.len() - after_space.len()]);
+            rest = after_space;
+            continue;
+        }
+        first.push_str(indent);
+        rest = trimmed;
+        break;
+    }
+    let mut cont = first.clone();
+    let has_marker = match marker.find(rest) {
+        Some(m) => {
+            first.push_str(m.as_str());
+            cont.push_str(&" ".repeat(display_width(m.as_str())));
+            rest = &rest[m.end()..];
+            true
+        }
+        None => false,
+    };
+
+    let (text, hard_break) = split_hard_break(rest);
+    Prose {
+        first,
+        cont,
+        depth,
+        has_marker,
+        text,
+        hard_break,
+    }
+}
+
+fn split_hard_break(text: &str) -> (&str, &'static str) {
+    let trimmed = text.trim_end_matches([' ', '\t', '\r']);
+    if text[trimmed.len()..].starts_with("  ") {
+        return (trimmed, "  ");
+    }
+    let slashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+    if slashes % 2 == 1 {
+        return (&trimmed[..trimmed.len() - 1], "\\");
+    }
+    (trimmed, "")
+}
+
+/// The line without leading blockquote markers and indentation.
+fn strip_quotes(line: &str) -> &str {
+    let mut rest = line.trim_start();
+    while let Some(after) = rest.strip_prefix('>') {
+        rest = after.trim_start();
+    }
+    rest
+}
+
+pub(crate) fn opens_fence(body: &str) -> Option<(char, usize)> {
+    let t = body.trim_start();
+    if t.starts_with("$$") {
+        return (t.trim_end() == "$$" || !t[2..].contains("$$")).then_some(('$', 2));
+    }
+    let ch = t.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = t.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then_some((ch, len))
+}
+
+pub(crate) fn closes_fence(body: &str, ch: char, len: usize) -> bool {
+    let t = body.trim();
+    if ch == '$' {
+        return t.ends_with("$$");
+    }
+    t.chars().take_while(|c| *c == ch).count() >= len && t.chars().all(|c| c == ch)
+}
+
+/// Lines that are never rewrapped: headings, rules, setext underlines,
+/// HTML, link reference definitions and footnote definitions.
+fn is_block_line(body: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:#{1,6}(?:\s|$)|(?:[-*_]\s*){3,}$|=+\s*$|<[a-zA-Z/!?]|\[[^\]]+\]:)")
+            .expect("valid regex")
+    });
+    re.is_match(body.trim_start())
+}
+
+fn is_table_row(body: &str) -> bool {
+    body.contains('|')
+}
+
+pub(crate) fn is_delimiter_row(body: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^\|?\s*:?-+:?\s*(?:\|\s*:?-+:?\s*)*\|?\s*$").expect("valid regex")
+    });
+    body.contains('|') && re.is_match(body.trim())
+}
+
+pub(crate) fn leading_columns(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// East Asian wide and fullwidth characters, which take two columns.
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}' |   // Hangul Jamo
+        '\u{2E80}'..='\u{303E}' |   // CJK radicals, symbols and punctuation
+        '\u{3041}'..='\u{33FF}' |   // Kana, Bopomofo, CJK compatibility
+        '\u{3400}'..='\u{4DBF}' |   // CJK Extension A
+        '\u{4E00}'..='\u{9FFF}' |   // CJK Unified Ideographs
+        '\u{A000}'..='\u{A4CF}' |   // Yi
+        '\u{AC00}'..='\u{D7A3}' |   // Hangul Syllables
+        '\u{F900}'..='\u{FAFF}' |   // CJK Compatibility Ideographs
+        '\u{FE30}'..='\u{FE4F}' |   // CJK Compatibility Forms
+        '\u{FF00}'..='\u{FF60}' |   // Fullwidth forms
+        '\u{FFE0}'..='\u{FFE6}' |
+        '\u{1F300}'..='\u{1F64F}' | // Emoji
+        '\u{1F900}'..='\u{1F9FF}' |
+        '\u{20000}'..='\u{3FFFD}'   // CJK Extensions B and later
+    )
+}
+
+pub(crate) fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| match c {
+            '\u{0300}'..='\u{036F}' | '\u{200B}'..='\u{200F}' | '\u{FE00}'..='\u{FE0F}' => 0,
+            c if is_wide(c) => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(s: &str, width: usize, reflow: bool) -> String {
+        rewrap(s, width, &RewrapOptions { reflow })
+    }
+
+    #[test]
+    fn should_wrap_mixed_cjk_and_latin_by_columns() {
+        let out = wrap(
+            "Moraya 是一个简洁的 Markdown 编辑器，支持中文和 English 混排。",
+            20,
+            false,
+        );
+        for line in out.lines() {
+            assert!(display_width(line) <= 20, "{line:?} is too wide");
+        }
+        // Nothing lost or added apart from line breaks
+        let squash = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+        assert_eq!(
+            squash(&out),
+            squash("Moraya 是一个简洁的 Markdown 编辑器，支持中文和 English 混排。")
+        );
+        // CJK lines break between ideographs, never before closing punctuation
+        assert!(!out
+            .lines()
+            .any(|l| l.starts_with('，') || l.starts_with('。')));
+    }
+
+    #[test]
+    fn should_keep_urls_and_code_whole() {
+        let url = "https://example.com/a/very/long/path/that/goes/on/and/on?q=1";
+        let out = wrap(
+            &format!("See {url} and `some inline code` for details"),
+            20,
+            false,
+        );
+        assert!(out.lines().any(|l| l == url));
+        assert!(out.contains("`some inline code`"));
+        assert_eq!(
+            wrap(
+                "Read [the docs](https://example.com/docs/getting/started) now",
+                24,
+                false
+            ),
+            "Read [the\ndocs](https://example.com/docs/getting/started)\nnow"
+        );
+    }
+
+    #[test]
+    fn should_leave_code_tables_and_frontmatter_alone() {
+        let doc = "---\ntitle: A very long title that would otherwise be wrapped\n---\n\n\
+                   ```\nlet x = \"a line of code that is much longer than the width\";\n```\n\n\
+                   | a long header | another long header |\n| --- | --- |\n| cell | cell |\n\n\
+                   # A heading that is longer than the width";
+        assert_eq!(wrap(doc, 20, true), doc);
+    }
+
+    #[test]
+    fn should_keep_list_and_quote_prefixes() {
+        assert_eq!(
+            wrap("- one two three four five six", 12, false),
+            "- one two\n  three four\n  five six"
+        );
+        assert_eq!(
+            wrap("> one two three four", 10, false),
+            "> one two\n> three\n> four"
+        );
+        // A break never lands where the next line would start a list
+        assert_eq!(wrap("alpha beta - gamma", 10, false), "alpha beta -\ngamma");
+    }
+
+    #[test]
+    fn should_reflow_paragraphs_and_keep_hard_breaks() {
+        let doc = "one\ntwo three\nfour  \nfive six\n\nseven";
+        assert_eq!(
+            wrap(doc, 80, true),
+            "one two three four  \nfive six\n\nseven"
+        );
+        assert_eq!(wrap(doc, 80, false), doc);
+        assert_eq!(wrap("中文\n段落", 80, true), "中文段落");
+        assert_eq!(wrap("line\\\nnext", 80, true), "line\\\nnext");
+        // Without reflow, existing short lines stay as they are
+        assert_eq!(wrap("a\nb", 80, false), "a\nb");
+    }
+}
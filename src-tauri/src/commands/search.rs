@@ -0,0 +1,419 @@
+//! Workspace-wide find and replace over the markdown files under a folder.
+//!
+//! The intended flow is a dry run first (per-file match counts, line
+//! previews and mtimes), then an apply call that passes those mtimes back so
+//! files edited in the meantime are skipped instead of clobbered.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{validate_path, write_atomic};
+
+/// Same cap as the file tree.
+const MAX_DEPTH: u32 = 10;
+/// Larger files are not notes; leave them alone.
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+pub(crate) const MAX_PREVIEWS_PER_FILE: usize = 20;
+const MAX_PREVIEW_CHARS: usize = 200;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplaceOptions {
+    /// Treat `query` as a regular expression; `$1` / `${name}` in the
+    /// replacement refer to its capture groups.
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Report what would change without writing anything.
+    pub dry_run: bool,
+    /// `modified` values from the dry run, keyed by path. When set, only
+    /// these files are written, and only if their mtime is unchanged.
+    pub expected_mtimes: Option<HashMap<String, f64>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchPreview {
+    /// 1-based line of the match start.
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: usize,
+    /// Seconds since the UNIX epoch, as in `get_files_mtime`.
+    pub modified: f64,
+    pub previews: Vec<MatchPreview>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason {
+    /// Modified on disk since the dry run.
+    ChangedOnDisk,
+    /// Matches now but was not part of the dry run.
+    NotPreviewed,
+    WriteFailed,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceSummary {
+    pub dry_run: bool,
+    pub files: Vec<FileMatches>,
+    pub total_matches: usize,
+    /// Files actually rewritten (always empty for a dry run).
+    pub changed_files: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+fn build_regex(query: &str, options: &ReplaceOptions) -> Result<Regex, CommandError> {
+    if query.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "Search text is empty",
+        ));
+    }
+    let mut pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|e| {
+            CommandError::new(
+                ErrorCode::InvalidArgument,
+                format!("Invalid regular expression: {}", e),
+            )
+        })
+}
+
+pub(crate) fn is_markdown(name: &str) -> bool {
+    name.ends_with(".md") || name.ends_with(".markdown")
+}
+
+/// Markdown files under `dir`, skipping the same entries as the file tree
+/// (hidden files, `node_modules`, `target`, symlinks).
+pub(crate) fn collect_files(dir: &Path, depth: u32, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == "node_modules" || name == "target" {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            if depth < MAX_DEPTH {
+                collect_files(&entry.path(), depth + 1, out);
+            }
+        } else if is_markdown(&name) {
+            out.push(entry.path());
+        }
+    }
+}
+
+pub(crate) fn mtime_secs(path: &Path) -> Option<f64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(
+        modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64(),
+    )
+}
+
+pub(crate) fn clip(line: &str) -> String {
+    if line.chars().count() > MAX_PREVIEW_CHARS {
+        let clipped: String = line.chars().take(MAX_PREVIEW_CHARS).collect();
+        format!("{}…", clipped)
+    } else {
+        line.to_string()
+    }
+}
+
+struct Replaced {
+    text: String,
+    matches: usize,
+    previews: Vec<MatchPreview>,
+}
+
+/// Replace every non-empty match. Literal mode inserts `replacement`
+/// verbatim; regex mode expands capture-group references in it.
+fn replace_all(text: &str, re: &Regex, replacement: &str, expand: bool) -> Replaced {
+    let mut out = String::with_capacity(text.len());
+    let mut matches = 0;
+    let mut previews = Vec::new();
+    let mut last = 0;
+    for caps in re.captures_iter(text) {
+        let m = caps.get(0).expect("group 0 always matches");
+        if m.is_empty() {
+            continue;
+        }
+        let mut with = String::new();
+        if expand {
+            caps.expand(replacement, &mut with);
+        } else {
+            with.push_str(replacement);
+        }
+
+        if previews.len() < MAX_PREVIEWS_PER_FILE {
+            let line_start = text[..m.start()].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[m.end()..]
+                .find('\n')
+                .map_or(text.len(), |i| m.end() + i);
+            previews.push(MatchPreview {
+                line: text[..m.start()].matches('\n').count() + 1,
+                before: clip(&text[line_start..line_end]),
+                after: clip(&format!(
+                    "{}{}{}",
+                    &text[line_start..m.start()],
+                    with,
+                    &text[m.end()..line_end]
+                )),
+            });
+        }
+
+        out.push_str(&text[last..m.start()]);
+        out.push_str(&with);
+        last = m.end();
+        matches += 1;
+    }
+    out.push_str(&text[last..]);
+    Replaced {
+        text: out,
+        matches,
+        previews,
+    }
+}
+
+/// `root` must already be validated.
+pub(crate) fn replace_in_dir(
+    root: &Path,
+    query: &str,
+    replacement: &str,
+    options: &ReplaceOptions,
+) -> Result<ReplaceSummary, CommandError> {
+    let re = build_regex(query, options)?;
+    let mut paths = Vec::new();
+    collect_files(root, 0, &mut paths);
+    paths.sort();
+
+    let mut summary = ReplaceSummary {
+        dry_run: options.dry_run,
+        files: Vec::new(),
+        total_matches: 0,
+        changed_files: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for path in paths {
+        let path_str = path.to_string_lossy().to_string();
+        if fs::metadata(&path).map_or(true, |m| m.len() > MAX_FILE_BYTES) {
+            continue;
+        }
+        let Some(modified) = mtime_secs(&path) else {
+            continue;
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let replaced = replace_all(&text, &re, replacement, options.regex);
+        if replaced.matches == 0 {
+            continue;
+        }
+
+        if !options.dry_run {
+            let skip = match options.expected_mtimes.as_ref().map(|m| m.get(&path_str)) {
+                Some(None) => Some(SkipReason::NotPreviewed),
+                Some(Some(expected)) if (expected - modified).abs() > 1e-3 => {
+                    Some(SkipReason::ChangedOnDisk)
+                }
+                _ => None,
+            };
+            let skip = skip.or_else(|| {
+                write_atomic(&path, replaced.text.as_bytes())
+                    .err()
+                    .map(|_| SkipReason::WriteFailed)
+            });
+            if let Some(reason) = skip {
+                summary.skipped.push(SkippedFile {
+                    path: path_str,
+                    reason,
+                });
+                continue;
+            }
+            summary.changed_files.push(path_str.clone());
+        }
+
+        summary.total_matches += replaced.matches;
+        summary.files.push(FileMatches {
+            path: path_str,
+            matches: replaced.matches,
+            modified,
+            previews: replaced.previews,
+        });
+    }
+    Ok(summary)
+}
+
+/// Find and replace `query` in every markdown file under `root`.
+#[tauri::command]
+pub async fn replace_in_files(
+    root: String,
+    query: String,
+    replacement: String,
+    options: Option<ReplaceOptions>,
+) -> Result<ReplaceSummary, CommandError> {
+    let root = validate_path(&root)?;
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotADirectory,
+            "Not a directory",
+        ));
+    }
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || replace_in_dir(&root, &query, &replacement, &options))
+        .await
+        .map_err(|e| CommandError::from(format!("Replace task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    fn workspace(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("replace-{}", name));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(dir.join("a.md"), "Project Foo\nfoo bar\nfood\n").unwrap();
+        fs::write(dir.join("sub/b.markdown"), "Ask foo about Foo.\n").unwrap();
+        fs::write(dir.join("sub/c.txt"), "foo").unwrap();
+        fs::write(dir.join(".hidden/d.md"), "foo").unwrap();
+        dir
+    }
+
+    #[test]
+    fn should_preview_without_writing_on_dry_run() {
+        let dir = workspace("dry");
+        let options = ReplaceOptions {
+            whole_word: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        let summary = replace_in_dir(&dir, "foo", "Bar", &options).unwrap();
+        assert_eq!(summary.total_matches, 4);
+        assert_eq!(summary.files.len(), 2);
+        assert!(summary.changed_files.is_empty());
+        let a = &summary.files[0];
+        assert!(a.path.ends_with("a.md"));
+        assert_eq!(a.previews[1].line, 2);
+        assert_eq!(a.previews[1].before, "foo bar");
+        assert_eq!(a.previews[1].after, "Bar bar");
+        assert_eq!(
+            fs::read_to_string(dir.join("a.md")).unwrap(),
+            "Project Foo\nfoo bar\nfood\n"
+        );
+    }
+
+    #[test]
+    fn should_substitute_capture_groups_in_regex_mode() {
+        let dir = workspace("regex");
+        let options = ReplaceOptions {
+            regex: true,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let summary = replace_in_dir(&dir, r"(\w+) (Foo)", "$2-$1", &options).unwrap();
+        assert_eq!(summary.changed_files.len(), 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("a.md")).unwrap(),
+            "Foo-Project\nfoo bar\nfood\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("sub/b.markdown")).unwrap(),
+            "Ask foo Foo-about.\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("sub/c.txt")).unwrap(), "foo");
+
+        // Literal mode inserts `$` verbatim
+        replace_in_dir(&dir, "food", "$1", &ReplaceOptions::default()).unwrap();
+        assert!(fs::read_to_string(dir.join("a.md"))
+            .unwrap()
+            .ends_with("$1\n"));
+    }
+
+    #[test]
+    fn should_skip_files_changed_since_the_dry_run() {
+        let dir = workspace("mtime");
+        let dry = ReplaceOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let preview = replace_in_dir(&dir, "foo", "baz", &dry).unwrap();
+        let mut expected: HashMap<String, f64> = preview
+            .files
+            .iter()
+            .map(|f| (f.path.clone(), f.modified))
+            .collect();
+        let b = dir.join("sub/b.markdown").to_string_lossy().to_string();
+        expected.insert(b.clone(), expected[&b] - 60.0);
+
+        let apply = ReplaceOptions {
+            expected_mtimes: Some(expected),
+            ..Default::default()
+        };
+        let summary = replace_in_dir(&dir, "foo", "baz", &apply).unwrap();
+        assert_eq!(summary.changed_files.len(), 1);
+        assert_eq!(summary.skipped.len(), 1);
+        assert_eq!(summary.skipped[0].path, b);
+        assert_eq!(summary.skipped[0].reason, SkipReason::ChangedOnDisk);
+        assert_eq!(
+            fs::read_to_string(dir.join("sub/b.markdown")).unwrap(),
+            "Ask foo about Foo.\n"
+        );
+    }
+
+    #[test]
+    fn should_reject_empty_query_and_bad_regex() {
+        let dir = std::env::temp_dir();
+        let options = ReplaceOptions {
+            regex: true,
+            ..Default::default()
+        };
+        for query in ["", "(unclosed"] {
+            assert_eq!(
+                replace_in_dir(&dir, query, "x", &options).unwrap_err().code,
+                ErrorCode::InvalidArgument
+            );
+        }
+    }
+}
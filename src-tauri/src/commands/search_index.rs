@@ -0,0 +1,785 @@
+//! Persistent full-text index over the markdown files under a folder, so
+//! searching a large vault doesn't re-read every file.
+//!
+//! It is a trigram index: every three-character window of a file's
+//! case-folded text points at the file. A query keeps the files holding all
+//! trigrams of its terms and then confirms each term by substring search;
+//! terms shorter than three characters fall back to scanning the texts.
+//!
+//! File texts are cached per folder in `app_data_dir()/search-index/` and the
+//! trigram table is rebuilt from them when loaded, so a cache that doesn't
+//! parse or comes from another version is simply rebuilt from the files.
+//! `write_file`, `rename_file`, `delete_file` and the workspace watcher keep
+//! the loaded index current between builds.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::Manager;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::validate_path;
+use super::search::{collect_files, is_markdown, mtime_secs};
+use super::workspace_watch::{ChangeKind, WorkspaceChange};
+
+/// Bump when the cache layout or text folding changes.
+const CACHE_VERSION: u32 = 1;
+/// Larger files are not notes; leave them out.
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Incremental changes reach the cache at most this often. A stale cache
+/// only means re-reading the files changed since on the next build.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Characters shown around the first match in a snippet.
+const SNIPPET_BEFORE: usize = 40;
+const SNIPPET_AFTER: usize = 100;
+/// Occurrences past this don't make a file rank higher.
+const MAX_COUNTED: usize = 20;
+const DEFAULT_LIMIT: usize = 50;
+
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Index of the folder last passed to `search_index_build`.
+static INDEX: Mutex<Option<SearchIndex>> = Mutex::new(None);
+static BUILDING: AtomicBool = AtomicBool::new(false);
+/// Progress of the running build: files looked at, of `TOTAL`.
+static SCANNED: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Deserialize)]
+struct StoredFile {
+    modified: f64,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Stored {
+    version: u32,
+    root: PathBuf,
+    updated_at: i64,
+    files: BTreeMap<String, StoredFile>,
+}
+
+/// What `Stored` is read back from, borrowing the index's texts.
+#[derive(Serialize)]
+struct StoredRef<'a> {
+    version: u32,
+    root: &'a Path,
+    updated_at: i64,
+    files: BTreeMap<&'a str, StoredFileRef<'a>>,
+}
+
+#[derive(Serialize)]
+struct StoredFileRef<'a> {
+    modified: f64,
+    text: &'a str,
+}
+
+struct Doc {
+    path: String,
+    modified: f64,
+    text: String,
+    /// `text` lowercased one char for one char, so char positions line up.
+    folded: String,
+}
+
+struct SearchIndex {
+    root: PathBuf,
+    /// Milliseconds since the UNIX epoch of the last change.
+    updated_at: i64,
+    /// Removed and replaced documents leave `None` behind; postings keep
+    /// pointing at them until the next compaction.
+    docs: Vec<Option<Doc>>,
+    by_path: HashMap<String, u32>,
+    /// Ascending document ids per trigram.
+    postings: HashMap<u64, Vec<u32>>,
+    live: usize,
+    last_saved: Option<Instant>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub path: String,
+    pub score: f32,
+    /// 1-based line of the snippet.
+    pub line: usize,
+    pub snippet: String,
+    /// `[start, end)` of each match in `snippet`, in UTF-16 code units as
+    /// JavaScript strings count them.
+    pub highlights: Vec<[usize; 2]>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndexStatus {
+    /// Folder of the loaded index; None before the first build.
+    pub root: Option<String>,
+    pub building: bool,
+    /// Files looked at so far by the running build, of `total`.
+    pub scanned: usize,
+    pub total: usize,
+    pub files: usize,
+    /// Size of the indexed text.
+    pub bytes: u64,
+    /// Size of the cache on disk.
+    pub cache_bytes: Option<u64>,
+    /// Milliseconds since the UNIX epoch.
+    pub updated_at: Option<i64>,
+}
+
+/// Resolve the cache directory. Called from the setup hook.
+pub fn init(app: &tauri::AppHandle) {
+    if let Ok(dir) = app.path().app_data_dir() {
+        let _ = CACHE_DIR.set(dir.join("search-index"));
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn fold(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+fn trigram(a: char, b: char, c: char) -> u64 {
+    (a as u64) << 42 | (b as u64) << 21 | c as u64
+}
+
+fn trigrams(folded: &str) -> HashSet<u64> {
+    let chars: Vec<char> = folded.chars().collect();
+    chars
+        .windows(3)
+        .map(|w| trigram(w[0], w[1], w[2]))
+        .collect()
+}
+
+fn read_doc(path: &Path) -> Option<(f64, String)> {
+    if std::fs::metadata(path).ok()?.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    let modified = mtime_secs(path)?;
+    let text = std::fs::read_to_string(path).ok()?;
+    Some((modified, text))
+}
+
+impl SearchIndex {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            updated_at: now_ms(),
+            docs: Vec::new(),
+            by_path: HashMap::new(),
+            postings: HashMap::new(),
+            live: 0,
+            last_saved: None,
+        }
+    }
+
+    fn from_stored(stored: Stored) -> Self {
+        let mut index = Self::new(stored.root);
+        for (path, file) in stored.files {
+            index.insert(path, file.modified, file.text);
+        }
+        index.updated_at = stored.updated_at;
+        index
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    fn doc(&self, path: &str) -> Option<&Doc> {
+        let id = *self.by_path.get(path)?;
+        self.docs[id as usize].as_ref()
+    }
+
+    fn insert(&mut self, path: String, modified: f64, text: String) {
+        self.remove(&path);
+        let id = self.docs.len() as u32;
+        let folded = fold(&text);
+        for t in trigrams(&folded) {
+            self.postings.entry(t).or_default().push(id);
+        }
+        self.by_path.insert(path.clone(), id);
+        self.docs.push(Some(Doc {
+            path,
+            modified,
+            text,
+            folded,
+        }));
+        self.live += 1;
+        self.updated_at = now_ms();
+        self.compact_if_needed();
+    }
+
+    fn remove(&mut self, path: &str) -> bool {
+        let Some(id) = self.by_path.remove(path) else {
+            return false;
+        };
+        if self.docs[id as usize].take().is_some() {
+            self.live -= 1;
+        }
+        self.updated_at = now_ms();
+        true
+    }
+
+    /// Rebuild the trigram table once most of it points at removed documents.
+    fn compact_if_needed(&mut self) {
+        if self.docs.len() < 64 || self.live * 2 > self.docs.len() {
+            return;
+        }
+        let updated_at = self.updated_at;
+        let docs = std::mem::take(&mut self.docs);
+        self.by_path.clear();
+        self.postings.clear();
+        self.live = 0;
+        for doc in docs.into_iter().flatten() {
+            self.insert(doc.path, doc.modified, doc.text);
+        }
+        self.updated_at = updated_at;
+    }
+
+    /// Re-read files whose mtime changed and drop files that are gone.
+    fn refresh(&mut self) {
+        let mut paths = Vec::new();
+        collect_files(&self.root, 0, &mut paths);
+        TOTAL.store(paths.len(), Ordering::Relaxed);
+        SCANNED.store(0, Ordering::Relaxed);
+        let present: HashSet<String> = paths.iter().map(|p| path_string(p)).collect();
+        let gone: Vec<String> = self
+            .by_path
+            .keys()
+            .filter(|k| !present.contains(*k))
+            .cloned()
+            .collect();
+        for key in gone {
+            self.remove(&key);
+        }
+        for path in paths {
+            self.update_file(&path);
+            SCANNED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns whether the index changed.
+    fn update_file(&mut self, path: &Path) -> bool {
+        let key = path_string(path);
+        let modified = mtime_secs(path);
+        if modified.is_some() && self.doc(&key).map(|d| d.modified) == modified {
+            return false;
+        }
+        match read_doc(path) {
+            Some((modified, text)) => {
+                self.insert(key, modified, text);
+                true
+            }
+            None => self.remove(&key),
+        }
+    }
+
+    /// Drop `path` and, for a directory, everything under it.
+    fn remove_path(&mut self, path: &Path) -> bool {
+        let gone: Vec<String> = self
+            .by_path
+            .keys()
+            .filter(|k| Path::new(k).starts_with(path))
+            .cloned()
+            .collect();
+        for key in &gone {
+            self.remove(key);
+        }
+        self.compact_if_needed();
+        !gone.is_empty()
+    }
+
+    fn rename_path(&mut self, old: &Path, new: &Path) -> bool {
+        let moved: Vec<String> = self
+            .by_path
+            .keys()
+            .filter(|k| Path::new(k).starts_with(old))
+            .cloned()
+            .collect();
+        for key in &moved {
+            let Some(id) = self.by_path.remove(key) else {
+                continue;
+            };
+            let Some(doc) = self.docs[id as usize].take() else {
+                continue;
+            };
+            self.live -= 1;
+            let Ok(rest) = Path::new(key).strip_prefix(old) else {
+                continue;
+            };
+            let target = if rest.as_os_str().is_empty() {
+                new.to_path_buf()
+            } else {
+                new.join(rest)
+            };
+            if self.contains(&target) && is_markdown(&target.to_string_lossy()) {
+                self.insert(path_string(&target), doc.modified, doc.text);
+            }
+        }
+        // A non-markdown file renamed to `.md` joins the index
+        if moved.is_empty()
+            && new.is_file()
+            && self.contains(new)
+            && is_markdown(&new.to_string_lossy())
+        {
+            return self.update_file(new);
+        }
+        self.compact_if_needed();
+        !moved.is_empty()
+    }
+
+    /// Documents that may contain every term: those holding all trigrams of
+    /// the terms long enough to have any. None when no term is.
+    fn candidates(&self, terms: &[String]) -> Option<Vec<u32>> {
+        let mut grams: Vec<u64> = terms
+            .iter()
+            .flat_map(|t| trigrams(t))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if grams.is_empty() {
+            return None;
+        }
+        let mut lists: Vec<&Vec<u32>> = Vec::with_capacity(grams.len());
+        for gram in grams.drain(..) {
+            match self.postings.get(&gram) {
+                Some(list) => lists.push(list),
+                None => return Some(Vec::new()),
+            }
+        }
+        lists.sort_by_key(|l| l.len());
+        let mut ids = lists[0].clone();
+        for list in &lists[1..] {
+            ids.retain(|id| list.binary_search(id).is_ok());
+        }
+        Some(ids)
+    }
+
+    fn query(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut terms: Vec<String> = Vec::new();
+        for term in fold(query).split_whitespace() {
+            if !terms.iter().any(|t| t == term) {
+                terms.push(term.to_string());
+            }
+        }
+        if terms.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+        let ids: Vec<u32> = self
+            .candidates(&terms)
+            .unwrap_or_else(|| (0..self.docs.len() as u32).collect());
+
+        let mut ranked: Vec<(f32, &Doc)> = Vec::new();
+        'docs: for id in ids {
+            let Some(doc) = &self.docs[id as usize] else {
+                continue;
+            };
+            let name = Path::new(&doc.path)
+                .file_name()
+                .map(|n| fold(&n.to_string_lossy()))
+                .unwrap_or_default();
+            let mut score = 0.0;
+            for term in &terms {
+                let count = doc.folded.matches(term.as_str()).take(MAX_COUNTED).count();
+                if count == 0 {
+                    continue 'docs;
+                }
+                score += 1.0 + (count as f32).ln_1p();
+                if name.contains(term.as_str()) {
+                    score += 5.0;
+                }
+            }
+            ranked.push((score, doc));
+        }
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path)));
+        ranked.truncate(limit);
+        ranked
+            .into_iter()
+            .map(|(score, doc)| snippet(doc, &terms, score))
+            .collect()
+    }
+
+    fn status(&self) -> SearchIndexStatus {
+        SearchIndexStatus {
+            root: Some(path_string(&self.root)),
+            building: BUILDING.load(Ordering::Relaxed),
+            scanned: SCANNED.load(Ordering::Relaxed),
+            total: TOTAL.load(Ordering::Relaxed),
+            files: self.live,
+            bytes: self
+                .docs
+                .iter()
+                .flatten()
+                .map(|d| d.text.len() as u64)
+                .sum(),
+            cache_bytes: cache_path(&self.root)
+                .and_then(|p| std::fs::metadata(p).ok())
+                .map(|m| m.len()),
+            updated_at: Some(self.updated_at),
+        }
+    }
+}
+
+/// The line around the earliest match, with every match highlighted.
+fn snippet(doc: &Doc, terms: &[String], score: f32) -> SearchHit {
+    let first = terms
+        .iter()
+        .filter_map(|t| doc.folded.find(t.as_str()))
+        .min()
+        .unwrap_or(0);
+    let text: Vec<char> = doc.text.chars().collect();
+    let folded: Vec<char> = doc.folded.chars().collect();
+    let at = doc.folded[..first].chars().count();
+    let line_start = text[..at]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let line_end = text[at..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(text.len(), |i| at + i);
+    let start = at.saturating_sub(SNIPPET_BEFORE).max(line_start);
+    let end = (at + SNIPPET_AFTER).min(line_end);
+
+    let window: String = folded[start..end].iter().collect();
+    let utf16 = |chars: usize| -> usize {
+        text[start..start + chars]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum()
+    };
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for term in terms {
+        let len = term.chars().count();
+        for (byte, _) in window.match_indices(term.as_str()) {
+            let from = window[..byte].chars().count();
+            ranges.push((from, from + len));
+        }
+    }
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (from, to) in ranges {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 => last.1 = last.1.max(to),
+            _ => merged.push((from, to)),
+        }
+    }
+
+    SearchHit {
+        path: doc.path.clone(),
+        score,
+        line: text[..at].iter().filter(|c| **c == '\n').count() + 1,
+        snippet: text[start..end].iter().collect(),
+        highlights: merged
+            .into_iter()
+            .map(|(from, to)| [utf16(from), utf16(to)])
+            .collect(),
+    }
+}
+
+fn cache_path(root: &Path) -> Option<PathBuf> {
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    Some(
+        CACHE_DIR
+            .get()?
+            .join(format!("{}.json", hex::encode(&digest[..8]))),
+    )
+}
+
+/// The cached index for `root`, or None when it is missing, damaged or
+/// from another version.
+fn parse_cache(bytes: &[u8], root: &Path) -> Option<SearchIndex> {
+    let stored: Stored = match serde_json::from_slice(bytes) {
+        Ok(stored) => stored,
+        Err(e) => {
+            log::info!("Search index cache unreadable, rebuilding: {}", e);
+            return None;
+        }
+    };
+    (stored.version == CACHE_VERSION && stored.root == root)
+        .then(|| SearchIndex::from_stored(stored))
+}
+
+fn load_cache(root: &Path) -> Option<SearchIndex> {
+    let bytes = std::fs::read(cache_path(root)?).ok()?;
+    parse_cache(&bytes, root)
+}
+
+fn save_cache(index: &mut SearchIndex) {
+    index.last_saved = Some(Instant::now());
+    let Some(path) = cache_path(&index.root) else {
+        return;
+    };
+    let stored = StoredRef {
+        version: CACHE_VERSION,
+        root: &index.root,
+        updated_at: index.updated_at,
+        files: index
+            .docs
+            .iter()
+            .flatten()
+            .map(|d| {
+                let file = StoredFileRef {
+                    modified: d.modified,
+                    text: &d.text,
+                };
+                (d.path.as_str(), file)
+            })
+            .collect(),
+    };
+    let result = serde_json::to_vec(&stored)
+        .map_err(std::io::Error::other)
+        .and_then(|bytes| {
+            std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+            super::file::write_atomic(&path, &bytes)
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to save search index: {}", e);
+    }
+}
+
+fn save_due(index: &SearchIndex) -> bool {
+    !matches!(index.last_saved, Some(t) if t.elapsed() < SAVE_INTERVAL)
+}
+
+/// Apply `f` to the loaded index if `path` is inside it, saving changes
+/// now and then.
+fn with_index(path: &Path, f: impl FnOnce(&mut SearchIndex) -> bool) {
+    let Ok(mut guard) = INDEX.lock() else {
+        return;
+    };
+    if let Some(index) = guard.as_mut().filter(|i| i.contains(path)) {
+        if f(index) && save_due(index) {
+            save_cache(index);
+        }
+    }
+}
+
+/// Called after `write_file` saves a document.
+pub(crate) fn file_written(path: &Path) {
+    if is_markdown(&path.to_string_lossy()) {
+        with_index(path, |index| index.update_file(path));
+    }
+}
+
+/// Called after `delete_file` removes a file or directory.
+pub(crate) fn path_removed(path: &Path) {
+    with_index(path, |index| index.remove_path(path));
+}
+
+/// Called after `rename_file`.
+pub(crate) fn path_renamed(old: &Path, new: &Path) {
+    let Ok(mut guard) = INDEX.lock() else {
+        return;
+    };
+    if let Some(index) = guard
+        .as_mut()
+        .filter(|i| i.contains(old) || i.contains(new))
+    {
+        if index.rename_path(old, new) && save_due(index) {
+            save_cache(index);
+        }
+    }
+}
+
+/// Called for each batch of the workspace watcher, for changes made
+/// outside Moraya.
+pub(crate) fn workspace_changed(change: &WorkspaceChange) {
+    match change.kind {
+        ChangeKind::Created | ChangeKind::Modified => {
+            for path in &change.paths {
+                let path = Path::new(path);
+                if change.dirs.iter().any(|d| Path::new(d) == path) {
+                    let mut files = Vec::new();
+                    collect_files(path, 0, &mut files);
+                    for file in files {
+                        file_written(&file);
+                    }
+                } else {
+                    file_written(path);
+                }
+            }
+        }
+        ChangeKind::Removed => {
+            for path in &change.paths {
+                path_removed(Path::new(path));
+            }
+        }
+        ChangeKind::Renamed => {
+            if let [old, new] = &change.paths[..] {
+                path_renamed(Path::new(old), Path::new(new));
+            }
+        }
+    }
+}
+
+/// Index the markdown files under `root`, reusing the cache for files whose
+/// mtime is unchanged, and make it the index queries run against.
+#[tauri::command]
+pub async fn search_index_build(root: String) -> Result<SearchIndexStatus, CommandError> {
+    let root = validate_path(&root)?;
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotADirectory,
+            "Not a directory",
+        ));
+    }
+    if BUILDING.swap(true, Ordering::AcqRel) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidArgument,
+            "The search index is already being built",
+        ));
+    }
+    let result = tokio::task::spawn_blocking(move || {
+        // The loaded index keeps answering queries until this one replaces it
+        let mut index = load_cache(&root).unwrap_or_else(|| SearchIndex::new(root.clone()));
+        index.refresh();
+        save_cache(&mut index);
+        BUILDING.store(false, Ordering::Release);
+        let status = index.status();
+        if let Ok(mut guard) = INDEX.lock() {
+            *guard = Some(index);
+        }
+        status
+    })
+    .await;
+    BUILDING.store(false, Ordering::Release);
+    result.map_err(|e| CommandError::from(format!("Search index task failed: {}", e)))
+}
+
+/// Ranked matches for `query` in the built index. Every whitespace-separated
+/// term must appear (case-insensitively); file name matches rank first.
+#[tauri::command]
+pub fn search_index_query(
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, CommandError> {
+    let guard = INDEX
+        .lock()
+        .map_err(|_| CommandError::from("Search index unavailable"))?;
+    let index = guard.as_ref().ok_or_else(|| {
+        CommandError::new(ErrorCode::Internal, "The search index has not been built")
+    })?;
+    Ok(index.query(&query, limit.unwrap_or(DEFAULT_LIMIT)))
+}
+
+/// Size, last update and build progress of the search index.
+#[tauri::command]
+pub fn search_index_status() -> SearchIndexStatus {
+    let loaded = INDEX
+        .lock()
+        .ok()
+        .and_then(|g| g.as_ref().map(SearchIndex::status));
+    loaded.unwrap_or_else(|| SearchIndexStatus {
+        root: None,
+        building: BUILDING.load(Ordering::Relaxed),
+        scanned: SCANNED.load(Ordering::Relaxed),
+        total: TOTAL.load(Ordering::Relaxed),
+        files: 0,
+        bytes: 0,
+        cache_bytes: None,
+        updated_at: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(files: &[(&str, &str)]) -> SearchIndex {
+        let mut index = SearchIndex::new(PathBuf::from("/vault"));
+        for (path, text) in files {
+            index.insert(format!("/vault/{}", path), 1.0, text.to_string());
+        }
+        index
+    }
+
+    fn paths(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.path.as_str()).collect()
+    }
+
+    #[test]
+    fn should_rank_matches_and_require_every_term() {
+        let index = index(&[
+            ("a.md", "Rust ownership rules\nBorrowing in Rust"),
+            ("rust.md", "nothing here but rust"),
+            ("b.md", "Python only"),
+        ]);
+        let hits = index.query("RUST", 10);
+        assert_eq!(paths(&hits), ["/vault/rust.md", "/vault/a.md"]);
+
+        let hits = index.query("ownership rust", 10);
+        assert_eq!(paths(&hits), ["/vault/a.md"]);
+        assert_eq!(hits[0].line, 1);
+        assert_eq!(hits[0].snippet, "Rust ownership rules");
+        assert_eq!(hits[0].highlights, [[0, 4], [5, 14]]);
+
+        // Too short for trigrams: scans instead
+        assert_eq!(
+            paths(&index.query("in", 10)),
+            ["/vault/a.md", "/vault/rust.md"]
+        );
+        assert!(index.query("ownership python", 10).is_empty());
+        assert_eq!(index.query("rust", 1).len(), 1);
+    }
+
+    #[test]
+    fn should_report_highlights_in_utf16_units() {
+        let index = index(&[("c.md", "first line\n😀 搜索 Index here")]);
+        let hits = index.query("index", 10);
+        assert_eq!(hits[0].line, 2);
+        assert_eq!(hits[0].snippet, "😀 搜索 Index here");
+        assert_eq!(hits[0].highlights, [[6, 11]]);
+    }
+
+    #[test]
+    fn should_follow_removals_and_renames() {
+        let mut index = index(&[("a.md", "alpha text"), ("dir/b.md", "beta text")]);
+        assert!(index.rename_path(Path::new("/vault/dir"), Path::new("/vault/moved")));
+        assert_eq!(paths(&index.query("beta", 10)), ["/vault/moved/b.md"]);
+        assert!(index.remove_path(Path::new("/vault/a.md")));
+        assert!(index.query("alpha", 10).is_empty());
+        assert_eq!(index.live, 1);
+
+        // Many replacements trigger a compaction without losing anything
+        for i in 0..100 {
+            index.insert("/vault/c.md".into(), i as f64, format!("gamma {}", i));
+        }
+        assert!(index.docs.len() < 64);
+        assert_eq!(index.query("gamma 99", 10)[0].path, "/vault/c.md");
+        assert_eq!(paths(&index.query("text", 10)), ["/vault/moved/b.md"]);
+    }
+
+    #[test]
+    fn should_rebuild_from_damaged_or_outdated_cache() {
+        let root = Path::new("/vault");
+        assert!(parse_cache(b"{not json", root).is_none());
+        let outdated = format!(
+            r#"{{"version":{},"root":"/vault","updated_at":5,"files":{{}}}}"#,
+            CACHE_VERSION + 1
+        );
+        assert!(parse_cache(outdated.as_bytes(), root).is_none());
+        let current = format!(
+            r#"{{"version":{},"root":"/vault","updated_at":5,"files":{{"/vault/a.md":{{"modified":1.0,"text":"cached words"}}}}}}"#,
+            CACHE_VERSION
+        );
+        let index = parse_cache(current.as_bytes(), root).unwrap();
+        assert_eq!(index.updated_at, 5);
+        assert_eq!(paths(&index.query("words", 10)), ["/vault/a.md"]);
+        assert!(parse_cache(current.as_bytes(), Path::new("/other")).is_none());
+    }
+}
@@ -0,0 +1,555 @@
+//! Settings the backend reads: one versioned JSON file
+//! (`app_data_dir()/app-settings.json`) validated against a fixed schema.
+//! Every change is broadcast as `settings:changed` so all windows can
+//! refresh; Rust code reads the current values through the typed accessors
+//! below.
+//!
+//! Purely frontend preferences stay in the plugin-store `settings.json`.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::{Emitter, Manager};
+
+pub const CHANGED_EVENT: &str = "settings:changed";
+const SETTINGS_FILE: &str = "app-settings.json";
+/// Bump together with a new step in `migrate`.
+const SCHEMA_VERSION: u64 = 1;
+const VERSION_KEY: &str = "schemaVersion";
+const VALUES_KEY: &str = "values";
+
+enum Kind {
+    Bool,
+    /// Absolute filesystem paths.
+    PathList,
+    /// `null` or a URL with one of the given schemes.
+    OptionalUrl(&'static [&'static str]),
+    /// `null` or an absolute filesystem path.
+    OptionalPath,
+    /// Non-empty relative path without `..`; may contain `{{...}}` placeholders.
+    RelativePattern,
+    /// Whole number in `min..=max`.
+    Integer {
+        min: u64,
+        max: u64,
+    },
+}
+
+struct SettingDef {
+    key: &'static str,
+    kind: Kind,
+    /// JSON literal.
+    default: &'static str,
+}
+
+const SCHEMA: &[SettingDef] = &[
+    // Extra directories, besides home and external volumes, that file
+    // commands may access.
+    SettingDef {
+        key: "allowedRoots",
+        kind: Kind::PathList,
+        default: "[]",
+    },
+    // Hours between scheduled vault backups; 0 turns them off.
+    SettingDef {
+        key: "backupIntervalHours",
+        kind: Kind::Integer {
+            min: 0,
+            max: 24 * 30,
+        },
+        default: "24",
+    },
+    // Folder backed up on schedule, usually the knowledge base...
+    SettingDef {
+        key: "backupSourceDir",
+        kind: Kind::OptionalPath,
+        default: "null",
+    },
+    // ...and where its zip archives are kept.
+    SettingDef {
+        key: "backupTargetDir",
+        kind: Kind::OptionalPath,
+        default: "null",
+    },
+    // Scheduled backups kept; older archives are deleted.
+    SettingDef {
+        key: "maxBackups",
+        kind: Kind::Integer { min: 1, max: 1000 },
+        default: "14",
+    },
+    // Entries a background folder scan reads before giving up and asking
+    // for a narrower folder.
+    SettingDef {
+        key: "dirScanMaxEntries",
+        kind: Kind::Integer {
+            min: 1_000,
+            max: 10_000_000,
+        },
+        default: "200000",
+    },
+    // Web clipper: note that clippings are appended to...
+    SettingDef {
+        key: "clippingsFile",
+        kind: Kind::OptionalPath,
+        default: "null",
+    },
+    // ...or, when that is unset, folder that gets one new note per clipping.
+    SettingDef {
+        key: "clippingsFolder",
+        kind: Kind::OptionalPath,
+        default: "null",
+    },
+    // Where daily notes live, relative to the knowledge base.
+    SettingDef {
+        key: "dailyNotePattern",
+        kind: Kind::RelativePattern,
+        default: "\"{{year}}/{{month}}/{{date}}.md\"",
+    },
+    // Template for new daily notes when none is passed explicitly.
+    SettingDef {
+        key: "dailyNoteTemplate",
+        kind: Kind::OptionalPath,
+        default: "null",
+    },
+    // Minutes an unlocked encrypted document stays unlocked while unused;
+    // 0 keeps it unlocked until locked explicitly or the app quits.
+    SettingDef {
+        key: "encryptionAutoLockMinutes",
+        kind: Kind::Integer { min: 0, max: 1440 },
+        default: "15",
+    },
+    // Windows/Linux: closing the main window hides it to the tray.
+    SettingDef {
+        key: "closeToTray",
+        kind: Kind::Bool,
+        default: "false",
+    },
+    // Allows `toggle_devtools` and leaves Ctrl+Shift+I to the WebView.
+    SettingDef {
+        key: "developerMode",
+        kind: Kind::Bool,
+        default: "false",
+    },
+    // Global switch over the per-manifest `autoRestart` flag.
+    SettingDef {
+        key: "pluginAutoRestart",
+        kind: Kind::Bool,
+        default: "true",
+    },
+    // Proxy for backend HTTP requests; null uses the system proxy.
+    SettingDef {
+        key: "proxyUrl",
+        kind: Kind::OptionalUrl(&["http", "https"]),
+        default: "null",
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsChanged {
+    pub key: String,
+    pub value: Value,
+}
+
+struct SettingsStore {
+    path: PathBuf,
+    /// Stored values as on disk. Keys unknown to this version (written by
+    /// a newer one) are kept so a downgrade doesn't drop them.
+    values: Mutex<Map<String, Value>>,
+}
+
+static SETTINGS: OnceLock<SettingsStore> = OnceLock::new();
+
+fn definition(key: &str) -> Result<&'static SettingDef, String> {
+    SCHEMA
+        .iter()
+        .find(|d| d.key == key)
+        .ok_or_else(|| format!("Unknown setting: {}", key))
+}
+
+fn default_value(def: &SettingDef) -> Value {
+    serde_json::from_str(def.default).unwrap_or(Value::Null)
+}
+
+/// Check `value` against the schema entry for `key`.
+fn validate(key: &str, value: &Value) -> Result<(), String> {
+    let def = definition(key)?;
+    match def.kind {
+        Kind::Bool => {
+            if !value.is_boolean() {
+                return Err(format!("Setting {} must be a boolean", key));
+            }
+        }
+        Kind::PathList => {
+            let Some(items) = value.as_array() else {
+                return Err(format!("Setting {} must be an array of paths", key));
+            };
+            for item in items {
+                let Some(path) = item.as_str() else {
+                    return Err(format!("Setting {} must be an array of paths", key));
+                };
+                if !Path::new(path).is_absolute() {
+                    return Err(format!(
+                        "Setting {} must contain absolute paths: {}",
+                        key, path
+                    ));
+                }
+            }
+        }
+        Kind::OptionalUrl(schemes) => {
+            if value.is_null() {
+                return Ok(());
+            }
+            let valid = value
+                .as_str()
+                .and_then(|s| url::Url::parse(s).ok())
+                .is_some_and(|u| schemes.contains(&u.scheme()) && u.host_str().is_some());
+            if !valid {
+                return Err(format!(
+                    "Setting {} must be null or a {} URL",
+                    key,
+                    schemes.join("/")
+                ));
+            }
+        }
+        Kind::OptionalPath => {
+            if !value.is_null() && !value.as_str().is_some_and(|p| Path::new(p).is_absolute()) {
+                return Err(format!("Setting {} must be null or an absolute path", key));
+            }
+        }
+        Kind::RelativePattern => {
+            let valid = value.as_str().is_some_and(|p| {
+                let path = Path::new(p);
+                !p.trim().is_empty()
+                    && path
+                        .components()
+                        .all(|c| matches!(c, std::path::Component::Normal(_)))
+            });
+            if !valid {
+                return Err(format!(
+                    "Setting {} must be a relative path inside the folder",
+                    key
+                ));
+            }
+        }
+        Kind::Integer { min, max } => {
+            if !value.as_u64().is_some_and(|n| (min..=max).contains(&n)) {
+                return Err(format!(
+                    "Setting {} must be a whole number from {} to {}",
+                    key, min, max
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Bring a stored document up to `SCHEMA_VERSION`, one step per version.
+/// Returns the stored values and whether anything changed.
+fn migrate(mut doc: Map<String, Value>) -> (Map<String, Value>, bool) {
+    let mut version = doc.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0);
+    let migrated = version < SCHEMA_VERSION;
+    while version < SCHEMA_VERSION {
+        match version {
+            // 0 → 1: unversioned flat object → `{ schemaVersion, values }`
+            0 => {
+                doc.remove(VERSION_KEY);
+                let values = std::mem::take(&mut doc);
+                doc.insert(VALUES_KEY.to_string(), Value::Object(values));
+            }
+            _ => unreachable!("missing settings migration from v{}", version),
+        }
+        version += 1;
+    }
+    let values = match doc.remove(VALUES_KEY) {
+        Some(Value::Object(values)) => values,
+        _ => Map::new(),
+    };
+    (values, migrated)
+}
+
+/// The value in effect for `key`: the stored one if it is valid, else the
+/// default.
+fn effective(values: &Map<String, Value>, def: &SettingDef) -> Value {
+    match values.get(def.key) {
+        Some(v) if validate(def.key, v).is_ok() => v.clone(),
+        _ => default_value(def),
+    }
+}
+
+fn read_document(path: &Path) -> Map<String, Value> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => match serde_json::from_str::<Value>(&text) {
+            Ok(Value::Object(doc)) => doc,
+            _ => {
+                log::warn!("Settings file is not a JSON object; using defaults");
+                Map::new()
+            }
+        },
+        Err(_) => Map::new(),
+    }
+}
+
+fn write_document(path: &Path, values: &Map<String, Value>) -> Result<(), String> {
+    let mut doc = Map::new();
+    doc.insert(VERSION_KEY.to_string(), SCHEMA_VERSION.into());
+    doc.insert(VALUES_KEY.to_string(), Value::Object(values.clone()));
+    let text = serde_json::to_string_pretty(&doc).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, text)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Load and migrate the settings file. Called from the setup hook, before
+/// anything reads a setting; until then the accessors return defaults.
+pub fn init(app: &tauri::AppHandle) {
+    let Ok(dir) = app.path().app_data_dir() else {
+        return;
+    };
+    let path = dir.join(SETTINGS_FILE);
+    let (values, migrated) = migrate(read_document(&path));
+    for def in SCHEMA {
+        if let Some(Err(e)) = values.get(def.key).map(|v| validate(def.key, v)) {
+            log::warn!("Ignoring stored value: {}", e);
+        }
+    }
+    if migrated && path.exists() {
+        if let Err(e) = write_document(&path, &values) {
+            log::warn!("{}", e);
+        }
+    }
+    let _ = SETTINGS.set(SettingsStore {
+        path,
+        values: Mutex::new(values),
+    });
+}
+
+fn current(key: &str) -> Value {
+    let Ok(def) = definition(key) else {
+        return Value::Null;
+    };
+    match SETTINGS.get().and_then(|s| s.values.lock().ok()) {
+        Some(values) => effective(&values, def),
+        None => default_value(def),
+    }
+}
+
+pub(crate) fn close_to_tray() -> bool {
+    current("closeToTray").as_bool().unwrap_or(false)
+}
+
+pub(crate) fn developer_mode() -> bool {
+    current("developerMode").as_bool().unwrap_or(false)
+}
+
+pub(crate) fn plugin_auto_restart() -> bool {
+    current("pluginAutoRestart").as_bool().unwrap_or(true)
+}
+
+pub(crate) fn allowed_roots() -> Vec<PathBuf> {
+    match current("allowedRoots") {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(PathBuf::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Test-only: add `dir` to `allowedRoots` in memory so commands accept
+/// fixtures outside the home folder. Nothing is written to disk.
+#[cfg(test)]
+pub(crate) fn allow_root(dir: &Path) {
+    let store = SETTINGS.get_or_init(|| SettingsStore {
+        path: PathBuf::new(),
+        values: Mutex::new(Map::new()),
+    });
+    let mut values = match store.values.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let roots = values
+        .entry("allowedRoots")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Value::Array(items) = roots {
+        items.push(Value::from(dir.to_string_lossy().into_owned()));
+    }
+}
+
+pub(crate) fn backup_interval_hours() -> u64 {
+    current("backupIntervalHours").as_u64().unwrap_or(24)
+}
+
+pub(crate) fn backup_source_dir() -> Option<String> {
+    current("backupSourceDir").as_str().map(String::from)
+}
+
+pub(crate) fn backup_target_dir() -> Option<String> {
+    current("backupTargetDir").as_str().map(String::from)
+}
+
+pub(crate) fn max_backups() -> u64 {
+    current("maxBackups").as_u64().unwrap_or(14)
+}
+
+pub(crate) fn dir_scan_max_entries() -> u64 {
+    current("dirScanMaxEntries").as_u64().unwrap_or(200_000)
+}
+
+pub(crate) fn clippings_file() -> Option<String> {
+    current("clippingsFile").as_str().map(String::from)
+}
+
+pub(crate) fn clippings_folder() -> Option<String> {
+    current("clippingsFolder").as_str().map(String::from)
+}
+
+pub(crate) fn daily_note_pattern() -> String {
+    current("dailyNotePattern")
+        .as_str()
+        .unwrap_or("{{year}}/{{month}}/{{date}}.md")
+        .to_string()
+}
+
+pub(crate) fn daily_note_template() -> Option<String> {
+    current("dailyNoteTemplate").as_str().map(String::from)
+}
+
+pub(crate) fn encryption_auto_lock_minutes() -> u64 {
+    current("encryptionAutoLockMinutes").as_u64().unwrap_or(15)
+}
+
+pub(crate) fn proxy_url() -> Option<String> {
+    current("proxyUrl").as_str().map(String::from)
+}
+
+/// `reqwest::Client::builder()` with the configured proxy applied. Clients
+/// built before a change (long-lived ones) keep the old proxy until restart.
+pub(crate) fn client_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder();
+    match proxy_url().map(|url| reqwest::Proxy::all(url.as_str())) {
+        Some(Ok(proxy)) => builder.proxy(proxy),
+        _ => builder,
+    }
+}
+
+#[tauri::command]
+pub fn settings_get(key: String) -> Result<Value, String> {
+    definition(&key)?;
+    Ok(current(&key))
+}
+
+/// All settings, with defaults filled in for unset keys.
+#[tauri::command]
+pub fn settings_get_all() -> Result<Map<String, Value>, String> {
+    let stored = SETTINGS.get().and_then(|s| s.values.lock().ok());
+    Ok(SCHEMA
+        .iter()
+        .map(|def| {
+            let value = match &stored {
+                Some(values) => effective(values, def),
+                None => default_value(def),
+            };
+            (def.key.to_string(), value)
+        })
+        .collect())
+}
+
+/// Validate, persist and broadcast one setting.
+#[tauri::command]
+pub fn settings_set(app: tauri::AppHandle, key: String, value: Value) -> Result<(), String> {
+    validate(&key, &value)?;
+    let store = SETTINGS.get().ok_or("Settings are not initialized")?;
+    {
+        let mut values = store
+            .values
+            .lock()
+            .map_err(|_| "Settings lock poisoned".to_string())?;
+        if values.get(&key) == Some(&value) {
+            return Ok(());
+        }
+        let mut next = values.clone();
+        next.insert(key.clone(), value.clone());
+        if let Some(dir) = store.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to save settings: {}", e))?;
+        }
+        write_document(&store.path, &next)?;
+        *values = next;
+    }
+    let _ = app.emit(CHANGED_EVENT, SettingsChanged { key, value });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_reject_unknown_keys_and_wrong_types() {
+        assert_eq!(
+            validate("colorTheme", &json!("dark")).unwrap_err(),
+            "Unknown setting: colorTheme"
+        );
+        assert_eq!(
+            validate("closeToTray", &json!("yes")).unwrap_err(),
+            "Setting closeToTray must be a boolean"
+        );
+        assert!(validate("closeToTray", &json!(true)).is_ok());
+        assert!(validate("developerMode", &json!("on")).is_err());
+        assert!(validate("allowedRoots", &json!([1])).is_err());
+        assert!(validate("allowedRoots", &json!(["notes"])).is_err());
+        assert!(validate("dailyNotePattern", &json!("journal/{{date}}.md")).is_ok());
+        assert!(validate("dailyNotePattern", &json!("../{{date}}.md")).is_err());
+        assert!(validate("dailyNotePattern", &json!("/abs/{{date}}.md")).is_err());
+        assert!(validate("dailyNoteTemplate", &json!("templates/daily.md")).is_err());
+        assert!(validate("encryptionAutoLockMinutes", &json!(0)).is_ok());
+        assert!(validate("encryptionAutoLockMinutes", &json!(-5)).is_err());
+        assert!(validate("encryptionAutoLockMinutes", &json!(2.5)).is_err());
+        assert!(validate("encryptionAutoLockMinutes", &json!(10_000)).is_err());
+        assert!(validate("backupIntervalHours", &json!(0)).is_ok());
+        assert!(validate("maxBackups", &json!(0)).is_err());
+        assert!(validate("backupTargetDir", &json!("backups")).is_err());
+        assert!(validate("proxyUrl", &json!(null)).is_ok());
+        assert!(validate("proxyUrl", &json!("http://127.0.0.1:7890")).is_ok());
+        assert_eq!(
+            validate("proxyUrl", &json!("ftp://proxy")).unwrap_err(),
+            "Setting proxyUrl must be null or a http/https URL"
+        );
+    }
+
+    #[test]
+    fn should_have_valid_defaults() {
+        for def in SCHEMA {
+            assert!(
+                validate(def.key, &default_value(def)).is_ok(),
+                "{}",
+                def.key
+            );
+        }
+    }
+
+    #[test]
+    fn should_migrate_unversioned_document() {
+        let doc = json!({ "closeToTray": true, "futureKey": 1 });
+        let (values, migrated) = migrate(doc.as_object().unwrap().clone());
+        assert!(migrated);
+        assert_eq!(values["closeToTray"], json!(true));
+        assert_eq!(values["futureKey"], json!(1));
+
+        let doc = json!({ "schemaVersion": 1, "values": { "closeToTray": false } });
+        let (values, migrated) = migrate(doc.as_object().unwrap().clone());
+        assert!(!migrated);
+        assert_eq!(values["closeToTray"], json!(false));
+    }
+
+    #[test]
+    fn should_fall_back_to_default_for_invalid_stored_value() {
+        let values = json!({ "pluginAutoRestart": "no" });
+        let def = definition("pluginAutoRestart").unwrap();
+        assert_eq!(effective(values.as_object().unwrap(), def), json!(true));
+    }
+}
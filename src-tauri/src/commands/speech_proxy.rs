@@ -11,8 +11,8 @@
 //! API keys never transit the network from the frontend.
 
 use std::collections::{HashMap, VecDeque};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use super::macos_system_audio::NativeSystemAudioCapture;
@@ -21,7 +21,7 @@ use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tauri::ipc::Channel;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio_tungstenite::tungstenite::Message;
 
 // ── Session counter ──────────────────────────────────────────────────────────
@@ -29,6 +29,14 @@ use tokio_tungstenite::tungstenite::Message;
 static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
 const PCM_MIX_CHUNK_SAMPLES: usize = 4_000;
 const PCM_MIX_INTERVAL_MS: u64 = 250;
+/// How long a flushing stop waits for the provider to finalize pending interim results.
+const SPEECH_FLUSH_TIMEOUT_MS: u64 = 2_000;
+/// How long the writer waits for the server's Close reply before reporting Disconnected.
+const SPEECH_CLOSE_TIMEOUT_MS: u64 = 1_500;
+/// Minimum interval between `SpeechEvent::Stats` events.
+const SPEECH_STATS_INTERVAL_MS: u64 = 500;
+/// Default cap on concurrent transcription sessions (see `speech_proxy_set_max_sessions`).
+const DEFAULT_MAX_SPEECH_SESSIONS: usize = 2;
 
 fn new_session_id() -> String {
     let count = SESSION_COUNTER.fetch_add(1, Ordering::SeqCst);
@@ -41,20 +49,119 @@ fn new_session_id() -> String {
 
 // ── Shared state ─────────────────────────────────────────────────────────────
 
+/// Stop signal sent from `speech_proxy_stop` / `speech_proxy_finalize` to the writer task.
+#[derive(Default)]
+struct StopRequest {
+    /// Send the provider's flush message and wait for final results before closing.
+    flush: bool,
+    /// Notified once the socket is closed and Disconnected has been emitted.
+    done_tx: Option<oneshot::Sender<()>>,
+}
+
 struct SpeechSession {
+    provider: String,
     audio_tx: mpsc::Sender<Vec<u8>>,
-    stop_tx: Option<oneshot::Sender<()>>,
+    stop_tx: Option<oneshot::Sender<StopRequest>>,
     native_system_capture: Option<NativeSystemAudioCapture>,
+    stats: Arc<SessionStats>,
+}
+
+/// Live counters updated by the writer task and read by `speech_proxy_get_session`.
+struct SessionStats {
+    started: std::time::Instant,
+    bytes_sent: AtomicU64,
+    /// `f32::to_bits` of the RMS level of the last chunk sent.
+    level_bits: AtomicU32,
+}
+
+impl SessionStats {
+    fn new() -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            bytes_sent: AtomicU64::new(0),
+            level_bits: AtomicU32::new(0),
+        }
+    }
+
+    fn record(&self, bytes: usize, level: f32) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.level_bits.store(level.to_bits(), Ordering::Relaxed);
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    fn level(&self) -> f32 {
+        f32::from_bits(self.level_bits.load(Ordering::Relaxed))
+    }
+}
+
+/// Snapshot returned by `speech_proxy_get_session` / `speech_proxy_list_sessions`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeechSessionStats {
+    pub session_id: String,
+    pub provider: String,
+    pub elapsed_ms: u64,
+    pub bytes_sent: u64,
+    /// RMS of the last chunk, normalized to 0.0–1.0.
+    pub level: f32,
+}
+
+/// RMS level of a little-endian PCM16 chunk, normalized to 0.0–1.0.
+fn pcm16_rms(chunk: &[u8]) -> f32 {
+    let samples = chunk.len() / 2;
+    if samples == 0 {
+        return 0.0;
+    }
+    let sum_sq: f64 = chunk
+        .chunks_exact(2)
+        .map(|pair| {
+            let v = i16::from_le_bytes([pair[0], pair[1]]) as f64;
+            v * v
+        })
+        .sum();
+    ((sum_sq / samples as f64).sqrt() / i16::MAX as f64).min(1.0) as f32
 }
 
 pub struct SpeechProxyState {
-    sessions: Mutex<HashMap<String, SpeechSession>>,
+    /// Shared with each session's writer task, which removes its own entry on exit.
+    sessions: Arc<Mutex<HashMap<String, SpeechSession>>>,
+    max_sessions: AtomicUsize,
 }
 
 impl SpeechProxyState {
     pub fn new() -> Self {
         Self {
-            sessions: Mutex::new(HashMap::new()),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            max_sessions: AtomicUsize::new(DEFAULT_MAX_SPEECH_SESSIONS),
+        }
+    }
+
+    fn ensure_capacity(sessions: &HashMap<String, SpeechSession>, max: usize) -> Result<(), String> {
+        if sessions.len() >= max {
+            return Err(format!(
+                "Too many active transcription sessions (max {}). Stop one before starting another.",
+                max
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SpeechSession {
+    fn snapshot(&self, session_id: &str) -> SpeechSessionStats {
+        SpeechSessionStats {
+            session_id: session_id.to_string(),
+            provider: self.provider.clone(),
+            elapsed_ms: self.stats.elapsed_ms(),
+            bytes_sent: self.stats.bytes_sent(),
+            level: self.stats.level(),
         }
     }
 }
@@ -173,13 +280,128 @@ pub enum SpeechEvent {
         session_id: String,
         error: String,
     },
-    #[allow(dead_code)]
+    /// Throttled progress report (every `SPEECH_STATS_INTERVAL_MS` while audio flows).
+    Stats {
+        #[serde(rename = "sessionId")]
+        session_id: String,
+        #[serde(rename = "elapsedMs")]
+        elapsed_ms: u64,
+        #[serde(rename = "bytesSent")]
+        bytes_sent: u64,
+        level: f32,
+    },
+    /// Always the last event of a session (emitted by the writer task).
     Disconnected {
         #[serde(rename = "sessionId")]
         session_id: String,
+        /// Finalized WAV recording, when the session was started with `record_path`.
+        #[serde(rename = "recordPath", skip_serializing_if = "Option::is_none")]
+        record_path: Option<String>,
     },
 }
 
+// ── Session recording (WAV) ──────────────────────────────────────────────────
+
+const WAV_HEADER_LEN: u64 = 44;
+const RECORD_SAMPLE_RATE: u32 = 16_000;
+/// Flush + header patch interval: a crash loses at most this much audio.
+const RECORD_FLUSH_INTERVAL_MS: u64 = 2_000;
+
+/// Canonical 44-byte PCM WAV header (16 kHz, 16-bit, mono).
+fn wav_header(data_len: u32) -> [u8; 44] {
+    let byte_rate = RECORD_SAMPLE_RATE * 2;
+    let mut h = [0u8; 44];
+    h[0..4].copy_from_slice(b"RIFF");
+    h[4..8].copy_from_slice(&data_len.saturating_add(36).to_le_bytes());
+    h[8..12].copy_from_slice(b"WAVE");
+    h[12..16].copy_from_slice(b"fmt ");
+    h[16..20].copy_from_slice(&16u32.to_le_bytes());
+    h[20..22].copy_from_slice(&1u16.to_le_bytes()); // PCM
+    h[22..24].copy_from_slice(&1u16.to_le_bytes()); // mono
+    h[24..28].copy_from_slice(&RECORD_SAMPLE_RATE.to_le_bytes());
+    h[28..32].copy_from_slice(&byte_rate.to_le_bytes());
+    h[32..34].copy_from_slice(&2u16.to_le_bytes()); // block align
+    h[34..36].copy_from_slice(&16u16.to_le_bytes()); // bits per sample
+    h[36..40].copy_from_slice(b"data");
+    h[40..44].copy_from_slice(&data_len.to_le_bytes());
+    h
+}
+
+/// Appends session audio to a WAV file as it flows through the writer task.
+/// The header is written up front and its length fields patched on every
+/// periodic flush and on `finish`, so the file is playable even after a crash.
+struct WavRecorder {
+    path: std::path::PathBuf,
+    writer: Option<std::io::BufWriter<std::fs::File>>,
+    data_len: u64,
+    last_flush: std::time::Instant,
+}
+
+impl WavRecorder {
+    fn create(path: std::path::PathBuf) -> Result<Self, String> {
+        use std::io::Write;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create recording directory: {}", e))?;
+        }
+        let mut file = std::fs::File::create(&path)
+            .map_err(|e| format!("Failed to create recording file: {}", e))?;
+        file.write_all(&wav_header(0))
+            .map_err(|e| format!("Failed to write recording header: {}", e))?;
+        Ok(Self {
+            path,
+            writer: Some(std::io::BufWriter::new(file)),
+            data_len: 0,
+            last_flush: std::time::Instant::now(),
+        })
+    }
+
+    /// Append a PCM chunk. On the first I/O failure (e.g. disk full) recording
+    /// is disabled and the error returned once; transcription is unaffected.
+    fn append(&mut self, pcm: &[u8]) -> Result<(), String> {
+        use std::io::Write;
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        let mut result = writer.write_all(pcm);
+        if result.is_ok() {
+            self.data_len += pcm.len() as u64;
+            if self.last_flush.elapsed() >= Duration::from_millis(RECORD_FLUSH_INTERVAL_MS) {
+                self.last_flush = std::time::Instant::now();
+                result = Self::flush_with_header(writer, self.data_len);
+            }
+        }
+        result.map_err(|e| {
+            self.writer = None;
+            format!("Recording stopped, transcription continues: {}", e)
+        })
+    }
+
+    fn flush_with_header(
+        writer: &mut std::io::BufWriter<std::fs::File>,
+        data_len: u64,
+    ) -> std::io::Result<()> {
+        use std::io::{Seek, SeekFrom, Write};
+        writer.flush()?;
+        let len = data_len.min((u32::MAX as u64) - WAV_HEADER_LEN) as u32;
+        let file = writer.get_mut();
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&wav_header(len))?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+
+    /// Patch the header with the final length and return the recording path.
+    fn finish(mut self) -> Result<String, String> {
+        if let Some(mut writer) = self.writer.take() {
+            Self::flush_with_header(&mut writer, self.data_len)
+                .and_then(|_| writer.get_ref().sync_all())
+                .map_err(|e| format!("Failed to finalize recording: {}", e))?;
+        }
+        Ok(self.path.to_string_lossy().into_owned())
+    }
+}
+
 // ── Deepgram JSON parsing ─────────────────────────────────────────────────────
 
 #[derive(Deserialize, Debug)]
@@ -279,6 +501,7 @@ struct AaiV3Message {
     end_of_turn: Option<bool>,           // true = final utterance; false = partial/streaming
     end_of_turn_confidence: Option<f64>, // model's confidence that the turn ended
     words: Option<Vec<AaiV3Word>>,
+    speaker_label: Option<String>,       // present when speaker_labels=true ("A", "B", …)
     // Error fields — server sends these in a text frame before Close
     error: Option<String>,
     message: Option<String>,
@@ -288,6 +511,19 @@ struct AaiV3Message {
 struct AaiV3Word {
     start: Option<f64>, // milliseconds
     end: Option<f64>,   // milliseconds
+    speaker: Option<String>,
+}
+
+/// Map an AssemblyAI speaker label ("A", "B", … or "UNKNOWN") onto the
+/// `SPEAKER_n` ids used by the other providers.
+fn assemblyai_speaker_id(label: &str) -> String {
+    let label = label.trim();
+    let mut chars = label.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => format!("SPEAKER_{}", c as u32 - 'A' as u32),
+        _ if label.is_empty() || label.eq_ignore_ascii_case("unknown") => "SPEAKER_0".to_string(),
+        _ => parse_gladia_speaker(Some(&Value::String(label.to_string()))),
+    }
 }
 
 fn parse_assemblyai(session_id: &str, text: &str) -> Option<SpeechEvent> {
@@ -319,7 +555,7 @@ fn parse_assemblyai(session_id: &str, text: &str) -> Option<SpeechEvent> {
     let is_partial = msg_type_str == "PartialTranscript";
     if !is_turn && !is_partial {
         #[cfg(debug_assertions)]
-        eprintln!("[speech_proxy:assemblyai] ignored msg type={}", msg_type_str);
+        log::debug!("assemblyai: ignored msg type={}", msg_type_str);
         return None;
     }
 
@@ -331,7 +567,7 @@ fn parse_assemblyai(session_id: &str, text: &str) -> Option<SpeechEvent> {
         None => {
             // Turn arrived but both transcript and text fields are empty — likely silence
             #[cfg(debug_assertions)]
-            eprintln!("[speech_proxy:assemblyai] {} with empty text (end_of_turn={:?})",
+            log::debug!("assemblyai: {} with empty text (end_of_turn={:?})",
                 msg_type_str, msg.end_of_turn);
             return None;
         }
@@ -352,10 +588,22 @@ fn parse_assemblyai(session_id: &str, text: &str) -> Option<SpeechEvent> {
         .map(|e| e as u64)
         .unwrap_or(0);
 
+    // Turn-level label first; fall back to the first labelled word.
+    let speaker_id = msg
+        .speaker_label
+        .as_deref()
+        .or_else(|| {
+            msg.words
+                .as_ref()
+                .and_then(|ws| ws.iter().find_map(|w| w.speaker.as_deref()))
+        })
+        .map(assemblyai_speaker_id)
+        .unwrap_or_else(|| "SPEAKER_0".to_string());
+
     Some(SpeechEvent::Transcript {
         session_id: session_id.to_string(),
         segment: SpeechSegmentData {
-            speaker_id: "SPEAKER_0".to_string(), // v3 basic mode has no speaker diarization
+            speaker_id,
             text,
             start_ms,
             end_ms,
@@ -462,7 +710,9 @@ fn parse_gladia(session_id: &str, text: &str) -> Option<SpeechEvent> {
                 .and_then(|v| v.as_f64())
                 .unwrap_or(0.0)
                 * 1000.0) as u64;
-            let speaker_id = parse_gladia_speaker(utterance.get("channel"));
+            // Diarized sessions carry `speaker`; otherwise fall back to the audio channel.
+            let speaker_id =
+                parse_gladia_speaker(utterance.get("speaker").or_else(|| utterance.get("channel")));
 
             return Some(SpeechEvent::Transcript {
                 session_id: session_id.to_string(),
@@ -701,6 +951,74 @@ fn build_realtime_audio_commit() -> String {
     serde_json::json!({ "type": "input_audio_buffer.commit" }).to_string()
 }
 
+/// "End of audio" frame for custom protocols, sent before the WebSocket Close.
+fn build_custom_end_frame(mode: CustomWsMode, dashscope_finish_msg: Option<&str>) -> Option<String> {
+    match mode {
+        CustomWsMode::DashScopeFunAsr => dashscope_finish_msg.map(str::to_string),
+        CustomWsMode::TencentAsrV2 => Some(build_tencent_end_signal()),
+        CustomWsMode::IflytekIatV2 => Some(build_iflytek_end_frame()),
+        CustomWsMode::OpenAiRealtime | CustomWsMode::VolcengineRealtime => {
+            Some(build_realtime_audio_commit())
+        }
+        CustomWsMode::RawBinary => None,
+    }
+}
+
+/// Provider flush message that forces pending interim results to be finalized.
+/// Custom protocols reuse their end-of-audio frame (see `build_custom_end_frame`).
+fn build_flush_frame(provider: &str, gladia_legacy_mode: bool) -> Option<String> {
+    match provider {
+        "deepgram" => Some(serde_json::json!({ "type": "Finalize" }).to_string()),
+        "assemblyai" => Some(serde_json::json!({ "type": "ForceEndpoint" }).to_string()),
+        "gladia" if !gladia_legacy_mode => {
+            Some(serde_json::json!({ "type": "stop_recording" }).to_string())
+        }
+        _ => None,
+    }
+}
+
+fn build_assemblyai_terminate() -> String {
+    serde_json::json!({ "type": "Terminate" }).to_string()
+}
+
+/// Append a chunk to the session recording, reporting (once) if recording fails.
+fn record_chunk(
+    recorder: &mut Option<WavRecorder>,
+    pcm: &[u8],
+    on_event: &Channel<SpeechEvent>,
+    session_id: &str,
+) {
+    if let Some(rec) = recorder.as_mut() {
+        if let Err(error) = rec.append(pcm) {
+            let _ = on_event.send(SpeechEvent::Error {
+                session_id: session_id.to_string(),
+                error,
+            });
+        }
+    }
+}
+
+/// Wrap a PCM chunk in the frame format expected by the session's protocol.
+fn build_audio_message(
+    mode: CustomWsMode,
+    iflytek_app_id: &str,
+    language: &str,
+    data: Vec<u8>,
+    iflytek_first_frame_sent: &mut bool,
+) -> Message {
+    match mode {
+        CustomWsMode::IflytekIatV2 => {
+            let status = if *iflytek_first_frame_sent { 1 } else { 0 };
+            *iflytek_first_frame_sent = true;
+            Message::Text(build_iflytek_audio_frame(iflytek_app_id, language, &data, status))
+        }
+        CustomWsMode::OpenAiRealtime | CustomWsMode::VolcengineRealtime => {
+            Message::Text(build_realtime_audio_append(&data))
+        }
+        _ => Message::Binary(data),
+    }
+}
+
 fn build_openai_realtime_session_update(model: &str) -> String {
     let selected_model = if model.trim().is_empty() {
         "gpt-4o-mini-transcribe"
@@ -1262,17 +1580,229 @@ fn parse_custom(session_id: &str, text: &str) -> Option<SpeechEvent> {
     })
 }
 
+// ── Custom provider message mapping ──────────────────────────────────────────
+
+/// User-supplied mapping for self-hosted STT servers that speak their own JSON.
+/// Paths are JSON pointers (`/result/text`) or dotted paths (`result.text`).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomMessageMapping {
+    /// Path to the transcript text (required).
+    text: String,
+    is_final: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    speaker: Option<String>,
+    /// Unit of `start`/`end`: `"ms"` (default) or `"s"`.
+    time_unit: Option<String>,
+    /// JSON message sent right after the WebSocket connects (e.g. a config frame).
+    init_message: Option<Value>,
+}
+
+impl CustomMessageMapping {
+    fn validate(&self) -> Result<(), String> {
+        if self.text.trim().is_empty() {
+            return Err("Custom message mapping requires a `text` path".to_string());
+        }
+        match self.time_unit.as_deref() {
+            None | Some("ms") | Some("s") => Ok(()),
+            Some(other) => Err(format!("Unsupported mapping time unit: {}", other)),
+        }
+    }
+
+    /// Text frame for `init_message`; strings are sent verbatim, objects serialized.
+    fn init_frame(&self) -> Option<String> {
+        match self.init_message.as_ref()? {
+            Value::Null => None,
+            Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+fn mapping_pointer(path: &str) -> String {
+    let path = path.trim();
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path.replace('.', "/"))
+    }
+}
+
+fn mapped_field<'a>(value: &'a Value, field: &str, path: &str) -> Result<&'a Value, String> {
+    value
+        .pointer(&mapping_pointer(path))
+        .ok_or_else(|| format!("Custom mapping: field `{}` ({}) not found in server message", field, path))
+}
+
+fn json_truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|f| f != 0.0).unwrap_or(false),
+        Value::String(s) => matches!(s.trim().to_ascii_lowercase().as_str(), "true" | "1" | "final"),
+        _ => false,
+    }
+}
+
+/// Parse a server message through a user-supplied mapping.
+/// `Err` names the mapped field that is missing so the caller can report it once.
+fn parse_mapped(
+    session_id: &str,
+    text: &str,
+    mapping: &CustomMessageMapping,
+) -> Result<Option<SpeechEvent>, String> {
+    let value: Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(_) => return Ok(None),
+    };
+    if let Some(err) = parse_generic_custom_error(text) {
+        return Ok(Some(SpeechEvent::Error {
+            session_id: session_id.to_string(),
+            error: err,
+        }));
+    }
+
+    let transcript = mapped_field(&value, "text", &mapping.text)?
+        .as_str()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if transcript.is_empty() {
+        return Ok(None);
+    }
+
+    let is_final = match mapping.is_final.as_deref() {
+        Some(path) => json_truthy(mapped_field(&value, "isFinal", path)?),
+        None => true,
+    };
+    let scale = if mapping.time_unit.as_deref() == Some("s") { 1000.0 } else { 1.0 };
+    let start_ms = match mapping.start.as_deref() {
+        Some(path) => (parse_json_number(Some(mapped_field(&value, "start", path)?)).unwrap_or(0.0) * scale) as u64,
+        None => 0,
+    };
+    let end_ms = match mapping.end.as_deref() {
+        Some(path) => (parse_json_number(Some(mapped_field(&value, "end", path)?)).unwrap_or(0.0) * scale) as u64,
+        None => 0,
+    };
+    let speaker_id = match mapping.speaker.as_deref() {
+        Some(path) => parse_gladia_speaker(Some(mapped_field(&value, "speaker", path)?)),
+        None => "SPEAKER_0".to_string(),
+    };
+
+    Ok(Some(SpeechEvent::Transcript {
+        session_id: session_id.to_string(),
+        segment: SpeechSegmentData {
+            speaker_id,
+            text: transcript,
+            start_ms,
+            end_ms,
+            confidence: 0.0,
+            is_final,
+            speech_final: is_final,
+        },
+    }))
+}
+
 // ── WebSocket request builders ────────────────────────────────────────────────
 
 type WsRequest = tokio_tungstenite::tungstenite::http::Request<()>;
 
-fn deepgram_request(base_url: &str, api_key: &str, model: &str, language: &str) -> Result<WsRequest, String> {
+/// Query parameters the frontend may override per provider. Audio format,
+/// model/language and auth stay under our control and cannot be overridden.
+fn allowed_query_params(provider: &str) -> &'static [&'static str] {
+    match provider {
+        "deepgram" => &[
+            "diarize",
+            "endpointing",
+            "interim_results",
+            "utterance_end_ms",
+            "vad_events",
+            "punctuate",
+            "smart_format",
+            "filler_words",
+            "numerals",
+            "profanity_filter",
+            "keywords",
+            "keyterm",
+        ],
+        "assemblyai" => &[
+            "speaker_labels",
+            "format_turns",
+            "end_of_turn_confidence_threshold",
+            "min_end_of_turn_silence_when_confident",
+            "max_turn_silence",
+            "keyterms_prompt",
+        ],
+        _ => &[],
+    }
+}
+
+/// Default query parameters per provider (today's behaviour when no override is given).
+fn default_query_params(provider: &str) -> &'static [(&'static str, &'static str)] {
+    match provider {
+        "deepgram" => &[("diarize", "true"), ("interim_results", "true"), ("endpointing", "500")],
+        "assemblyai" => &[("speaker_labels", "true")],
+        _ => &[],
+    }
+}
+
+fn is_safe_query_value(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 256
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | ',' | ' ' | '+'))
+}
+
+/// Merge user overrides into the provider defaults, rejecting keys outside the
+/// allow-list and values containing URL control characters.
+fn merge_query_params(
+    provider: &str,
+    overrides: Option<&HashMap<String, String>>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut merged: Vec<(String, String)> = default_query_params(provider)
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let Some(overrides) = overrides else {
+        return Ok(merged);
+    };
+
+    let allowed = allowed_query_params(provider);
+    let mut keys: Vec<&String> = overrides.keys().collect();
+    keys.sort();
+    for key in keys {
+        let value = overrides[key].trim();
+        if !allowed.contains(&key.as_str()) {
+            return Err(format!("Unsupported parameter for {}: {}", provider, key));
+        }
+        if !is_safe_query_value(value) {
+            return Err(format!("Invalid value for parameter {}", key));
+        }
+        match merged.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => merged.push((key.clone(), value.to_string())),
+        }
+    }
+    Ok(merged)
+}
+
+fn deepgram_request(
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    language: &str,
+    params: &[(String, String)],
+) -> Result<WsRequest, String> {
     use tokio_tungstenite::tungstenite::client::IntoClientRequest;
     let host = if base_url.is_empty() { "wss://api.deepgram.com" } else { base_url.trim_end_matches('/') };
-    let url = format!(
-        "{}/v1/listen?model={}&language={}&diarize=true&encoding=linear16&sample_rate=16000&interim_results=true&endpointing=500",
-        host, model, language
-    );
+    let mut url = reqwest::Url::parse(&format!("{}/v1/listen", host)).map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("model", model)
+        .append_pair("language", language)
+        .append_pair("encoding", "linear16")
+        .append_pair("sample_rate", "16000")
+        .extend_pairs(params);
     let mut req = url.as_str().into_client_request().map_err(|e| e.to_string())?;
     req.headers_mut().insert(
         "Authorization",
@@ -1319,15 +1849,23 @@ fn normalize_gladia_http_base(base_url: &str) -> String {
     }
 }
 
+/// Build the Gladia WebSocket request. Returns `(request, legacy_mode)`.
+///
+/// v2 (`/v2/live`) is the default: an HTTPS POST creates the session with the
+/// full audio/language configuration and returns the WebSocket URL.
+/// Grandfathered v1 keys keep working by pointing `base_url` at the legacy
+/// `/audio/text/audio-transcription` endpoint, unless `force_v2` is set
+/// (provider `"gladia-v2"`).
 async fn gladia_request(
     base_url: &str,
     api_key: &str,
     model: &str,
     language: &str,
+    force_v2: bool,
 ) -> Result<(WsRequest, bool), String> {
     // Explicit legacy endpoint support: users can still point to v1 WS URLs.
     let trimmed = base_url.trim().trim_end_matches('/');
-    if !trimmed.is_empty() && trimmed.contains("/audio/text/audio-transcription") {
+    if !force_v2 && !trimmed.is_empty() && trimmed.contains("/audio/text/audio-transcription") {
         let req = build_gladia_ws_request(trimmed, Some(api_key))?;
         return Ok((req, true));
     }
@@ -1359,7 +1897,7 @@ async fn gladia_request(
         payload["model"] = serde_json::json!(model.trim());
     }
 
-    let client = reqwest::Client::builder()
+    let client = super::settings::client_builder()
         .timeout(Duration::from_secs(20))
         .build()
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
@@ -1413,7 +1951,13 @@ fn gladia_legacy_model(model: &str) -> &str {
     }
 }
 
-fn assemblyai_request(base_url: &str, api_key: &str, model: &str, language: &str) -> Result<WsRequest, String> {
+fn assemblyai_request(
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    language: &str,
+    params: &[(String, String)],
+) -> Result<WsRequest, String> {
     use tokio_tungstenite::tungstenite::client::IntoClientRequest;
     // Universal Streaming v3 endpoint (old /v2/realtime/ws is deprecated)
     let host = if base_url.is_empty() {
@@ -1425,7 +1969,11 @@ fn assemblyai_request(base_url: &str, api_key: &str, model: &str, language: &str
     // The model tier is determined by the account plan, not a URL parameter.
     // Language is auto-detected by the multilingual model.
     let _ = (model, language); // intentionally unused for v3
-    let url = format!("{}/v3/ws?sample_rate=16000&encoding=pcm_s16le", host);
+    let mut url = reqwest::Url::parse(&format!("{}/v3/ws", host)).map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("sample_rate", "16000")
+        .append_pair("encoding", "pcm_s16le")
+        .extend_pairs(params);
     let mut req = url.as_str().into_client_request().map_err(|e| e.to_string())?;
     req.headers_mut().insert(
         "Authorization",
@@ -1504,6 +2052,9 @@ fn dispatch_message(provider: &str, session_id: &str, text: &str) -> Option<Spee
 ///
 /// `config_id` is used to look up the API key from the OS Keychain (via AIProxyState).
 /// Non-sensitive config (provider, base_url, language, model, region) are passed directly.
+/// `params` overrides provider query parameters (allow-listed per provider,
+/// see `allowed_query_params`); unknown keys are rejected.
+/// `mapping` (custom provider only) describes how to read the server's JSON.
 #[tauri::command]
 pub async fn speech_proxy_start(
     state: tauri::State<'_, SpeechProxyState>,
@@ -1516,7 +2067,25 @@ pub async fn speech_proxy_start(
     model: String,
     region: Option<String>,
     source_mode: Option<String>,
+    params: Option<HashMap<String, String>>,
+    record_path: Option<String>,
+    mapping: Option<CustomMessageMapping>,
 ) -> Result<String, String> {
+    // "gladia-v2" pins the v2 live API; afterwards it behaves exactly like "gladia".
+    let force_gladia_v2 = provider == "gladia-v2";
+    let provider = if force_gladia_v2 { "gladia".to_string() } else { provider };
+    let query_params = merge_query_params(&provider, params.as_ref())?;
+    if let Some(m) = mapping.as_ref() {
+        if provider != "custom" {
+            return Err("Message mapping is only supported for the custom provider".to_string());
+        }
+        m.validate()?;
+    }
+    let record_path = record_path
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| super::file::validate_path(&p))
+        .transpose()?;
+
     // Resolve API key from OS Keychain cache
     key_state.ensure_secrets_loaded().await;
     let api_key = {
@@ -1530,9 +2099,20 @@ pub async fn speech_proxy_start(
             .unwrap_or_default()
     };
 
+    // Fail fast before connecting; re-checked when the session is registered.
+    let max_sessions = state.max_sessions.load(Ordering::SeqCst);
+    {
+        let sessions = state
+            .sessions
+            .lock()
+            .map_err(|_| "State lock poisoned".to_string())?;
+        SpeechProxyState::ensure_capacity(&sessions, max_sessions)?;
+    }
+
     let session_id = new_session_id();
     let region = region.unwrap_or_default();
-    let custom_ws_mode = if provider == "custom" {
+    // A user-supplied mapping means a user-defined protocol: skip auto-detection.
+    let custom_ws_mode = if provider == "custom" && mapping.is_none() {
         detect_custom_ws_mode(&base_url, &model)
     } else {
         CustomWsMode::RawBinary
@@ -1558,13 +2138,14 @@ pub async fn speech_proxy_start(
     // Build provider-specific WebSocket request (with auth headers)
     let mut gladia_legacy_mode = false;
     let ws_request = match provider.as_str() {
-        "deepgram" => deepgram_request(&base_url, &api_key, &model, &language),
+        "deepgram" => deepgram_request(&base_url, &api_key, &model, &language, &query_params),
         "gladia" => {
-            let (req, legacy) = gladia_request(&base_url, &api_key, &model, &language).await?;
+            let (req, legacy) =
+                gladia_request(&base_url, &api_key, &model, &language, force_gladia_v2).await?;
             gladia_legacy_mode = legacy;
             Ok(req)
         }
-        "assemblyai" => assemblyai_request(&base_url, &api_key, &model, &language),
+        "assemblyai" => assemblyai_request(&base_url, &api_key, &model, &language, &query_params),
         "azure-speech" => azure_speech_request(&base_url, &api_key, &language, &region),
         "aws-transcribe" => {
             // AWS Transcribe requires SigV4 pre-signed URL; not yet implemented
@@ -1581,6 +2162,11 @@ pub async fn speech_proxy_start(
 
     let (mut ws_write, mut ws_read) = ws_stream.split();
 
+    let mut recorder = match record_path {
+        Some(path) => Some(WavRecorder::create(path)?),
+        None => None,
+    };
+
     // For legacy Gladia v1: send config JSON immediately after handshake.
     // Gladia v2 receives config during HTTPS /v2/live session creation.
     if provider == "gladia" && gladia_legacy_mode {
@@ -1605,6 +2191,13 @@ pub async fn speech_proxy_start(
             .map_err(|e| format!("Gladia config send failed: {}", e))?;
     }
 
+    if let Some(frame) = mapping.as_ref().and_then(|m| m.init_frame()) {
+        ws_write
+            .send(Message::Text(frame))
+            .await
+            .map_err(|e| format!("Custom init message send failed: {}", e))?;
+    }
+
     if provider == "custom" {
         match custom_ws_mode {
             CustomWsMode::DashScopeFunAsr => {
@@ -1635,7 +2228,7 @@ pub async fn speech_proxy_start(
     // Audio chunk channel (frontend → writer task → WebSocket)
     let (audio_tx, mut audio_rx) = mpsc::channel::<Vec<u8>>(64);
     // Stop signal (frontend → writer)
-    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    let (stop_tx, stop_rx) = oneshot::channel::<StopRequest>();
     // Reader-done signal (reader → writer): tells writer to stop when server closes
     let (reader_done_tx, reader_done_rx) = oneshot::channel::<()>();
     let normalized_source_mode = source_mode
@@ -1662,17 +2255,26 @@ pub async fn speech_proxy_start(
     };
 
     // Register session
+    let stats = Arc::new(SessionStats::new());
     {
         let mut sessions = state
             .sessions
             .lock()
             .map_err(|_| "State lock poisoned".to_string())?;
+        if let Err(e) = SpeechProxyState::ensure_capacity(&sessions, max_sessions) {
+            if let Some(mut capture) = native_system_capture {
+                capture.stop();
+            }
+            return Err(e);
+        }
         sessions.insert(
             session_id.clone(),
             SpeechSession {
+                provider: provider.clone(),
                 audio_tx: frontend_audio_tx,
                 stop_tx: Some(stop_tx),
                 native_system_capture,
+                stats: stats.clone(),
             },
         );
     }
@@ -1691,89 +2293,115 @@ pub async fn speech_proxy_start(
         });
     }
 
-    let dashscope_finish_msg = custom_dashscope_task_id
+    let dashscope_finish_msg_w = custom_dashscope_task_id
         .as_ref()
         .map(|task_id| build_dashscope_finish_task(task_id));
-    let dashscope_finish_msg_w = dashscope_finish_msg.clone();
     let custom_ws_mode_w = custom_ws_mode;
     let custom_ws_mode_r = custom_ws_mode;
-    let custom_iflytek_app_id_w = custom_iflytek_app_id.clone();
+    let custom_iflytek_app_id_w = custom_iflytek_app_id.unwrap_or_default();
     let language_w = language.clone();
+    let provider_w = provider.clone();
+    let sid_w = session_id.clone();
+    let on_event_w = on_event.clone();
+    let sessions_w = state.sessions.clone();
+
+    // Set once we start closing so the reader treats the server's Close reply
+    // as expected rather than surfacing it as an error.
+    let closing = Arc::new(AtomicBool::new(false));
+    let closing_w = closing.clone();
+    // True while the last transcript emitted was interim (reader → writer, used by flush).
+    let (interim_tx, mut interim_rx) = watch::channel(false);
 
     // ── Writer task: audio chunks → WebSocket Binary frames ──────────────────
     tokio::spawn(async move {
         let mut stop_rx = stop_rx;
         let mut reader_done_rx = reader_done_rx;
+        let mut reader_finished = false;
         let mut iflytek_first_frame_sent = false;
+        let mut done_tx: Option<oneshot::Sender<()>> = None;
+        let mut last_stats_at = std::time::Instant::now();
+        let custom_end_frame =
+            build_custom_end_frame(custom_ws_mode_w, dashscope_finish_msg_w.as_deref());
         loop {
             tokio::select! {
                 biased;
-                _ = &mut stop_rx => {
-                    match custom_ws_mode_w {
-                        CustomWsMode::DashScopeFunAsr => {
-                            if let Some(msg) = dashscope_finish_msg_w.as_ref() {
-                                let _ = ws_write.send(Message::Text(msg.clone())).await;
+                req = &mut stop_rx => {
+                    let req = req.unwrap_or_default();
+                    closing_w.store(true, Ordering::SeqCst);
+                    done_tx = req.done_tx;
+                    if req.flush {
+                        // Forward audio that is still queued so the provider hears
+                        // the tail of the last sentence before the flush.
+                        while let Ok(data) = audio_rx.try_recv() {
+                            record_chunk(&mut recorder, &data, &on_event_w, &sid_w);
+                            let msg = build_audio_message(
+                                custom_ws_mode_w,
+                                &custom_iflytek_app_id_w,
+                                &language_w,
+                                data,
+                                &mut iflytek_first_frame_sent,
+                            );
+                            if ws_write.send(msg).await.is_err() {
+                                break;
                             }
                         }
-                        CustomWsMode::TencentAsrV2 => {
-                            let _ = ws_write.send(Message::Text(build_tencent_end_signal())).await;
-                        }
-                        CustomWsMode::IflytekIatV2 => {
-                            let _ = ws_write.send(Message::Text(build_iflytek_end_frame())).await;
+                        let flush_frame = build_flush_frame(&provider_w, gladia_legacy_mode)
+                            .or_else(|| custom_end_frame.clone());
+                        if let Some(frame) = flush_frame {
+                            if ws_write.send(Message::Text(frame)).await.is_ok() {
+                                let _ = tokio::time::timeout(
+                                    Duration::from_millis(SPEECH_FLUSH_TIMEOUT_MS),
+                                    interim_rx.wait_for(|pending| !*pending),
+                                )
+                                .await;
+                            }
                         }
-                        CustomWsMode::OpenAiRealtime | CustomWsMode::VolcengineRealtime => {
-                            let _ = ws_write.send(Message::Text(build_realtime_audio_commit())).await;
+                        if provider_w == "assemblyai" {
+                            let _ = ws_write.send(Message::Text(build_assemblyai_terminate())).await;
                         }
-                        CustomWsMode::RawBinary => {}
+                    } else if let Some(frame) = custom_end_frame.clone() {
+                        let _ = ws_write.send(Message::Text(frame)).await;
                     }
                     let _ = ws_write.send(Message::Close(None)).await;
                     break;
                 }
                 // Server closed connection — stop writing silently (reader already reported error)
-                _ = &mut reader_done_rx => { break; }
+                _ = &mut reader_done_rx => {
+                    reader_finished = true;
+                    break;
+                }
                 chunk = audio_rx.recv() => {
                     match chunk {
                         Some(data) => {
-                            let send_result = match custom_ws_mode_w {
-                                CustomWsMode::IflytekIatV2 => {
-                                    let app_id = custom_iflytek_app_id_w.as_deref().unwrap_or_default();
-                                    let frame = if !iflytek_first_frame_sent {
-                                        iflytek_first_frame_sent = true;
-                                        build_iflytek_audio_frame(app_id, &language_w, &data, 0)
-                                    } else {
-                                        build_iflytek_audio_frame(app_id, &language_w, &data, 1)
-                                    };
-                                    ws_write.send(Message::Text(frame)).await
-                                }
-                                CustomWsMode::OpenAiRealtime | CustomWsMode::VolcengineRealtime => {
-                                    ws_write
-                                        .send(Message::Text(build_realtime_audio_append(&data)))
-                                        .await
-                                }
-                                _ => ws_write.send(Message::Binary(data)).await,
-                            };
-                            if send_result.is_err() {
+                            record_chunk(&mut recorder, &data, &on_event_w, &sid_w);
+                            let len = data.len();
+                            let level = pcm16_rms(&data);
+                            let msg = build_audio_message(
+                                custom_ws_mode_w,
+                                &custom_iflytek_app_id_w,
+                                &language_w,
+                                data,
+                                &mut iflytek_first_frame_sent,
+                            );
+                            if ws_write.send(msg).await.is_err() {
                                 break;
                             }
+                            stats.record(len, level);
+                            if last_stats_at.elapsed() >= Duration::from_millis(SPEECH_STATS_INTERVAL_MS) {
+                                last_stats_at = std::time::Instant::now();
+                                let _ = on_event_w.send(SpeechEvent::Stats {
+                                    session_id: sid_w.clone(),
+                                    elapsed_ms: stats.elapsed_ms(),
+                                    bytes_sent: stats.bytes_sent(),
+                                    level: stats.level(),
+                                });
+                            }
                         }
                         None => {
                             // Channel closed — send graceful close
-                            match custom_ws_mode_w {
-                                CustomWsMode::DashScopeFunAsr => {
-                                    if let Some(msg) = dashscope_finish_msg_w.as_ref() {
-                                        let _ = ws_write.send(Message::Text(msg.clone())).await;
-                                    }
-                                }
-                                CustomWsMode::TencentAsrV2 => {
-                                    let _ = ws_write.send(Message::Text(build_tencent_end_signal())).await;
-                                }
-                                CustomWsMode::IflytekIatV2 => {
-                                    let _ = ws_write.send(Message::Text(build_iflytek_end_frame())).await;
-                                }
-                                CustomWsMode::OpenAiRealtime | CustomWsMode::VolcengineRealtime => {
-                                    let _ = ws_write.send(Message::Text(build_realtime_audio_commit())).await;
-                                }
-                                CustomWsMode::RawBinary => {}
+                            closing_w.store(true, Ordering::SeqCst);
+                            if let Some(frame) = custom_end_frame.clone() {
+                                let _ = ws_write.send(Message::Text(frame)).await;
                             }
                             let _ = ws_write.send(Message::Close(None)).await;
                             break;
@@ -1782,6 +2410,39 @@ pub async fn speech_proxy_start(
                 }
             }
         }
+
+        // Let the reader drain the server's Close reply (and any final results)
+        // before reporting Disconnected.
+        if !reader_finished {
+            let _ = tokio::time::timeout(
+                Duration::from_millis(SPEECH_CLOSE_TIMEOUT_MS),
+                &mut reader_done_rx,
+            )
+            .await;
+        }
+        let record_path = recorder.and_then(|r| match r.finish() {
+            Ok(path) => Some(path),
+            Err(error) => {
+                let _ = on_event_w.send(SpeechEvent::Error {
+                    session_id: sid_w.clone(),
+                    error,
+                });
+                None
+            }
+        });
+        // Drop the registry entry if stop/finalize didn't already, so sessions
+        // that died on a server error don't leak (and stop counting toward the cap).
+        let leaked = sessions_w.lock().ok().and_then(|mut m| m.remove(&sid_w));
+        if let Some(mut capture) = leaked.and_then(|s| s.native_system_capture) {
+            capture.stop();
+        }
+        let _ = on_event_w.send(SpeechEvent::Disconnected {
+            session_id: sid_w,
+            record_path,
+        });
+        if let Some(tx) = done_tx {
+            let _ = tx.send(());
+        }
     });
 
     let sid_r = session_id.clone();
@@ -1794,12 +2455,14 @@ pub async fn speech_proxy_start(
         let mut last_server_text: Option<String> = None;
         // For AssemblyAI: becomes true once we receive the "Begin" handshake.
         let mut connected_emitted = !wait_for_begin;
+        // A broken custom mapping is reported once, not for every message.
+        let mut mapping_error_reported = false;
 
         while let Some(msg_result) = ws_read.next().await {
             match msg_result {
                 Ok(Message::Text(text)) => {
                     #[cfg(debug_assertions)]
-                    eprintln!("[speech_proxy:{}] ← {}", provider_r, text.chars().take(200).collect::<String>());
+                    log::debug!("[{}] ← {}", provider_r, text.chars().take(200).collect::<String>());
 
                     // Emit Connected only after server-ready handshake messages.
                     if !connected_emitted {
@@ -1871,8 +2534,31 @@ pub async fn speech_proxy_start(
                         continue;
                     }
 
-                    match dispatch_message(&provider_r, &sid_r, &text) {
-                        Some(event) => { let _ = on_event.send(event); }
+                    let parsed = match mapping.as_ref() {
+                        Some(m) => parse_mapped(&sid_r, &text, m).unwrap_or_else(|error| {
+                            if mapping_error_reported {
+                                None
+                            } else {
+                                mapping_error_reported = true;
+                                Some(SpeechEvent::Error {
+                                    session_id: sid_r.clone(),
+                                    error,
+                                })
+                            }
+                        }),
+                        None => dispatch_message(&provider_r, &sid_r, &text),
+                    };
+                    match parsed {
+                        Some(event) => {
+                            let interim_pending = match &event {
+                                SpeechEvent::Transcript { segment, .. } => Some(!segment.is_final),
+                                _ => None,
+                            };
+                            let _ = on_event.send(event);
+                            if let Some(pending) = interim_pending {
+                                interim_tx.send_replace(pending);
+                            }
+                        }
                         None => {
                             // Capture last unrecognized message (e.g. partial,
                             // or an error JSON we couldn't parse) for Close diagnostics.
@@ -1883,6 +2569,7 @@ pub async fn speech_proxy_start(
                 // Server closed the WebSocket — surface reason as an error so the
                 // user sees the actual rejection message (e.g. "invalid token")
                 // rather than the confusing downstream "Audio send failed".
+                Ok(Message::Close(_)) if closing.load(Ordering::SeqCst) => break,
                 Ok(Message::Close(frame)) => {
                     let reason = match &frame {
                         Some(f) if !f.reason.is_empty() => f.reason.to_string(),
@@ -1904,6 +2591,7 @@ pub async fn speech_proxy_start(
                     });
                     break;
                 }
+                Err(_) if closing.load(Ordering::SeqCst) => break,
                 Err(e) => {
                     let _ = on_event.send(SpeechEvent::Error {
                         session_id: sid_r.clone(),
@@ -1914,7 +2602,7 @@ pub async fn speech_proxy_start(
                 Ok(Message::Binary(_data)) => {
                     // AssemblyAI should not send binary frames — log if this happens
                     #[cfg(debug_assertions)]
-                    eprintln!("[speech_proxy:{}] ← binary frame {} bytes", provider_r, _data.len());
+                    log::debug!("[{}] ← binary frame {} bytes", provider_r, _data.len());
                 }
                 _ => {}
             }
@@ -1926,28 +2614,63 @@ pub async fn speech_proxy_start(
     Ok(session_id)
 }
 
-/// Send a raw PCM audio chunk (base64-encoded) to an active transcription session.
-/// Base64 is used instead of a JSON number array because WKWebView deserialises
-/// a single JSON string token orders of magnitude faster than N number tokens,
-/// preventing the IPC flooding that freezes the UI.
-#[tauri::command]
-pub async fn speech_proxy_send_audio(
-    state: tauri::State<'_, SpeechProxyState>,
-    session_id: String,
-    audio_b64: String,
-) -> Result<(), String> {
-    use base64::{engine::general_purpose::STANDARD, Engine};
-    let audio_bytes = STANDARD
-        .decode(audio_b64.as_bytes())
-        .map_err(|_| "Invalid base64 audio data".to_string())?;
+// ── IPC throughput counters (debug builds) ────────────────────────────────────
+
+#[cfg(debug_assertions)]
+static IPC_AUDIO_B64_BYTES: AtomicU64 = AtomicU64::new(0);
+#[cfg(debug_assertions)]
+static IPC_AUDIO_RAW_BYTES: AtomicU64 = AtomicU64::new(0);
+#[cfg(debug_assertions)]
+static IPC_AUDIO_WINDOW_START_MS: AtomicU64 = AtomicU64::new(0);
+#[cfg(debug_assertions)]
+const IPC_AUDIO_REPORT_INTERVAL_MS: u64 = 5_000;
+
+/// Count audio bytes received over IPC and log bytes/sec per path every few
+/// seconds (debug builds only; compiled out in release).
+fn note_ipc_audio(len: usize, raw: bool) {
+    #[cfg(debug_assertions)]
+    {
+        let counter = if raw { &IPC_AUDIO_RAW_BYTES } else { &IPC_AUDIO_B64_BYTES };
+        counter.fetch_add(len as u64, Ordering::Relaxed);
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let start = IPC_AUDIO_WINDOW_START_MS.load(Ordering::Relaxed);
+        if start == 0 {
+            IPC_AUDIO_WINDOW_START_MS.store(now_ms, Ordering::Relaxed);
+        } else if now_ms.saturating_sub(start) >= IPC_AUDIO_REPORT_INTERVAL_MS
+            && IPC_AUDIO_WINDOW_START_MS
+                .compare_exchange(start, now_ms, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            let secs = (now_ms - start) as f64 / 1000.0;
+            let raw_bytes = IPC_AUDIO_RAW_BYTES.swap(0, Ordering::Relaxed);
+            let b64_bytes = IPC_AUDIO_B64_BYTES.swap(0, Ordering::Relaxed);
+            log::debug!(
+                "ipc audio: raw {:.0} B/s, base64 {:.0} B/s (decoded)",
+                raw_bytes as f64 / secs,
+                b64_bytes as f64 / secs
+            );
+        }
+    }
+    #[cfg(not(debug_assertions))]
+    let _ = (len, raw);
+}
 
+/// Route a PCM chunk into the session's writer channel (shared by both IPC paths).
+async fn forward_audio(
+    state: &SpeechProxyState,
+    session_id: &str,
+    audio_bytes: Vec<u8>,
+) -> Result<(), String> {
     let tx = {
         let sessions = state
             .sessions
             .lock()
             .map_err(|_| "State lock poisoned".to_string())?;
         sessions
-            .get(&session_id)
+            .get(session_id)
             .map(|s| s.audio_tx.clone())
             .ok_or_else(|| "Session not found".to_string())?
     };
@@ -1957,18 +2680,62 @@ pub async fn speech_proxy_send_audio(
         .map_err(|_| "Session audio channel closed".to_string())
 }
 
-/// Stop a transcription session and close the WebSocket connection.
+/// Send a raw PCM audio chunk (base64-encoded) to an active transcription session.
+/// Base64 is used instead of a JSON number array because WKWebView deserialises
+/// a single JSON string token orders of magnitude faster than N number tokens,
+/// preventing the IPC flooding that freezes the UI.
+/// Kept for compatibility; prefer `speech_proxy_send_audio_raw`.
 #[tauri::command]
-pub async fn speech_proxy_stop(
+pub async fn speech_proxy_send_audio(
     state: tauri::State<'_, SpeechProxyState>,
     session_id: String,
+    audio_b64: String,
+) -> Result<(), String> {
+    let audio_bytes = BASE64_STANDARD
+        .decode(audio_b64.as_bytes())
+        .map_err(|_| "Invalid base64 audio data".to_string())?;
+    note_ipc_audio(audio_bytes.len(), false);
+    forward_audio(&state, &session_id, audio_bytes).await
+}
+
+/// Send a PCM audio chunk via the IPC raw-body path.
+///
+/// Frontend calls `invoke('speech_proxy_send_audio_raw', uint8Array, { headers: { 'X-Session-Id': id } })`.
+/// The body arrives as `InvokeBody::Raw(Vec<u8>)`: no base64 encode in the
+/// worklet and no decode here — the only cost is a single memcpy into the
+/// channel buffer (the request body is borrowed, so it cannot be moved out).
+#[tauri::command]
+pub async fn speech_proxy_send_audio_raw(
+    state: tauri::State<'_, SpeechProxyState>,
+    request: tauri::ipc::Request<'_>,
 ) -> Result<(), String> {
+    let session_id = request
+        .headers()
+        .get("X-Session-Id")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| "Missing X-Session-Id header".to_string())?
+        .to_string();
+
+    let audio_bytes = match request.body() {
+        tauri::ipc::InvokeBody::Raw(b) => b.clone(),
+        _ => return Err("Expected raw bytes body".to_string()),
+    };
+    note_ipc_audio(audio_bytes.len(), true);
+    forward_audio(&state, &session_id, audio_bytes).await
+}
+
+/// Remove a session from the registry and stop its native capture (if any).
+/// Returns the writer's stop sender so the caller can choose how to close.
+fn take_session(
+    state: &SpeechProxyState,
+    session_id: &str,
+) -> Result<Option<oneshot::Sender<StopRequest>>, String> {
     let (stop_tx, mut native_system_capture) = {
         let mut sessions = state
             .sessions
             .lock()
             .map_err(|_| "State lock poisoned".to_string())?;
-        match sessions.remove(&session_id) {
+        match sessions.remove(session_id) {
             Some(mut s) => (s.stop_tx.take(), s.native_system_capture.take()),
             None => (None, None),
         }
@@ -1978,13 +2745,335 @@ pub async fn speech_proxy_stop(
         capture.stop();
     }
 
-    if let Some(tx) = stop_tx {
-        let _ = tx.send(());
+    Ok(stop_tx)
+}
+
+/// Current statistics for an active session (same numbers as the `Stats` event).
+#[tauri::command]
+pub fn speech_proxy_get_session(
+    state: tauri::State<'_, SpeechProxyState>,
+    session_id: String,
+) -> Result<SpeechSessionStats, String> {
+    let sessions = state
+        .sessions
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    sessions
+        .get(&session_id)
+        .map(|s| s.snapshot(&session_id))
+        .ok_or_else(|| "Session not found".to_string())
+}
+
+/// All active transcription sessions, so the UI can reconcile after a reload.
+#[tauri::command]
+pub fn speech_proxy_list_sessions(
+    state: tauri::State<'_, SpeechProxyState>,
+) -> Result<Vec<SpeechSessionStats>, String> {
+    let sessions = state
+        .sessions
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    let mut list: Vec<SpeechSessionStats> =
+        sessions.iter().map(|(id, s)| s.snapshot(id)).collect();
+    list.sort_by(|a, b| b.elapsed_ms.cmp(&a.elapsed_ms));
+    Ok(list)
+}
+
+/// Change the cap on concurrent transcription sessions (minimum 1).
+/// Running sessions are never stopped by lowering the cap.
+#[tauri::command]
+pub fn speech_proxy_set_max_sessions(
+    state: tauri::State<'_, SpeechProxyState>,
+    max: usize,
+) -> Result<(), String> {
+    state.max_sessions.store(max.max(1), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Stop a transcription session and close the WebSocket connection.
+#[tauri::command]
+pub async fn speech_proxy_stop(
+    state: tauri::State<'_, SpeechProxyState>,
+    session_id: String,
+) -> Result<(), String> {
+    if let Some(tx) = take_session(&state, &session_id)? {
+        let _ = tx.send(StopRequest::default());
+    }
+
+    Ok(())
+}
+
+/// Flush pending interim results, then stop the session.
+///
+/// Sends the provider's flush message (Deepgram `Finalize`, AssemblyAI
+/// `ForceEndpoint`, Gladia v2 `stop_recording`, or the custom protocol's
+/// end-of-audio frame), waits up to `SPEECH_FLUSH_TIMEOUT_MS` for the
+/// resulting final transcripts, then closes like `speech_proxy_stop`.
+/// Resolves once the session has emitted `Disconnected`.
+#[tauri::command]
+pub async fn speech_proxy_finalize(
+    state: tauri::State<'_, SpeechProxyState>,
+    session_id: String,
+) -> Result<(), String> {
+    let Some(tx) = take_session(&state, &session_id)? else {
+        return Ok(());
+    };
+
+    let (done_tx, done_rx) = oneshot::channel::<()>();
+    let request = StopRequest {
+        flush: true,
+        done_tx: Some(done_tx),
+    };
+    if tx.send(request).is_err() {
+        // Writer already exited (server closed) and has emitted Disconnected.
+        return Ok(());
     }
 
+    let _ = tokio::time::timeout(
+        Duration::from_millis(SPEECH_FLUSH_TIMEOUT_MS + SPEECH_CLOSE_TIMEOUT_MS + 500),
+        done_rx,
+    )
+    .await;
     Ok(())
 }
 
+// ── Credential check ──────────────────────────────────────────────────────────
+
+const VALIDATE_TIMEOUT_SECS: u64 = 10;
+
+/// Why a credential check failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpeechCheckFailure {
+    MissingKey,
+    /// 401/403: the key is wrong, revoked or lacks access.
+    Unauthorized,
+    /// Out of credits or over the plan's limits.
+    QuotaExceeded,
+    Network,
+    Timeout,
+    /// Any other non-2xx answer.
+    HttpError,
+    /// No REST endpoint to check against (custom WebSocket, AWS).
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeechValidation {
+    pub ok: bool,
+    pub message: String,
+    pub failure: Option<SpeechCheckFailure>,
+    /// Round trip of the check request; 0 when none was sent.
+    pub latency_ms: u64,
+}
+
+/// A cheap authenticated request that only succeeds with a valid key.
+#[derive(Debug, PartialEq)]
+struct CheckRequest {
+    post: bool,
+    url: String,
+    auth_header: &'static str,
+    auth_value: String,
+}
+
+/// `wss://host/path` → `https://host`, or `default` when `base_url` is empty.
+fn rest_origin(base_url: &str, default: &str) -> Result<String, String> {
+    let raw = base_url.trim();
+    if raw.is_empty() {
+        return Ok(default.to_string());
+    }
+    let mut url = reqwest::Url::parse(raw).map_err(|e| format!("Invalid base URL: {}", e))?;
+    let scheme = match url.scheme() {
+        "wss" => "https",
+        "ws" => "http",
+        other => other,
+    }
+    .to_string();
+    url.set_scheme(&scheme)
+        .map_err(|_| "Invalid base URL scheme".to_string())?;
+    Ok(url.origin().ascii_serialization())
+}
+
+fn check_request(
+    provider: &str,
+    base_url: &str,
+    region: &str,
+    api_key: &str,
+) -> Result<Option<CheckRequest>, String> {
+    let request = match provider {
+        "deepgram" => CheckRequest {
+            post: false,
+            url: format!(
+                "{}/v1/projects",
+                rest_origin(base_url, "https://api.deepgram.com")?
+            ),
+            auth_header: "Authorization",
+            auth_value: format!("Token {}", api_key),
+        },
+        "assemblyai" => {
+            // Streaming lives on its own host; the REST API is on api.*
+            let origin = rest_origin(base_url, "https://api.assemblyai.com")?
+                .replace("://streaming.assemblyai.com", "://api.assemblyai.com");
+            CheckRequest {
+                post: false,
+                url: format!("{}/v2/transcript?limit=1", origin),
+                auth_header: "Authorization",
+                auth_value: api_key.to_string(),
+            }
+        }
+        "gladia" | "gladia-v2" => CheckRequest {
+            post: false,
+            url: format!("{}/v2/live?limit=1", normalize_gladia_origin(base_url)?),
+            auth_header: "X-Gladia-Key",
+            auth_value: api_key.to_string(),
+        },
+        "azure-speech" => {
+            let region = if region.trim().is_empty() { "eastus" } else { region.trim() };
+            CheckRequest {
+                post: true,
+                url: format!(
+                    "https://{}.api.cognitive.microsoft.com/sts/v1.0/issueToken",
+                    region
+                ),
+                auth_header: "Ocp-Apim-Subscription-Key",
+                auth_value: api_key.to_string(),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(request))
+}
+
+fn normalize_gladia_origin(base_url: &str) -> Result<String, String> {
+    rest_origin(&normalize_gladia_http_base(base_url), "https://api.gladia.io")
+}
+
+/// Map a failed check's HTTP status to a failure kind and message.
+fn classify_check_status(status: u16, body: &str) -> (SpeechCheckFailure, String) {
+    let lower = body.to_ascii_lowercase();
+    let mentions_quota = ["quota", "credit", "balance", "insufficient", "exceeded"]
+        .iter()
+        .any(|w| lower.contains(w));
+    match status {
+        402 | 429 => (
+            SpeechCheckFailure::QuotaExceeded,
+            format!("Quota exhausted or rate limited ({})", status),
+        ),
+        401 | 403 if mentions_quota => (
+            SpeechCheckFailure::QuotaExceeded,
+            format!("Quota exhausted ({})", status),
+        ),
+        401 | 403 => (
+            SpeechCheckFailure::Unauthorized,
+            format!("API key rejected ({})", status),
+        ),
+        _ => {
+            let excerpt: String = body.chars().take(200).collect();
+            (
+                SpeechCheckFailure::HttpError,
+                format!("Unexpected response ({}): {}", status, excerpt.trim()),
+            )
+        }
+    }
+}
+
+fn failed_check(
+    failure: SpeechCheckFailure,
+    message: impl Into<String>,
+    latency_ms: u64,
+) -> SpeechValidation {
+    SpeechValidation {
+        ok: false,
+        message: message.into(),
+        failure: Some(failure),
+        latency_ms,
+    }
+}
+
+/// Check a speech provider's key with one cheap authenticated request
+/// (Deepgram projects, AssemblyAI transcript list, Gladia live sessions,
+/// Azure token issue), for the settings page's "Test connection" button.
+/// The key comes from the secrets cache, like `speech_proxy_start`.
+#[tauri::command]
+pub async fn speech_validate_config(
+    key_state: tauri::State<'_, super::ai_proxy::AIProxyState>,
+    config_id: String,
+    provider: String,
+    base_url: String,
+    region: Option<String>,
+) -> Result<SpeechValidation, String> {
+    key_state.ensure_secrets_loaded().await;
+    let api_key = {
+        let cache = key_state
+            .key_cache
+            .lock()
+            .map_err(|_| "Keychain lock poisoned".to_string())?;
+        cache
+            .get(&format!("speech-key:{}", config_id))
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let region = region.unwrap_or_default();
+    let Some(check) = check_request(&provider, &base_url, &region, &api_key)? else {
+        return Ok(failed_check(
+            SpeechCheckFailure::Unsupported,
+            "This provider can't be checked before connecting",
+            0,
+        ));
+    };
+    if api_key.is_empty() {
+        return Ok(failed_check(SpeechCheckFailure::MissingKey, "No API key saved", 0));
+    }
+
+    let client = super::settings::client_builder()
+        .timeout(Duration::from_secs(VALIDATE_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let request = if check.post {
+        client.post(&check.url).header("Content-Length", "0")
+    } else {
+        client.get(&check.url)
+    };
+    let started = std::time::Instant::now();
+    let response = request
+        .header(check.auth_header, &check.auth_value)
+        .send()
+        .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let response = match response {
+        Ok(r) => r,
+        Err(e) if e.is_timeout() => {
+            return Ok(failed_check(
+                SpeechCheckFailure::Timeout,
+                format!("No answer within {}s", VALIDATE_TIMEOUT_SECS),
+                latency_ms,
+            ))
+        }
+        Err(_) => {
+            return Ok(failed_check(
+                SpeechCheckFailure::Network,
+                "Could not reach the provider",
+                latency_ms,
+            ))
+        }
+    };
+    let status = response.status().as_u16();
+    if response.status().is_success() {
+        return Ok(SpeechValidation {
+            ok: true,
+            message: "Connected".to_string(),
+            failure: None,
+            latency_ms,
+        });
+    }
+    let body = response.text().await.unwrap_or_default();
+    let (failure, message) = classify_check_status(status, &body);
+    Ok(failed_check(failure, message, latency_ms))
+}
+
 // ── Realtime Dialogue (bidirectional voice AI) ────────────────────────────────
 
 /// Events emitted to the frontend during a realtime dialogue session.
@@ -2766,6 +3855,20 @@ mod tests {
         assert!(!seg.speech_final);
     }
 
+    #[test]
+    fn should_prefer_gladia_v2_diarized_speaker_over_channel() {
+        let json = r#"{
+            "type": "transcript",
+            "data": {
+                "is_final": true,
+                "utterance": { "text": "who speaks", "start": 0, "end": 1, "channel": 0, "speaker": 2 }
+            }
+        }"#;
+
+        let seg = parse_transcript(json);
+        assert_eq!(seg.speaker_id, "SPEAKER_2");
+    }
+
     #[test]
     fn should_parse_gladia_v2_error_event() {
         let json = r#"{"type":"error","error":"invalid token"}"#;
@@ -3026,6 +4129,140 @@ mod tests {
         assert!(!segment.speech_final);
     }
 
+    #[test]
+    fn should_merge_query_param_overrides_with_defaults() {
+        let mut overrides = HashMap::new();
+        overrides.insert("diarize".to_string(), "false".to_string());
+        overrides.insert("smart_format".to_string(), "true".to_string());
+        let params = merge_query_params("deepgram", Some(&overrides)).unwrap();
+        assert!(params.contains(&("diarize".to_string(), "false".to_string())));
+        assert!(params.contains(&("endpointing".to_string(), "500".to_string())));
+        assert!(params.contains(&("smart_format".to_string(), "true".to_string())));
+    }
+
+    #[test]
+    fn should_reject_unknown_or_unsafe_query_params() {
+        let mut overrides = HashMap::new();
+        overrides.insert("sample_rate".to_string(), "8000".to_string());
+        assert!(merge_query_params("deepgram", Some(&overrides)).is_err());
+
+        let mut overrides = HashMap::new();
+        overrides.insert("endpointing".to_string(), "10&token=x".to_string());
+        assert!(merge_query_params("deepgram", Some(&overrides)).is_err());
+
+        let mut overrides = HashMap::new();
+        overrides.insert("diarize".to_string(), "true".to_string());
+        assert!(merge_query_params("gladia", Some(&overrides)).is_err());
+        assert!(merge_query_params("gladia", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_map_assemblyai_speaker_labels() {
+        let json = r#"{"type":"Turn","transcript":"hi there","end_of_turn":true,"speaker_label":"B"}"#;
+        match parse_assemblyai("test-session", json) {
+            Some(SpeechEvent::Transcript { segment, .. }) => assert_eq!(segment.speaker_id, "SPEAKER_1"),
+            _ => panic!("expected transcript event"),
+        }
+        assert_eq!(assemblyai_speaker_id("UNKNOWN"), "SPEAKER_0");
+        assert_eq!(assemblyai_speaker_id("A"), "SPEAKER_0");
+    }
+
+    #[test]
+    fn should_write_and_patch_wav_recording_header() {
+        let path = std::env::temp_dir().join(format!("moraya-rec-test-{}.wav", new_session_id()));
+        let mut rec = WavRecorder::create(path.clone()).unwrap();
+        rec.append(&[1, 0, 2, 0, 3, 0]).unwrap();
+        let out = rec.finish().unwrap();
+        let bytes = std::fs::read(&out).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(bytes.len(), 50);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 42);
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 16_000);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 6);
+    }
+
+    #[test]
+    fn should_compute_pcm16_rms_level() {
+        assert_eq!(pcm16_rms(&[]), 0.0);
+        assert_eq!(pcm16_rms(&[0, 0, 0, 0]), 0.0);
+        let full_scale: Vec<u8> = [i16::MAX, i16::MIN + 1]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert!((pcm16_rms(&full_scale) - 1.0).abs() < 1e-4);
+    }
+
+    fn test_mapping() -> CustomMessageMapping {
+        serde_json::from_str(
+            r#"{
+                "text": "/result/text",
+                "isFinal": "result.final",
+                "start": "result.t0",
+                "end": "result.t1",
+                "speaker": "/who",
+                "timeUnit": "s"
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn should_parse_custom_message_with_mapping() {
+        let json = r#"{"who":1,"result":{"text":"mapped","final":1,"t0":1.5,"t1":2}}"#;
+        let segment = match parse_mapped("test-session", json, &test_mapping()) {
+            Ok(Some(SpeechEvent::Transcript { segment, .. })) => segment,
+            _ => panic!("expected transcript event"),
+        };
+        assert_eq!(segment.text, "mapped");
+        assert_eq!(segment.speaker_id, "SPEAKER_1");
+        assert_eq!(segment.start_ms, 1500);
+        assert_eq!(segment.end_ms, 2000);
+        assert!(segment.is_final);
+    }
+
+    #[test]
+    fn should_name_missing_field_in_custom_mapping_error() {
+        let json = r#"{"result":{"text":"no flag"}}"#;
+        let err = parse_mapped("test-session", json, &test_mapping()).err().unwrap();
+        assert!(err.contains("isFinal"));
+        assert!(err.contains("result.final"));
+    }
+
+    #[test]
+    fn should_reject_sessions_beyond_capacity() {
+        let sessions: HashMap<String, SpeechSession> = HashMap::new();
+        assert!(SpeechProxyState::ensure_capacity(&sessions, 1).is_ok());
+        let err = SpeechProxyState::ensure_capacity(&sessions, 0).unwrap_err();
+        assert!(err.contains("max 0"));
+    }
+
+    #[test]
+    fn should_build_provider_flush_frames() {
+        let deepgram: Value = serde_json::from_str(&build_flush_frame("deepgram", false).unwrap()).unwrap();
+        assert_eq!(deepgram["type"], "Finalize");
+        let assemblyai: Value =
+            serde_json::from_str(&build_flush_frame("assemblyai", false).unwrap()).unwrap();
+        assert_eq!(assemblyai["type"], "ForceEndpoint");
+        let gladia: Value = serde_json::from_str(&build_flush_frame("gladia", false).unwrap()).unwrap();
+        assert_eq!(gladia["type"], "stop_recording");
+        assert!(build_flush_frame("gladia", true).is_none());
+        assert!(build_flush_frame("custom", false).is_none());
+    }
+
+    #[test]
+    fn should_reuse_custom_end_frame_for_flush() {
+        assert_eq!(
+            build_custom_end_frame(CustomWsMode::TencentAsrV2, None),
+            Some(build_tencent_end_signal())
+        );
+        assert_eq!(
+            build_custom_end_frame(CustomWsMode::DashScopeFunAsr, Some("finish")),
+            Some("finish".to_string())
+        );
+        assert!(build_custom_end_frame(CustomWsMode::RawBinary, None).is_none());
+    }
+
     #[test]
     fn should_build_iflytek_first_frame_with_common_and_business() {
         let frame = build_iflytek_audio_frame("appid123", "zh", &[1, 2, 3, 4], 0);
@@ -3049,4 +4286,44 @@ mod tests {
             Some(0)
         );
     }
+
+    #[test]
+    fn should_build_credential_check_per_provider() {
+        let url = |provider: &str, base: &str, region: &str| {
+            check_request(provider, base, region, "k").unwrap().map(|c| c.url)
+        };
+        assert_eq!(url("deepgram", "", "").unwrap(), "https://api.deepgram.com/v1/projects");
+        assert_eq!(
+            url("deepgram", "wss://dg.example.com/v1/listen", "").unwrap(),
+            "https://dg.example.com/v1/projects"
+        );
+        assert_eq!(
+            url("assemblyai", "wss://streaming.assemblyai.com", "").unwrap(),
+            "https://api.assemblyai.com/v2/transcript?limit=1"
+        );
+        assert_eq!(url("gladia", "", "").unwrap(), "https://api.gladia.io/v2/live?limit=1");
+        let azure = check_request("azure-speech", "", "westeurope", "k").unwrap().unwrap();
+        assert!(azure.post);
+        assert_eq!(
+            azure.url,
+            "https://westeurope.api.cognitive.microsoft.com/sts/v1.0/issueToken"
+        );
+        assert_eq!(azure.auth_header, "Ocp-Apim-Subscription-Key");
+        assert_eq!(url("custom", "wss://x.test", ""), None);
+    }
+
+    #[test]
+    fn should_tell_auth_and_quota_failures_apart() {
+        assert_eq!(classify_check_status(401, "").0, SpeechCheckFailure::Unauthorized);
+        assert_eq!(classify_check_status(403, "Forbidden").0, SpeechCheckFailure::Unauthorized);
+        assert_eq!(
+            classify_check_status(403, r#"{"error":"Insufficient balance"}"#).0,
+            SpeechCheckFailure::QuotaExceeded
+        );
+        assert_eq!(classify_check_status(402, "").0, SpeechCheckFailure::QuotaExceeded);
+        assert_eq!(classify_check_status(429, "").0, SpeechCheckFailure::QuotaExceeded);
+        let (failure, message) = classify_check_status(500, "boom");
+        assert_eq!(failure, SpeechCheckFailure::HttpError);
+        assert_eq!(message, "Unexpected response (500): boom");
+    }
 }
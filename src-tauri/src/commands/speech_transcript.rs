@@ -0,0 +1,440 @@
+//! Transcript post-processing for recorded speech sessions: merging a raw
+//! segment stream into speaker turns, rendering it for a note, and writing
+//! SRT/VTT subtitles.
+//!
+//! Segments arrive as the JSON the frontend collected from `SpeechEvent`s.
+//! Interim results are always dropped; they are superseded by a final one.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use super::error::{CommandError, ErrorCode};
+use crate::commands::file as file_cmd;
+
+/// Same speaker, at most this far apart: one turn.
+const DEFAULT_MERGE_GAP_MS: u64 = 2_000;
+
+/// A recorded segment; the shape of `SpeechSegmentData`, lenient about
+/// fields older transcripts did not store.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Segment {
+    #[serde(default)]
+    speaker_id: String,
+    text: String,
+    start_ms: u64,
+    end_ms: u64,
+    #[serde(default = "full_confidence")]
+    confidence: f64,
+    #[serde(default = "is_true")]
+    is_final: bool,
+}
+
+fn full_confidence() -> f64 {
+    1.0
+}
+
+fn is_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptStyle {
+    /// `**SPEAKER_0 [00:12:03]:** text`
+    #[default]
+    Markdown,
+    /// One paragraph per turn, no labels.
+    Plain,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscriptFormatOptions {
+    /// Largest silence between two segments of one speaker that still
+    /// merges them; 2 s when missing.
+    pub merge_gap_ms: Option<u64>,
+    pub style: TranscriptStyle,
+    /// Segments below this confidence (0–1) are dropped.
+    pub min_confidence: Option<f64>,
+    /// Display names by speaker id; unnamed speakers keep their id.
+    pub speaker_names: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeakerStats {
+    pub speaker_id: String,
+    pub talk_ms: u64,
+    /// Share of all talk time, 0–100.
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptStats {
+    /// Segments kept after filtering.
+    pub segments: usize,
+    pub dropped: usize,
+    pub turns: usize,
+    /// From the first segment's start to the last one's end.
+    pub duration_ms: u64,
+    /// Ordered by talk time, longest first.
+    pub speakers: Vec<SpeakerStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormattedTranscript {
+    pub markdown: String,
+    pub stats: TranscriptStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+/// A run of one speaker's consecutive segments.
+#[derive(Debug, PartialEq)]
+struct Turn {
+    speaker_id: String,
+    start_ms: u64,
+    end_ms: u64,
+    text: String,
+}
+
+fn parse_segments(segments_json: &str) -> Result<Vec<Segment>, CommandError> {
+    serde_json::from_str(segments_json).map_err(|e| {
+        CommandError::new(
+            ErrorCode::InvalidArgument,
+            format!("Invalid transcript segments: {}", e),
+        )
+    })
+}
+
+/// Final segments above the confidence floor with text, in time order.
+/// Returns them with the number dropped.
+fn keep_segments(segments: Vec<Segment>, min_confidence: Option<f64>) -> (Vec<Segment>, usize) {
+    let total = segments.len();
+    let floor = min_confidence.unwrap_or(0.0);
+    let mut kept: Vec<Segment> = segments
+        .into_iter()
+        .filter(|s| s.is_final && s.confidence >= floor && !s.text.trim().is_empty())
+        .collect();
+    kept.sort_by_key(|s| s.start_ms);
+    let dropped = total - kept.len();
+    (kept, dropped)
+}
+
+fn merge_turns(segments: &[Segment], gap_ms: u64) -> Vec<Turn> {
+    let mut turns: Vec<Turn> = Vec::new();
+    for segment in segments {
+        let text = segment.text.trim();
+        match turns.last_mut() {
+            Some(turn)
+                if turn.speaker_id == segment.speaker_id
+                    && segment.start_ms.saturating_sub(turn.end_ms) <= gap_ms =>
+            {
+                if needs_space(&turn.text, text) {
+                    turn.text.push(' ');
+                }
+                turn.text.push_str(text);
+                turn.end_ms = turn.end_ms.max(segment.end_ms);
+            }
+            _ => turns.push(Turn {
+                speaker_id: segment.speaker_id.clone(),
+                start_ms: segment.start_ms,
+                end_ms: segment.end_ms,
+                text: text.to_string(),
+            }),
+        }
+    }
+    turns
+}
+
+/// CJK text is written without spaces between words.
+fn needs_space(before: &str, after: &str) -> bool {
+    let is_cjk = |c: char| {
+        matches!(c as u32,
+            0x3000..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xFF00..=0xFFEF)
+    };
+    match (before.chars().last(), after.chars().next()) {
+        (Some(a), Some(b)) => !(is_cjk(a) || is_cjk(b)),
+        _ => false,
+    }
+}
+
+fn speaker_stats(turns: &[Turn]) -> Vec<SpeakerStats> {
+    let mut talk: HashMap<&str, u64> = HashMap::new();
+    for turn in turns {
+        *talk.entry(turn.speaker_id.as_str()).or_default() +=
+            turn.end_ms.saturating_sub(turn.start_ms);
+    }
+    let total: u64 = talk.values().sum();
+    let mut stats: Vec<SpeakerStats> = talk
+        .into_iter()
+        .map(|(id, talk_ms)| SpeakerStats {
+            speaker_id: id.to_string(),
+            talk_ms,
+            percent: if total == 0 {
+                0.0
+            } else {
+                (talk_ms as f64 * 1000.0 / total as f64).round() / 10.0
+            },
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        b.talk_ms
+            .cmp(&a.talk_ms)
+            .then_with(|| a.speaker_id.cmp(&b.speaker_id))
+    });
+    stats
+}
+
+/// `HH:MM:SS`
+fn clock(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// `HH:MM:SS,mmm` for SRT, `HH:MM:SS.mmm` for VTT.
+fn cue_time(ms: u64, format: SubtitleFormat) -> String {
+    let separator = match format {
+        SubtitleFormat::Srt => ',',
+        SubtitleFormat::Vtt => '.',
+    };
+    format!("{}{}{:03}", clock(ms), separator, ms % 1000)
+}
+
+fn render(turns: &[Turn], options: &TranscriptFormatOptions) -> String {
+    let mut out = String::new();
+    for turn in turns {
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        match options.style {
+            TranscriptStyle::Markdown => {
+                let name = options
+                    .speaker_names
+                    .get(&turn.speaker_id)
+                    .filter(|n| !n.trim().is_empty())
+                    .unwrap_or(&turn.speaker_id);
+                let _ = write!(
+                    out,
+                    "**{} [{}]:** {}",
+                    name.trim(),
+                    clock(turn.start_ms),
+                    turn.text
+                );
+            }
+            TranscriptStyle::Plain => out.push_str(&turn.text),
+        }
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+fn format_transcript(
+    segments: Vec<Segment>,
+    options: &TranscriptFormatOptions,
+) -> FormattedTranscript {
+    let (segments, dropped) = keep_segments(segments, options.min_confidence);
+    let turns = merge_turns(
+        &segments,
+        options.merge_gap_ms.unwrap_or(DEFAULT_MERGE_GAP_MS),
+    );
+    let duration_ms = match (segments.first(), segments.iter().map(|s| s.end_ms).max()) {
+        (Some(first), Some(end)) => end.saturating_sub(first.start_ms),
+        _ => 0,
+    };
+    FormattedTranscript {
+        markdown: render(&turns, options),
+        stats: TranscriptStats {
+            segments: segments.len(),
+            dropped,
+            turns: turns.len(),
+            duration_ms,
+            speakers: speaker_stats(&turns),
+        },
+    }
+}
+
+/// One cue per segment; merged turns would make cues too long to read.
+fn subtitles(segments: &[Segment], format: SubtitleFormat) -> String {
+    let multiple_speakers = segments
+        .windows(2)
+        .any(|w| w[0].speaker_id != w[1].speaker_id);
+    let mut out = String::new();
+    if format == SubtitleFormat::Vtt {
+        out.push_str("WEBVTT\n\n");
+    }
+    for (i, segment) in segments.iter().enumerate() {
+        if format == SubtitleFormat::Srt {
+            let _ = writeln!(out, "{}", i + 1);
+        }
+        // Cues need a positive duration
+        let end_ms = segment.end_ms.max(segment.start_ms + 1);
+        let _ = writeln!(
+            out,
+            "{} --> {}",
+            cue_time(segment.start_ms, format),
+            cue_time(end_ms, format)
+        );
+        // A blank line would end the cue early
+        let text = segment
+            .text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        match (multiple_speakers && !segment.speaker_id.is_empty(), format) {
+            (false, _) => {
+                let _ = writeln!(out, "{}", text);
+            }
+            (true, SubtitleFormat::Srt) => {
+                let _ = writeln!(out, "{}: {}", segment.speaker_id, text);
+            }
+            (true, SubtitleFormat::Vtt) => {
+                let _ = writeln!(out, "<v {}>{}", segment.speaker_id, text);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Merge a recorded transcript into speaker turns and render it for a note.
+#[tauri::command]
+pub async fn speech_format_transcript(
+    segments_json: String,
+    options: Option<TranscriptFormatOptions>,
+) -> Result<FormattedTranscript, CommandError> {
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        parse_segments(&segments_json).map(|segments| format_transcript(segments, &options))
+    })
+    .await
+    .map_err(|e| CommandError::from(format!("Transcript task failed: {}", e)))?
+}
+
+/// Write a recorded transcript as `srt` or `vtt` subtitles.
+/// Returns the number of cues written.
+#[tauri::command]
+pub async fn speech_export_transcript(
+    path: String,
+    segments_json: String,
+    format: String,
+) -> Result<usize, CommandError> {
+    let format = match format.to_ascii_lowercase().as_str() {
+        "srt" => SubtitleFormat::Srt,
+        "vtt" => SubtitleFormat::Vtt,
+        other => {
+            return Err(CommandError::new(
+                ErrorCode::InvalidArgument,
+                format!("Unsupported subtitle format: {}", other),
+            ))
+        }
+    };
+    let output = file_cmd::validate_path(&path)?;
+    let (segments, _) = keep_segments(parse_segments(&segments_json)?, None);
+    std::fs::write(&output, subtitles(&segments, format)).map_err(file_cmd::sanitize_io_error)?;
+    Ok(segments.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(speaker: &str, text: &str, start_ms: u64, end_ms: u64) -> Segment {
+        Segment {
+            speaker_id: speaker.to_string(),
+            text: text.to_string(),
+            start_ms,
+            end_ms,
+            confidence: 0.9,
+            is_final: true,
+        }
+    }
+
+    #[test]
+    fn should_merge_consecutive_segments_of_one_speaker() {
+        let segments = vec![
+            seg("SPEAKER_0", "Hello there.", 0, 1_000),
+            seg("SPEAKER_0", "How are you?", 1_500, 2_500),
+            seg("SPEAKER_1", "Fine.", 2_600, 3_000),
+            seg("SPEAKER_1", "Thanks.", 8_000, 9_000),
+        ];
+        let turns = merge_turns(&segments, DEFAULT_MERGE_GAP_MS);
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[0].text, "Hello there. How are you?");
+        assert_eq!(turns[0].end_ms, 2_500);
+        assert_eq!(turns[2].start_ms, 8_000);
+        let cjk = merge_turns(
+            &[seg("A", "你好", 0, 500), seg("A", "世界", 600, 900)],
+            1_000,
+        );
+        assert_eq!(cjk[0].text, "你好世界");
+    }
+
+    #[test]
+    fn should_render_markdown_with_stats() {
+        let json = r#"[
+            {"speakerId":"SPEAKER_0","text":"Welcome","startMs":723000,"endMs":726000,"confidence":0.95,"isFinal":true},
+            {"speakerId":"SPEAKER_0","text":"Welc","startMs":723000,"endMs":724000,"confidence":0.5,"isFinal":false},
+            {"speakerId":"SPEAKER_1","text":"uh","startMs":726500,"endMs":727000,"confidence":0.2,"isFinal":true},
+            {"speakerId":"SPEAKER_1","text":"Thanks","startMs":727000,"endMs":728000,"confidence":0.9,"isFinal":true}
+        ]"#;
+        let mut options = TranscriptFormatOptions {
+            min_confidence: Some(0.5),
+            ..Default::default()
+        };
+        options
+            .speaker_names
+            .insert("SPEAKER_1".into(), "Ana".into());
+        let result = format_transcript(parse_segments(json).unwrap(), &options);
+        assert_eq!(
+            result.markdown,
+            "**SPEAKER_0 [00:12:03]:** Welcome\n\n**Ana [00:12:07]:** Thanks\n"
+        );
+        assert_eq!(result.stats.segments, 2);
+        assert_eq!(result.stats.dropped, 2);
+        assert_eq!(result.stats.duration_ms, 5_000);
+        assert_eq!(result.stats.speakers[0].speaker_id, "SPEAKER_0");
+        assert_eq!(result.stats.speakers[0].percent, 75.0);
+        assert_eq!(result.stats.speakers[1].percent, 25.0);
+
+        options.style = TranscriptStyle::Plain;
+        let plain = format_transcript(parse_segments(json).unwrap(), &options);
+        assert_eq!(plain.markdown, "Welcome\n\nThanks\n");
+    }
+
+    #[test]
+    fn should_write_subtitle_cues() {
+        let segments = vec![
+            seg("SPEAKER_0", "First line", 1_200, 3_450),
+            seg("SPEAKER_1", "Second\n\nline", 3_600, 3_600),
+        ];
+        assert_eq!(
+            subtitles(&segments, SubtitleFormat::Srt),
+            "1\n00:00:01,200 --> 00:00:03,450\nSPEAKER_0: First line\n\n\
+             2\n00:00:03,600 --> 00:00:03,601\nSPEAKER_1: Second\nline\n\n"
+        );
+        assert_eq!(
+            subtitles(&segments[..1], SubtitleFormat::Vtt),
+            "WEBVTT\n\n00:00:01.200 --> 00:00:03.450\nFirst line\n\n"
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_segments() {
+        let err = parse_segments("{").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+}
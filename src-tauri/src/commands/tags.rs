@@ -0,0 +1,489 @@
+//! Vault-wide tag index: frontmatter `tags:` plus inline `#hashtags` from
+//! every markdown file under a folder.
+//!
+//! The index is cached per folder in `app_data_dir()/tag-index/`, so a
+//! rebuild only re-reads files whose mtime changed. `write_file`,
+//! `rename_file` and `delete_file` keep the loaded index current between
+//! rebuilds. Tags match case-insensitively; the first spelling seen (in path
+//! order) is the one displayed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::Manager;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::validate_path;
+use super::search::{collect_files, is_markdown, mtime_secs};
+
+/// Bump when extraction rules change so stale caches are rebuilt.
+const CACHE_VERSION: u32 = 1;
+
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Index of the folder last passed to `build_tag_index`.
+static INDEX: Mutex<Option<TagIndex>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FileTags {
+    modified: f64,
+    /// Display spellings in document order, without `#`, one per tag.
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TagIndex {
+    version: u32,
+    root: PathBuf,
+    /// Keyed by absolute path; ordered so "first occurrence" is stable.
+    files: BTreeMap<String, FileTags>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagEntry {
+    pub tag: String,
+    /// Number of files carrying the tag.
+    pub count: usize,
+    pub files: Vec<String>,
+}
+
+/// Resolve the cache directory. Called from the setup hook.
+pub fn init(app: &tauri::AppHandle) {
+    if let Ok(dir) = app.path().app_data_dir() {
+        let _ = CACHE_DIR.set(dir.join("tag-index"));
+    }
+}
+
+fn hashtag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:^|\s)#([\p{L}\p{N}_][\p{L}\p{N}_/-]*)").expect("valid hashtag regex")
+    })
+}
+
+fn push_tag(tags: &mut Vec<String>, tag: &str) {
+    let tag = tag
+        .trim()
+        .trim_start_matches('#')
+        .trim_end_matches(['/', '-']);
+    if tag.is_empty() || tag.chars().all(|c| c.is_ascii_digit()) {
+        return;
+    }
+    let key = tag.to_lowercase();
+    if !tags.iter().any(|t| t.to_lowercase() == key) {
+        tags.push(tag.to_string());
+    }
+}
+
+fn frontmatter_tags(frontmatter: &str, tags: &mut Vec<String>) {
+    let mut in_list = false;
+    for line in frontmatter.lines() {
+        if in_list {
+            if let Some(item) = line.trim_start().strip_prefix("- ") {
+                push_tag(tags, item.trim().trim_matches(['"', '\'']));
+                continue;
+            }
+            if line.starts_with([' ', '\t']) || line.trim().is_empty() {
+                continue;
+            }
+            in_list = false;
+        }
+        let Some(value) = line.strip_prefix("tags:") else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            in_list = true;
+            continue;
+        }
+        let value = value.trim_start_matches('[').trim_end_matches(']');
+        for item in value.split([',', ' ']) {
+            push_tag(tags, item.trim().trim_matches(['"', '\'']));
+        }
+    }
+}
+
+/// Remove inline code spans so `#` inside them isn't read as a tag.
+fn strip_code_spans(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        out.push_str(&rest[..start]);
+        let ticks = rest[start..].chars().take_while(|&c| c == '`').count();
+        let fence = &rest[start..start + ticks];
+        match rest[start + ticks..].find(fence) {
+            Some(end) => {
+                out.push(' ');
+                rest = &rest[start + ticks + end + ticks..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Tags of one document, in order of first appearance.
+fn extract_tags(text: &str) -> Vec<String> {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let mut tags = Vec::new();
+    let mut body = text;
+    if let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    {
+        if let Some(end) = rest.find("\n---") {
+            frontmatter_tags(&rest[..end], &mut tags);
+            body = &rest[end + 4..];
+        }
+    }
+
+    let mut fence: Option<String> = None;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if let Some(open) = &fence {
+            if trimmed.starts_with(open.as_str()) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let ch = trimmed.chars().next().unwrap_or('`');
+            fence = Some(trimmed.chars().take_while(|&c| c == ch).collect());
+            continue;
+        }
+        // `#` preceded by whitespace only, so URL fragments and `(#anchor)`
+        // links never count
+        for caps in hashtag_re().captures_iter(&strip_code_spans(line)) {
+            push_tag(&mut tags, &caps[1]);
+        }
+    }
+    tags
+}
+
+fn read_file_tags(path: &Path) -> Option<FileTags> {
+    let modified = mtime_secs(path)?;
+    let text = std::fs::read_to_string(path).ok()?;
+    Some(FileTags {
+        modified,
+        tags: extract_tags(&text),
+    })
+}
+
+impl TagIndex {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            root,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Re-read files whose mtime changed and drop files that are gone.
+    fn refresh(&mut self) {
+        let mut paths = Vec::new();
+        collect_files(&self.root, 0, &mut paths);
+        let mut files = BTreeMap::new();
+        for path in paths {
+            let key = path.to_string_lossy().to_string();
+            let cached = self
+                .files
+                .remove(&key)
+                .filter(|f| mtime_secs(&path) == Some(f.modified));
+            if let Some(tags) = cached.or_else(|| read_file_tags(&path)) {
+                files.insert(key, tags);
+            }
+        }
+        self.files = files;
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    /// Returns whether the stored tags changed.
+    fn update_file(&mut self, path: &Path) -> bool {
+        let key = path.to_string_lossy().to_string();
+        match read_file_tags(path) {
+            Some(tags) => {
+                let changed = self.files.get(&key).map(|f| &f.tags) != Some(&tags.tags);
+                self.files.insert(key, tags);
+                changed
+            }
+            None => self.files.remove(&key).is_some(),
+        }
+    }
+
+    /// Drop `path` and, for a directory, everything under it.
+    fn remove_path(&mut self, path: &Path) -> bool {
+        let before = self.files.len();
+        self.files.retain(|k, _| !Path::new(k).starts_with(path));
+        self.files.len() != before
+    }
+
+    fn rename_path(&mut self, old: &Path, new: &Path) -> bool {
+        let moved: Vec<String> = self
+            .files
+            .keys()
+            .filter(|k| Path::new(k).starts_with(old))
+            .cloned()
+            .collect();
+        for key in &moved {
+            let Some(tags) = self.files.remove(key) else {
+                continue;
+            };
+            let Ok(rest) = Path::new(key).strip_prefix(old) else {
+                continue;
+            };
+            let target = if rest.as_os_str().is_empty() {
+                new.to_path_buf()
+            } else {
+                new.join(rest)
+            };
+            if self.contains(&target) && is_markdown(&target.to_string_lossy()) {
+                self.files
+                    .insert(target.to_string_lossy().to_string(), tags);
+            }
+        }
+        // A non-markdown file renamed to `.md` starts carrying tags
+        if moved.is_empty()
+            && new.is_file()
+            && self.contains(new)
+            && is_markdown(&new.to_string_lossy())
+        {
+            return self.update_file(new);
+        }
+        !moved.is_empty()
+    }
+
+    fn entries(&self) -> Vec<TagEntry> {
+        // lowercase key -> (display spelling, files)
+        let mut by_key: HashMap<String, (String, Vec<String>)> = HashMap::new();
+        for (path, file) in &self.files {
+            for tag in &file.tags {
+                by_key
+                    .entry(tag.to_lowercase())
+                    .or_insert_with(|| (tag.clone(), Vec::new()))
+                    .1
+                    .push(path.clone());
+            }
+        }
+        let mut entries: Vec<TagEntry> = by_key
+            .into_values()
+            .map(|(tag, files)| TagEntry {
+                tag,
+                count: files.len(),
+                files,
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.tag.to_lowercase().cmp(&b.tag.to_lowercase()))
+        });
+        entries
+    }
+
+    fn files_for_tag(&self, tag: &str) -> Vec<String> {
+        let key = tag.trim().trim_start_matches('#').to_lowercase();
+        self.files
+            .iter()
+            .filter(|(_, f)| f.tags.iter().any(|t| t.to_lowercase() == key))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+fn cache_path(root: &Path) -> Option<PathBuf> {
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    Some(
+        CACHE_DIR
+            .get()?
+            .join(format!("{}.json", hex::encode(&digest[..8]))),
+    )
+}
+
+fn load_cache(root: &Path) -> Option<TagIndex> {
+    let text = std::fs::read_to_string(cache_path(root)?).ok()?;
+    let index: TagIndex = serde_json::from_str(&text).ok()?;
+    (index.version == CACHE_VERSION && index.root == root).then_some(index)
+}
+
+fn save_cache(index: &TagIndex) {
+    let Some(path) = cache_path(&index.root) else {
+        return;
+    };
+    let result = serde_json::to_vec(index)
+        .map_err(std::io::Error::other)
+        .and_then(|bytes| {
+            std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+            super::file::write_atomic(&path, &bytes)
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to save tag index: {}", e);
+    }
+}
+
+/// Apply `f` to the loaded index if `path` is inside it, persisting changes.
+fn with_index(path: &Path, f: impl FnOnce(&mut TagIndex) -> bool) {
+    let Ok(mut guard) = INDEX.lock() else {
+        return;
+    };
+    if let Some(index) = guard.as_mut().filter(|i| i.contains(path)) {
+        if f(index) {
+            save_cache(index);
+        }
+    }
+}
+
+/// Called after `write_file` saves a document.
+pub(crate) fn file_written(path: &Path) {
+    if is_markdown(&path.to_string_lossy()) {
+        with_index(path, |index| index.update_file(path));
+    }
+}
+
+/// Called after `delete_file` removes a file or directory.
+pub(crate) fn path_removed(path: &Path) {
+    with_index(path, |index| index.remove_path(path));
+}
+
+/// Called after `rename_file`.
+pub(crate) fn path_renamed(old: &Path, new: &Path) {
+    let Ok(mut guard) = INDEX.lock() else {
+        return;
+    };
+    if let Some(index) = guard
+        .as_mut()
+        .filter(|i| i.contains(old) || i.contains(new))
+    {
+        if index.rename_path(old, new) {
+            save_cache(index);
+        }
+    }
+}
+
+/// Scan `root` (reusing the cached index where files are unchanged) and
+/// return every tag with the files that carry it.
+#[tauri::command]
+pub async fn build_tag_index(root: String) -> Result<Vec<TagEntry>, CommandError> {
+    let root = validate_path(&root)?;
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotADirectory,
+            "Not a directory",
+        ));
+    }
+    tokio::task::spawn_blocking(move || {
+        let mut index = load_cache(&root).unwrap_or_else(|| TagIndex::new(root.clone()));
+        index.refresh();
+        save_cache(&index);
+        let entries = index.entries();
+        if let Ok(mut guard) = INDEX.lock() {
+            *guard = Some(index);
+        }
+        entries
+    })
+    .await
+    .map_err(|e| CommandError::from(format!("Tag index task failed: {}", e)))
+}
+
+/// Files carrying `tag` (with or without `#`, any casing) in the index
+/// built by the last `build_tag_index` call.
+#[tauri::command]
+pub fn get_files_for_tag(tag: String) -> Result<Vec<String>, CommandError> {
+    let guard = INDEX
+        .lock()
+        .map_err(|_| CommandError::from("Tag index unavailable"))?;
+    Ok(guard
+        .as_ref()
+        .map(|index| index.files_for_tag(&tag))
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    #[test]
+    fn should_extract_frontmatter_and_inline_tags() {
+        let text = "---\ntitle: x\ntags:\n  - Rust\n  - \"web dev\"\naliases: [a]\n---\n\
+# Heading\n\nSome #Idea and #rust again, issue #42.\n\
+See https://example.com/page#anchor and [link](#section).\n\
+Inline `#notatag` code.\n\n```sh\necho #comment\n```\n\n#project/alpha-\n";
+        assert_eq!(
+            extract_tags(text),
+            vec!["Rust", "web dev", "Idea", "project/alpha"]
+        );
+        assert_eq!(
+            extract_tags("---\ntags: [a, \"B\"]\n---\n#c"),
+            vec!["a", "B", "c"]
+        );
+        assert_eq!(
+            extract_tags("---\ntags: one two\n---\n"),
+            vec!["one", "two"]
+        );
+    }
+
+    fn vault(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("tags-{}", name));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.md"), "#Rust notes #todo").unwrap();
+        std::fs::write(dir.join("sub/b.md"), "---\ntags: [rust]\n---\n").unwrap();
+        std::fs::write(dir.join("sub/c.txt"), "#ignored").unwrap();
+        dir
+    }
+
+    #[test]
+    fn should_count_tags_case_insensitively_with_first_spelling() {
+        let dir = vault("count");
+        let mut index = TagIndex::new(dir.to_path_buf());
+        index.refresh();
+        let entries = index.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tag, "Rust");
+        assert_eq!(entries[0].count, 2);
+        assert_eq!(entries[1].tag, "todo");
+        assert_eq!(index.files_for_tag("#RUST").len(), 2);
+        assert!(index.files_for_tag("ignored").is_empty());
+    }
+
+    #[test]
+    fn should_update_incrementally() {
+        let dir = vault("incremental");
+        let mut index = TagIndex::new(dir.to_path_buf());
+        index.refresh();
+
+        let a = dir.join("a.md");
+        std::fs::write(&a, "#done").unwrap();
+        assert!(index.update_file(&a));
+        assert!(!index.update_file(&a));
+        assert_eq!(index.files_for_tag("done"), vec![a.to_string_lossy()]);
+        assert!(index.files_for_tag("todo").is_empty());
+
+        let moved = dir.join("moved");
+        std::fs::rename(dir.join("sub"), &moved).unwrap();
+        assert!(index.rename_path(&dir.join("sub"), &moved));
+        assert_eq!(
+            index.files_for_tag("rust"),
+            vec![moved.join("b.md").to_string_lossy()]
+        );
+
+        std::fs::remove_dir_all(&moved).unwrap();
+        assert!(index.remove_path(&moved));
+        assert!(index.files_for_tag("rust").is_empty());
+
+        // A later refresh keeps unchanged entries and picks up new files
+        std::fs::write(dir.join("new.md"), "#fresh").unwrap();
+        index.refresh();
+        assert_eq!(index.entries().len(), 2);
+    }
+}
@@ -0,0 +1,46 @@
+//! Helpers shared by the command tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// An empty folder under the system temp folder, deleted when dropped, so
+/// it is cleaned up even when an assertion fails. Derefs to its canonical
+/// path, as `validate_path` would return it.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `moraya-<name>-<pid>-<n>`, unique among the tests running in parallel.
+    pub(crate) fn new(name: &str) -> Self {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "moraya-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(super::file::strip_unc_prefix(dir.canonicalize().unwrap()))
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
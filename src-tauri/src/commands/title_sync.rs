@@ -0,0 +1,280 @@
+//! Keep a note's file name and its first `# Title` in step, in either
+//! direction. Renames go through `rename_file` and heading edits through
+//! `write_file`, so validation, atomic writes and the tag index behave as
+//! for any other change.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::clipper::note_name;
+use super::error::{CommandError, ErrorCode};
+use super::file::{rename_file, sanitize_io_error, validate_path, write_file};
+use super::frontmatter::body_start;
+
+/// Suffixes tried when the new file name is taken: "Title 2" … "Title 100".
+const MAX_SUFFIX: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleSyncDirection {
+    FilenameToHeading,
+    HeadingToFilename,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TitleSync {
+    /// Where the note is now; differs from the request when renamed.
+    pub path: String,
+    pub title: String,
+    pub renamed: bool,
+    pub heading_changed: bool,
+    /// Notes whose links were pointed at the new file name.
+    pub updated_links: Vec<String>,
+}
+
+/// Byte range of the text of the first ATX heading outside code, without
+/// the `#` markers or a closing `#` sequence.
+fn first_heading(content: &str) -> Option<Range<usize>> {
+    let mut offset = body_start(content);
+    let mut fence: Option<(char, usize)> = None;
+    for line in content[offset..].split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        if indent > 3 {
+            continue;
+        }
+        let marker = trimmed.chars().next().unwrap_or(' ');
+        if marker == '`' || marker == '~' {
+            let len = trimmed.chars().take_while(|c| *c == marker).count();
+            match fence {
+                Some((ch, open))
+                    if ch == marker && len >= open && trimmed.trim_end().len() == len =>
+                {
+                    fence = None
+                }
+                None if len >= 3 => fence = Some((marker, len)),
+                _ => {}
+            }
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+        let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+        let rest = &trimmed[hashes.min(trimmed.len())..];
+        if !(1..=6).contains(&hashes) || !(rest.trim().is_empty() || rest.starts_with([' ', '\t']))
+        {
+            continue;
+        }
+        let text_start = line_start + indent + hashes + (rest.len() - rest.trim_start().len());
+        let mut text = rest.trim();
+        let without_closing = text.trim_end_matches('#');
+        if without_closing.is_empty() {
+            text = "";
+        } else if without_closing.ends_with([' ', '\t']) {
+            text = without_closing.trim_end();
+        }
+        // An empty heading: put new text right after the markers
+        let text_start = if text.is_empty() {
+            line_start + indent + hashes
+        } else {
+            text_start
+        };
+        return Some(text_start..text_start + text.len());
+    }
+    None
+}
+
+/// `content` with its first heading set to `title`, or `# title` added at
+/// the top of the body. None when it already reads `title`.
+fn with_heading(content: &str, title: &str) -> Option<String> {
+    match first_heading(content) {
+        Some(range) if &content[range.clone()] == title => None,
+        Some(range) => {
+            let space = if range.is_empty() { " " } else { "" };
+            Some(format!(
+                "{}{}{}{}",
+                &content[..range.start],
+                space,
+                title,
+                &content[range.end..]
+            ))
+        }
+        None => {
+            let at = body_start(content);
+            Some(format!(
+                "{}# {}\n\n{}",
+                &content[..at],
+                title,
+                &content[at..]
+            ))
+        }
+    }
+}
+
+/// Candidate file names for `title`, keeping the note's extension.
+fn file_names(title: &str, extension: &str) -> impl Iterator<Item = String> {
+    let name = note_name(title);
+    let extension = extension.to_string();
+    (1..=MAX_SUFFIX).map(move |n| match n {
+        1 => format!("{}.{}", name, extension),
+        n => format!("{} {}.{}", name, n, extension),
+    })
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// Whether `a` and `b` are the same file, as when only the case of a name
+/// differs on a case-insensitive file system.
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        // Windows names ignore case
+        let _ = a;
+        b.exists()
+    }
+}
+
+/// Rename `path` after `title`, suffixing a number while the name is
+/// taken. Returns the new path, or None when the name already matches.
+fn rename_after(path: &Path, title: &str) -> Result<Option<PathBuf>, CommandError> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| CommandError::new(ErrorCode::InvalidPath, "Invalid path"))?;
+    let old_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "md".to_string());
+    for name in file_names(title, &extension) {
+        if name == old_name {
+            return Ok(None);
+        }
+        let new_path = dir.join(&name);
+        // Only the case differs: the same file where names ignore case
+        if name.to_lowercase() == old_name.to_lowercase() && same_file(path, &new_path) {
+            std::fs::rename(path, &new_path).map_err(sanitize_io_error)?;
+            super::tags::path_renamed(path, &new_path);
+            super::search_index::path_renamed(path, &new_path);
+            return Ok(Some(new_path));
+        }
+        match rename_file(path_string(path), path_string(&new_path)) {
+            Ok(()) => return Ok(Some(new_path)),
+            Err(e) if e.code == ErrorCode::FileExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(CommandError::new(
+        ErrorCode::FileExists,
+        "Too many notes with the same title",
+    ))
+}
+
+/// Make the file name and the first heading of the note at `path` agree.
+/// `filename_to_heading` rewrites (or adds) the heading; `heading_to_filename`
+/// renames the file, and with `root` also fixes links to it in the notes
+/// below `root`.
+#[tauri::command]
+pub async fn sync_title(
+    path: String,
+    direction: TitleSyncDirection,
+    root: Option<String>,
+) -> Result<TitleSync, CommandError> {
+    let safe_path = validate_path(&path)?;
+    let root = root.map(|r| validate_path(&r)).transpose()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let content = super::file::read_file(path_string(&safe_path))?;
+        let mut result = TitleSync {
+            path: path_string(&safe_path),
+            title: String::new(),
+            renamed: false,
+            heading_changed: false,
+            updated_links: Vec::new(),
+        };
+        match direction {
+            TitleSyncDirection::FilenameToHeading => {
+                let stem = safe_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if let Some(updated) = with_heading(&content, &stem) {
+                    write_file(path_string(&safe_path), updated, None)?;
+                    result.heading_changed = true;
+                }
+                result.title = stem;
+            }
+            TitleSyncDirection::HeadingToFilename => {
+                let title = first_heading(&content)
+                    .map(|range| content[range].trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .ok_or_else(|| {
+                        CommandError::new(ErrorCode::InvalidArgument, "The note has no heading")
+                    })?;
+                if let Some(new_path) = rename_after(&safe_path, &title)? {
+                    if let Some(root) = root.as_deref() {
+                        result.updated_links =
+                            super::backlinks::redirect_links(root, &safe_path, &new_path);
+                    }
+                    result.path = path_string(&new_path);
+                    result.renamed = true;
+                }
+                result.title = title;
+            }
+        }
+        Ok(result)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_first_heading_after_frontmatter_and_code() {
+        let doc = "---\ntitle: x\n# not a heading\n---\n\n```\n# code\n```\n\n## Real title ##\n# Later\n";
+        let range = first_heading(doc).unwrap();
+        assert_eq!(&doc[range], "Real title");
+        assert_eq!(first_heading("No heading\n#hashtag\n"), None);
+        assert_eq!(first_heading("    # indented code\n"), None);
+        let bare = "#\nbody";
+        assert_eq!(first_heading(bare), Some(1..1));
+    }
+
+    #[test]
+    fn should_set_or_add_heading() {
+        assert_eq!(with_heading("# Old\nbody", "New").unwrap(), "# New\nbody");
+        assert_eq!(with_heading("# Same\n", "Same"), None);
+        assert_eq!(with_heading("#\nbody", "New").unwrap(), "# New\nbody");
+        assert_eq!(
+            with_heading("---\na: 1\n---\nbody\n", "New").unwrap(),
+            "---\na: 1\n---\n# New\n\nbody\n"
+        );
+        assert_eq!(with_heading("body", "New").unwrap(), "# New\n\nbody");
+    }
+
+    #[test]
+    fn should_name_files_safely_with_suffixes() {
+        let names: Vec<String> = file_names("A/B: C?", "md").take(3).collect();
+        assert_eq!(names, ["A-B- C-.md", "A-B- C- 2.md", "A-B- C- 3.md"]);
+    }
+}
@@ -0,0 +1,361 @@
+//! Text-to-speech proxy.
+//!
+//! Mirrors the STT proxy: the frontend never sees the API key. Audio is
+//! streamed back as base64 chunks over a `Channel` while the provider is
+//! still synthesizing, so playback can start before the full clip arrives.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use futures_util::StreamExt;
+use serde::Serialize;
+use tauri::ipc::Channel;
+
+use super::ai_proxy::AIProxyState;
+
+const TTS_KEY_PREFIX: &str = "tts-key:";
+const OPENAI_DEFAULT_BASE: &str = "https://api.openai.com/v1";
+const ELEVENLABS_DEFAULT_BASE: &str = "https://api.elevenlabs.io";
+const TTS_CONNECT_TIMEOUT_SECS: u64 = 15;
+/// No bytes for this long means the provider stalled.
+const TTS_CHUNK_TIMEOUT_SECS: u64 = 30;
+/// Coalesce small network reads so the IPC channel isn't flooded.
+const TTS_MIN_CHUNK_BYTES: usize = 16 * 1024;
+
+static TTS_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn new_request_id() -> String {
+    let count = TTS_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("tts-{}-{}", ts, count)
+}
+
+/// Abort flags for in-flight synthesis requests.
+pub struct TtsProxyState {
+    abort_flags: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl TtsProxyState {
+    pub fn new() -> Self {
+        Self {
+            abort_flags: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TtsEvent {
+    /// First event; `requestId` is what `tts_proxy_abort` expects.
+    Started {
+        #[serde(rename = "requestId")]
+        request_id: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        #[serde(rename = "requestId")]
+        request_id: String,
+        /// Base64-encoded audio bytes.
+        data: String,
+    },
+    Done {
+        #[serde(rename = "requestId")]
+        request_id: String,
+        #[serde(rename = "totalBytes")]
+        total_bytes: u64,
+        aborted: bool,
+    },
+}
+
+/// Build the provider request. Returns the request and the audio MIME type.
+fn build_tts_request(
+    client: &reqwest::Client,
+    provider: &str,
+    base_url: Option<&str>,
+    api_key: &str,
+    model: &str,
+    voice: &str,
+    text: &str,
+) -> Result<(reqwest::RequestBuilder, &'static str), String> {
+    let base = base_url
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|b| b.trim_end_matches('/').to_string());
+    if let Some(ref b) = base {
+        if !b.starts_with("https://") && !b.starts_with("http://") {
+            return Err("TTS base URL must start with http:// or https://".to_string());
+        }
+    }
+
+    match provider {
+        "openai" => {
+            let base = base.unwrap_or_else(|| OPENAI_DEFAULT_BASE.to_string());
+            let body = serde_json::json!({
+                "model": model,
+                "voice": voice,
+                "input": text,
+                "response_format": "mp3",
+            });
+            let req = client
+                .post(format!("{}/audio/speech", base))
+                .bearer_auth(api_key)
+                .json(&body);
+            Ok((req, "audio/mpeg"))
+        }
+        "elevenlabs" => {
+            if voice.trim().is_empty() {
+                return Err("ElevenLabs requires a voice ID".to_string());
+            }
+            let base = base.unwrap_or_else(|| ELEVENLABS_DEFAULT_BASE.to_string());
+            let mut url = reqwest::Url::parse(&format!(
+                "{}/v1/text-to-speech/{}/stream",
+                base,
+                voice.trim()
+            ))
+            .map_err(|_| "Invalid TTS base URL".to_string())?;
+            url.query_pairs_mut()
+                .append_pair("output_format", "mp3_44100_128");
+            let mut body = serde_json::json!({ "text": text });
+            if !model.trim().is_empty() {
+                body["model_id"] = serde_json::Value::String(model.trim().to_string());
+            }
+            let req = client
+                .post(url)
+                .header("xi-api-key", api_key)
+                .header("Accept", "audio/mpeg")
+                .json(&body);
+            Ok((req, "audio/mpeg"))
+        }
+        other => Err(format!("Unknown TTS provider: {}", other)),
+    }
+}
+
+/// Turn a non-2xx response into a readable error, keeping the provider's own
+/// message (OpenAI: `error.message`, ElevenLabs: `detail.message` / `detail`).
+fn tts_provider_error(status: u16, body: &str) -> String {
+    let message = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .or_else(|| v.pointer("/detail/message"))
+                .or_else(|| v.get("detail").filter(|d| d.is_string()))
+                .or_else(|| v.get("message"))
+                .and_then(|m| m.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.chars().take(300).collect());
+    let message = message.trim();
+    if status == 429 {
+        format!("TTS rate limited (429): {}", message)
+    } else {
+        format!("TTS error ({}): {}", status, message)
+    }
+}
+
+/// Synthesize `text` and stream the audio back over `on_event`.
+/// Resolves when the stream ends, is aborted, or fails.
+#[tauri::command]
+pub async fn tts_proxy_start(
+    state: tauri::State<'_, TtsProxyState>,
+    key_state: tauri::State<'_, AIProxyState>,
+    config_id: String,
+    provider: String,
+    base_url: Option<String>,
+    model: String,
+    voice: String,
+    text: String,
+    on_event: Channel<TtsEvent>,
+) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("Nothing to read aloud".to_string());
+    }
+
+    key_state.ensure_secrets_loaded().await;
+    let api_key = {
+        let cache = key_state
+            .key_cache
+            .lock()
+            .map_err(|_| "Keychain lock poisoned".to_string())?;
+        cache
+            .get(&format!("{}{}", TTS_KEY_PREFIX, config_id))
+            .cloned()
+            .unwrap_or_default()
+    };
+    if api_key.is_empty() {
+        return Err("No API key configured for this TTS provider".to_string());
+    }
+
+    let client = super::settings::client_builder()
+        .connect_timeout(std::time::Duration::from_secs(TTS_CONNECT_TIMEOUT_SECS))
+        .build()
+        .map_err(|_| "Failed to create HTTP client".to_string())?;
+    let (req, mime_type) = build_tts_request(
+        &client,
+        &provider,
+        base_url.as_deref(),
+        &api_key,
+        &model,
+        &voice,
+        &text,
+    )?;
+
+    let request_id = new_request_id();
+    let abort_flag = Arc::new(AtomicBool::new(false));
+    {
+        let mut flags = state
+            .abort_flags
+            .lock()
+            .map_err(|_| "State lock poisoned".to_string())?;
+        flags.insert(request_id.clone(), abort_flag.clone());
+    }
+    let _ = on_event.send(TtsEvent::Started {
+        request_id: request_id.clone(),
+        mime_type: mime_type.to_string(),
+    });
+
+    let result = stream_tts(&on_event, &request_id, req, &abort_flag).await;
+
+    if let Ok(mut flags) = state.abort_flags.lock() {
+        flags.remove(&request_id);
+    }
+    result
+}
+
+async fn stream_tts(
+    on_event: &Channel<TtsEvent>,
+    request_id: &str,
+    req: reqwest::RequestBuilder,
+    abort_flag: &Arc<AtomicBool>,
+) -> Result<(), String> {
+    let response = req.send().await.map_err(|e| {
+        if e.is_timeout() {
+            "TTS request timed out".to_string()
+        } else {
+            "TTS request failed".to_string()
+        }
+    })?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(tts_provider_error(status, &body));
+    }
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut stream = response.bytes_stream();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut total_bytes: u64 = 0;
+
+    loop {
+        let chunk = {
+            let abort_wait = async {
+                loop {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    if abort_flag.load(Ordering::SeqCst) {
+                        return;
+                    }
+                }
+            };
+            tokio::select! {
+                c = tokio::time::timeout(
+                    std::time::Duration::from_secs(TTS_CHUNK_TIMEOUT_SECS),
+                    stream.next(),
+                ) => match c {
+                    Ok(c) => c,
+                    Err(_) => return Err("TTS stream stalled".to_string()),
+                },
+                _ = abort_wait => None,
+            }
+        };
+        let Some(chunk) = chunk else { break };
+        let bytes = chunk.map_err(|_| "TTS stream read error".to_string())?;
+        total_bytes += bytes.len() as u64;
+        pending.extend_from_slice(&bytes);
+        if pending.len() >= TTS_MIN_CHUNK_BYTES {
+            let _ = on_event.send(TtsEvent::Audio {
+                request_id: request_id.to_string(),
+                data: engine.encode(&pending),
+            });
+            pending.clear();
+        }
+    }
+
+    let aborted = abort_flag.load(Ordering::SeqCst);
+    if !pending.is_empty() && !aborted {
+        let _ = on_event.send(TtsEvent::Audio {
+            request_id: request_id.to_string(),
+            data: engine.encode(&pending),
+        });
+    }
+    let _ = on_event.send(TtsEvent::Done {
+        request_id: request_id.to_string(),
+        total_bytes,
+        aborted,
+    });
+    Ok(())
+}
+
+/// Abort an in-flight synthesis by the `requestId` from its `Started` event.
+#[tauri::command]
+pub fn tts_proxy_abort(
+    state: tauri::State<'_, TtsProxyState>,
+    request_id: String,
+) -> Result<(), String> {
+    let flags = state
+        .abort_flags
+        .lock()
+        .map_err(|_| "State lock poisoned".to_string())?;
+    if let Some(flag) = flags.get(&request_id) {
+        flag.store(true, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_surface_provider_rate_limit_message() {
+        let openai = r#"{"error":{"message":"Rate limit reached for tts-1","type":"requests"}}"#;
+        assert_eq!(
+            tts_provider_error(429, openai),
+            "TTS rate limited (429): Rate limit reached for tts-1"
+        );
+        let eleven = r#"{"detail":{"status":"too_many_concurrent_requests","message":"Too many requests"}}"#;
+        assert_eq!(
+            tts_provider_error(429, eleven),
+            "TTS rate limited (429): Too many requests"
+        );
+        assert_eq!(tts_provider_error(500, "oops"), "TTS error (500): oops");
+    }
+
+    #[test]
+    fn should_build_elevenlabs_stream_url() {
+        let client = reqwest::Client::new();
+        let (req, mime) =
+            build_tts_request(&client, "elevenlabs", None, "k", "eleven_turbo_v2", "v1", "hi")
+                .unwrap();
+        let req = req.build().unwrap();
+        assert_eq!(mime, "audio/mpeg");
+        assert_eq!(
+            req.url().as_str(),
+            "https://api.elevenlabs.io/v1/text-to-speech/v1/stream?output_format=mp3_44100_128"
+        );
+        assert_eq!(req.headers()["xi-api-key"], "k");
+    }
+
+    #[test]
+    fn should_reject_unknown_provider_and_bad_base_url() {
+        let client = reqwest::Client::new();
+        assert!(build_tts_request(&client, "nope", None, "k", "m", "v", "t").is_err());
+        assert!(
+            build_tts_request(&client, "openai", Some("ftp://x"), "k", "m", "v", "t").is_err()
+        );
+    }
+}
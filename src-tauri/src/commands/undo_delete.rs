@@ -0,0 +1,400 @@
+//! Deleting with an undo window. `delete_file_with_undo` moves a file or
+//! folder into a staging folder under app data and returns a token that
+//! `undo_delete` accepts for `UNDO_WINDOW`. After that, when the window that
+//! deleted it is destroyed, or when the app quits, the item goes to the OS trash.
+//!
+//! Items left in staging by a crash are sent to the trash on the next start.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime, WindowEvent};
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{copy_dir_recursive, sanitize_io_error, validate_path};
+
+const UNDO_WINDOW: Duration = Duration::from_secs(30);
+/// How often expired deletions are finalized.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Pending {
+    original: PathBuf,
+    /// `<staging>/<token>/<original name>`.
+    staged: PathBuf,
+    /// Label of the window that deleted it.
+    window: String,
+    deleted_at: Instant,
+}
+
+pub struct UndoDeleteState {
+    pending: Mutex<HashMap<String, Pending>>,
+    staging: OnceLock<PathBuf>,
+}
+
+impl UndoDeleteState {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            staging: OnceLock::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Pending>> {
+        match self.pending.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        }
+    }
+
+    /// Remove and return the pending deletions matching `f`.
+    fn take(&self, f: impl Fn(&Pending) -> bool) -> Vec<Pending> {
+        let mut pending = self.lock();
+        let tokens: Vec<String> = pending
+            .iter()
+            .filter(|(_, p)| f(p))
+            .map(|(token, _)| token.clone())
+            .collect();
+        tokens.iter().filter_map(|t| pending.remove(t)).collect()
+    }
+}
+
+impl Default for UndoDeleteState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingDelete {
+    pub token: String,
+    pub path: String,
+    /// How long `undo_delete` accepts the token.
+    pub undo_window_ms: u64,
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// `fs::rename`, falling back to copy and delete when app data is on
+/// another volume than the notes.
+fn move_path(src: &Path, dst: &Path) -> Result<(), CommandError> {
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    if src.is_dir() {
+        copy_dir_recursive(src, dst)?;
+        fs::remove_dir_all(src).map_err(sanitize_io_error)
+    } else {
+        fs::copy(src, dst).map_err(sanitize_io_error)?;
+        super::file_tags::copy_tags(src, dst);
+        fs::remove_file(src).map_err(sanitize_io_error)
+    }
+}
+
+fn stage(
+    state: &UndoDeleteState,
+    path: &Path,
+    window: String,
+) -> Result<PendingDelete, CommandError> {
+    if fs::symlink_metadata(path).is_err() {
+        return Err(CommandError::new(ErrorCode::FileNotFound, "File not found"));
+    }
+    let staging = state.staging.get().ok_or_else(|| {
+        CommandError::new(ErrorCode::Internal, "The staging folder is unavailable")
+    })?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| CommandError::new(ErrorCode::InvalidPath, "Invalid path"))?;
+    let token = hex::encode(&super::keychain::random_key()[..12]);
+    let token_dir = staging.join(&token);
+    fs::create_dir_all(&token_dir).map_err(sanitize_io_error)?;
+    let staged = token_dir.join(name);
+    if let Err(e) = move_path(path, &staged) {
+        let _ = fs::remove_dir(&token_dir);
+        return Err(e);
+    }
+    super::tags::path_removed(path);
+    super::search_index::path_removed(path);
+    state.lock().insert(
+        token.clone(),
+        Pending {
+            original: path.to_path_buf(),
+            staged,
+            window,
+            deleted_at: Instant::now(),
+        },
+    );
+    Ok(PendingDelete {
+        token,
+        path: path_string(path),
+        undo_window_ms: UNDO_WINDOW.as_millis() as u64,
+    })
+}
+
+fn restore(state: &UndoDeleteState, token: &str) -> Result<String, CommandError> {
+    let item = state.lock().remove(token).ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::FileNotFound,
+            "This deletion can no longer be undone",
+        )
+    })?;
+    let result = (|| {
+        if fs::symlink_metadata(&item.original).is_ok() {
+            return Err(CommandError::new(
+                ErrorCode::FileExists,
+                "Something new is at the original path",
+            ));
+        }
+        if let Some(parent) = item.original.parent() {
+            fs::create_dir_all(parent).map_err(sanitize_io_error)?;
+        }
+        move_path(&item.staged, &item.original)
+    })();
+    if let Err(e) = result {
+        // Still undoable once the path is free, until the window runs out
+        state.lock().insert(token.to_string(), item);
+        return Err(e);
+    }
+    if let Some(token_dir) = item.staged.parent() {
+        let _ = fs::remove_dir(token_dir);
+    }
+    if item.original.is_file() {
+        super::tags::file_written(&item.original);
+        super::search_index::file_written(&item.original);
+    }
+    Ok(path_string(&item.original))
+}
+
+/// Send a staged item to the OS trash, or to `~/.moraya/trash/notes` where
+/// there is none, and drop its token folder.
+fn finalize(staged: &Path) {
+    if let Err(e) = trash::delete(staged) {
+        log::warn!("OS trash unavailable, using the Moraya trash: {}", e);
+        let fallback = dirs::home_dir().map(|home| {
+            let token = staged
+                .parent()
+                .and_then(Path::file_name)
+                .unwrap_or_default();
+            home.join(".moraya")
+                .join("trash")
+                .join("notes")
+                .join(token)
+                .join(staged.file_name().unwrap_or_default())
+        });
+        let moved = fallback.is_some_and(|dest| {
+            dest.parent().is_some_and(|p| fs::create_dir_all(p).is_ok())
+                && move_path(staged, &dest).is_ok()
+        });
+        if !moved {
+            log::warn!("Could not finalize deletion of {}", staged.display());
+            return;
+        }
+    }
+    if let Some(token_dir) = staged.parent() {
+        let _ = fs::remove_dir(token_dir);
+    }
+}
+
+fn finalize_all_of(items: Vec<Pending>) {
+    for item in items {
+        finalize(&item.staged);
+    }
+}
+
+/// Trash what a previous run left in these token folders.
+fn finalize_leftovers(token_dirs: Vec<PathBuf>) {
+    for token_dir in token_dirs {
+        let Ok(items) = fs::read_dir(&token_dir) else {
+            continue;
+        };
+        for item in items.flatten() {
+            finalize(&item.path());
+        }
+        let _ = fs::remove_dir(&token_dir);
+    }
+}
+
+/// Resolve the staging folder and start finalizing expired deletions, and
+/// whatever a previous run left behind. Called once from the setup hook.
+pub fn start(app: &tauri::AppHandle) {
+    let mut leftovers = Vec::new();
+    if let Ok(dir) = app.path().app_data_dir() {
+        let staging = dir.join("pending-delete");
+        // Listed before any new deletion can land there
+        if let Ok(entries) = fs::read_dir(&staging) {
+            leftovers = entries.flatten().map(|e| e.path()).collect();
+        }
+        let _ = app.state::<UndoDeleteState>().staging.set(staging);
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if !leftovers.is_empty() {
+            let _ =
+                tauri::async_runtime::spawn_blocking(move || finalize_leftovers(leftovers)).await;
+        }
+        loop {
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+            let expired = app
+                .state::<UndoDeleteState>()
+                .take(|p| p.deleted_at.elapsed() >= UNDO_WINDOW);
+            if !expired.is_empty() {
+                let _ =
+                    tauri::async_runtime::spawn_blocking(move || finalize_all_of(expired)).await;
+            }
+        }
+    });
+}
+
+/// Finalize the deletions made from window `label` once it is destroyed
+/// and its undo prompts with it. A close request isn't enough: with
+/// `closeToTray` the main window only hides and its undo toast stays live.
+pub fn handle_window_event<R: Runtime>(app: &AppHandle<R>, label: &str, event: &WindowEvent) {
+    if let WindowEvent::Destroyed = event {
+        if let Some(state) = app.try_state::<UndoDeleteState>() {
+            finalize_all_of(state.take(|p| p.window == label));
+        }
+    }
+}
+
+/// Finalize every pending deletion. Called when the app exits.
+pub fn finalize_all(app: &tauri::AppHandle) {
+    if let Some(state) = app.try_state::<UndoDeleteState>() {
+        finalize_all_of(state.take(|_| true));
+    }
+}
+
+/// Delete `path` so that `undo_delete` can bring it back for a while.
+#[tauri::command]
+pub async fn delete_file_with_undo(
+    app: tauri::AppHandle,
+    window: tauri::Window,
+    path: String,
+) -> Result<PendingDelete, CommandError> {
+    let safe_path = validate_path(&path)?;
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        stage(&app.state::<UndoDeleteState>(), &safe_path, label)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Move a deleted item back to where it was. Returns its path.
+#[tauri::command]
+pub async fn undo_delete(app: tauri::AppHandle, token: String) -> Result<String, CommandError> {
+    tauri::async_runtime::spawn_blocking(move || restore(&app.state::<UndoDeleteState>(), &token))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(name: &str) -> (PathBuf, UndoDeleteState) {
+        let dir = std::env::temp_dir().join(format!(
+            "moraya-undo-delete-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes/sub")).unwrap();
+        let state = UndoDeleteState::new();
+        state.staging.set(dir.join("staging")).unwrap();
+        (dir, state)
+    }
+
+    #[test]
+    fn should_stage_and_restore_files_and_folders() {
+        let (dir, state) = setup("restore");
+        let note = dir.join("notes/a.md");
+        fs::write(&note, "alpha").unwrap();
+        fs::write(dir.join("notes/sub/b.md"), "beta").unwrap();
+
+        let file = stage(&state, &note, "main".into()).unwrap();
+        let folder = stage(&state, &dir.join("notes/sub"), "main".into()).unwrap();
+        assert!(!note.exists() && !dir.join("notes/sub").exists());
+        assert_eq!(state.lock().len(), 2);
+
+        assert_eq!(restore(&state, &file.token).unwrap(), path_string(&note));
+        restore(&state, &folder.token).unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "alpha");
+        assert_eq!(
+            fs::read_to_string(dir.join("notes/sub/b.md")).unwrap(),
+            "beta"
+        );
+        assert_eq!(fs::read_dir(dir.join("staging")).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_refuse_to_overwrite_and_keep_the_deletion_pending() {
+        let (dir, state) = setup("occupied");
+        let note = dir.join("notes/a.md");
+        fs::write(&note, "old").unwrap();
+        let pending = stage(&state, &note, "main".into()).unwrap();
+        fs::write(&note, "new").unwrap();
+
+        let err = restore(&state, &pending.token).unwrap_err();
+        assert_eq!(err.code, ErrorCode::FileExists);
+        assert_eq!(fs::read_to_string(&note).unwrap(), "new");
+
+        fs::remove_file(&note).unwrap();
+        restore(&state, &pending.token).unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "old");
+        let err = restore(&state, &pending.token).unwrap_err();
+        assert_eq!(err.code, ErrorCode::FileNotFound);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_keep_deletions_undoable_while_the_window_hides_to_the_tray() {
+        use tauri::test::{mock_builder, mock_context, noop_assets};
+        let (dir, state) = setup("tray");
+        let app = mock_builder()
+            .manage(state)
+            .build(mock_context(noop_assets()))
+            .unwrap();
+        let window = tauri::WebviewWindowBuilder::new(&app, "main", Default::default())
+            .build()
+            .unwrap();
+        let state = app.state::<UndoDeleteState>();
+        let note = dir.join("notes/a.md");
+        fs::write(&note, "alpha").unwrap();
+        let first = stage(&state, &note, "main".into()).unwrap();
+
+        // What `closeToTray` does with the main window's close request: the
+        // close is prevented and the window only hides, so nothing that
+        // reaches this handler ends the undo
+        window.hide().unwrap();
+        handle_window_event(app.handle(), "main", &WindowEvent::Focused(false));
+        assert_eq!(restore(&state, &first.token).unwrap(), path_string(&note));
+
+        // Another window going away leaves this one's deletions alone
+        let second = stage(&state, &note, "main".into()).unwrap();
+        handle_window_event(app.handle(), "moraya-2", &WindowEvent::Destroyed);
+        restore(&state, &second.token).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_take_only_matching_deletions() {
+        let (dir, state) = setup("take");
+        for (name, window) in [("a.md", "main"), ("b.md", "editor-2")] {
+            let path = dir.join("notes").join(name);
+            fs::write(&path, name).unwrap();
+            stage(&state, &path, window.into()).unwrap();
+        }
+        let taken = state.take(|p| p.window == "editor-2");
+        assert_eq!(taken.len(), 1);
+        assert!(taken[0].original.ends_with("b.md"));
+        assert_eq!(state.lock().len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
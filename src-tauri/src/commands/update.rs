@@ -1,12 +1,424 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
 use futures_util::StreamExt;
-use serde::Serialize;
-use tauri::Emitter;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager};
 use tokio::io::AsyncWriteExt;
 
+/// Abort flag for the in-flight update download (only one runs at a time).
+pub struct UpdateDownloadState {
+    abort_flag: AtomicBool,
+    /// SHA-256 of each file `download_update` checked against an expected
+    /// digest, by canonical path. `install_update` only hands off files
+    /// listed here, unchanged.
+    verified: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl UpdateDownloadState {
+    pub fn new() -> Self {
+        Self {
+            abort_flag: AtomicBool::new(false),
+            verified: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_verified<T>(&self, f: impl FnOnce(&mut HashMap<PathBuf, String>) -> T) -> T {
+        match self.verified.lock() {
+            Ok(mut g) => f(&mut g),
+            Err(e) => f(&mut e.into_inner()),
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct PlatformInfo {
     pub os: String,
     pub arch: String,
+    /// How Moraya was installed; `None` off Linux.
+    pub install_kind: Option<LinuxInstallKind>,
+}
+
+/// How Moraya was installed on Linux, which decides the update asset.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinuxInstallKind {
+    Appimage,
+    Deb,
+    Rpm,
+    Flatpak,
+    Snap,
+    Unknown,
+}
+
+impl LinuxInstallKind {
+    /// Flatpak and Snap builds are updated by their store, not in-app.
+    fn package_manager(self) -> Option<&'static str> {
+        match self {
+            LinuxInstallKind::Flatpak => Some("Flatpak"),
+            LinuxInstallKind::Snap => Some("Snap"),
+            _ => None,
+        }
+    }
+}
+
+/// Sandboxes first (their env vars are authoritative), then `$APPIMAGE`,
+/// then ask `owner` which package manager owns an executable under /usr or
+/// /opt.
+fn classify_linux_install(
+    env: impl Fn(&str) -> bool,
+    exe: &Path,
+    owner: impl FnOnce(&Path) -> Option<LinuxInstallKind>,
+) -> LinuxInstallKind {
+    if env("FLATPAK_ID") {
+        LinuxInstallKind::Flatpak
+    } else if env("SNAP") {
+        LinuxInstallKind::Snap
+    } else if env("APPIMAGE") {
+        LinuxInstallKind::Appimage
+    } else if exe.starts_with("/usr") || exe.starts_with("/opt") {
+        owner(exe).unwrap_or(LinuxInstallKind::Unknown)
+    } else {
+        LinuxInstallKind::Unknown
+    }
+}
+
+/// Package manager that owns `exe`, via `dpkg -S` / `rpm -qf`.
+fn package_owner(exe: &Path) -> Option<LinuxInstallKind> {
+    let owns = |program: &str, flag: &str| {
+        Command::new(program)
+            .arg(flag)
+            .arg(exe)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    if owns("dpkg", "-S") {
+        Some(LinuxInstallKind::Deb)
+    } else if owns("rpm", "-qf") {
+        Some(LinuxInstallKind::Rpm)
+    } else {
+        None
+    }
+}
+
+/// Detected once per run; `None` off Linux.
+pub fn detect_linux_install_kind() -> Option<LinuxInstallKind> {
+    static KIND: OnceLock<Option<LinuxInstallKind>> = OnceLock::new();
+    *KIND.get_or_init(|| {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let exe = std::env::current_exe()
+            .and_then(|p| p.canonicalize())
+            .unwrap_or_default();
+        let env = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        let kind = classify_linux_install(env, &exe, package_owner);
+        log::info!("Linux install kind: {:?}", kind);
+        Some(kind)
+    })
+}
+
+const RELEASES_API: &str = "https://api.github.com/repos/zouwei/moraya/releases?per_page=30";
+const UPDATER_USER_AGENT: &str = "Moraya-Updater/1.0";
+const CHECK_TIMEOUT_SECS: u64 = 20;
+/// Installers live in `app_data_dir()/updates` rather than Downloads.
+const UPDATES_DIR: &str = "updates";
+const PARTIAL_SUFFIX: &str = ".part";
+/// A `.part` file untouched this long belongs to no running download.
+const STALE_PARTIAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    html_url: String,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+    #[serde(default)]
+    size: u64,
+    /// `sha256:<hex>`; missing on assets uploaded before GitHub added it.
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+/// Outcome of `check_for_updates`. Failures are reported as data so the
+/// frontend can tell a rate limit from being offline.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum UpdateCheck {
+    #[serde(rename_all = "camelCase")]
+    Checked {
+        available: bool,
+        current_version: String,
+        version: String,
+        notes: String,
+        release_url: String,
+        published_at: Option<String>,
+        prerelease: bool,
+        /// `None` when the release has no installer for this platform.
+        asset_name: Option<String>,
+        asset_url: Option<String>,
+        asset_size: u64,
+        asset_sha256: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Failed {
+        reason: CheckFailure,
+        message: String,
+    },
+    /// Flatpak / Snap installs: updates come from the package manager.
+    #[serde(rename_all = "camelCase")]
+    Managed {
+        current_version: String,
+        install_kind: LinuxInstallKind,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckFailure {
+    RateLimited,
+    Network,
+    Http,
+    InvalidResponse,
+}
+
+fn check_failed(reason: CheckFailure, message: impl Into<String>) -> UpdateCheck {
+    UpdateCheck::Failed {
+        reason,
+        message: message.into(),
+    }
+}
+
+/// Parse a release tag such as `v0.42.0` or `0.43.0-beta.1`.
+fn parse_version(tag: &str) -> Option<semver::Version> {
+    semver::Version::parse(tag.trim().trim_start_matches('v')).ok()
+}
+
+/// Newest non-draft release for `channel`; only `"beta"` includes prereleases.
+fn pick_release<'a>(
+    releases: &'a [GithubRelease],
+    channel: &str,
+) -> Option<(&'a GithubRelease, semver::Version)> {
+    let include_prerelease = channel == "beta";
+    releases
+        .iter()
+        .filter(|r| !r.draft && (include_prerelease || !r.prerelease))
+        .filter_map(|r| parse_version(&r.tag_name).map(|v| (r, v)))
+        .filter(|(_, v)| include_prerelease || v.pre.is_empty())
+        .max_by(|a, b| a.1.cmp(&b.1))
+}
+
+/// Installer for `os`/`arch` (as reported by `get_platform_info`): dmg per
+/// arch on macOS, NSIS setup exe before msi on Windows. On Linux the format
+/// follows `install_kind` (AppImage before deb when unknown). An asset
+/// naming no known arch is accepted as universal.
+fn pick_asset<'a>(
+    assets: &'a [ReleaseAsset],
+    os: &str,
+    arch: &str,
+    install_kind: Option<LinuxInstallKind>,
+) -> Option<&'a ReleaseAsset> {
+    const ARCH_ALIASES: [(&str, &[&str]); 2] = [
+        ("aarch64", &["aarch64", "arm64"]),
+        ("x86_64", &["x64", "x86_64", "amd64"]),
+    ];
+    let exts: &[&str] = match (os, install_kind) {
+        ("macos", _) => &[".dmg"],
+        ("windows", _) => &["-setup.exe", ".msi"],
+        ("linux", Some(LinuxInstallKind::Appimage)) => &[".AppImage"],
+        ("linux", Some(LinuxInstallKind::Deb)) => &[".deb"],
+        ("linux", Some(LinuxInstallKind::Rpm)) => &[".rpm"],
+        ("linux", Some(LinuxInstallKind::Flatpak | LinuxInstallKind::Snap)) => &[],
+        ("linux", _) => &[".AppImage", ".deb"],
+        _ => return None,
+    };
+    let names_arch = |name: &str, aliases: &[&str]| {
+        let name = name.to_ascii_lowercase();
+        aliases.iter().any(|a| name.contains(a))
+    };
+    let ours: &[&str] = ARCH_ALIASES
+        .iter()
+        .find(|(a, _)| *a == arch)
+        .map(|(_, aliases)| *aliases)
+        .unwrap_or(&[]);
+    let any_arch = |name: &str| ARCH_ALIASES.iter().any(|(_, al)| names_arch(name, al));
+
+    exts.iter().find_map(|ext| {
+        let with_ext = || assets.iter().filter(move |a| a.name.ends_with(ext));
+        with_ext()
+            .find(|a| names_arch(&a.name, ours))
+            .or_else(|| with_ext().find(|a| !any_arch(&a.name)))
+    })
+}
+
+/// Map a non-success releases API response to a failure. GitHub signals
+/// rate limits with 429, or 403 plus `x-ratelimit-remaining: 0`.
+fn classify_http_failure(status: u16, remaining: Option<&str>, reset: Option<i64>) -> UpdateCheck {
+    if status == 429 || (status == 403 && remaining == Some("0")) {
+        let when = reset
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|t| format!(" until {}", t.format("%H:%M UTC")))
+            .unwrap_or_default();
+        return check_failed(
+            CheckFailure::RateLimited,
+            format!("GitHub API rate limit exceeded{}. Please try again later.", when),
+        );
+    }
+    check_failed(CheckFailure::Http, format!("GitHub API error: HTTP {}", status))
+}
+
+fn evaluate_releases(
+    releases: &[GithubRelease],
+    channel: &str,
+    current: &semver::Version,
+    os: &str,
+    arch: &str,
+    install_kind: Option<LinuxInstallKind>,
+) -> UpdateCheck {
+    let Some((release, version)) = pick_release(releases, channel) else {
+        return check_failed(
+            CheckFailure::InvalidResponse,
+            format!("No {} release found", channel),
+        );
+    };
+    let asset = pick_asset(&release.assets, os, arch, install_kind);
+    UpdateCheck::Checked {
+        available: version > *current,
+        current_version: current.to_string(),
+        version: version.to_string(),
+        notes: release.body.clone().unwrap_or_default(),
+        release_url: release.html_url.clone(),
+        published_at: release.published_at.clone(),
+        prerelease: release.prerelease || !version.pre.is_empty(),
+        asset_name: asset.map(|a| a.name.clone()),
+        asset_url: asset.map(|a| a.browser_download_url.clone()),
+        asset_size: asset.map_or(0, |a| a.size),
+        asset_sha256: asset
+            .and_then(|a| a.digest.clone())
+            .filter(|d| d.starts_with("sha256:")),
+    }
+}
+
+/// Query GitHub releases for `channel` (`"stable"` or `"beta"`) and compare
+/// against the running version. Never fails; see `UpdateCheck::Failed`.
+/// Flatpak / Snap installs get `UpdateCheck::Managed` without a request.
+/// Uses the `proxyUrl` setting, falling back to the system `HTTP(S)_PROXY`.
+#[tauri::command]
+pub async fn check_for_updates(app: tauri::AppHandle, channel: Option<String>) -> UpdateCheck {
+    let channel = channel.as_deref().unwrap_or("stable");
+    if channel != "stable" && channel != "beta" {
+        return check_failed(
+            CheckFailure::InvalidResponse,
+            format!("Unknown update channel: {}", channel),
+        );
+    }
+    let install_kind = tokio::task::spawn_blocking(detect_linux_install_kind)
+        .await
+        .unwrap_or(None);
+    if let Some(kind) = install_kind {
+        if let Some(manager) = kind.package_manager() {
+            return UpdateCheck::Managed {
+                current_version: app.package_info().version.to_string(),
+                install_kind: kind,
+                message: format!(
+                    "Moraya is managed by {}; update it through your package manager.",
+                    manager
+                ),
+            };
+        }
+    }
+    let client = match super::settings::client_builder()
+        .user_agent(UPDATER_USER_AGENT)
+        .timeout(std::time::Duration::from_secs(CHECK_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            let message = format!("Failed to create HTTP client: {}", e);
+            return check_failed(CheckFailure::Network, message);
+        }
+    };
+    let response = match client
+        .get(RELEASES_API)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            let message = if e.is_timeout() {
+                "Update check timed out".to_string()
+            } else {
+                format!("Update check failed: {}", e)
+            };
+            log::warn!("{}", message);
+            return check_failed(CheckFailure::Network, message);
+        }
+    };
+    let status = response.status().as_u16();
+    if !response.status().is_success() {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let remaining = header("x-ratelimit-remaining");
+        let reset = header("x-ratelimit-reset").and_then(|v| v.parse().ok());
+        return classify_http_failure(status, remaining.as_deref(), reset);
+    }
+    let releases: Vec<GithubRelease> = match response.json().await {
+        Ok(r) => r,
+        Err(e) => {
+            return check_failed(
+                CheckFailure::InvalidResponse,
+                format!("Unexpected releases response: {}", e),
+            )
+        }
+    };
+    let current = &app.package_info().version;
+    evaluate_releases(
+        &releases,
+        channel,
+        current,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        install_kind,
+    )
+}
+
+#[derive(Serialize)]
+pub struct DownloadedUpdate {
+    pub path: String,
+    /// Lowercase hex SHA-256 of the downloaded file.
+    pub sha256: String,
+    /// True when a matching file from an earlier attempt was used as-is.
+    pub reused: bool,
 }
 
 #[derive(Clone, Serialize)]
@@ -14,6 +426,71 @@ struct DownloadProgress {
     received: u64,
     total: u64,
     progress: u32,
+    bytes_per_sec: u64,
+    /// `None` until a rate is known or when the total size is unknown.
+    eta_secs: Option<u64>,
+}
+
+/// Speed is averaged over this trailing window, not the whole transfer.
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+/// At most ~4 progress events per second.
+const PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Sliding-window transfer rate and progress-event throttle, shared by the
+/// update and plugin downloads.
+pub(crate) struct TransferMeter {
+    /// (time, cumulative bytes); the front sample is the window baseline.
+    samples: VecDeque<(Instant, u64)>,
+    last_emit: Option<Instant>,
+}
+
+impl TransferMeter {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            samples: VecDeque::from([(now, 0)]),
+            last_emit: None,
+        }
+    }
+
+    /// Record the cumulative byte count at `now`.
+    pub(crate) fn record(&mut self, now: Instant, received: u64) {
+        self.samples.push_back((now, received));
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    pub(crate) fn bytes_per_sec(&self) -> u64 {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return 0;
+        };
+        let elapsed = last.0.duration_since(first.0).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0;
+        }
+        ((last.1 - first.1) as f64 / elapsed) as u64
+    }
+
+    pub(crate) fn eta_secs(&self, received: u64, total: u64) -> Option<u64> {
+        let rate = self.bytes_per_sec();
+        if rate == 0 || total == 0 {
+            return None;
+        }
+        Some(total.saturating_sub(received).div_ceil(rate))
+    }
+
+    /// Whether a progress event is due at `now`; `finished` always emits.
+    pub(crate) fn should_emit(&mut self, now: Instant, finished: bool) -> bool {
+        let due = finished
+            || match self.last_emit {
+                Some(last) => now.duration_since(last) >= PROGRESS_EMIT_INTERVAL,
+                None => true,
+            };
+        if due {
+            self.last_emit = Some(now);
+        }
+        due
+    }
 }
 
 #[tauri::command]
@@ -21,6 +498,7 @@ pub fn get_platform_info() -> PlatformInfo {
     PlatformInfo {
         os: std::env::consts::OS.to_string(),
         arch: std::env::consts::ARCH.to_string(),
+        install_kind: detect_linux_install_kind(),
     }
 }
 
@@ -29,161 +507,1022 @@ pub fn exit_app(app: tauri::AppHandle) {
     app.exit(0);
 }
 
-/// Download a file from `url` into the user's Downloads folder as `filename`.
+/// Normalize an expected digest: accepts GitHub's `sha256:<hex>` form and
+/// any case. Returns `None` when the value is not a SHA-256 hex string.
+fn normalize_sha256(expected: &str) -> Option<String> {
+    let hex = expected.trim();
+    let hex = hex.strip_prefix("sha256:").unwrap_or(hex).to_ascii_lowercase();
+    (hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit())).then_some(hex)
+}
+
+/// `app_data_dir()/updates`, created on demand.
+fn updates_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map(|d| d.join(UPDATES_DIR))
+        .map_err(|_| "Cannot resolve app data directory".to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hash of `path` when it already holds the expected update. Needs at least
+/// one of `expected_size` / `expected_sha256` to compare against.
+fn existing_download(
+    path: &Path,
+    expected_size: Option<u64>,
+    expected_sha256: Option<&str>,
+) -> Option<String> {
+    if expected_size.is_none() && expected_sha256.is_none() {
+        return None;
+    }
+    let meta = std::fs::metadata(path).ok()?;
+    if !meta.is_file() || expected_size.is_some_and(|size| size != meta.len()) {
+        return None;
+    }
+    let sha256 = sha256_file(path).ok()?;
+    if let Some(expected) = expected_sha256 {
+        if expected != sha256 {
+            return None;
+        }
+    }
+    Some(sha256)
+}
+
+/// Download the update `filename` from `url` into `app_data_dir()/updates`.
 /// Emits `download-progress` events with { received, total, progress } payload.
-/// Returns the full path of the downloaded file.
+/// A file already there that matches `expected_size` / `expected_sha256` is
+/// reused without downloading. Otherwise the body streams into a `.part`
+/// file that only takes the final name once the size and checksum check out;
+/// it is deleted on mismatch, failure or `cancel_update_download`.
+/// `launch: false` skips opening the installer so the caller can use
+/// `install_update`. Returns the full path, computed hash and whether the
+/// file was reused.
 #[tauri::command]
 pub async fn download_update(
     app: tauri::AppHandle,
+    state: tauri::State<'_, UpdateDownloadState>,
     url: String,
     filename: String,
-) -> Result<String, String> {
-    println!("[update] Starting download: {}", url);
-    println!("[update] Filename: {}", filename);
+    expected_sha256: Option<String>,
+    expected_size: Option<u64>,
+    launch: Option<bool>,
+) -> Result<DownloadedUpdate, String> {
+    log::info!("Starting download: {}", url);
+    log::debug!("Filename: {}", filename);
+
+    if filename.is_empty() || filename.starts_with('.') || filename.contains(['/', '\\']) {
+        return Err(format!("Invalid update filename: {}", filename));
+    }
+    let expected_sha256 = match expected_sha256.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(raw) => Some(
+            normalize_sha256(raw).ok_or_else(|| format!("Invalid expected SHA-256: {}", raw))?,
+        ),
+    };
+    let expected_size = expected_size.filter(|size| *size > 0);
+    state.abort_flag.store(false, Ordering::SeqCst);
 
-    // Resolve Downloads directory
-    let download_dir = dirs::download_dir()
-        .ok_or_else(|| "Cannot resolve Downloads directory".to_string())?;
-    let dest_path = download_dir.join(&filename);
-    println!("[update] Destination: {}", dest_path.display());
+    let dest_path = updates_dir(&app)?.join(&filename);
+    log::debug!("Destination: {}", dest_path.display());
 
+    let check_path = dest_path.clone();
+    let check_sha256 = expected_sha256.clone();
+    let existing = tokio::task::spawn_blocking(move || {
+        existing_download(&check_path, expected_size, check_sha256.as_deref())
+    })
+    .await
+    .ok()
+    .flatten();
+    let reused = existing.is_some();
+    let sha256 = match existing {
+        Some(sha256) => {
+            log::info!("Reusing verified download: {}", dest_path.display());
+            sha256
+        }
+        None => {
+            fetch_update(
+                &app,
+                &state.abort_flag,
+                &url,
+                &dest_path,
+                expected_size,
+                expected_sha256.as_deref(),
+            )
+            .await?
+        }
+    };
+    log::info!("SHA-256: {}", sha256);
+    // Without an expected digest the file is only as good as its URL
+    if let (Some(_), Ok(canonical)) = (&expected_sha256, dest_path.canonicalize()) {
+        state.with_verified(|verified| verified.insert(canonical, sha256.clone()));
+    }
+
+    let full_path = dest_path.to_string_lossy().into_owned();
+    log::info!("Download complete: {}", full_path);
+
+    // Linux: AppImage files need executable permission before opening
+    #[cfg(target_os = "linux")]
+    if filename.ends_with(".AppImage") {
+        use std::os::unix::fs::PermissionsExt;
+        log::debug!("Setting executable permission on AppImage...");
+        let _ = std::fs::set_permissions(&dest_path, std::fs::Permissions::from_mode(0o755));
+    }
+
+    let downloaded = DownloadedUpdate {
+        path: full_path,
+        sha256,
+        reused,
+    };
+    if !launch.unwrap_or(true) {
+        return Ok(downloaded);
+    }
+
+    // Open the installer with the OS default handler
+    // macOS: mounts DMG → user drags .app to /Applications
+    // Windows: launches NSIS/MSI installer → replaces app files
+    // Linux: opens AppImage/deb with default handler
+    log::info!("Opening installer...");
+    match open::that(&dest_path) {
+        Ok(()) => {
+            log::info!("Installer opened successfully");
+            schedule_exit(&app);
+        }
+        Err(e) => log::error!("Failed to open installer: {} (file is at {})", e, downloaded.path),
+    }
+
+    Ok(downloaded)
+}
+
+/// Download `url` to `dest_path` via a `.part` file. Returns the hex SHA-256.
+async fn fetch_update(
+    app: &tauri::AppHandle,
+    abort_flag: &AtomicBool,
+    url: &str,
+    dest_path: &Path,
+    expected_size: Option<u64>,
+    expected_sha256: Option<&str>,
+) -> Result<String, String> {
     // Build HTTP client with proper User-Agent (GitHub CDN rejects bare requests)
-    let client = reqwest::Client::builder()
-        .user_agent("Moraya-Updater/1.0")
+    let client = super::settings::client_builder()
+        .user_agent(UPDATER_USER_AGENT)
         .timeout(std::time::Duration::from_secs(300))
         .build()
         .map_err(|e| {
             let msg = format!("Failed to create HTTP client: {}", e);
-            eprintln!("[update] ERROR: {}", msg);
+            log::error!("{}", msg);
             msg
         })?;
 
-    println!("[update] Sending HTTP request...");
+    log::debug!("Sending HTTP request...");
     let response = client
-        .get(&url)
+        .get(url)
         .send()
         .await
         .map_err(|e| {
             let msg = format!("HTTP request failed: {}", e);
-            eprintln!("[update] ERROR: {}", msg);
+            log::error!("{}", msg);
             msg
         })?;
 
-    println!("[update] Response status: {}", response.status());
-    println!("[update] Response headers: {:?}", response.headers());
+    log::debug!("Response status: {}", response.status());
+    log::debug!("Response headers: {:?}", response.headers());
 
     if !response.status().is_success() {
         let msg = format!("Download failed: HTTP {}", response.status());
-        eprintln!("[update] ERROR: {}", msg);
+        log::error!("{}", msg);
         return Err(msg);
     }
 
     let total = response.content_length().unwrap_or(0);
-    println!("[update] Content-Length: {} bytes ({:.2} MB)", total, total as f64 / 1024.0 / 1024.0);
+    log::info!("Content-Length: {} bytes ({:.2} MB)", total, total as f64 / 1024.0 / 1024.0);
 
-    // Create destination file
-    let mut file = tokio::fs::File::create(&dest_path)
+    // Create the partial file; it only takes the final name once verified
+    let mut part_name = dest_path.as_os_str().to_os_string();
+    part_name.push(PARTIAL_SUFFIX);
+    let part_path = PathBuf::from(part_name);
+    let mut file = tokio::fs::File::create(&part_path)
         .await
         .map_err(|e| {
-            let msg = format!("Failed to create file {}: {}", dest_path.display(), e);
-            eprintln!("[update] ERROR: {}", msg);
+            let msg = format!("Failed to create file {}: {}", part_path.display(), e);
+            log::error!("{}", msg);
             msg
         })?;
-    println!("[update] File created, starting stream download...");
+    log::debug!("File created, starting stream download...");
+
+    let streamed = stream_to_file(app, abort_flag, response, &mut file, total).await;
+    drop(file);
+    let verified = streamed.and_then(|(sha256, received)| {
+        if let Some(size) = expected_size.filter(|size| *size != received) {
+            return Err(format!(
+                "Size mismatch: expected {} bytes, got {}. The download was discarded.",
+                size, received
+            ));
+        }
+        match expected_sha256 {
+            Some(expected) if expected != sha256 => Err(format!(
+                "Checksum mismatch: expected {}, got {}. The download was discarded.",
+                expected, sha256
+            )),
+            Some(_) => {
+                log::info!("Checksum verified");
+                Ok(sha256)
+            }
+            None => Ok(sha256),
+        }
+    });
+    let result = match verified {
+        Ok(sha256) => tokio::fs::rename(&part_path, dest_path)
+            .await
+            .map(|_| sha256)
+            .map_err(|e| format!("Failed to move download into place: {}", e)),
+        Err(msg) => Err(msg),
+    };
+    if let Err(msg) = &result {
+        log::error!("{}", msg);
+        let _ = tokio::fs::remove_file(&part_path).await;
+    }
+    result
+}
 
-    // Stream response body directly to disk
+/// Stream `response` into `file`, hashing as it goes. Returns the hex
+/// SHA-256 and the number of bytes written.
+async fn stream_to_file(
+    app: &tauri::AppHandle,
+    abort_flag: &AtomicBool,
+    response: reqwest::Response,
+    file: &mut tokio::fs::File,
+    total: u64,
+) -> Result<(String, u64), String> {
     let mut stream = response.bytes_stream();
+    let mut hasher = Sha256::new();
     let mut received: u64 = 0;
-    let mut last_progress: u32 = 0;
+    let mut last_logged: u32 = 0;
     let mut chunk_count: u64 = 0;
+    let mut meter = TransferMeter::new(Instant::now());
 
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result
-            .map_err(|e| {
-                let msg = format!("Download stream error at {} bytes (chunk #{}): {}", received, chunk_count, e);
-                eprintln!("[update] ERROR: {}", msg);
-                msg
-            })?;
+    loop {
+        // Poll the abort flag while waiting so a stalled connection can
+        // still be cancelled.
+        let abort_wait = async {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                if abort_flag.load(Ordering::SeqCst) {
+                    return;
+                }
+            }
+        };
+        let next = tokio::select! {
+            c = stream.next() => c,
+            _ = abort_wait => None,
+        };
+        if abort_flag.load(Ordering::SeqCst) {
+            log::info!("Download cancelled at {} bytes", received);
+            return Err("Download cancelled".to_string());
+        }
+        let Some(chunk_result) = next else { break };
+        let chunk = chunk_result.map_err(|e| {
+            format!(
+                "Download stream error at {} bytes (chunk #{}): {}",
+                received, chunk_count, e
+            )
+        })?;
         file.write_all(&chunk)
             .await
-            .map_err(|e| {
-                let msg = format!("File write error at {} bytes: {}", received, e);
-                eprintln!("[update] ERROR: {}", msg);
-                msg
-            })?;
+            .map_err(|e| format!("File write error at {} bytes: {}", received, e))?;
+        hasher.update(&chunk);
         received += chunk.len() as u64;
         chunk_count += 1;
 
+        let now = Instant::now();
+        meter.record(now, received);
+        let progress = if total > 0 {
+            ((received as f64 / total as f64) * 100.0) as u32
+        } else {
+            0
+        };
+        // Print progress every 10%
+        if progress / 10 > last_logged / 10 {
+            last_logged = progress;
+            log::debug!("Progress: {}% ({}/{} bytes, {} chunks)", progress, received, total, chunk_count);
+        }
         // Emit progress events (throttle to avoid flooding)
-        if total > 0 {
-            let progress = ((received as f64 / total as f64) * 100.0) as u32;
-            if progress != last_progress {
-                last_progress = progress;
-                // Print progress every 10%
-                if progress % 10 == 0 {
-                    println!("[update] Progress: {}% ({}/{} bytes, {} chunks)", progress, received, total, chunk_count);
-                }
-                let _ = app.emit(
-                    "download-progress",
-                    DownloadProgress {
-                        received,
-                        total,
-                        progress,
-                    },
-                );
-            }
+        if meter.should_emit(now, total > 0 && received >= total) {
+            let _ = app.emit(
+                "download-progress",
+                DownloadProgress {
+                    received,
+                    total,
+                    progress,
+                    bytes_per_sec: meter.bytes_per_sec(),
+                    eta_secs: meter.eta_secs(received, total),
+                },
+            );
         }
     }
 
-    println!("[update] Stream finished. Total received: {} bytes in {} chunks", received, chunk_count);
+    log::info!("Stream finished. Total received: {} bytes in {} chunks", received, chunk_count);
 
     file.flush()
         .await
-        .map_err(|e| {
-            let msg = format!("File flush error: {}", e);
-            eprintln!("[update] ERROR: {}", msg);
-            msg
-        })?;
+        .map_err(|e| format!("File flush error: {}", e))?;
 
     // Verify file size if content-length was known
     if total > 0 && received != total {
-        let msg = format!("Download incomplete: received {} of {} bytes", received, total);
-        eprintln!("[update] ERROR: {}", msg);
-        return Err(msg);
+        return Err(format!("Download incomplete: received {} of {} bytes", received, total));
     }
 
-    let full_path = dest_path.to_string_lossy().into_owned();
-    println!("[update] Download complete: {}", full_path);
+    Ok((hex::encode(hasher.finalize()), received))
+}
 
-    // Linux: AppImage files need executable permission before opening
+/// Cancel the in-flight `download_update`; its partial file is deleted.
+#[tauri::command]
+pub fn cancel_update_download(state: tauri::State<'_, UpdateDownloadState>) {
+    state.abort_flag.store(true, Ordering::SeqCst);
+}
+
+/// Delete installers in `dir` except `keep` and the newest files (by
+/// modification time) up to `keep_latest` in total. `.part` files are left
+/// to a running download unless stale. Returns how many files were removed.
+fn prune_updates(dir: &Path, keep_latest: usize, keep: Option<&Path>) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    let mut installers = Vec::new();
+    let mut removed = 0;
+    for entry in entries.filter_map(Result::ok) {
+        let Ok(meta) = entry.metadata() else { continue };
+        if !meta.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        if path.to_string_lossy().ends_with(PARTIAL_SUFFIX) {
+            let stale = now.duration_since(modified).unwrap_or_default() > STALE_PARTIAL;
+            if stale && std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+            continue;
+        }
+        installers.push((path, modified));
+    }
+
+    installers.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    let mut kept = usize::from(keep.is_some_and(|k| installers.iter().any(|(p, _)| p == k)));
+    for (path, _) in installers {
+        if keep == Some(path.as_path()) {
+            continue;
+        }
+        if kept < keep_latest {
+            kept += 1;
+            continue;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            log::info!("Removed old installer: {}", path.display());
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Remove old installers from `app_data_dir()/updates`, keeping the
+/// `keep_latest` newest (default 1). Returns the number of files removed.
+#[tauri::command]
+pub async fn cleanup_old_updates(
+    app: tauri::AppHandle,
+    keep_latest: Option<usize>,
+) -> Result<usize, String> {
+    let dir = updates_dir(&app)?;
+    tokio::task::spawn_blocking(move || prune_updates(&dir, keep_latest.unwrap_or(1), None))
+        .await
+        .map_err(|e| format!("Cleanup task failed: {}", e))
+}
+
+/// Auto-exit after brief delay so frontend can show completion message.
+/// macOS: frees /Applications/Moraya.app so drag-replace works
+/// Windows: releases locked DLLs/EXEs so NSIS/MSI can overwrite
+/// Linux: avoids duplicate AppImage instances
+fn schedule_exit(app: &tauri::AppHandle) {
+    let app_handle = app.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        log::info!("Auto-exiting for update installation...");
+        app_handle.exit(0);
+    });
+}
+
+/// How `install_update` handed the update off.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum InstallMethod {
+    /// Opened with the OS default handler (DMG mounted, installer wizard).
+    Opened,
+    /// Windows installer runs unattended once Moraya has exited.
+    Silent,
+    /// New build copied over the installed app or AppImage.
+    Replaced,
+}
+
+/// Install a downloaded update, then exit Moraya.
+///
+/// With `silent`: Windows runs the NSIS installer with `/S` (MSI with `/qn`)
+/// once Moraya has exited; macOS copies the .app from the DMG into
+/// /Applications when that is writable; Linux replaces the running AppImage
+/// (`$APPIMAGE`). Anything else falls back to opening the file as before.
+/// `relaunch` starts the new version afterwards. On failure the downloaded
+/// file is left in place and the error names it.
+///
+/// Only a file `download_update` checked against `expected_sha256` in this
+/// session is accepted, and only while it still has that SHA-256.
+#[tauri::command]
+pub async fn install_update(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, UpdateDownloadState>,
+    path: String,
+    silent: Option<bool>,
+    relaunch: Option<bool>,
+) -> Result<InstallMethod, String> {
+    let updates = updates_dir(&app)?;
+    let installer = PathBuf::from(&path);
+    let expected = installer
+        .canonicalize()
+        .ok()
+        .and_then(|p| state.with_verified(|verified| verified.get(&p).cloned()));
+    let silent = silent.unwrap_or(false);
+    let relaunch = relaunch.unwrap_or(false);
+    log::info!("Installing {} (silent: {}, relaunch: {})", path, silent, relaunch);
+
+    let method = tokio::task::spawn_blocking(move || {
+        let installer = checked_installer(&installer, &updates, expected.as_deref())?;
+        let method = install_for_platform(&installer, silent, relaunch)?;
+        prune_updates(&updates, 1, Some(&installer));
+        Ok(method)
+    })
+    .await
+    .map_err(|e| format!("Install task failed: {}", e))
+    .and_then(|r| r)
+    .map_err(|e: String| {
+        let msg = format!("{} The downloaded update is still at {}.", e, path);
+        log::error!("{}", msg);
+        msg
+    })?;
+    log::info!("Install handed off: {:?}", method);
+    schedule_exit(&app);
+    Ok(method)
+}
+
+/// The canonical path of `installer` when it lies in `updates` and still
+/// hashes to `expected`, the digest recorded when it was downloaded.
+fn checked_installer(
+    installer: &Path,
+    updates: &Path,
+    expected: Option<&str>,
+) -> Result<PathBuf, String> {
+    let resolved = installer
+        .canonicalize()
+        .ok()
+        .filter(|p| p.is_file())
+        .ok_or_else(|| "Downloaded update not found.".to_string())?;
+    let updates = updates
+        .canonicalize()
+        .map_err(|e| format!("Cannot resolve {}: {}.", updates.display(), e))?;
+    if !resolved.starts_with(&updates) {
+        return Err(format!(
+            "Refusing to install a file outside {}.",
+            updates.display()
+        ));
+    }
+    let expected = expected
+        .ok_or_else(|| "This file wasn't downloaded with a verified checksum.".to_string())?;
+    let actual = sha256_file(&resolved).map_err(|e| format!("Cannot read the update: {}.", e))?;
+    if actual != expected {
+        return Err(format!(
+            "The update changed since it was downloaded (SHA-256 {} instead of {}).",
+            actual, expected
+        ));
+    }
+    Ok(resolved)
+}
+
+fn install_for_platform(installer: &Path, silent: bool, relaunch: bool) -> Result<InstallMethod, String> {
+    #[cfg(target_os = "windows")]
+    if silent {
+        return install_windows_silent(installer, relaunch);
+    }
+    #[cfg(target_os = "macos")]
+    if silent && has_extension(installer, "dmg") && applications_writable() {
+        return install_macos_copy(installer, relaunch);
+    }
     #[cfg(target_os = "linux")]
-    if filename.ends_with(".AppImage") {
-        use std::os::unix::fs::PermissionsExt;
-        println!("[update] Setting executable permission on AppImage...");
-        let _ = std::fs::set_permissions(&dest_path, std::fs::Permissions::from_mode(0o755));
+    if silent && has_extension(installer, "AppImage") {
+        if let Some(current) = std::env::var_os("APPIMAGE") {
+            return replace_appimage(installer, Path::new(&current), relaunch);
+        }
     }
+    let _ = (silent, relaunch);
+    open::that(installer).map_err(|e| format!("Failed to open the installer: {}.", e))?;
+    Ok(InstallMethod::Opened)
+}
 
-    // Open the installer with the OS default handler
-    // macOS: mounts DMG → user drags .app to /Applications
-    // Windows: launches NSIS/MSI installer → replaces app files
-    // Linux: opens AppImage/deb with default handler
-    println!("[update] Opening installer...");
-    match open::that(&dest_path) {
-        Ok(()) => {
-            println!("[update] Installer opened successfully");
-            // Auto-exit after brief delay so frontend can show completion message.
-            // macOS: frees /Applications/Moraya.app so drag-replace works
-            // Windows: releases locked DLLs/EXEs so NSIS/MSI can overwrite
-            // Linux: avoids duplicate AppImage instances
-            let app_handle = app.clone();
-            tokio::spawn(async move {
-                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                println!("[update] Auto-exiting for update installation...");
-                app_handle.exit(0);
-            });
-        }
-        Err(e) => eprintln!("[update] Failed to open installer: {} (file is in Downloads)", e),
-    }
-
-    Ok(full_path)
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case(ext))
+}
+
+/// Single-quote for PowerShell (embedded quotes are doubled).
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// PowerShell script that waits for process `pid` to exit, runs the
+/// installer unattended and optionally starts `relaunch`.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn windows_install_script(installer: &Path, pid: u32, relaunch: Option<&Path>) -> Result<String, String> {
+    let installer_path = installer.to_string_lossy();
+    let install = if has_extension(installer, "msi") {
+        let args = format!("/i \"{}\" /qn /norestart", installer_path);
+        format!("Start-Process -FilePath 'msiexec.exe' -ArgumentList {} -Wait", ps_quote(&args))
+    } else if has_extension(installer, "exe") {
+        format!("Start-Process -FilePath {} -ArgumentList '/S' -Wait", ps_quote(&installer_path))
+    } else {
+        return Err(format!(
+            "Silent install needs an .exe or .msi installer, got {}.",
+            installer.display()
+        ));
+    };
+    let mut script = format!("Wait-Process -Id {} -ErrorAction SilentlyContinue; {}", pid, install);
+    if let Some(exe) = relaunch {
+        script.push_str(&format!("; Start-Process -FilePath {}", ps_quote(&exe.to_string_lossy())));
+    }
+    Ok(script)
+}
+
+#[cfg(target_os = "windows")]
+fn install_windows_silent(installer: &Path, relaunch: bool) -> Result<InstallMethod, String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+    let exe = if relaunch {
+        Some(std::env::current_exe().map_err(|e| format!("Cannot locate Moraya.exe: {}.", e))?)
+    } else {
+        None
+    };
+    let script = windows_install_script(installer, std::process::id(), exe.as_deref())?;
+    Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-ExecutionPolicy", "Bypass", "-Command", script.as_str()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP)
+        .spawn()
+        .map_err(|e| format!("Failed to start the installer: {}.", e))?;
+    Ok(InstallMethod::Silent)
+}
+
+/// Start `command` once this process has exited, detached from our group.
+#[cfg(unix)]
+fn spawn_after_exit(command: &[&std::ffi::OsStr]) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+    const WAIT_THEN_EXEC: &str =
+        r#"while kill -0 "$1" 2>/dev/null; do sleep 0.2; done; shift; exec "$@""#;
+    Command::new("/bin/sh")
+        .args(["-c", WAIT_THEN_EXEC, "sh", &std::process::id().to_string()])
+        .args(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to schedule relaunch: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn applications_writable() -> bool {
+    let probe = Path::new("/Applications").join(format!(".moraya-write-test-{}", std::process::id()));
+    let writable = std::fs::File::create(&probe).is_ok();
+    let _ = std::fs::remove_file(&probe);
+    writable
+}
+
+#[cfg(target_os = "macos")]
+fn run_tool(program: &str, args: &[&std::ffi::OsStr]) -> Result<(), String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}.", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} failed: {}.",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Mount the DMG, copy its .app into /Applications and unmount.
+#[cfg(target_os = "macos")]
+fn install_macos_copy(dmg: &Path, relaunch: bool) -> Result<InstallMethod, String> {
+    use std::ffi::OsStr;
+
+    let mount = std::env::temp_dir().join(format!("moraya-update-{}", std::process::id()));
+    std::fs::create_dir_all(&mount).map_err(|e| format!("Cannot create mount point: {}.", e))?;
+    run_tool(
+        "hdiutil",
+        &[
+            OsStr::new("attach"),
+            dmg.as_os_str(),
+            OsStr::new("-nobrowse"),
+            OsStr::new("-readonly"),
+            OsStr::new("-noautoopen"),
+            OsStr::new("-mountpoint"),
+            mount.as_os_str(),
+        ],
+    )?;
+    let copied = copy_app_bundle(&mount, Path::new("/Applications"));
+    let _ = run_tool("hdiutil", &[OsStr::new("detach"), mount.as_os_str(), OsStr::new("-force")]);
+    let _ = std::fs::remove_dir(&mount);
+    let target = copied?;
+    if relaunch {
+        if let Err(e) = spawn_after_exit(&[OsStr::new("open"), target.as_os_str()]) {
+            log::warn!("{}", e);
+        }
+    }
+    Ok(InstallMethod::Replaced)
+}
+
+/// Copy the first .app in `volume` over the same-named bundle in `dest`.
+/// The old bundle is moved aside first and restored if the swap fails.
+#[cfg(target_os = "macos")]
+fn copy_app_bundle(volume: &Path, dest: &Path) -> Result<PathBuf, String> {
+    let app = std::fs::read_dir(volume)
+        .map_err(|e| format!("Cannot read the disk image: {}.", e))?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .find(|p| has_extension(p, "app"))
+        .ok_or_else(|| "No .app bundle found in the disk image.".to_string())?;
+    let name = app.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let target = dest.join(&name);
+    let staging = dest.join(format!(".{}.update", name));
+    let backup = dest.join(format!(".{}.old", name));
+    let _ = std::fs::remove_dir_all(&staging);
+    let _ = std::fs::remove_dir_all(&backup);
+
+    run_tool("ditto", &[app.as_os_str(), staging.as_os_str()]).inspect_err(|_| {
+        let _ = std::fs::remove_dir_all(&staging);
+    })?;
+    let had_old = target.exists();
+    if had_old {
+        std::fs::rename(&target, &backup).map_err(|e| {
+            let _ = std::fs::remove_dir_all(&staging);
+            format!("Cannot replace {}: {}.", target.display(), e)
+        })?;
+    }
+    if let Err(e) = std::fs::rename(&staging, &target) {
+        if had_old {
+            let _ = std::fs::rename(&backup, &target);
+        }
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(format!("Cannot replace {}: {}.", target.display(), e));
+    }
+    let _ = std::fs::remove_dir_all(&backup);
+    Ok(target)
+}
+
+/// Hidden sibling the new AppImage is staged in before the atomic rename.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn appimage_staging_path(current: &Path) -> PathBuf {
+    let name = current.file_name().unwrap_or_default().to_string_lossy();
+    current.with_file_name(format!(".{}.update", name))
+}
+
+#[cfg(target_os = "linux")]
+fn replace_appimage(new: &Path, current: &Path, relaunch: bool) -> Result<InstallMethod, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let staging = appimage_staging_path(current);
+    let staged = std::fs::copy(new, &staging)
+        .and_then(|_| std::fs::set_permissions(&staging, std::fs::Permissions::from_mode(0o755)))
+        .and_then(|_| std::fs::rename(&staging, current));
+    if let Err(e) = staged {
+        let _ = std::fs::remove_file(&staging);
+        return Err(format!("Cannot replace {}: {}.", current.display(), e));
+    }
+    if relaunch {
+        if let Err(e) = spawn_after_exit(&[current.as_os_str()]) {
+            log::warn!("{}", e);
+        }
+    }
+    Ok(InstallMethod::Replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TempDir;
+
+    fn asset(name: &str) -> ReleaseAsset {
+        ReleaseAsset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 1,
+            digest: None,
+        }
+    }
+
+    fn release(tag: &str, prerelease: bool, assets: &[&str]) -> GithubRelease {
+        GithubRelease {
+            tag_name: tag.to_string(),
+            body: Some(format!("notes {}", tag)),
+            html_url: String::new(),
+            published_at: None,
+            prerelease,
+            draft: false,
+            assets: assets.iter().map(|n| asset(n)).collect(),
+        }
+    }
+
+    #[test]
+    fn should_pick_release_by_channel_and_semver() {
+        let releases = vec![
+            release("v0.41.1", false, &[]),
+            release("v0.43.0-beta.2", true, &[]),
+            release("v0.42.0", false, &[]),
+            release("v0.43.0-beta.10", true, &[]),
+            release("nightly", true, &[]),
+        ];
+        let (stable, _) = pick_release(&releases, "stable").unwrap();
+        assert_eq!(stable.tag_name, "v0.42.0");
+        let (beta, v) = pick_release(&releases, "beta").unwrap();
+        assert_eq!(beta.tag_name, "v0.43.0-beta.10");
+        assert_eq!(v.to_string(), "0.43.0-beta.10");
+
+        let current = semver::Version::parse("0.42.0").unwrap();
+        let UpdateCheck::Checked { available, .. } =
+            evaluate_releases(&releases, "stable", &current, "linux", "x86_64", None)
+        else {
+            panic!("expected a checked result");
+        };
+        assert!(!available);
+        let UpdateCheck::Checked { available, prerelease, .. } =
+            evaluate_releases(&releases, "beta", &current, "linux", "x86_64", None)
+        else {
+            panic!("expected a checked result");
+        };
+        assert!(available && prerelease);
+    }
+
+    #[test]
+    fn should_pick_platform_asset() {
+        let assets: Vec<ReleaseAsset> = [
+            "Moraya_0.42.0_aarch64.dmg",
+            "Moraya_0.42.0_x64.dmg",
+            "Moraya_0.42.0_x64_en-US.msi",
+            "Moraya_0.42.0_x64-setup.exe",
+            "Moraya_0.42.0_amd64.deb",
+            "Moraya_0.42.0_amd64.AppImage",
+            "latest.json",
+        ]
+        .iter()
+        .map(|n| asset(n))
+        .collect();
+        let name = |os, arch| pick_asset(&assets, os, arch, None).map(|a| a.name.as_str());
+        assert_eq!(name("macos", "aarch64"), Some("Moraya_0.42.0_aarch64.dmg"));
+        assert_eq!(name("macos", "x86_64"), Some("Moraya_0.42.0_x64.dmg"));
+        assert_eq!(name("windows", "x86_64"), Some("Moraya_0.42.0_x64-setup.exe"));
+        assert_eq!(name("linux", "x86_64"), Some("Moraya_0.42.0_amd64.AppImage"));
+        // Never hand an x64 build to an arm64 machine.
+        assert_eq!(name("linux", "aarch64"), None);
+        assert_eq!(name("freebsd", "x86_64"), None);
+        let universal = [asset("Moraya_0.42.0_universal.dmg")];
+        assert_eq!(
+            pick_asset(&universal, "macos", "aarch64", None).map(|a| a.name.as_str()),
+            Some("Moraya_0.42.0_universal.dmg")
+        );
+    }
+
+    #[test]
+    fn should_follow_linux_install_kind() {
+        let assets: Vec<ReleaseAsset> = [
+            "Moraya_0.42.0_amd64.AppImage",
+            "Moraya_0.42.0_amd64.deb",
+            "Moraya-0.42.0-1.x86_64.rpm",
+        ]
+        .iter()
+        .map(|n| asset(n))
+        .collect();
+        let name = |kind| {
+            pick_asset(&assets, "linux", "x86_64", Some(kind)).map(|a| a.name.as_str())
+        };
+        assert_eq!(name(LinuxInstallKind::Deb), Some("Moraya_0.42.0_amd64.deb"));
+        assert_eq!(name(LinuxInstallKind::Rpm), Some("Moraya-0.42.0-1.x86_64.rpm"));
+        assert_eq!(name(LinuxInstallKind::Appimage), Some("Moraya_0.42.0_amd64.AppImage"));
+        assert_eq!(name(LinuxInstallKind::Unknown), Some("Moraya_0.42.0_amd64.AppImage"));
+        assert_eq!(name(LinuxInstallKind::Flatpak), None);
+
+        let no_owner = |_: &Path| -> Option<LinuxInstallKind> { panic!("not a system path") };
+        let deb = |_: &Path| Some(LinuxInstallKind::Deb);
+        let exe = Path::new("/usr/bin/moraya");
+        assert_eq!(
+            classify_linux_install(|v| v == "FLATPAK_ID" || v == "APPIMAGE", exe, no_owner),
+            LinuxInstallKind::Flatpak
+        );
+        assert_eq!(classify_linux_install(|v| v == "SNAP", exe, no_owner), LinuxInstallKind::Snap);
+        assert_eq!(
+            classify_linux_install(|v| v == "APPIMAGE", Path::new("/tmp/.mount_x/moraya"), no_owner),
+            LinuxInstallKind::Appimage
+        );
+        assert_eq!(classify_linux_install(|_| false, exe, deb), LinuxInstallKind::Deb);
+        assert_eq!(
+            classify_linux_install(|_| false, Path::new("/opt/Moraya/moraya"), |_| None),
+            LinuxInstallKind::Unknown
+        );
+        assert_eq!(
+            classify_linux_install(|_| false, Path::new("/home/u/moraya"), no_owner),
+            LinuxInstallKind::Unknown
+        );
+    }
+
+    #[test]
+    fn should_report_rate_limits_as_structured_failures() {
+        let UpdateCheck::Failed { reason, message } =
+            classify_http_failure(403, Some("0"), Some(1_700_000_000))
+        else {
+            panic!("expected a failure");
+        };
+        assert_eq!(reason, CheckFailure::RateLimited);
+        assert_eq!(
+            message,
+            "GitHub API rate limit exceeded until 22:13 UTC. Please try again later."
+        );
+        assert_eq!(
+            classify_http_failure(403, Some("12"), None),
+            check_failed(CheckFailure::Http, "GitHub API error: HTTP 403")
+        );
+        let json = serde_json::to_value(classify_http_failure(429, None, None)).unwrap();
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["reason"], "rateLimited");
+    }
+
+    #[test]
+    fn should_build_silent_windows_install_script() {
+        let script = windows_install_script(
+            Path::new(r"C:\Users\o'neil\Downloads\Moraya_0.42.0_x64-setup.exe"),
+            4242,
+            Some(Path::new(r"C:\Program Files\Moraya\Moraya.exe")),
+        )
+        .unwrap();
+        assert_eq!(
+            script,
+            "Wait-Process -Id 4242 -ErrorAction SilentlyContinue; \
+             Start-Process -FilePath 'C:\\Users\\o''neil\\Downloads\\Moraya_0.42.0_x64-setup.exe' -ArgumentList '/S' -Wait; \
+             Start-Process -FilePath 'C:\\Program Files\\Moraya\\Moraya.exe'"
+        );
+        let msi = windows_install_script(Path::new(r"D:\dl\Moraya.MSI"), 1, None).unwrap();
+        assert!(msi.ends_with(
+            "Start-Process -FilePath 'msiexec.exe' -ArgumentList '/i \"D:\\dl\\Moraya.MSI\" /qn /norestart' -Wait"
+        ));
+        assert!(windows_install_script(Path::new("Moraya.zip"), 1, None).is_err());
+    }
+
+    #[test]
+    fn should_stage_appimage_next_to_current() {
+        assert_eq!(
+            appimage_staging_path(Path::new("/home/u/Apps/Moraya.AppImage")),
+            PathBuf::from("/home/u/Apps/.Moraya.AppImage.update")
+        );
+        assert!(has_extension(Path::new("/tmp/Moraya_amd64.appimage"), "AppImage"));
+    }
+
+    #[test]
+    fn should_reuse_existing_download_only_when_it_matches() {
+        let dir = TempDir::new("update-reuse");
+        let path = dir.join("Moraya_0.42.0_x64.dmg");
+        std::fs::write(&path, b"test").unwrap();
+        let sha = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        assert_eq!(existing_download(&path, Some(4), Some(sha)).as_deref(), Some(sha));
+        assert_eq!(existing_download(&path, Some(4), None).as_deref(), Some(sha));
+        assert!(existing_download(&path, Some(5), None).is_none());
+        assert!(existing_download(&path, None, Some(&"0".repeat(64))).is_none());
+        // Nothing to compare against: always download again.
+        assert!(existing_download(&path, None, None).is_none());
+        assert!(existing_download(&dir.join("missing.dmg"), Some(4), None).is_none());
+    }
+
+    #[test]
+    fn should_install_only_unchanged_downloads_from_the_updates_dir() {
+        let dir = TempDir::new("update-install");
+        let updates = dir.join("updates");
+        std::fs::create_dir_all(&updates).unwrap();
+        let installer = updates.join("Moraya_0.42.0_x64.dmg");
+        std::fs::write(&installer, b"test").unwrap();
+        let outside = dir.join("Moraya_0.42.0_x64.dmg");
+        std::fs::write(&outside, b"test").unwrap();
+        let sha = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        assert_eq!(
+            checked_installer(&installer, &updates, Some(sha)).unwrap(),
+            installer.canonicalize().unwrap()
+        );
+        let sneaky = updates.join("..").join("Moraya_0.42.0_x64.dmg");
+        assert!(checked_installer(&sneaky, &updates, Some(sha))
+            .unwrap_err()
+            .contains("outside"));
+        assert!(checked_installer(&installer, &updates, None).is_err());
+        std::fs::write(&installer, b"tampered").unwrap();
+        assert!(checked_installer(&installer, &updates, Some(sha))
+            .unwrap_err()
+            .contains("changed"));
+        assert!(checked_installer(&updates.join("missing.dmg"), &updates, Some(sha)).is_err());
+    }
+
+    #[test]
+    fn should_prune_all_but_latest_installers() {
+        let dir = TempDir::new("update-prune");
+        let base = SystemTime::now() - Duration::from_secs(3 * 60 * 60);
+        let touch = |name: &str, age_mins: u64| {
+            let path = dir.join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(base + Duration::from_secs(3 * 60 * 60 - age_mins * 60))
+                .unwrap();
+            path
+        };
+        touch("Moraya_0.40.0_x64.dmg", 150);
+        let installing = touch("Moraya_0.41.0_x64.dmg", 100);
+        touch("Moraya_0.42.0_x64.dmg", 10);
+        touch("Moraya_0.43.0_x64.dmg.part", 1);
+        touch("Moraya_0.39.0_x64.dmg.part", 120);
+
+        assert_eq!(prune_updates(&dir, 1, Some(&installing)), 3);
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["Moraya_0.41.0_x64.dmg", "Moraya_0.43.0_x64.dmg.part"]);
+
+        touch("Moraya_0.42.0_x64.dmg", 5);
+        assert_eq!(prune_updates(&dir, 1, None), 1);
+        assert!(dir.join("Moraya_0.42.0_x64.dmg").exists());
+    }
+
+    #[test]
+    fn should_measure_rate_over_recent_window_and_throttle() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut meter = TransferMeter::new(start);
+        // 10 s at 1 MB/s, then the link drops to 100 KB/s.
+        for s in 1..=10 {
+            meter.record(at(s * 1000), s * 1_000_000);
+        }
+        assert_eq!(meter.bytes_per_sec(), 1_000_000);
+        for s in 1..=6 {
+            meter.record(at(10_000 + s * 1000), 10_000_000 + s * 100_000);
+        }
+        // Only the last ~5 s count, not the 10 fast seconds before.
+        assert_eq!(meter.bytes_per_sec(), 100_000);
+        assert_eq!(meter.eta_secs(10_600_000, 11_000_001), Some(5));
+        assert_eq!(meter.eta_secs(10_600_000, 0), None);
+
+        let mut idle = TransferMeter::new(start);
+        assert_eq!(idle.eta_secs(0, 100), None);
+        assert!(idle.should_emit(at(0), false));
+        assert!(!idle.should_emit(at(100), false));
+        assert!(idle.should_emit(at(100), true));
+        assert!(!idle.should_emit(at(300), false));
+        assert!(idle.should_emit(at(350), false));
+    }
+
+    #[test]
+    fn should_normalize_expected_sha256() {
+        let hex = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08";
+        assert_eq!(
+            normalize_sha256(&format!("sha256:{}", hex)).as_deref(),
+            Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")
+        );
+        assert_eq!(
+            normalize_sha256(&format!(" {} ", hex.to_lowercase())),
+            normalize_sha256(hex)
+        );
+        assert!(normalize_sha256("md5:d41d8cd98f00b204e9800998ecf8427e").is_none());
+        assert!(normalize_sha256(&hex[..63]).is_none());
+        assert_eq!(
+            hex::encode(Sha256::digest(b"test")),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+    }
 }
@@ -0,0 +1,289 @@
+//! User-presence confirmation (Touch ID / Windows Hello / polkit) before
+//! stored secrets are revealed in the UI.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// How long one successful confirmation is trusted, so revealing several
+/// keys during one settings session prompts only once.
+const GRACE_PERIOD: Duration = Duration::from_secs(120);
+
+/// How the user can be asked to confirm their presence on this machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceMethod {
+    /// Touch ID / Windows Hello (face, fingerprint or PIN).
+    Biometric,
+    /// Account password (macOS without Touch ID, Linux polkit agent).
+    Password,
+    /// Nothing available; protected reads are refused.
+    None,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BiometricAvailability {
+    pub available: bool,
+    pub method: PresenceMethod,
+    /// The `reason` asked about, returned when the system prompt can't show
+    /// it (polkit shows its own fixed message), so the UI shows it instead.
+    pub reason: Option<String>,
+}
+
+/// Time of the last successful confirmation. tokio::sync::Mutex so that
+/// concurrent protected reads wait for one prompt instead of each
+/// raising their own.
+pub struct UserPresenceState {
+    verified_at: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl UserPresenceState {
+    pub fn new() -> Self {
+        Self {
+            verified_at: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Confirm user presence unless it was confirmed within the grace
+    /// period. `reason` is shown in the system prompt where supported; the
+    /// prompt is owned by the main window where the platform allows it.
+    pub(crate) async fn confirm(
+        &self,
+        app: &tauri::AppHandle,
+        reason: String,
+    ) -> Result<(), String> {
+        let mut verified_at = self.verified_at.lock().await;
+        if within_grace(*verified_at, Instant::now()) {
+            return Ok(());
+        }
+
+        let app = app.clone();
+        let confirmed = tokio::task::spawn_blocking(move || imp::verify(&app, &reason))
+            .await
+            .map_err(|_| "Authentication task failed".to_string())??;
+        if !confirmed {
+            return Err("Authentication was cancelled or failed".to_string());
+        }
+        *verified_at = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl Default for UserPresenceState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn within_grace(verified_at: Option<Instant>, now: Instant) -> bool {
+    verified_at.is_some_and(|at| now.saturating_duration_since(at) < GRACE_PERIOD)
+}
+
+/// Which confirmation flow this machine supports. `reason` is the text the
+/// caller will pass to `keychain_get_protected`.
+#[tauri::command]
+pub async fn is_biometric_available(
+    reason: Option<String>,
+) -> Result<BiometricAvailability, String> {
+    let method = tokio::task::spawn_blocking(imp::method)
+        .await
+        .unwrap_or(PresenceMethod::None);
+    Ok(BiometricAvailability {
+        available: method == PresenceMethod::Biometric,
+        method,
+        reason: reason.filter(|_| method != PresenceMethod::None && !imp::SHOWS_REASON),
+    })
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use super::PresenceMethod;
+    use std::ffi::CString;
+    use std::sync::mpsc::channel;
+
+    use block::ConcreteBlock;
+    use objc::runtime::{Object, BOOL, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    /// LAPolicyDeviceOwnerAuthenticationWithBiometrics
+    const POLICY_BIOMETRICS: isize = 1;
+    /// LAPolicyDeviceOwnerAuthentication (biometrics, falling back to the
+    /// account password)
+    const POLICY_OWNER: isize = 2;
+
+    pub(super) const SHOWS_REASON: bool = true;
+
+    #[link(name = "LocalAuthentication", kind = "framework")]
+    extern "C" {}
+
+    pub(super) fn method() -> PresenceMethod {
+        // SAFETY: LAContext is created and released on this thread; a null
+        // error out-pointer is allowed by canEvaluatePolicy:error:.
+        unsafe {
+            let context: *mut Object = msg_send![class!(LAContext), new];
+            if context.is_null() {
+                return PresenceMethod::None;
+            }
+            let no_error: *mut *mut Object = std::ptr::null_mut();
+            let biometric: BOOL =
+                msg_send![context, canEvaluatePolicy: POLICY_BIOMETRICS error: no_error];
+            let owner: BOOL = msg_send![context, canEvaluatePolicy: POLICY_OWNER error: no_error];
+            let _: () = msg_send![context, release];
+            if biometric == YES {
+                PresenceMethod::Biometric
+            } else if owner == YES {
+                PresenceMethod::Password
+            } else {
+                PresenceMethod::None
+            }
+        }
+    }
+
+    pub(super) fn verify(_app: &tauri::AppHandle, reason: &str) -> Result<bool, String> {
+        let reason = CString::new(reason).map_err(|_| "Invalid reason".to_string())?;
+        let (tx, rx) = channel::<bool>();
+        // SAFETY: the reply block is copied to the heap and kept alive until
+        // the reply arrives; LAContext is released only after that.
+        unsafe {
+            let context: *mut Object = msg_send![class!(LAContext), new];
+            if context.is_null() {
+                return Err("LocalAuthentication is unavailable".to_string());
+            }
+            let reason: *mut Object =
+                msg_send![class!(NSString), stringWithUTF8String: reason.as_ptr()];
+            let reply = ConcreteBlock::new(move |success: BOOL, _error: *mut Object| {
+                let _ = tx.send(success == YES);
+            });
+            let reply = reply.copy();
+            let _: () = msg_send![context,
+                evaluatePolicy: POLICY_OWNER
+                localizedReason: reason
+                reply: &*reply];
+            let confirmed = rx.recv().unwrap_or(false);
+            let _: () = msg_send![context, release];
+            Ok(confirmed)
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use super::PresenceMethod;
+    use tauri::Manager;
+    use windows::core::{factory, HSTRING};
+    use windows::Foundation::IAsyncOperation;
+    use windows::Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+
+    pub(super) const SHOWS_REASON: bool = true;
+
+    pub(super) fn method() -> PresenceMethod {
+        match UserConsentVerifier::CheckAvailabilityAsync().and_then(|op| op.get()) {
+            Ok(UserConsentVerifierAvailability::Available) => PresenceMethod::Biometric,
+            _ => PresenceMethod::None,
+        }
+    }
+
+    /// Desktop apps must name the window that owns the Hello dialog;
+    /// without one it opens behind Moraya or not at all.
+    pub(super) fn verify(app: &tauri::AppHandle, reason: &str) -> Result<bool, String> {
+        if method() == PresenceMethod::None {
+            return Err("Windows Hello is not set up on this device".to_string());
+        }
+        let hwnd = app
+            .get_webview_window("main")
+            .and_then(|w| w.hwnd().ok())
+            .map(|h| HWND(h.0 as _))
+            .ok_or_else(|| "The main window is not available".to_string())?;
+        let result = factory::<UserConsentVerifier, IUserConsentVerifierInterop>()
+            .and_then(|interop| {
+                // SAFETY: `hwnd` is the live main window, owned by this process.
+                unsafe {
+                    interop.RequestVerificationForWindowAsync::<
+                        IAsyncOperation<UserConsentVerificationResult>,
+                    >(hwnd, &HSTRING::from(reason))
+                }
+            })
+            .and_then(|op| op.get())
+            .map_err(|e| format!("Windows Hello failed: {}", e))?;
+        Ok(result == UserConsentVerificationResult::Verified)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use super::PresenceMethod;
+    use std::process::{Command, Stdio};
+
+    /// Moraya's own action (`polkit/` in the bundle), which asks for the
+    /// user's own password (`auth_self`). Actions shipped with polkit, like
+    /// `exec`, want an administrator's. The .deb and .rpm install it; where
+    /// it is missing (AppImage) protected reads are refused.
+    const POLKIT_ACTION: &str = "com.moraya.app.confirm-presence";
+
+    /// The agent shows the action's fixed message, not ours.
+    pub(super) const SHOWS_REASON: bool = false;
+
+    pub(super) fn method() -> PresenceMethod {
+        let action_installed = Command::new("pkaction")
+            .args(["--action-id", POLKIT_ACTION])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+        let has_pkcheck = Command::new("pkcheck")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+        if action_installed && has_pkcheck {
+            PresenceMethod::Password
+        } else {
+            PresenceMethod::None
+        }
+    }
+
+    /// Ask the session's polkit agent for the user's password. pkcheck
+    /// can't show a custom message, so `reason` is shown by the UI instead
+    /// (see `SHOWS_REASON`).
+    pub(super) fn verify(_app: &tauri::AppHandle, _reason: &str) -> Result<bool, String> {
+        if method() == PresenceMethod::None {
+            return Err("Moraya's polkit action is not installed".to_string());
+        }
+        let status = Command::new("pkcheck")
+            .args([
+                "--action-id",
+                POLKIT_ACTION,
+                "--process",
+                &std::process::id().to_string(),
+                "--allow-user-interaction",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|_| "No polkit authentication agent is available".to_string())?;
+        Ok(status.success())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_trust_confirmation_only_within_grace_period() {
+        let now = Instant::now();
+        assert!(!within_grace(None, now));
+        assert!(within_grace(Some(now), now));
+        assert!(within_grace(
+            Some(now),
+            now + GRACE_PERIOD - Duration::from_secs(1)
+        ));
+        assert!(!within_grace(Some(now), now + GRACE_PERIOD));
+    }
+}
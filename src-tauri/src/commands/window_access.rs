@@ -0,0 +1,285 @@
+//! Which windows may call the sensitive command groups: secrets, object
+//! storage, MCP servers and plugins.
+//!
+//! The capability files are the first gate: Tauri rejects a command unless
+//! the calling window's label is granted a permission set that lists it
+//! (see `permissions/`). This is the second. The invoke handler checks the
+//! label and the page origin against an allow-list kept in managed state,
+//! so a window given a broader capability by mistake, or a page that has
+//! navigated away from the app, still can't read a key or upload a file.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde_json::json;
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime, Url};
+
+use super::error::{CommandError, ErrorCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandGroup {
+    Secrets,
+    ObjectStorage,
+    Mcp,
+    Plugins,
+}
+
+impl CommandGroup {
+    const ALL: [Self; 4] = [Self::Secrets, Self::ObjectStorage, Self::Mcp, Self::Plugins];
+
+    /// The permission set listing the group's commands, so the capability
+    /// files and this check can't disagree about what is gated.
+    fn permission_set(self) -> &'static str {
+        match self {
+            Self::Secrets => include_str!("../../permissions/secrets.toml"),
+            Self::ObjectStorage => include_str!("../../permissions/object-storage.toml"),
+            Self::Mcp => include_str!("../../permissions/mcp.toml"),
+            Self::Plugins => include_str!("../../permissions/plugins.toml"),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Secrets => "secrets",
+            Self::ObjectStorage => "object storage",
+            Self::Mcp => "MCP servers",
+            Self::Plugins => "plugins",
+        }
+    }
+
+    /// The group `command` belongs to; `None` for commands any window with
+    /// the matching capability may call.
+    pub fn of(command: &str) -> Option<Self> {
+        static GROUPS: OnceLock<HashMap<String, CommandGroup>> = OnceLock::new();
+        GROUPS
+            .get_or_init(|| {
+                Self::ALL
+                    .into_iter()
+                    .flat_map(|g| allowed_commands(g.permission_set()).map(move |c| (c, g)))
+                    .collect()
+            })
+            .get(command)
+            .copied()
+    }
+}
+
+/// A window label, or a family of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelRule {
+    Exact(&'static str),
+    /// The prefix followed by digits only, like `moraya-3`. Hidden render
+    /// windows (`moraya-print-…`, `moraya-capture-…`) don't match.
+    Numbered(&'static str),
+}
+
+impl LabelRule {
+    fn matches(&self, label: &str) -> bool {
+        match self {
+            Self::Exact(name) => label == *name,
+            Self::Numbered(prefix) => label
+                .strip_prefix(prefix)
+                .is_some_and(|rest| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit())),
+        }
+    }
+}
+
+pub struct WindowAccess {
+    allowed: HashMap<CommandGroup, Vec<LabelRule>>,
+}
+
+impl WindowAccess {
+    /// Every group open to the editor windows, as named in `lib.rs`, and
+    /// to nothing else.
+    pub fn new() -> Self {
+        let editors = vec![LabelRule::Exact("main"), LabelRule::Numbered("moraya-")];
+        Self {
+            allowed: CommandGroup::ALL
+                .into_iter()
+                .map(|g| (g, editors.clone()))
+                .collect(),
+        }
+    }
+
+    /// Reject `command` unless the window `label`, showing `url`, may use
+    /// its group. Ungated commands always pass.
+    pub(crate) fn check(&self, command: &str, label: &str, url: &Url) -> Result<(), CommandError> {
+        let Some(group) = CommandGroup::of(command) else {
+            return Ok(());
+        };
+        let listed = self
+            .allowed
+            .get(&group)
+            .is_some_and(|rules| rules.iter().any(|r| r.matches(label)));
+        if listed && is_app_origin(url) {
+            return Ok(());
+        }
+        log::warn!("{command} denied to window {label} at {url}");
+        Err(denied(group, label))
+    }
+}
+
+impl Default for WindowAccess {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wrap the generated invoke handler so gated commands are checked before
+/// they run. Fails closed when the state isn't managed.
+pub fn gated<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        if let Some(group) = CommandGroup::of(command) {
+            let webview = invoke.message.webview_ref();
+            let label = webview.label();
+            let verdict = match (webview.try_state::<WindowAccess>(), webview.url()) {
+                (Some(access), Ok(url)) => access.check(command, label, &url),
+                _ => Err(denied(group, label)),
+            };
+            if let Err(e) = verdict {
+                invoke.resolver.reject(e);
+                return true;
+            }
+        }
+        handler(invoke)
+    }
+}
+
+/// Pages the app itself serves, and the dev server in debug builds.
+fn is_app_origin(url: &Url) -> bool {
+    match (url.scheme(), url.host_str()) {
+        ("tauri", Some("localhost")) => true,
+        // Windows and Android serve the app from http(s)://tauri.localhost
+        ("http" | "https", Some("tauri.localhost")) => true,
+        ("http", Some("localhost" | "127.0.0.1")) => cfg!(debug_assertions),
+        _ => false,
+    }
+}
+
+fn denied(group: CommandGroup, label: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::WindowNotAllowed,
+        format!("Window {label} isn't allowed to use {}", group.name()),
+    )
+    .with_details(json!({ "window": label, "group": group.name() }))
+}
+
+/// Command names behind the `"allow-<command>"` entries of a permission
+/// set, the same way `build.rs` reads them.
+fn allowed_commands(set: &str) -> impl Iterator<Item = String> + '_ {
+    set.lines().filter_map(|line| {
+        let name = line.trim().trim_end_matches(',').trim_matches('"');
+        name.strip_prefix("allow-").map(|c| c.replace('-', "_"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::ipc::{CallbackFn, InvokeBody};
+    use tauri::test::{
+        get_ipc_response, mock_builder, mock_context, noop_assets, MockRuntime, INVOKE_KEY,
+    };
+    use tauri::webview::InvokeRequest;
+    use tauri::{WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+    // Stand-ins with the names of a gated and an ungated command. The mock
+    // context has no app manifest, so only the runtime check applies.
+    #[tauri::command]
+    fn keychain_get() -> &'static str {
+        "secret"
+    }
+
+    #[tauri::command]
+    fn read_file() -> &'static str {
+        "text"
+    }
+
+    fn call(window: &WebviewWindow<MockRuntime>, cmd: &str) -> Result<String, serde_json::Value> {
+        get_ipc_response(
+            window,
+            InvokeRequest {
+                cmd: cmd.into(),
+                callback: CallbackFn(0),
+                error: CallbackFn(1),
+                url: "tauri://localhost".parse().unwrap(),
+                body: InvokeBody::default(),
+                headers: Default::default(),
+                invoke_key: INVOKE_KEY.to_string(),
+            },
+        )
+        .map(|body| body.deserialize().unwrap())
+    }
+
+    #[test]
+    fn should_reject_gated_commands_from_restricted_windows() {
+        let app = mock_builder()
+            .manage(WindowAccess::new())
+            .invoke_handler(gated(tauri::generate_handler![keychain_get, read_file]))
+            .build(mock_context(noop_assets()))
+            .unwrap();
+        let open = |label: &str| {
+            WebviewWindowBuilder::new(&app, label, WebviewUrl::default())
+                .build()
+                .unwrap()
+        };
+
+        for label in ["main", "moraya-2"] {
+            assert_eq!(call(&open(label), "keychain_get").unwrap(), "secret");
+        }
+        let capture = open("moraya-capture-main");
+        let err = call(&capture, "keychain_get").unwrap_err();
+        assert_eq!(err["code"], "WINDOW_NOT_ALLOWED");
+        assert_eq!(err["details"]["window"], "moraya-capture-main");
+        assert_eq!(call(&capture, "read_file").unwrap(), "text");
+    }
+
+    #[test]
+    fn should_check_labels_origins_and_sets() {
+        let access = WindowAccess::new();
+        let app: Url = "tauri://localhost/".parse().unwrap();
+        assert!(access
+            .check("upload_to_object_storage", "moraya-12", &app)
+            .is_ok());
+        assert!(access
+            .check("plugin_invoke", "moraya-print-7", &app)
+            .is_err());
+        assert!(access.check("mcp_connect_stdio", "moraya-", &app).is_err());
+        let remote: Url = "https://example.com/".parse().unwrap();
+        assert!(access.check("keychain_set", "main", &remote).is_err());
+        assert!(access
+            .check("read_file", "moraya-capture-x", &remote)
+            .is_ok());
+
+        // Every registered command is in some permission set, or the
+        // capability files would deny it to every window.
+        let sets: Vec<String> = [
+            include_str!("../../permissions/editor.toml"),
+            include_str!("../../permissions/render.toml"),
+        ]
+        .into_iter()
+        .flat_map(allowed_commands)
+        .collect();
+        let lib = include_str!("../lib.rs");
+        let handler = &lib[lib.find("generate_handler![").unwrap()..];
+        let handler = &handler[..handler.find("])").unwrap()];
+        for line in handler.lines().skip(1) {
+            let command = line
+                .trim()
+                .trim_end_matches(',')
+                .rsplit("::")
+                .next()
+                .unwrap();
+            if command.is_empty() || command.starts_with("//") {
+                continue;
+            }
+            assert!(
+                sets.iter().any(|c| c == command) || CommandGroup::of(command).is_some(),
+                "{command} is in no permission set"
+            );
+        }
+    }
+}
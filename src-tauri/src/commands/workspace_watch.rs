@@ -0,0 +1,498 @@
+//! Workspace folder watcher: file system events under the open folder are
+//! batched and emitted as `workspace:changed` so the sidebar can patch its
+//! tree instead of re-reading the folder.
+//!
+//! Events are filtered by the same rules as `read_dir_recursive`. Each batch
+//! closes after a short quiet period, or after [`MAX_BATCH_WINDOW`] while
+//! events keep coming, so a git checkout touching hundreds of files arrives
+//! as one event per kind. A removal and a creation in the same batch are
+//! reported as a rename when they look like one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::Emitter;
+
+use super::error::{CommandError, ErrorCode};
+use super::file::{is_ignored_name, is_markdown_name, validate_path};
+
+pub const CHANGED_EVENT: &str = "workspace:changed";
+/// A batch closes once no event has arrived for this long.
+const QUIET_WINDOW: Duration = Duration::from_millis(150);
+/// Longest a batch stays open while events keep arriving.
+const MAX_BATCH_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Removed,
+    /// `paths` is `[from, to]`.
+    Renamed,
+    Modified,
+}
+
+/// Payload of [`CHANGED_EVENT`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceChange {
+    /// The watched folder, so late events from a previous folder can be told apart.
+    pub root: String,
+    pub kind: ChangeKind,
+    pub paths: Vec<String>,
+    /// Which of the created or renamed-to `paths` are directories.
+    pub dirs: Vec<String>,
+}
+
+/// What the first event of a batch said about a path. Later events only
+/// matter through the path's state on disk when the batch closes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FirstSeen {
+    Created,
+    Removed,
+    Modified,
+    /// One side of a rename, without saying which (macOS FSEvents).
+    Renamed,
+}
+
+#[derive(Default)]
+struct Batch {
+    order: Vec<PathBuf>,
+    first: HashMap<PathBuf, FirstSeen>,
+    /// Renames the platform reported with both paths.
+    renames: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Batch {
+    fn touch(&mut self, path: &Path, seen: FirstSeen) {
+        if !self.first.contains_key(path) {
+            self.first.insert(path.to_path_buf(), seen);
+            self.order.push(path.to_path_buf());
+        }
+    }
+
+    fn record(&mut self, event: notify::Event) {
+        let seen = match event.kind {
+            EventKind::Access(_) => return,
+            EventKind::Create(_) => FirstSeen::Created,
+            EventKind::Remove(_) => FirstSeen::Removed,
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                self.touch(&event.paths[0], FirstSeen::Removed);
+                self.touch(&event.paths[1], FirstSeen::Created);
+                self.renames
+                    .push((event.paths[0].clone(), event.paths[1].clone()));
+                return;
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => FirstSeen::Removed,
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FirstSeen::Created,
+            EventKind::Modify(ModifyKind::Name(_)) => FirstSeen::Renamed,
+            _ => FirstSeen::Modified,
+        };
+        for path in &event.paths {
+            self.touch(path, seen);
+        }
+    }
+
+    /// Net changes of the batch, compared against the disk now.
+    fn resolve(self, root: &Path, all_files: bool) -> Vec<WorkspaceChange> {
+        let mut created: Vec<(PathBuf, bool)> = Vec::new();
+        let mut removed: Vec<PathBuf> = Vec::new();
+        let mut modified: Vec<PathBuf> = Vec::new();
+        for path in self.order {
+            if !is_visible_path(root, &path) {
+                continue;
+            }
+            // Symlinks are left out of the tree, so they count as absent
+            let now = path
+                .symlink_metadata()
+                .ok()
+                .filter(|m| !m.file_type().is_symlink())
+                .map(|m| m.is_dir());
+            let existed = match self.first[&path] {
+                FirstSeen::Created => false,
+                FirstSeen::Removed | FirstSeen::Modified => true,
+                FirstSeen::Renamed => now.is_none(),
+            };
+            match (existed, now) {
+                (false, Some(is_dir)) => created.push((path, is_dir)),
+                (true, None) => removed.push(path),
+                (true, Some(false)) => modified.push(path),
+                _ => {}
+            }
+        }
+
+        let renames = pair_renames(&mut removed, &mut created, &self.renames);
+        let shown = |path: &Path, is_dir: bool| is_dir || all_files || has_markdown_name(path);
+        let root_str = root.to_string_lossy().to_string();
+        let change = |kind, paths: Vec<String>, dirs: Vec<String>| WorkspaceChange {
+            root: root_str.clone(),
+            kind,
+            paths,
+            dirs,
+        };
+
+        let mut changes = Vec::new();
+        for (from, to, is_dir) in renames {
+            if shown(&to, is_dir) {
+                let to = to.to_string_lossy().to_string();
+                let dirs = if is_dir { vec![to.clone()] } else { Vec::new() };
+                changes.push(change(
+                    ChangeKind::Renamed,
+                    vec![from.to_string_lossy().to_string(), to],
+                    dirs,
+                ));
+            } else {
+                // Renamed to something the tree doesn't show
+                removed.push(from);
+            }
+        }
+        // Removed paths can't be checked for being a directory any more,
+        // so all of them are reported; unknown paths are a no-op to the tree
+        if !removed.is_empty() {
+            changes.push(change(
+                ChangeKind::Removed,
+                to_strings(&removed),
+                Vec::new(),
+            ));
+        }
+        created.retain(|(path, is_dir)| shown(path, *is_dir));
+        if !created.is_empty() {
+            let dirs = created
+                .iter()
+                .filter(|(_, is_dir)| *is_dir)
+                .map(|(p, _)| p.to_string_lossy().to_string())
+                .collect();
+            let paths: Vec<PathBuf> = created.into_iter().map(|(p, _)| p).collect();
+            changes.push(change(ChangeKind::Created, to_strings(&paths), dirs));
+        }
+        modified.retain(|path| all_files || has_markdown_name(path));
+        if !modified.is_empty() {
+            changes.push(change(
+                ChangeKind::Modified,
+                to_strings(&modified),
+                Vec::new(),
+            ));
+        }
+        changes
+    }
+}
+
+fn to_strings(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect()
+}
+
+fn has_markdown_name(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|n| is_markdown_name(&n.to_string_lossy()))
+}
+
+/// Inside `root` and not under a hidden or ignored directory.
+fn is_visible_path(root: &Path, path: &Path) -> bool {
+    match path.strip_prefix(root) {
+        Ok(rel) => {
+            rel.components().next().is_some()
+                && rel
+                    .components()
+                    .all(|c| !is_ignored_name(&c.as_os_str().to_string_lossy()))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Take matching removal/creation pairs out of the two lists, as
+/// `(from, to, to_is_dir)`. Reported renames pair first, then a removal and
+/// a creation with the same file name (a move), then the only removal and
+/// only creation in the same folder (a rename in place).
+fn pair_renames(
+    removed: &mut Vec<PathBuf>,
+    created: &mut Vec<(PathBuf, bool)>,
+    reported: &[(PathBuf, PathBuf)],
+) -> Vec<(PathBuf, PathBuf, bool)> {
+    let mut pairs = Vec::new();
+    let mut take =
+        |removed: &mut Vec<PathBuf>, created: &mut Vec<(PathBuf, bool)>, from: usize, to: usize| {
+            let from = removed.remove(from);
+            let (to, is_dir) = created.remove(to);
+            pairs.push((from, to, is_dir));
+        };
+
+    for (from, to) in reported {
+        let from = removed.iter().position(|p| p == from);
+        let to = created.iter().position(|(p, _)| p == to);
+        if let (Some(from), Some(to)) = (from, to) {
+            take(removed, created, from, to);
+        }
+    }
+
+    let mut i = 0;
+    while i < removed.len() {
+        let name = removed[i].file_name();
+        let matches: Vec<usize> = created
+            .iter()
+            .enumerate()
+            .filter(|(_, (p, _))| p.file_name() == name)
+            .map(|(j, _)| j)
+            .collect();
+        if let [to] = matches[..] {
+            take(removed, created, i, to);
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut i = 0;
+    while i < removed.len() {
+        let parent = removed[i].parent();
+        let from: Vec<usize> = (0..removed.len())
+            .filter(|&k| removed[k].parent() == parent)
+            .collect();
+        let to: Vec<usize> = (0..created.len())
+            .filter(|&k| created[k].0.parent() == parent)
+            .collect();
+        if let ([from], [to]) = (&from[..], &to[..]) {
+            take(removed, created, *from, *to);
+        } else {
+            i += 1;
+        }
+    }
+    pairs
+}
+
+#[derive(Default)]
+pub struct WorkspaceWatchState(Mutex<Option<RecommendedWatcher>>);
+
+impl WorkspaceWatchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Swap the running watcher. Dropping the old one ends its batching thread.
+    fn replace(&self, watcher: Option<RecommendedWatcher>) -> bool {
+        let old = match self.0.lock() {
+            Ok(mut g) => std::mem::replace(&mut *g, watcher),
+            Err(e) => std::mem::replace(&mut *e.into_inner(), watcher),
+        };
+        old.is_some()
+    }
+}
+
+fn watch_failed(e: &notify::Error) -> CommandError {
+    CommandError::new(
+        ErrorCode::IoError,
+        format!("Cannot watch the workspace folder: {}", e),
+    )
+}
+
+/// Watch `root` recursively and emit [`CHANGED_EVENT`] batches. Replaces
+/// any previous workspace watcher. With `all_files` off, only markdown files
+/// and folders are reported, as in `read_dir_recursive`.
+#[tauri::command]
+pub fn watch_workspace(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, WorkspaceWatchState>,
+    root: String,
+    all_files: Option<bool>,
+) -> Result<(), CommandError> {
+    let root = validate_path(&root)?;
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::FileNotFound,
+            "Folder not found",
+        ));
+    }
+    let all_files = all_files.unwrap_or(false);
+
+    let (tx, rx) = mpsc::channel::<notify::Event>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    })
+    .map_err(|e| watch_failed(&e))?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| watch_failed(&e))?;
+    state.replace(Some(watcher));
+
+    std::thread::spawn(move || {
+        while let Ok(event) = rx.recv() {
+            let mut batch = Batch::default();
+            batch.record(event);
+            let opened = Instant::now();
+            while opened.elapsed() < MAX_BATCH_WINDOW {
+                match rx.recv_timeout(QUIET_WINDOW) {
+                    Ok(event) => batch.record(event),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            for change in batch.resolve(&root, all_files) {
+                super::search_index::workspace_changed(&change);
+                let _ = app.emit(CHANGED_EVENT, change);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Stop watching the workspace folder. Returns whether a watcher was running.
+#[tauri::command]
+pub fn unwatch_workspace(state: tauri::State<'_, WorkspaceWatchState>) -> bool {
+    state.replace(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+    use std::fs;
+
+    fn setup(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("moraya-watch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        dir
+    }
+
+    fn event(kind: EventKind, paths: &[PathBuf]) -> notify::Event {
+        paths
+            .iter()
+            .fold(notify::Event::new(kind), |e, p| e.add_path(p.clone()))
+    }
+
+    fn s(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn should_pair_remove_and_create_into_renames() {
+        let dir = setup("rename");
+        fs::write(dir.join("b.md"), "").unwrap();
+        fs::write(dir.join("notes/moved.md"), "").unwrap();
+        let mut batch = Batch::default();
+        // Rename in place, then a move into a subfolder
+        batch.record(event(
+            EventKind::Remove(RemoveKind::File),
+            &[dir.join("a.md")],
+        ));
+        batch.record(event(
+            EventKind::Create(CreateKind::File),
+            &[dir.join("b.md")],
+        ));
+        batch.record(event(
+            EventKind::Remove(RemoveKind::File),
+            &[dir.join("moved.md")],
+        ));
+        batch.record(event(
+            EventKind::Create(CreateKind::File),
+            &[dir.join("notes/moved.md")],
+        ));
+        let changes = batch.resolve(&dir, false);
+        let renames: Vec<Vec<String>> = changes
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Renamed)
+            .map(|c| c.paths.clone())
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert!(renames.contains(&vec![
+            s(&dir.join("moved.md")),
+            s(&dir.join("notes/moved.md"))
+        ]));
+        assert!(renames.contains(&vec![s(&dir.join("a.md")), s(&dir.join("b.md"))]));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_coalesce_a_burst_and_filter_like_the_file_tree() {
+        let dir = setup("burst");
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("new")).unwrap();
+        let mut batch = Batch::default();
+        let mut paths = Vec::new();
+        for i in 0..200 {
+            let path = dir.join(format!("notes/{}.md", i));
+            fs::write(&path, "").unwrap();
+            batch.record(event(EventKind::Create(CreateKind::File), &[path.clone()]));
+            batch.record(event(
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                &[path.clone()],
+            ));
+            paths.push(s(&path));
+        }
+        fs::write(dir.join("notes/image.png"), "").unwrap();
+        fs::write(dir.join(".git/index"), "").unwrap();
+        // Created and deleted again within the batch
+        let temp = dir.join("notes/temp.md");
+        batch.record(event(EventKind::Create(CreateKind::File), &[temp.clone()]));
+        batch.record(event(EventKind::Remove(RemoveKind::File), &[temp.clone()]));
+        batch.record(event(
+            EventKind::Create(CreateKind::File),
+            &[dir.join("notes/image.png"), dir.join(".git/index")],
+        ));
+        batch.record(event(
+            EventKind::Create(CreateKind::Folder),
+            &[dir.join("new")],
+        ));
+        paths.push(s(&dir.join("new")));
+
+        let changes = batch.resolve(&dir, false);
+        assert_eq!(
+            changes,
+            vec![WorkspaceChange {
+                root: s(&dir),
+                kind: ChangeKind::Created,
+                paths,
+                dirs: vec![s(&dir.join("new"))],
+            }]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_report_saves_and_deletions() {
+        let dir = setup("modify");
+        fs::write(dir.join("kept.md"), "").unwrap();
+        let mut batch = Batch::default();
+        // Atomic save: the file is replaced by a new one
+        batch.record(event(
+            EventKind::Remove(RemoveKind::File),
+            &[dir.join("kept.md")],
+        ));
+        batch.record(event(
+            EventKind::Create(CreateKind::File),
+            &[dir.join("kept.md")],
+        ));
+        batch.record(event(
+            EventKind::Remove(RemoveKind::Folder),
+            &[dir.join("old")],
+        ));
+        batch.record(event(
+            EventKind::Remove(RemoveKind::File),
+            &[dir.join("x.txt")],
+        ));
+        let changes = batch.resolve(&dir, false);
+        let kinds: Vec<(ChangeKind, Vec<String>)> =
+            changes.into_iter().map(|c| (c.kind, c.paths)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    ChangeKind::Removed,
+                    vec![s(&dir.join("old")), s(&dir.join("x.txt"))]
+                ),
+                (ChangeKind::Modified, vec![s(&dir.join("kept.md"))]),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
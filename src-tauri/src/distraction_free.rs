@@ -0,0 +1,139 @@
+//! Distraction-free mode: native fullscreen with the window chrome hidden —
+//! the menu bar on Windows/Linux, the overlay traffic lights on macOS.
+//! Tracked per window; leaving the mode puts back what was there before.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize, State, WebviewWindow};
+
+/// Menu check item mirroring the focused window's mode.
+#[cfg(not(target_os = "ios"))]
+const MENU_ID: &str = "view_distraction_free";
+
+/// Window state captured when entering the mode.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+struct SavedWindow {
+    fullscreen: bool,
+    maximized: bool,
+    position: Option<PhysicalPosition<i32>>,
+    size: Option<PhysicalSize<u32>>,
+    decorations: bool,
+    #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
+    menu_visible: bool,
+}
+
+/// Saved state of every window currently in distraction-free mode, by label.
+pub struct DistractionFreeState(Mutex<HashMap<String, SavedWindow>>);
+
+impl DistractionFreeState {
+    pub fn new() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SavedWindow>> {
+        match self.0.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        }
+    }
+}
+
+impl Default for DistractionFreeState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn enter(window: &WebviewWindow) -> tauri::Result<SavedWindow> {
+    let saved = SavedWindow {
+        fullscreen: window.is_fullscreen()?,
+        maximized: window.is_maximized()?,
+        position: window.outer_position().ok(),
+        size: window.inner_size().ok(),
+        decorations: window.is_decorated()?,
+        #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
+        menu_visible: window.is_menu_visible()?,
+    };
+    #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
+    window.hide_menu()?;
+    // The overlay style keeps the traffic lights drawn over the content;
+    // the standard title bar slides away with the menu bar in fullscreen.
+    #[cfg(target_os = "macos")]
+    window.set_title_bar_style(tauri::TitleBarStyle::Visible)?;
+    window.set_fullscreen(true)?;
+    Ok(saved)
+}
+
+fn leave(window: &WebviewWindow, saved: SavedWindow) -> tauri::Result<()> {
+    window.set_fullscreen(saved.fullscreen)?;
+    #[cfg(target_os = "macos")]
+    window.set_title_bar_style(tauri::TitleBarStyle::Overlay)?;
+    #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
+    if saved.menu_visible {
+        window.show_menu()?;
+    }
+    window.set_decorations(saved.decorations)?;
+    if saved.fullscreen {
+        return Ok(());
+    }
+    if saved.maximized {
+        return window.maximize();
+    }
+    // AppKit restores the frame itself when fullscreen ends; elsewhere the
+    // window comes back at whatever size the window manager picks.
+    #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
+    {
+        if let Some(size) = saved.size {
+            window.set_size(size)?;
+        }
+        if let Some(position) = saved.position {
+            window.set_position(position)?;
+        }
+    }
+    Ok(())
+}
+
+/// Show the focused window's mode in the View menu (the menu is app-wide).
+pub fn sync_menu_check(app: &AppHandle, label: &str) {
+    let _enabled = app
+        .try_state::<DistractionFreeState>()
+        .is_some_and(|state| state.lock().contains_key(label));
+    #[cfg(not(target_os = "ios"))]
+    crate::menu::set_check_item(app, MENU_ID, _enabled);
+}
+
+/// Drop the saved state of a closed window.
+pub fn forget(app: &AppHandle, label: &str) {
+    if let Some(state) = app.try_state::<DistractionFreeState>() {
+        state.lock().remove(label);
+    }
+}
+
+/// Enter or leave distraction-free mode for window `label`. Returns whether
+/// the window is now in the mode.
+#[tauri::command]
+pub fn set_distraction_free(
+    app: AppHandle,
+    state: State<'_, DistractionFreeState>,
+    label: String,
+    enabled: bool,
+) -> Result<bool, String> {
+    let window = app.get_webview_window(&label).ok_or("Window not found")?;
+    {
+        let mut windows = state.lock();
+        match (enabled, windows.remove(&label)) {
+            (true, None) => {
+                let saved = enter(&window).map_err(|e| e.to_string())?;
+                windows.insert(label.clone(), saved);
+            }
+            (true, Some(saved)) => {
+                windows.insert(label.clone(), saved);
+            }
+            (false, Some(saved)) => leave(&window, saved).map_err(|e| e.to_string())?,
+            (false, None) => {}
+        }
+    }
+    sync_menu_check(&app, &label);
+    Ok(enabled)
+}
@@ -3,15 +3,19 @@ use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Mutex;
 use tauri::{Emitter, Manager};
 
+mod ai_shortcut;
 mod commands;
+mod distraction_free;
 #[cfg(target_os = "macos")]
 mod dock;
 #[cfg(not(target_os = "ios"))]
 mod menu;
 #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
 mod tray;
+mod window_fit;
 
-/// Holds file paths requested to be opened via OS file association or CLI args.
+/// Holds file paths requested to be opened via OS file association or CLI args,
+/// queued until the main window takes them. Each entry is handed out once.
 pub struct OpenedFiles(pub Mutex<Vec<String>>);
 
 /// Maps window labels to file paths that should be opened when the window mounts.
@@ -32,8 +36,9 @@ pub struct TabTransferData {
 /// Maps window labels to pending tab data for newly created windows (tab detach).
 pub struct PendingTabData(pub Mutex<HashMap<String, TabTransferData>>);
 
-/// Tracks whether the main window has called get_opened_file (i.e. frontend is ready).
-/// Used to distinguish cold-start file association from runtime file opens.
+/// Tracks whether the main window has called drain_pending_open_files (i.e. its
+/// listeners are installed). Used to distinguish cold-start file association
+/// from runtime file opens.
 pub struct MainWindowReady(pub AtomicBool);
 
 /// Holds a pending Picora deep-link payload received before the frontend is ready.
@@ -63,39 +68,19 @@ pub(crate) static FOCUSED_WINDOW_LABEL: Mutex<Option<String>> = Mutex::new(None)
 #[cfg(not(target_os = "macos"))]
 static SAVED_WINDOW_POSITIONS: Mutex<Option<HashMap<String, (f64, f64)>>> = Mutex::new(None);
 
-/// Tracks the number of focused Moraya windows (Windows only).
-/// Used to register/unregister the global Ctrl+Shift+I shortcut so it
-/// intercepts the key before WebView2 opens DevTools.
-#[cfg(target_os = "windows")]
-static FOCUSED_WINDOW_COUNT: AtomicU32 = AtomicU32::new(0);
-
-/// Windows/Linux: shrink window if it exceeds the available screen area.
-/// Reserves space for OS taskbar (~48px) and window decorations (~52px).
-#[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
-fn fit_window_to_screen(window: &tauri::WebviewWindow) {
-    use tauri::WebviewWindow;
-    let _ = &window; // suppress unused import warning in cfg-off builds
-    if let Ok(Some(monitor)) = WebviewWindow::current_monitor(window) {
-        let scale = monitor.scale_factor();
-        let monitor_h = monitor.size().height as f64 / scale;
-        let monitor_w = monitor.size().width as f64 / scale;
-
-        // Reserve ~100 logical px for taskbar + title bar + menu bar + borders
-        let max_h = monitor_h - 100.0;
-        let max_w = monitor_w - 20.0;
-
-        if let Ok(current) = WebviewWindow::inner_size(window) {
-            let cur_w = current.width as f64 / scale;
-            let cur_h = current.height as f64 / scale;
-            let new_w = if cur_w > max_w && max_w >= 600.0 { max_w } else { cur_w };
-            let new_h = if cur_h > max_h && max_h >= 400.0 { max_h } else { cur_h };
-
-            if (new_w - cur_w).abs() > 1.0 || (new_h - cur_h).abs() > 1.0 {
-                let _ = window.set_size(tauri::LogicalSize::new(new_w, new_h));
-                let _ = window.center();
-            }
-        }
+/// Whether `set_window_alpha` has parked the window off-screen on purpose;
+/// window fitting must leave it there.
+pub(crate) fn is_parked_offscreen(_label: &str) -> bool {
+    #[cfg(not(target_os = "macos"))]
+    {
+        let guard = match SAVED_WINDOW_POSITIONS.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        };
+        guard.as_ref().is_some_and(|map| map.contains_key(_label))
     }
+    #[cfg(target_os = "macos")]
+    false
 }
 
 #[tauri::command]
@@ -124,13 +109,13 @@ fn update_mcp_menu(_app: tauri::AppHandle, _servers: Vec<menu::MCPMenuServer>, _
 
 /// Called by the frontend once it's ready; returns the file path to open (if any).
 /// For new windows created via drag-drop, looks up PendingFiles by window label.
-/// For the main window, falls back to OpenedFiles (startup CLI args / file association).
+/// For the main window, takes the first OpenedFiles entry (startup CLI args /
+/// file association); the rest come from drain_pending_open_files.
 #[tauri::command]
 fn get_opened_file(
     window: tauri::Window,
     state: tauri::State<'_, OpenedFiles>,
     pending: tauri::State<'_, PendingFiles>,
-    ready: tauri::State<'_, MainWindowReady>,
 ) -> Option<String> {
     let label = window.label();
 
@@ -144,16 +129,50 @@ fn get_opened_file(
 
     // Main window: fall back to startup OpenedFiles
     if label == "main" {
-        // Mark main window as ready so future RunEvent::Opened events
-        // create new windows instead of routing to main
-        ready.0.store(true, Ordering::SeqCst);
-        let files = state.0.lock().unwrap();
-        return files.first().cloned();
+        let mut files = state.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !files.is_empty() {
+            return Some(files.remove(0));
+        }
     }
 
     None
 }
 
+/// Queue `path` for the main window unless its frontend has already drained
+/// the queue. The flag is checked under the queue lock so a concurrent drain
+/// can't miss the entry. Returns false when the caller must open it itself.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn queue_opened_file(files: &OpenedFiles, ready: &MainWindowReady, path: String) -> bool {
+    let mut queue = files.0.lock().unwrap_or_else(|e| e.into_inner());
+    if ready.0.load(Ordering::SeqCst) {
+        return false;
+    }
+    queue.push(path);
+    true
+}
+
+/// Take every queued path and mark the main window ready, so later
+/// RunEvent::Opened events create new windows instead of queueing.
+fn drain_opened_files(files: &OpenedFiles, ready: &MainWindowReady) -> Vec<String> {
+    let mut queue = files.0.lock().unwrap_or_else(|e| e.into_inner());
+    ready.0.store(true, Ordering::SeqCst);
+    std::mem::take(&mut *queue)
+}
+
+/// Called by the main window once its `open-file` listener is installed and
+/// its startup document is restored; returns files opened in the meantime.
+#[tauri::command]
+fn drain_pending_open_files(
+    window: tauri::Window,
+    state: tauri::State<'_, OpenedFiles>,
+    ready: tauri::State<'_, MainWindowReady>,
+) -> Vec<String> {
+    if window.label() != "main" {
+        return Vec::new();
+    }
+    drain_opened_files(&state, &ready)
+}
+
 /// Create a new editor window, optionally for a specific file path.
 pub(crate) fn create_editor_window(
     app: &tauri::AppHandle,
@@ -431,6 +450,29 @@ fn set_window_alpha(app: tauri::AppHandle, label: String, alpha: f64) -> Result<
     Ok(())
 }
 
+/// Open or close the web inspector of window `label`. Refused unless the
+/// `developerMode` setting is on; release builds also need the `devtools`
+/// feature. Returns whether the inspector is now open.
+#[tauri::command]
+fn toggle_devtools(app: tauri::AppHandle, label: String) -> Result<bool, String> {
+    if !commands::settings::developer_mode() {
+        return Err("Developer mode is off".to_string());
+    }
+    let _win = app.get_webview_window(&label).ok_or("Window not found")?;
+    #[cfg(any(debug_assertions, feature = "devtools"))]
+    {
+        if _win.is_devtools_open() {
+            _win.close_devtools();
+            Ok(false)
+        } else {
+            _win.open_devtools();
+            Ok(true)
+        }
+    }
+    #[cfg(not(any(debug_assertions, feature = "devtools")))]
+    Err("This build does not include DevTools".to_string())
+}
+
 /// Move a window to a specific logical position (used for dragging detached tab windows).
 #[tauri::command]
 fn move_window(app: tauri::AppHandle, label: String, x: f64, y: f64) -> Result<(), String> {
@@ -537,6 +579,28 @@ fn parse_picora_deeplink(raw: &str) -> Option<serde_json::Value> {
     Some(serde_json::Value::Object(payload))
 }
 
+/// Label of the Moraya window that currently has keyboard focus, if any.
+pub(crate) fn focused_window_label(app: &tauri::AppHandle) -> Option<String> {
+    app.webview_windows()
+        .into_iter()
+        .find(|(_, w)| w.is_focused().unwrap_or(false))
+        .map(|(label, _)| label)
+}
+
+/// Emit `event` to the focused window only. Global shortcuts and the tray
+/// must not use `app.emit()`, which would act in every open window. Returns
+/// false (and drops the event) when no Moraya window is focused.
+pub(crate) fn emit_to_focused<S: serde::Serialize + Clone>(
+    app: &tauri::AppHandle,
+    event: &str,
+    payload: S,
+) -> bool {
+    let Some(label) = focused_window_label(app) else {
+        return false;
+    };
+    app.emit_to(&label, event, payload).is_ok()
+}
+
 /// Dispatch a deep-link URL: parse it, then either emit `picora-import-request`
 /// to the main window (if the frontend is ready) or buffer it for later pickup.
 fn handle_picora_deeplink(app: &tauri::AppHandle, raw_url: &str) {
@@ -571,6 +635,23 @@ fn take_pending_picora_import(
     state.0.lock().ok().and_then(|mut g| g.take())
 }
 
+#[cfg(test)]
+mod opened_files_tests {
+    use super::*;
+
+    #[test]
+    fn should_hand_out_queued_files_once() {
+        let files = OpenedFiles(Mutex::new(vec!["/a.md".into()]));
+        let ready = MainWindowReady(AtomicBool::new(false));
+        assert!(queue_opened_file(&files, &ready, "/b.md".into()));
+        assert_eq!(drain_opened_files(&files, &ready), vec!["/a.md", "/b.md"]);
+        assert!(drain_opened_files(&files, &ready).is_empty());
+        // After the drain the caller opens files itself
+        assert!(!queue_opened_file(&files, &ready, "/c.md".into()));
+        assert!(files.0.lock().unwrap().is_empty());
+    }
+}
+
 #[cfg(test)]
 mod deeplink_tests {
     use super::parse_picora_deeplink;
@@ -630,6 +711,8 @@ fn file_paths_from_args() -> Vec<String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    commands::crash_report::install_panic_hook();
+
     // Fix PATH for macOS GUI apps (Dock/Finder don't inherit shell PATH)
     #[cfg(not(target_os = "ios"))]
     let _ = fix_path_env::fix();
@@ -660,11 +743,12 @@ pub fn run() {
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_http::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_handler(move |app, _shortcut, event| {
+                .with_handler(move |app, shortcut, event| {
                     if event.state == tauri_plugin_global_shortcut::ShortcutState::Pressed {
-                        let _ = app.emit("menu:view_ai_panel", ());
+                        ai_shortcut::handle_pressed(app, shortcut);
                     }
                 })
                 .build(),
@@ -674,42 +758,117 @@ pub fn run() {
         .manage(commands::kb::KBIndexState::new())
         .manage(commands::speech_proxy::SpeechProxyState::new())
         .manage(commands::speech_proxy::RtDialogueState::new())
+        .manage(commands::tts_proxy::TtsProxyState::new())
         .manage(commands::plugin_manager::PluginProcessManager::new())
         .manage(commands::pdf_export::PdfExportState::new())
+        .manage(commands::image_capture::ImageCaptureState::new())
+        .manage(commands::update::UpdateDownloadState::new())
+        .manage(commands::user_presence::UserPresenceState::new())
+        .manage(commands::encryption::EncryptionState::new())
+        .manage(commands::clipper::ClipperState::new())
+        .manage(commands::backup::BackupState::new())
+        .manage(commands::undo_delete::UndoDeleteState::new())
+        .manage(commands::workspace_watch::WorkspaceWatchState::new())
+        .manage(commands::dir_scan::DirScanState::new())
+        .manage(commands::window_access::WindowAccess::new())
+        .manage(distraction_free::DistractionFreeState::new())
+        .manage(ai_shortcut::AiShortcutState::new())
         .manage(OpenedFiles(Mutex::new(initial_files)))
         .manage(PendingFiles(Mutex::new(HashMap::new())))
         .manage(PendingTabData(Mutex::new(HashMap::new())))
         .manage(MainWindowReady(AtomicBool::new(false)))
         .manage(PendingPicoraImport(Mutex::new(None)))
         .manage(DockDocumentTracker(Mutex::new(HashMap::new())))
-        .invoke_handler(tauri::generate_handler![
+        // Gated commands (the secrets, object-storage, mcp and plugins sets)
+        // are checked against the calling window; see `window_access`.
+        .invoke_handler(commands::window_access::gated(tauri::generate_handler![
             commands::file::read_file,
+            commands::cloud_files::get_file_availability,
             commands::file::read_file_binary,
             commands::file::read_resource_file,
             commands::file::write_file,
             commands::file::write_file_binary,
             commands::file::write_file_bytes,
+            commands::file::make_writable,
             commands::pdf_export::export_pdf_native,
             commands::pdf_export::export_print_ready,
+            commands::image_capture::capture_document_png,
+            commands::image_capture::capture_document_ready,
+            commands::rewrap::rewrap_text,
+            commands::invisible_chars::clean_invisible_chars,
+            commands::markdown_format::format_markdown,
+            commands::markdown_format::lint_markdown,
+            commands::footnotes::normalize_footnotes,
+            commands::backlinks::check_references,
+            commands::backlinks::remove_or_redirect_links,
+            commands::title_sync::sync_title,
+            commands::pdf_export::export_pdf,
+            commands::export_preset::read_export_preset,
+            commands::epub_export::export_epub,
+            commands::docx_export::export_docx,
+            commands::html_assets::inline_html_assets,
             commands::file::read_dir_recursive,
+            commands::dir_scan::scan_dir_recursive,
+            commands::dir_scan::cancel_dir_scan,
+            commands::file_tags::get_file_tags,
+            commands::file_tags::set_file_tags,
+            commands::workspace_watch::watch_workspace,
+            commands::workspace_watch::unwatch_workspace,
             commands::file::migrate_voice_profiles_dir,
             commands::file::create_markdown_file,
+            commands::daily_note::open_daily_note,
+            commands::daily_note::list_daily_notes,
+            commands::drafts::save_draft,
+            commands::drafts::list_recoverable_drafts,
+            commands::drafts::discard_draft,
+            commands::encryption::read_file_encrypted,
+            commands::encryption::write_file_encrypted,
+            commands::encryption::lock_encrypted_documents,
+            commands::link_metadata::fetch_link_metadata,
+            commands::links::open_link,
+            commands::html_markdown::html_to_markdown,
+            commands::clipper::clipper_enable,
+            commands::clipper::clipper_disable,
+            commands::clipper::clipper_status,
+            commands::backup::backup_workspace,
+            commands::backup::run_backup_now,
+            commands::backup::get_backup_status,
+            commands::assets::find_orphaned_assets,
+            commands::assets::delete_orphaned_assets,
             commands::file::create_dir,
             commands::file::rename_file,
             commands::file::delete_file,
+            commands::undo_delete::delete_file_with_undo,
+            commands::undo_delete::undo_delete,
             commands::file::read_file_previews,
             commands::file::get_files_mtime,
-            commands::mcp::mcp_connect_stdio,
-            commands::mcp::mcp_send_request,
-            commands::mcp::mcp_send_notification,
-            commands::mcp::mcp_disconnect,
-            commands::mcp::check_command_exists,
-            commands::keychain::keychain_set,
-            commands::keychain::keychain_get,
-            commands::keychain::keychain_delete,
+            commands::file::get_relative_path,
+            commands::clipboard::copy_to_clipboard,
+            commands::search::replace_in_files,
+            commands::search_index::search_index_build,
+            commands::search_index::search_index_query,
+            commands::search_index::search_index_status,
+            commands::tags::build_tag_index,
+            commands::tags::get_files_for_tag,
+            commands::diagram::render_diagram,
+            commands::app_log::log_get_recent,
+            commands::app_log::log_set_level,
+            commands::app_log::log_get_path,
+            commands::crash_report::list_crash_reports,
+            commands::crash_report::read_crash_report,
+            commands::settings::settings_get,
+            commands::settings::settings_set,
+            commands::settings::settings_get_all,
+            commands::user_presence::is_biometric_available,
             commands::ai_proxy::ai_proxy_fetch,
             commands::ai_proxy::ai_proxy_stream,
             commands::ai_proxy::ai_proxy_abort,
+            commands::ai_proxy::ai_proxy_resume,
+            commands::ai_proxy::ai_proxy_ack,
+            commands::ai_proxy::ai_proxy_detached_streams,
+            commands::ai_proxy::ai_list_models,
+            commands::ai_usage::usage_summary,
+            commands::ai_usage::usage_clear,
             commands::kb::kb_index_files,
             commands::kb::kb_index_single_file,
             commands::kb::kb_search,
@@ -724,8 +883,11 @@ pub fn run() {
             commands::kb::kb_suggest_filename,
             commands::update::get_platform_info,
             commands::update::exit_app,
+            commands::update::check_for_updates,
             commands::update::download_update,
-            commands::object_storage::upload_to_object_storage,
+            commands::update::cancel_update_download,
+            commands::update::install_update,
+            commands::update::cleanup_old_updates,
             commands::image_hosting_picora::upload_to_picora,
             commands::image_hosting_picora::verify_picora_token,
             commands::image_hosting_picora::test_picora_connection,
@@ -733,24 +895,21 @@ pub fn run() {
             take_pending_picora_import,
             commands::speech_proxy::speech_proxy_start,
             commands::speech_proxy::speech_proxy_send_audio,
+            commands::speech_proxy::speech_proxy_send_audio_raw,
             commands::speech_proxy::speech_proxy_stop,
+            commands::speech_proxy::speech_proxy_finalize,
+            commands::speech_proxy::speech_validate_config,
+            commands::speech_transcript::speech_format_transcript,
+            commands::speech_transcript::speech_export_transcript,
+            commands::speech_proxy::speech_proxy_get_session,
+            commands::speech_proxy::speech_proxy_list_sessions,
+            commands::speech_proxy::speech_proxy_set_max_sessions,
+            commands::tts_proxy::tts_proxy_start,
+            commands::tts_proxy::tts_proxy_abort,
             commands::speech_proxy::rt_dialogue_start,
             commands::speech_proxy::rt_dialogue_send_text,
             commands::speech_proxy::rt_dialogue_send_audio,
             commands::speech_proxy::rt_dialogue_stop,
-            commands::plugin_manager::plugin_validate_manifest,
-            commands::plugin_manager::plugin_install_local,
-            commands::plugin_manager::plugin_install_from_url,
-            commands::plugin_manager::plugin_enable,
-            commands::plugin_manager::plugin_disable,
-            commands::plugin_manager::plugin_uninstall,
-            commands::plugin_manager::plugin_list_running,
-            commands::plugin_manager::plugin_invoke,
-            commands::plugin_manager::plugin_registry_fetch,
-            commands::plugin_manager::plugin_fetch_blacklist,
-            commands::plugin_manager::plugin_fetch_github_asset,
-            commands::plugin_manager::download_renderer_plugin,
-            commands::plugin_manager::delete_renderer_plugin,
             commands::git::git_check_installed,
             commands::git::git_clone,
             commands::git::git_init_and_push,
@@ -766,6 +925,7 @@ pub fn run() {
             commands::git::git_show_file,
             commands::git::git_blame,
             commands::git::git_in_merge,
+            commands::git_publish::publish_to_git_repo,
             commands::kb_sync::picora_kb_list,
             commands::kb_sync::picora_kb_create,
             commands::kb_sync::picora_kb_manifest,
@@ -785,6 +945,11 @@ pub fn run() {
             set_menu_check,
             update_mcp_menu,
             get_opened_file,
+            drain_pending_open_files,
+            window_fit::fit_all_windows_to_screen,
+            toggle_devtools,
+            distraction_free::set_distraction_free,
+            ai_shortcut::set_ai_panel_shortcut,
             open_file_in_new_window,
             create_new_window,
             get_all_window_bounds,
@@ -795,8 +960,74 @@ pub fn run() {
             close_window_by_label,
             set_window_visible,
             register_dock_document,
-        ])
+            // Secrets
+            commands::keychain::keychain_set,
+            commands::keychain::keychain_get,
+            commands::keychain::keychain_get_protected,
+            commands::keychain::keychain_delete,
+            commands::keychain::keychain_list,
+            commands::keychain::secrets_export,
+            commands::keychain::secrets_import,
+            commands::keychain::get_secret_backend,
+            commands::keychain::migrate_secrets_to_keychain,
+            // Object storage
+            commands::object_storage::upload_to_object_storage,
+            commands::object_storage::upload_to_object_storage_by_config,
+            commands::object_storage::qiniu_sign_download_url,
+            commands::object_storage::storage_upload_abort,
+            commands::object_storage::delete_object,
+            commands::object_storage::list_objects,
+            // MCP servers
+            commands::mcp::mcp_connect_stdio,
+            commands::mcp::mcp_send_request,
+            commands::mcp::mcp_send_notification,
+            commands::mcp::mcp_disconnect,
+            commands::mcp::mcp_get_cached,
+            commands::mcp::mcp_read_resource,
+            commands::mcp::check_command_exists,
+            // Plugins
+            commands::plugin_manager::plugin_validate_manifest,
+            commands::plugin_manager::plugin_install_local,
+            commands::plugin_manager::plugin_install_from_url,
+            commands::plugin_manager::plugin_enable,
+            commands::plugin_manager::plugin_disable,
+            commands::plugin_manager::plugin_uninstall,
+            commands::plugin_manager::plugin_list_running,
+            commands::plugin_manager::plugin_list_running_ids,
+            commands::plugin_manager::plugin_state_load,
+            commands::plugin_manager::plugin_state_save,
+            commands::plugin_manager::plugin_scan_installed,
+            commands::plugin_manager::plugin_invoke,
+            commands::plugin_manager::plugin_read_logs,
+            commands::plugin_manager::plugin_dev_load,
+            commands::plugin_manager::plugin_dev_watch,
+            commands::plugin_manager::plugin_export_data,
+            commands::plugin_manager::plugin_import_data,
+            commands::plugin_manager::plugin_get_capabilities,
+            commands::plugin_manager::plugin_set_locale,
+            commands::plugin_manager::plugin_set_workspace_roots,
+            commands::plugin_manager::plugin_set_registry_mirror,
+            commands::plugin_manager::plugin_registry_mirrors,
+            commands::plugin_manager::plugin_registry_fetch,
+            commands::plugin_manager::plugin_fetch_blacklist,
+            commands::plugin_manager::plugin_fetch_github_asset,
+            commands::plugin_manager::download_renderer_plugin,
+            commands::plugin_manager::delete_renderer_plugin,
+        ]))
         .setup(|app| {
+            commands::app_log::init(app.handle());
+            commands::crash_report::init(app.handle());
+            commands::settings::init(app.handle());
+            commands::tags::init(app.handle());
+            commands::search_index::init(app.handle());
+            commands::drafts::init(app.handle());
+            commands::ai_usage::init(app.handle());
+            commands::cloud_files::init(app.handle());
+            // Built after settings load so its shared HTTP client gets the proxy
+            app.manage(commands::object_storage::ObjectStorageState::new());
+            commands::backup::start_scheduler(app.handle());
+            commands::undo_delete::start(app.handle());
+
             // Pre-warm OS keychain in background during app setup.
             // Windows Credential Manager can take 500ms–2s on cold start;
             // starting early means secrets are cached by the time the frontend
@@ -809,6 +1040,15 @@ pub fn run() {
                 });
             }
 
+            // Restart plugins left enabled in plugins/state.json. Runs off the
+            // main thread since spawning sidecars touches the filesystem.
+            {
+                let app_handle = app.handle().clone();
+                std::thread::spawn(move || {
+                    commands::plugin_manager::autostart_enabled_plugins(&app_handle);
+                });
+            }
+
             // Wire moraya:// deep-link delivery. Three entry points:
             //   1. Cold start via OS scheme association → on_open_url callback
             //   2. Cold start via CLI argv (Linux) → handled via single_instance plugin
@@ -847,7 +1087,9 @@ pub fn run() {
 
                 // Windows/Linux: shrink window to fit screen (taskbar/decorations)
                 #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
-                fit_window_to_screen(&window);
+                if window_fit::fit_window_to_screen(&window) {
+                    let _ = window.center();
+                }
 
                 // Create and set native menu
                 let app_handle = app.handle().clone();
@@ -879,17 +1121,15 @@ pub fn run() {
                     // Find target window: prefer focused, fall back to first visible
                     // non-pool window. NEVER broadcast to all windows — that causes
                     // duplicate actions (e.g., "New Window" creating N windows).
-                    let windows = app_handle_for_events.webview_windows();
-                    let target_label = windows
-                        .iter()
-                        .find(|(_, w)| w.is_focused().unwrap_or(false))
-                        .or_else(|| {
-                            windows.iter().find(|(l, w)| {
-                                !l.starts_with("moraya-pool-")
-                                    && w.is_visible().unwrap_or(false)
+                    let target_label = focused_window_label(&app_handle_for_events).or_else(|| {
+                        app_handle_for_events
+                            .webview_windows()
+                            .into_iter()
+                            .find(|(l, w)| {
+                                !l.starts_with("moraya-pool-") && w.is_visible().unwrap_or(false)
                             })
-                        })
-                        .map(|(label, _)| label.clone());
+                            .map(|(label, _)| label)
+                    });
 
                     let Some(label) = target_label else {
                         return;
@@ -899,7 +1139,8 @@ pub fn run() {
                     // payload so the frontend can SET (not toggle) the value.
                     match id {
                         "view_mode_visual" | "view_mode_source" | "view_mode_split"
-                        | "view_sidebar" | "view_ai_panel" | "view_outline" => {
+                        | "view_sidebar" | "view_ai_panel" | "view_outline"
+                        | "view_distraction_free" => {
                             if let Some(checked) = menu::get_check_state(&app_handle_for_events, id) {
                                 let _ = app_handle_for_events.emit_to(&label, &event_name, checked);
                             }
@@ -924,29 +1165,40 @@ pub fn run() {
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|_app, _event| {
-            // Windows: register/unregister global Ctrl+Shift+I shortcut based on
-            // window focus to intercept the key before WebView2 opens DevTools.
-            #[cfg(target_os = "windows")]
+            // Keep windows reachable after DPI or monitor changes, the
+            // distraction-free menu check in line with the focused window,
+            // and the AI panel shortcut registered while it should be
+            if let tauri::RunEvent::WindowEvent { label, event, .. } = &_event {
+                window_fit::handle_window_event(_app, label, event);
+                ai_shortcut::handle_window_event(_app, label, event);
+                match event {
+                    tauri::WindowEvent::Focused(true) => {
+                        distraction_free::sync_menu_check(_app, label)
+                    }
+                    tauri::WindowEvent::Destroyed => distraction_free::forget(_app, label),
+                    _ => {}
+                }
+                // Nothing can undo its deletions once the window is gone
+                commands::undo_delete::handle_window_event(_app, label, event);
+            }
+            if let tauri::RunEvent::Exit = _event {
+                commands::undo_delete::finalize_all(_app);
+            }
+
+            // Windows/Linux: with the `closeToTray` setting on, closing the main
+            // window hides it instead; the tray icon brings it back.
+            #[cfg(all(not(target_os = "macos"), not(target_os = "ios")))]
             {
                 if let tauri::RunEvent::WindowEvent {
-                    event: tauri::WindowEvent::Focused(focused),
+                    label,
+                    event: tauri::WindowEvent::CloseRequested { api, .. },
                     ..
                 } = &_event
                 {
-                    use tauri_plugin_global_shortcut::{
-                        Code, GlobalShortcutExt, Modifiers, Shortcut,
-                    };
-                    let shortcut =
-                        Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyI);
-                    if *focused {
-                        let prev = FOCUSED_WINDOW_COUNT.fetch_add(1, Ordering::SeqCst);
-                        if prev == 0 {
-                            let _ = _app.global_shortcut().register(shortcut);
-                        }
-                    } else {
-                        let prev = FOCUSED_WINDOW_COUNT.fetch_sub(1, Ordering::SeqCst);
-                        if prev == 1 {
-                            let _ = _app.global_shortcut().unregister(shortcut);
+                    if label == "main" && commands::settings::close_to_tray() {
+                        api.prevent_close();
+                        if let Some(window) = _app.get_webview_window(label) {
+                            let _ = window.hide();
                         }
                     }
                 }
@@ -1000,25 +1252,24 @@ pub fn run() {
                 match &_event {
                     // Handle macOS "Open With" / file association events
                     tauri::RunEvent::Opened { urls } => {
-                        let main_ready = _app
-                            .try_state::<MainWindowReady>()
-                            .map(|s| s.0.load(Ordering::SeqCst))
-                            .unwrap_or(false);
-
                         for u in urls {
                             if u.scheme() == "file" {
                                 if let Ok(p) = u.to_file_path() {
                                     let path = p.to_string_lossy().into_owned();
 
-                                    if !main_ready {
-                                        // Cold start: store file for the main window to pick up
-                                        // via get_opened_file(). Also emit open-file in case the
-                                        // frontend has already called get_opened_file.
-                                        if let Some(opened) = _app.try_state::<OpenedFiles>() {
-                                            opened.0.lock().unwrap().push(path.clone());
+                                    // Cold start: queue the file for the main window, which
+                                    // takes it exactly once via get_opened_file() or
+                                    // drain_pending_open_files().
+                                    let queued = match (
+                                        _app.try_state::<OpenedFiles>(),
+                                        _app.try_state::<MainWindowReady>(),
+                                    ) {
+                                        (Some(files), Some(ready)) => {
+                                            queue_opened_file(&files, &ready, path.clone())
                                         }
-                                        let _ = _app.emit("open-file", &path);
-                                    } else {
+                                        _ => false,
+                                    };
+                                    if !queued {
                                         // Runtime: create a new window for the file.
                                         // Also emit open-file to all windows so an existing
                                         // window can pick it up if window creation fails.
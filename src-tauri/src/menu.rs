@@ -47,9 +47,12 @@ pub fn create_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
     // File menu
     let file_new = MenuItem::with_id(app, "file_new", "New", true, Some("CmdOrCtrl+N"))?;
     let file_new_window = MenuItem::with_id(app, "file_new_window", "New Window", true, Some("CmdOrCtrl+Shift+N"))?;
+    let file_new_daily_note = MenuItem::with_id(app, "file_new_daily_note", "New Daily Note", true, Some("CmdOrCtrl+Shift+D"))?;
     let file_open = MenuItem::with_id(app, "file_open", "Open...", true, Some("CmdOrCtrl+O"))?;
     let file_save = MenuItem::with_id(app, "file_save", "Save", true, Some("CmdOrCtrl+S"))?;
     let file_save_as = MenuItem::with_id(app, "file_save_as", "Save As...", true, Some("CmdOrCtrl+Shift+S"))?;
+    let file_copy_path = MenuItem::with_id(app, "file_copy_path", "Copy Path", true, None::<&str>)?;
+    let file_copy_relative_path = MenuItem::with_id(app, "file_copy_relative_path", "Copy Relative Path", true, None::<&str>)?;
     let export_submenu = Submenu::with_id_and_items(
         app,
         "menu_export",
@@ -59,7 +62,9 @@ pub fn create_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
             &MenuItem::with_id(app, "file_export_html", "HTML", true, Some("CmdOrCtrl+Shift+E"))?,
             &MenuItem::with_id(app, "file_export_pdf", "PDF", true, None::<&str>)?,
             &MenuItem::with_id(app, "file_export_image", "Image (PNG)", true, None::<&str>)?,
-            &MenuItem::with_id(app, "file_export_doc", "Word (.doc)", true, None::<&str>)?,
+            &MenuItem::with_id(app, "file_export_docx", "Word (.docx)", true, None::<&str>)?,
+            &MenuItem::with_id(app, "file_export_epub", "EPUB", true, None::<&str>)?,
+            &MenuItem::with_id(app, "file_export_text", "Plain Text (.txt)", true, None::<&str>)?,
         ],
     )?;
     let close_window = PredefinedMenuItem::close_window(app, Some("Close Window"))?;
@@ -73,11 +78,15 @@ pub fn create_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
         &[
             &file_new,
             &file_new_window,
+            &file_new_daily_note,
             &file_open,
             &PredefinedMenuItem::separator(app)?,
             &file_save,
             &file_save_as,
             &PredefinedMenuItem::separator(app)?,
+            &file_copy_path,
+            &file_copy_relative_path,
+            &PredefinedMenuItem::separator(app)?,
             &export_submenu,
             &PredefinedMenuItem::separator(app)?,
             &close_window,
@@ -95,11 +104,15 @@ pub fn create_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
             &[
                 &file_new,
                 &file_new_window,
+                &file_new_daily_note,
                 &file_open,
                 &PredefinedMenuItem::separator(app)?,
                 &file_save,
                 &file_save_as,
                 &PredefinedMenuItem::separator(app)?,
+                &file_copy_path,
+                &file_copy_relative_path,
+                &PredefinedMenuItem::separator(app)?,
                 &export_submenu,
                 &PredefinedMenuItem::separator(app)?,
                 &preferences,
@@ -213,6 +226,7 @@ pub fn create_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
             &CheckMenuItem::with_id(app, "view_sidebar", "Toggle Sidebar", true, false, Some("CmdOrCtrl+\\"))?,
             &CheckMenuItem::with_id(app, "view_ai_panel", "Toggle AI Panel", true, false, Some("CmdOrCtrl+Shift+I"))?,
             &CheckMenuItem::with_id(app, "view_outline", "Toggle Outline", true, false, Some("CmdOrCtrl+Shift+O"))?,
+            &CheckMenuItem::with_id(app, "view_distraction_free", "Distraction-Free Mode", true, false, Some("F11"))?,
             &PredefinedMenuItem::separator(app)?,
             &MenuItem::with_id(app, "view_zoom_in", "Zoom In", true, Some("CmdOrCtrl+="))?,
             &MenuItem::with_id(app, "view_zoom_out", "Zoom Out", true, Some("CmdOrCtrl+-"))?,
@@ -253,6 +267,8 @@ pub fn create_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
             &PredefinedMenuItem::minimize(app, None)?,
             &PredefinedMenuItem::maximize(app, None)?,
             &PredefinedMenuItem::fullscreen(app, None)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "window_fit_to_screen", "Fit Windows to Screen", true, None::<&str>)?,
         ],
     )?;
 
@@ -2,6 +2,8 @@ use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
 use tauri::{Emitter, Manager};
 
+use crate::emit_to_focused;
+
 /// Set up a system tray icon with a context menu for Windows/Linux.
 /// Provides quick access to New Window, Open File, Settings, and Quit.
 pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
@@ -76,12 +78,8 @@ pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
                     let _ = crate::create_editor_window(app, &pending, None);
                 }
             }
-            "tray_open_file" => {
-                let _ = app.emit("menu:file_open", ());
-            }
-            "tray_settings" => {
-                let _ = app.emit("menu:preferences", ());
-            }
+            "tray_open_file" => emit_to_window(app, "menu:file_open"),
+            "tray_settings" => emit_to_window(app, "menu:preferences"),
             "tray_quit" => {
                 app.exit(0);
             }
@@ -91,3 +89,17 @@ pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Tray actions go to the focused window. Clicking the tray usually takes
+/// focus away from Moraya, so fall back to bringing up the main window.
+fn emit_to_window(app: &tauri::AppHandle, event: &str) {
+    if emit_to_focused(app, event, ()) {
+        return;
+    }
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+        let _ = app.emit_to("main", event, ());
+    }
+}
@@ -0,0 +1,237 @@
+//! Keeping editor windows on screen: fitting them into the monitor work area
+//! at creation, after DPI or monitor changes, and on request from the
+//! Window menu.
+
+use tauri::{
+    AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow, WindowEvent,
+};
+
+/// Rectangle in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+/// Height of the strip at the top of a window that must stay on screen so
+/// the window can still be dragged, in logical px.
+const TITLE_BAR_HEIGHT: f64 = 32.0;
+/// How much of that strip has to be visible, in logical px.
+const MIN_VISIBLE_WIDTH: f64 = 100.0;
+/// Linux doesn't report the work area; leave this much room for panels.
+#[cfg(target_os = "linux")]
+const FALLBACK_PANEL_HEIGHT: f64 = 48.0;
+
+/// The part of `monitor` not covered by taskbars, docks or panels.
+fn work_area(monitor: &Monitor) -> Rect {
+    let area = monitor.work_area();
+    #[allow(unused_mut)]
+    let mut rect = Rect {
+        x: area.position.x,
+        y: area.position.y,
+        w: area.size.width as i32,
+        h: area.size.height as i32,
+    };
+    #[cfg(target_os = "linux")]
+    {
+        rect.h -= (FALLBACK_PANEL_HEIGHT * monitor.scale_factor()) as i32;
+    }
+    rect
+}
+
+/// Shrink `window` to fit `work`, then move it fully inside.
+fn fit_rect(window: Rect, work: Rect) -> Rect {
+    let w = window.w.min(work.w);
+    let h = window.h.min(work.h);
+    Rect {
+        x: window.x.clamp(work.x, work.x + work.w - w),
+        y: window.y.clamp(work.y, work.y + work.h - h),
+        w,
+        h,
+    }
+}
+
+/// Whether at least `min_visible` px of the window's title bar lie inside
+/// one of the work areas.
+fn is_reachable(window: Rect, works: &[Rect], title_bar: i32, min_visible: i32) -> bool {
+    works.iter().any(|work| {
+        let left = window.x.max(work.x);
+        let right = (window.x + window.w).min(work.x + work.w);
+        let top = window.y.max(work.y);
+        let bottom = (window.y + title_bar).min(work.y + work.h);
+        right - left >= min_visible.min(window.w) && bottom > top
+    })
+}
+
+fn outer_rect(window: &WebviewWindow) -> Option<Rect> {
+    let pos = window.outer_position().ok()?;
+    let size = window.outer_size().ok()?;
+    Some(Rect {
+        x: pos.x,
+        y: pos.y,
+        w: size.width as i32,
+        h: size.height as i32,
+    })
+}
+
+/// Minimized, maximized and fullscreen windows are sized by the OS, and
+/// windows parked off-screen by `set_window_alpha` stay where they are.
+fn is_adjustable(window: &WebviewWindow) -> bool {
+    !crate::is_parked_offscreen(window.label())
+        && !window.is_minimized().unwrap_or(false)
+        && !window.is_maximized().unwrap_or(false)
+        && !window.is_fullscreen().unwrap_or(false)
+}
+
+/// Apply `target` to `window`, keeping its frame size as it is.
+fn apply(window: &WebviewWindow, current: Rect, target: Rect) {
+    if (target.w, target.h) != (current.w, current.h) {
+        // set_size takes the inner size
+        if let Ok(inner) = window.inner_size() {
+            let frame_w = current.w - inner.width as i32;
+            let frame_h = current.h - inner.height as i32;
+            let _ = window.set_size(PhysicalSize::new(
+                (target.w - frame_w).max(1) as u32,
+                (target.h - frame_h).max(1) as u32,
+            ));
+        }
+    }
+    if (target.x, target.y) != (current.x, current.y) {
+        let _ = window.set_position(PhysicalPosition::new(target.x, target.y));
+    }
+}
+
+/// Shrink and move `window` into the work area of its monitor, or of the
+/// primary monitor when it is on none. Returns whether anything changed.
+pub fn fit_window_to_screen(window: &WebviewWindow) -> bool {
+    if !is_adjustable(window) {
+        return false;
+    }
+    let monitor = window
+        .current_monitor()
+        .ok()
+        .flatten()
+        .or_else(|| window.primary_monitor().ok().flatten());
+    let (Some(monitor), Some(current)) = (monitor, outer_rect(window)) else {
+        return false;
+    };
+    let target = fit_rect(current, work_area(&monitor));
+    if target == current {
+        return false;
+    }
+    apply(window, current, target);
+    true
+}
+
+/// Fit `window` if its title bar is on no monitor, e.g. after the display
+/// it was on was disconnected.
+fn ensure_reachable(window: &WebviewWindow) {
+    let (Ok(monitors), Some(current)) = (window.available_monitors(), outer_rect(window)) else {
+        return;
+    };
+    let works: Vec<Rect> = monitors.iter().map(work_area).collect();
+    let scale = window.scale_factor().unwrap_or(1.0);
+    let title_bar = (TITLE_BAR_HEIGHT * scale) as i32;
+    let min_visible = (MIN_VISIBLE_WIDTH * scale) as i32;
+    if !is_reachable(current, &works, title_bar, min_visible) {
+        fit_window_to_screen(window);
+    }
+}
+
+/// After a DPI change (moving to another display, docking or undocking),
+/// shrink the window to its new monitor without moving it, so a drag across
+/// displays isn't interrupted, then make sure it is still reachable.
+fn refit_after_scale_change(window: &WebviewWindow) {
+    if !is_adjustable(window) {
+        return;
+    }
+    let monitor = window.current_monitor().ok().flatten();
+    if let (Some(monitor), Some(current)) = (monitor, outer_rect(window)) {
+        let work = work_area(&monitor);
+        let target = Rect {
+            w: current.w.min(work.w),
+            h: current.h.min(work.h),
+            ..current
+        };
+        apply(window, current, target);
+    }
+    ensure_reachable(window);
+}
+
+/// Run-loop hook for window events that can leave a window off-screen.
+pub fn handle_window_event(app: &AppHandle, label: &str, event: &WindowEvent) {
+    if !matches!(
+        event,
+        WindowEvent::ScaleFactorChanged { .. } | WindowEvent::Moved(_)
+    ) {
+        return;
+    }
+    let Some(window) = app.get_webview_window(label) else {
+        return;
+    };
+    // Hidden windows (pool, print jobs) are left alone
+    if !window.is_visible().unwrap_or(false) {
+        return;
+    }
+    match event {
+        WindowEvent::ScaleFactorChanged { .. } => refit_after_scale_change(&window),
+        _ if is_adjustable(&window) => ensure_reachable(&window),
+        _ => {}
+    }
+}
+
+/// Bring every visible window back into its monitor's work area ("rescue
+/// my window"). Returns how many windows were moved or resized.
+#[tauri::command]
+pub fn fit_all_windows_to_screen(app: AppHandle) -> usize {
+    app.webview_windows()
+        .values()
+        .filter(|w| w.is_visible().unwrap_or(false))
+        .filter(|w| fit_window_to_screen(w))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    #[test]
+    fn should_shrink_and_clamp_windows_into_the_work_area() {
+        // Laptop work area below a 40 px top bar, taskbar at the bottom
+        let work = rect(0, 40, 1920, 1000);
+        // Sized for a 4K display and partly off to the right
+        assert_eq!(
+            fit_rect(rect(1500, 10, 3000, 1800), work),
+            rect(0, 40, 1920, 1000)
+        );
+        // Already inside: untouched
+        assert_eq!(
+            fit_rect(rect(100, 100, 800, 600), work),
+            rect(100, 100, 800, 600)
+        );
+        // Fits, but hangs off the bottom
+        assert_eq!(
+            fit_rect(rect(100, 900, 800, 600), work),
+            rect(100, 440, 800, 600)
+        );
+    }
+
+    #[test]
+    fn should_require_part_of_the_title_bar_on_some_monitor() {
+        let works = [rect(0, 0, 1920, 1040), rect(1920, 0, 2560, 1400)];
+        // Straddling both monitors
+        assert!(is_reachable(rect(1800, 100, 800, 600), &works, 32, 100));
+        // Only 50 px of the title bar visible on the left edge
+        assert!(!is_reachable(rect(-750, 100, 800, 600), &works, 32, 100));
+        // Left behind on a disconnected monitor
+        assert!(!is_reachable(rect(5000, 100, 800, 600), &works, 32, 100));
+        // Title bar above the top of every screen
+        assert!(!is_reachable(rect(100, -200, 800, 600), &works, 32, 100));
+    }
+}
@@ -1,42 +1,48 @@
 /**
  * Tauri implementation of `LinkOpener` from `@moraya/core`.
  *
- * Routes by the href shape:
- *   - Local file path (absolute / relative / file://) → `plugin-opener.openPath`
- *   - HTTP(S) / mailto / etc. URL → `plugin-opener.openUrl`
+ * Every href goes through the `open_link` command, which percent-decodes
+ * local paths, converts `file://` URLs (drive letters, UNC shares),
+ * resolves relative links against the current document's folder and opens
+ * web links and non-markdown files with the system defaults.
  *
- * The core's editor-props-plugin already runs the relative-path resolution
- * via `Platform.getCurrentFilePath()` before calling `linkOpener.open(href)`,
- * so the href received here is always either an absolute file path, an
- * absolute URL, or a verbatim string the consumer can pass through unchanged.
+ * Markdown documents come back to us and are opened in the editor via the
+ * `moraya:open-document` event; failures surface as `moraya:link-error`
+ * instead of being thrown into the editor's click handler.
  */
 
 import type { LinkOpener } from '@moraya/core'
+import { invoke } from '@tauri-apps/api/core'
+import { editorStore } from '$lib/stores/editor-store'
+import { commandErrorMessage } from '$lib/utils/command-error'
 
-function isLocalFilePath(href: string): boolean {
-  if (href.startsWith('/')) return true
-  if (/^[A-Za-z]:[/\\]/.test(href)) return true
-  if (href.startsWith('file://')) return true
-  return false
+type OpenedLink =
+  | { kind: 'document'; path: string; fragment: string | null }
+  | { kind: 'external'; url: string }
+  | { kind: 'file'; path: string }
+
+function currentDir(): string | null {
+  const path = editorStore.getState().currentFilePath
+  if (!path) return null
+  const cut = Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'))
+  return cut > 0 ? path.slice(0, cut) : null
 }
 
 export class TauriLinkOpener implements LinkOpener {
   open(href: string): void {
-    if (isLocalFilePath(href)) {
-      // Strip file:// prefix if present
-      let path = href
-      if (path.startsWith('file:///')) path = path.slice(7)
-      else if (path.startsWith('file://')) path = path.slice(5)
-      try { path = decodeURIComponent(path) } catch { /* keep */ }
-
-      import('@tauri-apps/plugin-opener')
-        .then(({ openPath }) => openPath(path))
-        .catch((e) => { console.warn('[TauriLinkOpener] openPath failed:', path, e) })
-    } else {
-      import('@tauri-apps/plugin-opener')
-        .then(({ openUrl }) => openUrl(href))
-        .catch((e) => { console.warn('[TauriLinkOpener] openUrl failed:', href, e) })
-    }
+    invoke<OpenedLink>('open_link', { href, baseDir: currentDir() })
+      .then((result) => {
+        if (result.kind === 'document') {
+          window.dispatchEvent(new CustomEvent('moraya:open-document', {
+            detail: { path: result.path, fragment: result.fragment },
+          }))
+        }
+      })
+      .catch((e) => {
+        window.dispatchEvent(new CustomEvent('moraya:link-error', {
+          detail: { message: commandErrorMessage(e) },
+        }))
+      })
   }
 }
 
@@ -0,0 +1,93 @@
+/**
+ * Smart link paste: a bare http(s) URL pasted on its own is inserted as-is
+ * right away, then replaced by a `[Page Title](url)` link once the page title
+ * arrives from the `fetch_link_metadata` Rust command.
+ *
+ * Pending pastes are tracked in plugin state and mapped through every
+ * transaction, so typing elsewhere doesn't lose them. If the pasted text was
+ * edited or deleted in the meantime, it is left alone.
+ */
+
+import { Plugin, PluginKey } from 'prosemirror-state';
+import type { EditorView } from 'prosemirror-view';
+import { fetchLinkMetadata } from '$lib/services/link-metadata';
+import { isTauri } from '$lib/utils/platform';
+
+interface PendingLink {
+  id: number;
+  from: number;
+  to: number;
+  url: string;
+}
+
+type LinkPasteMeta = { add: PendingLink } | { remove: number };
+
+const linkPasteKey = new PluginKey<PendingLink[]>('link-paste');
+
+const BARE_URL = /^https?:\/\/[^\s<>()[\]]+$/i;
+
+let nextId = 1;
+
+function upgradeLink(view: EditorView, id: number, url: string) {
+  const done: LinkPasteMeta = { remove: id };
+  fetchLinkMetadata(url)
+    .then((meta) => {
+      if (view.isDestroyed) return;
+      const pending = linkPasteKey.getState(view.state)?.find((p) => p.id === id);
+      const title = meta.title?.trim();
+      const { tr, schema } = view.state;
+      tr.setMeta(linkPasteKey, done);
+      if (pending && title && tr.doc.textBetween(pending.from, pending.to) === url) {
+        const link = schema.marks.link.create({ href: url, title: null });
+        tr.replaceWith(pending.from, pending.to, schema.text(title, [link]));
+      }
+      view.dispatch(tr);
+    })
+    .catch(() => {
+      if (!view.isDestroyed) {
+        view.dispatch(view.state.tr.setMeta(linkPasteKey, done));
+      }
+    });
+}
+
+export function createLinkPastePlugin(): Plugin<PendingLink[]> {
+  return new Plugin<PendingLink[]>({
+    key: linkPasteKey,
+
+    state: {
+      init: () => [],
+      apply(tr, pending) {
+        const meta = tr.getMeta(linkPasteKey) as LinkPasteMeta | undefined;
+        let next = tr.docChanged
+          ? pending
+            .map((p) => ({ ...p, from: tr.mapping.map(p.from, 1), to: tr.mapping.map(p.to, -1) }))
+            .filter((p) => p.to > p.from)
+          : pending;
+        if (meta && 'add' in meta) next = [...next, meta.add];
+        if (meta && 'remove' in meta) next = next.filter((p) => p.id !== meta.remove);
+        return next;
+      },
+    },
+
+    props: {
+      handlePaste(view, event) {
+        if (!isTauri) return false;
+        const url = event.clipboardData?.getData('text/plain')?.trim();
+        if (!url || !BARE_URL.test(url)) return false;
+        const { selection } = view.state;
+        const $from = selection.$from;
+        if ($from.parent.type.spec.code || view.state.schema.marks.code?.isInSet($from.marks())) {
+          return false;
+        }
+
+        const id = nextId++;
+        const from = selection.from;
+        const add: LinkPasteMeta = { add: { id, from, to: from + url.length, url } };
+        const tr = view.state.tr.insertText(url).setMeta(linkPasteKey, add);
+        view.dispatch(tr.scrollIntoView());
+        upgradeLink(view, id, url);
+        return true;
+      },
+    },
+  });
+}
@@ -36,6 +36,7 @@ import { editorStore } from '../stores/editor-store'
 import { isMacOS } from '../utils/platform'
 import { schema } from './schema'
 import { createReviewDecorationPlugin } from './plugins/review-decoration'
+import { createLinkPastePlugin } from './plugins/link-paste'
 
 // ── Public types (legacy moraya shape, kept for zero-modification call sites) ──
 
@@ -87,10 +88,11 @@ export async function createEditor(options: EditorOptions): Promise<MorayaEditor
     ...(options.changeDebounceMs !== undefined ? { changeDebounceMs: options.changeDebounceMs } : {}),
   }
 
-  // Build core's plugin array, then append moraya-only review-decoration.
+  // Build core's plugin array, then add the moraya-only plugins. Link paste
+  // goes first so its handlePaste runs before core's markdown paste handling.
   // (Cannot use coreCreateEditor directly because it doesn't accept extra plugins.)
   const corePlugins = await createEditorPlugins(baseOpts, schema as Schema)
-  const plugins = [...corePlugins, createReviewDecorationPlugin()]
+  const plugins = [createLinkPastePlugin(), ...corePlugins, createReviewDecorationPlugin()]
 
   // Tier 1 nodeViews — code_block NodeView (toolbar / picker / mermaid / renderer).
   // Re-call preloadEnhancementPlugins; cache hit returns instantly.
@@ -29,6 +29,7 @@ import { computeImageDir, isInsideKnowledgeBase } from './image-path-utils';
 import { refreshFileTree } from '$lib/services/file-watcher';
 import { documentDir } from '@tauri-apps/api/path';
 import { invoke } from '@tauri-apps/api/core';
+import { commandErrorCode, commandErrorMessage, type CommandError } from '$lib/utils/command-error';
 import { rendererManager } from '$lib/services/plugin/renderer-manager';
 import { reviewStore } from '$lib/services/review/review-store';
 import { locale as i18nLocale } from '$lib/i18n';
@@ -721,7 +722,7 @@ export async function executeAICommand(
       return '';
     }
     if (!isStale) {
-      const errMsg = error?.message || get(t)('errors.aiRequestFailed');
+      const errMsg = (error ? commandErrorMessage(error) : '') || get(t)('errors.aiRequestFailed');
       aiStore.setError(errMsg);
     }
     throw error;
@@ -1763,8 +1764,8 @@ export async function sendChatMessage(message: string, documentContext?: string,
       if (partial) {
         aiStore.addMessage({ role: 'assistant', content: partial, timestamp: Date.now() });
       }
-      // Tauri invoke rejects with a plain string; JS errors have .message
-      const errMsg = (typeof error === 'string' ? error : error?.message) || get(t)('errors.chatRequestFailed');
+      // Proxy commands reject with a CommandError; JS errors have .message
+      const errMsg = commandErrorMessage(error) || get(t)('errors.chatRequestFailed');
       aiStore.setError(errMsg);
     }
     throw error;
@@ -1779,7 +1780,9 @@ export async function sendChatMessage(message: string, documentContext?: string,
 /**
  * Test the AI connection with a given or active config
  */
-export async function testAIConnection(config?: AIProviderConfig): Promise<{ success: boolean; error?: string }> {
+export async function testAIConnection(
+  config?: AIProviderConfig,
+): Promise<{ success: boolean; error?: string; status?: number }> {
   const testConfig = config || aiStore.getActiveConfig();
   if (!testConfig) return { success: false, error: 'No configuration' };
 
@@ -1794,8 +1797,11 @@ export async function testAIConnection(config?: AIProviderConfig): Promise<{ suc
     });
     return { success: !!response.content };
   } catch (e: unknown) {
-    const error = typeof e === 'string' ? e : (e instanceof Error ? e.message : 'Connection failed');
-    return { success: false, error };
+    // HTTP status when the provider answered with an error
+    const status = commandErrorCode(e) === 'AI_HTTP_ERROR'
+      ? Number((e as CommandError).details?.status) || undefined
+      : undefined;
+    return { success: false, error: commandErrorMessage(e) || 'Connection failed', status };
   }
 }
 
@@ -1836,11 +1842,11 @@ export async function testAIConnectionWithResolve(
     const result = await testAIConnection({ ...config, baseUrl: url || undefined });
     if (result.success) return { success: true, resolvedBaseUrl: url };
     lastError = result.error;
-    // A non-404 "API error" means the endpoint was reached but the request
+    // A non-404 HTTP status means the endpoint was reached but the request
     // itself was rejected (e.g. 400 invalid body, 401 bad key, 429 rate limit).
     // Stop retrying — further URL stripping won't help and may surface a
     // misleading 404 from a non-existent stripped path.
-    if (typeof lastError === 'string' && lastError.startsWith('API error (') && !lastError.includes('API error (404)')) break;
+    if (result.status !== undefined && result.status !== 404) break;
   }
   return { success: false, error: lastError };
 }
@@ -10,6 +10,7 @@ import { sendAIRequest, openaiEndpoint } from './providers';
 import { generateBaseUrlCandidates } from './ai-service';
 import { extractOpenAICompatImageUrl, extractDashScopeImageUrl } from './image-response-parser';
 import { invoke } from '@tauri-apps/api/core';
+import { commandErrorCode, commandErrorMessage, type CommandError } from '$lib/utils/command-error';
 
 export interface ImageGenerationResult {
   url: string;
@@ -38,6 +39,22 @@ export type ImageStyle =
   // Portrait
   | 'portrait' | 'headshot' | 'fullbody' | 'fashion' | 'street' | 'glamour' | 'environmental' | 'candid' | 'group';
 
+/** Proxy args for a saved config: the backend reads its key from the keychain. */
+function savedKeyArgs(config: ImageProviderConfig) {
+  return { configId: config.id, keyPrefix: 'image-key:' };
+}
+
+/** Proxy args for connection tests, which may run on unsaved form values. */
+function formKeyArgs(config: ImageProviderConfig) {
+  return { ...savedKeyArgs(config), apiKeyOverride: config.apiKey || undefined };
+}
+
+/** HTTP status of a rejected `ai_proxy_fetch` when the provider answered with an error. */
+function httpStatus(e: unknown): number | undefined {
+  if (commandErrorCode(e) !== 'AI_HTTP_ERROR') return undefined;
+  return Number((e as CommandError).details?.status) || undefined;
+}
+
 /** Strip compatible-mode or api suffixes to get the DashScope base domain. */
 function dashScopeBase(baseURL: string): string {
   return baseURL.replace(/\/+$/, '').replace(/\/(compatible-mode|api)\/v\d+(\/.*)?$/, '');
@@ -95,9 +112,7 @@ async function callOpenAIImageAPI(
   });
 
   const responseText = await invoke<string>('ai_proxy_fetch', {
-    configId: config.id,
-    keyPrefix: 'image-key:',
-    apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+    ...savedKeyArgs(config),
     provider: 'openai',
     url,
     body: bodyPayload,
@@ -137,9 +152,7 @@ async function callDashScopeTaskAPI(
   for (const mode of ['sync', 'async'] as const) {
     try {
       const response = await invoke<string>('ai_proxy_fetch', {
-        configId: config.id,
-        keyPrefix: 'image-key:',
-        apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+        ...savedKeyArgs(config),
         provider: 'openai',
         url,
         body,
@@ -160,9 +173,7 @@ async function callDashScopeTaskAPI(
         for (let i = 0; i < 60; i++) {
           await new Promise(resolve => setTimeout(resolve, 2000));
           const statusResponse = await invoke<string>('ai_proxy_fetch', {
-            configId: config.id,
-            keyPrefix: 'image-key:',
-            apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+            ...savedKeyArgs(config),
             provider: 'openai',
             url: taskUrl,
             method: 'GET',
@@ -183,9 +194,8 @@ async function callDashScopeTaskAPI(
 
       throw new Error('No task_id or image URL in DashScope response');
     } catch (e) {
-      const msg = e instanceof Error ? e.message : String(e);
-      const lower = msg.toLowerCase();
-      if (msg.includes('(403)') && (lower.includes('async') || lower.includes('synchronous'))) {
+      const lower = commandErrorMessage(e).toLowerCase();
+      if (httpStatus(e) === 403 && (lower.includes('async') || lower.includes('synchronous'))) {
         continue;
       }
       throw e;
@@ -216,9 +226,7 @@ async function callDashScopeMultimodalAPI(
   });
 
   const response = await invoke<string>('ai_proxy_fetch', {
-    configId: config.id,
-    keyPrefix: 'image-key:',
-    apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+    ...savedKeyArgs(config),
     provider: 'openai',
     url,
     body,
@@ -253,9 +261,7 @@ async function generateImageGemini(
   });
 
   const responseText = await invoke<string>('ai_proxy_fetch', {
-    configId: config.id,
-    keyPrefix: 'image-key:',
-    apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+    ...savedKeyArgs(config),
     provider: 'gemini',
     url,
     body: bodyPayload,
@@ -308,8 +314,7 @@ export async function generateImage(
       try {
         return await callByEndpoint(cached, prompt, resolvedSize);
       } catch (e) {
-        const msg = e instanceof Error ? e.message : String(e);
-        if (msg.includes('(401)')) throw e;
+        if (httpStatus(e) === 401) throw e;
         _qwenEndpointCache.delete(config.id);
       }
     }
@@ -324,8 +329,7 @@ export async function generateImage(
         return result;
       } catch (e) {
         lastError = e;
-        const msg = e instanceof Error ? e.message : String(e);
-        if (msg.includes('(401)')) throw e;
+        if (httpStatus(e) === 401) throw e;
       }
     }
 
@@ -338,8 +342,7 @@ export async function generateImage(
         return result;
       } catch (e) {
         lastError = e;
-        const msg = e instanceof Error ? e.message : String(e);
-        if (msg.includes('(401)')) throw e;
+        if (httpStatus(e) === 401) throw e;
       }
     }
 
@@ -357,7 +360,7 @@ export async function generateImage(
  */
 export async function testImageConnection(config: ImageProviderConfig): Promise<{ success: boolean; error?: string }> {
   function errMsg(e: unknown): string {
-    return typeof e === 'string' ? e : (e instanceof Error ? e.message : 'Connection failed');
+    return commandErrorMessage(e) || 'Connection failed';
   }
 
   if (config.provider === 'qwen') {
@@ -368,22 +371,24 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
     // 401/403 (non-async) = bad API key → stop.
     let lastError: string | undefined;
 
-    function isDashScopeCallModeError(msg: string): boolean {
-      const lower = msg.toLowerCase();
-      return msg.includes('(403)') && (lower.includes('async') || lower.includes('synchronous'));
+    function isDashScopeCallModeError(e: unknown): boolean {
+      const lower = errMsg(e).toLowerCase();
+      return httpStatus(e) === 403 && (lower.includes('async') || lower.includes('synchronous'));
     }
 
-    function isDashScopeTestOK(msg: string): boolean {
+    function isDashScopeTestOK(e: unknown): boolean {
       // 403 sync/async is NOT proof the endpoint works — only that the call mode is wrong.
       // Let the loop try the other mode; only 400/422 (non-url-error) proves endpoint validity.
-      if ((msg.includes('(400)') || msg.includes('(422)')) && !msg.toLowerCase().includes('url error')) return true;
+      const status = httpStatus(e);
+      if ((status === 400 || status === 422) && !errMsg(e).toLowerCase().includes('url error')) return true;
       return false;
     }
 
-    function isDashScopeAuthError(msg: string): boolean {
-      if (msg.includes('(401)')) return true;
+    function isDashScopeAuthError(e: unknown): boolean {
+      const status = httpStatus(e);
+      if (status === 401) return true;
       // 403 NOT about sync/async = real auth/permission error
-      if (msg.includes('(403)') && !isDashScopeCallModeError(msg)) return true;
+      if (status === 403 && !isDashScopeCallModeError(e)) return true;
       return false;
     }
 
@@ -391,9 +396,7 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
     for (const url of dashScopeOpenAICandidates(config.baseURL)) {
       try {
         await invoke<string>('ai_proxy_fetch', {
-          configId: config.id,
-          keyPrefix: 'image-key:',
-          apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+          ...formKeyArgs(config),
           provider: 'openai',
           url,
           body: JSON.stringify({ model: config.model, prompt: 'test', n: 1 }),
@@ -402,11 +405,11 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
         return { success: true };
       } catch (e: unknown) {
         const msg = errMsg(e);
-        if (isDashScopeTestOK(msg)) {
+        if (isDashScopeTestOK(e)) {
           _qwenEndpointCache.set(config.id, { url });
           return { success: true };
         }
-        if (isDashScopeAuthError(msg)) return { success: false, error: msg };
+        if (isDashScopeAuthError(e)) return { success: false, error: msg };
         lastError = msg;
       }
     }
@@ -431,9 +434,7 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
       for (const asyncMode of [false, true]) {
         try {
           await invoke<string>('ai_proxy_fetch', {
-            configId: config.id,
-            keyPrefix: 'image-key:',
-            apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+            ...formKeyArgs(config),
             provider: 'openai',
             url,
             body: testBody,
@@ -443,11 +444,11 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
           return { success: true };
         } catch (e: unknown) {
           const msg = errMsg(e);
-          if (isDashScopeTestOK(msg)) {
+          if (isDashScopeTestOK(e)) {
             _qwenEndpointCache.set(config.id, { url, native: true, format });
             return { success: true };
           }
-          if (isDashScopeAuthError(msg)) {
+          if (isDashScopeAuthError(e)) {
             if (msg.toLowerCase().includes('synchronous') || msg.toLowerCase().includes('async')) continue;
             return { success: false, error: msg };
           }
@@ -465,9 +466,7 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
     const url = `${base}/v1beta/models`;
     try {
       await invoke<string>('ai_proxy_fetch', {
-        configId: config.id,
-        keyPrefix: 'image-key:',
-        apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+        ...formKeyArgs(config),
         provider: 'gemini',
         url,
         body: '{}',
@@ -475,7 +474,7 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
       return { success: true };
     } catch (e: unknown) {
       const msg = errMsg(e);
-      if (msg.includes('(400)')) return { success: true };
+      if (httpStatus(e) === 400) return { success: true };
       return { success: false, error: msg };
     }
   }
@@ -486,17 +485,16 @@ export async function testImageConnection(config: ImageProviderConfig): Promise<
   try {
     const url = openaiEndpoint(config.baseURL, '/images/generations');
     await invoke<string>('ai_proxy_fetch', {
-      configId: config.id,
-      keyPrefix: 'image-key:',
-      apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+      ...formKeyArgs(config),
       provider: 'openai',
       url,
       body: JSON.stringify({ model: config.model, prompt: 'test', n: 1 }),
     });
     return { success: true };
   } catch (e: unknown) {
+    const status = httpStatus(e);
+    if (status === 400 || status === 422) return { success: true };
     const msg = errMsg(e);
-    if (msg.includes('(400)') || msg.includes('(422)')) return { success: true };
     return { success: false, error: msg };
   }
 }
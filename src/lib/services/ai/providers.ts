@@ -13,6 +13,7 @@ import {
   parseGeminiToolCalls,
 } from './tool-bridge';
 import { invoke, Channel } from '@tauri-apps/api/core';
+import { commandErrorCode, commandErrorMessage } from '$lib/utils/command-error';
 
 /** Build OpenAI-compatible endpoint URL, avoiding double version prefix (e.g., /v3/v1/...) */
 export function openaiEndpoint(baseUrl: string, path: string): string {
@@ -64,8 +65,8 @@ async function proxyFetch(
     return JSON.parse(responseText);
   } catch (err) {
     console.error(`[AI] proxy fetch ERROR (${Math.round(performance.now() - t0)}ms):`, err);
-    // Convert Rust "Aborted by user" to standard AbortError
-    if (signal?.aborted || (typeof err === 'string' && err.includes('Aborted'))) {
+    // Convert the Rust-side abort to standard AbortError
+    if (signal?.aborted || commandErrorCode(err) === 'AI_ABORTED') {
       throw new DOMException('Aborted', 'AbortError');
     }
     throw err;
@@ -126,7 +127,7 @@ async function* streamViaProxy(
     waitResolve?.();
   }).catch((err: unknown) => {
     streamDone = true;
-    streamError = new Error(typeof err === 'string' ? err : 'Stream failed');
+    streamError = new Error(commandErrorMessage(err) || 'Stream failed');
     waitResolve?.();
   });
 
@@ -364,6 +365,10 @@ async function* streamOpenAICompatible(config: AIProviderConfig, request: AIRequ
     stream: true,
     messages: buildOpenAIMessages(request.messages),
   };
+  // Token counts for the usage ledger; not every compatible API accepts this
+  if (config.provider === 'openai' || config.provider === 'deepseek') {
+    body.stream_options = { include_usage: true };
+  }
 
   if (request.tools && request.tools.length > 0) {
     Object.assign(body, formatToolsForProvider(config.provider, request.tools));
@@ -676,6 +681,28 @@ function parseOpenAIStreamEvents(events: string[]): { toolCalls: ToolCallRequest
 
 // ── Public API ──
 
+export interface ModelInfo {
+  id: string;
+  displayName: string;
+  contextLength?: number;
+  capabilities?: string[];
+}
+
+/**
+ * Models the provider offers, normalized by the backend. `supported` is
+ * false for providers without a list endpoint.
+ */
+export async function listModels(
+  config: AIProviderConfig,
+): Promise<{ supported: boolean; models: ModelInfo[] }> {
+  return invoke('ai_list_models', {
+    configId: config.id,
+    provider: config.provider,
+    baseUrl: config.baseUrl || PROVIDER_BASE_URLS[config.provider] || '',
+    apiKeyOverride: config.apiKey !== '***' ? config.apiKey : undefined,
+  });
+}
+
 export async function sendAIRequest(config: AIProviderConfig, request: AIRequest, signal?: AbortSignal): Promise<AIResponse> {
   switch (config.provider) {
     case 'claude':
@@ -0,0 +1,33 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface OrphanedAsset {
+  path: string;
+  size: number;
+}
+
+/** Result of `find_orphaned_assets`. */
+export interface OrphanReport {
+  /** Nothing refers to these; safe to delete */
+  orphaned: OrphanedAsset[];
+  /** Not linked, but their name appears in a note; never deleted automatically */
+  uncertain: OrphanedAsset[];
+  orphanedBytes: number;
+  notesScanned: number;
+}
+
+export interface TrashedAssets {
+  trashed: string[];
+  /** Paths that were referenced or uncertain by the time of deletion */
+  skipped: string[];
+  trashDir: string | null;
+}
+
+/** Images and attachments under `root`'s assets/attachments folders that no note references. */
+export function findOrphanedAssets(root: string): Promise<OrphanReport> {
+  return invoke<OrphanReport>('find_orphaned_assets', { root });
+}
+
+/** Move orphans to the Moraya trash; paths are re-checked before moving. */
+export function deleteOrphanedAssets(root: string, paths: string[]): Promise<TrashedAssets> {
+  return invoke<TrashedAssets>('delete_orphaned_assets', { root, paths });
+}
@@ -0,0 +1,36 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { CommandError } from '$lib/utils/command-error';
+
+/** Payload of the `backup:completed` event and result of `run_backup_now`. */
+export interface BackupCompleted {
+  path: string;
+  files: number;
+  /** Archive size */
+  bytes: number;
+  /** Old archives deleted to stay within `maxBackups` */
+  removed: number;
+}
+
+export interface BackupStatus {
+  /** Milliseconds since the Unix epoch */
+  lastRun: number | null;
+  lastArchive: string | null;
+  lastError: CommandError | null;
+  /** Null when scheduled backups are off or not configured */
+  nextScheduled: number | null;
+  running: boolean;
+}
+
+/** Zip `source` into a new archive in `targetDir`; resolves to its path. */
+export function backupWorkspace(source: string, targetDir: string): Promise<string> {
+  return invoke<string>('backup_workspace', { source, targetDir });
+}
+
+/** Back up the configured vault now, outside the schedule. */
+export function runBackupNow(): Promise<BackupCompleted> {
+  return invoke<BackupCompleted>('run_backup_now');
+}
+
+export function getBackupStatus(): Promise<BackupStatus> {
+  return invoke<BackupStatus>('get_backup_status');
+}
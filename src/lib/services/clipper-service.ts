@@ -0,0 +1,24 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** Payload of the `clipper:received` event. */
+export interface ClipReceived {
+  title: string;
+  url: string | null;
+  /** File the clipping was written to */
+  path: string;
+}
+
+/** Start the local listener for the browser extension; resolves to the bound port. */
+export function enableClipper(port: number, token: string): Promise<number> {
+  return invoke<number>('clipper_enable', { port, token });
+}
+
+/** Stop the listener; resolves to whether one was running. */
+export function disableClipper(): Promise<boolean> {
+  return invoke<boolean>('clipper_disable');
+}
+
+/** Port of the running listener, or null when it is off. */
+export function clipperStatus(): Promise<number | null> {
+  return invoke<number | null>('clipper_status');
+}
@@ -0,0 +1,42 @@
+import { describe, it, expect } from 'vitest';
+import { renderDiagramBlocks, type RenderedDiagram } from './diagram-service';
+
+const ok = async (): Promise<RenderedDiagram> => ({
+  format: 'png',
+  mimeType: 'image/png',
+  data: 'AAAA',
+});
+
+const missing = async (): Promise<RenderedDiagram> => {
+  throw {
+    code: 'DIAGRAM_RENDERER_MISSING',
+    message: 'mmdc is not installed',
+    details: { renderer: 'mmdc', kind: 'mermaid' },
+  };
+};
+
+describe('renderDiagramBlocks', () => {
+  it('replaces mermaid and graphviz fences with data-URI images', async () => {
+    const md = 'Intro\n\n```mermaid\ngraph TD; A-->B\n```\n\n~~~graphviz\ndigraph {}\n~~~\n';
+    const { markdown, errors } = await renderDiagramBlocks(md, 'png', ['mermaid', 'dot'], ok);
+    expect(errors).toEqual([]);
+    expect(markdown).toContain('![mermaid diagram](data:image/png;base64,AAAA)');
+    expect(markdown).toContain('![dot diagram](data:image/png;base64,AAAA)');
+    expect(markdown).not.toContain('```');
+    expect(markdown.startsWith('Intro')).toBe(true);
+  });
+
+  it('only touches the requested kinds', async () => {
+    const md = '```mermaid\ngraph TD\n```\n\n```dot\ndigraph {}\n```';
+    const { markdown } = await renderDiagramBlocks(md, 'svg', ['dot'], ok);
+    expect(markdown).toContain('```mermaid\ngraph TD\n```');
+    expect(markdown).not.toContain('```dot');
+  });
+
+  it('keeps blocks as code and reports each error code once', async () => {
+    const md = '```mermaid\na\n```\n\n```mermaid\nb\n```';
+    const { markdown, errors } = await renderDiagramBlocks(md, 'png', ['mermaid'], missing);
+    expect(markdown).toBe(md);
+    expect(errors).toHaveLength(1);
+  });
+});
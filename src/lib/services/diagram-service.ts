@@ -0,0 +1,79 @@
+import { invoke } from '@tauri-apps/api/core';
+import { commandErrorCode } from '$lib/utils/command-error';
+
+/**
+ * Diagram rendering for exports via the `render_diagram` Rust command
+ * (mermaid-cli `mmdc` / Graphviz `dot`). The editor renders mermaid
+ * client-side; this covers export paths that have no DOM to render into.
+ */
+
+export type DiagramKind = 'mermaid' | 'dot';
+export type DiagramFormat = 'svg' | 'png';
+
+export interface RenderedDiagram {
+  format: DiagramFormat;
+  mimeType: string;
+  /** Base64-encoded image bytes */
+  data: string;
+}
+
+export function renderDiagram(
+  kind: DiagramKind,
+  source: string,
+  format: DiagramFormat,
+): Promise<RenderedDiagram> {
+  return invoke<RenderedDiagram>('render_diagram', { kind, source, format });
+}
+
+/** Fenced ```mermaid / ```dot / ```graphviz blocks (backtick or tilde fences). */
+const DIAGRAM_FENCE = /^(`{3,}|~{3,})[ \t]*(mermaid|dot|graphviz)[ \t]*\n([\s\S]*?)\n\1[ \t]*$/gm;
+
+function fenceKind(lang: string): DiagramKind {
+  return lang === 'graphviz' ? 'dot' : (lang as DiagramKind);
+}
+
+export interface DiagramBlocksResult {
+  markdown: string;
+  /** Rejections for blocks left as code, at most one per error code. */
+  errors: unknown[];
+}
+
+/**
+ * Replace fenced diagram blocks of the given kinds with inline data-URI
+ * images. Blocks that fail to render stay as code blocks.
+ */
+export async function renderDiagramBlocks(
+  markdown: string,
+  format: DiagramFormat,
+  kinds: DiagramKind[] = ['mermaid', 'dot'],
+  render: typeof renderDiagram = renderDiagram,
+): Promise<DiagramBlocksResult> {
+  const matches = [...markdown.matchAll(DIAGRAM_FENCE)].filter((m) =>
+    kinds.includes(fenceKind(m[2])),
+  );
+  if (matches.length === 0) return { markdown, errors: [] };
+
+  const errors: unknown[] = [];
+  const seenCodes = new Set<string>();
+  let out = '';
+  let last = 0;
+  for (const m of matches) {
+    const kind = fenceKind(m[2]);
+    const start = m.index ?? 0;
+    let replacement = m[0];
+    try {
+      const img = await render(kind, m[3], format);
+      replacement = `\n![${kind} diagram](data:${img.mimeType};base64,${img.data})\n`;
+    } catch (e) {
+      const key = commandErrorCode(e) ?? String(e);
+      if (!seenCodes.has(key)) {
+        seenCodes.add(key);
+        errors.push(e);
+      }
+    }
+    out += markdown.slice(last, start) + replacement;
+    last = start + m[0].length;
+  }
+  out += markdown.slice(last);
+  return { markdown: out, errors };
+}
@@ -0,0 +1,57 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import type { FileEntry } from '$lib/stores/files-store';
+
+/** Payload of the `dirscan:progress` event, sent about every 200 ms. */
+export interface DirScanProgress {
+  scanId: string;
+  directories: number;
+  entries: number;
+}
+
+export interface DirScanOptions {
+  depth?: number;
+  allFiles?: boolean;
+  includeMeta?: boolean;
+  onProgress?: (progress: DirScanProgress) => void;
+}
+
+export interface DirScan {
+  id: string;
+  /**
+   * The tree, like `read_dir_recursive`. Rejects with `DIR_SCAN_CANCELLED`
+   * after `cancel()`, or `TOO_MANY_ENTRIES` past `dirScanMaxEntries`.
+   */
+  result: Promise<FileEntry[]>;
+  /** Resolves to false when the scan already finished. */
+  cancel: () => Promise<boolean>;
+}
+
+/** Read the tree under `path` in the background, for folders that may be huge. */
+export function scanDir(path: string, options: DirScanOptions = {}): DirScan {
+  const id = crypto.randomUUID();
+  const { depth, allFiles, includeMeta, onProgress } = options;
+  const result = (async () => {
+    const unlisten = onProgress
+      ? await listen<DirScanProgress>('dirscan:progress', (event) => {
+          if (event.payload.scanId === id) onProgress(event.payload);
+        })
+      : null;
+    try {
+      return await invoke<FileEntry[]>('scan_dir_recursive', {
+        scanId: id,
+        path,
+        depth,
+        allFiles,
+        includeMeta,
+      });
+    } finally {
+      unlisten?.();
+    }
+  })();
+  return {
+    id,
+    result,
+    cancel: () => invoke<boolean>('cancel_dir_scan', { scanId: id }),
+  };
+}
@@ -0,0 +1,63 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface Draft {
+  id: string;
+  windowLabel: string;
+  /** Document path, or null for an untitled buffer */
+  path: string | null;
+  untitledId: string | null;
+  /** Milliseconds since the Unix epoch */
+  savedAt: number;
+  content: string;
+}
+
+const DRAFT_DEBOUNCE_MS = 2000;
+
+/** Tab ids restart at 1 every launch; prefix them so untitled drafts from
+ *  different sessions never share a key. */
+const sessionId = Date.now().toString(36);
+
+const timers = new Map<string, ReturnType<typeof setTimeout>>();
+/** Draft ids returned by `save_draft`, by key */
+const savedIds = new Map<string, string>();
+
+export function untitledDraftKey(tabId: string): string {
+  return `${sessionId}-${tabId}`;
+}
+
+/** Save an unsaved buffer as a crash-recovery draft after a short pause.
+ *  `key` is the document path, or `untitledDraftKey(tabId)`. */
+export function scheduleDraftSave(windowLabel: string, key: string, content: string) {
+  const pending = timers.get(key);
+  if (pending) clearTimeout(pending);
+  timers.set(key, setTimeout(() => {
+    timers.delete(key);
+    invoke<string>('save_draft', { windowLabel, pathOrUntitledId: key, content })
+      .then(id => savedIds.set(key, id))
+      .catch(() => {});
+  }, DRAFT_DEBOUNCE_MS));
+}
+
+/** Forget the draft for `key` once the document was saved for real.
+ *  `write_file` already drops drafts for the saved path; this also covers
+ *  untitled buffers saved under a new name. */
+export function dropDraft(key: string) {
+  const pending = timers.get(key);
+  if (pending) {
+    clearTimeout(pending);
+    timers.delete(key);
+  }
+  const id = savedIds.get(key);
+  if (id) {
+    savedIds.delete(key);
+    discardDraft(id).catch(() => {});
+  }
+}
+
+export function listRecoverableDrafts(): Promise<Draft[]> {
+  return invoke<Draft[]>('list_recoverable_drafts');
+}
+
+export function discardDraft(id: string): Promise<void> {
+  return invoke('discard_draft', { id });
+}
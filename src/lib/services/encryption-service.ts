@@ -0,0 +1,32 @@
+import { invoke } from '@tauri-apps/api/core';
+import { commandErrorCode } from '$lib/utils/command-error';
+
+/**
+ * Passphrase-encrypted documents. `read_file` rejects them with the
+ * `ENCRYPTED` code; the UI then asks for the passphrase and reads them
+ * through here. Once unlocked, a document can be read and saved without the
+ * passphrase until it is locked (explicitly or after the auto-lock timeout).
+ */
+
+export function isEncryptedError(e: unknown): boolean {
+  return commandErrorCode(e) === 'ENCRYPTED';
+}
+
+export function isWrongPassphraseError(e: unknown): boolean {
+  return commandErrorCode(e) === 'WRONG_PASSPHRASE';
+}
+
+export function readEncryptedFile(path: string, passphrase?: string): Promise<string> {
+  return invoke<string>('read_file_encrypted', { path, passphrase: passphrase ?? null });
+}
+
+/** Pass `passphrase` to encrypt a document for the first time or to change
+ *  its passphrase; omit it to save an already unlocked document. */
+export function writeEncryptedFile(path: string, content: string, passphrase?: string): Promise<void> {
+  return invoke('write_file_encrypted', { path, content, passphrase: passphrase ?? null });
+}
+
+/** Forget all unlocked keys. Resolves to the number of documents locked. */
+export function lockEncryptedDocuments(): Promise<number> {
+  return invoke<number>('lock_encrypted_documents');
+}
@@ -1,25 +1,32 @@
-import { save as saveDialog } from '@tauri-apps/plugin-dialog';
+import { save as saveDialog, message } from '@tauri-apps/plugin-dialog';
 import { invoke } from '@tauri-apps/api/core';
 import { get } from 'svelte/store';
-import { t } from '$lib/i18n';
+import { t, locale } from '$lib/i18n';
 import html2canvas from 'html2canvas';
 import { jsPDF } from 'jspdf';
 import katex from 'katex';
 import { settingsStore } from '$lib/stores/settings-store';
 import { exportProgressStore } from '$lib/stores/export-progress-store';
+import { editorStore } from '$lib/stores/editor-store';
+import { commandErrorMessage } from '$lib/utils/command-error';
+import { renderDiagramBlocks, type DiagramFormat, type DiagramKind } from './diagram-service';
 import {
-  exportPdfNative,
+  exportPdfTypeset,
+  exportImageNative,
   defaultExportOptions,
   type PdfExportOptions,
+  type PresetUsage,
 } from './pdf-export-native';
 
 export type ExportFormat =
   | 'pdf'
   | 'html'
   | 'html-plain'
-  | 'doc'
+  | 'docx'
   | 'latex'
-  | 'image';
+  | 'image'
+  | 'epub'
+  | 'text';
 
 interface ExportOption {
   format: ExportFormat;
@@ -33,8 +40,15 @@ export const exportOptions: ExportOption[] = [
   { format: 'html', labelKey: 'export.html', extension: 'html', mimeType: 'text/html' },
   { format: 'html-plain', labelKey: 'export.htmlPlain', extension: 'html', mimeType: 'text/html' },
   { format: 'image', labelKey: 'export.image', extension: 'png', mimeType: 'image/png' },
-  { format: 'doc', labelKey: 'export.doc', extension: 'doc', mimeType: 'application/msword' },
+  {
+    format: 'docx',
+    labelKey: 'export.docx',
+    extension: 'docx',
+    mimeType: 'application/vnd.openxmlformats-officedocument.wordprocessingml.document',
+  },
   { format: 'latex', labelKey: 'export.latex', extension: 'tex', mimeType: 'application/x-latex' },
+  { format: 'epub', labelKey: 'export.epub', extension: 'epub', mimeType: 'application/epub+zip' },
+  { format: 'text', labelKey: 'export.text', extension: 'txt', mimeType: 'text/plain' },
 ];
 
 /**
@@ -502,7 +516,44 @@ async function writeBinary(path: string, bytes: Uint8Array): Promise<void> {
 /**
  * Export as PNG image
  */
+/**
+ * PNG export. Renders the full document in a hidden WebView and captures
+ * it natively (long documents are captured in slices and stitched); falls
+ * back to the canvas capture on failure if `autoFallbackOnFailure` is on.
+ */
 async function exportAsImage(markdown: string, path: string): Promise<void> {
+  exportProgressStore.start();
+  const autoFallback = get(settingsStore).exportSettings?.autoFallbackOnFailure ?? true;
+
+  try {
+    await exportImageNative(markdown, path, EXPORT_CONTAINER_WIDTH, (update) => {
+      if (update.phase) exportProgressStore.setPhase(update.phase);
+      if (update.phase === 'paginating' && update.current != null && update.total != null) {
+        exportProgressStore.setPaginating(update.current, update.total);
+      }
+    });
+    exportProgressStore.done();
+  } catch (nativeErr) {
+    if (!autoFallback) {
+      exportProgressStore.error(commandErrorMessage(nativeErr));
+      throw nativeErr;
+    }
+    exportProgressStore.fallback();
+    try {
+      exportProgressStore.setPhase('rendering');
+      await exportAsCanvasImage(markdown, path);
+      exportProgressStore.done();
+    } catch (canvasErr) {
+      exportProgressStore.error(
+        canvasErr instanceof Error ? canvasErr.message : String(canvasErr),
+      );
+      throw canvasErr;
+    }
+  }
+}
+
+/** Canvas-based PNG export, used when the native capture fails. */
+async function exportAsCanvasImage(markdown: string, path: string): Promise<void> {
   const container = await getDocumentContainer(markdown);
   let canvas: HTMLCanvasElement;
   try {
@@ -600,8 +651,8 @@ function buildNativeOptions(documentTitle: string): PdfExportOptions {
 }
 
 /**
- * Top-level PDF export. Tries the native print-to-PDF path first (selectable
- * text, vector fonts, real CSS pagination); falls back to the canvas path on
+ * Top-level PDF export. Typesets the document in Rust (selectable text,
+ * embedded fonts, page headers/footers); falls back to the canvas path on
  * any failure if `autoFallbackOnFailure` is enabled in settings.
  */
 async function exportAsPdf(markdown: string, path: string): Promise<void> {
@@ -612,21 +663,20 @@ async function exportAsPdf(markdown: string, path: string): Promise<void> {
   const autoFallback = settings.exportSettings?.autoFallbackOnFailure ?? true;
 
   try {
-    await exportPdfNative(markdown, path, opts, (update) => {
+    const preset = await exportPdfTypeset(markdown, documentDir(), path, opts, (update) => {
       if (update.phase) exportProgressStore.setPhase(update.phase);
       if (update.phase === 'paginating' && update.current != null && update.total != null) {
         exportProgressStore.setPaginating(update.current, update.total);
       }
     });
     exportProgressStore.done();
+    reportPresetWarnings(preset);
   } catch (nativeErr) {
     if (!autoFallback) {
-      exportProgressStore.error(
-        nativeErr instanceof Error ? nativeErr.message : String(nativeErr),
-      );
+      exportProgressStore.error(commandErrorMessage(nativeErr));
       throw nativeErr;
     }
-    // Native failed — switch to canvas path and mark as fallback.
+    // Typesetting failed — switch to canvas path and mark as fallback.
     exportProgressStore.fallback();
     try {
       await exportAsCanvasPdf(markdown, path);
@@ -640,6 +690,34 @@ async function exportAsPdf(markdown: string, path: string): Promise<void> {
   }
 }
 
+/**
+ * Settings the document's `moraya.export` frontmatter would apply to an
+ * export, so the dialog can show it is using the document preset.
+ */
+export function readExportPreset(
+  markdown: string,
+  format: 'pdf' | 'docx' | 'epub',
+): Promise<PresetUsage> {
+  return invoke<PresetUsage>('read_export_preset', { content: markdown, format });
+}
+
+/** Tell the user which settings in the document's export preset were ignored. */
+function reportPresetWarnings(preset: PresetUsage): void {
+  if (preset.warnings.length === 0) return;
+  void message(preset.warnings.join('\n'), {
+    title: get(t)('export.presetWarnings'),
+    kind: 'warning',
+  });
+}
+
+/** Folder of the open file, for resolving relative image paths. */
+function documentDir(): string | null {
+  const filePath = get(editorStore).currentFilePath;
+  if (!filePath) return null;
+  const idx = Math.max(filePath.lastIndexOf('/'), filePath.lastIndexOf('\\'));
+  return idx > 0 ? filePath.slice(0, idx) : null;
+}
+
 /** Extract document title from the first H1, falling back to first non-blank line. */
 function inferDocumentTitle(markdown: string): string {
   for (const raw of markdown.split('\n')) {
@@ -669,6 +747,12 @@ export async function exportDocument(
 
   const tr = get(t);
   const label = tr(option.labelKey);
+  // EPUB is built by the backend from the file on disk, not the editor buffer
+  const { currentFilePath, isDirty } = get(editorStore);
+  if (format === 'epub' && (!currentFilePath || isDirty)) {
+    await message(tr('export.epubSaveFirst'), { title: label, kind: 'warning' });
+    return false;
+  }
   // Show the save dialog FIRST. It only depends on the format, not the
   // content — so it can appear instantly even while the editor is still
   // busy. Resolving the markdown beforehand would block the JS main thread
@@ -686,7 +770,7 @@ export async function exportDocument(
   // freeze the JS main thread for tens of seconds; without this yield the
   // user sees a totally unresponsive UI between picking a path and the
   // first real progress event.
-  if (format === 'pdf') {
+  if (format === 'pdf' || format === 'image') {
     exportProgressStore.start();
     // Yield one frame so Svelte renders the status pill before we block.
     await new Promise((r) => setTimeout(r, 0));
@@ -696,24 +780,51 @@ export async function exportDocument(
     typeof markdownOrGetter === 'function' ? markdownOrGetter() : markdownOrGetter;
 
   switch (format) {
-    case 'html':
-      await invoke('write_file', { path, content: await markdownToHtml(markdown, true) });
+    // Mermaid renders client-side in HTML exports; Graphviz needs the backend
+    case 'html': {
+      const md = await withRenderedDiagrams(markdown, 'svg', ['dot']);
+      const html = await withInlinedAssets(await markdownToHtml(md, true));
+      await invoke('write_file', { path, content: html });
       break;
-    case 'html-plain':
-      await invoke('write_file', { path, content: await markdownToHtml(markdown, false) });
+    }
+    case 'html-plain': {
+      const md = await withRenderedDiagrams(markdown, 'svg', ['dot']);
+      const html = await withInlinedAssets(await markdownToHtml(md, false));
+      await invoke('write_file', { path, content: html });
       break;
+    }
     case 'latex':
       await invoke('write_file', { path, content: markdownToLatex(markdown) });
       break;
+    // Hard-wrapped at the configured column, e.g. for pasting into email
+    case 'text': {
+      const width = get(settingsStore).exportSettings?.wrapWidth ?? 72;
+      const content = await invoke<string>('rewrap_text', {
+        content: markdown,
+        width,
+        options: { reflow: true },
+      });
+      await invoke('write_file', { path, content });
+      break;
+    }
     case 'pdf':
-      await exportAsPdf(markdown, path);
+      await exportAsPdf(await withRenderedDiagrams(markdown, 'png', rasterDiagramKinds()), path);
       break;
     case 'image':
       await exportAsImage(markdown, path);
       break;
-    case 'doc':
-      await invoke('write_file', { path, content: await markdownToHtml(markdown, true) });
+    case 'epub':
+      await exportAsEpub(currentFilePath!, path);
+      break;
+    case 'docx': {
+      const md = await withRenderedDiagrams(markdown, 'png', rasterDiagramKinds());
+      const preset = await invoke<PresetUsage>('export_docx', {
+        source: { content: { markdown: md, base_dir: documentDir() } },
+        outputPath: path,
+      });
+      reportPresetWarnings(preset);
       break;
+    }
     default:
       await invoke('write_file', { path, content: markdown });
   }
@@ -721,6 +832,83 @@ export async function exportDocument(
   return true;
 }
 
+/** Diagram kinds rendered to images for exports without a DOM (PDF, Word). */
+function rasterDiagramKinds(): DiagramKind[] {
+  const mermaid = get(settingsStore).exportSettings?.enableMermaid ?? true;
+  return mermaid ? ['mermaid', 'dot'] : ['dot'];
+}
+
+/**
+ * Swap fenced diagram blocks for rendered images. Blocks that can't be
+ * rendered (e.g. renderer not installed) stay as code and the user is told
+ * why, without holding up the export.
+ */
+async function withRenderedDiagrams(
+  markdown: string,
+  format: DiagramFormat,
+  kinds: DiagramKind[],
+): Promise<string> {
+  const { markdown: rendered, errors } = await renderDiagramBlocks(markdown, format, kinds);
+  if (errors.length > 0) {
+    void message(errors.map(commandErrorMessage).join('\n'), {
+      title: get(t)('export.diagramsNotRendered'),
+      kind: 'warning',
+    });
+  }
+  return rendered;
+}
+
+interface InlinedHtml {
+  html: string;
+  inlined: number;
+  totalBytes: number;
+  skipped: { src: string; reason: string; size: number | null }[];
+}
+
+/**
+ * Embed local images as data URIs so the exported file stands alone.
+ * Images left out for size are listed, without holding up the export.
+ */
+async function withInlinedAssets(html: string): Promise<string> {
+  if (!(get(settingsStore).exportSettings?.inlineAssets ?? true)) return html;
+  const result = await invoke<InlinedHtml>('inline_html_assets', {
+    html,
+    baseDir: documentDir(),
+  });
+  const tooBig = result.skipped.filter((s) => s.reason === 'tooLarge' || s.reason === 'totalLimit');
+  if (tooBig.length > 0) {
+    const lines = tooBig.map((s) =>
+      s.size != null ? `${s.src} (${(s.size / 1024 / 1024).toFixed(1)} MB)` : s.src,
+    );
+    void message(lines.join('\n'), { title: get(t)('export.assetsNotInlined'), kind: 'warning' });
+  }
+  return result.html;
+}
+
+interface EpubExportResult {
+  chapters: number;
+  images: number;
+  warnings: { file: string; message: string }[];
+  preset: PresetUsage;
+}
+
+/** Build an EPUB from the saved document; per-file problems are shown, not fatal. */
+async function exportAsEpub(documentPath: string, outputPath: string): Promise<void> {
+  const result = await invoke<EpubExportResult>('export_epub', {
+    documentPaths: [documentPath],
+    outputPath,
+    metadata: { language: get(locale) },
+  });
+  if (result.warnings.length > 0) {
+    const lines = result.warnings.map((w) => {
+      const name = w.file.slice(Math.max(w.file.lastIndexOf('/'), w.file.lastIndexOf('\\')) + 1);
+      return name ? `${name}: ${w.message}` : w.message;
+    });
+    void message(lines.join('\n'), { title: get(t)('export.epubWarnings'), kind: 'warning' });
+  }
+  reportPresetWarnings(result.preset);
+}
+
 /**
  * Basic Markdown to LaTeX converter
  */
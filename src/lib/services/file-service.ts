@@ -1,10 +1,13 @@
 import { invoke } from '@tauri-apps/api/core';
-import { open as openDialog, save as saveDialog } from '@tauri-apps/plugin-dialog';
+import { ask, open as openDialog, save as saveDialog } from '@tauri-apps/plugin-dialog';
 import { readFile } from '@tauri-apps/plugin-fs';
 import { editorStore } from '../stores/editor-store';
+import { settingsStore } from '../stores/settings-store';
 import { filesStore, type FileEntry } from '../stores/files-store';
 import { invalidateDocCache } from '../editor/doc-cache';
 import { computeImageDir, computeImageRelativePath } from './ai/image-path-utils';
+import { commandErrorCode } from '$lib/utils/command-error';
+import { t } from '$lib/i18n';
 import { get } from 'svelte/store';
 
 const MIME_MAP: Record<string, string> = {
@@ -42,11 +45,56 @@ export async function openFile(): Promise<string | null> {
   return content;
 }
 
+/**
+ * Write `content` to `path`. A file marked read-only is only made writable
+ * after the user agrees; returns false when they decline.
+ */
+async function writeDocument(path: string, content: string): Promise<boolean> {
+  // Default cleanup: BOMs and zero-width characters outside code
+  const clean = settingsStore.getState().cleanInvisibleOnSave ? {} : undefined;
+  try {
+    await invoke('write_file', { path, content, clean });
+    return true;
+  } catch (e) {
+    if (commandErrorCode(e) !== 'READ_ONLY_FILE') throw e;
+    const tr = get(t);
+    const name = path.split(/[\\/]/).pop() ?? path;
+    const proceed = await ask(tr('editor.readOnlyMsg', { name }), {
+      title: tr('editor.readOnlyTitle'),
+      kind: 'warning',
+      okLabel: tr('editor.makeWritable'),
+    });
+    if (!proceed) return false;
+    await invoke('make_writable', { path });
+    await invoke('write_file', { path, content, clean });
+    return true;
+  }
+}
+
+/**
+ * `content` formatted by `format_markdown` when format on save is on and
+ * `path` is a markdown file; the editor is updated to match.
+ */
+async function formatForSave(path: string, content: string): Promise<string> {
+  if (!settingsStore.getState().formatOnSave || !/\.(md|markdown)$/i.test(path)) return content;
+  try {
+    const result = await invoke<{ content: string; applied: string[] }>('format_markdown', { content });
+    if (result.content === content) return content;
+    editorStore.setContent(result.content);
+    window.dispatchEvent(new CustomEvent('moraya:file-synced', { detail: { content: result.content } }));
+    return result.content;
+  } catch {
+    // Saving matters more than formatting
+    return content;
+  }
+}
+
 export async function saveFile(content: string): Promise<boolean> {
   const state = editorStore.getState();
 
   if (state.currentFilePath) {
-    await invoke('write_file', { path: state.currentFilePath, content });
+    content = await formatForSave(state.currentFilePath, content);
+    if (!(await writeDocument(state.currentFilePath, content))) return false;
     invalidateDocCache(state.currentFilePath);
     editorStore.setDirty(false);
     // Auto-index on save (best-effort, non-blocking)
@@ -72,7 +120,7 @@ export async function saveFileAs(content: string, suggestedPath?: string): Promi
   if (!selected || typeof selected !== 'string') return false;
 
   const path = selected.endsWith('.md') ? selected : `${selected}.md`;
-  await invoke('write_file', { path, content });
+  if (!(await writeDocument(path, content))) return false;
   invalidateDocCache(path);
   editorStore.setCurrentFile(path);
   editorStore.setDirty(false);
@@ -80,6 +128,18 @@ export async function saveFileAs(content: string, suggestedPath?: string): Promi
   return true;
 }
 
+/**
+ * Copy `path` to the clipboard, relative to the open folder when `relative`
+ * is set (absolute when it's on another volume or no folder is open).
+ */
+export async function copyPathToClipboard(path: string, relative = false): Promise<void> {
+  const root = get(filesStore).openFolderPath;
+  const text = relative && root
+    ? await invoke<string>('get_relative_path', { path, root })
+    : path;
+  await invoke('copy_to_clipboard', { text });
+}
+
 export async function openFolder(): Promise<void> {
   const selected = await openDialog({
     directory: true,
@@ -0,0 +1,18 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface FileTags {
+  tags: string[];
+  /** Where the tags are kept: 'finder' (macOS), 'xdg' (Linux) or 'ads' (Windows) */
+  store: 'finder' | 'xdg' | 'ads' | 'none';
+  /** Set when other apps won't see the tags */
+  limitation: string | null;
+}
+
+export function getFileTags(path: string): Promise<FileTags> {
+  return invoke<FileTags>('get_file_tags', { path });
+}
+
+/** Replace the tags of `path`; an empty list removes them. */
+export function setFileTags(path: string, tags: string[]): Promise<FileTags> {
+  return invoke<FileTags>('set_file_tags', { path, tags });
+}
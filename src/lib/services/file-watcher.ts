@@ -1,18 +1,31 @@
-import { watch, type UnwatchFn } from '@tauri-apps/plugin-fs';
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { filesStore, type FileEntry } from '$lib/stores/files-store';
 
-let unwatchFn: UnwatchFn | null = null;
-let debounceTimer: ReturnType<typeof setTimeout> | null = null;
+/** Payload of the backend's `workspace:changed` event. */
+interface WorkspaceChange {
+  root: string;
+  kind: 'created' | 'removed' | 'renamed' | 'modified';
+  /** For `renamed`: `[from, to]`. */
+  paths: string[];
+  /** Which of the created or renamed-to paths are directories. */
+  dirs: string[];
+}
+
+let unlistenFn: UnlistenFn | null = null;
 let currentWatchPath: string | null = null;
+let watchingAllFiles = false;
+
+function showsAllFiles(): boolean {
+  return filesStore.getState().sidebarViewMode === 'tree';
+}
 
 export async function refreshFileTree(folderPath: string) {
   try {
-    const allFiles = filesStore.getState().sidebarViewMode === 'tree';
     const tree = await invoke<FileEntry[]>('read_dir_recursive', {
       path: folderPath,
       depth: 3,
-      allFiles,
+      allFiles: showsAllFiles(),
     });
     filesStore.setFileTree(tree);
   } catch {
@@ -25,35 +38,160 @@ export async function startWatching(folderPath: string): Promise<void> {
   await stopWatching();
 
   currentWatchPath = folderPath;
+  watchingAllFiles = showsAllFiles();
 
   try {
-    unwatchFn = await watch(folderPath, () => {
-      // Debounce: coalesce rapid file system events
-      if (debounceTimer) clearTimeout(debounceTimer);
-      debounceTimer = setTimeout(() => {
-        if (currentWatchPath) {
-          refreshFileTree(currentWatchPath);
-        }
-      }, 500);
-    }, { recursive: true });
+    unlistenFn = await listen<WorkspaceChange>('workspace:changed', (event) => {
+      void applyChange(event.payload);
+    });
+    await invoke('watch_workspace', { root: folderPath, allFiles: watchingAllFiles });
   } catch {
-    // watch() may fail if path doesn't exist or permission denied
-    unwatchFn = null;
+    // The folder may not exist or not be readable
+    unlistenFn?.();
+    unlistenFn = null;
   }
 }
 
 export async function stopWatching(): Promise<void> {
-  if (debounceTimer) {
-    clearTimeout(debounceTimer);
-    debounceTimer = null;
+  if (unlistenFn) {
+    unlistenFn();
+    unlistenFn = null;
   }
-  if (unwatchFn) {
+  if (currentWatchPath) {
     try {
-      await unwatchFn();
+      await invoke('unwatch_workspace');
     } catch {
       // Ignore unwatch errors
     }
-    unwatchFn = null;
   }
   currentWatchPath = null;
 }
+
+async function applyChange(change: WorkspaceChange): Promise<void> {
+  const root = currentWatchPath;
+  if (!root || change.root !== root || change.kind === 'modified') return;
+  // The view mode changed since the watcher started, so its filter is stale
+  if (showsAllFiles() !== watchingAllFiles) {
+    await startWatching(root);
+    await refreshFileTree(root);
+    return;
+  }
+
+  const dirs = new Set(change.dirs);
+  // Moved-in folders arrive as one event; read what they contain
+  const contents = new Map<string, FileEntry[]>();
+  const newDirs = change.kind === 'renamed' ? [] : change.dirs;
+  for (const dir of newDirs) {
+    try {
+      contents.set(
+        dir,
+        await invoke<FileEntry[]>('read_dir_recursive', {
+          path: dir,
+          depth: 2,
+          allFiles: watchingAllFiles,
+        }),
+      );
+    } catch {
+      // Gone again already
+    }
+  }
+  if (currentWatchPath !== root) return;
+
+  let tree = filesStore.getState().fileTree;
+  switch (change.kind) {
+    case 'removed':
+      tree = removeEntries(tree, new Set(change.paths));
+      break;
+    case 'created':
+      for (const path of change.paths) {
+        const isDir = dirs.has(path);
+        tree = insertEntry(tree, root, {
+          name: baseName(path),
+          path,
+          is_dir: isDir,
+          children: isDir ? contents.get(path) ?? [] : undefined,
+        });
+      }
+      break;
+    case 'renamed': {
+      const [from, to] = change.paths;
+      const moved = findEntry(tree, from);
+      tree = removeEntries(tree, new Set([from]));
+      const isDir = dirs.has(to);
+      tree = insertEntry(
+        tree,
+        root,
+        moved
+          ? withPath(moved, from, to)
+          : { name: baseName(to), path: to, is_dir: isDir, children: isDir ? [] : undefined },
+      );
+      break;
+    }
+  }
+  filesStore.setFileTree(tree);
+}
+
+function baseName(path: string): string {
+  return path.slice(Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\')) + 1);
+}
+
+function parentPath(path: string): string {
+  return path.slice(0, Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\')));
+}
+
+/** Same order as `read_dir_recursive`: directories first, names descending. */
+function sortEntries(entries: FileEntry[]): FileEntry[] {
+  return entries.sort((a, b) => {
+    if (a.is_dir !== b.is_dir) return a.is_dir ? -1 : 1;
+    const an = a.name.toLowerCase();
+    const bn = b.name.toLowerCase();
+    return an < bn ? 1 : an > bn ? -1 : 0;
+  });
+}
+
+function findEntry(entries: FileEntry[], path: string): FileEntry | null {
+  for (const entry of entries) {
+    if (entry.path === path) return entry;
+    if (entry.children) {
+      const found = findEntry(entry.children, path);
+      if (found) return found;
+    }
+  }
+  return null;
+}
+
+function removeEntries(entries: FileEntry[], paths: Set<string>): FileEntry[] {
+  return entries
+    .filter((entry) => !paths.has(entry.path))
+    .map((entry) =>
+      entry.children ? { ...entry, children: removeEntries(entry.children, paths) } : entry,
+    );
+}
+
+/**
+ * Add `entry` under its parent folder. Folders beyond the loaded depth are
+ * left alone; they are read when expanded.
+ */
+function insertEntry(entries: FileEntry[], root: string, entry: FileEntry): FileEntry[] {
+  const parent = parentPath(entry.path);
+  const add = (siblings: FileEntry[]) =>
+    sortEntries([...siblings.filter((e) => e.path !== entry.path), entry]);
+  if (parent === root) return add(entries);
+  return entries.map((e) => {
+    if (!e.is_dir || !e.children) return e;
+    if (e.path === parent) return { ...e, children: add(e.children) };
+    if (parent.startsWith(e.path)) return { ...e, children: insertEntry(e.children, root, entry) };
+    return e;
+  });
+}
+
+/** `entry` moved from `from` to `to`, with its descendants' paths updated. */
+function withPath(entry: FileEntry, from: string, to: string): FileEntry {
+  const path = to + entry.path.slice(from.length);
+  return {
+    ...entry,
+    name: entry.path === from ? baseName(to) : entry.name,
+    path,
+    children: entry.children?.map((child) => withPath(child, from, to)),
+  };
+}
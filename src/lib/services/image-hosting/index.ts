@@ -1,4 +1,15 @@
-export type { ImageHostProvider, ImageHostConfig, UploadResult, GitHubCdnMode, ImageHostTarget } from './types';
+export type {
+  ImageHostProvider,
+  ImageHostConfig,
+  UploadResult,
+  UploadOptions,
+  UploadProgress,
+  ImageTransform,
+  StoredObject,
+  StoredObjectPage,
+  GitHubCdnMode,
+  ImageHostTarget,
+} from './types';
 export {
   DEFAULT_IMAGE_HOST_CONFIG,
   generateImageHostTargetId,
@@ -11,7 +22,15 @@ export {
   PICORA_DEFAULT_API_BASE,
 } from './types';
 import { fetch as tauriFetch } from '@tauri-apps/plugin-http';
-import type { ImageHostConfig, UploadResult } from './types';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type {
+  ImageHostConfig,
+  StoredObjectPage,
+  UploadOptions,
+  UploadProgress,
+  UploadResult,
+} from './types';
 import { providers } from './providers';
 
 /**
@@ -21,19 +40,73 @@ import { providers } from './providers';
 export async function uploadImage(
   blob: Blob,
   config: ImageHostConfig,
+  options?: UploadOptions,
 ): Promise<UploadResult> {
   const uploader = providers[config.provider];
   if (!uploader) {
     throw new Error(`Unknown image hosting provider: ${config.provider}`);
   }
   try {
-    return await uploader(blob, config);
+    return await uploader(blob, config, options);
   } catch (firstErr) {
+    // A user abort is final — don't retry it.
+    if (String(firstErr).includes('Upload aborted')) throw firstErr;
     await new Promise<void>(resolve => setTimeout(resolve, 600));
-    return uploader(blob, config);
+    return uploader(blob, config, options);
   }
 }
 
+/**
+ * Subscribe to progress of an object-storage upload started with `uploadId`.
+ */
+export function onUploadProgress(
+  uploadId: string,
+  cb: (progress: UploadProgress) => void,
+): Promise<UnlistenFn> {
+  return listen<UploadProgress>('storage:upload_progress', (event) => {
+    if (event.payload.uploadId === uploadId) cb(event.payload);
+  });
+}
+
+/** Cancel an in-flight object-storage upload. */
+export async function abortUpload(uploadId: string): Promise<void> {
+  await invoke('storage_upload_abort', { uploadId });
+}
+
+function objectStorageCredentials(config: ImageHostConfig) {
+  const stored = !!config.ossConfigId &&
+    (config.ossAccessKey === '***' || config.ossSecretKey === '***');
+  return {
+    configId: stored ? config.ossConfigId : null,
+    provider: config.provider,
+    accessKey: config.ossAccessKey,
+    secretKey: config.ossSecretKey,
+    bucket: config.ossBucket,
+    region: config.ossRegion,
+    endpoint: config.ossEndpoint || null,
+    pathStyle: config.ossPathStyle ?? null,
+    ossSignV4: config.ossSignV4 ?? null,
+  };
+}
+
+/** List one page of objects in an object-storage bucket. */
+export function listObjects(
+  config: ImageHostConfig,
+  options: { prefix?: string; maxKeys?: number; continuationToken?: string | null } = {},
+): Promise<StoredObjectPage> {
+  return invoke<StoredObjectPage>('list_objects', {
+    ...objectStorageCredentials(config),
+    prefix: options.prefix ?? null,
+    maxKeys: options.maxKeys ?? null,
+    continuationToken: options.continuationToken ?? null,
+  });
+}
+
+/** Delete an object previously uploaded to an object-storage bucket. */
+export async function deleteObject(config: ImageHostConfig, objectKey: string): Promise<void> {
+  await invoke('delete_object', { ...objectStorageCredentials(config), objectKey });
+}
+
 /**
  * Fetch a blob URL and return the underlying Blob.
  */
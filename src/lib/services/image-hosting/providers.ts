@@ -1,6 +1,6 @@
 import { fetch as tauriFetch } from '@tauri-apps/plugin-http';
 import { invoke } from '@tauri-apps/api/core';
-import type { ImageHostConfig, UploadResult } from './types';
+import type { ImageHostConfig, UploadOptions, UploadResult } from './types';
 
 /** Map a MIME to a bare file extension (no dot). */
 function extFromMime(mime: string): string | null {
@@ -45,58 +45,58 @@ function parseGitRepoUrl(repoUrl: string): { host: string; owner: string; repo:
   return { host: match[1], owner: match[2], repo: match[3] };
 }
 
-async function uploadToGitHub(blob: Blob, config: ImageHostConfig): Promise<UploadResult> {
+/** Response of the `upload_to_object_storage*` commands. */
+interface StorageUploadResponse {
+  url: string;
+  objectKey: string;
+  originalSize: number;
+  finalSize: number;
+  cdnUrl?: string;
+  /** Qiniu only: content hash of the stored object. */
+  hash?: string;
+}
+
+/**
+ * Commit to a GitHub repository through the Rust backend, which resolves
+ * the token, handles path conflicts and builds the raw / jsDelivr URLs.
+ */
+async function uploadToGitHub(
+  blob: Blob,
+  config: ImageHostConfig,
+  options?: UploadOptions,
+): Promise<UploadResult> {
   if (!config.githubRepoUrl || !config.githubToken) {
     throw new Error('GitHub image hosting is not configured');
   }
 
   const { owner, repo } = parseGitRepoUrl(config.githubRepoUrl);
-  const branch = config.githubBranch || 'main';
   const dir = (config.githubDir || 'images/').replace(/\/$/, '');
-
-  // Generate timestamped filename
   const fileName = timestampedName((blob as File).name || 'image.png');
-  const filePath = `${dir}/${fileName}`;
+  const filePath = dir ? `${dir}/${fileName}` : fileName;
 
-  // Read blob as base64
   const arrayBuffer = await blob.arrayBuffer();
-  const bytes = new Uint8Array(arrayBuffer);
-  let binary = '';
-  for (let i = 0; i < bytes.length; i++) {
-    binary += String.fromCharCode(bytes[i]);
-  }
-  const base64Content = btoa(binary);
-
-  // Upload via GitHub Contents API
-  const url = `https://api.github.com/repos/${owner}/${repo}/contents/${filePath}`;
-  const res = await tauriFetch(url, {
-    method: 'PUT',
-    headers: {
-      Authorization: `Bearer ${config.githubToken}`,
-      Accept: 'application/vnd.github.v3+json',
-      'Content-Type': 'application/json',
+  const stored = !!config.ossConfigId && config.githubToken === '***';
+  const credentials = stored
+    ? { configId: config.ossConfigId }
+    : { accessKey: '', secretKey: config.githubToken };
+  const result = await invoke<StorageUploadResponse>(
+    stored ? 'upload_to_object_storage_by_config' : 'upload_to_object_storage',
+    {
+      ...credentials,
+      provider: 'github',
+      bucket: `${owner}/${repo}`,
+      // Empty means the repository's default branch.
+      region: config.githubBranch || '',
+      objectKey: filePath,
+      data: Array.from(new Uint8Array(arrayBuffer)),
+      contentType: blob.type || 'image/png',
+      uploadId: options?.uploadId ?? null,
+      transform: options?.transform ?? null,
     },
-    body: JSON.stringify({
-      message: `upload: ${fileName}`,
-      content: base64Content,
-      branch,
-    }),
-  });
-
-  if (!res.ok) {
-    const errBody = await res.text();
-    throw new Error(`GitHub upload failed (${res.status}): ${errBody}`);
-  }
-
-  // Build access URL based on CDN mode
-  let imageUrl: string;
-  if (config.githubCdn === 'jsdelivr') {
-    imageUrl = `https://cdn.jsdelivr.net/gh/${owner}/${repo}@${branch}/${filePath}`;
-  } else {
-    imageUrl = `https://raw.githubusercontent.com/${owner}/${repo}/${branch}/${filePath}`;
-  }
+  );
 
-  return { url: imageUrl };
+  const url = config.githubCdn === 'jsdelivr' ? result.cdnUrl ?? result.url : result.url;
+  return { url, originalSize: result.originalSize, finalSize: result.finalSize };
 }
 
 async function uploadToGitLab(blob: Blob, config: ImageHostConfig): Promise<UploadResult> {
@@ -378,15 +378,23 @@ function pickUrlFromResponse(json: Record<string, unknown>): string | undefined
  * Upload to object storage providers (Qiniu, Aliyun OSS, Tencent COS, AWS S3, Google GCS).
  * HMAC signing is handled by the Rust backend to keep secrets out of frontend.
  */
-async function uploadToObjectStorage(blob: Blob, config: ImageHostConfig): Promise<UploadResult> {
-  if (!config.ossBucket || !config.ossRegion) {
+async function uploadToObjectStorage(
+  blob: Blob,
+  config: ImageHostConfig,
+  options?: UploadOptions,
+): Promise<UploadResult> {
+  const isWebdav = config.provider === 'webdav';
+  const isQiniu = config.provider === 'qiniu';
+  if (isWebdav && !config.ossEndpoint) {
+    throw new Error('WebDAV is not configured (missing endpoint)');
+  }
+  if (!isWebdav && (!config.ossBucket || !config.ossRegion)) {
     throw new Error('Object storage is not configured (missing bucket or region)');
   }
 
-  // Qiniu upload API returns only the object key, not a full URL.
-  // A CDN domain is required to construct a valid download URL.
-  // Without it, the upload "succeeds" but sets src to just a filename, breaking the image.
-  if (config.provider === 'qiniu' && !config.ossCdnDomain) {
+  // Qiniu has no default public URL; the backend builds (and for private
+  // buckets signs) it on the bound CDN domain.
+  if (isQiniu && !config.ossCdnDomain) {
     throw new Error('七牛云上传失败：请先在图床设置中配置 CDN 加速域名（CDN Domain），否则无法生成可访问的图片链接。');
   }
 
@@ -396,30 +404,45 @@ async function uploadToObjectStorage(blob: Blob, config: ImageHostConfig): Promi
   const prefix = (config.ossPathPrefix || '').replace(/\/$/, '');
   const objectKey = prefix ? `${prefix}/${fileName}` : fileName;
 
-  const resultUrl = await invoke<string>('upload_to_object_storage', {
+  // Saved targets keep their keys in the keychain (masked as '***'); Rust
+  // resolves them by id so they never transit IPC.
+  const stored = !!config.ossConfigId &&
+    (config.ossAccessKey === '***' || config.ossSecretKey === '***');
+  const credentials = stored
+    ? { configId: config.ossConfigId }
+    : { accessKey: config.ossAccessKey, secretKey: config.ossSecretKey };
+  const result = await invoke<StorageUploadResponse>(stored ? 'upload_to_object_storage_by_config' : 'upload_to_object_storage', {
+    ...credentials,
     provider: config.provider,
-    accessKey: config.ossAccessKey,
-    secretKey: config.ossSecretKey,
     bucket: config.ossBucket,
     region: config.ossRegion,
     endpoint: config.ossEndpoint || null,
     objectKey,
     data: bytes,
     contentType: blob.type || 'image/png',
+    uploadId: options?.uploadId ?? null,
+    // WebDAV builds the share URL itself so non-ASCII path segments are encoded.
+    publicBase: isWebdav ? config.ossCdnDomain || null : null,
+    publicDomain: isQiniu ? config.ossCdnDomain : null,
+    private: isQiniu ? config.ossPrivate ?? false : null,
+    pathStyle: config.ossPathStyle ?? null,
+    ossSignV4: config.ossSignV4 ?? null,
+    transform: options?.transform ?? null,
   });
+  const sizes = { originalSize: result.originalSize, finalSize: result.finalSize };
 
-  // Apply CDN domain if configured
-  if (config.ossCdnDomain) {
+  // Apply CDN domain if configured. A transform may have changed the key's extension.
+  if (config.ossCdnDomain && !isWebdav && !isQiniu) {
     const cdnBase = config.ossCdnDomain.replace(/\/$/, '');
-    return { url: `${cdnBase}/${objectKey}` };
+    return { url: `${cdnBase}/${result.objectKey}`, ...sizes };
   }
 
-  return { url: resultUrl };
+  return { url: result.url, ...sizes };
 }
 
 export const providers: Record<
   string,
-  (blob: Blob, config: ImageHostConfig) => Promise<UploadResult>
+  (blob: Blob, config: ImageHostConfig, options?: UploadOptions) => Promise<UploadResult>
 > = {
   picora: uploadToPicora,
   smms: uploadToSmms,
@@ -433,4 +456,5 @@ export const providers: Record<
   'tencent-cos': uploadToObjectStorage,
   'aws-s3': uploadToObjectStorage,
   'google-gcs': uploadToObjectStorage,
+  webdav: uploadToObjectStorage,
 };
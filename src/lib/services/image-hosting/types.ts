@@ -1,7 +1,7 @@
 export type ImageHostProvider =
   | 'picora'
   | 'smms' | 'imgur' | 'github' | 'gitlab' | 'git-custom' | 'custom'
-  | 'qiniu' | 'aliyun-oss' | 'tencent-cos' | 'aws-s3' | 'google-gcs';
+  | 'qiniu' | 'aliyun-oss' | 'tencent-cos' | 'aws-s3' | 'google-gcs' | 'webdav';
 
 /** Default Picora SaaS endpoints. Editable per target. */
 export const PICORA_DEFAULT_API_URL = 'https://api.picora.me/v1/images';
@@ -41,6 +41,10 @@ export interface ImageHostConfig {
   ossEndpoint: string;       // Custom endpoint (S3-compatible or private)
   ossCdnDomain: string;      // CDN domain (replaces default URL prefix)
   ossPathPrefix: string;     // Path prefix inside bucket (e.g. "images/blog/")
+  ossPathStyle?: boolean;    // S3: endpoint/bucket/key addressing (default on with a custom endpoint)
+  ossSignV4?: boolean;       // OSS: V4 signatures (default only in regions that require them)
+  ossPrivate?: boolean;      // Qiniu: private bucket, URLs carry a download token
+  ossConfigId?: string;      // Target id; keys live in the keychain when masked as '***'
   // Picora SaaS image host
   picoraApiUrl: string;      // Upload endpoint (default https://api.picora.me/v1/images)
   picoraApiKey: string;      // Bearer token (sk_live_...)
@@ -51,6 +55,44 @@ export interface ImageHostConfig {
 export interface UploadResult {
   url: string;
   deleteUrl?: string;
+  /** Object-storage only: bytes before and after `UploadOptions.transform`. */
+  originalSize?: number;
+  finalSize?: number;
+}
+
+/** Downscale / re-encode applied in Rust before an object-storage upload. */
+export interface ImageTransform {
+  max_width?: number;
+  max_height?: number;
+  format?: 'png' | 'jpeg' | 'webp';
+  /** JPEG quality, 1–100. */
+  quality?: number;
+}
+
+export interface UploadOptions {
+  /** Object-storage only: enables progress events and `abortUpload`. */
+  uploadId?: string;
+  /** Object-storage only; animations and unsupported formats are uploaded as-is. */
+  transform?: ImageTransform;
+}
+
+/** Payload of the `storage:upload_progress` event. */
+export interface UploadProgress {
+  uploadId: string;
+  sent: number;
+  total: number;
+}
+
+export interface StoredObject {
+  key: string;
+  size: number;
+  lastModified: string;
+}
+
+/** One page from `listObjects`; pass `continuationToken` back for the next page. */
+export interface StoredObjectPage {
+  objects: StoredObject[];
+  continuationToken: string | null;
 }
 
 export const DEFAULT_IMAGE_HOST_CONFIG: ImageHostConfig = {
@@ -115,6 +157,9 @@ export interface ImageHostTarget {
   ossEndpoint: string;
   ossCdnDomain: string;
   ossPathPrefix: string;
+  ossPathStyle?: boolean;
+  ossSignV4?: boolean;
+  ossPrivate?: boolean;
   picoraApiUrl: string;
   picoraApiKey: string;
   picoraImgDomain: string;
@@ -167,14 +212,15 @@ export function createDefaultImageHostTarget(provider: ImageHostProvider): Image
 }
 
 export function targetToConfig(target: ImageHostTarget): ImageHostConfig {
-  const { id: _id, name: _name, featured: _f, picoraImportedAt: _t, ...config } = target;
-  return config;
+  const { id, name: _name, featured: _f, picoraImportedAt: _t, ...config } = target;
+  return { ...config, ossConfigId: id };
 }
 
 /** Whether this provider uses HMAC-signed object storage (Rust command) */
 export function isObjectStorageProvider(provider: ImageHostProvider): boolean {
   return provider === 'qiniu' || provider === 'aliyun-oss' ||
-         provider === 'tencent-cos' || provider === 'aws-s3' || provider === 'google-gcs';
+         provider === 'tencent-cos' || provider === 'aws-s3' || provider === 'google-gcs' ||
+         provider === 'webdav';
 }
 
 /** Whether this provider uses Git repository hosting */
@@ -0,0 +1,17 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** Page metadata from the `fetch_link_metadata` Rust command. */
+export interface LinkMetadata {
+  /** Final URL after redirects */
+  url: string;
+  /** `og:title`, else `<title>` */
+  title: string | null;
+  description: string | null;
+  image: string | null;
+  siteName: string | null;
+  favicon: string | null;
+}
+
+export function fetchLinkMetadata(url: string): Promise<LinkMetadata> {
+  return invoke<LinkMetadata>('fetch_link_metadata', { url });
+}
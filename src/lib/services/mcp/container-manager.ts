@@ -12,19 +12,13 @@ import { exists, remove, mkdir } from '@tauri-apps/plugin-fs';
 import { ask } from '@tauri-apps/plugin-dialog';
 import { get } from 'svelte/store';
 import { t } from '$lib/i18n';
+import { commandErrorMessage } from '$lib/utils/command-error';
 import { settingsStore } from '$lib/stores/settings-store';
 import { mcpStore, connectServer, disconnectServer } from './mcp-manager';
 import { containerStore, type DynamicService } from './container-store';
 import { MCP_RUNTIME_JS } from './mcp-runtime';
 import type { MCPServerConfig } from './types';
 
-/** Extract a human-readable message from unknown caught values. */
-function errMsg(e: unknown): string {
-  if (e instanceof Error) return e.message;
-  if (typeof e === 'string') return e;
-  return String(e);
-}
-
 const DYNAMIC_STORE_FILE = 'dynamic-mcp-services.json';
 
 let cachedAppDataDir: string | null = null;
@@ -90,14 +84,14 @@ export async function initContainerManager(): Promise<void> {
             console.error(`[Container] Failed to reconnect "${svc.name}":`, e);
             containerStore.updateService(svc.id, {
               status: 'error',
-              error: `Reconnect failed: ${errMsg(e)}`,
+              error: `Reconnect failed: ${commandErrorMessage(e)}`,
             });
           }
         }
       }
     }
   } catch (e: unknown) {
-    console.warn('[Container] Failed to load saved services:', errMsg(e));
+    console.warn('[Container] Failed to load saved services:', commandErrorMessage(e));
   }
 }
 
@@ -229,9 +223,9 @@ export async function createService(params: CreateServiceParams): Promise<Dynami
   } catch (e: any) {
     containerStore.updateService(serviceId, {
       status: 'error',
-      error: errMsg(e),
+      error: commandErrorMessage(e),
     });
-    throw new Error(`Service "${name}" failed to start: ${errMsg(e)}`);
+    throw new Error(`Service "${name}" failed to start: ${commandErrorMessage(e)}`);
   }
 }
 
@@ -319,8 +313,7 @@ export async function startService(serviceId: string): Promise<void> {
   try {
     await reconnectSavedService(service);
   } catch (e: unknown) {
-    const msg = e instanceof Error ? e.message : String(e);
-    containerStore.updateService(serviceId, { status: 'error', error: msg });
+    containerStore.updateService(serviceId, { status: 'error', error: commandErrorMessage(e) });
     throw e;
   }
 }
@@ -356,7 +349,7 @@ async function persistSavedServices(): Promise<void> {
     await store.set('savedServices', saved);
     await store.save();
   } catch (e: unknown) {
-    console.error('[Container] Failed to persist saved services:', errMsg(e));
+    console.error('[Container] Failed to persist saved services:', commandErrorMessage(e));
   }
 }
 
@@ -259,8 +259,19 @@ class MCPClient {
    * List available resources from the server
    */
   async listResources(): Promise<MCPResource[]> {
-    const result = await this.sendRequest('resources/list') as { resources: Array<{ uri: string; name: string; description?: string; mimeType?: string }> };
-    return (result?.resources || []).map(resource => ({
+    type Listed = Array<{ uri: string; name: string; description?: string; mimeType?: string }>;
+    let resources: Listed;
+    if (this.serverConfig.transport.type === 'stdio') {
+      // Cached by the Rust backend until the server reports a change
+      resources = await invoke<Listed>('mcp_get_cached', {
+        serverId: this.serverConfig.id,
+        kind: 'resources',
+      });
+    } else {
+      const result = await this.sendRequest('resources/list') as { resources: Listed };
+      resources = result?.resources || [];
+    }
+    return resources.map(resource => ({
       ...resource,
       serverId: this.serverConfig.id,
     }));
@@ -281,8 +292,16 @@ class MCPClient {
    * Read a resource from the MCP server
    */
   async readResource(uri: string): Promise<string> {
+    type Contents = Array<{ text?: string; blob?: string }>;
+    if (this.serverConfig.transport.type === 'stdio') {
+      const contents = await invoke<Contents>('mcp_read_resource', {
+        serverId: this.serverConfig.id,
+        uri,
+      });
+      return contents?.[0]?.text || '';
+    }
     const result = await this.sendRequest('resources/read', { uri }) as {
-      contents: Array<{ text?: string; blob?: string }>;
+      contents: Contents;
     };
     return result?.contents?.[0]?.text || '';
   }
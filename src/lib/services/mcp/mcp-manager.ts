@@ -6,6 +6,7 @@
 import { writable, get } from 'svelte/store';
 import { load } from '@tauri-apps/plugin-store';
 import MCPClient from './mcp-client';
+import { commandErrorMessage } from '$lib/utils/command-error';
 import { MCP_PRESETS } from './presets';
 import type {
   MCPServerConfig,
@@ -219,7 +220,7 @@ export async function connectServer(config: MCPServerConfig): Promise<void> {
     mcpStore.setError(null);
   } catch (error: any) {
     console.error(`[MCP] Failed to connect to ${config.name}:`, error);
-    mcpStore.setError(`Failed to connect to ${config.name}: ${error?.message || error}`);
+    mcpStore.setError(`Failed to connect to ${config.name}: ${commandErrorMessage(error)}`);
     throw error;
   } finally {
     mcpStore.setLoading(false);
@@ -291,7 +292,7 @@ export async function publishDocument(request: PublishRequest): Promise<PublishR
   } catch (error: any) {
     return {
       success: false,
-      message: error.message,
+      message: commandErrorMessage(error),
     };
   }
 }
@@ -337,7 +338,7 @@ export async function syncToKnowledgeBase(syncConfigId: string, files: Array<{ p
       configId: syncConfigId,
       status: 'error',
       lastSync: null,
-      error: error.message,
+      error: commandErrorMessage(error),
       filesChanged: 0,
     });
     throw error;
@@ -50,6 +50,14 @@ export type RustProgressEvent =
 
 export type ProgressHandler = (state: Partial<ExportProgressState>) => void;
 
+/** Settings an export took from the document's `moraya.export` frontmatter. */
+export interface PresetUsage {
+  /** Preset keys applied, as written in the frontmatter */
+  fromDocument: string[];
+  /** Preset values that were ignored, and why */
+  warnings: string[];
+}
+
 export function defaultExportOptions(
   documentTitle: string = '',
 ): PdfExportOptions {
@@ -107,7 +115,7 @@ export async function exportPdfNative(
   outputPath: string,
   options: PdfExportOptions,
   onProgress: ProgressHandler,
-): Promise<void> {
+): Promise<PresetUsage> {
   const jobId = generateJobId();
 
   const channel = new Channel<RustProgressEvent>();
@@ -116,7 +124,7 @@ export async function exportPdfNative(
     if (update) onProgress(update);
   };
 
-  await invoke('export_pdf_native', {
+  return invoke<PresetUsage>('export_pdf_native', {
     job: {
       job_id: jobId,
       markdown,
@@ -127,6 +135,67 @@ export async function exportPdfNative(
   });
 }
 
+/**
+ * Run the typeset export: markdown is laid out and written to PDF in Rust
+ * (`export_pdf`), without going through a WebView print pipeline.
+ *
+ * @param markdown   Markdown content to render.
+ * @param baseDir    Folder relative image paths resolve against, if any.
+ * @param outputPath Absolute filesystem path (validated server-side).
+ * @param options    Page size / margins / header and footer templates.
+ * @param onProgress Receives partial state updates as the export progresses.
+ * @throws on any failure.
+ */
+export async function exportPdfTypeset(
+  markdown: string,
+  baseDir: string | null,
+  outputPath: string,
+  options: PdfExportOptions,
+  onProgress: ProgressHandler,
+): Promise<PresetUsage> {
+  const channel = new Channel<RustProgressEvent>();
+  channel.onmessage = (ev) => {
+    const update = eventToState(ev);
+    if (update) onProgress(update);
+  };
+
+  return invoke<PresetUsage>('export_pdf', {
+    source: { content: { markdown, base_dir: baseDir } },
+    outputPath,
+    options,
+    onProgress: channel,
+  });
+}
+
+/**
+ * Render the whole document in a hidden WebView and save it as one PNG
+ * (`capture_document_png`). Slices captured so far are reported as
+ * `paginating` progress.
+ *
+ * @param width Page width in CSS px.
+ * @throws on any failure; caller should fall back to the canvas capture.
+ */
+export async function exportImageNative(
+  markdown: string,
+  outputPath: string,
+  width: number,
+  onProgress: ProgressHandler,
+): Promise<void> {
+  const channel = new Channel<RustProgressEvent>();
+  channel.onmessage = (ev) => {
+    const update = eventToState(ev);
+    if (update) onProgress(update);
+  };
+
+  await invoke('capture_document_png', {
+    label: generateJobId(),
+    outputPath,
+    width: Math.round(width),
+    markdown,
+    onProgress: channel,
+  });
+}
+
 function generateJobId(): string {
   if (typeof crypto !== 'undefined' && typeof crypto.randomUUID === 'function') {
     return crypto.randomUUID();
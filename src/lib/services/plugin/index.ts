@@ -1,5 +1,10 @@
 export * from './types';
-export { pluginStore } from './plugin-manager';
+export {
+  pluginStore,
+  localizedText,
+  manifestDisplayName,
+  manifestDisplayDescription,
+} from './plugin-manager';
 export * from './renderer-registry';
 export { rendererManager } from './renderer-manager';
 export { loadRendererPlugin, clearRendererModuleCache } from './renderer-loader';
@@ -1,6 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { open } from '@tauri-apps/plugin-dialog';
+import { open, save } from '@tauri-apps/plugin-dialog';
 import { writable, get } from 'svelte/store';
 import { load } from '@tauri-apps/plugin-store';
 import type {
@@ -9,20 +9,40 @@ import type {
   PluginMarketData,
   ValidationResult,
   InstallResult,
+  RunningPlugin,
+  PluginDataTransfer,
+  PluginHandshake,
+  PluginManifest,
+  RegistryMirrors,
 } from './types';
+import { locale as i18nLocale } from '$lib/i18n';
+import { filesStore } from '$lib/stores/files-store';
+import { commandErrorMessage, type CommandError } from '$lib/utils/command-error';
 
 // ---------------------------------------------------------------------------
 // Store state
 // ---------------------------------------------------------------------------
 
+/** Payload of `plugin:download_progress`; rate fields are absent on the initial placeholder. */
+interface DownloadProgress {
+  downloaded: number;
+  total: number;
+  bytes_per_sec?: number;
+  eta_secs?: number | null;
+}
+
 interface PluginStoreState {
   installed: InstalledPlugin[];
   market: PluginMarketData[];
   marketFetchedAt: number;
   marketFromCache: boolean;
+  /** GitHub rate-limit reset (epoch ms) when the last fetch was throttled */
+  marketRateLimitedUntil: number | null;
+  /** 'direct' or the mirror the last registry fetch went through */
+  marketSource: string | null;
   loading: boolean;
   marketLoading: boolean;
-  installProgress: Record<string, { downloaded: number; total: number }>;
+  installProgress: Record<string, DownloadProgress>;
   blacklist: string[];
 }
 
@@ -31,6 +51,8 @@ const initialState: PluginStoreState = {
   market: [],
   marketFetchedAt: 0,
   marketFromCache: false,
+  marketRateLimitedUntil: null,
+  marketSource: null,
   loading: false,
   marketLoading: false,
   installProgress: {},
@@ -40,15 +62,24 @@ const initialState: PluginStoreState = {
 const { subscribe, set, update } = writable<PluginStoreState>(initialState);
 
 // ---------------------------------------------------------------------------
-// Persistence (plugin-state.json via Tauri Store)
+// Persistence (appData/plugins/state.json, owned by the Rust side)
 // ---------------------------------------------------------------------------
 
-const STORE_KEY = 'installed-plugins';
+const LEGACY_STORE_KEY = 'installed-plugins';
 
 async function loadPersistedState(): Promise<PluginStateEntry[]> {
   try {
+    const entries = await invoke<PluginStateEntry[]>('plugin_scan_installed');
+    if (entries.length > 0) return entries;
+    // One-time migration from the old Tauri Store file
     const store = await load('plugin-state.json');
-    return (await store.get<PluginStateEntry[]>(STORE_KEY)) ?? [];
+    const legacy = (await store.get<PluginStateEntry[]>(LEGACY_STORE_KEY)) ?? [];
+    if (legacy.length > 0) {
+      await invoke('plugin_state_save', { entries: legacy });
+      await store.delete(LEGACY_STORE_KEY);
+      return await invoke<PluginStateEntry[]>('plugin_scan_installed');
+    }
+    return [];
   } catch {
     return [];
   }
@@ -56,13 +87,28 @@ async function loadPersistedState(): Promise<PluginStateEntry[]> {
 
 async function persistState(entries: PluginStateEntry[]): Promise<void> {
   try {
-    const store = await load('plugin-state.json');
-    await store.set(STORE_KEY, entries);
+    // Dev plugins live only for the current session
+    await invoke('plugin_state_save', { entries: entries.filter(e => !isDevPlugin(e.id)) });
   } catch {
-    // Non-critical — state will be reloaded next launch
+    // Non-critical — state is rescanned from disk next launch
   }
 }
 
+const DEV_PLUGIN_PREFIX = 'dev:';
+
+function isDevPlugin(pluginId: string): boolean {
+  return pluginId.startsWith(DEV_PLUGIN_PREFIX);
+}
+
+async function listRunningIds(): Promise<string[]> {
+  return invoke<string[]>('plugin_list_running_ids');
+}
+
+/** Running plugins with pid, uptime and memory usage. */
+async function listRunning(): Promise<RunningPlugin[]> {
+  return invoke<RunningPlugin[]>('plugin_list_running');
+}
+
 function entryToPlugin(entry: PluginStateEntry, runningIds: string[]): InstalledPlugin {
   return {
     manifest: entry.manifest,
@@ -70,6 +116,7 @@ function entryToPlugin(entry: PluginStateEntry, runningIds: string[]): Installed
     pluginDir: entry.pluginDir,
     installedAt: entry.installedAt,
     processState: runningIds.includes(entry.id) ? 'running' : 'stopped',
+    dev: isDevPlugin(entry.id),
   };
 }
 
@@ -78,13 +125,36 @@ function entryToPlugin(entry: PluginStateEntry, runningIds: string[]): Installed
 // ---------------------------------------------------------------------------
 
 let _downloadProgressUnlisten: (() => void) | null = null;
+let _exitedUnlisten: (() => void) | null = null;
+let _forceDisabledUnlisten: (() => void) | null = null;
+let _devReloadedUnlisten: (() => void) | null = null;
+let _incompatibleUnlisten: (() => void) | null = null;
+let _localeUnsubscribe: (() => void) | null = null;
+let _workspaceUnsubscribe: (() => void) | null = null;
 
 async function init(): Promise<void> {
   update(s => ({ ...s, loading: true }));
 
+  // Plugins receive the UI locale in their initialize handshake
+  if (!_localeUnsubscribe) {
+    _localeUnsubscribe = i18nLocale.subscribe(l => {
+      invoke('plugin_set_locale', { locale: l }).catch(() => {});
+    });
+  }
+
+  // The open folder is the only workspace root `host/fs.*` calls may reach
+  if (!_workspaceUnsubscribe) {
+    let lastRoot: string | null | undefined;
+    _workspaceUnsubscribe = filesStore.subscribe(s => {
+      if (s.openFolderPath === lastRoot) return;
+      lastRoot = s.openFolderPath;
+      invoke('plugin_set_workspace_roots', { roots: lastRoot ? [lastRoot] : [] }).catch(() => {});
+    });
+  }
+
   const [entries, runningIds, blacklist] = await Promise.all([
     loadPersistedState(),
-    invoke<string[]>('plugin_list_running'),
+    listRunningIds(),
     invoke<string[]>('plugin_fetch_blacklist').catch(() => [] as string[]),
   ]);
 
@@ -96,9 +166,15 @@ async function init(): Promise<void> {
   const plugins = cleaned.map(e => entryToPlugin(e, runningIds));
   update(s => ({ ...s, installed: plugins, blacklist, loading: false }));
 
-  // Restart enabled plugins
+  // Enabled plugins are auto-started by the backend; stop blacklisted ones
+  // and start any that the backend could not bring up yet.
+  for (const entry of entries) {
+    if (blacklist.includes(entry.id) && (entry.enabled || runningIds.includes(entry.id))) {
+      await invoke('plugin_disable', { pluginId: entry.id }).catch(() => {});
+    }
+  }
   for (const entry of cleaned) {
-    if (entry.enabled && !blacklist.includes(entry.id)) {
+    if (entry.enabled && !runningIds.includes(entry.id)) {
       try {
         await invoke('plugin_enable', { entry });
       } catch {
@@ -107,9 +183,84 @@ async function init(): Promise<void> {
     }
   }
 
+  // Reflect crashes reported by the backend monitor
+  if (!_exitedUnlisten) {
+    _exitedUnlisten = await listen<{ pluginId: string; code: number | null; restarting: boolean }>(
+      'plugin:exited',
+      ({ payload }) => {
+        update(s => ({
+          ...s,
+          installed: s.installed.map(p =>
+            p.manifest.id === payload.pluginId
+              ? { ...p, processState: payload.restarting ? 'starting' as const : 'error' as const }
+              : p
+          ),
+        }));
+      }
+    );
+  }
+
+  // Blacklist refreshes can stop a running plugin from the backend
+  if (!_forceDisabledUnlisten) {
+    _forceDisabledUnlisten = await listen<{ pluginId: string; reason: string }>(
+      'plugin:force_disabled',
+      ({ payload }) => {
+        update(s => ({
+          ...s,
+          blacklist: s.blacklist.includes(payload.pluginId)
+            ? s.blacklist
+            : [...s.blacklist, payload.pluginId],
+          installed: s.installed.map(p =>
+            p.manifest.id === payload.pluginId
+              ? { ...p, enabled: false, processState: 'stopped' as const }
+              : p
+          ),
+        }));
+      }
+    );
+  }
+
+  // Plugins that failed the initialize handshake
+  if (!_incompatibleUnlisten) {
+    _incompatibleUnlisten = await listen<{ pluginId: string; error: string; rawResponse: string | null }>(
+      'plugin:incompatible',
+      ({ payload }) => {
+        update(s => ({
+          ...s,
+          installed: s.installed.map(p =>
+            p.manifest.id === payload.pluginId
+              ? { ...p, processState: 'incompatible' as const, incompatibleError: payload.error }
+              : p
+          ),
+        }));
+      }
+    );
+  }
+
+  // Dev plugins restarted after their entry file changed
+  if (!_devReloadedUnlisten) {
+    _devReloadedUnlisten = await listen<{ pluginId: string; ok: boolean; error: CommandError | null }>(
+      'plugin:dev_reloaded',
+      ({ payload }) => {
+        update(s => ({
+          ...s,
+          installed: s.installed.map(p =>
+            p.manifest.id !== payload.pluginId
+              ? p
+              : payload.ok
+                ? { ...p, processState: 'running' as const, incompatibleError: undefined }
+                : p.processState === 'incompatible'
+                  ? p
+                  : { ...p, processState: 'error' as const }
+          ),
+        }));
+      }
+    );
+  }
+
   // Subscribe to download progress events
   if (!_downloadProgressUnlisten) {
-    _downloadProgressUnlisten = await listen<{ downloaded: number; total: number }>(
+    _downloadProgressUnlisten = await listen<DownloadProgress>(
       'plugin:download_progress',
       ({ payload }) => {
         // Progress is keyed by the currently installing plugin id (tracked separately)
@@ -151,14 +302,15 @@ async function installFromFile(): Promise<{ ok: boolean; error?: string }> {
   if (result.ok && result.plugin) {
     await _addInstalledPlugin(result.plugin);
   }
-  return { ok: result.ok, error: result.error ?? undefined };
+  return { ok: result.ok, error: result.error ? commandErrorMessage(result.error) : undefined };
 }
 
 /** Install from a download URL with SHA256 verification (marketplace one-click). */
 async function installFromUrl(
   pluginId: string,
   downloadUrl: string,
-  expectedSha256: string
+  expectedSha256: string,
+  signatureUrl?: string
 ): Promise<{ ok: boolean; error?: string }> {
   update(s => ({
     ...s,
@@ -170,6 +322,7 @@ async function installFromUrl(
     result = await invoke<InstallResult>('plugin_install_from_url', {
       downloadUrl,
       expectedSha256,
+      signatureUrl: signatureUrl ?? null,
     });
   } finally {
     update(s => {
@@ -182,11 +335,11 @@ async function installFromUrl(
   if (result.ok && result.plugin) {
     await _addInstalledPlugin(result.plugin);
   }
-  return { ok: result.ok, error: result.error ?? undefined };
+  return { ok: result.ok, error: result.error ? commandErrorMessage(result.error) : undefined };
 }
 
 async function _addInstalledPlugin(entry: PluginStateEntry): Promise<void> {
-  const runningIds = await invoke<string[]>('plugin_list_running');
+  const runningIds = await listRunningIds();
   const plugin = entryToPlugin(entry, runningIds);
 
   update(s => {
@@ -207,6 +360,35 @@ async function _addInstalledPlugin(entry: PluginStateEntry): Promise<void> {
   );
 }
 
+// ---------------------------------------------------------------------------
+// Dev mode (unpacked plugin folder, session only)
+// ---------------------------------------------------------------------------
+
+/** Load and start a plugin from a local folder (user picks via dialog). */
+async function loadDevPlugin(): Promise<{ ok: boolean; error?: string }> {
+  const selected = await open({ directory: true, multiple: false });
+  if (!selected) return { ok: false };
+
+  try {
+    const entry = await invoke<PluginStateEntry>('plugin_dev_load', {
+      dirPath: selected as string,
+    });
+    const plugin = entryToPlugin(entry, await listRunningIds());
+    update(s => ({
+      ...s,
+      installed: [...s.installed.filter(p => p.manifest.id !== entry.id), plugin],
+    }));
+    return { ok: true };
+  } catch (e) {
+    return { ok: false, error: commandErrorMessage(e) };
+  }
+}
+
+/** Toggle hot-restart on entry file changes for a dev plugin. */
+async function setDevWatch(pluginId: string, enabled: boolean): Promise<void> {
+  await invoke('plugin_dev_watch', { pluginId, enabled });
+}
+
 // ---------------------------------------------------------------------------
 // Enable / Disable / Uninstall
 // ---------------------------------------------------------------------------
@@ -215,7 +397,9 @@ async function enablePlugin(entry: PluginStateEntry): Promise<void> {
   update(s => ({
     ...s,
     installed: s.installed.map(p =>
-      p.manifest.id === entry.id ? { ...p, processState: 'starting' as const } : p
+      p.manifest.id === entry.id
+        ? { ...p, processState: 'starting' as const, incompatibleError: undefined }
+        : p
     ),
   }));
 
@@ -230,10 +414,13 @@ async function enablePlugin(entry: PluginStateEntry): Promise<void> {
       ),
     }));
   } catch {
+    // 'incompatible' was already set by the plugin:incompatible event
     update(s => ({
       ...s,
       installed: s.installed.map(p =>
-        p.manifest.id === entry.id ? { ...p, processState: 'error' as const } : p
+        p.manifest.id === entry.id && p.processState !== 'incompatible'
+          ? { ...p, processState: 'error' as const }
+          : p
       ),
     }));
     return;
@@ -288,6 +475,8 @@ async function fetchMarket(forceRefresh = false): Promise<void> {
       plugins: PluginMarketData[];
       fromCache: boolean;
       fetchedAt: number;
+      rateLimitedUntil?: number | null;
+      source?: string;
     }>('plugin_registry_fetch', { forceRefresh });
 
     update(s => ({
@@ -295,6 +484,8 @@ async function fetchMarket(forceRefresh = false): Promise<void> {
       market: result.plugins,
       marketFetchedAt: result.fetchedAt,
       marketFromCache: result.fromCache,
+      marketRateLimitedUntil: result.rateLimitedUntil ?? null,
+      marketSource: result.source ?? null,
       marketLoading: false,
     }));
   } catch {
@@ -302,6 +493,15 @@ async function fetchMarket(forceRefresh = false): Promise<void> {
   }
 }
 
+async function getRegistryMirrors(): Promise<RegistryMirrors> {
+  return invoke<RegistryMirrors>('plugin_registry_mirrors');
+}
+
+/** Prefer a mirror for GitHub downloads (null = try the direct connection first). */
+async function setRegistryMirror(index: number | null): Promise<void> {
+  await invoke('plugin_set_registry_mirror', { index });
+}
+
 // ---------------------------------------------------------------------------
 // Validate (URL import)
 // ---------------------------------------------------------------------------
@@ -331,6 +531,85 @@ async function invokePlugin(
   return response.result;
 }
 
+/** Subscribe to id-less JSON-RPC messages pushed by a plugin. */
+async function onPluginNotification(
+  pluginId: string,
+  handler: (message: { method?: string; params?: unknown }) => void
+): Promise<() => void> {
+  return listen<{ pluginId: string; message: { method?: string; params?: unknown } }>(
+    'plugin:notification',
+    ({ payload }) => {
+      if (payload.pluginId === pluginId) handler(payload.message);
+    }
+  );
+}
+
+// ---------------------------------------------------------------------------
+// Data backup (appData/plugins/{id}/data)
+// ---------------------------------------------------------------------------
+
+/** Export a plugin's data directory to a zip chosen by the user. */
+async function exportData(pluginId: string): Promise<PluginDataTransfer | null> {
+  const outputZip = await save({
+    defaultPath: `${pluginId.replace(/^dev:/, '')}-data.zip`,
+    filters: [{ name: 'Zip', extensions: ['zip'] }],
+  });
+  if (!outputZip) return null;
+  return invoke<PluginDataTransfer>('plugin_export_data', { pluginId, outputZip });
+}
+
+/** Replace a plugin's data directory from a backup zip chosen by the user. */
+async function importData(pluginId: string): Promise<PluginDataTransfer | null> {
+  const selected = await open({
+    filters: [{ name: 'Zip', extensions: ['zip'] }],
+    multiple: false,
+  });
+  if (!selected) return null;
+  return invoke<PluginDataTransfer>('plugin_import_data', {
+    pluginId,
+    zipPath: selected as string,
+  });
+}
+
+/** Capabilities declared in the plugin's initialize reply. */
+async function getCapabilities(pluginId: string): Promise<PluginHandshake> {
+  return invoke<PluginHandshake>('plugin_get_capabilities', { pluginId });
+}
+
+/** Recent stderr output of a plugin (newest last). */
+async function readLogs(pluginId: string, tailLines = 200): Promise<string[]> {
+  return invoke<string[]>('plugin_read_logs', { pluginId, tailLines });
+}
+
+// ---------------------------------------------------------------------------
+// Localized display text (mirrors localized_text in plugin_manager.rs)
+// ---------------------------------------------------------------------------
+
+/** Exact locale, then its language (`zh-CN` → `zh`), then any tag of that language, then `base`. */
+export function localizedText(
+  map: Record<string, string> | undefined,
+  base: string,
+  locale: string
+): string {
+  if (!map) return base;
+  const lang = locale.split(/[-_]/)[0].toLowerCase();
+  const tags = Object.keys(map);
+  const tag =
+    tags.find(t => t.toLowerCase() === locale.toLowerCase()) ??
+    tags.find(t => t.toLowerCase() === lang) ??
+    tags.find(t => t.split(/[-_]/)[0].toLowerCase() === lang);
+  const text = tag ? map[tag] : undefined;
+  return text && text.trim() ? text : base;
+}
+
+export function manifestDisplayName(manifest: PluginManifest, locale: string): string {
+  return localizedText(manifest.nameLocalized, manifest.name, locale);
+}
+
+export function manifestDisplayDescription(manifest: PluginManifest, locale: string): string {
+  return localizedText(manifest.descriptionLocalized, manifest.description, locale);
+}
+
 // ---------------------------------------------------------------------------
 // Export
 // ---------------------------------------------------------------------------
@@ -343,7 +622,17 @@ export const pluginStore = {
   enablePlugin,
   disablePlugin,
   uninstallPlugin,
+  loadDevPlugin,
+  setDevWatch,
   fetchMarket,
+  getRegistryMirrors,
+  setRegistryMirror,
   validateManifest,
   invokePlugin,
+  onPluginNotification,
+  readLogs,
+  listRunning,
+  getCapabilities,
+  exportData,
+  importData,
 };
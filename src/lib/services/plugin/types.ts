@@ -2,8 +2,14 @@
  * Plugin system type definitions for Moraya v0.16.0
  */
 
-/** Sandbox level declared in plugin.json */
-export type PluginSandboxLevel = 'sandbox' | 'local' | 'system';
+import type { CommandError } from '$lib/utils/command-error';
+
+/**
+ * Sandbox level declared in plugin.json. `strict` plugins get no direct
+ * filesystem access and read or write workspace files through the host's
+ * `host/fs.read` / `host/fs.write` methods.
+ */
+export type PluginSandboxLevel = 'strict' | 'sandbox' | 'local' | 'system';
 
 /** Platform keys used in the entry field of plugin.json */
 export type PluginPlatform = 'darwin-aarch64' | 'darwin-x86_64' | 'win32' | 'linux-x86_64';
@@ -27,7 +33,8 @@ export interface PluginManifest {
   author: string;
   license: string;
   apiVersion: string;
-  entry: Partial<Record<PluginPlatform, string>>;
+  /** Platform-keyed binaries, or an interpreter entry like `{ runtime: 'node', script: 'index.js' }` */
+  entry: Partial<Record<PluginPlatform, string>> | { runtime: string; script: string };
   protocol: 'jsonrpc-stdio';
   permissions: PluginPermission[];
   permissionReasons?: Partial<Record<PluginPermission, string>>;
@@ -37,10 +44,15 @@ export interface PluginManifest {
     'ai:chat:callsPerMinute'?: number;
     'ai:chat:tokensPerDay'?: number;
   };
+  /** Restart (with backoff, up to 3 times) if the process exits unexpectedly */
+  autoRestart?: boolean;
+  /** Translations keyed by locale (e.g. `zh-CN`); unknown locales fall back to `name` */
+  nameLocalized?: Record<string, string>;
+  descriptionLocalized?: Record<string, string>;
 }
 
 /** State of a plugin process */
-export type PluginProcessState = 'stopped' | 'starting' | 'running' | 'error';
+export type PluginProcessState = 'stopped' | 'starting' | 'running' | 'error' | 'incompatible';
 
 /** An installed plugin with runtime state */
 export interface InstalledPlugin {
@@ -52,6 +64,40 @@ export interface InstalledPlugin {
   installedAt: number;
   /** Runtime process state (not persisted) */
   processState: PluginProcessState;
+  /** Loaded from a local folder in dev mode (id prefixed with `dev:`) */
+  dev?: boolean;
+  /** Why the `initialize` handshake failed (processState 'incompatible') */
+  incompatibleError?: string;
+}
+
+/** Result of the `initialize` handshake (`plugin_get_capabilities`) */
+export interface PluginHandshake {
+  compatible: boolean;
+  capabilities: Record<string, unknown> | null;
+  methods: string[];
+  error: string | null;
+  /** Plugin's raw reply, only when the handshake failed */
+  rawResponse: string | null;
+}
+
+/** Counts returned by `plugin_export_data` / `plugin_import_data` */
+export interface PluginDataTransfer {
+  files: number;
+  /** Uncompressed bytes */
+  bytes: number;
+  skippedSymlinks: number;
+}
+
+/** Entry returned by `plugin_list_running` (`plugin_list_running_ids` returns ids only) */
+export interface RunningPlugin {
+  id: string;
+  dev: boolean;
+  pid: number;
+  uptimeMs: number;
+  /** Resident memory; null when the OS query failed */
+  rssBytes: number | null;
+  /** Epoch ms of the last plugin_invoke call */
+  lastInvokeMs: number | null;
 }
 
 /** Entry in plugin-state.json (persisted to Tauri Store) */
@@ -111,13 +157,23 @@ export interface PluginMarketData {
 
   /** From GitHub /repos/{owner}/{repo}/releases/latest */
   downloadUrls: Partial<Record<PluginPlatform, string>>;
+  /** Minisign `.sig` assets matching downloadUrls */
+  signatureUrls?: Partial<Record<PluginPlatform, string>>;
   changelog: string;
 
   /** From raw plugin.json in the repo */
   manifest: PluginManifest | null;
+  nameLocalized?: Record<string, string>;
+  descriptionLocalized?: Record<string, string>;
+  /** name/description resolved by the backend for the UI locale */
+  displayName?: string;
+  displayDescription?: string;
 
   /** Icon URL from registry (raw.githubusercontent.com) */
   iconUrl: string;
+
+  /** Set when some GitHub metadata could not be fetched */
+  enrichError?: string;
 }
 
 /** Cached registry data written to plugin-registry-cache.json */
@@ -133,7 +189,9 @@ export interface RegistryCache {
 export interface InstallResult {
   ok: boolean;
   plugin?: PluginStateEntry;
-  error?: string;
+  error?: CommandError | null;
+  /** 'direct' or the mirror the package was downloaded through */
+  source?: string | null;
 }
 
 export interface ValidationResult {
@@ -141,12 +199,24 @@ export interface ValidationResult {
   manifest?: PluginManifest;
   errors: string[];
   warnings: string[];
+  /** Manifest name/description resolved for the UI locale */
+  displayName?: string | null;
+  displayDescription?: string | null;
 }
 
 export interface RegistryFetchResult {
   plugins: PluginMarketData[];
   fromCache: boolean;
   fetchedAt: number;
+  /** 'direct' or the mirror the registry was fetched through */
+  source?: string;
+}
+
+/** Allowlisted GitHub mirrors (`plugin_registry_mirrors`) */
+export interface RegistryMirrors {
+  mirrors: string[];
+  /** Preferred mirror index; null = direct connection first */
+  selected: number | null;
 }
 
 // ---------------------------------------------------------------------------
@@ -0,0 +1,32 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * Publish a saved document into a local git checkout (GitHub Pages, Hugo,
+ * Jekyll…) via the `publish_to_git_repo` Rust command. Unlike
+ * `publishToGitHub` this uses the user's own git credentials and history.
+ */
+
+export interface GitRepoPublishResult {
+  /** HEAD after publishing (unchanged when nothing differed) */
+  commit: string;
+  committed: boolean;
+  pushed: boolean;
+  /** Written files, relative to the repository */
+  files: string[];
+}
+
+export function publishToGitRepo(
+  documentPath: string,
+  repoDir: string,
+  destRelativePath: string,
+  commitMessage: string,
+  push = false,
+): Promise<GitRepoPublishResult> {
+  return invoke<GitRepoPublishResult>('publish_to_git_repo', {
+    documentPath,
+    repoDir,
+    destRelativePath,
+    commitMessage,
+    push,
+  });
+}
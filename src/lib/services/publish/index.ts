@@ -13,4 +13,5 @@ export {
 } from './types';
 export { publishToGitHub, testGitHubConnection } from './github-publisher';
 export { publishToCustomAPI, testCustomAPIConnection } from './api-publisher';
+export { publishToGitRepo, type GitRepoPublishResult } from './git-repo-publisher';
 export { updateGitHubRSSFeed, updateCustomAPIRSSFeed } from './rss-publisher';
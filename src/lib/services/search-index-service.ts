@@ -0,0 +1,40 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface SearchHit {
+  path: string;
+  score: number;
+  /** 1-based line of the snippet */
+  line: number;
+  snippet: string;
+  /** `[start, end)` of each match in `snippet` */
+  highlights: [number, number][];
+}
+
+export interface SearchIndexStatus {
+  /** Folder of the loaded index; null before the first build */
+  root: string | null;
+  building: boolean;
+  /** Progress of the running build */
+  scanned: number;
+  total: number;
+  files: number;
+  bytes: number;
+  cacheBytes: number | null;
+  /** Milliseconds since the epoch */
+  updatedAt: number | null;
+}
+
+/** Index the markdown files under `root`; later edits keep it current. */
+export function buildSearchIndex(root: string): Promise<SearchIndexStatus> {
+  return invoke<SearchIndexStatus>('search_index_build', { root });
+}
+
+/** Files containing every word of `query`, best first. Fails until the index is built. */
+export function querySearchIndex(query: string, limit?: number): Promise<SearchHit[]> {
+  return invoke<SearchHit[]>('search_index_query', { query, limit });
+}
+
+/** Poll while `building` to show indexing progress. */
+export function getSearchIndexStatus(): Promise<SearchIndexStatus> {
+  return invoke<SearchIndexStatus>('search_index_status');
+}
@@ -6,25 +6,10 @@
 import { writable, get } from 'svelte/store';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { fetch as tauriFetch } from '@tauri-apps/plugin-http';
+import { settingsStore } from '$lib/stores/settings-store';
 
 // --- Types ---
 
-interface ReleaseAsset {
-  name: string;
-  browser_download_url: string;
-  size: number;
-}
-
-interface GitHubRelease {
-  tag_name: string;
-  name: string;
-  body: string;
-  html_url: string;
-  published_at: string;
-  assets: ReleaseAsset[];
-}
-
 export interface UpdateInfo {
   available: boolean;
   currentVersion: string;
@@ -35,9 +20,40 @@ export interface UpdateInfo {
   downloadUrl: string | null;
   assetName: string | null;
   assetSize: number;
+  assetSha256: string | null;
+  prerelease: boolean;
 }
 
-export type UpdateCheckStatus = 'idle' | 'checking' | 'available' | 'latest' | 'error';
+/** Result of the `check_for_updates` command. */
+type UpdateCheckResult =
+  | {
+      status: 'checked';
+      available: boolean;
+      currentVersion: string;
+      version: string;
+      notes: string;
+      releaseUrl: string;
+      publishedAt: string | null;
+      prerelease: boolean;
+      assetName: string | null;
+      assetUrl: string | null;
+      assetSize: number;
+      assetSha256: string | null;
+    }
+  | {
+      status: 'failed';
+      reason: 'rateLimited' | 'network' | 'http' | 'invalidResponse';
+      message: string;
+    }
+  | {
+      status: 'managed';
+      currentVersion: string;
+      installKind: 'flatpak' | 'snap';
+      message: string;
+    };
+
+/** `managed`: Flatpak / Snap install, updated by the package manager. */
+export type UpdateCheckStatus = 'idle' | 'checking' | 'available' | 'latest' | 'managed' | 'error';
 export type DownloadStatus = 'idle' | 'downloading' | 'completed' | 'error';
 
 interface UpdateState {
@@ -45,19 +61,15 @@ interface UpdateState {
   downloadStatus: DownloadStatus;
   updateInfo: UpdateInfo | null;
   downloadProgress: number;
+  /** Recent transfer speed; 0 until measured. */
+  downloadBytesPerSec: number;
+  /** Seconds left at the recent speed; null while unknown. */
+  downloadEtaSecs: number | null;
+  /** The installer from an earlier attempt was verified and reused. */
+  downloadReused: boolean;
   error: string | null;
 }
 
-interface PlatformInfo {
-  os: string;
-  arch: string;
-}
-
-// --- Constants ---
-
-const GITHUB_REPO = 'zouwei/moraya';
-const RELEASES_API = `https://api.github.com/repos/${GITHUB_REPO}/releases/latest`;
-
 // --- Store ---
 
 const DEFAULT_STATE: UpdateState = {
@@ -65,6 +77,9 @@ const DEFAULT_STATE: UpdateState = {
   downloadStatus: 'idle',
   updateInfo: null,
   downloadProgress: 0,
+  downloadBytesPerSec: 0,
+  downloadEtaSecs: null,
+  downloadReused: false,
   error: null,
 };
 
@@ -80,64 +95,6 @@ export const updateStore = {
   },
 };
 
-// --- Semver Comparison ---
-
-function compareSemver(a: string, b: string): number {
-  const pa = a.replace(/^v/, '').split('.').map(Number);
-  const pb = b.replace(/^v/, '').split('.').map(Number);
-  for (let i = 0; i < 3; i++) {
-    const na = pa[i] || 0;
-    const nb = pb[i] || 0;
-    if (na > nb) return 1;
-    if (na < nb) return -1;
-  }
-  return 0;
-}
-
-// --- Platform Asset Matching ---
-
-function findMatchingAsset(
-  assets: ReleaseAsset[],
-  platform: PlatformInfo,
-): ReleaseAsset | null {
-  const { os, arch } = platform;
-
-  // Map Rust arch names to Tauri asset naming conventions
-  const archMap: Record<string, string[]> = {
-    aarch64: ['aarch64', 'arm64'],
-    x86_64: ['x64', 'x86_64', 'amd64'],
-  };
-
-  const archNames = archMap[arch] || [arch];
-
-  // Define extension preferences per OS
-  const extPrefs: Record<string, string[]> = {
-    macos: ['.dmg'],
-    windows: ['-setup.exe', '.msi'],
-    linux: ['.AppImage', '.deb'],
-  };
-
-  const exts = extPrefs[os] || [];
-
-  // Try exact match: extension + arch
-  for (const ext of exts) {
-    for (const archName of archNames) {
-      const match = assets.find(
-        a => a.name.includes(archName) && a.name.endsWith(ext),
-      );
-      if (match) return match;
-    }
-  }
-
-  // Fallback: any asset with the right extension
-  for (const ext of exts) {
-    const match = assets.find(a => a.name.endsWith(ext));
-    if (match) return match;
-  }
-
-  return null;
-}
-
 // --- Date Utilities ---
 
 export function getTodayDateString(): string {
@@ -167,71 +124,47 @@ function extractErrorMessage(err: unknown): string {
   return String(err);
 }
 
-/** Fetch GitHub releases API with timeout and User-Agent header */
-async function fetchReleasesAPI(): Promise<Response> {
-  const controller = new AbortController();
-  const timeoutId = setTimeout(() => controller.abort(), 20_000);
-  try {
-    return await tauriFetch(RELEASES_API, {
-      headers: {
-        Accept: 'application/vnd.github.v3+json',
-        'User-Agent': 'Moraya-Updater/1.0',
-      },
-      signal: controller.signal,
-    });
-  } finally {
-    clearTimeout(timeoutId);
-  }
-}
-
-/** Retry fetchReleasesAPI with exponential backoff (up to 3 attempts). */
-async function fetchWithRetry(): Promise<Response> {
-  const delays = [0, 1500, 3000]; // instant, 1.5s, 3s
-  let lastErr: unknown;
-  for (let attempt = 0; attempt < delays.length; attempt++) {
-    if (delays[attempt] > 0) {
-      await new Promise(resolve => setTimeout(resolve, delays[attempt]));
-    }
-    try {
-      return await fetchReleasesAPI();
-    } catch (err) {
-      lastErr = err;
-    }
-  }
-  throw lastErr;
-}
-
 export async function checkForUpdate(): Promise<UpdateInfo> {
   update(s => ({ ...s, checkStatus: 'checking', downloadStatus: 'idle', downloadProgress: 0, error: null }));
 
   try {
-    const res = await fetchWithRetry();
-
-    if (res.status === 403 || res.status === 429) {
-      throw new Error('GitHub API rate limit exceeded. Please try again later.');
+    // Rust queries GitHub so system proxy settings apply and the channel
+    // filter, asset matching and semver comparison live in one place.
+    const result = await invoke<UpdateCheckResult>('check_for_updates', {
+      channel: settingsStore.getState().updateChannel ?? 'stable',
+    });
+    if (result.status === 'failed') {
+      throw new Error(result.message);
     }
-
-    if (!res.ok) {
-      throw new Error(`GitHub API error: ${res.status}`);
+    if (result.status === 'managed') {
+      update(s => ({ ...s, checkStatus: 'managed', updateInfo: null }));
+      return {
+        available: false,
+        currentVersion: result.currentVersion,
+        latestVersion: result.currentVersion,
+        releaseNotes: '',
+        releaseUrl: '',
+        publishedAt: '',
+        downloadUrl: null,
+        assetName: null,
+        assetSize: 0,
+        assetSha256: null,
+        prerelease: false,
+      };
     }
 
-    const release: GitHubRelease = await res.json();
-    const latestVersion = release.tag_name.replace(/^v/, '');
-    const currentVersion = __APP_VERSION__;
-
-    const platform: PlatformInfo = await invoke('get_platform_info');
-    const asset = findMatchingAsset(release.assets, platform);
-
     const info: UpdateInfo = {
-      available: compareSemver(latestVersion, currentVersion) > 0,
-      currentVersion,
-      latestVersion,
-      releaseNotes: release.body || '',
-      releaseUrl: release.html_url,
-      publishedAt: release.published_at,
-      downloadUrl: asset?.browser_download_url || null,
-      assetName: asset?.name || null,
-      assetSize: asset?.size || 0,
+      available: result.available,
+      currentVersion: result.currentVersion,
+      latestVersion: result.version,
+      releaseNotes: result.notes,
+      releaseUrl: result.releaseUrl,
+      publishedAt: result.publishedAt || '',
+      downloadUrl: result.assetUrl,
+      assetName: result.assetName,
+      assetSize: result.assetSize,
+      assetSha256: result.assetSha256,
+      prerelease: result.prerelease,
     };
 
     update(s => ({
@@ -254,7 +187,15 @@ function formatBytes(bytes: number): string {
   return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
 }
 
-export { formatBytes };
+/** "45 s" / "3 min 20 s" for a download ETA. */
+function formatDuration(secs: number): string {
+  if (secs < 60) return `${secs} s`;
+  const m = Math.floor(secs / 60);
+  const s = secs % 60;
+  return s > 0 ? `${m} min ${s} s` : `${m} min`;
+}
+
+export { formatBytes, formatDuration };
 
 export async function downloadAndInstall(): Promise<void> {
   const state = get({ subscribe });
@@ -263,26 +204,61 @@ export async function downloadAndInstall(): Promise<void> {
     throw new Error('No download URL available');
   }
 
-  update(s => ({ ...s, downloadStatus: 'downloading', downloadProgress: 0 }));
+  update(s => ({
+    ...s,
+    downloadStatus: 'downloading',
+    downloadProgress: 0,
+    downloadBytesPerSec: 0,
+    downloadEtaSecs: null,
+    downloadReused: false,
+  }));
 
   // Listen for progress events from Rust
-  const unlisten = await listen<{ received: number; total: number; progress: number }>(
+  const unlisten = await listen<{
+    received: number;
+    total: number;
+    progress: number;
+    bytes_per_sec: number;
+    eta_secs: number | null;
+  }>(
     'download-progress',
     (event) => {
-      update(s => ({ ...s, downloadProgress: event.payload.progress }));
+      update(s => ({
+        ...s,
+        downloadProgress: event.payload.progress,
+        downloadBytesPerSec: event.payload.bytes_per_sec ?? 0,
+        downloadEtaSecs: event.payload.eta_secs ?? null,
+      }));
     },
   );
 
   try {
     // Download entirely in Rust — reqwest streams directly to disk,
     // no IPC binary transfer needed.
-    const fullPath: string = await invoke('download_update', {
+    // Only an asset with a published digest may be installed unattended;
+    // older releases have none, so their installer is just opened.
+    const verified = !!info.assetSha256;
+    const result = await invoke<{ path: string; sha256: string; reused: boolean }>('download_update', {
       url: info.downloadUrl,
       filename: info.assetName,
+      expectedSha256: info.assetSha256,
+      expectedSize: info.assetSize || null,
+      launch: !verified,
     });
+    console.info(`[update] ${result.reused ? 'Reused' : 'Downloaded'} ${result.path} (sha256 ${result.sha256})`);
+    if (result.reused) {
+      update(s => ({ ...s, downloadReused: true }));
+    }
+
+    // Installs unattended where the platform allows it (falls back to
+    // opening the installer) and exits; the new version starts afterwards.
+    if (verified) {
+      await invoke('install_update', { path: result.path, silent: true, relaunch: true });
+    }
 
     update(s => ({ ...s, downloadStatus: 'completed', downloadProgress: 100 }));
   } catch (err) {
+    if (get({ subscribe }).downloadStatus === 'idle') return; // cancelled
     // Tauri invoke errors are strings, not Error objects
     const message = err instanceof Error ? err.message : String(err) || 'Download failed';
     update(s => ({ ...s, downloadStatus: 'error', error: message }));
@@ -291,3 +267,9 @@ export async function downloadAndInstall(): Promise<void> {
     unlisten();
   }
 }
+
+/** Cancel an in-flight `downloadAndInstall`; the partial file is removed. */
+export async function cancelDownload(): Promise<void> {
+  update(s => ({ ...s, downloadStatus: 'idle', downloadProgress: 0 }));
+  await invoke('cancel_update_download');
+}
@@ -228,24 +228,11 @@ export async function sendAudioChunk(
   sessionId: string,
   chunk: ArrayBuffer,
 ): Promise<void> {
-  // Base64-encode the binary payload before sending over Tauri IPC.
-  // Sending a JSON string token is orders of magnitude faster than a JSON number
-  // array in WKWebView's IPC parser.
-  //
-  // IMPORTANT: never use `binary += String.fromCharCode(bytes[i])` in a plain loop
-  // — JavaScriptCore (WebKit) copies the whole string on every concat, making it
-  // O(n²) for large buffers (~0.5 s block per 32 KB chunk → UI freeze).
-  // Process in 8 KB pages so each concat is O(page_size) instead:
-  const bytes = new Uint8Array(chunk);
-  const PAGE = 8192;
-  let binary = '';
-  for (let i = 0; i < bytes.length; i += PAGE) {
-    binary += String.fromCharCode.apply(
-      null,
-      bytes.subarray(i, i + PAGE) as unknown as number[],
-    );
-  }
-  await invoke('speech_proxy_send_audio', { sessionId, audioB64: btoa(binary) });
+  // Raw IPC body: the PCM bytes reach Rust as-is, with no base64 encode here
+  // and no decode on the Rust side (~33 % less data per 100 ms chunk).
+  await invoke('speech_proxy_send_audio_raw', new Uint8Array(chunk), {
+    headers: { 'X-Session-Id': sessionId },
+  });
 }
 
 /**
@@ -266,9 +253,9 @@ export async function stopTranscription(
   // Stop mic
   stopAudioCapture(state);
 
-  // Tell Rust proxy to close the WebSocket
+  // Ask the provider to finalize the last interim sentence, then close the WebSocket
   try {
-    await invoke('speech_proxy_stop', { sessionId });
+    await invoke('speech_proxy_finalize', { sessionId });
   } catch { /* ignore if already closed */ }
 
   // Flush any segments still waiting in merge windows
@@ -1,13 +1,16 @@
 import { writable, get } from 'svelte/store';
 import { load } from '@tauri-apps/plugin-store';
-import { invoke } from '@tauri-apps/api/core';
 import type { KbBinding } from '$lib/services/kb-sync/types';
+import { scanDir } from '$lib/services/dir-scan-service';
+import { commandErrorMessage, isCommandError } from '$lib/utils/command-error';
 
 export interface FileEntry {
   name: string;
   path: string;
   is_dir: boolean;
   children?: FileEntry[];
+  /** Finder or xdg tags; only present when listed with `includeMeta` */
+  tags?: string[];
 }
 
 export interface FilePreview {
@@ -200,11 +203,7 @@ function createFilesStore() {
 
       try {
         const allFiles = state.sidebarViewMode === 'tree';
-        const tree = await invoke<FileEntry[]>('read_dir_recursive', {
-          path: kb.path,
-          depth: 3,
-          allFiles,
-        });
+        const tree = await scanDir(kb.path, { depth: 3, allFiles }).result;
 
         const kbs = state.knowledgeBases.map(k =>
           k.id === id ? { ...k, lastAccessedAt: Date.now() } : k
@@ -221,7 +220,7 @@ function createFilesStore() {
         }));
         return { success: true };
       } catch (e: unknown) {
-        const error = typeof e === 'string' ? e : (e instanceof Error ? e.message : 'Folder inaccessible');
+        const error = typeof e === 'string' ? e : (isCommandError(e) || e instanceof Error ? commandErrorMessage(e) : 'Folder inaccessible');
         return { success: false, error };
       }
     },
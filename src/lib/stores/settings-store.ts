@@ -5,7 +5,13 @@ import { emit, listen } from '@tauri-apps/api/event';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { setLocale, detectSystemLocale, type SupportedLocale, type LocaleSelection } from '$lib/i18n';
 import { getThemeById } from '$lib/styles/themes';
-import { type ImageHostConfig, type ImageHostTarget, DEFAULT_IMAGE_HOST_CONFIG, generateImageHostTargetId } from '$lib/services/image-hosting';
+import {
+  type ImageHostConfig,
+  type ImageHostTarget,
+  DEFAULT_IMAGE_HOST_CONFIG,
+  generateImageHostTargetId,
+  isObjectStorageProvider,
+} from '$lib/services/image-hosting';
 import type { ImageProviderConfig, SpeechProviderConfig } from '$lib/services/ai/types';
 import type { PublishTarget } from '$lib/services/publish/types';
 import type { VoiceProfile } from '$lib/services/voice/types';
@@ -15,6 +21,9 @@ const KEYCHAIN_IMAGE_PREFIX = 'image-key:';
 const KEYCHAIN_SPEECH_PREFIX = 'speech-key:';
 const KEYCHAIN_SPEECH_AWS_AK_PREFIX = 'speech-aws-ak:';
 const KEYCHAIN_SPEECH_AWS_SK_PREFIX = 'speech-aws-sk:';
+// Object-storage image hosts; resolved in Rust by target id, never restored into JS.
+export const KEYCHAIN_STORAGE_KEY_PREFIX = 'storage-key:';
+export const KEYCHAIN_STORAGE_SECRET_PREFIX = 'storage-secret:';
 
 export type Theme = 'light' | 'dark' | 'system';
 
@@ -35,7 +44,9 @@ export interface ExportSettings {
   enableHighlight: boolean;
   enableMermaid: boolean;
   enableMath: boolean;
+  inlineAssets: boolean;  // embed local images in HTML exports
   autoFallbackOnFailure: boolean;
+  wrapWidth: number;      // columns for plain-text export
 }
 
 export const DEFAULT_EXPORT_SETTINGS: ExportSettings = {
@@ -51,7 +62,9 @@ export const DEFAULT_EXPORT_SETTINGS: ExportSettings = {
   enableHighlight: true,
   enableMermaid: true,
   enableMath: true,
+  inlineAssets: true,
   autoFallbackOnFailure: true,
+  wrapWidth: 72,
 };
 
 interface Settings {
@@ -70,6 +83,8 @@ interface Settings {
   editorLineWidth: number;
   editorTabSize: number;
   showLineNumbers: boolean;
+  cleanInvisibleOnSave: boolean; // strip BOMs and zero-width characters when saving
+  formatOnSave: boolean;         // run format_markdown on markdown files before saving
   imageHostConfig: ImageHostConfig;
   imageHostTargets: ImageHostTarget[];
   defaultImageHostId: string;
@@ -77,6 +92,8 @@ interface Settings {
   activeImageConfigId: string | null;
   publishTargets: PublishTarget[];
   lastUpdateCheckDate: string | null;  // "YYYY-MM-DD" format
+  updateChannel: 'stable' | 'beta';
+  lastSeenCrashReport: string | null;  // newest crash report already shown
   rememberLastFolder: boolean;
   lastOpenedFolder: string | null;
   mcpAutoApprove: boolean;
@@ -91,6 +108,8 @@ interface Settings {
   showOutline: boolean;
   outlineWidth: number;
   aiPanelWidth: number | null;   // null = use default (33% of window)
+  aiPanelShortcut: string;       // accelerator, '' = Ctrl+Shift+I
+  aiPanelShortcutGlobal: boolean; // keep it working while other apps are focused
   rulesHistoryCount: number;
   // Knowledge base embedding settings
   embeddingProvider: string | null;       // null = follow AI chat provider
@@ -136,6 +155,8 @@ const DEFAULT_SETTINGS: Settings = {
   editorLineWidth: 800,
   editorTabSize: 4,
   showLineNumbers: false,
+  cleanInvisibleOnSave: false,
+  formatOnSave: false,
   imageHostConfig: { ...DEFAULT_IMAGE_HOST_CONFIG },
   imageHostTargets: [],
   defaultImageHostId: '',
@@ -143,6 +164,8 @@ const DEFAULT_SETTINGS: Settings = {
   activeImageConfigId: null,
   publishTargets: [],
   lastUpdateCheckDate: null,
+  updateChannel: 'stable',
+  lastSeenCrashReport: null,
   rememberLastFolder: true,
   lastOpenedFolder: null,
   mcpAutoApprove: false,
@@ -157,6 +180,8 @@ const DEFAULT_SETTINGS: Settings = {
   showOutline: false,
   outlineWidth: 200,
   aiPanelWidth: null,
+  aiPanelShortcut: '',
+  aiPanelShortcutGlobal: false,
   rulesHistoryCount: 10,
   embeddingProvider: null,
   embeddingConfigId: '',
@@ -239,6 +264,36 @@ function emitViewSync(partial: Record<string, unknown>) {
   emit(VIEW_SYNC_EVENT, { source: windowLabel, ...partial }).catch(() => {});
 }
 
+/** Targets whose credentials Rust resolves from the keychain by target id. */
+function hasStoredCredentials(t: ImageHostTarget): boolean {
+  return isObjectStorageProvider(t.provider) || t.provider === 'github';
+}
+
+/**
+ * Move an object-storage target's access/secret pair (a GitHub target's
+ * token) into the keychain and return a copy with them masked as '***'.
+ * Keeps plaintext if the keychain fails.
+ */
+async function storeStorageCredentials(t: ImageHostTarget): Promise<ImageHostTarget> {
+  const masked = { ...t };
+  const entries: [string, 'ossAccessKey' | 'ossSecretKey' | 'githubToken'][] =
+    t.provider === 'github'
+      ? [[KEYCHAIN_STORAGE_SECRET_PREFIX, 'githubToken']]
+      : [
+          [KEYCHAIN_STORAGE_KEY_PREFIX, 'ossAccessKey'],
+          [KEYCHAIN_STORAGE_SECRET_PREFIX, 'ossSecretKey'],
+        ];
+  for (const [prefix, field] of entries) {
+    if (t[field] && t[field] !== '***') {
+      try {
+        await invoke('keychain_set', { key: `${prefix}${t.id}`, value: t[field] });
+        masked[field] = '***';
+      } catch { /* fallback: keep plaintext */ }
+    }
+  }
+  return masked;
+}
+
 /** Debounced persist to avoid excessive disk writes */
 let persistTimer: ReturnType<typeof setTimeout> | null = null;
 function schedulePersist(state: Settings) {
@@ -290,6 +345,16 @@ function schedulePersist(state: Settings) {
         }
       }
 
+      // Sanitize object-storage credentials for disk storage
+      if (state.imageHostTargets?.length > 0) {
+        diskState.imageHostTargets = [];
+        for (const t of state.imageHostTargets) {
+          diskState.imageHostTargets.push(
+            hasStoredCredentials(t) ? await storeStorageCredentials(t) : { ...t },
+          );
+        }
+      }
+
       const store = await load(SETTINGS_STORE_FILE);
       await store.set('data', diskState);
       await store.save();
@@ -458,6 +523,19 @@ export async function initSettingsStore() {
         }
       }
 
+      // Migration: plaintext object-storage / GitHub credentials → keychain (masked in memory too)
+      const storageState = settingsStore.getState();
+      const plaintext = (v?: string) => !!v && v !== '***';
+      if (storageState.imageHostTargets?.some(t => t.provider === 'github'
+          ? plaintext(t.githubToken)
+          : isObjectStorageProvider(t.provider) && (plaintext(t.ossAccessKey) || plaintext(t.ossSecretKey)))) {
+        const migrated: ImageHostTarget[] = [];
+        for (const t of storageState.imageHostTargets) {
+          migrated.push(hasStoredCredentials(t) ? await storeStorageCredentials(t) : t);
+        }
+        settingsStore.update({ imageHostTargets: migrated });
+      }
+
       // v0.37.0 migration: auto-pick the sole Picora target as default Picora account
       const picoraState = settingsStore.getState();
       if (!picoraState.defaultPicoraAccountId) {
@@ -536,6 +614,12 @@ export async function initSettingsStore() {
       applyColorTheme(state);
       setLocale(resolveLocale(state.localeSelection));
       document.documentElement.style.setProperty('--font-size-base', `${state.fontSize}px`);
+      if ((state.aiPanelShortcut || state.aiPanelShortcutGlobal) && getCurrentWindow().label === 'main') {
+        invoke('set_ai_panel_shortcut', {
+          accel: state.aiPanelShortcut,
+          global: state.aiPanelShortcutGlobal,
+        }).catch((e) => console.warn('[Settings] AI panel shortcut:', e));
+      }
     }
   } catch { /* first launch — no saved data */ }
   settingsStore._setInitialized();
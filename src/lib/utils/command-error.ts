@@ -0,0 +1,47 @@
+import { get } from 'svelte/store';
+import { t } from '$lib/i18n';
+
+/**
+ * Error returned by Rust commands that use `CommandError`
+ * (src-tauri/src/commands/error.rs).
+ */
+export interface CommandError {
+  /** Stable identifier, e.g. `FILE_EXISTS` */
+  code: string;
+  /** English fallback */
+  message: string;
+  details?: Record<string, unknown>;
+}
+
+export function isCommandError(e: unknown): e is CommandError {
+  return (
+    typeof e === 'object' && e !== null &&
+    typeof (e as CommandError).code === 'string' &&
+    typeof (e as CommandError).message === 'string'
+  );
+}
+
+/** Error code of a rejected `invoke`, or null for plain string/Error rejections. */
+export function commandErrorCode(e: unknown): string | null {
+  return isCommandError(e) ? e.code : null;
+}
+
+/**
+ * User-facing text for any rejected `invoke`: the localized `errors.codes.<CODE>`
+ * string (filled from `details`) when there is one, else the backend message.
+ */
+export function commandErrorMessage(e: unknown): string {
+  if (isCommandError(e)) {
+    const key = `errors.codes.${e.code}`;
+    const params: Record<string, string> = {};
+    for (const [k, v] of Object.entries(e.details ?? {})) {
+      if (typeof v === 'string' || typeof v === 'number') params[k] = String(v);
+    }
+    const text = get(t)(key, params);
+    // Missing translation or a placeholder the details couldn't fill
+    if (text === key || /\{\w+\}/.test(text)) return e.message;
+    return text.trim();
+  }
+  if (e instanceof Error) return e.message;
+  return String(e);
+}